@@ -0,0 +1,170 @@
+// Copyright 2020 The Exonum Team
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Benchmarks of the anchoring transactions building and signing.
+//!
+//! Run them with `cargo bench --bench anchoring`.
+
+use bitcoin::network::constants::Network;
+use btc_transaction_utils::{
+    multisig::{RedeemScript, RedeemScriptBuilder},
+    p2wsh, TxInRef,
+};
+use criterion::{criterion_group, criterion_main, BatchSize, BenchmarkId, Criterion, Throughput};
+use exonum::{crypto::Hash, helpers::Height};
+use exonum_btc_anchoring::{
+    btc::{self, BtcAnchoringTransactionBuilder},
+    test_helpers::{create_fake_funding_transaction, AnchoringTestKit},
+};
+
+/// Numbers of the anchoring keys in the redeem script.
+const KEYS_COUNTS: &[usize] = &[4, 10, 20];
+/// Numbers of the inputs of the anchoring transaction.
+const INPUTS_COUNTS: &[usize] = &[1, 10, 50, 100];
+/// Value of each funding transaction.
+const FUNDING_VALUE: u64 = 100_000;
+
+fn gen_public_keys(count: usize) -> Vec<bitcoin::PublicKey> {
+    (0..count)
+        .map(|_| btc::gen_keypair(Network::Testnet).0.into())
+        .collect()
+}
+
+fn redeem_script(keys: &[bitcoin::PublicKey]) -> RedeemScript {
+    RedeemScriptBuilder::with_public_keys(keys.iter().copied())
+        .to_script()
+        .unwrap()
+}
+
+/// Creates the builder of the anchoring transaction, which spends the given number
+/// of the funding transactions.
+fn proposal_builder(
+    redeem_script: &RedeemScript,
+    funding_txs: &[btc::Transaction],
+) -> BtcAnchoringTransactionBuilder {
+    let mut builder = BtcAnchoringTransactionBuilder::new(redeem_script);
+    for tx in funding_txs {
+        builder.additional_funds(tx.clone()).unwrap();
+    }
+    builder.fee(10);
+    builder.payload(Height(1_000), Hash::zero());
+    builder
+}
+
+fn create_funding_txs(redeem_script: &RedeemScript, count: usize) -> Vec<btc::Transaction> {
+    let address = p2wsh::address(redeem_script, Network::Testnet).into();
+    (0..count)
+        .map(|_| create_fake_funding_transaction(&address, FUNDING_VALUE))
+        .collect()
+}
+
+fn bench_redeem_script(c: &mut Criterion) {
+    let mut group = c.benchmark_group("redeem_script");
+    for &count in KEYS_COUNTS {
+        let keys = gen_public_keys(count);
+        group.bench_with_input(BenchmarkId::from_parameter(count), &keys, |b, keys| {
+            b.iter(|| redeem_script(keys))
+        });
+    }
+    group.finish();
+}
+
+fn bench_proposal(c: &mut Criterion) {
+    let redeem_script = redeem_script(&gen_public_keys(4));
+    let mut group = c.benchmark_group("proposal");
+    for &count in INPUTS_COUNTS {
+        let funding_txs = create_funding_txs(&redeem_script, count);
+        group.throughput(Throughput::Elements(count as u64));
+        group.bench_with_input(
+            BenchmarkId::from_parameter(count),
+            &funding_txs,
+            |b, funding_txs| {
+                b.iter_batched(
+                    || proposal_builder(&redeem_script, funding_txs),
+                    |builder| builder.create().unwrap(),
+                    BatchSize::SmallInput,
+                )
+            },
+        );
+    }
+    group.finish();
+}
+
+fn bench_sighash(c: &mut Criterion) {
+    let redeem_script = redeem_script(&gen_public_keys(4));
+    let mut group = c.benchmark_group("sighash");
+    for &count in INPUTS_COUNTS {
+        let funding_txs = create_funding_txs(&redeem_script, count);
+        let proposal = proposal_builder(&redeem_script, &funding_txs)
+            .create()
+            .unwrap();
+        let mut signer = p2wsh::InputSigner::new(redeem_script.clone());
+        // Sighashes of all inputs are computed, as the anchoring node does to sign the proposal.
+        group.throughput(Throughput::Elements(count as u64));
+        group.bench_with_input(
+            BenchmarkId::from_parameter(count),
+            &proposal,
+            |b, (proposal, inputs)| {
+                b.iter(|| {
+                    for (index, input) in inputs.iter().enumerate() {
+                        signer
+                            .signature_hash(TxInRef::new(proposal.as_ref(), index), input.as_ref());
+                    }
+                })
+            },
+        );
+    }
+    group.finish();
+}
+
+fn bench_sign_input(c: &mut Criterion) {
+    let mut group = c.benchmark_group("sign_input");
+    group.sample_size(10);
+    // The proposal of the testkit spends the single funding transaction, so the execution
+    // is measured for the different numbers of the anchoring nodes instead.
+    for &count in &KEYS_COUNTS[..2] {
+        let mut testkit = AnchoringTestKit::new(count as u16, 5);
+        let (funding_txs, _) = testkit.create_funding_confirmation_txs(FUNDING_VALUE);
+        testkit.inner.create_block_with_transactions(funding_txs);
+        // The signatures of a single node don't reach the quorum, so the proposal
+        // stays the same after the block.
+        let signatures = testkit.create_signature_txs().swap_remove(0);
+
+        group.bench_with_input(
+            BenchmarkId::from_parameter(count),
+            &signatures,
+            |b, signatures| {
+                b.iter_batched(
+                    || signatures.clone(),
+                    |signatures| {
+                        testkit.inner.checkpoint();
+                        testkit.inner.create_block_with_transactions(signatures);
+                        testkit.inner.rollback();
+                    },
+                    BatchSize::SmallInput,
+                )
+            },
+        );
+    }
+    group.finish();
+}
+
+criterion_group!(
+    benches,
+    bench_redeem_script,
+    bench_proposal,
+    bench_sighash,
+    bench_sign_input
+);
+criterion_main!(benches);
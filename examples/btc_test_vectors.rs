@@ -0,0 +1,59 @@
+// Copyright 2019 The Exonum Team
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+use structopt::StructOpt;
+
+use std::{fs::File, path::PathBuf};
+
+use exonum_btc_anchoring::test_helpers::vectors::{generate_test_vectors, verify_test_vector};
+
+/// BTC anchoring test vectors generator
+///
+/// Generates the canonical test vectors of the anchoring protocol as JSON fixtures.
+#[derive(StructOpt)]
+enum Opts {
+    /// Write the generated test vectors to the file or to the standard output.
+    Generate {
+        /// Path to the output file.
+        #[structopt(long, short = "o")]
+        output: Option<PathBuf>,
+    },
+    /// Check that the test vectors from the given file match this implementation.
+    Verify {
+        /// Path to the file with the test vectors.
+        input: PathBuf,
+    },
+}
+
+fn main() -> anyhow::Result<()> {
+    match Opts::from_args() {
+        Opts::Generate { output } => {
+            let vectors = generate_test_vectors();
+            match output {
+                Some(path) => serde_json::to_writer_pretty(File::create(path)?, &vectors)?,
+                None => println!("{}", serde_json::to_string_pretty(&vectors)?),
+            }
+        }
+        Opts::Verify { input } => {
+            let vectors: Vec<_> = serde_json::from_reader(File::open(input)?)?;
+            for vector in &vectors {
+                verify_test_vector(vector)?;
+            }
+            if vectors != generate_test_vectors() {
+                anyhow::bail!("Test vectors differ from the generated ones");
+            }
+            println!("{} test vectors are valid", vectors.len());
+        }
+    }
+    Ok(())
+}
@@ -0,0 +1,32 @@
+// Copyright 2020 The Exonum Team
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Parsing of the anchoring configuration from the arbitrary bytes.
+
+#![no_main]
+
+use exonum_btc_anchoring::config::Config;
+use exonum_merkledb::BinaryValue;
+use libfuzzer_sys::fuzz_target;
+
+use std::borrow::Cow;
+
+fuzz_target!(|data: &[u8]| {
+    if let Ok(config) = Config::from_bytes(Cow::Borrowed(data)) {
+        if config.validate().is_ok() {
+            let _ = config.anchoring_address();
+            let _ = config.anchoring_out_script();
+        }
+    }
+});
@@ -0,0 +1,37 @@
+// Copyright 2020 The Exonum Team
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Parsing of the anchoring payload from the arbitrary `OP_RETURN` scripts.
+
+#![no_main]
+
+use bitcoin::blockdata::script::Script;
+use exonum_btc_anchoring::btc::{Payload, PayloadCodec, StrictPayloadCodec};
+use libfuzzer_sys::fuzz_target;
+
+fuzz_target!(|data: &[u8]| {
+    // Payload v.2 may be split across the several outputs, so the data is split too.
+    let scripts = data
+        .split(|&byte| byte == 0xff)
+        .map(|chunk| Script::from(chunk.to_vec()))
+        .collect::<Vec<_>>();
+    let scripts = scripts.iter().collect::<Vec<_>>();
+
+    let payload = Payload::from_scripts(scripts.iter().copied());
+    let strict_payload = StrictPayloadCodec.decode(&scripts);
+    // Any payload accepted by the strict codec is accepted by the default one.
+    if strict_payload.is_some() {
+        assert_eq!(payload, strict_payload);
+    }
+});
@@ -0,0 +1,33 @@
+// Copyright 2020 The Exonum Team
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Parsing of the anchoring transactions from the arbitrary bytes.
+
+#![no_main]
+
+use exonum_btc_anchoring::btc::Transaction;
+use exonum_merkledb::BinaryValue;
+use libfuzzer_sys::fuzz_target;
+
+use std::borrow::Cow;
+
+fuzz_target!(|data: &[u8]| {
+    if let Ok(tx) = Transaction::from_bytes(Cow::Borrowed(data)) {
+        let _ = tx.anchoring_payload();
+        let _ = tx.id();
+        // Parsed transaction has the canonical encoding.
+        let bytes = tx.clone().into_bytes();
+        assert_eq!(Transaction::from_bytes(Cow::Owned(bytes)).unwrap(), tx);
+    }
+});
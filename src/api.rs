@@ -18,28 +18,44 @@
 //! unauthorized use, and private part intended to be used by [sync][sync] module.
 //! Private part is implementation detail and should not be used directly.
 //!
+//! Bitcoin transaction identifiers and block hashes are represented as hex strings in
+//! the same reversed byte order as in the Bitcoin RPC, see [`btc::Sha256d`] for details.
+//!
+//! [`btc::Sha256d`]: ../btc/struct.Sha256d.html
+//!
 //! [sync]: ../sync/index.html
 
-use anyhow::{anyhow, ensure};
+use anyhow::{anyhow, bail, ensure};
 use async_trait::async_trait;
 use btc_transaction_utils::{p2wsh, TxInRef};
-use exonum::{blockchain::IndexProof, crypto::Hash, helpers::Height};
-use exonum_merkledb::ListProof;
+use exonum::{
+    blockchain::{Block, BlockProof, IndexProof},
+    crypto::{self, Hash, KeyPair, PublicKey, Signature},
+    helpers::{Height, ValidateInput},
+    messages::{AnyTx, Verified},
+};
+use exonum_merkledb::{ListProof, ObjectHash};
 use exonum_rust_runtime::{
-    api::{self, ServiceApiBuilder, ServiceApiState},
+    api::{self, HttpStatusCode, ServiceApiBuilder, ServiceApiState},
     Broadcaster,
 };
+use exonum_supervisor::ConfigPropose;
+use serde::{de::DeserializeOwned, Serialize as SerializeTrait};
 use serde_derive::{Deserialize, Serialize};
 
-use std::cmp::{
-    self,
-    Ordering::{self, Equal, Greater, Less},
-};
+use std::{future::Future, marker::PhantomData, time::Duration};
 
 use crate::{
-    blockchain::{AddFunds, BtcAnchoringInterface, Schema, SignInput},
+    blockchain::{
+        backup::{AnchoringArchive, AnchoringBackup},
+        data_layout::{
+            AnchoringDelay, AnchoringDelayReason, ConfigChange, TransitionState, TxInputId,
+        },
+        AccelerateAnchoring, AddFunds, BtcAnchoringInterface, RecoverAnchoring,
+        ReportInclusionProof, Schema, SignInput, SignInputs, VoteFeeRate,
+    },
     btc,
-    config::Config,
+    config::{AnchoringKeys, Config},
 };
 
 /// A proof of existence for an anchoring transaction at the given height.
@@ -51,11 +67,207 @@ pub struct TransactionProof {
     pub transaction_proof: ListProof<btc::Transaction>,
 }
 
+/// Proof that the Exonum block is covered by the anchoring transaction.
+///
+/// The anchoring transaction anchors the last block of `anchored_blocks`, and each of
+/// these blocks references the previous one by its `prev_hash`, so the block with
+/// the requested hash, which is the first one, is anchored too.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct BlockAnchorProof {
+    /// Height of the Exonum block with the requested hash.
+    pub block_height: Height,
+    /// Index of the earliest anchoring transaction covering the block.
+    pub index: u64,
+    /// Proof of existence for the anchoring transaction.
+    pub transaction_proof: TransactionProof,
+    /// Exonum blocks from the requested one to the anchored one inclusive.
+    pub anchored_blocks: Vec<Block>,
+}
+
+/// Complete chain of evidence that the Exonum transaction is anchored to the Bitcoin
+/// blockchain.
+///
+/// The evidence is verified as follows:
+///
+/// - `block_proof` is authenticated by the precommits of the validators;
+/// - `transaction_proof` links the transaction hash to the `tx_hash` of the block;
+/// - `anchor_proof` links the block to the anchoring transaction, which existence
+///   is authenticated by the validators too;
+/// - `inclusion_proof`, if any, links the anchoring transaction to the Bitcoin block.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ExonumTransactionProof {
+    /// Exonum transaction.
+    pub transaction: Verified<AnyTx>,
+    /// Exonum block containing the transaction along with the precommits of validators.
+    pub block_proof: BlockProof,
+    /// Proof of the transaction hash in the list of the block transactions.
+    pub transaction_proof: ListProof<Hash>,
+    /// Proof that the block is covered by the anchoring transaction.
+    pub anchor_proof: BlockAnchorProof,
+    /// Proof of inclusion of the anchoring transaction in the Bitcoin block, if it
+    /// has been reported by the anchoring nodes.
+    pub inclusion_proof: Option<TransactionInclusionProof>,
+}
+
+/// Proof that the Exonum block header at the given height is anchored to the Bitcoin
+/// blockchain, which combines the proofs otherwise obtained from several endpoints.
+///
+/// The proof is verified in the same way as [`ExonumTransactionProof`], except that
+/// the header of the block is the subject of the proof itself.
+///
+/// [`ExonumTransactionProof`]: struct.ExonumTransactionProof.html
+#[derive(Debug, Serialize, Deserialize)]
+pub struct BlockHeaderProof {
+    /// Exonum block at the requested height along with the precommits of validators.
+    pub block_proof: BlockProof,
+    /// Proof that the block is covered by the anchoring transaction.
+    pub anchor_proof: BlockAnchorProof,
+    /// Proof of inclusion of the anchoring transaction in the Bitcoin block, if it
+    /// has been reported by the anchoring nodes.
+    pub inclusion_proof: Option<TransactionInclusionProof>,
+}
+
+/// Self-contained proof that the Exonum block at the given height is anchored to
+/// the Bitcoin blockchain, which can be stored in a file and verified offline by
+/// third parties.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct AnchoringProofBundle {
+    /// Version of the bundle format.
+    pub version: u32,
+    /// Name of the anchoring service instance.
+    pub instance_name: String,
+    /// Height of the anchored Exonum block.
+    pub height: Height,
+    /// Consensus keys of the validators reported by the node at the time of the export.
+    /// The verifier should compare them with the keys obtained from a trusted source.
+    pub validator_keys: Vec<PublicKey>,
+    /// Anchoring transaction which covers the block.
+    pub anchoring_transaction: btc::Transaction,
+    /// Proof that the block is anchored by the anchoring transaction.
+    pub proof: BlockHeaderProof,
+}
+
+impl AnchoringProofBundle {
+    /// Current version of the bundle format.
+    pub const VERSION: u32 = 1;
+
+    /// Creates a bundle with the given proof of the block anchoring.
+    pub fn new(
+        instance_name: impl Into<String>,
+        validator_keys: Vec<PublicKey>,
+        proof: BlockHeaderProof,
+    ) -> anyhow::Result<Self> {
+        let anchoring_transaction = proof
+            .anchor_proof
+            .transaction_proof
+            .transaction_proof
+            .entries_unchecked()
+            .first()
+            .map(|(_, tx)| tx.clone())
+            .ok_or_else(|| anyhow!("Proof doesn't contain the anchoring transaction"))?;
+        Ok(Self {
+            version: Self::VERSION,
+            instance_name: instance_name.into(),
+            height: proof.block_proof.block.height,
+            validator_keys,
+            anchoring_transaction,
+            proof,
+        })
+    }
+
+    /// Verifies the bundle against the given consensus keys of the validators.
+    ///
+    /// The Exonum block and the anchoring transactions chain must be authenticated
+    /// by the validators, the anchored blocks must reference each other by their hashes,
+    /// and the anchoring transaction must commit the hash of the last anchored block.
+    /// The proof of inclusion in the Bitcoin block, if any, must contain the anchoring
+    /// transaction.
+    pub fn verify(&self, validator_keys: &[PublicKey]) -> anyhow::Result<()> {
+        ensure!(
+            self.version == Self::VERSION,
+            "Unsupported proof bundle version {}",
+            self.version
+        );
+
+        let block = &self.proof.block_proof.block;
+        self.proof.block_proof.verify(validator_keys)?;
+        ensure!(
+            block.height == self.height,
+            "Proof is given for the block at height {} instead of {}",
+            block.height,
+            self.height
+        );
+
+        let anchor_proof = &self.proof.anchor_proof;
+        let first_block = anchor_proof
+            .anchored_blocks
+            .first()
+            .ok_or_else(|| anyhow!("Proof doesn't contain the anchored blocks"))?;
+        ensure!(
+            first_block.object_hash() == block.object_hash(),
+            "Anchored blocks don't start with the proven block"
+        );
+        for blocks in anchor_proof.anchored_blocks.windows(2) {
+            ensure!(
+                blocks[1].prev_hash == blocks[0].object_hash(),
+                "Anchored block at height {} doesn't reference the previous one",
+                blocks[1].height
+            );
+        }
+
+        let transaction_proof = &anchor_proof.transaction_proof;
+        let (index_name, index_hash) = transaction_proof.index_proof.verify(validator_keys)?;
+        ensure!(
+            index_name == format!("{}.transactions_chain", self.instance_name),
+            "Proof is given for the unexpected index {}",
+            index_name
+        );
+        let checked_proof = transaction_proof
+            .transaction_proof
+            .check_against_hash(index_hash)?;
+        match checked_proof.entries() {
+            [(index, tx)] if *index == anchor_proof.index => ensure!(
+                tx == &self.anchoring_transaction,
+                "Anchoring transaction doesn't match the proven one"
+            ),
+            _ => bail!(
+                "Proof doesn't contain the anchoring transaction with index {}",
+                anchor_proof.index
+            ),
+        }
+
+        let payload = self
+            .anchoring_transaction
+            .anchoring_payload()
+            .ok_or_else(|| anyhow!("Anchoring transaction has no payload"))?;
+        let anchored_block = anchor_proof.anchored_blocks.last().unwrap();
+        ensure!(
+            payload.block_height == anchored_block.height
+                && payload.block_hash == anchored_block.object_hash(),
+            "Anchoring transaction doesn't commit the last anchored block"
+        );
+
+        if let Some(inclusion_proof) = &self.proof.inclusion_proof {
+            let matched = inclusion_proof
+                .merkle_block
+                .matched_transactions()
+                .unwrap_or_default();
+            ensure!(
+                matched.contains(&self.anchoring_transaction.id()),
+                "Bitcoin Merkle block doesn't contain the anchoring transaction"
+            );
+        }
+        Ok(())
+    }
+}
+
 /// State of the next anchoring transaction proposal.
 #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub enum AnchoringProposalState {
     /// There is no anchoring transaction proposal at the time.
     None,
+    /// Anchoring is frozen by the emergency freeze flag in the anchoring configuration.
+    Frozen,
     /// There is a non-finalized anchoring transaction.
     Available {
         /// Proposal content.
@@ -90,7 +302,7 @@ impl AnchoringProposalState {
                 Ok(AnchoringProposalState::InsufficientFunds { total_fee, balance })
             }
             Some(Err(btc::BuilderError::NoInputs)) => Ok(AnchoringProposalState::NoInitialFunds),
-            Some(Err(e)) => Err(api::Error::internal(e)),
+            Some(Err(e)) => Err(ApiErrorKind::Internal.with_cause(e)),
         }
     }
 }
@@ -108,6 +320,425 @@ impl From<u64> for AnchoringChainLength {
     }
 }
 
+/// Slice of the anchoring transactions chain.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct AnchoringTransactionsPage {
+    /// Total length of the anchoring transactions chain.
+    pub total: u64,
+    /// Anchoring transactions starting from the requested index.
+    pub transactions: Vec<btc::Transaction>,
+}
+
+/// Signing progress of the single input of the anchoring transaction proposal.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct InputSigningProgress {
+    /// Index of the input.
+    pub input: u32,
+    /// Anchoring nodes that have already signed this input.
+    pub signed: Vec<AnchoringKeys>,
+    /// Anchoring nodes that have not signed this input yet.
+    pub missing: Vec<AnchoringKeys>,
+    /// Number of signatures required to reach the quorum for this input.
+    pub signatures_left: usize,
+}
+
+/// State of the signing session for the actual anchoring transaction proposal.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SigningSession {
+    /// Identifier of the anchoring transaction proposal.
+    pub txid: btc::Sha256d,
+    /// Number of signatures required to finalize each input.
+    pub quorum: usize,
+    /// Height at which the proposal expires and its signatures are discarded,
+    /// if the proposal lifetime is limited.
+    pub expires_at: Option<Height>,
+    /// Signing progress for each input of the proposal.
+    pub inputs: Vec<InputSigningProgress>,
+    /// Total number of signatures required to finalize the proposal.
+    pub signatures_left: usize,
+}
+
+/// Stage of the transition of the anchoring chain to the following address.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum TransitionStage {
+    /// Transition transaction proposal cannot be built at the time, e.g. due to
+    /// insufficient funds.
+    Pending,
+    /// Transition transaction proposal is being signed by the anchoring nodes.
+    Signing,
+    /// Transition transaction is signed and is waiting for the inclusion in the Bitcoin
+    /// blockchain. The transaction is broadcast by the sync utility, so the moment of
+    /// broadcasting is not observable by the service.
+    Signed,
+    /// Inclusion of the transition transaction in the Bitcoin block has been reported.
+    Included,
+    /// Transition transaction has reached the finality depth.
+    Confirmed,
+}
+
+/// Progress of the transition of the anchoring chain to the following address.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct TransitionProgress {
+    /// Configuration with the following anchoring address.
+    pub following_config: Config,
+    /// Stage of the transition.
+    pub stage: TransitionStage,
+    /// Identifier of the transition transaction if it has been proposed.
+    pub txid: Option<btc::Sha256d>,
+    /// Index of the transition transaction in the anchoring chain if it has been signed.
+    pub index: Option<u64>,
+    /// Signing session of the transition transaction proposal if it is being signed.
+    pub signing_session: Option<SigningSession>,
+    /// Funds on the previous anchoring address which have not been transferred yet,
+    /// in satoshis.
+    pub remaining_funds: u64,
+}
+
+/// Participation of the anchoring node in signing the actual anchoring transaction proposal.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct NodeParticipation {
+    /// Keys of the anchoring node.
+    pub anchoring_keys: AnchoringKeys,
+    /// Number of the proposal inputs signed by this node.
+    pub signed_inputs: usize,
+    /// Whether this node has signed all inputs of the proposal.
+    pub signed: bool,
+    /// Height of the block with the latest input signature of this node.
+    ///
+    /// Exonum heights are used as timestamps, since there is no notion of time in the service.
+    pub latest_signature_height: Option<Height>,
+}
+
+/// Statistics of the anchoring node participation in signing of the finalized proposals.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct NodeSigningStats {
+    /// Keys of the anchoring node.
+    pub anchoring_keys: AnchoringKeys,
+    /// Number of the latest finalized proposals in the rolling window.
+    pub window_proposals: u64,
+    /// Number of proposals in the rolling window signed by this node before finalization.
+    pub window_signed_proposals: u64,
+    /// Total number of finalized proposals.
+    pub total_proposals: u64,
+    /// Total number of finalized proposals signed by this node.
+    pub signed_proposals: u64,
+}
+
+/// Aggregate statistics of the anchoring process.
+///
+/// Latency is measured in Exonum blocks between the finalization of the anchoring transaction
+/// in the Exonum blockchain and the attestation of its inclusion in a Bitcoin block by
+/// a byzantine majority of the anchoring nodes, i.e. the moment it becomes final.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct AnchoringStatistics {
+    /// Total number of anchoring transactions.
+    pub transactions_count: u64,
+    /// Total fee paid by anchoring transactions in satoshis.
+    pub total_fee: u64,
+    /// Average fee of an anchoring transaction in satoshis.
+    pub average_fee: u64,
+    /// Average size of an anchoring transaction in bytes.
+    pub average_size: u64,
+    /// Average latency of anchoring in blocks among the final anchoring transactions.
+    pub average_latency: u64,
+    /// Number of blocks committed after the latest anchored block, if any.
+    pub current_lag: Option<u64>,
+    /// Index of the latest final anchoring transaction, if any.
+    pub latest_final_transaction: Option<u64>,
+    /// Balance of the anchoring wallet in satoshis.
+    pub balance: u64,
+}
+
+/// Aggregated status of the anchoring process.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct AnchoringStatus {
+    /// Actual anchoring configuration.
+    pub config: Config,
+    /// Height of the latest anchored Exonum block, if any.
+    pub latest_anchored_height: Option<Height>,
+    /// Identifier of the latest anchoring transaction, if any.
+    pub latest_txid: Option<btc::Sha256d>,
+    /// Height of the Exonum block which should be anchored next.
+    pub following_anchoring_height: Height,
+    /// Whether the transition to the following anchoring address is in progress.
+    pub transition_in_progress: bool,
+    /// Funds available for the anchoring in satoshis.
+    pub remaining_funds: u64,
+}
+
+/// Anchoring address with the range of anchoring transactions that send funds to it.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct AnchoringAddressRange {
+    /// Anchoring address.
+    pub address: btc::Address,
+    /// Index of the first anchoring transaction sending funds to this address.
+    pub first_transaction: u64,
+    /// Index of the last anchoring transaction sending funds to this address.
+    pub last_transaction: u64,
+}
+
+/// Identifiers of the anchoring transaction.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct AnchoringTransactionIds {
+    /// Index of the transaction in the anchoring chain.
+    pub index: u64,
+    /// Bitcoin transaction identifier.
+    pub txid: btc::Sha256d,
+    /// Bitcoin witness transaction identifier.
+    pub wtxid: btc::Sha256d,
+}
+
+/// Index of the anchoring transaction which anchors the given Exonum height.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct AnchoredHeightIndex {
+    /// Index of the transaction in the anchoring chain.
+    pub index: u64,
+    /// Exonum height anchored by the transaction.
+    pub anchored_height: Height,
+}
+
+/// Cost of the single anchoring transaction.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct AnchoringCost {
+    /// Index of the transaction in the anchoring chain.
+    pub index: u64,
+    /// Bitcoin transaction identifier.
+    pub txid: btc::Sha256d,
+    /// Height of the Exonum block anchored by the transaction.
+    pub anchored_height: Height,
+    /// Fee paid by the transaction in satoshis.
+    pub fee: u64,
+    /// Size of the transaction in bytes.
+    pub size: u64,
+    /// Time of the Bitcoin block containing the transaction in seconds since the Unix
+    /// epoch, if the inclusion of the transaction has been reported.
+    pub block_time: Option<u32>,
+}
+
+/// Anchoring transactions matching the cost report query with their total cost.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct AnchoringCostReport {
+    /// Anchoring transactions in the order of the anchoring chain.
+    pub transactions: Vec<AnchoringCost>,
+    /// Total fee paid by the transactions in satoshis.
+    pub total_fee: u64,
+}
+
+/// Anchoring heights skipped before the anchoring transaction has been finalized.
+///
+/// The skipped heights are not left unanchored: the anchoring chain is always continued
+/// from the latest anchored height, so the skipped blocks are covered by the
+/// following anchoring transactions.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct AnchoringGap {
+    /// Height of the Exonum block anchored by the transaction.
+    pub anchored_height: Height,
+    /// Height of the Exonum block in which the transaction has been finalized.
+    pub finalized_at: Height,
+    /// Anchoring heights which have been passed before the transaction finalization.
+    pub skipped_heights: Vec<Height>,
+    /// Prevailing reason of the delay.
+    pub reason: AnchoringDelayReason,
+    /// Numbers of blocks accounted for each reason of the delay.
+    pub delay: AnchoringDelay,
+}
+
+/// Proof of inclusion of the anchoring transaction in the Bitcoin block.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct TransactionInclusionProof {
+    /// Height of the Bitcoin block.
+    pub block_height: u64,
+    /// Bitcoin Merkle block which proves the inclusion of the anchoring transaction.
+    pub merkle_block: btc::MerkleBlock,
+    /// Number of Bitcoin blocks in the stored headers chain confirming the anchoring
+    /// transaction, including the block with the transaction itself.
+    pub confirmations: u64,
+    /// Whether the inclusion of the anchoring transaction at sufficient depth has been
+    /// attested by a byzantine majority of the anchoring nodes.
+    pub is_final: bool,
+}
+
+/// Proof of inclusion of the registered funding transaction in the Bitcoin block.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct FundingInclusionProof {
+    /// Funding transaction.
+    pub transaction: btc::Transaction,
+    /// Height of the Bitcoin block.
+    pub block_height: u64,
+    /// Bitcoin Merkle block which proves the inclusion of the funding transaction.
+    pub merkle_block: btc::MerkleBlock,
+    /// Number of Bitcoin blocks in the stored headers chain confirming the funding
+    /// transaction, including the block with the transaction itself.
+    pub confirmations: u64,
+    /// Whether the funding transaction has been already spent by the anchoring chain.
+    pub is_spent: bool,
+}
+
+/// Funding transaction registered by the anchoring service.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct FundingTransactionInfo {
+    /// Bitcoin transaction identifier.
+    pub txid: btc::Sha256d,
+    /// Amount in satoshis sent by the transaction to the anchoring addresses.
+    pub amount: u64,
+    /// Whether the funding transaction has been already spent by the anchoring chain.
+    pub is_spent: bool,
+    /// Index of the anchoring transaction which has spent the funding transaction.
+    ///
+    /// May be `None` for the spent transactions, if they have been spent before
+    /// the spending anchoring transactions were recorded.
+    pub spent_by: Option<u64>,
+}
+
+/// Signature of the anchoring transaction input made by the anchoring node.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct NodeInputSignature {
+    /// Identifier of the anchoring node, i.e. its position in the anchoring keys
+    /// of the configuration which has been actual during the signing.
+    pub anchoring_node: u16,
+    /// Input signature.
+    pub input_signature: btc::InputSignature,
+}
+
+/// History of the funding transactions of the anchoring wallet.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct FundingHistory {
+    /// Registered funding transactions, the unspent one goes last.
+    pub transactions: Vec<FundingTransactionInfo>,
+    /// Total amount in satoshis received by the anchoring wallet from
+    /// the funding transactions.
+    pub total_received: u64,
+    /// Total amount in satoshis of the spent funding transactions.
+    pub total_spent: u64,
+}
+
+/// API response signed by the node, which can be relayed through untrusted proxies.
+///
+/// The payload is kept as a JSON string, so the signed bytes are not affected by
+/// the possible re-serialization of the response.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(bound = "")]
+pub struct SignedResponse<T> {
+    /// JSON-encoded response payload.
+    pub payload: String,
+    /// Public key of the node which has signed the response.
+    pub public_key: PublicKey,
+    /// Signature of the payload bytes.
+    pub signature: Signature,
+    #[serde(skip)]
+    _marker: PhantomData<T>,
+}
+
+impl<T: SerializeTrait> SignedResponse<T> {
+    /// Signs the given response payload with the given key pair.
+    pub fn new(payload: &T, keypair: &KeyPair) -> Self {
+        let payload = serde_json::to_string(payload).expect("Unable to serialize response");
+        Self {
+            signature: crypto::sign(payload.as_bytes(), keypair.secret_key()),
+            public_key: keypair.public_key(),
+            payload,
+            _marker: PhantomData,
+        }
+    }
+}
+
+impl<T: DeserializeOwned> SignedResponse<T> {
+    /// Verifies that the response is signed by the given public key and returns
+    /// its payload.
+    pub fn verify(&self, public_key: &PublicKey) -> anyhow::Result<T> {
+        ensure!(
+            &self.public_key == public_key,
+            "Response is signed by the unexpected key {}",
+            self.public_key.to_hex()
+        );
+        ensure!(
+            crypto::verify(&self.signature, self.payload.as_bytes(), public_key),
+            "Response signature is invalid"
+        );
+        serde_json::from_str(&self.payload).map_err(From::from)
+    }
+}
+
+/// Kind of the error returned by the anchoring API.
+///
+/// The numeric code of the kind is passed in the `error_code` field of the error body,
+/// so the API clients can decide whether the failed request should be retried.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[repr(u8)]
+pub enum ApiErrorKind {
+    /// Request is malformed or does not pass the verification.
+    BadRequest = 1,
+    /// Request cannot be processed by this node, e.g. it is not a validator.
+    NotValidator = 2,
+    /// Request has not been processed within the configured timeout.
+    Timeout = 3,
+    /// Request payload exceeds the configured limit.
+    PayloadTooLarge = 4,
+    /// Transaction created by the request has not been broadcast.
+    Broadcast = 5,
+    /// Unexpected internal error of the service.
+    Internal = 6,
+}
+
+impl ApiErrorKind {
+    /// Returns the numeric code of this error kind.
+    pub fn code(self) -> u8 {
+        self as u8
+    }
+
+    /// Returns the error kind with the given numeric code, if any.
+    pub fn from_code(code: u8) -> Option<Self> {
+        Some(match code {
+            1 => ApiErrorKind::BadRequest,
+            2 => ApiErrorKind::NotValidator,
+            3 => ApiErrorKind::Timeout,
+            4 => ApiErrorKind::PayloadTooLarge,
+            5 => ApiErrorKind::Broadcast,
+            6 => ApiErrorKind::Internal,
+            _ => return None,
+        })
+    }
+
+    /// Returns the HTTP status code of the errors of this kind.
+    pub fn http_code(self) -> HttpStatusCode {
+        match self {
+            ApiErrorKind::BadRequest | ApiErrorKind::NotValidator => HttpStatusCode::BAD_REQUEST,
+            ApiErrorKind::Timeout => HttpStatusCode::SERVICE_UNAVAILABLE,
+            ApiErrorKind::PayloadTooLarge => HttpStatusCode::PAYLOAD_TOO_LARGE,
+            ApiErrorKind::Broadcast | ApiErrorKind::Internal => {
+                HttpStatusCode::INTERNAL_SERVER_ERROR
+            }
+        }
+    }
+
+    /// Returns `true` if the request failed with this error kind may succeed
+    /// if retried later without changes.
+    pub fn is_transient(self) -> bool {
+        match self {
+            ApiErrorKind::Timeout | ApiErrorKind::Broadcast => true,
+            ApiErrorKind::BadRequest
+            | ApiErrorKind::NotValidator
+            | ApiErrorKind::PayloadTooLarge
+            | ApiErrorKind::Internal => false,
+        }
+    }
+
+    /// Returns the kind of the given API error, if it has been returned by the anchoring API.
+    pub fn of(error: &api::Error) -> Option<Self> {
+        error.body.error_code.and_then(Self::from_code)
+    }
+
+    /// Creates an API error of this kind.
+    pub fn error(self) -> api::Error {
+        api::Error::new(self.http_code()).error_code(self.code())
+    }
+
+    /// Creates an API error of this kind caused by the given error.
+    pub fn with_cause(self, cause: impl std::fmt::Display) -> api::Error {
+        self.error().detail(cause.to_string())
+    }
+}
+
 /// Public API client for the Exonum Bitcoin anchoring service.
 #[async_trait]
 pub trait PublicApi {
@@ -163,233 +794,1393 @@ pub trait PublicApi {
     ///
     /// [`config`]: ../config/struct.Config.html
     async fn config(&self) -> Result<Config, Self::Error>;
-}
-
-/// Private API client for the Exonum Bitcoin anchoring service.
-#[async_trait]
-pub trait PrivateApi {
-    /// Error type for the current API client implementation.
-    type Error;
-    /// Creates and broadcasts the `TxSignature` transaction, which is signed
-    /// by the current node, and returns its hash.
+    /// Returns every anchoring configuration ever used by the service along with
+    /// the heights from which they have been actual, in the chronological order.
+    ///
+    /// The history allows to verify the old anchoring transactions signed by
+    /// the previous sets of the anchoring nodes.
     ///
     /// | Property    | Value |
     /// |-------------|-------|
-    /// | Path        | `/api/services/{btc_anchoring}/sign-input` |
-    /// | Method      | POST   |
-    /// | Query type  | [`SignInput`] |
-    /// | Return type | [`Hash`] |
+    /// | Path        | `/api/services/{btc_anchoring}/config-history` |
+    /// | Method      | GET   |
+    /// | Query type  | - |
+    /// | Return type | [`Vec<ConfigChange>`] |
     ///
-    /// [`SignInput`]: ../blockchain/struct.SignInput.html
-    /// [`Hash`]: https://docs.rs/exonum-crypto/latest/exonum_crypto/struct.Hash.html
-    async fn sign_input(&self, sign_input: SignInput) -> Result<Hash, Self::Error>;
-    /// Adds funds via suitable funding transaction.
+    /// [`Vec<ConfigChange>`]: ../blockchain/data_layout/struct.ConfigChange.html
+    async fn config_history(&self) -> Result<Vec<ConfigChange>, Self::Error>;
+    /// Returns participation of each anchoring node in signing the actual anchoring
+    /// transaction proposal. If there is no proposal, an empty list is returned.
     ///
-    /// Bitcoin transaction should have output with value to the current anchoring address.
-    /// The transaction will be applied if 2/3+1 anchoring nodes sent it.
+    /// | Property    | Value |
+    /// |-------------|-------|
+    /// | Path        | `/api/services/{btc_anchoring}/proposal-participation` |
+    /// | Method      | GET   |
+    /// | Query type  | - |
+    /// | Return type | [`Vec<NodeParticipation>`] |
+    ///
+    /// [`Vec<NodeParticipation>`]: struct.NodeParticipation.html
+    async fn proposal_participation(&self) -> Result<Vec<NodeParticipation>, Self::Error>;
+    /// Returns the signatures submitted for each input of the actual anchoring transaction
+    /// proposal by the anchoring nodes and the number of signatures which are still
+    /// missing, if the proposal exists.
     ///
     /// | Property    | Value |
     /// |-------------|-------|
-    /// | Path        | `/api/services/{btc_anchoring}/add-funds` |
-    /// | Method      | POST   |
-    /// | Query type  | [`AddFunds`] |
-    /// | Return type | [`Hash`] |
+    /// | Path        | `/api/services/{btc_anchoring}/signing-session` |
+    /// | Method      | GET   |
+    /// | Query type  | - |
+    /// | Return type | [`Option<SigningSession>`] |
     ///
-    /// [`AddFunds`]: ../blockchain/struct.AddFunds.html
-    /// [`Hash`]: https://docs.rs/exonum-crypto/latest/exonum_crypto/struct.Hash.html
-    async fn add_funds(&self, transaction: btc::Transaction) -> Result<Hash, Self::Error>;
-    /// Returns a proposal for the next anchoring transaction, if it makes sense.
-    /// If there is not enough satoshis to create a proposal an error is returned.
+    /// [`Option<SigningSession>`]: struct.SigningSession.html
+    async fn signing_session(&self) -> Result<Option<SigningSession>, Self::Error>;
+    /// Returns statistics of participation in signing of the finalized proposals for each
+    /// node from the actual anchoring configuration.
     ///
     /// | Property    | Value |
     /// |-------------|-------|
-    /// | Path        | `/api/services/{btc_anchoring}/anchoring-proposal` |
+    /// | Path        | `/api/services/{btc_anchoring}/signing-stats` |
     /// | Method      | GET   |
     /// | Query type  | - |
-    /// | Return type | [`AnchoringProposalState`] |
+    /// | Return type | [`Vec<NodeSigningStats>`] |
     ///
-    /// [`AnchoringProposalState`]: enum.AnchoringProposalState.html
-    async fn anchoring_proposal(&self) -> Result<AnchoringProposalState, Self::Error>;
-    /// Returns an actual anchoring configuration.
+    /// [`Vec<NodeSigningStats>`]: struct.NodeSigningStats.html
+    async fn signing_stats(&self) -> Result<Vec<NodeSigningStats>, Self::Error>;
+    /// Returns aggregate statistics of the anchoring process.
     ///
     /// | Property    | Value |
     /// |-------------|-------|
-    /// | Path        | `/api/services/{btc_anchoring}/config` |
+    /// | Path        | `/api/services/{btc_anchoring}/stats` |
     /// | Method      | GET   |
     /// | Query type  | - |
-    /// | Return type | [`Config`] |
+    /// | Return type | [`AnchoringStatistics`] |
     ///
-    /// [`config`]: ../config/struct.Config.html
-    async fn config(&self) -> Result<Config, Self::Error>;
-    /// Returns an anchoring transaction with the specified index in anchoring transactions chain.
+    /// [`AnchoringStatistics`]: struct.AnchoringStatistics.html
+    async fn stats(&self) -> Result<AnchoringStatistics, Self::Error>;
+    /// Returns the aggregated status of the anchoring process, which allows to check
+    /// whether the anchoring is healthy by a single request.
     ///
     /// | Property    | Value |
     /// |-------------|-------|
-    /// | Path        | `/api/services/{btc_anchoring}/transaction` |
+    /// | Path        | `/api/services/{btc_anchoring}/status` |
+    /// | Method      | GET   |
+    /// | Query type  | - |
+    /// | Return type | [`AnchoringStatus`] |
+    ///
+    /// [`AnchoringStatus`]: struct.AnchoringStatus.html
+    async fn status(&self) -> Result<AnchoringStatus, Self::Error>;
+    /// Returns every anchoring address ever used by the service in the chronological order,
+    /// along with the ranges of anchoring transactions that send funds to it.
+    ///
+    /// | Property    | Value |
+    /// |-------------|-------|
+    /// | Path        | `/api/services/{btc_anchoring}/address/history` |
+    /// | Method      | GET   |
+    /// | Query type  | - |
+    /// | Return type | [`Vec<AnchoringAddressRange>`] |
+    ///
+    /// [`Vec<AnchoringAddressRange>`]: struct.AnchoringAddressRange.html
+    async fn address_history(&self) -> Result<Vec<AnchoringAddressRange>, Self::Error>;
+    /// Returns the identifiers of the anchoring transaction with the given index
+    /// if it exists.
+    ///
+    /// | Property    | Value |
+    /// |-------------|-------|
+    /// | Path        | `/api/services/{btc_anchoring}/transaction-ids` |
     /// | Method      | GET   |
     /// | Query type  | [`IndexQuery`] |
-    /// | Return type | [`Option<btc::Transaction>`] |
+    /// | Return type | [`Option<AnchoringTransactionIds>`] |
     ///
-    /// ['IndexQuery']: struct.IndexQuery.html
-    /// [`Option<btc::Transaction>`]: ../btc/struct.Transaction.html
-    async fn transaction_with_index(
+    /// [`IndexQuery`]: struct.IndexQuery.html
+    /// [`Option<AnchoringTransactionIds>`]: struct.AnchoringTransactionIds.html
+    async fn transaction_ids(
         &self,
         index: u64,
-    ) -> Result<Option<btc::Transaction>, Self::Error>;
-    /// Returns a total number of anchoring transactions in the chain.
+    ) -> Result<Option<AnchoringTransactionIds>, Self::Error>;
+    /// Returns the index of the anchoring transaction which anchors the nearest block
+    /// with the given or greater height along with the anchored height, or `None` if
+    /// the block with the given height is not anchored yet.
+    ///
+    /// If several transactions anchor the same height, the latest of them is returned.
     ///
     /// | Property    | Value |
     /// |-------------|-------|
-    /// | Path        | `/api/services/{btc_anchoring}/transactions-count` |
+    /// | Path        | `/api/services/{btc_anchoring}/transaction-index-by-height` |
     /// | Method      | GET   |
-    /// | Query type  | - |
-    /// | Return type | [`AnchoringChainLength`] |
+    /// | Query type  | [`BlockHeightQuery`] |
+    /// | Return type | [`Option<AnchoredHeightIndex>`] |
     ///
-    /// [`AnchoringChainLength`]: struct.AnchoringChainLength.html
-    async fn transactions_count(&self) -> Result<AnchoringChainLength, Self::Error>;
-}
-
-struct ApiImpl(ServiceApiState);
-
-impl ApiImpl {
-    fn broadcaster(&self) -> api::Result<Broadcaster> {
-        self.0.broadcaster().ok_or_else(|| {
-            api::Error::bad_request()
-                .title("Invalid broadcast request")
-                .detail("Node is not a validator")
-        })
-    }
-
-    fn actual_config(self) -> api::Result<Config> {
-        Ok(Schema::new(self.0.service_data()).actual_config())
-    }
-
-    fn verify_sign_input(&self, sign_input: &SignInput) -> anyhow::Result<()> {
-        let schema = Schema::new(self.0.service_data());
-        let (proposal, inputs) = schema
-            .actual_proposed_anchoring_transaction(self.0.data().for_core())
-            .ok_or_else(|| anyhow!("Anchoring transaction proposal is absent."))??;
-
-        // Verify transaction content.
-        let input = inputs
-            .get(sign_input.input as usize)
-            .ok_or_else(|| anyhow!("Missing input with index: {}", sign_input.input))?;
-
-        // Find corresponding Bitcoin key.
-        let config = schema.actual_config();
-        let bitcoin_key = config
-            .find_bitcoin_key(&self.0.service_key())
-            .ok_or_else(|| anyhow!("This node is not an anchoring node."))?
-            .1;
-
-        // Verify input signature.
-        p2wsh::InputSigner::new(config.redeem_script())
-            .verify_input(
-                TxInRef::new(proposal.as_ref(), sign_input.input as usize),
-                input.as_ref(),
-                &bitcoin_key.0,
-                sign_input.input_signature.as_ref(),
-            )
-            .map_err(|e| anyhow!("Input signature verification failed: {}", e))
+    /// [`BlockHeightQuery`]: struct.BlockHeightQuery.html
+    /// [`Option<AnchoredHeightIndex>`]: struct.AnchoredHeightIndex.html
+    async fn transaction_index_by_height(
+        &self,
+        height: Height,
+    ) -> Result<Option<AnchoredHeightIndex>, Self::Error>;
+    /// Returns at most `count` anchoring transactions starting from the index `from`
+    /// along with the total length of the anchoring transactions chain.
+    ///
+    /// The `count` should not exceed [`TransactionsRangeQuery::MAX_COUNT`].
+    ///
+    /// | Property    | Value |
+    /// |-------------|-------|
+    /// | Path        | `/api/services/{btc_anchoring}/transactions` |
+    /// | Method      | GET   |
+    /// | Query type  | [`TransactionsRangeQuery`] |
+    /// | Return type | [`AnchoringTransactionsPage`] |
+    ///
+    /// [`TransactionsRangeQuery::MAX_COUNT`]: struct.TransactionsRangeQuery.html#associatedconstant.MAX_COUNT
+    /// [`TransactionsRangeQuery`]: struct.TransactionsRangeQuery.html
+    /// [`AnchoringTransactionsPage`]: struct.AnchoringTransactionsPage.html
+    async fn transactions(
+        &self,
+        from: u64,
+        count: u64,
+    ) -> Result<AnchoringTransactionsPage, Self::Error>;
+    /// Returns a proof of existence for the anchoring transaction with the given
+    /// witness transaction identifier if it exists.
+    ///
+    /// | Property    | Value |
+    /// |-------------|-------|
+    /// | Path        | `/api/services/{btc_anchoring}/find-transaction-by-wtxid` |
+    /// | Method      | GET   |
+    /// | Query type  | [`WtxidQuery`] |
+    /// | Return type | [`Option<TransactionProof>`] |
+    ///
+    /// [`WtxidQuery`]: struct.WtxidQuery.html
+    /// [`Option<TransactionProof>`]: struct.TransactionProof.html
+    async fn find_transaction_by_wtxid(
+        &self,
+        wtxid: btc::Sha256d,
+    ) -> Result<Option<TransactionProof>, Self::Error>;
+    /// Returns a proof of existence for the earliest anchoring transaction which anchors
+    /// the block with the given or greater height. Unlike [`find_transaction`], returns
+    /// `None` if the block with the given height is not anchored yet.
+    ///
+    /// | Property    | Value |
+    /// |-------------|-------|
+    /// | Path        | `/api/services/{btc_anchoring}/nearest-anchoring-transaction` |
+    /// | Method      | GET   |
+    /// | Query type  | [`BlockHeightQuery`] |
+    /// | Return type | [`Option<TransactionProof>`] |
+    ///
+    /// [`find_transaction`]: #tymethod.find_transaction
+    /// [`BlockHeightQuery`]: struct.BlockHeightQuery.html
+    /// [`Option<TransactionProof>`]: struct.TransactionProof.html
+    async fn nearest_anchoring_transaction(
+        &self,
+        height: Height,
+    ) -> Result<Option<TransactionProof>, Self::Error>;
+    /// Returns a proof that the Exonum block with the given hash is covered by
+    /// the earliest anchoring transaction which anchors this or any following block.
+    /// Returns `None` if the block is unknown or is not anchored yet.
+    ///
+    /// | Property    | Value |
+    /// |-------------|-------|
+    /// | Path        | `/api/services/{btc_anchoring}/find-transaction-by-block-hash` |
+    /// | Method      | GET   |
+    /// | Query type  | [`BlockHashQuery`] |
+    /// | Return type | [`Option<BlockAnchorProof>`] |
+    ///
+    /// [`BlockHashQuery`]: struct.BlockHashQuery.html
+    /// [`Option<BlockAnchorProof>`]: struct.BlockAnchorProof.html
+    async fn find_transaction_by_block_hash(
+        &self,
+        block_hash: Hash,
+    ) -> Result<Option<BlockAnchorProof>, Self::Error>;
+    /// Returns the complete chain of evidence that the Exonum transaction with the given
+    /// hash is anchored to the Bitcoin blockchain, which can be verified by the end users
+    /// without access to the node. Returns `None` if the transaction is unknown or
+    /// its block is not anchored yet.
+    ///
+    /// | Property    | Value |
+    /// |-------------|-------|
+    /// | Path        | `/api/services/{btc_anchoring}/exonum-transaction-proof` |
+    /// | Method      | GET   |
+    /// | Query type  | [`TransactionHashQuery`] |
+    /// | Return type | [`Option<ExonumTransactionProof>`] |
+    ///
+    /// [`TransactionHashQuery`]: struct.TransactionHashQuery.html
+    /// [`Option<ExonumTransactionProof>`]: struct.ExonumTransactionProof.html
+    async fn exonum_transaction_proof(
+        &self,
+        tx_hash: Hash,
+    ) -> Result<Option<ExonumTransactionProof>, Self::Error>;
+    /// Returns the proof that the Exonum block header at the given height is anchored
+    /// to the Bitcoin blockchain. Returns `None` if there is no block at the given height
+    /// or the block is not anchored yet.
+    ///
+    /// | Property    | Value |
+    /// |-------------|-------|
+    /// | Path        | `/api/services/{btc_anchoring}/block-header-proof` |
+    /// | Method      | GET   |
+    /// | Query type  | [`BlockHeightQuery`] |
+    /// | Return type | [`Option<BlockHeaderProof>`] |
+    ///
+    /// [`BlockHeightQuery`]: struct.BlockHeightQuery.html
+    /// [`Option<BlockHeaderProof>`]: struct.BlockHeaderProof.html
+    async fn block_header_proof(
+        &self,
+        height: Height,
+    ) -> Result<Option<BlockHeaderProof>, Self::Error>;
+    /// Returns a proof of inclusion of the anchoring transaction with the given index
+    /// in the Bitcoin block, if it has been reported by the anchoring nodes.
+    ///
+    /// | Property    | Value |
+    /// |-------------|-------|
+    /// | Path        | `/api/services/{btc_anchoring}/transaction-inclusion-proof` |
+    /// | Method      | GET   |
+    /// | Query type  | [`IndexQuery`] |
+    /// | Return type | [`Option<TransactionInclusionProof>`] |
+    ///
+    /// [`IndexQuery`]: struct.IndexQuery.html
+    /// [`Option<TransactionInclusionProof>`]: struct.TransactionInclusionProof.html
+    async fn transaction_inclusion_proof(
+        &self,
+        index: u64,
+    ) -> Result<Option<TransactionInclusionProof>, Self::Error>;
+    /// Returns a proof of inclusion of the registered funding transaction with the given
+    /// identifier in the Bitcoin block, if it has been reported by the anchoring nodes.
+    ///
+    /// | Property    | Value |
+    /// |-------------|-------|
+    /// | Path        | `/api/services/{btc_anchoring}/funding-inclusion-proof` |
+    /// | Method      | GET   |
+    /// | Query type  | [`TxidQuery`] |
+    /// | Return type | [`Option<FundingInclusionProof>`] |
+    ///
+    /// [`TxidQuery`]: struct.TxidQuery.html
+    /// [`Option<FundingInclusionProof>`]: struct.FundingInclusionProof.html
+    async fn funding_inclusion_proof(
+        &self,
+        txid: btc::Sha256d,
+    ) -> Result<Option<FundingInclusionProof>, Self::Error>;
+    /// Returns all funding transactions registered by the service, either spent
+    /// or not, along with the total amounts received and spent by the anchoring wallet.
+    ///
+    /// | Property    | Value |
+    /// |-------------|-------|
+    /// | Path        | `/api/services/{btc_anchoring}/funding-history` |
+    /// | Method      | GET   |
+    /// | Query type  | - |
+    /// | Return type | [`FundingHistory`] |
+    ///
+    /// [`FundingHistory`]: struct.FundingHistory.html
+    async fn funding_history(&self) -> Result<FundingHistory, Self::Error>;
+    /// Returns the stored signatures of the anchoring transaction input made by
+    /// the anchoring nodes. The signatures are kept for the finalized anchoring
+    /// transactions as well as for the actual proposal.
+    ///
+    /// | Property    | Value |
+    /// |-------------|-------|
+    /// | Path        | `/api/services/{btc_anchoring}/input-signatures` |
+    /// | Method      | GET   |
+    /// | Query type  | [`TxInputQuery`] |
+    /// | Return type | [`Vec<NodeInputSignature>`] |
+    ///
+    /// [`TxInputQuery`]: struct.TxInputQuery.html
+    /// [`Vec<NodeInputSignature>`]: struct.NodeInputSignature.html
+    async fn input_signatures(
+        &self,
+        txid: btc::Sha256d,
+        input: u32,
+    ) -> Result<Vec<NodeInputSignature>, Self::Error>;
+    /// Returns the archive with the anchoring state, which can be imported
+    /// into another database.
+    ///
+    /// | Property    | Value |
+    /// |-------------|-------|
+    /// | Path        | `/api/services/{btc_anchoring}/backup` |
+    /// | Method      | GET   |
+    /// | Query type  | - |
+    /// | Return type | [`AnchoringArchive`] |
+    ///
+    /// [`AnchoringArchive`]: ../blockchain/backup/struct.AnchoringArchive.html
+    async fn backup(&self) -> Result<AnchoringArchive, Self::Error>;
+    /// Returns the fees paid by the anchoring transactions which anchor the Exonum blocks
+    /// and are included in the Bitcoin blocks within the given ranges.
+    ///
+    /// Transactions are filtered by the Bitcoin block time only if the time range is
+    /// specified, in which case the transactions without the reported inclusion proofs
+    /// are skipped.
+    ///
+    /// | Property    | Value |
+    /// |-------------|-------|
+    /// | Path        | `/api/services/{btc_anchoring}/cost-report` |
+    /// | Method      | GET   |
+    /// | Query type  | [`CostReportQuery`] |
+    /// | Return type | [`AnchoringCostReport`] |
+    ///
+    /// [`CostReportQuery`]: struct.CostReportQuery.html
+    /// [`AnchoringCostReport`]: struct.AnchoringCostReport.html
+    async fn cost_report(&self, query: CostReportQuery)
+        -> Result<AnchoringCostReport, Self::Error>;
+    /// Returns the anchoring heights skipped before the anchoring transactions have been
+    /// finalized, along with the reasons of the delays, in the order of the anchoring chain.
+    ///
+    /// | Property    | Value |
+    /// |-------------|-------|
+    /// | Path        | `/api/services/{btc_anchoring}/anchoring-gaps` |
+    /// | Method      | GET   |
+    /// | Query type  | - |
+    /// | Return type | [`Vec<AnchoringGap>`] |
+    ///
+    /// [`Vec<AnchoringGap>`]: struct.AnchoringGap.html
+    async fn anchoring_gaps(&self) -> Result<Vec<AnchoringGap>, Self::Error>;
+    /// Returns the progress of the in-flight transition of the anchoring chain to
+    /// the following address, or the state of the latest completed transition if
+    /// there is no in-flight one. Returns `None` if the anchoring address has never
+    /// been changed.
+    ///
+    /// | Property    | Value |
+    /// |-------------|-------|
+    /// | Path        | `/api/services/{btc_anchoring}/transition-progress` |
+    /// | Method      | GET   |
+    /// | Query type  | - |
+    /// | Return type | [`Option<TransitionProgress>`] |
+    ///
+    /// [`Option<TransitionProgress>`]: struct.TransitionProgress.html
+    async fn transition_progress(&self) -> Result<Option<TransitionProgress>, Self::Error>;
+    /// Returns the state of the transition of the anchoring chain to the address
+    /// of the following configuration.
+    ///
+    /// | Property    | Value |
+    /// |-------------|-------|
+    /// | Path        | `/api/services/{btc_anchoring}/transition-state` |
+    /// | Method      | GET   |
+    /// | Query type  | - |
+    /// | Return type | [`TransitionState`] |
+    ///
+    /// [`TransitionState`]: ../blockchain/data_layout/enum.TransitionState.html
+    async fn transition_state(&self) -> Result<TransitionState, Self::Error>;
+    /// Returns the same proof as the `find-transaction` method, signed by the node.
+    ///
+    /// The endpoint is available only if the service has been created with the API
+    /// signing key, see [`BtcAnchoringService::with_api_signing_key`].
+    ///
+    /// | Property    | Value |
+    /// |-------------|-------|
+    /// | Path        | `/api/services/{btc_anchoring}/signed/find-transaction` |
+    /// | Method      | GET   |
+    /// | Query type  | [`FindTransactionQuery`] |
+    /// | Return type | [`SignedResponse<TransactionProof>`] |
+    ///
+    /// [`BtcAnchoringService::with_api_signing_key`]: ../struct.BtcAnchoringService.html#method.with_api_signing_key
+    /// [`FindTransactionQuery`]: struct.FindTransactionQuery.html
+    /// [`SignedResponse<TransactionProof>`]: struct.SignedResponse.html
+    async fn signed_find_transaction(
+        &self,
+        height: Option<Height>,
+    ) -> Result<SignedResponse<TransactionProof>, Self::Error>;
+    /// Returns the same proof as the `transaction-inclusion-proof` method, signed
+    /// by the node.
+    ///
+    /// The endpoint is available only if the service has been created with the API
+    /// signing key.
+    ///
+    /// | Property    | Value |
+    /// |-------------|-------|
+    /// | Path        | `/api/services/{btc_anchoring}/signed/transaction-inclusion-proof` |
+    /// | Method      | GET   |
+    /// | Query type  | [`IndexQuery`] |
+    /// | Return type | [`SignedResponse<Option<TransactionInclusionProof>>`] |
+    ///
+    /// [`IndexQuery`]: struct.IndexQuery.html
+    /// [`SignedResponse<Option<TransactionInclusionProof>>`]: struct.SignedResponse.html
+    async fn signed_transaction_inclusion_proof(
+        &self,
+        index: u64,
+    ) -> Result<SignedResponse<Option<TransactionInclusionProof>>, Self::Error>;
+}
+
+/// Private API client for the Exonum Bitcoin anchoring service.
+#[async_trait]
+pub trait PrivateApi {
+    /// Error type for the current API client implementation.
+    type Error;
+    /// Creates and broadcasts the `TxSignature` transaction, which is signed
+    /// by the current node, and returns its hash.
+    ///
+    /// | Property    | Value |
+    /// |-------------|-------|
+    /// | Path        | `/api/services/{btc_anchoring}/sign-input` |
+    /// | Method      | POST   |
+    /// | Query type  | [`SignInput`] |
+    /// | Return type | [`Hash`] |
+    ///
+    /// [`SignInput`]: ../blockchain/struct.SignInput.html
+    /// [`Hash`]: https://docs.rs/exonum-crypto/latest/exonum_crypto/struct.Hash.html
+    async fn sign_input(&self, sign_input: SignInput) -> Result<Hash, Self::Error>;
+    /// Creates and broadcasts the single [`SignInputs`] transaction with the signatures
+    /// of several inputs of the anchoring proposal, which is signed by the current node,
+    /// and returns its hash.
+    ///
+    /// | Property    | Value |
+    /// |-------------|-------|
+    /// | Path        | `/api/services/{btc_anchoring}/sign-inputs` |
+    /// | Method      | POST   |
+    /// | Query type  | [`SignInputs`] |
+    /// | Return type | [`Hash`] |
+    ///
+    /// [`SignInputs`]: ../blockchain/struct.SignInputs.html
+    /// [`Hash`]: https://docs.rs/exonum-crypto/latest/exonum_crypto/struct.Hash.html
+    async fn sign_inputs(&self, sign_inputs: Vec<SignInput>) -> Result<Hash, Self::Error>;
+    /// Adds funds via suitable funding transaction.
+    ///
+    /// Bitcoin transaction should have output with value to the current anchoring address.
+    /// The transaction will be applied if 2/3+1 anchoring nodes sent it.
+    ///
+    /// | Property    | Value |
+    /// |-------------|-------|
+    /// | Path        | `/api/services/{btc_anchoring}/add-funds` |
+    /// | Method      | POST   |
+    /// | Query type  | [`AddFunds`] |
+    /// | Return type | [`Hash`] |
+    ///
+    /// [`AddFunds`]: ../blockchain/struct.AddFunds.html
+    /// [`Hash`]: https://docs.rs/exonum-crypto/latest/exonum_crypto/struct.Hash.html
+    async fn add_funds(&self, transaction: btc::Transaction) -> Result<Hash, Self::Error>;
+    /// Returns a proposal for the next anchoring transaction, if it makes sense.
+    /// If there is not enough satoshis to create a proposal an error is returned.
+    ///
+    /// | Property    | Value |
+    /// |-------------|-------|
+    /// | Path        | `/api/services/{btc_anchoring}/anchoring-proposal` |
+    /// | Method      | GET   |
+    /// | Query type  | - |
+    /// | Return type | [`AnchoringProposalState`] |
+    ///
+    /// [`AnchoringProposalState`]: enum.AnchoringProposalState.html
+    async fn anchoring_proposal(&self) -> Result<AnchoringProposalState, Self::Error>;
+    /// Returns the actual anchoring proposal as an unsigned PSBT, which can be signed
+    /// by an external wallet, or `None` if there is no proposal to sign.
+    ///
+    /// | Property    | Value |
+    /// |-------------|-------|
+    /// | Path        | `/api/services/{btc_anchoring}/anchoring-proposal-psbt` |
+    /// | Method      | GET   |
+    /// | Query type  | - |
+    /// | Return type | [`Option<btc::Psbt>`] |
+    ///
+    /// [`Option<btc::Psbt>`]: ../btc/struct.Psbt.html
+    async fn anchoring_proposal_psbt(&self) -> Result<Option<btc::Psbt>, Self::Error>;
+    /// Imports the signatures of the anchoring proposal inputs made by the Bitcoin key
+    /// of the current node from the given PSBT. Each signature is verified and sent
+    /// as a separate [`SignInput`] transaction.
+    ///
+    /// | Property    | Value |
+    /// |-------------|-------|
+    /// | Path        | `/api/services/{btc_anchoring}/import-psbt` |
+    /// | Method      | POST   |
+    /// | Query type  | [`btc::Psbt`] |
+    /// | Return type | [`Vec<Hash>`] |
+    ///
+    /// [`SignInput`]: ../blockchain/struct.SignInput.html
+    /// [`btc::Psbt`]: ../btc/struct.Psbt.html
+    /// [`Vec<Hash>`]: https://docs.rs/exonum-crypto/latest/exonum_crypto/struct.Hash.html
+    async fn import_psbt(&self, psbt: btc::Psbt) -> Result<Vec<Hash>, Self::Error>;
+    /// Returns an actual anchoring configuration.
+    ///
+    /// | Property    | Value |
+    /// |-------------|-------|
+    /// | Path        | `/api/services/{btc_anchoring}/config` |
+    /// | Method      | GET   |
+    /// | Query type  | - |
+    /// | Return type | [`Config`] |
+    ///
+    /// [`config`]: ../config/struct.Config.html
+    async fn config(&self) -> Result<Config, Self::Error>;
+    /// Returns the following anchoring configuration if the transition to it is in progress.
+    ///
+    /// | Property    | Value |
+    /// |-------------|-------|
+    /// | Path        | `/api/services/{btc_anchoring}/following-config` |
+    /// | Method      | GET   |
+    /// | Query type  | - |
+    /// | Return type | [`Option<Config>`] |
+    ///
+    /// [`Option<Config>`]: ../config/struct.Config.html
+    async fn following_config(&self) -> Result<Option<Config>, Self::Error>;
+    /// Returns an anchoring transaction with the specified index in anchoring transactions chain.
+    ///
+    /// | Property    | Value |
+    /// |-------------|-------|
+    /// | Path        | `/api/services/{btc_anchoring}/transaction` |
+    /// | Method      | GET   |
+    /// | Query type  | [`IndexQuery`] |
+    /// | Return type | [`Option<btc::Transaction>`] |
+    ///
+    /// ['IndexQuery']: struct.IndexQuery.html
+    /// [`Option<btc::Transaction>`]: ../btc/struct.Transaction.html
+    async fn transaction_with_index(
+        &self,
+        index: u64,
+    ) -> Result<Option<btc::Transaction>, Self::Error>;
+    /// Returns a total number of anchoring transactions in the chain.
+    ///
+    /// | Property    | Value |
+    /// |-------------|-------|
+    /// | Path        | `/api/services/{btc_anchoring}/transactions-count` |
+    /// | Method      | GET   |
+    /// | Query type  | - |
+    /// | Return type | [`AnchoringChainLength`] |
+    ///
+    /// [`AnchoringChainLength`]: struct.AnchoringChainLength.html
+    async fn transactions_count(&self) -> Result<AnchoringChainLength, Self::Error>;
+    /// Returns aggregate statistics of the anchoring process, e.g. to expose them
+    /// as the sync utility metrics.
+    ///
+    /// | Property    | Value |
+    /// |-------------|-------|
+    /// | Path        | `/api/services/{btc_anchoring}/stats` |
+    /// | Method      | GET   |
+    /// | Query type  | - |
+    /// | Return type | [`AnchoringStatistics`] |
+    ///
+    /// [`AnchoringStatistics`]: struct.AnchoringStatistics.html
+    async fn stats(&self) -> Result<AnchoringStatistics, Self::Error>;
+    /// Returns at most `count` anchoring transactions starting from the index `from`
+    /// along with the total length of the anchoring transactions chain.
+    ///
+    /// | Property    | Value |
+    /// |-------------|-------|
+    /// | Path        | `/api/services/{btc_anchoring}/transactions` |
+    /// | Method      | GET   |
+    /// | Query type  | [`TransactionsRangeQuery`] |
+    /// | Return type | [`AnchoringTransactionsPage`] |
+    ///
+    /// [`TransactionsRangeQuery`]: struct.TransactionsRangeQuery.html
+    /// [`AnchoringTransactionsPage`]: struct.AnchoringTransactionsPage.html
+    async fn transactions(
+        &self,
+        from: u64,
+        count: u64,
+    ) -> Result<AnchoringTransactionsPage, Self::Error>;
+    /// Returns the state of the signing session for the actual anchoring transaction proposal
+    /// if the proposal exists.
+    ///
+    /// | Property    | Value |
+    /// |-------------|-------|
+    /// | Path        | `/api/services/{btc_anchoring}/signing-session` |
+    /// | Method      | GET   |
+    /// | Query type  | - |
+    /// | Return type | [`Option<SigningSession>`] |
+    ///
+    /// [`Option<SigningSession>`]: struct.SigningSession.html
+    async fn signing_session(&self) -> Result<Option<SigningSession>, Self::Error>;
+    /// Reports a proof of inclusion of anchoring or funding transactions in a Bitcoin block.
+    ///
+    /// | Property    | Value |
+    /// |-------------|-------|
+    /// | Path        | `/api/services/{btc_anchoring}/report-inclusion-proof` |
+    /// | Method      | POST   |
+    /// | Query type  | [`btc::MerkleBlock`] |
+    /// | Return type | [`Hash`] |
+    ///
+    /// [`btc::MerkleBlock`]: ../btc/struct.MerkleBlock.html
+    /// [`Hash`]: https://docs.rs/exonum-crypto/latest/exonum_crypto/struct.Hash.html
+    async fn report_inclusion_proof(&self, proof: btc::MerkleBlock) -> Result<Hash, Self::Error>;
+    /// Votes for the fee per byte in satoshis of the anchoring transactions on behalf of
+    /// the current node.
+    ///
+    /// | Property    | Value |
+    /// |-------------|-------|
+    /// | Path        | `/api/services/{btc_anchoring}/vote-fee-rate` |
+    /// | Method      | POST   |
+    /// | Query type  | [`VoteFeeRate`] |
+    /// | Return type | [`Hash`] |
+    ///
+    /// [`VoteFeeRate`]: ../blockchain/struct.VoteFeeRate.html
+    /// [`Hash`]: https://docs.rs/exonum-crypto/latest/exonum_crypto/struct.Hash.html
+    async fn vote_fee_rate(&self, fee_rate: u64) -> Result<Hash, Self::Error>;
+    /// Votes for the acceleration of the latest anchoring transaction stuck in the Bitcoin
+    /// mempool by the child transaction with the given fee per byte in satoshis on behalf
+    /// of the current node.
+    ///
+    /// | Property    | Value |
+    /// |-------------|-------|
+    /// | Path        | `/api/services/{btc_anchoring}/accelerate-anchoring` |
+    /// | Method      | POST   |
+    /// | Query type  | [`AccelerateAnchoring`] |
+    /// | Return type | [`Hash`] |
+    ///
+    /// [`AccelerateAnchoring`]: ../blockchain/struct.AccelerateAnchoring.html
+    /// [`Hash`]: https://docs.rs/exonum-crypto/latest/exonum_crypto/struct.Hash.html
+    async fn accelerate_anchoring(
+        &self,
+        txid: btc::Sha256d,
+        fee_rate: u64,
+    ) -> Result<Hash, Self::Error>;
+    /// Votes for the recovery of the anchoring chain broken at the latest anchoring
+    /// transaction with the given identifier on behalf of the current node. The next
+    /// anchoring transaction spends a fresh funding transaction instead of the unusable
+    /// output of the broken one.
+    ///
+    /// | Property    | Value |
+    /// |-------------|-------|
+    /// | Path        | `/api/services/{btc_anchoring}/recover-anchoring` |
+    /// | Method      | POST   |
+    /// | Query type  | [`RecoverAnchoring`] |
+    /// | Return type | [`Hash`] |
+    ///
+    /// [`RecoverAnchoring`]: ../blockchain/struct.RecoverAnchoring.html
+    /// [`Hash`]: https://docs.rs/exonum-crypto/latest/exonum_crypto/struct.Hash.html
+    async fn recover_anchoring(&self, txid: btc::Sha256d) -> Result<Hash, Self::Error>;
+    /// Creates the supervisor configuration proposal which replaces the compromised Bitcoin
+    /// key of the anchoring node and excludes it from signing.
+    ///
+    /// The signatures made by the excluded key are rejected as soon as the proposal is
+    /// applied, and the transition transaction to the new anchoring address is proposed
+    /// immediately, regardless of the anchoring schedule.
+    ///
+    /// | Property    | Value |
+    /// |-------------|-------|
+    /// | Path        | `/api/services/{btc_anchoring}/key-replacement-proposal` |
+    /// | Method      | GET   |
+    /// | Query type  | [`KeyReplacementQuery`] |
+    /// | Return type | [`ConfigPropose`] |
+    ///
+    /// [`KeyReplacementQuery`]: struct.KeyReplacementQuery.html
+    /// [`ConfigPropose`]: https://docs.rs/exonum-supervisor/latest/exonum_supervisor/struct.ConfigPropose.html
+    async fn key_replacement_proposal(
+        &self,
+        query: KeyReplacementQuery,
+    ) -> Result<ConfigPropose, Self::Error>;
+}
+
+struct ApiImpl(ServiceApiState);
+
+impl ApiImpl {
+    fn broadcaster(&self) -> api::Result<Broadcaster> {
+        self.0.broadcaster().ok_or_else(|| {
+            ApiErrorKind::NotValidator
+                .error()
+                .title("Invalid broadcast request")
+                .detail("Node is not a validator")
+        })
+    }
+
+    fn actual_config(self) -> api::Result<Config> {
+        Ok(Schema::new(self.0.service_data()).actual_config())
+    }
+
+    fn verify_sign_input(&self, sign_input: &SignInput) -> anyhow::Result<()> {
+        let schema = Schema::new(self.0.service_data());
+        let (proposal, inputs) = schema
+            .actual_proposed_anchoring_transaction(self.0.data().for_core())
+            .ok_or_else(|| anyhow!("Anchoring transaction proposal is absent."))??;
+
+        // Verify transaction content.
+        let input = inputs
+            .get(sign_input.input as usize)
+            .ok_or_else(|| anyhow!("Missing input with index: {}", sign_input.input))?;
+
+        // Find corresponding Bitcoin key.
+        let config = schema.actual_config();
+        let bitcoin_key = config
+            .find_bitcoin_key(&self.0.service_key())
+            .ok_or_else(|| anyhow!("This node is not an anchoring node."))?
+            .1;
+        ensure!(
+            !schema.is_excluded_key(&bitcoin_key),
+            "Bitcoin key of this node is excluded by the following configuration."
+        );
+
+        // Verify input signature.
+        p2wsh::InputSigner::new(config.redeem_script())
+            .verify_input(
+                TxInRef::new(proposal.as_ref(), sign_input.input as usize),
+                input.as_ref(),
+                &bitcoin_key.0,
+                sign_input.input_signature.as_ref(),
+            )
+            .map_err(|e| anyhow!("Input signature verification failed: {}", e))
+    }
+
+    fn verify_psbt(&self, psbt: &btc::Psbt) -> anyhow::Result<Vec<SignInput>> {
+        let config = Schema::new(self.0.service_data()).actual_config();
+        let bitcoin_key = config
+            .find_bitcoin_key(&self.0.service_key())
+            .ok_or_else(|| anyhow!("This node is not an anchoring node."))?
+            .1;
+
+        let txid = psbt.txid();
+        let sign_inputs = psbt
+            .input_signatures(&bitcoin_key)?
+            .into_iter()
+            .map(|(input, input_signature)| SignInput {
+                txid,
+                input,
+                input_signature,
+            })
+            .collect::<Vec<_>>();
+        ensure!(
+            !sign_inputs.is_empty(),
+            "PSBT doesn't contain signatures made by the Bitcoin key of this node."
+        );
+        for sign_input in &sign_inputs {
+            self.verify_sign_input(sign_input)?;
+        }
+        Ok(sign_inputs)
+    }
+
+    fn verify_funding_tx(&self, tx: &btc::Transaction) -> anyhow::Result<()> {
+        let txid = tx.id();
+
+        let schema = Schema::new(self.0.service_data());
+        let config = schema.actual_config();
+        ensure!(
+            !schema.spent_funding_transactions.contains(&txid),
+            "Funding transaction {} has been already used.",
+            txid
+        );
+        ensure!(
+            tx.find_out(&config.anchoring_out_script()).is_some(),
+            "Funding transaction {} is not suitable.",
+            txid
+        );
+        Ok(())
+    }
+
+    fn actual_signing_session(&self) -> Option<SigningSession> {
+        let schema = Schema::new(self.0.service_data());
+        // There is no signing session if the proposal cannot be created.
+        let (proposal, inputs) = schema
+            .actual_proposed_anchoring_transaction(self.0.data().for_core())?
+            .ok()?;
+
+        let config = schema.actual_config();
+        let quorum = config.byzantine_quorum();
+        let txid = proposal.id();
+        let inputs = (0..inputs.len() as u32)
+            .map(|input| {
+                let signatures = schema.input_signatures(&TxInputId::new(txid, input));
+                let mut progress = InputSigningProgress {
+                    input,
+                    signed: Vec::new(),
+                    missing: Vec::new(),
+                    signatures_left: quorum.saturating_sub(signatures.0.len()),
+                };
+                // Signatures are keyed by the position of the node in the anchoring keys.
+                for (id, keys) in config.anchoring_keys.iter().enumerate() {
+                    if signatures.0.contains_key(&(id as u16)) {
+                        progress.signed.push(keys.clone());
+                    } else {
+                        progress.missing.push(keys.clone());
+                    }
+                }
+                progress
+            })
+            .collect::<Vec<_>>();
+
+        // Expiration height makes sense only for the already recorded proposal.
+        let expires_at = match schema.actual_proposal() {
+            Some(info) if info.txid == txid => schema.proposal_expiration_height(),
+            _ => None,
+        };
+        let signatures_left = inputs.iter().map(|input| input.signatures_left).sum();
+        Some(SigningSession {
+            txid,
+            quorum,
+            expires_at,
+            inputs,
+            signatures_left,
+        })
+    }
+
+    fn transaction_proof(&self, tx_index: u64) -> TransactionProof {
+        let index_proof = self
+            .0
+            .data()
+            .proof_for_service_index("transactions_chain")
+            .unwrap();
+        let transaction_proof = Schema::new(self.0.service_data())
+            .transactions_chain
+            .get_proof(tx_index);
+
+        TransactionProof {
+            index_proof,
+            transaction_proof,
+        }
+    }
+
+    fn inclusion_proof(&self, tx_index: u64) -> Option<TransactionInclusionProof> {
+        let schema = Schema::new(self.0.service_data());
+        let txid = schema.transactions_chain.get(tx_index)?.id();
+        let merkle_block = schema.inclusion_proof(&txid)?;
+        let block_height = schema.bitcoin_header_height(&merkle_block.header().block_hash())?;
+        Some(TransactionInclusionProof {
+            block_height,
+            merkle_block,
+            confirmations: schema.inclusion_depth(&txid)?,
+            is_final: schema.transaction_finality_height(&txid).is_some(),
+        })
+    }
+
+    /// Returns the index of the earliest anchoring transaction which anchors the block
+    /// with the given or greater height along with the anchored height, if the block
+    /// with the given height is anchored.
+    fn nearest_anchoring_transaction_index(&self, block_height: Height) -> Option<(u64, Height)> {
+        Schema::new(self.0.service_data()).anchoring_transaction_index(block_height)
+    }
+
+    /// Returns the proof that the block with the given height is covered by the earliest
+    /// anchoring transaction, if the block is anchored.
+    fn block_anchor_proof(&self, block_height: Height) -> anyhow::Result<Option<BlockAnchorProof>> {
+        let (index, anchored_height) = match self.nearest_anchoring_transaction_index(block_height)
+        {
+            Some(value) => value,
+            None => return Ok(None),
+        };
+
+        let core_schema = self.0.data().for_core();
+        let block_hashes = core_schema.block_hashes_by_height();
+        let blocks = core_schema.blocks();
+        let anchored_blocks = (block_height.0..=anchored_height.0)
+            .map(|height| {
+                block_hashes
+                    .get(height)
+                    .and_then(|hash| blocks.get(&hash))
+                    .ok_or_else(|| anyhow!("Unable to find block at height {}", height))
+            })
+            .collect::<anyhow::Result<Vec<_>>>()?;
+        Ok(Some(BlockAnchorProof {
+            block_height,
+            index,
+            transaction_proof: self.transaction_proof(index),
+            anchored_blocks,
+        }))
+    }
+}
+
+// Public API implementation
+impl ApiImpl {
+    async fn actual_address(self) -> api::Result<btc::Address> {
+        Ok(Schema::new(self.0.service_data())
+            .actual_config()
+            .anchoring_address())
+    }
+
+    async fn following_address(self) -> api::Result<Option<btc::Address>> {
+        Ok(Schema::new(self.0.service_data())
+            .following_config()
+            .map(|config| config.anchoring_address()))
+    }
+
+    async fn find_transaction(self, height: Option<Height>) -> api::Result<TransactionProof> {
+        let anchoring_schema = Schema::new(self.0.service_data());
+        let tx_chain_len = anchoring_schema.transactions_chain.len();
+
+        if tx_chain_len == 0 {
+            return Ok(self.transaction_proof(0));
+        }
+
+        let tx_index = height
+            .and_then(|height| anchoring_schema.anchoring_transaction_index(height))
+            .map_or(tx_chain_len - 1, |(index, _)| index);
+
+        Ok(self.transaction_proof(tx_index))
+    }
+
+    async fn find_transaction_by_block_hash(
+        self,
+        block_hash: Hash,
+    ) -> api::Result<Option<BlockAnchorProof>> {
+        match self.0.data().for_core().blocks().get(&block_hash) {
+            Some(block) => self
+                .block_anchor_proof(block.height)
+                .map_err(|e| ApiErrorKind::Internal.with_cause(e)),
+            None => Ok(None),
+        }
+    }
+
+    async fn exonum_transaction_proof(
+        self,
+        tx_hash: Hash,
+    ) -> api::Result<Option<ExonumTransactionProof>> {
+        let core_schema = self.0.data().for_core();
+        let location = match core_schema.transactions_locations().get(&tx_hash) {
+            Some(location) => location,
+            None => return Ok(None),
+        };
+        let block_height = location.block_height();
+        let anchor_proof = match self
+            .block_anchor_proof(block_height)
+            .map_err(|e| ApiErrorKind::Internal.with_cause(e))?
+        {
+            Some(proof) => proof,
+            None => return Ok(None),
+        };
+
+        let transaction = core_schema.transactions().get(&tx_hash).unwrap();
+        let block_proof = core_schema.block_and_precommits(block_height).unwrap();
+        let transaction_proof = core_schema
+            .block_transactions(block_height)
+            .get_proof(u64::from(location.position_in_block()));
+        let inclusion_proof = self.inclusion_proof(anchor_proof.index);
+        Ok(Some(ExonumTransactionProof {
+            transaction,
+            block_proof,
+            transaction_proof,
+            anchor_proof,
+            inclusion_proof,
+        }))
+    }
+
+    async fn block_header_proof(self, height: Height) -> api::Result<Option<BlockHeaderProof>> {
+        let block_proof = match self.0.data().for_core().block_and_precommits(height) {
+            Some(proof) => proof,
+            None => return Ok(None),
+        };
+        let anchor_proof = match self
+            .block_anchor_proof(height)
+            .map_err(|e| ApiErrorKind::Internal.with_cause(e))?
+        {
+            Some(proof) => proof,
+            None => return Ok(None),
+        };
+
+        let inclusion_proof = self.inclusion_proof(anchor_proof.index);
+        Ok(Some(BlockHeaderProof {
+            block_proof,
+            anchor_proof,
+            inclusion_proof,
+        }))
+    }
+
+    async fn config(self) -> api::Result<Config> {
+        self.actual_config()
+            .map_err(|e| ApiErrorKind::Internal.with_cause(e))
+    }
+
+    async fn following_config(self) -> api::Result<Option<Config>> {
+        Ok(Schema::new(self.0.service_data()).following_config())
+    }
+
+    async fn config_history(self) -> api::Result<Vec<ConfigChange>> {
+        Ok(Schema::new(self.0.service_data()).config_history())
+    }
+
+    async fn proposal_participation(self) -> api::Result<Vec<NodeParticipation>> {
+        let session = if let Some(session) = self.actual_signing_session() {
+            session
+        } else {
+            return Ok(Vec::new());
+        };
+
+        let schema = Schema::new(self.0.service_data());
+        let participation = schema
+            .actual_config()
+            .anchoring_keys
+            .into_iter()
+            .map(|anchoring_keys| {
+                let signed_inputs = session
+                    .inputs
+                    .iter()
+                    .filter(|input| input.signed.contains(&anchoring_keys))
+                    .count();
+                NodeParticipation {
+                    latest_signature_height: schema
+                        .latest_signature_height(&anchoring_keys.service_key),
+                    signed: signed_inputs == session.inputs.len(),
+                    signed_inputs,
+                    anchoring_keys,
+                }
+            })
+            .collect();
+        Ok(participation)
     }
 
-    fn verify_funding_tx(&self, tx: &btc::Transaction) -> anyhow::Result<()> {
-        let txid = tx.id();
+    async fn signing_stats(self) -> api::Result<Vec<NodeSigningStats>> {
+        let schema = Schema::new(self.0.service_data());
+        let stats = schema
+            .actual_config()
+            .anchoring_keys
+            .into_iter()
+            .map(|anchoring_keys| {
+                let stats = schema.signing_stats(&anchoring_keys.service_key);
+                NodeSigningStats {
+                    anchoring_keys,
+                    window_proposals: stats.window_proposals(),
+                    window_signed_proposals: stats.window_signed_proposals(),
+                    total_proposals: stats.total_proposals,
+                    signed_proposals: stats.signed_proposals,
+                }
+            })
+            .collect();
+        Ok(stats)
+    }
 
+    async fn stats(self) -> api::Result<AnchoringStatistics> {
         let schema = Schema::new(self.0.service_data());
-        let config = schema.actual_config();
-        ensure!(
-            !schema.spent_funding_transactions.contains(&txid),
-            "Funding transaction {} has been already used.",
-            txid
-        );
-        ensure!(
-            tx.find_out(&config.anchoring_out_script()).is_some(),
-            "Funding transaction {} is not suitable.",
-            txid
-        );
-        Ok(())
+        let stats = schema.anchoring_stats();
+        let average = |total: u64| total.checked_div(stats.transactions_count).unwrap_or(0);
+
+        let current_height = self.0.data().for_core().height();
+        let current_lag = schema
+            .latest_anchored_height()
+            .map(|height| current_height.0.saturating_sub(height.0));
+        Ok(AnchoringStatistics {
+            transactions_count: stats.transactions_count,
+            total_fee: stats.total_fee,
+            average_fee: average(stats.total_fee),
+            average_size: average(stats.total_size),
+            average_latency: stats
+                .total_latency
+                .checked_div(stats.final_transactions_count)
+                .unwrap_or(0),
+            current_lag,
+            latest_final_transaction: schema.latest_final_transaction(),
+            balance: schema.anchoring_balance(),
+        })
     }
 
-    fn transaction_proof(&self, tx_index: u64) -> TransactionProof {
-        let index_proof = self
-            .0
-            .data()
-            .proof_for_service_index("transactions_chain")
-            .unwrap();
-        let transaction_proof = Schema::new(self.0.service_data())
+    async fn status(self) -> api::Result<AnchoringStatus> {
+        let schema = Schema::new(self.0.service_data());
+        Ok(AnchoringStatus {
+            config: schema.actual_config(),
+            latest_anchored_height: schema.latest_anchored_height(),
+            latest_txid: schema.transactions_chain.last().map(|tx| tx.id()),
+            following_anchoring_height: schema.following_anchoring_height(),
+            transition_in_progress: schema.actual_state().is_transition(),
+            remaining_funds: schema.anchoring_balance(),
+        })
+    }
+
+    async fn transaction_ids(self, index: u64) -> api::Result<Option<AnchoringTransactionIds>> {
+        Ok(Schema::new(self.0.service_data())
             .transactions_chain
-            .get_proof(tx_index);
+            .get(index)
+            .map(|tx| AnchoringTransactionIds {
+                index,
+                txid: tx.id(),
+                wtxid: tx.wtxid(),
+            }))
+    }
 
-        TransactionProof {
-            index_proof,
-            transaction_proof,
+    async fn transactions(
+        self,
+        query: TransactionsRangeQuery,
+    ) -> api::Result<AnchoringTransactionsPage> {
+        if query.count > TransactionsRangeQuery::MAX_COUNT {
+            return Err(ApiErrorKind::BadRequest
+                .error()
+                .title("Too many transactions requested")
+                .detail(format!(
+                    "Count should not exceed {}",
+                    TransactionsRangeQuery::MAX_COUNT
+                )));
         }
+
+        let transactions_chain = Schema::new(self.0.service_data()).transactions_chain;
+        let total = transactions_chain.len();
+        let to = query.from.saturating_add(query.count).min(total);
+        let transactions = (query.from..to)
+            .map(|index| transactions_chain.get(index).unwrap())
+            .collect();
+        Ok(AnchoringTransactionsPage {
+            total,
+            transactions,
+        })
     }
-}
 
-// Public API implementation
-impl ApiImpl {
-    async fn actual_address(self) -> api::Result<btc::Address> {
-        Ok(Schema::new(self.0.service_data())
-            .actual_config()
-            .anchoring_address())
+    async fn nearest_anchoring_transaction(
+        self,
+        height: Height,
+    ) -> api::Result<Option<TransactionProof>> {
+        Ok(self
+            .nearest_anchoring_transaction_index(height)
+            .map(|(index, _)| self.transaction_proof(index)))
     }
 
-    async fn following_address(self) -> api::Result<Option<btc::Address>> {
+    async fn transaction_index_by_height(
+        self,
+        height: Height,
+    ) -> api::Result<Option<AnchoredHeightIndex>> {
+        Ok(self
+            .nearest_anchoring_transaction_index(height)
+            .map(|(index, anchored_height)| AnchoredHeightIndex {
+                index,
+                anchored_height,
+            }))
+    }
+
+    async fn find_transaction_by_wtxid(
+        self,
+        wtxid: btc::Sha256d,
+    ) -> api::Result<Option<TransactionProof>> {
         Ok(Schema::new(self.0.service_data())
-            .following_config()
-            .map(|config| config.anchoring_address()))
+            .transaction_index_by_wtxid(&wtxid)
+            .map(|index| self.transaction_proof(index)))
     }
 
-    async fn find_transaction(self, height: Option<Height>) -> api::Result<TransactionProof> {
-        let anchoring_schema = Schema::new(self.0.service_data());
-        let tx_chain = anchoring_schema.transactions_chain;
+    async fn transaction_inclusion_proof(
+        self,
+        index: u64,
+    ) -> api::Result<Option<TransactionInclusionProof>> {
+        Ok(self.inclusion_proof(index))
+    }
 
-        if tx_chain.is_empty() {
-            return Ok(self.transaction_proof(0));
+    async fn funding_inclusion_proof(
+        self,
+        txid: btc::Sha256d,
+    ) -> api::Result<Option<FundingInclusionProof>> {
+        let schema = Schema::new(self.0.service_data());
+        let merkle_block = if let Some(merkle_block) = schema.funding_inclusion_proof(&txid) {
+            merkle_block
+        } else {
+            return Ok(None);
+        };
+        let (transaction, is_spent) =
+            if let Some(transaction) = schema.spent_funding_transactions.get(&txid) {
+                (transaction, true)
+            } else if let Some(transaction) = schema
+                .unspent_funding_transaction()
+                .filter(|tx| tx.id() == txid)
+            {
+                (transaction, false)
+            } else {
+                return Ok(None);
+            };
+        let block_height = schema.bitcoin_header_height(&merkle_block.header().block_hash());
+        let tip_height = schema.bitcoin_chain_tip().map(|(height, _)| height);
+        Ok(block_height
+            .and_then(|block_height| tip_height.map(|tip_height| (block_height, tip_height)))
+            .map(|(block_height, tip_height)| FundingInclusionProof {
+                transaction,
+                block_height,
+                merkle_block,
+                confirmations: tip_height + 1 - block_height,
+                is_spent,
+            }))
+    }
+
+    async fn backup(self) -> api::Result<AnchoringArchive> {
+        let schema = Schema::new(self.0.service_data());
+        Ok(AnchoringArchive::new(AnchoringBackup::export(&schema)))
+    }
+
+    async fn cost_report(self, query: CostReportQuery) -> api::Result<AnchoringCostReport> {
+        let schema = Schema::new(self.0.service_data());
+        let mut transactions = Vec::new();
+        for (index, tx) in schema.transactions_chain.iter().enumerate() {
+            let anchored_height = tx
+                .anchoring_payload()
+                .ok_or_else(|| {
+                    ApiErrorKind::Internal.with_cause(anyhow!(
+                        "Unable to find payload in anchoring transaction {}",
+                        tx.id()
+                    ))
+                })?
+                .block_height;
+            if !query.contains_height(anchored_height) {
+                continue;
+            }
+
+            let txid = tx.id();
+            let block_time = schema
+                .inclusion_proof(&txid)
+                .map(|proof| proof.header().0.time);
+            if !query.contains_time(block_time) {
+                continue;
+            }
+
+            let fee = schema
+                .spent_transactions(&tx)
+                .and_then(|inputs| tx.fee(&inputs))
+                .ok_or_else(|| {
+                    ApiErrorKind::Internal.with_cause(anyhow!(
+                        "Unable to compute fee of anchoring transaction {}",
+                        txid
+                    ))
+                })?;
+            transactions.push(AnchoringCost {
+                index: index as u64,
+                txid,
+                anchored_height,
+                fee,
+                size: tx.size(),
+                block_time,
+            });
         }
 
-        let tx_index = if let Some(height) = height {
-            // Handmade binary search.
-            let f = |index| -> Ordering {
-                // index is always in [0, size), that means index is >= 0 and < size.
-                // index >= 0: by definition
-                // index < size: index = size / 2 + size / 4 + size / 8 ...
-                let other = tx_chain
-                    .get(index)
-                    .unwrap()
-                    .anchoring_payload()
-                    .unwrap()
-                    .block_height;
-                other.cmp(&height)
-            };
+        Ok(AnchoringCostReport {
+            total_fee: transactions.iter().map(|tx| tx.fee).sum(),
+            transactions,
+        })
+    }
+
+    async fn anchoring_gaps(self) -> api::Result<Vec<AnchoringGap>> {
+        let schema = Schema::new(self.0.service_data());
+        let schedule = schema.anchoring_schedule();
+        let gaps = schema
+            .anchoring_delays
+            .iter()
+            .map(|delay| {
+                let finalized_at = Height(delay.finalized_at);
+                let mut height = Height(delay.anchored_height);
+                let mut skipped_heights = Vec::new();
+                loop {
+                    height = schedule.following_anchoring_height(height);
+                    if height >= finalized_at {
+                        break;
+                    }
+                    skipped_heights.push(height);
+                }
+                AnchoringGap {
+                    anchored_height: Height(delay.anchored_height),
+                    finalized_at,
+                    skipped_heights,
+                    reason: delay.reason(),
+                    delay,
+                }
+            })
+            .collect();
+        Ok(gaps)
+    }
+
+    async fn funding_history(self) -> api::Result<FundingHistory> {
+        let schema = Schema::new(self.0.service_data());
+        let anchoring_scripts = schema
+            .config_history()
+            .into_iter()
+            .map(|change| change.config.anchoring_out_script())
+            .collect::<Vec<_>>();
+        let funding_transactions = schema
+            .spent_funding_transactions
+            .values()
+            .map(|tx| (tx, true))
+            .chain(schema.unspent_funding_transaction().map(|tx| (tx, false)));
 
-            let mut base = 0;
-            let mut size = tx_chain.len();
-            while size > 1 {
-                let half = size / 2;
-                let mid = base + half;
-                let cmp = f(mid);
-                base = if cmp == Greater { base } else { mid };
-                size -= half;
+        let mut history = FundingHistory::default();
+        for (tx, is_spent) in funding_transactions {
+            let amount =
+                tx.0.output
+                    .iter()
+                    .filter(|out| anchoring_scripts.contains(&out.script_pubkey))
+                    .map(|out| out.value)
+                    .sum();
+            history.total_received += amount;
+            if is_spent {
+                history.total_spent += amount;
             }
-            // Don't forget to check base value.
-            let cmp = f(base);
-            if cmp == Equal {
-                base
+            history.transactions.push(FundingTransactionInfo {
+                txid: tx.id(),
+                amount,
+                is_spent,
+                spent_by: schema.funding_transaction_spender(&tx.id()),
+            });
+        }
+        Ok(history)
+    }
+
+    async fn input_signatures(
+        self,
+        txid: btc::Sha256d,
+        input: u32,
+    ) -> api::Result<Vec<NodeInputSignature>> {
+        Ok(Schema::new(self.0.service_data())
+            .input_signatures(&TxInputId::new(txid, input))
+            .0
+            .into_iter()
+            .map(|(anchoring_node, input_signature)| NodeInputSignature {
+                anchoring_node,
+                input_signature,
+            })
+            .collect())
+    }
+
+    async fn transition_progress(self) -> api::Result<Option<TransitionProgress>> {
+        let schema = Schema::new(self.0.service_data());
+        let actual_state = schema.actual_state();
+        if let Some(following_config) = actual_state.following_config().cloned() {
+            // Funds on the previous address consist of the output of the latest anchoring
+            // transaction and of the unspent funding transaction.
+            let old_script = actual_state.actual_config().anchoring_out_script();
+            let remaining_funds = schema
+                .transactions_chain
+                .last()
+                .into_iter()
+                .chain(schema.unspent_funding_transaction())
+                .filter_map(|tx| tx.find_out(&old_script).map(|(_, out)| out.value))
+                .sum();
+
+            let signing_session = self.actual_signing_session();
+            let stage = if signing_session.is_some() {
+                TransitionStage::Signing
             } else {
-                cmp::min(base + (cmp == Less) as u64, tx_chain.len() - 1)
-            }
+                TransitionStage::Pending
+            };
+            return Ok(Some(TransitionProgress {
+                following_config,
+                stage,
+                txid: signing_session.as_ref().map(|session| session.txid),
+                index: None,
+                signing_session,
+                remaining_funds,
+            }));
+        }
+
+        // The first address change corresponds to the initial anchoring address.
+        let index = match schema.address_changes.last() {
+            Some(index) if index > 0 => index,
+            _ => return Ok(None),
+        };
+        let txid = schema.transactions_chain.get(index).unwrap().id();
+        let stage = if schema.latest_final_transaction() >= Some(index) {
+            TransitionStage::Confirmed
+        } else if schema.inclusion_proof(&txid).is_some() {
+            TransitionStage::Included
         } else {
-            tx_chain.len() - 1
+            TransitionStage::Signed
         };
+        Ok(Some(TransitionProgress {
+            following_config: actual_state.actual_config().clone(),
+            stage,
+            txid: Some(txid),
+            index: Some(index),
+            signing_session: None,
+            remaining_funds: 0,
+        }))
+    }
 
-        Ok(self.transaction_proof(tx_index))
+    async fn transition_state(self) -> api::Result<TransitionState> {
+        Ok(Schema::new(self.0.service_data()).transition_state())
     }
 
-    async fn config(self) -> api::Result<Config> {
-        self.actual_config().map_err(api::Error::internal)
+    async fn address_history(self) -> api::Result<Vec<AnchoringAddressRange>> {
+        let schema = Schema::new(self.0.service_data());
+        let network = schema.actual_config().network;
+
+        // Consecutive anchoring transactions which send funds to the same anchoring
+        // output form the range of the corresponding address.
+        let mut ranges: Vec<(btc::Transaction, u64, u64)> = Vec::new();
+        for (index, tx) in (0..).zip(schema.transactions_chain.iter()) {
+            match ranges.last_mut() {
+                Some((first_tx, _, last_transaction))
+                    if first_tx.0.output[0].script_pubkey == tx.0.output[0].script_pubkey =>
+                {
+                    *last_transaction = index;
+                }
+                _ => ranges.push((tx, index, index)),
+            }
+        }
+
+        let mut history = Vec::with_capacity(ranges.len());
+        for (tx, first_transaction, last_transaction) in ranges {
+            let address = bitcoin::Address::from_script(&tx.0.output[0].script_pubkey, network)
+                .ok_or_else(|| {
+                    ApiErrorKind::Internal.with_cause(anyhow!(
+                        "Unable to derive address from anchoring transaction {}",
+                        tx.id()
+                    ))
+                })?;
+            history.push(AnchoringAddressRange {
+                address: address.into(),
+                first_transaction,
+                last_transaction,
+            });
+        }
+        Ok(history)
     }
 }
 
@@ -398,7 +2189,8 @@ impl ApiImpl {
     async fn sign_input(self, sign_input: SignInput) -> Result<Hash, api::Error> {
         // Verify Bitcoin signature.
         self.verify_sign_input(&sign_input).map_err(|e| {
-            api::Error::bad_request()
+            ApiErrorKind::BadRequest
+                .error()
                 .title("Sign input request verification has failed")
                 .detail(e.to_string())
         })?;
@@ -406,12 +2198,44 @@ impl ApiImpl {
         self.broadcaster()?
             .sign_input((), sign_input)
             .await
-            .map_err(|e| api::Error::internal(e).title("Sign input request failed"))
+            .map_err(|e| {
+                ApiErrorKind::Broadcast
+                    .with_cause(e)
+                    .title("Sign input request failed")
+            })
+    }
+
+    async fn sign_inputs(self, sign_inputs: SignInputs) -> Result<Hash, api::Error> {
+        // Verify Bitcoin signatures.
+        if sign_inputs.inputs.is_empty() {
+            return Err(ApiErrorKind::BadRequest
+                .error()
+                .title("Sign inputs request verification has failed")
+                .detail("No input signatures in the request"));
+        }
+        for sign_input in &sign_inputs.inputs {
+            self.verify_sign_input(sign_input).map_err(|e| {
+                ApiErrorKind::BadRequest
+                    .error()
+                    .title("Sign inputs request verification has failed")
+                    .detail(e.to_string())
+            })?;
+        }
+
+        self.broadcaster()?
+            .sign_inputs((), sign_inputs)
+            .await
+            .map_err(|e| {
+                ApiErrorKind::Broadcast
+                    .with_cause(e)
+                    .title("Sign inputs request failed")
+            })
     }
 
     async fn add_funds(self, transaction: btc::Transaction) -> Result<Hash, api::Error> {
         self.verify_funding_tx(&transaction).map_err(|e| {
-            api::Error::bad_request()
+            ApiErrorKind::BadRequest
+                .error()
                 .title("Funding tx verification has failed")
                 .detail(e.to_string())
         })?;
@@ -419,18 +2243,71 @@ impl ApiImpl {
         self.broadcaster()?
             .add_funds((), AddFunds { transaction })
             .await
-            .map_err(|e| api::Error::internal(e).title("Add funds request failed"))
+            .map_err(|e| {
+                ApiErrorKind::Broadcast
+                    .with_cause(e)
+                    .title("Add funds request failed")
+            })
     }
 
     async fn anchoring_proposal(self) -> Result<AnchoringProposalState, api::Error> {
         let core_schema = self.0.data().for_core();
         let anchoring_schema = Schema::new(self.0.service_data());
+        if anchoring_schema.is_frozen() {
+            return Ok(AnchoringProposalState::Frozen);
+        }
 
         AnchoringProposalState::try_from_proposal(
             anchoring_schema.actual_proposed_anchoring_transaction(core_schema),
         )
     }
 
+    async fn anchoring_proposal_psbt(self) -> api::Result<Option<btc::Psbt>> {
+        let anchoring_schema = Schema::new(self.0.service_data());
+        if anchoring_schema.is_frozen() {
+            return Ok(None);
+        }
+
+        let proposal =
+            anchoring_schema.actual_proposed_anchoring_transaction(self.0.data().for_core());
+        let (proposal, inputs) = match proposal {
+            Some(Ok(proposal)) => proposal,
+            _ => return Ok(None),
+        };
+        let redeem_script = anchoring_schema.actual_config().redeem_script();
+        btc::Psbt::from_proposal(&proposal, &inputs, &redeem_script)
+            .map(Some)
+            .map_err(|e| {
+                ApiErrorKind::Internal
+                    .with_cause(e)
+                    .title("Unable to create PSBT")
+            })
+    }
+
+    async fn import_psbt(self, psbt: btc::Psbt) -> Result<Vec<Hash>, api::Error> {
+        let sign_inputs = self.verify_psbt(&psbt).map_err(|e| {
+            ApiErrorKind::BadRequest
+                .error()
+                .title("PSBT verification has failed")
+                .detail(e.to_string())
+        })?;
+
+        let mut hashes = Vec::with_capacity(sign_inputs.len());
+        for sign_input in sign_inputs {
+            let hash = self
+                .broadcaster()?
+                .sign_input((), sign_input)
+                .await
+                .map_err(|e| {
+                    ApiErrorKind::Broadcast
+                        .with_cause(e)
+                        .title("Sign input request failed")
+                })?;
+            hashes.push(hash);
+        }
+        Ok(hashes)
+    }
+
     async fn transaction_with_index(self, index: u64) -> api::Result<Option<btc::Transaction>> {
         Ok(Schema::new(self.0.service_data())
             .transactions_chain
@@ -443,6 +2320,142 @@ impl ApiImpl {
             .len()
             .into())
     }
+
+    async fn signing_session(self) -> api::Result<Option<SigningSession>> {
+        Ok(self.actual_signing_session())
+    }
+
+    async fn key_replacement_proposal(
+        self,
+        query: KeyReplacementQuery,
+    ) -> api::Result<ConfigPropose> {
+        let schema = Schema::new(self.0.service_data());
+        // Replacement is based on the latest configuration, which may be not actual yet.
+        let mut config = schema
+            .following_config()
+            .unwrap_or_else(|| schema.actual_config());
+        let keys = config
+            .anchoring_keys
+            .iter_mut()
+            .find(|keys| keys.bitcoin_key == query.bitcoin_key)
+            .ok_or_else(|| {
+                ApiErrorKind::BadRequest
+                    .error()
+                    .title("Unknown anchoring key")
+                    .detail(format!(
+                        "There is no anchoring node with the Bitcoin key {}",
+                        query.bitcoin_key
+                    ))
+            })?;
+        keys.bitcoin_key = query.new_bitcoin_key;
+        config.sort_anchoring_keys();
+        if !config.excluded_keys.contains(&query.bitcoin_key) {
+            config.excluded_keys.push(query.bitcoin_key);
+        }
+        config.validate().map_err(|e| {
+            ApiErrorKind::BadRequest
+                .error()
+                .title("Invalid key replacement")
+                .detail(e.to_string())
+        })?;
+
+        Ok(
+            ConfigPropose::new(query.configuration_number, query.actual_from)
+                .service_config(self.0.instance().id, config),
+        )
+    }
+
+    async fn report_inclusion_proof(self, proof: btc::MerkleBlock) -> Result<Hash, api::Error> {
+        if proof.matched_transactions().is_none() {
+            return Err(ApiErrorKind::BadRequest
+                .error()
+                .title("Inclusion proof verification has failed")
+                .detail("Merkle proof doesn't match the block header"));
+        }
+
+        self.broadcaster()?
+            .report_inclusion_proof((), ReportInclusionProof { proof })
+            .await
+            .map_err(|e| {
+                ApiErrorKind::Broadcast
+                    .with_cause(e)
+                    .title("Report inclusion proof request failed")
+            })
+    }
+
+    async fn vote_fee_rate(self, vote: VoteFeeRate) -> Result<Hash, api::Error> {
+        if vote.fee_rate < Config::MIN_TX_FEE {
+            return Err(ApiErrorKind::BadRequest
+                .error()
+                .title("Unsuitable fee rate")
+                .detail(format!(
+                    "Fee rate should be at least {} satoshis per byte",
+                    Config::MIN_TX_FEE
+                )));
+        }
+
+        self.broadcaster()?
+            .vote_fee_rate((), vote)
+            .await
+            .map_err(|e| {
+                ApiErrorKind::Broadcast
+                    .with_cause(e)
+                    .title("Vote fee rate request failed")
+            })
+    }
+
+    async fn accelerate_anchoring(self, vote: AccelerateAnchoring) -> Result<Hash, api::Error> {
+        let latest_txid = Schema::new(self.0.service_data())
+            .transactions_chain
+            .last()
+            .map(|tx| tx.id());
+        if latest_txid != Some(vote.txid) {
+            return Err(ApiErrorKind::BadRequest
+                .error()
+                .title("Unsuitable accelerated transaction")
+                .detail("Accelerated transaction is not the latest anchoring transaction"));
+        }
+        if vote.fee_rate < Config::MIN_TX_FEE {
+            return Err(ApiErrorKind::BadRequest
+                .error()
+                .title("Unsuitable fee rate")
+                .detail(format!(
+                    "Fee rate should be at least {} satoshis per byte",
+                    Config::MIN_TX_FEE
+                )));
+        }
+
+        self.broadcaster()?
+            .accelerate_anchoring((), vote)
+            .await
+            .map_err(|e| {
+                ApiErrorKind::Broadcast
+                    .with_cause(e)
+                    .title("Accelerate anchoring request failed")
+            })
+    }
+
+    async fn recover_anchoring(self, vote: RecoverAnchoring) -> Result<Hash, api::Error> {
+        let latest_txid = Schema::new(self.0.service_data())
+            .transactions_chain
+            .last()
+            .map(|tx| tx.id());
+        if latest_txid != Some(vote.txid) {
+            return Err(ApiErrorKind::BadRequest
+                .error()
+                .title("Unsuitable recovered transaction")
+                .detail("Abandoned transaction is not the latest anchoring transaction"));
+        }
+
+        self.broadcaster()?
+            .recover_anchoring((), vote)
+            .await
+            .map_err(|e| {
+                ApiErrorKind::Broadcast
+                    .with_cause(e)
+                    .title("Recover anchoring request failed")
+            })
+    }
 }
 
 /// Query parameters for the find transaction request.
@@ -459,37 +2472,390 @@ pub struct IndexQuery {
     pub index: u64,
 }
 
-pub(crate) fn wire(builder: &mut ServiceApiBuilder) {
+/// Query parameters for the anchoring transactions chain slice request.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct TransactionsRangeQuery {
+    /// Index of the first anchoring transaction.
+    pub from: u64,
+    /// Maximum number of anchoring transactions.
+    pub count: u64,
+}
+
+impl TransactionsRangeQuery {
+    /// Maximum number of anchoring transactions returned by the single request.
+    pub const MAX_COUNT: u64 = 1_000;
+}
+
+/// Query parameters for the anchoring transaction lookup by the witness identifier.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+pub struct WtxidQuery {
+    /// Bitcoin witness transaction identifier.
+    pub wtxid: btc::Sha256d,
+}
+
+/// Query parameters for the funding transaction lookup by the identifier.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+pub struct TxidQuery {
+    /// Bitcoin transaction identifier.
+    pub txid: btc::Sha256d,
+}
+
+/// Query parameters for the requests related to the anchoring transaction input.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+pub struct TxInputQuery {
+    /// Bitcoin transaction identifier.
+    pub txid: btc::Sha256d,
+    /// Index of the transaction input.
+    pub input: u32,
+}
+
+/// Query parameters for the anchoring transaction lookup by the Exonum block hash.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+pub struct BlockHashQuery {
+    /// Hash of the Exonum block.
+    pub block_hash: Hash,
+}
+
+/// Query parameters for the requests related to the Exonum block with the given height.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+pub struct BlockHeightQuery {
+    /// Height of the Exonum block.
+    pub height: Height,
+}
+
+/// Query parameters for the Exonum transaction proof request.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+pub struct TransactionHashQuery {
+    /// Hash of the Exonum transaction.
+    pub tx_hash: Hash,
+}
+
+/// Query parameters for the emergency replacement of the anchoring node Bitcoin key.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct KeyReplacementQuery {
+    /// Compromised Bitcoin key of the anchoring node.
+    pub bitcoin_key: btc::PublicKey,
+    /// New Bitcoin key of the anchoring node.
+    pub new_bitcoin_key: btc::PublicKey,
+    /// Actual configuration number of the supervisor.
+    pub configuration_number: u64,
+    /// Height from which the new configuration becomes actual.
+    pub actual_from: Height,
+}
+
+/// Query parameters for the anchoring cost report request.
+///
+/// All bounds are inclusive, missing bounds are not applied.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+pub struct CostReportQuery {
+    /// Lowest height of the anchored Exonum block.
+    pub from_height: Option<Height>,
+    /// Highest height of the anchored Exonum block.
+    pub to_height: Option<Height>,
+    /// Earliest time of the Bitcoin block with the anchoring transaction in seconds
+    /// since the Unix epoch.
+    pub from_time: Option<u32>,
+    /// Latest time of the Bitcoin block with the anchoring transaction in seconds
+    /// since the Unix epoch.
+    pub to_time: Option<u32>,
+}
+
+impl CostReportQuery {
+    fn contains_height(&self, height: Height) -> bool {
+        self.from_height.unwrap_or(Height(0)) <= height
+            && height <= self.to_height.unwrap_or(Height(u64::MAX))
+    }
+
+    fn contains_time(&self, time: Option<u32>) -> bool {
+        if self.from_time.is_none() && self.to_time.is_none() {
+            return true;
+        }
+        match time {
+            Some(time) => {
+                self.from_time.unwrap_or(0) <= time && time <= self.to_time.unwrap_or(u32::MAX)
+            }
+            None => false,
+        }
+    }
+}
+
+/// Limits applied to the requests of the anchoring service HTTP API.
+///
+/// Note that the CORS policy is not a part of this configuration, since it is
+/// applied by the node to the whole public API, see the `api.public_allow_origin`
+/// option of the node configuration.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+pub struct ApiConfig {
+    /// Maximum size of the JSON-encoded request payload of the `POST` endpoints
+    /// in bytes.
+    #[serde(default)]
+    pub max_body_size: Option<usize>,
+    /// Maximum time in milliseconds to process a single request.
+    #[serde(default)]
+    pub request_timeout: Option<u64>,
+}
+
+impl ApiConfig {
+    async fn limit<I>(self, response: impl Future<Output = api::Result<I>>) -> api::Result<I> {
+        match self.request_timeout {
+            Some(timeout) => tokio::time::timeout(Duration::from_millis(timeout), response)
+                .await
+                .map_err(|_| {
+                    ApiErrorKind::Timeout
+                        .error()
+                        .title("Request timed out")
+                        .detail(format!("Request has not been processed in {} ms", timeout))
+                })?,
+            None => response.await,
+        }
+    }
+
+    async fn limit_body<Q, I, F, R>(self, query: Q, handler: F) -> api::Result<I>
+    where
+        Q: SerializeTrait,
+        F: FnOnce(Q) -> R,
+        R: Future<Output = api::Result<I>>,
+    {
+        if let Some(max_body_size) = self.max_body_size {
+            let body_size = serde_json::to_vec(&query)
+                .map_err(|e| ApiErrorKind::Internal.with_cause(e))?
+                .len();
+            if body_size > max_body_size {
+                return Err(ApiErrorKind::PayloadTooLarge
+                    .error()
+                    .title("Request payload is too large")
+                    .detail(format!(
+                        "Request payload size {} exceeds the limit of {} bytes",
+                        body_size, max_body_size
+                    )));
+            }
+        }
+        self.limit(handler(query)).await
+    }
+}
+
+pub(crate) fn wire(
+    builder: &mut ServiceApiBuilder,
+    api_signing_keypair: Option<&KeyPair>,
+    config: ApiConfig,
+) {
     builder
         .public_scope()
-        .endpoint("address/actual", |state, _query: ()| {
-            ApiImpl(state).actual_address()
+        .endpoint("address/actual", move |state, _query: ()| {
+            config.limit(ApiImpl(state).actual_address())
+        })
+        .endpoint("address/following", move |state, _query: ()| {
+            config.limit(ApiImpl(state).following_address())
+        })
+        .endpoint("address/history", move |state, _query: ()| {
+            config.limit(ApiImpl(state).address_history())
+        })
+        .endpoint(
+            "find-transaction",
+            move |state, query: FindTransactionQuery| {
+                config.limit(ApiImpl(state).find_transaction(query.height))
+            },
+        )
+        .endpoint("transaction-ids", move |state, query: IndexQuery| {
+            config.limit(ApiImpl(state).transaction_ids(query.index))
+        })
+        .endpoint(
+            "transactions",
+            move |state, query: TransactionsRangeQuery| {
+                config.limit(ApiImpl(state).transactions(query))
+            },
+        )
+        .endpoint(
+            "nearest-anchoring-transaction",
+            move |state, query: BlockHeightQuery| {
+                config.limit(ApiImpl(state).nearest_anchoring_transaction(query.height))
+            },
+        )
+        .endpoint(
+            "transaction-index-by-height",
+            move |state, query: BlockHeightQuery| {
+                config.limit(ApiImpl(state).transaction_index_by_height(query.height))
+            },
+        )
+        .endpoint(
+            "find-transaction-by-wtxid",
+            move |state, query: WtxidQuery| {
+                config.limit(ApiImpl(state).find_transaction_by_wtxid(query.wtxid))
+            },
+        )
+        .endpoint(
+            "find-transaction-by-block-hash",
+            move |state, query: BlockHashQuery| {
+                config.limit(ApiImpl(state).find_transaction_by_block_hash(query.block_hash))
+            },
+        )
+        .endpoint(
+            "exonum-transaction-proof",
+            move |state, query: TransactionHashQuery| {
+                config.limit(ApiImpl(state).exonum_transaction_proof(query.tx_hash))
+            },
+        )
+        .endpoint(
+            "block-header-proof",
+            move |state, query: BlockHeightQuery| {
+                config.limit(ApiImpl(state).block_header_proof(query.height))
+            },
+        )
+        .endpoint(
+            "transaction-inclusion-proof",
+            move |state, query: IndexQuery| {
+                config.limit(ApiImpl(state).transaction_inclusion_proof(query.index))
+            },
+        )
+        .endpoint("funding-inclusion-proof", move |state, query: TxidQuery| {
+            config.limit(ApiImpl(state).funding_inclusion_proof(query.txid))
+        })
+        .endpoint("funding-history", move |state, _query: ()| {
+            config.limit(ApiImpl(state).funding_history())
+        })
+        .endpoint("input-signatures", move |state, query: TxInputQuery| {
+            config.limit(ApiImpl(state).input_signatures(query.txid, query.input))
         })
-        .endpoint("address/following", |state, _query: ()| {
-            ApiImpl(state).following_address()
+        .endpoint("config", move |state, _query: ()| {
+            config.limit(ApiImpl(state).config())
         })
-        .endpoint("find-transaction", |state, query: FindTransactionQuery| {
-            ApiImpl(state).find_transaction(query.height)
+        .endpoint("config-history", move |state, _query: ()| {
+            config.limit(ApiImpl(state).config_history())
         })
-        .endpoint("config", |state, _query: ()| ApiImpl(state).config());
+        .endpoint("proposal-participation", move |state, _query: ()| {
+            config.limit(ApiImpl(state).proposal_participation())
+        })
+        .endpoint("signing-session", move |state, _query: ()| {
+            config.limit(ApiImpl(state).signing_session())
+        })
+        .endpoint("signing-stats", move |state, _query: ()| {
+            config.limit(ApiImpl(state).signing_stats())
+        })
+        .endpoint("stats", move |state, _query: ()| {
+            config.limit(ApiImpl(state).stats())
+        })
+        .endpoint("status", move |state, _query: ()| {
+            config.limit(ApiImpl(state).status())
+        })
+        .endpoint("backup", move |state, _query: ()| {
+            config.limit(ApiImpl(state).backup())
+        })
+        .endpoint("cost-report", move |state, query: CostReportQuery| {
+            config.limit(ApiImpl(state).cost_report(query))
+        })
+        .endpoint("anchoring-gaps", move |state, _query: ()| {
+            config.limit(ApiImpl(state).anchoring_gaps())
+        })
+        .endpoint("transition-progress", move |state, _query: ()| {
+            config.limit(ApiImpl(state).transition_progress())
+        })
+        .endpoint("transition-state", move |state, _query: ()| {
+            config.limit(ApiImpl(state).transition_state())
+        });
+    if let Some(keypair) = api_signing_keypair {
+        wire_signed(builder, keypair.clone(), config);
+    }
     builder
         .private_scope()
-        .endpoint_mut("sign-input", |state, query: SignInput| {
-            ApiImpl(state).sign_input(query)
+        .endpoint_mut("sign-input", move |state, query: SignInput| {
+            config.limit_body(query, |query| ApiImpl(state).sign_input(query))
         })
-        .endpoint_mut("add-funds", |state, query: btc::Transaction| {
-            ApiImpl(state).add_funds(query)
+        .endpoint_mut("sign-inputs", move |state, query: SignInputs| {
+            config.limit_body(query, |query| ApiImpl(state).sign_inputs(query))
         })
-        .endpoint("anchoring-proposal", |state, _query: ()| {
-            ApiImpl(state).anchoring_proposal()
+        .endpoint_mut("add-funds", move |state, query: btc::Transaction| {
+            config.limit_body(query, |query| ApiImpl(state).add_funds(query))
         })
-        .endpoint("config", |state, _query: ()| ApiImpl(state).config())
-        .endpoint("transaction", |state, query: IndexQuery| {
-            ApiImpl(state).transaction_with_index(query.index)
+        .endpoint("anchoring-proposal", move |state, _query: ()| {
+            config.limit(ApiImpl(state).anchoring_proposal())
         })
-        .endpoint("transactions-count", |state, _query: ()| {
-            ApiImpl(state).transactions_count()
-        });
+        .endpoint("anchoring-proposal-psbt", move |state, _query: ()| {
+            config.limit(ApiImpl(state).anchoring_proposal_psbt())
+        })
+        .endpoint_mut("import-psbt", move |state, query: btc::Psbt| {
+            config.limit_body(query, |query| ApiImpl(state).import_psbt(query))
+        })
+        .endpoint("config", move |state, _query: ()| {
+            config.limit(ApiImpl(state).config())
+        })
+        .endpoint("following-config", move |state, _query: ()| {
+            config.limit(ApiImpl(state).following_config())
+        })
+        .endpoint("transaction", move |state, query: IndexQuery| {
+            config.limit(ApiImpl(state).transaction_with_index(query.index))
+        })
+        .endpoint("transactions-count", move |state, _query: ()| {
+            config.limit(ApiImpl(state).transactions_count())
+        })
+        .endpoint(
+            "transactions",
+            move |state, query: TransactionsRangeQuery| {
+                config.limit(ApiImpl(state).transactions(query))
+            },
+        )
+        .endpoint("stats", move |state, _query: ()| {
+            config.limit(ApiImpl(state).stats())
+        })
+        .endpoint("signing-session", move |state, _query: ()| {
+            config.limit(ApiImpl(state).signing_session())
+        })
+        .endpoint_mut(
+            "report-inclusion-proof",
+            move |state, query: btc::MerkleBlock| {
+                config.limit_body(query, |query| ApiImpl(state).report_inclusion_proof(query))
+            },
+        )
+        .endpoint_mut("vote-fee-rate", move |state, query: VoteFeeRate| {
+            config.limit_body(query, |query| ApiImpl(state).vote_fee_rate(query))
+        })
+        .endpoint_mut(
+            "accelerate-anchoring",
+            move |state, query: AccelerateAnchoring| {
+                config.limit_body(query, |query| ApiImpl(state).accelerate_anchoring(query))
+            },
+        )
+        .endpoint_mut(
+            "recover-anchoring",
+            move |state, query: RecoverAnchoring| {
+                config.limit_body(query, |query| ApiImpl(state).recover_anchoring(query))
+            },
+        )
+        .endpoint(
+            "key-replacement-proposal",
+            move |state, query: KeyReplacementQuery| {
+                config.limit(ApiImpl(state).key_replacement_proposal(query))
+            },
+        );
+}
+
+fn wire_signed(builder: &mut ServiceApiBuilder, keypair: KeyPair, config: ApiConfig) {
+    let inclusion_keypair = keypair.clone();
+    builder
+        .public_scope()
+        .endpoint(
+            "signed/find-transaction",
+            move |state, query: FindTransactionQuery| {
+                let keypair = keypair.clone();
+                async move {
+                    let proof = config
+                        .limit(ApiImpl(state).find_transaction(query.height))
+                        .await?;
+                    Ok(SignedResponse::new(&proof, &keypair))
+                }
+            },
+        )
+        .endpoint(
+            "signed/transaction-inclusion-proof",
+            move |state, query: IndexQuery| {
+                let keypair = inclusion_keypair.clone();
+                async move {
+                    let proof = config
+                        .limit(ApiImpl(state).transaction_inclusion_proof(query.index))
+                        .await?;
+                    Ok(SignedResponse::new(&proof, &keypair))
+                }
+            },
+        );
 }
 
 impl<T> std::fmt::Debug for dyn PublicApi<Error = T> {
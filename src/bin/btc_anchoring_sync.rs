@@ -0,0 +1,2356 @@
+// Copyright 2019 The Exonum Team
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Sync utility of the anchoring nodes, which signs the anchoring proposals
+//! and keeps the anchoring chain in sync with the Bitcoin network.
+
+use anyhow::{anyhow, bail, ensure};
+use async_trait::async_trait;
+use bitcoincore_rpc::RpcApi;
+use exonum::blockchain::ConsensusConfig;
+use exonum::crypto::{Hash, PublicKey};
+use exonum::helpers::{Height, ValidateInput};
+use exonum::merkledb::{access::Prefixed, BinaryValue, Database, DbOptions, RocksDB};
+#[cfg(feature = "pkcs11")]
+use exonum_btc_anchoring::sync::{Pkcs11Config, Pkcs11Signer};
+use exonum_btc_anchoring::{
+    api::{
+        AnchoringChainLength, AnchoringProofBundle, AnchoringProposalState, AnchoringStatistics,
+        AnchoringTransactionsPage, ApiErrorKind, BlockHeaderProof, BlockHeightQuery, IndexQuery,
+        KeyReplacementQuery, PrivateApi, SigningSession, TransactionsRangeQuery,
+    },
+    blockchain::{
+        backup::AnchoringArchive, verification::verify_anchoring_chain, AccelerateAnchoring,
+        RecoverAnchoring, Schema, SignInput, SignInputs, VoteFeeRate,
+    },
+    btc::{self, EncryptedPrivateKey},
+    config::{AnchoringKeys, Config as AnchoringConfig},
+    sync::{
+        AnchoringChainUpdateTask, AnchoringFundingTask, AnchoringTopUpTask, ApiClientError,
+        AuditRelay, BitcoinNotification, BitcoinNotifications, BitcoindRelay, BitcoindRelayConfig,
+        BroadcastAllRelay, BroadcastQueue, ChainUpdateError, ExternalSpend, ExternalSpendWatchdog,
+        FeeRatePolicy, FundingError, HwiConfig, HwiSigner, PendingTopUp, ProxyConfig,
+        RelayAuditLog, RemoteSignerConfig, SyncMetrics, SyncStateStore, SyncWithBitcoinError,
+        SyncWithBitcoinTask, TopUpError, TopUpPolicy, ZmqNotificationsConfig,
+    },
+};
+use exonum_cli::password::{PassInputMethod, Passphrase, PassphraseUsage};
+use exonum_supervisor::{ConfigPropose, Supervisor};
+use futures::future::{join_all, BoxFuture, FutureExt, Shared};
+use hex::FromHex;
+use hyper::{
+    service::{make_service_fn, service_fn},
+    Body, Method, Request, Response, Server as HttpServer, StatusCode,
+};
+use serde::{de::DeserializeOwned, ser::Serialize};
+use serde_derive::{Deserialize, Serialize};
+use structopt::StructOpt;
+use tokio::{
+    sync::{mpsc, oneshot},
+    time::delay_for,
+};
+use tracing::Instrument;
+
+use std::{
+    collections::HashMap,
+    convert::Infallible,
+    fmt::Display,
+    fs::{self, File},
+    io::prelude::*,
+    net::SocketAddr,
+    path::{Path, PathBuf},
+    sync::{Arc, Mutex},
+    time::{Duration, SystemTime},
+};
+
+/// Error of the anchoring API client.
+#[derive(Debug, thiserror::Error)]
+pub enum ClientError {
+    /// Request has failed before reaching the anchoring API.
+    #[error("{0}")]
+    Http(#[from] reqwest::Error),
+    /// Request has been rejected by the anchoring API.
+    #[error("{} {}: {}", status, body.title, body.detail)]
+    Api {
+        /// HTTP status of the response.
+        status: reqwest::StatusCode,
+        /// Body of the error response.
+        body: ErrorResponse,
+    },
+}
+
+/// Body of the error response of the anchoring API.
+#[derive(Debug, Default, Deserialize)]
+pub struct ErrorResponse {
+    /// Short description of the error.
+    #[serde(default)]
+    pub title: String,
+    /// Detailed description of the error.
+    #[serde(default)]
+    pub detail: String,
+    /// Numeric code of the error kind.
+    #[serde(default)]
+    pub error_code: Option<u8>,
+}
+
+impl ApiClientError for ClientError {
+    fn api_error_kind(&self) -> Option<ApiErrorKind> {
+        match self {
+            ClientError::Http(_) => None,
+            ClientError::Api { body, .. } => body.error_code.and_then(ApiErrorKind::from_code),
+        }
+    }
+}
+
+/// Client implementation for the API of the anchoring service instance.
+#[derive(Debug, Clone)]
+pub struct ApiClient {
+    /// Complete prefix with the port and the anchoring instance name.
+    prefix: String,
+    /// Underlying HTTP client.
+    client: reqwest::Client,
+}
+
+impl ApiClient {
+    /// Create a new anchoring API relay with the specified host and name of instance.
+    /// Hostname should be in form `{http|https}://{address}:{port}`.
+    pub fn new(hostname: impl AsRef<str>, instance_name: impl AsRef<str>) -> Self {
+        Self {
+            prefix: format!(
+                "{}/api/services/{}",
+                hostname.as_ref(),
+                instance_name.as_ref()
+            ),
+            client: reqwest::Client::new(),
+        }
+    }
+
+    fn endpoint(&self, name: impl AsRef<str>) -> String {
+        format!("{}/{}", self.prefix, name.as_ref())
+    }
+
+    async fn response<R>(response: reqwest::Response) -> Result<R, ClientError>
+    where
+        R: DeserializeOwned,
+    {
+        let status = response.status();
+        if status.is_success() {
+            return response.json().await.map_err(From::from);
+        }
+        let body = response.json().await.unwrap_or_default();
+        Err(ClientError::Api { status, body })
+    }
+
+    async fn get<R>(&self, endpoint: &str) -> Result<R, ClientError>
+    where
+        R: DeserializeOwned + Send + 'static,
+    {
+        let response = self.client.get(&self.endpoint(endpoint)).send().await?;
+        Self::response(response).await
+    }
+
+    async fn get_query<Q, R>(&self, endpoint: &str, query: &Q) -> Result<R, ClientError>
+    where
+        Q: Serialize,
+        R: DeserializeOwned + Send + 'static,
+    {
+        let response = self
+            .client
+            .get(&self.endpoint(endpoint))
+            .query(query)
+            .send()
+            .await?;
+        Self::response(response).await
+    }
+
+    async fn post<Q, R>(&self, endpoint: &str, body: &Q) -> Result<R, ClientError>
+    where
+        Q: Serialize,
+        R: DeserializeOwned + Send + 'static,
+    {
+        let response = self
+            .client
+            .post(&self.endpoint(endpoint))
+            .json(&body)
+            .send()
+            .await?;
+        Self::response(response).await
+    }
+}
+
+#[async_trait]
+impl PrivateApi for ApiClient {
+    type Error = ClientError;
+
+    async fn sign_input(&self, sign_input: SignInput) -> Result<Hash, Self::Error> {
+        self.post("sign-input", &sign_input).await
+    }
+
+    async fn sign_inputs(&self, sign_inputs: Vec<SignInput>) -> Result<Hash, Self::Error> {
+        let sign_inputs = SignInputs {
+            inputs: sign_inputs,
+        };
+        self.post("sign-inputs", &sign_inputs).await
+    }
+
+    async fn add_funds(&self, transaction: btc::Transaction) -> Result<Hash, Self::Error> {
+        self.post("add-funds", &transaction).await
+    }
+
+    async fn anchoring_proposal(&self) -> Result<AnchoringProposalState, Self::Error> {
+        self.get("anchoring-proposal").await
+    }
+
+    async fn anchoring_proposal_psbt(&self) -> Result<Option<btc::Psbt>, Self::Error> {
+        self.get("anchoring-proposal-psbt").await
+    }
+
+    async fn import_psbt(&self, psbt: btc::Psbt) -> Result<Vec<Hash>, Self::Error> {
+        self.post("import-psbt", &psbt).await
+    }
+
+    async fn config(&self) -> Result<AnchoringConfig, Self::Error> {
+        self.get("config").await
+    }
+
+    async fn following_config(&self) -> Result<Option<AnchoringConfig>, Self::Error> {
+        self.get("following-config").await
+    }
+
+    async fn transaction_with_index(
+        &self,
+        index: u64,
+    ) -> Result<Option<btc::Transaction>, Self::Error> {
+        self.get_query("transaction", &IndexQuery { index }).await
+    }
+
+    async fn transactions_count(&self) -> Result<AnchoringChainLength, Self::Error> {
+        self.get("transactions-count").await
+    }
+
+    async fn transactions(
+        &self,
+        from: u64,
+        count: u64,
+    ) -> Result<AnchoringTransactionsPage, Self::Error> {
+        self.get_query("transactions", &TransactionsRangeQuery { from, count })
+            .await
+    }
+
+    async fn stats(&self) -> Result<AnchoringStatistics, Self::Error> {
+        self.get("stats").await
+    }
+
+    async fn signing_session(&self) -> Result<Option<SigningSession>, Self::Error> {
+        self.get("signing-session").await
+    }
+
+    async fn report_inclusion_proof(&self, proof: btc::MerkleBlock) -> Result<Hash, Self::Error> {
+        self.post("report-inclusion-proof", &proof).await
+    }
+
+    async fn vote_fee_rate(&self, fee_rate: u64) -> Result<Hash, Self::Error> {
+        self.post("vote-fee-rate", &VoteFeeRate { fee_rate }).await
+    }
+
+    async fn accelerate_anchoring(
+        &self,
+        txid: btc::Sha256d,
+        fee_rate: u64,
+    ) -> Result<Hash, Self::Error> {
+        self.post(
+            "accelerate-anchoring",
+            &AccelerateAnchoring { txid, fee_rate },
+        )
+        .await
+    }
+
+    async fn recover_anchoring(&self, txid: btc::Sha256d) -> Result<Hash, Self::Error> {
+        self.post("recover-anchoring", &RecoverAnchoring { txid })
+            .await
+    }
+
+    async fn key_replacement_proposal(
+        &self,
+        query: KeyReplacementQuery,
+    ) -> Result<ConfigPropose, Self::Error> {
+        self.get_query("key-replacement-proposal", &query).await
+    }
+}
+
+/// Generate initial configuration for the btc anchoring sync utility.
+#[derive(Debug, StructOpt)]
+struct GenerateConfigCommand {
+    /// Path to a sync utility configuration file which will be created after
+    /// running this command.
+    #[structopt(long, short = "o", default_value = "btc_anchoring_sync.toml")]
+    output: PathBuf,
+    /// Anchoring node private API url address.
+    #[structopt(long, short = "e", default_value = "http://localhost:8081")]
+    exonum_private_api: String,
+    /// Bitcoin network type.
+    #[structopt(long, short = "n", default_value = "testnet")]
+    bitcoin_network: bitcoin::Network,
+    /// Name of the anchoring service instance.
+    #[structopt(long, short = "i", default_value = "anchoring")]
+    instance_name: String,
+    /// Bitcoin RPC url.
+    #[structopt(long)]
+    bitcoin_rpc_host: Option<String>,
+    /// Bitcoin RPC username.
+    #[structopt(long)]
+    bitcoin_rpc_user: Option<String>,
+    /// Bitcoin RPC password.
+    #[structopt(long)]
+    bitcoin_rpc_password: Option<String>,
+    /// Interval in seconds between the sync iterations.
+    #[structopt(long, default_value = "5")]
+    sync_interval: u64,
+    /// Path to the keystore file with the encrypted Bitcoin private key which will be
+    /// created after running this command.
+    #[structopt(long, short = "k", default_value = "btc_anchoring_key.toml")]
+    keystore: PathBuf,
+    /// Passphrase entry method for the keystore.
+    ///
+    /// Possible values are: `stdin`, `env{:ENV_VAR_NAME}`, `pass:PASSWORD`.
+    /// Default Value is `stdin`.
+    /// If `ENV_VAR_NAME` is not specified `$EXONUM_MASTER_PASS` is used
+    /// by default.
+    #[structopt(long)]
+    keystore_pass: Option<PassInputMethod>,
+    /// Generate the configuration of the watch-only utility, which doesn't sign
+    /// the anchoring proposals. The keystore is not created in this case.
+    #[structopt(long, requires = "bitcoin-rpc-host")]
+    watch_only: bool,
+}
+
+/// Run btc anchoring sync utility.
+///
+/// The configuration file is watched during the utility work, so changes in it
+/// are applied without restarting the utility.
+///
+/// If the configuration file contains the `instances` array, each of its sections
+/// configures a separate anchoring service instance, which is synchronized concurrently
+/// with the other ones. Adding or removing the instances requires restarting.
+#[derive(Debug, StructOpt)]
+struct RunCommand {
+    /// Path to a sync utility configuration file.
+    #[structopt(long, short = "c")]
+    config: PathBuf,
+    /// Passphrase entry method for the Bitcoin keystores.
+    ///
+    /// Possible values are: `stdin`, `env{:ENV_VAR_NAME}`, `pass:PASSWORD`.
+    /// Default Value is `stdin`.
+    /// If `ENV_VAR_NAME` is not specified `$EXONUM_MASTER_PASS` is used
+    /// by default.
+    #[structopt(long)]
+    keystore_pass: Option<PassInputMethod>,
+}
+
+/// Generates a new Bitcoin key pair, writes the private key to the encrypted keystore file
+/// and adds this file to the keystores of the specified configuration file.
+#[derive(Debug, StructOpt)]
+struct GenerateKeypairCommand {
+    /// Path to a sync utility configuration file.
+    #[structopt(long, short = "c")]
+    config: PathBuf,
+    /// Path to the keystore file which will be created after running this command.
+    #[structopt(long, short = "k")]
+    keystore: PathBuf,
+    /// Passphrase entry method for the keystore.
+    ///
+    /// Possible values are: `stdin`, `env{:ENV_VAR_NAME}`, `pass:PASSWORD`.
+    /// Default Value is `stdin`.
+    /// If `ENV_VAR_NAME` is not specified `$EXONUM_MASTER_PASS` is used
+    /// by default.
+    #[structopt(long)]
+    keystore_pass: Option<PassInputMethod>,
+}
+
+/// Moves the Bitcoin private keys kept in plain text in the `bitcoin_key_pool` section
+/// of the configuration file into the encrypted keystore files.
+#[derive(Debug, StructOpt)]
+struct EncryptKeyPoolCommand {
+    /// Path to a sync utility configuration file.
+    #[structopt(long, short = "c")]
+    config: PathBuf,
+    /// Directory in which the keystore files will be created, one file per Bitcoin key.
+    #[structopt(long, short = "o")]
+    output_dir: PathBuf,
+    /// Passphrase entry method for the keystores.
+    ///
+    /// Possible values are: `stdin`, `env{:ENV_VAR_NAME}`, `pass:PASSWORD`.
+    /// Default Value is `stdin`.
+    /// If `ENV_VAR_NAME` is not specified `$EXONUM_MASTER_PASS` is used
+    /// by default.
+    #[structopt(long)]
+    keystore_pass: Option<PassInputMethod>,
+}
+
+/// Generates a new Bitcoin key pair, writes the private key to the encrypted keystore file
+/// and prints the anchoring keys of the node to use them in the configuration proposal.
+#[derive(Debug, StructOpt)]
+struct GenerateKeystoreCommand {
+    /// Path to the keystore file which will be created after running this command.
+    #[structopt(long, short = "o")]
+    output: PathBuf,
+    /// Bitcoin network type.
+    #[structopt(long, short = "n", default_value = "testnet")]
+    bitcoin_network: bitcoin::Network,
+    /// Service public key of the anchoring node, which can be found in its `node.toml`.
+    #[structopt(long, short = "s", parse(try_from_str = PublicKey::from_hex))]
+    service_key: PublicKey,
+    /// Passphrase entry method for the keystore.
+    ///
+    /// Possible values are: `stdin`, `env{:ENV_VAR_NAME}`, `pass:PASSWORD`.
+    /// Default Value is `stdin`.
+    /// If `ENV_VAR_NAME` is not specified `$EXONUM_MASTER_PASS` is used
+    /// by default.
+    #[structopt(long)]
+    keystore_pass: Option<PassInputMethod>,
+}
+
+/// Prints the redeem script, the anchoring address and the quorum derived from
+/// the anchoring configuration, so they can be verified before funding the address.
+#[derive(Debug, StructOpt)]
+struct InspectConfigCommand {
+    /// Path to the anchoring configuration file in the JSON or TOML format.
+    /// The format is chosen by the file extension.
+    #[structopt(long, short = "c", required_unless = "exonum-public-api")]
+    config: Option<PathBuf>,
+    /// Anchoring node public API url address to fetch the actual anchoring
+    /// configuration from.
+    #[structopt(long, short = "e", conflicts_with = "config")]
+    exonum_public_api: Option<String>,
+    /// Name of the anchoring service instance.
+    #[structopt(long, short = "i", default_value = "anchoring")]
+    instance_name: String,
+}
+
+/// Export the anchoring state of the node into a hash-verified archive.
+#[derive(Debug, StructOpt)]
+struct ExportStateCommand {
+    /// Path to the archive file which will be created after running this command.
+    #[structopt(long, short = "o")]
+    output: PathBuf,
+    /// Anchoring node public API url address.
+    #[structopt(long, short = "e", default_value = "http://localhost:8080")]
+    exonum_public_api: String,
+    /// Name of the anchoring service instance.
+    #[structopt(long, short = "i", default_value = "anchoring")]
+    instance_name: String,
+}
+
+/// Download the proof that the Exonum block is anchored to the Bitcoin blockchain
+/// into a self-contained file, which can be verified offline.
+#[derive(Debug, StructOpt)]
+struct ExportProofCommand {
+    /// Height of the anchored Exonum block.
+    #[structopt(long)]
+    height: u64,
+    /// Path to the proof file which will be created after running this command.
+    #[structopt(long, short = "o")]
+    output: PathBuf,
+    /// Anchoring node public API url address.
+    #[structopt(long, short = "e", default_value = "http://localhost:8080")]
+    exonum_public_api: String,
+    /// Name of the anchoring service instance.
+    #[structopt(long, short = "i", default_value = "anchoring")]
+    instance_name: String,
+}
+
+/// Import the anchoring state from the archive into the database, for example,
+/// to set up an auditor's replica.
+#[derive(Debug, StructOpt)]
+struct ImportStateCommand {
+    /// Path to the archive file.
+    #[structopt(long, short = "a")]
+    archive: PathBuf,
+    /// Path to the database directory.
+    #[structopt(long, short = "d")]
+    db_path: PathBuf,
+    /// Name of the anchoring service instance.
+    #[structopt(long, short = "i", default_value = "anchoring")]
+    instance_name: String,
+}
+
+/// Verify the anchoring transactions chain stored in the database, e.g. the one of
+/// the stopped node or of the auditor's replica.
+#[derive(Debug, StructOpt)]
+struct VerifyChainCommand {
+    /// Path to the database directory.
+    #[structopt(long, short = "d")]
+    db_path: PathBuf,
+    /// Name of the anchoring service instance.
+    #[structopt(long, short = "i", default_value = "anchoring")]
+    instance_name: String,
+}
+
+/// Print the confirmed balance of the funding wallet in satoshis.
+#[derive(Debug, StructOpt)]
+struct WalletBalanceCommand {
+    /// Path to a sync utility configuration file.
+    #[structopt(long, short = "c")]
+    config: PathBuf,
+}
+
+/// Print the anchoring transactions which have failed to broadcast and wait for the retry.
+#[derive(Debug, StructOpt)]
+struct BroadcastQueueCommand {
+    /// Path to a sync utility configuration file.
+    #[structopt(long, short = "c")]
+    config: PathBuf,
+}
+
+/// Vote for the recovery of the anchoring chain on behalf of this node. The next
+/// anchoring transaction spends a fresh funding transaction instead of the output
+/// of the latest anchoring transaction.
+#[derive(Debug, StructOpt)]
+struct RecoverAnchoringCommand {
+    /// Path to a sync utility configuration file.
+    #[structopt(long, short = "c")]
+    config: PathBuf,
+    /// Identifier of the latest anchoring transaction with the unusable output.
+    #[structopt(long, short = "t")]
+    txid: btc::Sha256d,
+}
+
+/// Send Bitcoins from the funding wallet to the actual anchoring address and
+/// vote for the created funding transaction on behalf of this node.
+#[derive(Debug, StructOpt)]
+struct FundCommand {
+    /// Path to a sync utility configuration file.
+    #[structopt(long, short = "c")]
+    config: PathBuf,
+    /// Amount of satoshis to send.
+    #[structopt(long, short = "a")]
+    amount: u64,
+}
+
+/// Compute the actual anchoring proposal, its fee and the inputs which this node
+/// would sign, and print the report without submitting the signatures.
+#[derive(Debug, StructOpt)]
+struct DryRunCommand {
+    /// Path to a sync utility configuration file.
+    #[structopt(long, short = "c")]
+    config: PathBuf,
+    /// Passphrase entry method for the Bitcoin keystores.
+    ///
+    /// Possible values are: `stdin`, `env{:ENV_VAR_NAME}`, `pass:PASSWORD`.
+    /// Default Value is `stdin`.
+    /// If `ENV_VAR_NAME` is not specified `$EXONUM_MASTER_PASS` is used
+    /// by default.
+    #[structopt(long)]
+    keystore_pass: Option<PassInputMethod>,
+}
+
+/// Vote for the funding transaction on behalf of this node, if it has the number
+/// of Bitcoin confirmations required by the anchoring configuration.
+#[derive(Debug, StructOpt)]
+struct AddFundsCommand {
+    /// Path to a sync utility configuration file.
+    #[structopt(long, short = "c")]
+    config: PathBuf,
+    /// Hex-encoded raw funding transaction.
+    #[structopt(long, short = "t")]
+    transaction: btc::Transaction,
+}
+
+/// Create a funding transaction which pays to the actual anchoring address and print
+/// the `AddFunds` transaction payload to be submitted by the anchoring nodes.
+#[derive(Debug, StructOpt)]
+struct PrepareFundingCommand {
+    /// Path to a sync utility configuration file.
+    #[structopt(long, short = "c")]
+    config: PathBuf,
+    /// Amount of satoshis to send.
+    #[structopt(long, short = "a")]
+    amount: u64,
+    /// Broadcast the funding transaction via the funding wallet. Otherwise the signed
+    /// transaction is only printed.
+    #[structopt(long)]
+    broadcast: bool,
+}
+
+/// Funding transaction along with the `AddFunds` transaction payload.
+#[derive(Debug, Serialize)]
+struct FundingPayload {
+    /// Identifier of the funding transaction.
+    transaction_id: btc::Sha256d,
+    /// Raw funding transaction, which is accepted by the `add-funds` private API method.
+    transaction: btc::Transaction,
+    /// Hex-encoded `AddFunds` transaction payload.
+    add_funds: String,
+}
+
+#[derive(Debug, StructOpt)]
+enum Commands {
+    /// Generate initial configuration for the btc anchoring sync utility.
+    GenerateConfig(GenerateConfigCommand),
+    /// Run btc anchoring sync utility.
+    Run(RunCommand),
+    /// Generate a new Bitcoin key pair, write the private key to the encrypted keystore
+    /// file and add it to the keystores of the specified configuration file.
+    GenerateKeypair(GenerateKeypairCommand),
+    /// Move the plain text Bitcoin private keys of the configuration file into
+    /// the encrypted keystore files.
+    EncryptKeyPool(EncryptKeyPoolCommand),
+    /// Generate a new Bitcoin key pair, write the private key to the encrypted keystore
+    /// file and print the anchoring keys of the node.
+    GenerateKeystore(GenerateKeystoreCommand),
+    /// Print the redeem script, the anchoring address and the quorum derived from
+    /// the anchoring configuration.
+    InspectConfig(InspectConfigCommand),
+    /// Export the anchoring state of the node into a hash-verified archive.
+    ExportState(ExportStateCommand),
+    /// Import the anchoring state from the archive into the database.
+    ImportState(ImportStateCommand),
+    /// Download the proof of the Exonum block anchoring into a self-contained file.
+    ExportProof(ExportProofCommand),
+    /// Verify payloads, scripts and signatures of the anchoring transactions chain
+    /// stored in the database.
+    VerifyChain(VerifyChainCommand),
+    /// Print the confirmed balance of the funding wallet in satoshis.
+    WalletBalance(WalletBalanceCommand),
+    /// Send Bitcoins from the funding wallet to the actual anchoring address.
+    Fund(FundCommand),
+    /// Create a funding transaction, optionally broadcast it, and print the `AddFunds`
+    /// transaction payload.
+    PrepareFunding(PrepareFundingCommand),
+    /// Vote for the confirmed funding transaction on behalf of this node.
+    AddFunds(AddFundsCommand),
+    /// Print the actual anchoring proposal, its fee and the inputs which this node would
+    /// sign without submitting the signatures.
+    DryRun(DryRunCommand),
+    /// Print the anchoring transactions which have failed to broadcast and wait
+    /// for the retry.
+    BroadcastQueue(BroadcastQueueCommand),
+    /// Vote for the recovery of the anchoring chain, whose latest transaction output
+    /// is unusable, on behalf of this node.
+    RecoverAnchoring(RecoverAnchoringCommand),
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct SyncConfig {
+    exonum_private_api: String,
+    instance_name: String,
+    /// Paths to the keystore files with the encrypted Bitcoin private keys of this node.
+    /// The keystores are decrypted by the passphrase given to the `run` command.
+    #[serde(default)]
+    bitcoin_keystores: Vec<PathBuf>,
+    /// Bitcoin private keys kept in plain text by the previous versions of the utility.
+    /// They are not used anymore and should be moved to the keystores via
+    /// the `encrypt-key-pool` command.
+    #[serde(
+        default,
+        rename = "bitcoin_key_pool",
+        deserialize_with = "flatten_keypairs::deserialize",
+        skip_serializing
+    )]
+    legacy_key_pool: HashMap<btc::PublicKey, btc::PrivateKey>,
+    /// Bitcoin private keys decrypted from the keystores.
+    #[serde(skip)]
+    bitcoin_key_pool: HashMap<btc::PublicKey, btc::PrivateKey>,
+    bitcoin_rpc_config: Option<BitcoindRelayConfig>,
+    /// Additional Bitcoin RPC nodes to which the anchoring transactions are sent
+    /// simultaneously with the `bitcoin_rpc_config` node.
+    #[serde(default)]
+    broadcast_rpc_configs: Vec<BitcoindRelayConfig>,
+    /// Path to the file with the anchoring transactions which have failed to broadcast
+    /// and wait for the retry. If it is not specified, the queue is kept in memory.
+    broadcast_queue: Option<PathBuf>,
+    /// Path to the file with the progress of the sync with Bitcoin. If it is specified,
+    /// the restarted sync utility resumes from the latest synchronized anchoring
+    /// transaction instead of checking the whole anchoring chain.
+    #[serde(default)]
+    sync_state: Option<PathBuf>,
+    /// Path to the append-only log of the Bitcoin RPC requests made by the sync with
+    /// Bitcoin and the fee estimation, which allows to find out what the Bitcoin nodes
+    /// have answered about a missing anchoring transaction.
+    #[serde(default)]
+    relay_audit_log: Option<PathBuf>,
+    /// Bitcoin RPC of the `bitcoind` wallet with the operational Bitcoins used to fund
+    /// the anchoring, e.g. `http://localhost:18332/wallet/anchoring`.
+    funding_wallet: Option<BitcoindRelayConfig>,
+    /// Policy of the automatic anchoring funding top-up from the funding wallet.
+    top_up_policy: Option<TopUpPolicy>,
+    /// Policy of the voting for the anchoring transactions fee rate estimated by
+    /// the Bitcoin RPC nodes. If it is absent, the fee from the anchoring configuration
+    /// is not voted against.
+    /// The same policy is used to vote for the acceleration of the anchoring transaction
+    /// stuck in the Bitcoin mempool.
+    fee_rate_policy: Option<FeeRatePolicy>,
+    /// Interval in seconds between the sync iterations.
+    #[serde(default = "SyncConfig::default_sync_interval")]
+    sync_interval: u64,
+    /// Number of Bitcoin confirmations of the anchoring transaction required to send
+    /// the following one. Zero means that it is enough for the transaction to be known
+    /// to the Bitcoin RPC node.
+    #[serde(default)]
+    min_confirmations: u32,
+    /// Run only the sync with Bitcoin without any Bitcoin keys, so the utility rebroadcasts
+    /// and monitors the anchoring chain but never signs the anchoring proposals.
+    /// This mode is intended for the auditor nodes.
+    #[serde(default)]
+    watch_only: bool,
+    /// Address of the sync utility control API, e.g. `127.0.0.1:8091`. The API allows
+    /// to trigger an immediate sync iteration via `POST /sync`, so it should not
+    /// be reachable from the outside.
+    control_api_address: Option<SocketAddr>,
+    /// Address of the metrics endpoint of the sync utility, e.g. `127.0.0.1:9091`.
+    /// Metrics are exposed via `GET /metrics` in the Prometheus text format.
+    metrics_address: Option<SocketAddr>,
+    /// Bitcoin Core ZMQ endpoints. If they are specified, the sync iteration is performed
+    /// as soon as a new Bitcoin block is published, while the sync interval is kept
+    /// as the fallback.
+    zmq_notifications: Option<ZmqNotificationsConfig>,
+    /// SOCKS5 proxy, e.g. of the Tor daemon, which is used by all Bitcoin RPC sections
+    /// without their own proxy, so the Bitcoin nodes don't see the IP address of
+    /// the anchoring node.
+    proxy: Option<ProxyConfig>,
+    /// Hardware wallet which signs the anchoring proposals via HWI if the key pool
+    /// doesn't contain the Bitcoin key of this node.
+    hwi: Option<HwiConfig>,
+    /// Remote signing service, e.g. a daemon which keeps the Bitcoin keys inside a hardware
+    /// security module, which signs the anchoring proposals if the key pool doesn't contain
+    /// the Bitcoin key of this node.
+    remote_signer: Option<RemoteSignerConfig>,
+    /// PKCS#11 token, e.g. YubiHSM 2, which signs the anchoring proposals without exporting
+    /// the Bitcoin keys if the key pool doesn't contain the Bitcoin key of this node.
+    /// Requires the `pkcs11` feature.
+    #[cfg(feature = "pkcs11")]
+    pkcs11: Option<Pkcs11Config>,
+}
+
+impl SyncConfig {
+    /// Returns the Bitcoin keys of the PKCS#11 token, if it is specified.
+    #[cfg(feature = "pkcs11")]
+    fn pkcs11_keys(&self) -> Option<Vec<btc::PublicKey>> {
+        self.pkcs11
+            .as_ref()
+            .map(|pkcs11| pkcs11.keys.iter().map(|key| key.bitcoin_key).collect())
+    }
+
+    #[cfg(not(feature = "pkcs11"))]
+    fn pkcs11_keys(&self) -> Option<Vec<btc::PublicKey>> {
+        None
+    }
+
+    fn default_sync_interval() -> u64 {
+        5
+    }
+
+    /// Applies the common proxy to the Bitcoin RPC sections without their own one.
+    fn with_common_proxy(mut self) -> Self {
+        if let Some(proxy) = &self.proxy {
+            let rpc_configs = self
+                .bitcoin_rpc_config
+                .iter_mut()
+                .chain(&mut self.broadcast_rpc_configs)
+                .chain(&mut self.funding_wallet);
+            for rpc_config in rpc_configs {
+                rpc_config.proxy.get_or_insert_with(|| proxy.clone());
+            }
+        }
+        self
+    }
+
+    /// Opens the audit log of the Bitcoin RPC requests, or returns the disabled log
+    /// if it is not specified.
+    fn relay_audit_log(&self) -> anyhow::Result<RelayAuditLog> {
+        match &self.relay_audit_log {
+            Some(path) => RelayAuditLog::open(path)
+                .map_err(|e| anyhow!("Unable to open relay audit log {}: {}", path.display(), e)),
+            None => Ok(RelayAuditLog::default()),
+        }
+    }
+
+    /// Creates the audited relays for the main Bitcoin RPC node and the additional
+    /// broadcast nodes, if the main one is specified.
+    fn broadcast_relays(
+        &self,
+        audit_log: &RelayAuditLog,
+    ) -> anyhow::Result<Option<BroadcastAllRelay<AuditRelay<BitcoindRelay>>>> {
+        let rpc_config = match &self.bitcoin_rpc_config {
+            Some(rpc_config) => rpc_config,
+            None => return Ok(None),
+        };
+        let relays = std::iter::once(rpc_config)
+            .chain(&self.broadcast_rpc_configs)
+            .map(|rpc_config| {
+                BitcoindRelay::new(rpc_config)
+                    .map(|relay| AuditRelay::new(relay, audit_log.clone()))
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok(Some(BroadcastAllRelay::new(relays)))
+    }
+
+    /// Extracts Bitcoin network type from the one of Bitcoin private keys in this config.
+    fn bitcoin_network(&self) -> Option<bitcoin::Network> {
+        self.bitcoin_key_pool
+            .values()
+            .next()
+            .map(|key| key.0.network)
+    }
+
+    /// Loads the encrypted Bitcoin private keys from the keystores of this config.
+    fn keystores(&self) -> anyhow::Result<Vec<EncryptedPrivateKey>> {
+        self.bitcoin_keystores
+            .iter()
+            .map(|path| {
+                EncryptedPrivateKey::load(path)
+                    .map_err(|e| anyhow!("Unable to load keystore {}: {}", path.display(), e))
+            })
+            .collect()
+    }
+
+    /// Decrypts the Bitcoin private keys from the keystores of this config and adds them
+    /// to the key pool.
+    fn unlock_keys(&mut self, passphrase: &Passphrase) -> anyhow::Result<()> {
+        ensure!(
+            self.legacy_key_pool.is_empty(),
+            "Bitcoin private keys are stored in plain text in the `bitcoin_key_pool` section. \
+             Use the `encrypt-key-pool` command to move them into the encrypted keystores."
+        );
+        for keystore in self.keystores()? {
+            let private_key = keystore.decrypt(passphrase.as_bytes())?;
+            self.bitcoin_key_pool
+                .insert(keystore.public_key, private_key);
+        }
+        Ok(())
+    }
+
+    /// Checks that this configuration is consistent with the actual anchoring
+    /// configuration and that the Bitcoin relay is reachable.
+    async fn validate(&self) -> anyhow::Result<()> {
+        if self.watch_only {
+            return self.validate_watch_only().await;
+        }
+
+        let network = self.bitcoin_network();
+        ensure!(
+            network.is_some()
+                || self.hwi.is_some()
+                || self.remote_signer.is_some()
+                || self.pkcs11_keys().is_some(),
+            "Bitcoin key pool is empty. Use the `generate-keypair` command to add a new \
+             key pair or specify the hardware wallet in the `hwi` section, the signing \
+             service in the `remote_signer` section or the token in the `pkcs11` section."
+        );
+        if let Some(network) = network {
+            if let Some(key) = self
+                .bitcoin_key_pool
+                .values()
+                .find(|key| key.0.network != network)
+            {
+                bail!(
+                    "Bitcoin key pool contains keys for different networks: {} and {}.",
+                    network,
+                    key.0.network
+                );
+            }
+        }
+
+        let client = self.api_client();
+        let anchoring_config = self.anchoring_config().await?;
+        // The node may be added by the configuration which is not actual yet, so it has
+        // to sign the transition transaction only after that.
+        let following_config = client.following_config().await.map_err(|e| {
+            anyhow!(
+                "Unable to get following anchoring configuration from `{}`: {}.",
+                client.prefix,
+                e
+            )
+        })?;
+        if let Some(network) = network {
+            ensure!(
+                anchoring_config.network == network,
+                "Bitcoin keys in the key pool are intended for the {} network, \
+                 but anchoring is performed in the {} network.",
+                network,
+                anchoring_config.network
+            );
+        }
+        let hwi_key = self.hwi.as_ref().map(|hwi| hwi.bitcoin_key);
+        let remote_keys = self
+            .remote_signer
+            .as_ref()
+            .map(|signer| signer.bitcoin_keys.as_slice())
+            .unwrap_or_default();
+        let pkcs11_keys = self.pkcs11_keys().unwrap_or_default();
+        let mut anchoring_keys = anchoring_config
+            .anchoring_keys
+            .iter()
+            .chain(following_config.iter().flat_map(|c| &c.anchoring_keys));
+        ensure!(
+            anchoring_keys.any(|keys| {
+                self.bitcoin_key_pool.contains_key(&keys.bitcoin_key)
+                    || hwi_key == Some(keys.bitcoin_key)
+                    || remote_keys.contains(&keys.bitcoin_key)
+                    || pkcs11_keys.contains(&keys.bitcoin_key)
+            }),
+            "None of the Bitcoin keys in the key pool, the hardware wallet, the signing \
+             service or the PKCS#11 token is used in the actual or following anchoring \
+             configuration. Make sure that the Bitcoin public key of this node has been \
+             added to the `anchoring_keys` via the configuration update."
+        );
+
+        ensure!(
+            self.broadcast_rpc_configs.is_empty() || self.bitcoin_rpc_config.is_some(),
+            "Additional broadcast Bitcoin RPC nodes require the `bitcoin_rpc_config` section."
+        );
+        if self.top_up_policy.is_some() {
+            ensure!(
+                self.funding_wallet.is_some() && self.bitcoin_rpc_config.is_some(),
+                "Automatic funding top-up requires both the `funding_wallet` and \
+                 the `bitcoin_rpc_config` sections."
+            );
+        }
+        ensure!(
+            self.fee_rate_policy.is_none() || self.bitcoin_rpc_config.is_some(),
+            "Fee rate estimation requires the `bitcoin_rpc_config` section."
+        );
+        self.validate_bitcoin_rpc(anchoring_config.network).await
+    }
+
+    /// Checks that the watch-only configuration contains nothing that signs transactions
+    /// on behalf of this node.
+    async fn validate_watch_only(&self) -> anyhow::Result<()> {
+        ensure!(
+            self.bitcoin_keystores.is_empty()
+                && self.legacy_key_pool.is_empty()
+                && self.hwi.is_none()
+                && self.remote_signer.is_none()
+                && self.pkcs11_keys().is_none(),
+            "Watch-only sync utility never signs the anchoring proposals, so the Bitcoin \
+             keys, the hardware wallet, the signing service and the PKCS#11 token \
+             should not be specified."
+        );
+        ensure!(
+            self.top_up_policy.is_none() && self.fee_rate_policy.is_none(),
+            "Watch-only sync utility doesn't vote for the funding top-ups and the fee rate, \
+             so the `top_up_policy` and `fee_rate_policy` sections should not be specified."
+        );
+        ensure!(
+            self.bitcoin_rpc_config.is_some(),
+            "Watch-only sync utility requires the `bitcoin_rpc_config` section."
+        );
+
+        let anchoring_config = self.anchoring_config().await?;
+        self.validate_bitcoin_rpc(anchoring_config.network).await
+    }
+
+    /// Creates a client of the private API of the anchoring instance.
+    fn api_client(&self) -> ApiClient {
+        ApiClient::new(&self.exonum_private_api, &self.instance_name)
+    }
+
+    /// Fetches the actual anchoring configuration via the anchoring API.
+    async fn anchoring_config(&self) -> anyhow::Result<AnchoringConfig> {
+        let client = self.api_client();
+        let anchoring_config = client.config().await.map_err(|e| {
+            anyhow!(
+                "Unable to get anchoring configuration from `{}`: {}. Make sure that \
+                 the `exonum_private_api` and `instance_name` options are correct.",
+                client.prefix,
+                e
+            )
+        })?;
+        Ok(anchoring_config)
+    }
+
+    /// Checks that the Bitcoin RPC node, if any, is reachable and serves the given network.
+    async fn validate_bitcoin_rpc(&self, network: bitcoin::Network) -> anyhow::Result<()> {
+        if let Some(rpc_config) = self.bitcoin_rpc_config.clone() {
+            let host = rpc_config.host.clone();
+            // The Bitcoin RPC client is blocking, so it is called outside of the runtime thread.
+            let info = tokio::task::spawn_blocking(move || {
+                rpc_config
+                    .client()
+                    .and_then(|relay| relay.get_blockchain_info())
+            })
+            .await?
+            .map_err(|e| {
+                anyhow!(
+                    "Bitcoin RPC at `{}` is unreachable: {}. \
+                         Check the `bitcoin_rpc_config` section.",
+                    host,
+                    e
+                )
+            })?;
+            ensure!(
+                info.chain == rpc_chain_name(network),
+                "Bitcoin RPC at `{}` serves the `{}` chain, but anchoring is performed \
+                 in the {} network.",
+                host,
+                info.chain,
+                network
+            );
+        }
+        Ok(())
+    }
+
+    fn read(path: impl AsRef<Path>) -> anyhow::Result<toml::Value> {
+        let mut file = File::open(path)?;
+        let mut toml = String::new();
+        file.read_to_string(&mut toml)?;
+        toml::de::from_str(&toml).map_err(From::from)
+    }
+
+    fn load(path: impl AsRef<Path>) -> anyhow::Result<Self> {
+        let value = Self::read(path)?;
+        ensure!(
+            value.get("instances").is_none(),
+            "The configuration file contains several anchoring instances in the `instances` \
+             section, while this command requires the configuration of a single instance."
+        );
+        value.try_into().map_err(From::from)
+    }
+
+    /// Loads the configurations of all anchoring instances served by the utility.
+    /// The file contains either the configuration of a single instance or
+    /// the `instances` array of them.
+    fn load_instances(path: impl AsRef<Path>) -> anyhow::Result<Vec<Self>> {
+        let mut value = Self::read(path)?;
+        let instances: Vec<Self> = match value.as_table_mut().and_then(|t| t.remove("instances")) {
+            Some(instances) => instances.try_into()?,
+            None => return Ok(vec![value.try_into()?]),
+        };
+        ensure!(
+            !instances.is_empty(),
+            "The `instances` section of the configuration file is empty."
+        );
+
+        let mut addresses = Vec::new();
+        for (i, instance) in instances.iter().enumerate() {
+            ensure!(
+                instances[..i]
+                    .iter()
+                    .all(|other| other.instance_name != instance.instance_name),
+                "Anchoring instance `{}` is specified several times.",
+                instance.instance_name
+            );
+            for address in instance
+                .control_api_address
+                .iter()
+                .chain(&instance.metrics_address)
+            {
+                ensure!(
+                    !addresses.contains(address),
+                    "Address {} is used by several anchoring instances.",
+                    address
+                );
+                addresses.push(*address);
+            }
+        }
+        Ok(instances)
+    }
+
+    fn save(&self, path: impl AsRef<Path>) -> anyhow::Result<()> {
+        let path = path.as_ref();
+
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+
+        let mut file = File::create(path)?;
+        let value_toml = toml::Value::try_from(&self)?;
+        file.write_all(value_toml.to_string().as_bytes())?;
+        Ok(())
+    }
+}
+
+/// Watches the sync utility configuration file for changes.
+#[derive(Debug)]
+struct ConfigWatcher {
+    /// Path to a sync utility configuration file.
+    path: PathBuf,
+    /// Name of the anchoring instance whose section is loaded from the file
+    /// with several instances.
+    instance_name: Option<String>,
+    /// Modification time of the latest loaded configuration.
+    modified: Option<SystemTime>,
+}
+
+impl ConfigWatcher {
+    fn new(path: PathBuf, instance_name: Option<String>) -> Self {
+        Self {
+            path,
+            instance_name,
+            modified: None,
+        }
+    }
+
+    fn modified(&self) -> Option<SystemTime> {
+        fs::metadata(&self.path)
+            .and_then(|metadata| metadata.modified())
+            .ok()
+    }
+
+    /// Loads the actual configuration and remembers its modification time.
+    fn load(&mut self) -> anyhow::Result<SyncConfig> {
+        self.modified = self.modified();
+        let mut instances = SyncConfig::load_instances(&self.path)?;
+        match &self.instance_name {
+            Some(name) => instances
+                .into_iter()
+                .find(|instance| &instance.instance_name == name)
+                .ok_or_else(|| {
+                    anyhow!(
+                        "Anchoring instance `{}` is absent in the configuration file.",
+                        name
+                    )
+                }),
+            None => {
+                ensure!(
+                    instances.len() == 1,
+                    "Anchoring instances have been added to the configuration file. \
+                     Restart the utility to synchronize them."
+                );
+                Ok(instances.remove(0))
+            }
+        }
+    }
+
+    /// Reloads the configuration if the file has been modified since the last load.
+    fn poll(&mut self) -> Option<anyhow::Result<SyncConfig>> {
+        let modified = self.modified()?;
+        if self.modified == Some(modified) {
+            return None;
+        }
+        Some(self.load())
+    }
+}
+
+/// Bitcoin relay which sends the requests to all Bitcoin RPC nodes of the sync utility
+/// and records them to the audit log.
+type AuditedRelay = BroadcastAllRelay<AuditRelay<BitcoindRelay>>;
+
+/// Sync tasks created from the sync utility configuration.
+struct SyncTasks {
+    /// Complete prefix of the anchoring private API.
+    api_prefix: String,
+    client: ApiClient,
+    /// Anchoring chain updater, which is absent in the watch-only mode.
+    chain_updater: Option<AnchoringChainUpdateTask<ApiClient>>,
+    bitcoin_relay: Option<SyncWithBitcoinTask<ApiClient, AuditedRelay>>,
+    watchdog: Option<ExternalSpendWatchdog<ApiClient, AuditedRelay>>,
+    top_up: Option<AnchoringTopUpTask<ApiClient, BitcoindRelay, BitcoindRelay>>,
+    broadcast_queue: BroadcastQueue,
+    fee_rate_policy: Option<FeeRatePolicy>,
+    sync_interval: Duration,
+    metrics: SyncMetrics,
+}
+
+impl SyncTasks {
+    async fn new(sync_config: SyncConfig, metrics: SyncMetrics) -> anyhow::Result<Self> {
+        let sync_config = sync_config.with_common_proxy();
+        sync_config.validate().await?;
+
+        let client = ApiClient::new(&sync_config.exonum_private_api, &sync_config.instance_name);
+        let audit_log = sync_config.relay_audit_log()?;
+        let chain_updater = if sync_config.watch_only {
+            None
+        } else {
+            Some(Self::chain_updater(
+                &sync_config,
+                &client,
+                &metrics,
+                &audit_log,
+            )?)
+        };
+        let relay = sync_config.broadcast_relays(&audit_log)?;
+        let top_up = match (
+            sync_config.top_up_policy,
+            sync_config.funding_wallet,
+            sync_config.bitcoin_rpc_config.clone(),
+        ) {
+            (Some(policy), Some(wallet), Some(relay)) => Some(AnchoringTopUpTask::new(
+                AnchoringFundingTask::new(BitcoindRelay::new(&wallet)?, client.clone()),
+                BitcoindRelay::new(&relay)?,
+                policy,
+            )),
+            _ => None,
+        };
+        let broadcast_queue = match sync_config.broadcast_queue {
+            Some(path) => BroadcastQueue::load(path)?,
+            None => BroadcastQueue::default(),
+        };
+        let (bitcoin_relay, watchdog) = match relay {
+            Some(relay) => {
+                let watchdog = ExternalSpendWatchdog::new(relay.clone(), client.clone())
+                    .with_metrics(metrics.clone());
+                let mut bitcoin_relay = SyncWithBitcoinTask::new(relay, client.clone())
+                    .with_min_confirmations(sync_config.min_confirmations)
+                    .with_metrics(metrics.clone());
+                if let Some(path) = sync_config.sync_state {
+                    bitcoin_relay = bitcoin_relay.with_state_store(SyncStateStore::new(path));
+                }
+                (Some(bitcoin_relay), Some(watchdog))
+            }
+            None => (None, None),
+        };
+
+        Ok(Self {
+            api_prefix: client.prefix.clone(),
+            client,
+            chain_updater,
+            bitcoin_relay,
+            watchdog,
+            top_up,
+            broadcast_queue,
+            fee_rate_policy: sync_config.fee_rate_policy,
+            sync_interval: Duration::from_secs(sync_config.sync_interval),
+            metrics,
+        })
+    }
+
+    /// Creates the anchoring chain updater with the signers and the fee estimation
+    /// specified in the configuration.
+    fn chain_updater(
+        sync_config: &SyncConfig,
+        client: &ApiClient,
+        metrics: &SyncMetrics,
+        audit_log: &RelayAuditLog,
+    ) -> anyhow::Result<AnchoringChainUpdateTask<ApiClient>> {
+        let mut chain_updater =
+            AnchoringChainUpdateTask::new(sync_config.bitcoin_key_pool.clone(), client.clone())
+                .with_metrics(metrics.clone());
+        #[cfg(feature = "pkcs11")]
+        {
+            if let Some(pkcs11) = &sync_config.pkcs11 {
+                chain_updater = chain_updater.with_signer(Pkcs11Signer::open(pkcs11)?);
+            }
+        }
+        if let Some(remote_signer) = &sync_config.remote_signer {
+            chain_updater = chain_updater.with_signer(remote_signer.signer()?);
+        }
+        if let Some(hwi) = sync_config.hwi.clone() {
+            chain_updater = chain_updater.with_psbt_signer(HwiSigner::new(hwi));
+        }
+        if let Some(policy) = sync_config.fee_rate_policy {
+            if let Some(relay) = sync_config.broadcast_relays(audit_log)? {
+                chain_updater = chain_updater.with_fee_estimation(relay, policy);
+            }
+        }
+        Ok(chain_updater)
+    }
+
+    /// Performs a single sync iteration and updates the index of the latest
+    /// synchronized anchoring transaction and the pending funding top-up.
+    ///
+    /// Errors that do not stop the sync utility are logged and returned in the outcome.
+    async fn process(
+        &mut self,
+        latest_synced_tx_index: &mut Option<u64>,
+        pending_top_up: &mut Option<PendingTopUp>,
+    ) -> anyhow::Result<SyncOutcome> {
+        let mut outcome = SyncOutcome::default();
+        if let Some(chain_updater) = self.chain_updater.as_ref() {
+            match chain_updater.process().await {
+                Ok(_) => {}
+                // Requests rejected by the anchoring API will not succeed if retried.
+                Err(ChainUpdateError::Client(e)) if !e.is_retriable() => outcome.error(format!(
+                    "The anchoring API has rejected the request of the sync utility. {}",
+                    e
+                )),
+                // Client problems most often occurs due to network problems.
+                Err(ChainUpdateError::Client(e)) => outcome.error(format!(
+                    "An error in the anchoring API client occurred. {}",
+                    e
+                )),
+                // Sometimes Bitcoin end in the anchoring wallet.
+                Err(ChainUpdateError::InsufficientFunds { total_fee, balance }) => {
+                    outcome.warning(format!(
+                        "Insufficient funds to construct a new anchoring transaction, \
+                         total fee is {}, total balance is {}",
+                        total_fee, balance
+                    ))
+                }
+                // For the work of anchoring you need to replenish anchoring wallet.
+                Err(ChainUpdateError::NoInitialFunds) => {
+                    match chain_updater.anchoring_config().await {
+                        Ok(config) => {
+                            outcome.warning(format!(
+                                "Initial funding transaction is absent, you should send some \
+                                 Bitcoins to the address {}",
+                                config.anchoring_address()
+                            ));
+                            log::warn!(
+                                "And then confirm this transaction using the private \
+                                 `add-funds` API method."
+                            )
+                        }
+                        Err(e) => {
+                            outcome.error(format!(
+                                "An error in the anchoring API client occurred. {}",
+                                e
+                            ));
+                            outcome.latest_synced_tx_index = *latest_synced_tx_index;
+                            return Ok(outcome);
+                        }
+                    }
+                }
+                // Stop execution if an internal error occurred.
+                Err(ChainUpdateError::Internal(e)) => return Err(e),
+            }
+        }
+
+        if let Some(relay) = self.bitcoin_relay.as_ref() {
+            match relay
+                .process_with_queue(*latest_synced_tx_index, &mut self.broadcast_queue)
+                .await
+            {
+                Ok(index) => *latest_synced_tx_index = index,
+
+                Err(SyncWithBitcoinError::Client(e)) => outcome.error(format!(
+                    "An error in the anchoring API client occurred. {}",
+                    e
+                )),
+
+                Err(SyncWithBitcoinError::Relay(e)) => {
+                    outcome.error(format!("An error in the Bitcoin relay occurred. {}", e))
+                }
+
+                Err(SyncWithBitcoinError::UnconfirmedFundingTransaction(id)) => bail!(
+                    "Funding transaction with id {} is unconfirmed by Bitcoin network. \
+                     This is a serious mistake that can break anchoring process.",
+                    id
+                ),
+
+                Err(SyncWithBitcoinError::TransactionMismatch { index, relayed }) => bail!(
+                    "Anchoring transaction with index {} differs from the transaction {} \
+                     confirmed in the Bitcoin network. Either the transaction has been \
+                     malleated or the Bitcoin relay is compromised.",
+                    index,
+                    relayed.map_or_else(|| "unknown".to_owned(), |tx| tx.to_string())
+                ),
+
+                // Stop execution if an internal error occurred.
+                Err(SyncWithBitcoinError::Internal(e)) => return Err(e),
+            }
+
+            if let Some(policy) = self.fee_rate_policy {
+                match relay.accelerate_stuck_transaction(policy).await {
+                    Ok(_) => {}
+                    Err(SyncWithBitcoinError::Client(e)) => outcome.error(format!(
+                        "An error in the anchoring API client occurred. {}",
+                        e
+                    )),
+                    Err(SyncWithBitcoinError::Relay(e)) => {
+                        outcome.error(format!("An error in the Bitcoin relay occurred. {}", e))
+                    }
+                    Err(e) => outcome.error(format!(
+                        "Unable to accelerate the stuck anchoring transaction. {:?}",
+                        e
+                    )),
+                }
+            }
+        }
+
+        if let Some(watchdog) = self.watchdog.as_ref() {
+            match watchdog.process().await {
+                Ok(spends) => {
+                    if !spends.is_empty() {
+                        outcome.error(format!(
+                            "Funds of the anchoring multisig have been spent by transactions \
+                             absent in the anchoring chain: {}. The quorum of the anchoring \
+                             keys may be compromised.",
+                            spends
+                                .iter()
+                                .map(|spend| format!("{}:{}", spend.txid, spend.vout))
+                                .collect::<Vec<_>>()
+                                .join(", ")
+                        ));
+                    }
+                    outcome.external_spends = spends;
+                }
+                Err(SyncWithBitcoinError::Client(e)) => outcome.error(format!(
+                    "An error in the anchoring API client occurred. {}",
+                    e
+                )),
+                Err(SyncWithBitcoinError::Relay(e)) => {
+                    outcome.error(format!("An error in the Bitcoin relay occurred. {}", e))
+                }
+                Err(e) => outcome.error(format!(
+                    "Unable to check the anchoring outputs for external spends. {:?}",
+                    e
+                )),
+            }
+        }
+
+        if let Some(top_up) = self.top_up.as_ref() {
+            match top_up.process(pending_top_up.take()).await {
+                Ok(pending) => *pending_top_up = pending,
+
+                Err(TopUpError::Funding(FundingError::Client(e))) => outcome.error(format!(
+                    "An error in the anchoring API client occurred. {}",
+                    e
+                )),
+
+                Err(TopUpError::Funding(FundingError::Wallet(e))) => {
+                    outcome.error(format!("An error in the funding wallet occurred. {}", e))
+                }
+
+                Err(TopUpError::Funding(FundingError::InsufficientBalance { amount, balance })) => {
+                    outcome.warning(format!(
+                        "Insufficient balance of the funding wallet to top up the anchoring \
+                         with {} satoshis, total balance is {}",
+                        amount, balance
+                    ))
+                }
+
+                Err(TopUpError::Relay(e)) => {
+                    outcome.error(format!("An error in the Bitcoin relay occurred. {}", e))
+                }
+            }
+        }
+        match self.client.stats().await {
+            Ok(stats) => self.metrics.update_stats(stats),
+            Err(e) => log::warn!("Unable to update the anchoring metrics. {}", e),
+        }
+        outcome.latest_synced_tx_index = *latest_synced_tx_index;
+        outcome.latest_synced_tx_confirmations = self
+            .bitcoin_relay
+            .as_ref()
+            .and_then(SyncWithBitcoinTask::latest_synced)
+            .filter(|tx| Some(tx.index) == *latest_synced_tx_index)
+            .map(|tx| tx.confirmations);
+        Ok(outcome)
+    }
+}
+
+/// Outcome of a single sync iteration.
+#[derive(Debug, Clone, Default, Serialize)]
+struct SyncOutcome {
+    /// Index of the latest anchoring transaction synchronized with the Bitcoin network.
+    latest_synced_tx_index: Option<u64>,
+    /// Number of confirmations of the latest synchronized anchoring transaction, if it
+    /// has been checked during the iteration.
+    latest_synced_tx_confirmations: Option<u32>,
+    /// Outputs of the anchoring multisig spent by the transactions absent in the anchoring
+    /// chain. They are a sign of the compromised anchoring keys.
+    external_spends: Vec<ExternalSpend>,
+    /// Problems which have occurred during the iteration, but have not stopped
+    /// the sync utility.
+    errors: Vec<String>,
+}
+
+impl SyncOutcome {
+    fn error(&mut self, message: String) {
+        log::error!("{}", message);
+        self.errors.push(message);
+    }
+
+    fn warning(&mut self, message: String) {
+        log::warn!("{}", message);
+        self.errors.push(message);
+    }
+}
+
+/// Request for an immediate sync iteration received by the control API.
+type SyncTrigger = oneshot::Sender<Result<SyncOutcome, String>>;
+
+/// Runs the control API of the sync utility on the given address.
+///
+/// `POST /sync` performs a sync iteration right now instead of waiting for the next
+/// scheduled one and responds with its outcome.
+async fn run_control_api(address: SocketAddr, triggers: mpsc::Sender<SyncTrigger>) {
+    let make_service = make_service_fn(move |_| {
+        let triggers = triggers.clone();
+        async move {
+            Ok::<_, Infallible>(service_fn(move |request| {
+                handle_control_request(request, triggers.clone())
+            }))
+        }
+    });
+
+    log::info!("Starting the sync utility control API on {}", address);
+    if let Err(e) = HttpServer::bind(&address).serve(make_service).await {
+        log::error!("An error in the sync utility control API occurred. {}", e);
+    }
+}
+
+async fn handle_control_request(
+    request: Request<Body>,
+    mut triggers: mpsc::Sender<SyncTrigger>,
+) -> Result<Response<Body>, Infallible> {
+    if (request.method(), request.uri().path()) != (&Method::POST, "/sync") {
+        return Ok(control_response(StatusCode::NOT_FOUND, &"Not found"));
+    }
+
+    let (trigger, outcome) = oneshot::channel();
+    let outcome = match triggers.send(trigger).await {
+        Ok(()) => outcome.await.ok(),
+        Err(_) => None,
+    };
+    Ok(match outcome {
+        Some(Ok(outcome)) => control_response(StatusCode::OK, &outcome),
+        Some(Err(e)) => control_response(StatusCode::INTERNAL_SERVER_ERROR, &e),
+        None => control_response(
+            StatusCode::SERVICE_UNAVAILABLE,
+            &"Sync utility is shutting down",
+        ),
+    })
+}
+
+fn control_response(status: StatusCode, body: &impl Serialize) -> Response<Body> {
+    let mut response = Response::new(Body::from(serde_json::to_vec(body).unwrap()));
+    *response.status_mut() = status;
+    response
+}
+
+/// Runs the metrics endpoint of the sync utility on the given address.
+async fn run_metrics_api(address: SocketAddr, metrics: SyncMetrics) {
+    let make_service = make_service_fn(move |_| {
+        let metrics = metrics.clone();
+        async move {
+            Ok::<_, Infallible>(service_fn(move |request: Request<Body>| {
+                let response =
+                    if (request.method(), request.uri().path()) == (&Method::GET, "/metrics") {
+                        Response::new(Body::from(metrics.encode()))
+                    } else {
+                        control_response(StatusCode::NOT_FOUND, &"Not found")
+                    };
+                async move { Ok::<_, Infallible>(response) }
+            }))
+        }
+    });
+
+    log::info!("Starting the sync utility metrics endpoint on {}", address);
+    if let Err(e) = HttpServer::bind(&address).serve(make_service).await {
+        log::error!(
+            "An error in the sync utility metrics endpoint occurred. {}",
+            e
+        );
+    }
+}
+
+/// Waits for the Bitcoin network event which requires the sync iteration.
+async fn wait_bitcoin_event(notifications: &mut BitcoinNotifications, queue: &BroadcastQueue) {
+    loop {
+        match notifications.next().await {
+            BitcoinNotification::Block(hash) => {
+                log::info!(
+                    "Sync iteration has been triggered by the new block {}.",
+                    hash
+                );
+                return;
+            }
+            // The transaction from the broadcast queue has been relayed by someone else.
+            BitcoinNotification::Transaction(txid) if queue.contains(txid) => {
+                log::info!(
+                    "Sync iteration has been triggered by the transaction {}.",
+                    txid
+                );
+                return;
+            }
+            BitcoinNotification::Transaction(_) => {}
+        }
+    }
+}
+
+/// Waits for the `Ctrl+C` or, on Unix, `SIGTERM` signal.
+async fn shutdown_signal() {
+    #[cfg(unix)]
+    {
+        use tokio::signal::unix::{signal, SignalKind};
+
+        match signal(SignalKind::terminate()) {
+            Ok(mut terminate) => {
+                tokio::select! {
+                    _ = tokio::signal::ctrl_c() => {},
+                    _ = terminate.recv() => {},
+                };
+                return;
+            }
+            Err(e) => log::warn!("Unable to install SIGTERM handler: {}", e),
+        }
+    }
+
+    if let Err(e) = tokio::signal::ctrl_c().await {
+        log::warn!("Unable to install Ctrl+C handler: {}", e);
+        futures::future::pending::<()>().await;
+    }
+}
+
+/// Returns the chain name of the given network as reported by the Bitcoin RPC.
+fn rpc_chain_name(network: bitcoin::Network) -> &'static str {
+    match network {
+        bitcoin::Network::Bitcoin => "main",
+        bitcoin::Network::Testnet => "test",
+        bitcoin::Network::Regtest => "regtest",
+    }
+}
+
+impl GenerateConfigCommand {
+    fn run(mut self) -> anyhow::Result<()> {
+        let bitcoin_key = if self.watch_only {
+            None
+        } else {
+            Some(generate_keystore(
+                &self.keystore,
+                self.bitcoin_network,
+                self.keystore_pass.take().unwrap_or_default(),
+            )?)
+        };
+
+        let bitcoin_rpc_config = self.bitcoin_rpc_config();
+        let sync_config = SyncConfig {
+            exonum_private_api: self.exonum_private_api,
+            bitcoin_keystores: if self.watch_only {
+                Vec::new()
+            } else {
+                vec![self.keystore]
+            },
+            legacy_key_pool: HashMap::new(),
+            bitcoin_key_pool: HashMap::new(),
+            instance_name: self.instance_name,
+            bitcoin_rpc_config,
+            broadcast_rpc_configs: Vec::new(),
+            broadcast_queue: None,
+            sync_state: None,
+            relay_audit_log: None,
+            funding_wallet: None,
+            top_up_policy: None,
+            fee_rate_policy: None,
+            sync_interval: self.sync_interval,
+            min_confirmations: 0,
+            watch_only: self.watch_only,
+            control_api_address: None,
+            metrics_address: None,
+            zmq_notifications: None,
+            proxy: None,
+            hwi: None,
+            remote_signer: None,
+            #[cfg(feature = "pkcs11")]
+            pkcs11: None,
+        };
+
+        sync_config.save(self.output)?;
+        log::info!("Generated initial configuration for the btc anchoring sync util.");
+        // Print the received Bitcoin public key to use it in scripts.
+        if let Some(bitcoin_key) = bitcoin_key {
+            println!("{}", bitcoin_key);
+        }
+        Ok(())
+    }
+
+    fn bitcoin_rpc_config(&self) -> Option<BitcoindRelayConfig> {
+        self.bitcoin_rpc_host
+            .clone()
+            .map(|host| BitcoindRelayConfig {
+                host,
+                user: self.bitcoin_rpc_user.clone(),
+                password: self.bitcoin_rpc_password.clone(),
+                ..BitcoindRelayConfig::default()
+            })
+    }
+}
+
+/// Decrypts the Bitcoin keystores of the sync utility configurations.
+///
+/// The passphrase is requested only once, when the first configuration with
+/// the keystores is loaded, and is reused on the configuration reloads.
+struct KeystoreUnlocker {
+    pass_input: Option<PassInputMethod>,
+    passphrase: Option<Passphrase>,
+}
+
+impl KeystoreUnlocker {
+    fn new(pass_input: Option<PassInputMethod>) -> Self {
+        Self {
+            pass_input,
+            passphrase: None,
+        }
+    }
+
+    fn unlock(&mut self, mut sync_config: SyncConfig) -> anyhow::Result<SyncConfig> {
+        if sync_config.bitcoin_keystores.is_empty() {
+            sync_config.unlock_keys(&Passphrase::default())?;
+            return Ok(sync_config);
+        }
+
+        if self.passphrase.is_none() {
+            let pass_input = self.pass_input.take().unwrap_or_default();
+            self.passphrase = Some(pass_input.get_passphrase(PassphraseUsage::Using)?);
+        }
+        if let Some(passphrase) = &self.passphrase {
+            sync_config.unlock_keys(passphrase)?;
+        }
+        Ok(sync_config)
+    }
+}
+
+impl RunCommand {
+    async fn run(self) -> anyhow::Result<()> {
+        let path = self.config;
+        let instances = SyncConfig::load_instances(&path)?;
+        let unlocker = Arc::new(Mutex::new(KeystoreUnlocker::new(self.keystore_pass)));
+        // Signal handlers are installed right now, so the signal received during
+        // the sync iteration stops the utility as soon as the iteration is completed.
+        let (shutdown_sender, shutdown) = oneshot::channel();
+        tokio::spawn(async move {
+            shutdown_signal().await;
+            let _ = shutdown_sender.send(());
+        });
+        let shutdown = shutdown.map(drop).boxed().shared();
+
+        // The single instance is allowed to be renamed without restarting.
+        if instances.len() == 1 {
+            let config_watcher = ConfigWatcher::new(path, None);
+            return run_instance(config_watcher, unlocker, shutdown).await;
+        }
+
+        // Each instance is synchronized by its own task, so the failure of one of them
+        // doesn't affect the other ones.
+        let instances = instances.into_iter().map(|instance| {
+            let name = instance.instance_name;
+            let config_watcher = ConfigWatcher::new(path.clone(), Some(name.clone()));
+            // Events of the sync tasks are attributed to the instance via the span.
+            let sync = run_instance(config_watcher, unlocker.clone(), shutdown.clone())
+                .instrument(tracing::info_span!("anchoring_instance", instance = %name));
+            tokio::spawn(async move {
+                let result = sync.await;
+                if let Err(e) = &result {
+                    log::error!(
+                        "Sync of the anchoring instance `{}` has failed. {}",
+                        name,
+                        e
+                    );
+                }
+                result.map_err(|_| name)
+            })
+        });
+        let failed = join_all(instances)
+            .await
+            .into_iter()
+            .filter_map(|result| match result {
+                Ok(result) => result.err(),
+                Err(e) => Some(e.to_string()),
+            })
+            .collect::<Vec<_>>();
+        ensure!(
+            failed.is_empty(),
+            "Sync of the anchoring instances has failed: {}",
+            failed.join(", ")
+        );
+        Ok(())
+    }
+}
+
+/// Runs the sync iterations of a single anchoring instance until the shutdown signal.
+async fn run_instance(
+    mut config_watcher: ConfigWatcher,
+    unlocker: Arc<Mutex<KeystoreUnlocker>>,
+    shutdown: Shared<BoxFuture<'static, ()>>,
+) -> anyhow::Result<()> {
+    let sync_config = unlock(&unlocker, config_watcher.load()?)?;
+    // Control API is started once, so changes of its address require restarting.
+    // The sender is kept alive to not close the channel if the API is disabled.
+    let (trigger_sender, mut triggers) = mpsc::channel(1);
+    if let Some(address) = sync_config.control_api_address {
+        tokio::spawn(run_control_api(address, trigger_sender.clone()));
+    }
+    // ZMQ subscriptions are also established once and are restored by themselves
+    // if the Bitcoin node is unavailable.
+    let mut notifications = BitcoinNotifications::spawn(
+        &sync_config.zmq_notifications.clone().unwrap_or_default(),
+        Duration::from_secs(sync_config.sync_interval),
+    );
+    // Metrics are kept across the configuration reloads.
+    let metrics = SyncMetrics::new();
+    if let Some(address) = sync_config.metrics_address {
+        tokio::spawn(run_metrics_api(address, metrics.clone()));
+    }
+    let mut tasks = SyncTasks::new(sync_config, metrics.clone()).await?;
+
+    let mut latest_synced_tx_index = match tasks.bitcoin_relay.as_ref() {
+        Some(relay) => relay.restore_state().await.unwrap_or_else(|e| {
+            log::warn!(
+                "Unable to restore the sync state, the whole anchoring chain \
+                 will be checked. {:?}",
+                e
+            );
+            None
+        }),
+        None => None,
+    };
+    let mut pending_top_up: Option<PendingTopUp> = None;
+    let mut trigger: Option<SyncTrigger> = None;
+    loop {
+        // Apply changes in the configuration file without losing the sync state.
+        if let Some(sync_config) = config_watcher.poll() {
+            let new_tasks = match sync_config.and_then(|config| unlock(&unlocker, config)) {
+                Ok(sync_config) => SyncTasks::new(sync_config, metrics.clone()).await,
+                Err(e) => Err(e),
+            };
+            match new_tasks {
+                Ok(mut new_tasks) => {
+                    // Synced index makes sense only for the same anchoring instance.
+                    if new_tasks.api_prefix != tasks.api_prefix {
+                        latest_synced_tx_index = None;
+                        pending_top_up = None;
+                    } else if new_tasks.broadcast_queue.path().is_none() {
+                        // Keep the transactions from the in-memory queue.
+                        new_tasks.broadcast_queue = tasks.broadcast_queue;
+                    }
+                    tasks = new_tasks;
+                    log::info!("Sync utility configuration has been reloaded.");
+                }
+                Err(e) => log::error!(
+                    "Unable to reload sync utility configuration, \
+                     the previous one will be used. {}",
+                    e
+                ),
+            }
+        }
+
+        let outcome = tasks
+            .process(&mut latest_synced_tx_index, &mut pending_top_up)
+            .await;
+        if let Some(trigger) = trigger.take() {
+            let _ = trigger.send(match &outcome {
+                Ok(outcome) => Ok(outcome.clone()),
+                Err(e) => Err(e.to_string()),
+            });
+        }
+        outcome?;
+        // Don't perform this actions too frequent to avoid DOS attack, but perform
+        // the sync iteration requested via the control API or caused by the Bitcoin
+        // network events right now.
+        let bitcoin_event = wait_bitcoin_event(&mut notifications, &tasks.broadcast_queue);
+        trigger = tokio::select! {
+            _ = delay_for(tasks.sync_interval) => None,
+            _ = bitcoin_event => None,
+            trigger = triggers.recv() => trigger,
+            _ = shutdown.clone() => {
+                log::info!("Sync utility has been stopped.");
+                return Ok(());
+            }
+        };
+        if trigger.is_some() {
+            log::info!("Sync iteration has been triggered via the control API.");
+        }
+    }
+}
+
+/// Decrypts the keystores of the given configuration; the passphrase is shared
+/// by all anchoring instances.
+fn unlock(
+    unlocker: &Mutex<KeystoreUnlocker>,
+    sync_config: SyncConfig,
+) -> anyhow::Result<SyncConfig> {
+    unlocker.lock().unwrap().unlock(sync_config)
+}
+
+impl GenerateKeypairCommand {
+    fn run(self) -> anyhow::Result<()> {
+        let mut sync_config = SyncConfig::load(&self.config)?;
+
+        let network = sync_config
+            .keystores()?
+            .first()
+            .map(|keystore| keystore.network)
+            .ok_or_else(|| {
+                anyhow!(
+                    "Unable to determine Bitcoin network type from config. \
+                     Perhaps the list of keystores in config is empty."
+                )
+            })?;
+        let bitcoin_pub_key = generate_keystore(
+            &self.keystore,
+            network,
+            self.keystore_pass.unwrap_or_default(),
+        )?;
+
+        sync_config.bitcoin_keystores.push(self.keystore);
+        sync_config.save(self.config)?;
+        // Print the received Bitcoin public key to use it in scripts.
+        println!("{}", bitcoin_pub_key);
+        Ok(())
+    }
+}
+
+impl EncryptKeyPoolCommand {
+    fn run(self) -> anyhow::Result<()> {
+        let mut sync_config = SyncConfig::load(&self.config)?;
+        ensure!(
+            !sync_config.legacy_key_pool.is_empty(),
+            "There are no plain text Bitcoin private keys in the configuration file."
+        );
+        let passphrase = self
+            .keystore_pass
+            .unwrap_or_default()
+            .get_passphrase(PassphraseUsage::SettingUp)?;
+
+        fs::create_dir_all(&self.output_dir)?;
+        for (public_key, private_key) in std::mem::take(&mut sync_config.legacy_key_pool) {
+            let path = self.output_dir.join(format!("{}.toml", public_key));
+            ensure!(
+                !path.exists(),
+                "Keystore file {} already exists",
+                path.display()
+            );
+            EncryptedPrivateKey::encrypt(&private_key, passphrase.as_bytes())?.save(&path)?;
+            log::info!(
+                "Bitcoin private key {} has been moved to {}",
+                public_key,
+                path.display()
+            );
+            sync_config.bitcoin_keystores.push(path);
+        }
+        // Plain text keys are not serialized anymore.
+        sync_config.save(self.config)?;
+        Ok(())
+    }
+}
+
+impl GenerateKeystoreCommand {
+    fn run(self) -> anyhow::Result<()> {
+        let bitcoin_key = generate_keystore(
+            &self.output,
+            self.bitcoin_network,
+            self.keystore_pass.unwrap_or_default(),
+        )?;
+        // Print the anchoring keys to paste them into the configuration proposal.
+        let anchoring_keys = AnchoringKeys {
+            bitcoin_key,
+            service_key: self.service_key,
+        };
+        println!("{}", serde_json::to_string_pretty(&anchoring_keys)?);
+        Ok(())
+    }
+}
+
+/// Generates a new Bitcoin key pair, writes the private key encrypted by the passphrase
+/// to the new keystore file and returns the public key.
+fn generate_keystore(
+    path: &Path,
+    network: bitcoin::Network,
+    pass_input: PassInputMethod,
+) -> anyhow::Result<btc::PublicKey> {
+    ensure!(
+        !path.exists(),
+        "Keystore file {} already exists",
+        path.display()
+    );
+    let passphrase = pass_input.get_passphrase(PassphraseUsage::SettingUp)?;
+
+    let (bitcoin_key, private_key) = btc::gen_keypair(network);
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir)?;
+    }
+    EncryptedPrivateKey::encrypt(&private_key, passphrase.as_bytes())?.save(path)?;
+    log::info!("Bitcoin private key has been saved to {}", path.display());
+    Ok(bitcoin_key)
+}
+
+impl InspectConfigCommand {
+    async fn run(self) -> anyhow::Result<()> {
+        let config = match (self.config, self.exonum_public_api) {
+            (Some(path), _) => Self::read_config(&path)?,
+            (None, Some(public_api)) => {
+                ApiClient::new(public_api, self.instance_name)
+                    .get("config")
+                    .await?
+            }
+            (None, None) => bail!("Either configuration file or public API should be specified"),
+        };
+        config.validate()?;
+
+        let redeem_script = config.redeem_script();
+        let content = redeem_script.content();
+        println!("Network: {}", config.network);
+        println!(
+            "Quorum: {} of {}",
+            content.quorum,
+            content.public_keys.len()
+        );
+        println!("Anchoring keys:");
+        for (index, keys) in config.anchoring_keys.iter().enumerate() {
+            println!(
+                "  {}: bitcoin_key {}, service_key {}",
+                index,
+                keys.bitcoin_key,
+                hex::encode(keys.service_key)
+            );
+        }
+        println!(
+            "Redeem script: {}",
+            hex::encode(redeem_script.as_ref().as_bytes())
+        );
+        println!("Redeem script asm: {}", redeem_script.as_ref().asm());
+        if config.p2sh_wrapped {
+            println!("P2SH-P2WSH address: {}", config.anchoring_address());
+        } else {
+            println!("P2WSH address: {}", config.anchoring_address());
+        }
+        println!(
+            "Output script: {}",
+            hex::encode(config.anchoring_out_script().as_bytes())
+        );
+        Ok(())
+    }
+
+    fn read_config(path: &Path) -> anyhow::Result<AnchoringConfig> {
+        let content = fs::read_to_string(path)?;
+        if path.extension() == Some("toml".as_ref()) {
+            Ok(toml::from_str(&content)?)
+        } else {
+            Ok(serde_json::from_str(&content)?)
+        }
+    }
+}
+
+impl ExportStateCommand {
+    async fn run(self) -> anyhow::Result<()> {
+        let client = ApiClient::new(self.exonum_public_api, self.instance_name);
+        let archive: AnchoringArchive = client.get("backup").await?;
+        archive.verify()?;
+        archive.write_to(File::create(&self.output)?)?;
+
+        log::info!(
+            "Exported {} anchoring transactions.",
+            archive.state.transactions.len()
+        );
+        // Print the archive digest to use it in scripts.
+        println!("{}", archive.digest);
+        Ok(())
+    }
+}
+
+impl ImportStateCommand {
+    fn run(self) -> anyhow::Result<()> {
+        let archive = AnchoringArchive::read_from(File::open(&self.archive)?)?;
+        let digest = archive.digest;
+
+        let db = RocksDB::open(&self.db_path, &DbOptions::default())?;
+        let fork = db.fork();
+        archive
+            .state
+            .import(&mut Schema::new(Prefixed::new(&self.instance_name, &fork)))?;
+        db.merge(fork.into_patch())?;
+
+        log::info!("Imported anchoring state into {}.", self.db_path.display());
+        println!("{}", digest);
+        Ok(())
+    }
+}
+
+impl ExportProofCommand {
+    async fn run(self) -> anyhow::Result<()> {
+        let height = Height(self.height);
+        let client = ApiClient::new(&self.exonum_public_api, &self.instance_name);
+        let proof: Option<BlockHeaderProof> = client
+            .get_query("block-header-proof", &BlockHeightQuery { height })
+            .await?;
+        let proof = proof.ok_or_else(|| {
+            anyhow!(
+                "Block at height {} doesn't exist or is not anchored yet.",
+                height
+            )
+        })?;
+        let consensus_config: ConsensusConfig =
+            ApiClient::new(&self.exonum_public_api, Supervisor::NAME)
+                .get("consensus-config")
+                .await?;
+        let validator_keys = consensus_config
+            .validator_keys
+            .into_iter()
+            .map(|keys| keys.consensus_key)
+            .collect::<Vec<_>>();
+
+        let bundle = AnchoringProofBundle::new(&self.instance_name, validator_keys, proof)?;
+        // Make sure that the proof is consistent before writing it.
+        bundle.verify(&bundle.validator_keys)?;
+        serde_json::to_writer_pretty(File::create(&self.output)?, &bundle)?;
+
+        log::info!(
+            "Block at height {} is anchored by the transaction {}.",
+            height,
+            bundle.anchoring_transaction.id()
+        );
+        // Print the anchoring transaction identifier to use it in scripts.
+        println!("{}", bundle.anchoring_transaction.id());
+        Ok(())
+    }
+}
+
+impl VerifyChainCommand {
+    fn run(self) -> anyhow::Result<()> {
+        let db = RocksDB::open(&self.db_path, &DbOptions::default())?;
+        let snapshot = db.snapshot();
+        let count = verify_anchoring_chain(Prefixed::new(&self.instance_name, &snapshot))?;
+
+        log::info!("Verified {} anchoring transactions.", count);
+        println!("{}", count);
+        Ok(())
+    }
+}
+
+impl SyncConfig {
+    /// Creates the anchoring funding task, if the funding wallet is configured.
+    fn funding_task(self) -> anyhow::Result<AnchoringFundingTask<ApiClient, BitcoindRelay>> {
+        let wallet = self.funding_wallet.ok_or_else(|| {
+            anyhow!("Funding wallet is not configured. Check the `funding_wallet` section.")
+        })?;
+        let client = ApiClient::new(self.exonum_private_api, self.instance_name);
+        Ok(AnchoringFundingTask::new(
+            BitcoindRelay::new(&wallet)?,
+            client,
+        ))
+    }
+}
+
+impl WalletBalanceCommand {
+    async fn run(self) -> anyhow::Result<()> {
+        let funding = SyncConfig::load(&self.config)?.funding_task()?;
+        println!("{}", funding.balance().await?);
+        Ok(())
+    }
+}
+
+impl FundCommand {
+    async fn run(self) -> anyhow::Result<()> {
+        let funding = SyncConfig::load(&self.config)?.funding_task()?;
+        let transaction = funding.fund(self.amount).await.map_err(funding_error)?;
+
+        log::info!(
+            "Funding transaction {} has been sent, anchoring nodes should vote for it \
+             using the `add-funds` command once it has the number of confirmations \
+             required by the anchoring configuration.",
+            transaction.id()
+        );
+        // Print the raw funding transaction to use it in scripts.
+        println!("{}", transaction);
+        Ok(())
+    }
+}
+
+impl PrepareFundingCommand {
+    async fn run(self) -> anyhow::Result<()> {
+        let funding = SyncConfig::load(&self.config)?.funding_task()?;
+        let payload = funding
+            .prepare(self.amount, self.broadcast)
+            .await
+            .map_err(funding_error)?;
+
+        if !self.broadcast {
+            log::info!(
+                "Funding transaction {} has not been broadcast, send it to the Bitcoin \
+                 network before submitting the `AddFunds` payload.",
+                payload.transaction.id()
+            );
+        }
+        let payload = FundingPayload {
+            transaction_id: payload.transaction.id(),
+            add_funds: hex::encode(payload.to_bytes()),
+            transaction: payload.transaction,
+        };
+        println!("{}", serde_json::to_string_pretty(&payload)?);
+        Ok(())
+    }
+}
+
+fn funding_error<C: Display, W: Display>(e: FundingError<C, W>) -> anyhow::Error {
+    match e {
+        FundingError::Client(e) => anyhow!("An error in the anchoring API client occurred. {}", e),
+        FundingError::Wallet(e) => anyhow!("An error in the funding wallet occurred. {}", e),
+        FundingError::InsufficientBalance { amount, balance } => anyhow!(
+            "Insufficient balance of the funding wallet to send {} satoshis, \
+             total balance is {}",
+            amount,
+            balance
+        ),
+    }
+}
+
+impl BroadcastQueueCommand {
+    fn run(self) -> anyhow::Result<()> {
+        let path = SyncConfig::load(&self.config)?
+            .broadcast_queue
+            .ok_or_else(|| {
+                anyhow!(
+                    "Broadcast queue is kept in memory of the running sync utility. \
+                 Specify the `broadcast_queue` option to make it persistent."
+                )
+            })?;
+        let queue = BroadcastQueue::load(path)?;
+        println!("{}", serde_json::to_string_pretty(queue.entries())?);
+        Ok(())
+    }
+}
+
+impl DryRunCommand {
+    async fn run(self) -> anyhow::Result<()> {
+        let sync_config = KeystoreUnlocker::new(self.keystore_pass)
+            .unlock(SyncConfig::load(&self.config)?)?
+            .with_common_proxy();
+        ensure!(
+            !sync_config.watch_only,
+            "Watch-only sync utility doesn't sign the anchoring proposals."
+        );
+        sync_config.validate().await?;
+
+        let client = ApiClient::new(&sync_config.exonum_private_api, &sync_config.instance_name);
+        let chain_updater = SyncTasks::chain_updater(
+            &sync_config,
+            &client,
+            &SyncMetrics::new(),
+            &sync_config.relay_audit_log()?,
+        )?;
+        let report = match chain_updater.process_dry_run().await {
+            Ok(report) => report,
+            Err(ChainUpdateError::Client(e)) => {
+                bail!("An error in the anchoring API client occurred. {}", e)
+            }
+            Err(ChainUpdateError::InsufficientFunds { total_fee, balance }) => bail!(
+                "Insufficient funds to construct a new anchoring transaction, \
+                 total fee is {}, total balance is {}",
+                total_fee,
+                balance
+            ),
+            Err(ChainUpdateError::NoInitialFunds) => {
+                bail!("Initial funding transaction is absent.")
+            }
+            Err(ChainUpdateError::Internal(e)) => return Err(e),
+        };
+
+        match &report {
+            Some(report) if report.bitcoin_key.is_none() => log::warn!(
+                "None of the Bitcoin keys of this node is able to sign the anchoring proposal."
+            ),
+            Some(_) => {}
+            None => log::info!("There is no anchoring proposal to sign."),
+        }
+        println!("{}", serde_json::to_string_pretty(&report)?);
+        Ok(())
+    }
+}
+
+impl AddFundsCommand {
+    async fn run(self) -> anyhow::Result<()> {
+        let sync_config = SyncConfig::load(&self.config)?;
+        let rpc_config = sync_config.bitcoin_rpc_config.ok_or_else(|| {
+            anyhow!("Bitcoin RPC is not configured. Check the `bitcoin_rpc_config` section.")
+        })?;
+        let client = ApiClient::new(sync_config.exonum_private_api, sync_config.instance_name);
+        let relay = SyncWithBitcoinTask::new(BitcoindRelay::new(&rpc_config)?, client);
+
+        let txid = self.transaction.id();
+        let hash = match relay.add_funds(self.transaction).await {
+            Ok(hash) => hash,
+            Err(SyncWithBitcoinError::Client(e)) => {
+                bail!("An error in the anchoring API client occurred. {}", e)
+            }
+            Err(SyncWithBitcoinError::Relay(e)) => {
+                bail!("An error in the Bitcoin relay occurred. {}", e)
+            }
+            Err(SyncWithBitcoinError::UnconfirmedFundingTransaction(_)) => bail!(
+                "Funding transaction {} doesn't have enough confirmations yet, \
+                 try again later.",
+                txid
+            ),
+            Err(SyncWithBitcoinError::Internal(e)) => return Err(e),
+            Err(SyncWithBitcoinError::TransactionMismatch { .. }) => {
+                unreachable!("Funding transaction is never compared with the anchoring chain")
+            }
+        };
+
+        log::info!("Vote for the funding transaction {} has been sent.", txid);
+        println!("{}", hash);
+        Ok(())
+    }
+}
+
+impl RecoverAnchoringCommand {
+    async fn run(self) -> anyhow::Result<()> {
+        let sync_config = SyncConfig::load(&self.config)?;
+        let client = ApiClient::new(sync_config.exonum_private_api, sync_config.instance_name);
+        let hash = client
+            .recover_anchoring(self.txid)
+            .await
+            .map_err(|e| anyhow!("An error in the anchoring API client occurred. {}", e))?;
+
+        log::info!(
+            "Vote for the recovery of the anchoring chain has been sent, other anchoring \
+             nodes should vote for it as well. Send the fresh funding transaction to \
+             the anchoring address to start the new chain segment."
+        );
+        println!("{}", hash);
+        Ok(())
+    }
+}
+
+impl Commands {
+    async fn run(self) -> anyhow::Result<()> {
+        match self {
+            Commands::GenerateConfig(cmd) => cmd.run(),
+            Commands::GenerateKeypair(cmd) => cmd.run(),
+            Commands::EncryptKeyPool(cmd) => cmd.run(),
+            Commands::GenerateKeystore(cmd) => cmd.run(),
+            Commands::InspectConfig(cmd) => cmd.run().await,
+            Commands::Run(cmd) => cmd.run().await,
+            Commands::ExportState(cmd) => cmd.run().await,
+            Commands::ImportState(cmd) => cmd.run(),
+            Commands::ExportProof(cmd) => cmd.run().await,
+            Commands::VerifyChain(cmd) => cmd.run(),
+            Commands::WalletBalance(cmd) => cmd.run().await,
+            Commands::Fund(cmd) => cmd.run().await,
+            Commands::PrepareFunding(cmd) => cmd.run().await,
+            Commands::AddFunds(cmd) => cmd.run().await,
+            Commands::DryRun(cmd) => cmd.run().await,
+            Commands::BroadcastQueue(cmd) => cmd.run(),
+            Commands::RecoverAnchoring(cmd) => cmd.run().await,
+        }
+    }
+}
+
+#[tokio::main]
+async fn main() -> anyhow::Result<()> {
+    exonum::helpers::init_logger()?;
+    Commands::from_args().run().await
+}
+
+mod flatten_keypairs {
+    use crate::btc::{PrivateKey, PublicKey};
+
+    use serde_derive::Deserialize;
+
+    use std::collections::HashMap;
+
+    /// The structure for storing the bitcoin keypair.
+    /// It is required for reading data from the .toml file into memory.
+    #[derive(Deserialize)]
+    struct BitcoinKeypair {
+        /// Bitcoin public key.
+        public_key: PublicKey,
+        /// Corresponding private key.
+        private_key: PrivateKey,
+    }
+
+    pub fn deserialize<'de, D>(deserializer: D) -> Result<HashMap<PublicKey, PrivateKey>, D::Error>
+    where
+        D: serde::Deserializer<'de>,
+    {
+        use serde::Deserialize;
+        Vec::<BitcoinKeypair>::deserialize(deserializer).map(|keypairs| {
+            keypairs
+                .into_iter()
+                .map(|keypair| (keypair.public_key, keypair.private_key))
+                .collect()
+        })
+    }
+}
@@ -0,0 +1,196 @@
+// Copyright 2020 The Exonum Team
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Export and import of the anchoring state.
+//!
+//! The anchoring state is exported into a portable archive which contains the digest
+//! of its content, so the archive integrity can be verified before the import.
+
+use exonum::crypto::{self, Hash};
+use exonum_merkledb::access::{Access, RawAccessMut};
+use serde_derive::{Deserialize, Serialize};
+use thiserror::Error;
+
+use std::io::{Read, Write};
+
+use super::{data_layout::AnchoringIntervalChange, Schema};
+use crate::{btc, config::Config};
+
+/// Anchoring-related part of the service schema.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct AnchoringBackup {
+    /// Complete chain of the anchoring transactions.
+    pub transactions: Vec<btc::Transaction>,
+    /// Actual anchoring configuration.
+    pub actual_config: Config,
+    /// Following anchoring configuration, if any.
+    pub following_config: Option<Config>,
+    /// Changes of the anchoring interval.
+    pub anchoring_schedule: Vec<AnchoringIntervalChange>,
+    /// Unspent funding transaction, if any.
+    pub unspent_funding_transaction: Option<btc::Transaction>,
+    /// Already spent funding transactions.
+    pub spent_funding_transactions: Vec<btc::Transaction>,
+}
+
+/// Portable archive with the anchoring state.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct AnchoringArchive {
+    /// Version of the archive format.
+    pub version: u32,
+    /// Digest of the archived state.
+    pub digest: Hash,
+    /// Archived anchoring state.
+    pub state: AnchoringBackup,
+}
+
+/// Errors which may occur during the anchoring state export or import.
+#[derive(Debug, Error)]
+pub enum BackupError {
+    /// Input or output error.
+    #[error("I/O error: {0}")]
+    Io(#[from] std::io::Error),
+    /// Archive has an invalid format.
+    #[error("Invalid archive format: {0}")]
+    Format(#[from] serde_json::Error),
+    /// Archive format version is not supported.
+    #[error("Unsupported archive version {0}")]
+    UnsupportedVersion(u32),
+    /// Digest of the archived state doesn't match the declared one.
+    #[error("Archive digest mismatch, expected {expected}, actual {actual}")]
+    DigestMismatch {
+        /// Digest declared in the archive.
+        expected: Hash,
+        /// Digest of the archived state.
+        actual: Hash,
+    },
+    /// Anchoring chain in the target schema is not empty.
+    #[error("Anchoring chain in the target schema is not empty")]
+    NonEmptySchema,
+    /// Archived anchoring transaction is malformed.
+    #[error("Anchoring transaction with index {0} is malformed")]
+    MalformedTransaction(u64),
+}
+
+impl AnchoringBackup {
+    /// Exports the anchoring state from the given schema.
+    pub fn export<T: Access>(schema: &Schema<T>) -> Self {
+        Self {
+            transactions: schema.transactions_chain.iter().collect(),
+            actual_config: schema.actual_config(),
+            following_config: schema.following_config(),
+            anchoring_schedule: schema.anchoring_schedule.iter().collect(),
+            unspent_funding_transaction: schema.unspent_funding_transaction(),
+            spent_funding_transactions: schema.spent_funding_transactions.values().collect(),
+        }
+    }
+
+    /// Returns the digest of the anchoring state.
+    pub fn digest(&self) -> Hash {
+        let bytes = serde_json::to_vec(self).expect("Unable to serialize anchoring state");
+        crypto::hash(&bytes)
+    }
+
+    /// Imports the anchoring state into the given schema. The anchoring chain in
+    /// the schema must be empty.
+    pub fn import<T>(self, schema: &mut Schema<T>) -> Result<(), BackupError>
+    where
+        T: Access,
+        T::Base: RawAccessMut,
+    {
+        if !schema.transactions_chain.is_empty() {
+            return Err(BackupError::NonEmptySchema);
+        }
+        for (index, tx) in self.transactions.iter().enumerate() {
+            if tx
+                .anchoring_payload_with(&btc::StrictPayloadCodec)
+                .is_none()
+            {
+                return Err(BackupError::MalformedTransaction(index as u64));
+            }
+        }
+
+        schema.frozen.set(self.actual_config.frozen);
+        schema.actual_config.set(self.actual_config);
+        if let Some(config) = self.following_config {
+            schema.following_config.set(config);
+        }
+        schema.anchoring_schedule.clear();
+        schema.anchoring_schedule.extend(self.anchoring_schedule);
+        for tx in self.transactions {
+            schema.append_anchoring_transaction(tx);
+        }
+        for tx in self.spent_funding_transactions {
+            // Spending anchoring transactions are not archived, so look for them in the chain.
+            let spender = schema.transactions_chain.iter().position(|anchoring_tx| {
+                anchoring_tx
+                    .0
+                    .input
+                    .iter()
+                    .any(|input| input.previous_output.txid == tx.0.txid())
+            });
+            if let Some(index) = spender {
+                schema
+                    .funding_transaction_spenders
+                    .put(&tx.id(), index as u64);
+            }
+            schema.spent_funding_transactions.put(&tx.id(), tx);
+        }
+        if let Some(tx) = self.unspent_funding_transaction {
+            schema.set_funding_transaction(tx);
+        }
+        Ok(())
+    }
+}
+
+impl AnchoringArchive {
+    /// Current version of the archive format.
+    pub const VERSION: u32 = 1;
+
+    /// Creates an archive with the given anchoring state.
+    pub fn new(state: AnchoringBackup) -> Self {
+        Self {
+            version: Self::VERSION,
+            digest: state.digest(),
+            state,
+        }
+    }
+
+    /// Verifies the archive version and digest.
+    pub fn verify(&self) -> Result<(), BackupError> {
+        if self.version != Self::VERSION {
+            return Err(BackupError::UnsupportedVersion(self.version));
+        }
+        let actual = self.state.digest();
+        if actual != self.digest {
+            return Err(BackupError::DigestMismatch {
+                expected: self.digest,
+                actual,
+            });
+        }
+        Ok(())
+    }
+
+    /// Writes the archive to the given writer.
+    pub fn write_to(&self, writer: impl Write) -> Result<(), BackupError> {
+        serde_json::to_writer_pretty(writer, self).map_err(BackupError::from)
+    }
+
+    /// Reads the archive from the given reader and verifies it.
+    pub fn read_from(reader: impl Read) -> Result<Self, BackupError> {
+        let archive: Self = serde_json::from_reader(reader)?;
+        archive.verify()?;
+        Ok(archive)
+    }
+}
@@ -15,8 +15,17 @@
 //! Additional data types for the BTC anchoring information schema.
 
 use byteorder::{LittleEndian, ReadBytesExt, WriteBytesExt};
-use exonum::crypto::{self, Hash};
+use exonum::{
+    crypto::{self, Hash},
+    helpers::Height,
+};
 use exonum_merkledb::{BinaryKey, ObjectHash};
+use serde_derive::{Deserialize, Serialize};
+
+pub use crate::proto::{
+    AnchoringDelay, AnchoringIntervalChange, AnchoringProposalInfo, AnchoringStats, ConfigChange,
+    SigningStats, TransitionState,
+};
 
 use crate::btc::Sha256d;
 
@@ -72,6 +81,144 @@ impl ObjectHash for TxInputId {
     }
 }
 
+impl SigningStats {
+    /// Maximum number of the latest finalized proposals taken into account
+    /// in the rolling window.
+    pub const WINDOW_SIZE: u64 = 64;
+
+    /// Records the participation of the node in signing of the finalized proposal.
+    pub fn record(&mut self, signed: bool) {
+        self.recent_participation = (self.recent_participation << 1) | signed as u64;
+        self.total_proposals += 1;
+        if signed {
+            self.signed_proposals += 1;
+        }
+    }
+
+    /// Returns the number of finalized proposals in the rolling window.
+    pub fn window_proposals(&self) -> u64 {
+        self.total_proposals.min(Self::WINDOW_SIZE)
+    }
+
+    /// Returns the number of finalized proposals in the rolling window signed by the node.
+    pub fn window_signed_proposals(&self) -> u64 {
+        u64::from(self.recent_participation.count_ones())
+    }
+}
+
+impl AnchoringStats {
+    /// Records the finalized anchoring transaction.
+    pub fn record(&mut self, fee: u64, size: u64) {
+        self.transactions_count += 1;
+        self.total_fee += fee;
+        self.total_size += size;
+    }
+
+    /// Records the anchoring transaction which became final the given number of blocks
+    /// after its finalization.
+    pub fn record_attestation(&mut self, latency: u64) {
+        self.final_transactions_count += 1;
+        self.total_latency += latency;
+    }
+}
+
+/// Prevailing reason of the anchoring delay.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum AnchoringDelayReason {
+    /// Anchoring has been frozen.
+    Frozen,
+    /// Proposal could not be created, e.g. due to insufficient funds.
+    Funding,
+    /// Anchoring has been waiting for the following configuration.
+    Transition,
+    /// Proposal has been waiting for the signatures.
+    Signing,
+}
+
+impl AnchoringDelay {
+    /// Returns the reason which has blocked the anchoring for the most blocks.
+    pub fn reason(&self) -> AnchoringDelayReason {
+        [
+            (self.signing_blocks, AnchoringDelayReason::Signing),
+            (self.transition_blocks, AnchoringDelayReason::Transition),
+            (self.funding_blocks, AnchoringDelayReason::Funding),
+            (self.frozen_blocks, AnchoringDelayReason::Frozen),
+        ]
+        .iter()
+        .max_by_key(|(blocks, _)| *blocks)
+        .unwrap()
+        .1
+    }
+}
+
+/// Schedule of the anchored heights which takes into account the changes of
+/// the anchoring interval.
+///
+/// Each change takes effect from an anchoring height of the previous schedule,
+/// so the anchored heights before the switch point are not affected.
+#[derive(Debug, Clone, PartialEq)]
+pub struct AnchoringSchedule {
+    changes: Vec<AnchoringIntervalChange>,
+}
+
+impl AnchoringSchedule {
+    /// Creates a schedule from the interval changes ordered by their effective heights.
+    /// The first change must be effective from the zero height.
+    pub fn new(changes: Vec<AnchoringIntervalChange>) -> Self {
+        assert!(
+            changes.first().map(|change| change.effective_from) == Some(0),
+            "Anchoring schedule must start from the zero height"
+        );
+        Self { changes }
+    }
+
+    /// Creates a schedule with the constant anchoring interval.
+    pub fn with_interval(interval: u64) -> Self {
+        Self::new(vec![AnchoringIntervalChange {
+            effective_from: 0,
+            interval,
+        }])
+    }
+
+    /// Returns the interval changes ordered by their effective heights.
+    pub fn changes(&self) -> &[AnchoringIntervalChange] {
+        &self.changes
+    }
+
+    /// Returns the anchoring interval in effect at the given height.
+    pub fn interval_at(&self, height: Height) -> u64 {
+        self.change_at(height).interval
+    }
+
+    /// Returns the nearest anchoring height below or equal to the given height.
+    pub fn previous_anchoring_height(&self, height: Height) -> Height {
+        let change = self.change_at(height);
+        let offset = height.0 - change.effective_from;
+        Height(change.effective_from + offset - offset % change.interval)
+    }
+
+    /// Returns the nearest anchoring height above the given height.
+    pub fn following_anchoring_height(&self, height: Height) -> Height {
+        let change = self.change_at(height);
+        let following = self.previous_anchoring_height(height).0 + change.interval;
+        // Following interval change resets the schedule.
+        let next_change = self
+            .changes
+            .iter()
+            .find(|change| change.effective_from > height.0)
+            .map_or(following, |change| change.effective_from);
+        Height(following.min(next_change))
+    }
+
+    fn change_at(&self, height: Height) -> &AnchoringIntervalChange {
+        self.changes
+            .iter()
+            .rev()
+            .find(|change| change.effective_from <= height.0)
+            .expect("Anchoring schedule must start from the zero height")
+    }
+}
+
 #[test]
 fn test_tx_input_id_binary_key() {
     let txout = TxInputId {
@@ -88,3 +235,62 @@ fn test_tx_input_id_binary_key() {
     let buf_hash = crypto::hash(&buf);
     assert_eq!(txout2.object_hash(), buf_hash);
 }
+
+#[test]
+fn test_signing_stats_window() {
+    let mut stats = SigningStats::default();
+    stats.record(true);
+    stats.record(false);
+    assert_eq!(stats.window_proposals(), 2);
+    assert_eq!(stats.window_signed_proposals(), 1);
+
+    // Old proposals are shifted out of the window.
+    for _ in 0..SigningStats::WINDOW_SIZE {
+        stats.record(false);
+    }
+    assert_eq!(stats.window_proposals(), SigningStats::WINDOW_SIZE);
+    assert_eq!(stats.window_signed_proposals(), 0);
+    assert_eq!(stats.total_proposals, SigningStats::WINDOW_SIZE + 2);
+    assert_eq!(stats.signed_proposals, 1);
+}
+
+#[test]
+fn test_anchoring_schedule() {
+    let schedule = AnchoringSchedule::with_interval(1000);
+    assert_eq!(schedule.previous_anchoring_height(Height(999)), Height(0));
+    assert_eq!(
+        schedule.following_anchoring_height(Height(1000)),
+        Height(2000)
+    );
+
+    // Interval is changed from 10 to 4 at the height 20 and then to 15 at the height 28.
+    let schedule = AnchoringSchedule::new(vec![
+        AnchoringIntervalChange {
+            effective_from: 0,
+            interval: 10,
+        },
+        AnchoringIntervalChange {
+            effective_from: 20,
+            interval: 4,
+        },
+        AnchoringIntervalChange {
+            effective_from: 28,
+            interval: 15,
+        },
+    ]);
+    assert_eq!(schedule.interval_at(Height(19)), 10);
+    assert_eq!(schedule.interval_at(Height(20)), 4);
+
+    assert_eq!(schedule.previous_anchoring_height(Height(19)), Height(10));
+    assert_eq!(schedule.previous_anchoring_height(Height(20)), Height(20));
+    assert_eq!(schedule.previous_anchoring_height(Height(23)), Height(20));
+    assert_eq!(schedule.previous_anchoring_height(Height(27)), Height(24));
+    assert_eq!(schedule.previous_anchoring_height(Height(42)), Height(28));
+    assert_eq!(schedule.previous_anchoring_height(Height(43)), Height(43));
+
+    assert_eq!(schedule.following_anchoring_height(Height(10)), Height(20));
+    assert_eq!(schedule.following_anchoring_height(Height(19)), Height(20));
+    assert_eq!(schedule.following_anchoring_height(Height(20)), Height(24));
+    assert_eq!(schedule.following_anchoring_height(Height(24)), Height(28));
+    assert_eq!(schedule.following_anchoring_height(Height(28)), Height(43));
+}
@@ -36,6 +36,30 @@ pub enum Error {
     AlreadyUsedFundingTx = 5,
     /// Funding transaction is unsuitable.
     UnsuitableFundingTx = 6,
+    /// Reported Bitcoin block headers don't extend the stored headers chain.
+    InvalidBitcoinHeaders = 7,
+    /// Inclusion proof doesn't prove inclusion of anchoring transactions in the stored
+    /// Bitcoin headers chain.
+    InvalidInclusionProof = 8,
+    /// Anchoring is frozen, so the signatures are not accepted.
+    AnchoringFrozen = 9,
+    /// Configuration gives the anchoring key to the node which is not a validator.
+    NonValidatorAnchoringKey = 10,
+    /// Bitcoin key of the anchoring node is excluded by the following configuration.
+    ExcludedAnchoringKey = 11,
+    /// Excluded keys leave not enough anchoring nodes to sign the transition transaction.
+    InsufficientAnchoringKeys = 12,
+    /// Voted fee rate is less than the minimal one.
+    UnsuitableFeeRate = 13,
+    /// Accelerated transaction is not the latest anchoring transaction.
+    UnsuitableAcceleratedTx = 14,
+    /// Batch of the input signatures is empty.
+    NoInputSignatures = 15,
+    /// Abandoned transaction is not the latest anchoring transaction.
+    UnsuitableRecoveredTx = 16,
+    /// Configuration changes the anchoring address, but its anchoring keys are not sorted
+    /// in the canonical order.
+    UnsortedAnchoringKeys = 17,
 }
 
 impl Error {
@@ -43,4 +67,9 @@ impl Error {
     pub fn anchoring_builder_error(error: btc::BuilderError) -> ExecutionError {
         Error::AnchoringBuilderError.with_description(error)
     }
+
+    /// Creates an error instance from the Bitcoin headers chain error.
+    pub fn header_chain_error(error: btc::HeaderChainError) -> ExecutionError {
+        Error::InvalidBitcoinHeaders.with_description(error)
+    }
 }
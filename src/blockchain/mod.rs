@@ -15,18 +15,30 @@
 //! Blockchain implementation details for the BTC anchoring service.
 
 pub use self::{schema::Schema, transactions::BtcAnchoringInterface};
-pub use crate::proto::{AddFunds, SignInput};
+pub use crate::proto::{
+    AccelerateAnchoring, AddFunds, RecoverAnchoring, ReportBitcoinHeaders, ReportInclusionProof,
+    SignInput, SignInputs, VoteFeeRate,
+};
 
 use bitcoin::blockdata::script::Script;
-use btc_transaction_utils::{multisig::RedeemScript, p2wsh};
+use btc_transaction_utils::multisig::RedeemScript;
 use exonum::helpers::Height;
 
 use crate::{btc::Address, config::Config};
 
+use self::data_layout::AnchoringSchedule;
+
+pub mod backup;
 pub mod data_layout;
 pub mod errors;
 pub mod schema;
+pub(crate) mod signatures;
 pub mod transactions;
+pub mod verification;
+
+/// Number of Bitcoin blocks, including the block with the anchoring transaction itself,
+/// required to consider the anchoring transaction final.
+pub const FINALITY_DEPTH: u64 = 6;
 
 /// Current state of the BTC anchoring service.
 #[derive(Debug, Clone)]
@@ -62,12 +74,19 @@ impl BtcAnchoringState {
 
     /// Returns the `script_pubkey` for the corresponding redeem script.
     pub fn script_pubkey(&self) -> Script {
-        self.redeem_script().as_ref().to_v0_p2wsh()
+        self.output_config().anchoring_out_script()
     }
 
     /// Returns the output address for the corresponding redeem script.
     pub fn output_address(&self) -> Address {
-        p2wsh::address(&self.redeem_script(), self.actual_config().network).into()
+        self.output_config().anchoring_address()
+    }
+
+    /// Returns the configuration which locks the output of the following anchoring
+    /// transaction.
+    fn output_config(&self) -> &Config {
+        self.following_config()
+            .unwrap_or_else(|| self.actual_config())
     }
 
     /// Checks that anchoring state is regular.
@@ -112,12 +131,15 @@ impl BtcAnchoringState {
         }
     }
 
-    /// Returns the nearest following anchoring height for the given height.
-    pub fn following_anchoring_height(&self, latest_anchored_height: Option<Height>) -> Height {
+    /// Returns the nearest following anchoring height for the given height
+    /// according to the given anchoring schedule.
+    pub fn following_anchoring_height(
+        &self,
+        schedule: &AnchoringSchedule,
+        latest_anchored_height: Option<Height>,
+    ) -> Height {
         latest_anchored_height.map_or_else(Height::zero, |height| match self {
-            BtcAnchoringState::Regular {
-                ref actual_configuration,
-            } => actual_configuration.following_anchoring_height(height),
+            BtcAnchoringState::Regular { .. } => schedule.following_anchoring_height(height),
             BtcAnchoringState::Transition { .. } => height,
         })
     }
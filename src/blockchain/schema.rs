@@ -14,46 +14,131 @@
 
 //! Information schema for the btc anchoring service.
 
-use exonum::{blockchain::Schema as CoreSchema, helpers::Height};
+use bitcoin::{consensus::params::Params, util::uint::Uint256};
+use exonum::{blockchain::Schema as CoreSchema, crypto::PublicKey, helpers::Height};
 use exonum_derive::FromAccess;
 use exonum_merkledb::{
     access::{Access, FromAccess, RawAccessMut},
-    Entry, ProofListIndex, ProofMapIndex,
+    Entry, MapIndex, ObjectHash, ProofListIndex, ProofMapIndex,
 };
-use log::{error, trace};
+use tracing::{error, trace};
 
 use crate::{
-    btc::{self, BtcAnchoringTransactionBuilder, BuilderError, Sha256d, Transaction},
+    btc::{
+        self, BlockHeader, BtcAnchoringTransactionBuilder, BuilderError, HeaderChainError,
+        MerkleBlock, Sha256d, StrictPayloadCodec, Transaction,
+    },
     config::Config,
-    proto::BinaryMap,
+    proto::{AccelerateAnchoring, BinaryMap, RecoverAnchoring, ReportBitcoinHeaders, SignInput},
 };
 
-use super::{data_layout::*, BtcAnchoringState};
+use std::{
+    cmp::{
+        self,
+        Ordering::{self, Equal, Greater, Less},
+    },
+    collections::BTreeSet,
+};
+
+use super::{data_layout::*, BtcAnchoringState, FINALITY_DEPTH};
 
 /// A set of signatures for a transaction input ordered by the anchoring node identifiers.
 pub type InputSignatures = BinaryMap<u16, btc::InputSignature>;
 /// A set of funding transaction confirmations.
 pub type TransactionConfirmations = BinaryMap<btc::PublicKey, ()>;
+/// Proofs of inclusion of a Bitcoin transaction reported by the anchoring nodes with
+/// the given Bitcoin keys.
+pub type InclusionReports = BinaryMap<btc::PublicKey, MerkleBlock>;
 
 /// Information schema for `exonum-btc-anchoring`.
 #[derive(Debug, FromAccess)]
 pub struct Schema<T: Access> {
     /// Complete chain of the anchoring transactions.
     pub transactions_chain: ProofListIndex<T::Base, Transaction>,
+    /// Indices of the anchoring transactions in the chain by their identifiers.
+    pub(crate) transaction_indices: ProofMapIndex<T::Base, Sha256d, u64>,
+    /// Indices of the anchoring transactions in the chain by their witness identifiers.
+    pub(crate) transactions_by_wtxid: ProofMapIndex<T::Base, Sha256d, u64>,
+    /// Indices of the latest anchoring transactions in the chain by the anchored
+    /// Exonum heights. Unlike the proof map, this index is ordered by the heights.
+    pub(crate) anchored_heights: MapIndex<T::Base, u64, u64>,
+    /// Indices of the anchoring transactions which send funds to a new anchoring address.
+    pub(crate) address_changes: ProofListIndex<T::Base, u64>,
     /// Already spent funding transactions.
     pub(crate) spent_funding_transactions: ProofMapIndex<T::Base, Sha256d, Transaction>,
+    /// Indices of the anchoring transactions which have spent the funding transactions
+    /// with the given identifiers.
+    pub(crate) funding_transaction_spenders: ProofMapIndex<T::Base, Sha256d, u64>,
     /// Signatures for the given transaction input.
     pub(crate) transaction_signatures: ProofMapIndex<T::Base, TxInputId, InputSignatures>,
+    /// Identity of the actual anchoring transaction proposal.
+    pub(crate) actual_proposal: Entry<T::Base, AnchoringProposalInfo>,
+    /// Heights of the blocks with the latest input signatures of the anchoring nodes.
+    pub(crate) latest_signatures: ProofMapIndex<T::Base, PublicKey, Height>,
+    /// Statistics of the anchoring nodes participation in signing of the proposals.
+    pub(crate) signing_stats: ProofMapIndex<T::Base, PublicKey, SigningStats>,
+    /// Aggregate statistics of the anchoring transactions chain.
+    pub(crate) anchoring_stats: Entry<T::Base, AnchoringStats>,
+    /// Delays of the anchoring transactions finalized after the following anchoring
+    /// height has passed.
+    pub(crate) anchoring_delays: ProofListIndex<T::Base, AnchoringDelay>,
+    /// Delay of the anchoring transaction which is not finalized yet.
+    pub(crate) pending_anchoring_delay: Entry<T::Base, AnchoringDelay>,
+    /// Changes of the anchoring interval ordered by their effective heights.
+    pub(crate) anchoring_schedule: ProofListIndex<T::Base, AnchoringIntervalChange>,
+    /// Emergency freeze flag of the anchoring transactions signing.
+    pub(crate) frozen: Entry<T::Base, bool>,
     /// Actual anchoring configuration entry.
     pub(crate) actual_config: Entry<T::Base, Config>,
     /// Following anchoring configuration entry.
     pub(crate) following_config: Entry<T::Base, Config>,
+    /// State of the transition of the anchoring chain to the following address.
+    pub(crate) transition_state: Entry<T::Base, TransitionState>,
+    /// Anchoring configurations in the order in which they have become actual.
+    pub(crate) config_history: ProofListIndex<T::Base, ConfigChange>,
     /// Confirmations for the corresponding funding transaction.
     pub(crate) unconfirmed_funding_transactions:
         ProofMapIndex<T::Base, Sha256d, TransactionConfirmations>,
     /// Entry that may contain an unspent funding transaction for the
     /// actual configuration.
     pub(crate) unspent_funding_transaction: Entry<T::Base, Transaction>,
+    /// Bitcoin block headers of the best known chain in ascending order of heights.
+    pub(crate) bitcoin_headers: ProofListIndex<T::Base, BlockHeader>,
+    /// Heights of the stored Bitcoin block headers of all known branches by the block hashes.
+    pub(crate) bitcoin_header_heights: ProofMapIndex<T::Base, Sha256d, u64>,
+    /// Height of the first stored Bitcoin block header.
+    pub(crate) bitcoin_headers_offset: Entry<T::Base, u64>,
+    /// Bitcoin block headers of the branches competing with the best known chain
+    /// by the block hashes.
+    pub(crate) bitcoin_fork_headers: ProofMapIndex<T::Base, Sha256d, BlockHeader>,
+    /// Checkpoints of the empty Bitcoin headers chain voted by the anchoring nodes with
+    /// the given service keys. Each vote contains the single block header.
+    pub(crate) bitcoin_checkpoint_votes: ProofMapIndex<T::Base, PublicKey, ReportBitcoinHeaders>,
+    /// Proofs of inclusion of the anchoring transactions in the blocks of the best stored
+    /// Bitcoin chain which have been reported by the most anchoring nodes.
+    pub(crate) inclusion_proofs: ProofMapIndex<T::Base, Sha256d, MerkleBlock>,
+    /// Proofs of inclusion of the registered funding transactions in the blocks of the best
+    /// stored Bitcoin chain which have been reported by the most anchoring nodes.
+    pub(crate) funding_inclusion_proofs: ProofMapIndex<T::Base, Sha256d, MerkleBlock>,
+    /// Proofs of inclusion of the not yet final anchoring transactions and of the funding
+    /// transactions reported by the anchoring nodes.
+    pub(crate) inclusion_reports: ProofMapIndex<T::Base, Sha256d, InclusionReports>,
+    /// Heights of the Exonum blocks in which the anchoring transactions have been finalized,
+    /// i.e. signed by the anchoring nodes.
+    pub(crate) finalization_heights: ProofMapIndex<T::Base, Sha256d, Height>,
+    /// Heights of the Exonum blocks in which the anchoring transactions became final.
+    pub(crate) final_transactions: ProofMapIndex<T::Base, Sha256d, Height>,
+    /// Index of the latest final anchoring transaction.
+    pub(crate) latest_final_transaction: Entry<T::Base, u64>,
+    /// Fee rates of the anchoring transactions voted by the anchoring nodes with the given
+    /// service keys.
+    pub(crate) fee_rate_votes: ProofMapIndex<T::Base, PublicKey, u64>,
+    /// Accelerations of the latest anchoring transaction voted by the anchoring nodes with
+    /// the given service keys.
+    pub(crate) acceleration_votes: ProofMapIndex<T::Base, PublicKey, AccelerateAnchoring>,
+    /// Recoveries of the anchoring chain broken at the latest anchoring transaction voted
+    /// by the anchoring nodes with the given service keys.
+    pub(crate) recovery_votes: ProofMapIndex<T::Base, PublicKey, RecoverAnchoring>,
 }
 
 impl<T: Access> Schema<T> {
@@ -75,11 +160,258 @@ impl<T: Access> Schema<T> {
         self.following_config.get()
     }
 
+    /// Returns the state of the transition of the anchoring chain to the address
+    /// of the following configuration.
+    pub fn transition_state(&self) -> TransitionState {
+        self.transition_state.get().unwrap_or_else(|| {
+            // The state is not recorded if the following configuration has been set
+            // by the previous versions of the service or by the backup import.
+            if self.actual_state().is_transition() {
+                TransitionState::TransitionPending {
+                    started_at: Height(0),
+                }
+            } else {
+                TransitionState::Regular
+            }
+        })
+    }
+
+    /// Returns every anchoring configuration ever used by the service along with
+    /// the heights from which they have been actual, in the chronological order.
+    ///
+    /// If the history has not been recorded yet, e.g. by the previous versions of
+    /// the service, it consists of the actual configuration only.
+    pub fn config_history(&self) -> Vec<ConfigChange> {
+        if self.config_history.is_empty() {
+            vec![ConfigChange {
+                actual_from: 0,
+                config: self.actual_config(),
+            }]
+        } else {
+            self.config_history.iter().collect()
+        }
+    }
+
+    /// Returns `true` if the anchoring is frozen, i.e. the service neither creates
+    /// anchoring proposals nor accepts signatures for them.
+    pub fn is_frozen(&self) -> bool {
+        self.frozen.get().unwrap_or_default()
+    }
+
+    /// Returns the schedule of the anchored heights. If the anchoring interval
+    /// has never been changed, the schedule uses the interval of the actual configuration.
+    pub fn anchoring_schedule(&self) -> AnchoringSchedule {
+        if self.anchoring_schedule.is_empty() {
+            AnchoringSchedule::with_interval(self.actual_config().anchoring_interval)
+        } else {
+            AnchoringSchedule::new(self.anchoring_schedule.iter().collect())
+        }
+    }
+
+    /// Returns the height of the Exonum block which should be anchored by the following
+    /// regular anchoring transaction. The height is determined by the anchoring schedule
+    /// in effect at the latest anchored height, so the interval changes never cause
+    /// the anchored heights to be skipped or duplicated.
+    pub fn following_anchoring_height(&self) -> Height {
+        self.actual_state()
+            .following_anchoring_height(&self.anchoring_schedule(), self.latest_anchored_height())
+    }
+
     /// Returns the list of signatures for the given transaction input.
     pub fn input_signatures(&self, input: &TxInputId) -> InputSignatures {
         self.transaction_signatures.get(input).unwrap_or_default()
     }
 
+    /// Returns `true` if exactly the same input signature of the anchoring node with
+    /// the given identifier is already stored.
+    pub(crate) fn has_input_signature(
+        &self,
+        anchoring_node_id: u16,
+        sign_input: &SignInput,
+    ) -> bool {
+        self.input_signatures(&TxInputId::new(sign_input.txid, sign_input.input))
+            .0
+            .get(&anchoring_node_id)
+            == Some(&sign_input.input_signature)
+    }
+
+    /// Returns the identity of the actual anchoring transaction proposal, if any.
+    pub fn actual_proposal(&self) -> Option<AnchoringProposalInfo> {
+        self.actual_proposal.get()
+    }
+
+    /// Returns the height at which the actual anchoring transaction proposal expires,
+    /// if the proposal lifetime is limited.
+    pub fn proposal_expiration_height(&self) -> Option<Height> {
+        let lifetime = self.actual_config().proposal_lifetime;
+        if lifetime == 0 {
+            return None;
+        }
+        self.actual_proposal()
+            .map(|info| Height(info.created_at + lifetime))
+    }
+
+    /// Returns the index of the anchoring transaction with the given identifier.
+    pub fn transaction_index(&self, txid: &Sha256d) -> Option<u64> {
+        self.transaction_indices.get(txid)
+    }
+
+    /// Returns the proof of inclusion of the anchoring transaction with the given
+    /// identifier in the Bitcoin block.
+    pub fn inclusion_proof(&self, txid: &Sha256d) -> Option<MerkleBlock> {
+        self.inclusion_proofs.get(txid)
+    }
+
+    /// Returns `true` if the transaction with the given identifier is a registered
+    /// funding transaction, either spent or not.
+    pub fn is_funding_transaction(&self, txid: &Sha256d) -> bool {
+        self.spent_funding_transactions.contains(txid)
+            || self
+                .unspent_funding_transaction
+                .get()
+                .filter(|tx| tx.id() == *txid)
+                .is_some()
+    }
+
+    /// Returns the index of the anchoring transaction which has spent the funding
+    /// transaction with the given identifier.
+    pub fn funding_transaction_spender(&self, txid: &Sha256d) -> Option<u64> {
+        self.funding_transaction_spenders.get(txid)
+    }
+
+    /// Returns the proof of inclusion of the registered funding transaction with the given
+    /// identifier in the Bitcoin block.
+    pub fn funding_inclusion_proof(&self, txid: &Sha256d) -> Option<MerkleBlock> {
+        self.funding_inclusion_proofs.get(txid)
+    }
+
+    /// Returns the number of Bitcoin blocks confirming the anchoring transaction with
+    /// the given identifier, including the block with the transaction itself.
+    pub fn inclusion_depth(&self, txid: &Sha256d) -> Option<u64> {
+        self.proof_depth(&self.inclusion_proof(txid)?)
+    }
+
+    /// Returns the number of Bitcoin blocks confirming the registered funding transaction
+    /// with the given identifier, including the block with the transaction itself.
+    pub fn funding_inclusion_depth(&self, txid: &Sha256d) -> Option<u64> {
+        self.proof_depth(&self.funding_inclusion_proof(txid)?)
+    }
+
+    fn proof_depth(&self, proof: &MerkleBlock) -> Option<u64> {
+        let height = self.bitcoin_header_height(&proof.header().block_hash())?;
+        let (tip_height, _) = self.bitcoin_chain_tip()?;
+        Some(tip_height + 1 - height)
+    }
+
+    /// Returns the height of the Exonum block in which the anchoring transaction with
+    /// the given identifier became final, i.e. its inclusion in the Bitcoin block
+    /// at sufficient depth has been attested by a byzantine majority of anchoring nodes.
+    pub fn transaction_finality_height(&self, txid: &Sha256d) -> Option<Height> {
+        self.final_transactions.get(txid)
+    }
+
+    /// Returns the index of the latest final anchoring transaction.
+    pub fn latest_final_transaction(&self) -> Option<u64> {
+        self.latest_final_transaction.get()
+    }
+
+    /// Returns the index of the anchoring transaction with the given witness identifier.
+    pub fn transaction_index_by_wtxid(&self, wtxid: &Sha256d) -> Option<u64> {
+        self.transactions_by_wtxid.get(wtxid)
+    }
+
+    /// Returns the index of the anchoring transaction which anchors the nearest block
+    /// with the given or greater height along with the anchored height, or `None` if
+    /// the block with the given height is not anchored yet.
+    ///
+    /// If several transactions anchor the same height, e.g. the accelerating or
+    /// transition ones, the index of the latest of them is returned.
+    pub fn anchoring_transaction_index(&self, height: Height) -> Option<(u64, Height)> {
+        let indexed = self
+            .anchored_heights
+            .iter_from(&height.0)
+            .next()
+            .map(|(anchored_height, index)| (index, Height(anchored_height)));
+        // The index lacks the transactions added before its introduction unless
+        // it has been rebuilt by the migration, so it is checked against the transaction
+        // preceding the found one.
+        let preceding = match indexed {
+            Some((index, _)) => index.checked_sub(1),
+            None => self.transactions_chain.len().checked_sub(1),
+        };
+        let is_consistent = preceding.map_or(true, |index| {
+            let preceding_height = self.anchored_height(index);
+            preceding_height < height || indexed.map(|(_, h)| h) == Some(preceding_height)
+        });
+        if is_consistent {
+            return indexed;
+        }
+
+        let chain_len = self.transactions_chain.len();
+        let mut index =
+            find_transaction_index(chain_len, height, |index| self.anchored_height(index));
+        let anchored_height = self.anchored_height(index);
+        if anchored_height < height {
+            // The block is not anchored yet.
+            return None;
+        }
+        // Take the latest of the transactions anchoring the same height.
+        while index + 1 < chain_len && self.anchored_height(index + 1) == anchored_height {
+            index += 1;
+        }
+        Some((index, anchored_height))
+    }
+
+    /// Returns the Exonum height anchored by the transaction with the given index.
+    fn anchored_height(&self, index: u64) -> Height {
+        self.transactions_chain
+            .get(index)
+            .and_then(|tx| tx.anchoring_payload())
+            .expect("Expected payload in the anchoring transaction.")
+            .block_height
+    }
+
+    /// Returns the height of the block with the latest input signature of the anchoring node
+    /// with the given service key.
+    pub fn latest_signature_height(&self, service_key: &PublicKey) -> Option<Height> {
+        self.latest_signatures.get(service_key)
+    }
+
+    /// Returns the signing statistics of the anchoring node with the given service key.
+    pub fn signing_stats(&self, service_key: &PublicKey) -> SigningStats {
+        self.signing_stats.get(service_key).unwrap_or_default()
+    }
+
+    /// Returns aggregate statistics of the anchoring transactions chain.
+    pub fn anchoring_stats(&self) -> AnchoringStats {
+        self.anchoring_stats.get().unwrap_or_default()
+    }
+
+    /// Checks that the given Bitcoin key is excluded by the following configuration,
+    /// so its signatures are not accepted anymore.
+    pub fn is_excluded_key(&self, bitcoin_key: &btc::PublicKey) -> bool {
+        self.following_config()
+            .into_iter()
+            .any(|config| config.excluded_keys.contains(bitcoin_key))
+    }
+
+    /// Returns the balance of the anchoring wallet in satoshis, i.e. the value of the latest
+    /// anchoring transaction output along with the unspent funding transaction output.
+    pub fn anchoring_balance(&self) -> u64 {
+        let anchoring_value = self
+            .transactions_chain
+            .last()
+            .and_then(|tx| tx.unspent_value())
+            .unwrap_or_default();
+        let script_pubkey = self.actual_config().anchoring_out_script();
+        let funding_value = self
+            .unspent_funding_transaction
+            .get()
+            .and_then(|tx| tx.find_out(&script_pubkey).map(|(_, out)| out.value))
+            .unwrap_or_default();
+        anchoring_value + funding_value
+    }
+
     /// Returns an unspent funding transaction for the actual configurations if it exists.
     pub fn unspent_funding_transaction(&self) -> Option<Transaction> {
         self.unspent_funding_transaction.get()
@@ -89,7 +421,9 @@ impl<T: Access> Schema<T> {
     pub fn actual_state(&self) -> BtcAnchoringState {
         let actual_configuration = self.actual_config();
         if let Some(following_configuration) = self.following_config() {
-            if actual_configuration.redeem_script() != following_configuration.redeem_script() {
+            if actual_configuration.anchoring_out_script()
+                != following_configuration.anchoring_out_script()
+            {
                 return BtcAnchoringState::Transition {
                     actual_configuration,
                     following_configuration,
@@ -102,18 +436,167 @@ impl<T: Access> Schema<T> {
         }
     }
 
+    /// Returns the fee per byte of the anchoring transactions for the given configuration.
+    ///
+    /// If a byzantine majority of the anchoring nodes has voted for the fee rate, the median
+    /// of their votes is used, so a minority of nodes cannot impose an arbitrary fee.
+    /// Otherwise, the `transaction_fee` of the configuration is used. The voted fee rate
+    /// is bounded by the `min_fee_rate` and `max_fee_rate` of the configuration.
+    pub fn fee_rate(&self, config: &Config) -> u64 {
+        let mut votes = config
+            .anchoring_keys
+            .iter()
+            .filter_map(|keys| self.fee_rate_votes.get(&keys.service_key))
+            .collect::<Vec<_>>();
+        if votes.len() < config.byzantine_quorum() {
+            return config.transaction_fee;
+        }
+        votes.sort_unstable();
+        config.bound_fee_rate(votes[votes.len() / 2])
+    }
+
+    /// Returns the fee per byte of the child transaction which accelerates the latest
+    /// anchoring transaction for the given configuration.
+    ///
+    /// The acceleration is active only if a byzantine majority of the anchoring nodes has
+    /// voted for the acceleration of the latest anchoring transaction, in this case the median
+    /// of their votes bounded by the configuration is used.
+    pub fn acceleration_fee_rate(&self, config: &Config) -> Option<u64> {
+        let txid = self.transactions_chain.last()?.id();
+        let mut votes = config
+            .anchoring_keys
+            .iter()
+            .filter_map(|keys| self.acceleration_votes.get(&keys.service_key))
+            .filter(|vote| vote.txid == txid)
+            .map(|vote| vote.fee_rate)
+            .collect::<Vec<_>>();
+        if votes.len() < config.byzantine_quorum() {
+            return None;
+        }
+        votes.sort_unstable();
+        Some(config.bound_fee_rate(votes[votes.len() / 2]))
+    }
+
+    /// Returns the identifier of the latest anchoring transaction, if a byzantine majority
+    /// of the anchoring nodes of the given configuration has voted for the recovery of
+    /// the anchoring chain broken at this transaction.
+    pub fn recovered_transaction(&self, config: &Config) -> Option<Sha256d> {
+        let txid = self.transactions_chain.last()?.id();
+        let votes = config
+            .anchoring_keys
+            .iter()
+            .filter_map(|keys| self.recovery_votes.get(&keys.service_key))
+            .filter(|vote| vote.txid == txid)
+            .count();
+        if votes < config.byzantine_quorum() {
+            return None;
+        }
+        Some(txid)
+    }
+
+    /// Returns the stored Bitcoin block header with the given height.
+    pub fn bitcoin_header(&self, height: u64) -> Option<BlockHeader> {
+        let offset = self.bitcoin_headers_offset.get()?;
+        self.bitcoin_headers.get(height.checked_sub(offset)?)
+    }
+
+    /// Returns the height of the Bitcoin block header with the given hash from the best
+    /// stored chain.
+    pub fn bitcoin_header_height(&self, block_hash: &Sha256d) -> Option<u64> {
+        let height = self.bitcoin_header_heights.get(block_hash)?;
+        if self.bitcoin_header(height)?.block_hash() != *block_hash {
+            return None;
+        }
+        Some(height)
+    }
+
+    /// Returns `true` if the Bitcoin block header with the given hash is stored either
+    /// in the best chain or in one of the competing branches.
+    pub fn is_known_bitcoin_header(&self, block_hash: &Sha256d) -> bool {
+        self.bitcoin_header_heights.contains(block_hash)
+    }
+
+    /// Returns the height and the header of the stored Bitcoin block with the given hash
+    /// from the best chain or from one of the competing branches.
+    fn known_bitcoin_header(&self, block_hash: &Sha256d) -> Option<(u64, BlockHeader)> {
+        let height = self.bitcoin_header_heights.get(block_hash)?;
+        let header = self
+            .bitcoin_header(height)
+            .filter(|header| header.block_hash() == *block_hash)
+            .or_else(|| self.bitcoin_fork_headers.get(block_hash))?;
+        Some((height, header))
+    }
+
+    /// Returns the height and the header of the checkpoint of the empty Bitcoin headers
+    /// chain if a byzantine majority of the anchoring nodes of the given configuration
+    /// has voted for it.
+    pub fn bitcoin_checkpoint(&self, config: &Config) -> Option<(u64, BlockHeader)> {
+        let votes = config
+            .anchoring_keys
+            .iter()
+            .filter_map(|keys| self.bitcoin_checkpoint_votes.get(&keys.service_key))
+            .filter_map(|vote| Some((vote.first_height, *vote.headers.first()?)))
+            .collect::<Vec<_>>();
+        votes
+            .iter()
+            .find(|checkpoint| {
+                votes.iter().filter(|vote| vote == checkpoint).count() >= config.byzantine_quorum()
+            })
+            .copied()
+    }
+
+    /// Returns the height and the header of the top block in the stored Bitcoin chain.
+    pub fn bitcoin_chain_tip(&self) -> Option<(u64, BlockHeader)> {
+        let offset = self.bitcoin_headers_offset.get()?;
+        let header = self.bitcoin_headers.last()?;
+        Some((offset + self.bitcoin_headers.len() - 1, header))
+    }
+
+    /// Verifies that the given Merkle block proves inclusion of the Bitcoin transaction
+    /// with the given identifier in the stored Bitcoin chain and returns the height of
+    /// the corresponding block.
+    pub fn verify_bitcoin_inclusion(&self, txid: &Sha256d, proof: &MerkleBlock) -> Option<u64> {
+        let header = proof.header();
+        let height = self.bitcoin_header_height(&header.block_hash())?;
+        if !proof.matched_transactions()?.contains(txid) {
+            return None;
+        }
+        Some(height)
+    }
+
     /// Returns the proposal of the next anchoring transaction for the given anchoring state.
     pub fn proposed_anchoring_transaction(
         &self,
         core_schema: CoreSchema<impl Access>,
         actual_state: &BtcAnchoringState,
     ) -> Option<Result<(Transaction, Vec<Transaction>), BuilderError>> {
+        if self.is_frozen() {
+            trace!("Anchoring is frozen, skip the proposal creation.");
+            return None;
+        }
+
         let config = actual_state.actual_config();
         let unspent_anchoring_transaction = self.transactions_chain.last();
         let unspent_funding_transaction = self.unspent_funding_transaction.get();
 
+        // Broken anchoring chain is continued by the new chain segment which spends
+        // the funding transaction only.
+        let recovered_tx = self.recovered_transaction(config);
+        // Stuck anchoring transaction is accelerated by the child transaction which
+        // anchors the same height again.
+        let acceleration_fee_rate = if actual_state.is_transition() || recovered_tx.is_some() {
+            None
+        } else {
+            self.acceleration_fee_rate(config)
+        };
+
         let mut builder = BtcAnchoringTransactionBuilder::new(&config.redeem_script());
-        // First anchoring transaction doesn't have previous.
+        builder.p2sh_wrapped(config.p2sh_wrapped);
+        // First anchoring transaction doesn't have previous, but it commits
+        // the digest of the initial anchoring configuration.
+        if unspent_anchoring_transaction.is_none() {
+            builder.config_digest(config.object_hash());
+        }
         if let Some(tx) = unspent_anchoring_transaction {
             let tx_id = tx.id();
 
@@ -137,8 +620,22 @@ impl<T: Access> Schema<T> {
                 }
             }
 
-            // TODO Re-implement recovery business logic [ECR-3581]
-            if let Err(e) = builder.prev_tx(tx) {
+            if acceleration_fee_rate.is_some() {
+                let parent_fee = self
+                    .spent_transactions(&tx)
+                    .and_then(|inputs| tx.fee(&inputs))
+                    .unwrap_or_default();
+                builder.pay_for_parent(tx.size(), parent_fee);
+            }
+
+            if recovered_tx.is_some() {
+                if unspent_funding_transaction.is_none() {
+                    trace!("Waiting for the funding transaction to recover the anchoring chain.");
+                    return Some(Err(BuilderError::NoInputs));
+                }
+                trace!("Recover the anchoring chain broken at {}.", tx_id);
+                builder.recover(tx_id);
+            } else if let Err(e) = builder.prev_tx(tx) {
                 if unspent_funding_transaction.is_none() {
                     return Some(Err(e));
                 }
@@ -155,11 +652,16 @@ impl<T: Access> Schema<T> {
 
         // Add corresponding payload.
         let latest_anchored_height = self.latest_anchored_height();
-        let anchoring_height = actual_state.following_anchoring_height(latest_anchored_height);
+        let anchoring_height = match (acceleration_fee_rate, latest_anchored_height) {
+            (Some(_), Some(height)) => height,
+            _ => actual_state
+                .following_anchoring_height(&self.anchoring_schedule(), latest_anchored_height),
+        };
         let anchoring_block_hash = core_schema.block_hash_by_height(anchoring_height)?;
 
         builder.payload(anchoring_height, anchoring_block_hash);
-        builder.fee(config.transaction_fee);
+        builder.fee(acceleration_fee_rate.unwrap_or_else(|| self.fee_rate(config)));
+        builder.payload_format(config.payload_format());
 
         // Create anchoring proposal.
         Some(builder.create())
@@ -178,7 +680,7 @@ impl<T: Access> Schema<T> {
     pub fn latest_anchored_height(&self) -> Option<Height> {
         let tx = self.transactions_chain.last()?;
         Some(
-            tx.anchoring_metadata()
+            tx.anchoring_metadata_with(&StrictPayloadCodec)
                 .expect(
                     "Expected payload in the anchoring transaction. \
                      If this error occurs, inform the service authors about it.",
@@ -187,6 +689,20 @@ impl<T: Access> Schema<T> {
                 .block_height,
         )
     }
+
+    /// Returns the transactions spent by the inputs of the given anchoring transaction
+    /// in the same order as the inputs, or `None` if some of them are unknown.
+    pub fn spent_transactions(&self, tx: &Transaction) -> Option<Vec<Transaction>> {
+        tx.0.input
+            .iter()
+            .map(|input| {
+                let txid = Sha256d::from(input.previous_output.txid);
+                self.transaction_index(&txid)
+                    .and_then(|index| self.transactions_chain.get(index))
+                    .or_else(|| self.spent_funding_transactions.get(&txid))
+            })
+            .collect()
+    }
 }
 
 impl<T> Schema<T>
@@ -195,10 +711,12 @@ where
     T::Base: RawAccessMut,
 {
     /// Adds a finalized transaction to the tail of the anchoring transactions.
-    pub(crate) fn push_anchoring_transaction(&mut self, tx: Transaction) {
+    pub(crate) fn push_anchoring_transaction(&mut self, tx: Transaction, height: Height) {
         // An unspent funding transaction is always unconditionally added to the anchoring
         // transaction proposal, so we can simply move it to the list of spent.
         if let Some(funding_transaction) = self.unspent_funding_transaction.take() {
+            self.funding_transaction_spenders
+                .put(&funding_transaction.id(), self.transactions_chain.len());
             self.spent_funding_transactions
                 .put(&funding_transaction.id(), funding_transaction);
         }
@@ -206,7 +724,7 @@ where
         if let Some(config) = self.following_config() {
             // Check that the anchoring transaction is correct.
             let tx_out_script = tx
-                .anchoring_metadata()
+                .anchoring_metadata_with(&StrictPayloadCodec)
                 .expect(
                     "Unable to find metadata in the anchoring transaction. \
                      If this error occurs, inform the service authors about it.",
@@ -222,12 +740,534 @@ where
                  If this error occurs, inform the service authors about it."
             );
             // If preconditions are correct, just reassign the config as an actual.
+            let started_at = match self.transition_state() {
+                TransitionState::TransitionPending { started_at } => started_at,
+                _ => Height(0),
+            };
+            self.transition_state
+                .set(TransitionState::TransitionBroadcast {
+                    started_at,
+                    finalized_at: height,
+                    txid: tx.id(),
+                    index: self.transactions_chain.len(),
+                });
             self.following_config.remove();
-            self.actual_config.set(config);
+            self.set_actual_config(config, height);
         }
+        // Votes for the acceleration and the recovery of the previous transaction are
+        // no longer relevant.
+        self.acceleration_votes.clear();
+        self.recovery_votes.clear();
+        self.finalization_heights.put(&tx.id(), height);
+        self.append_anchoring_transaction(tx);
+    }
+
+    /// Sets the following anchoring configuration with the new anchoring address and
+    /// starts the transition of the anchoring chain to this address.
+    pub(crate) fn start_transition(&mut self, config: Config, height: Height) {
+        self.following_config.set(config);
+        self.transition_state
+            .set(TransitionState::TransitionPending { started_at: height });
+    }
+
+    /// Sets the actual anchoring configuration and records it in the configuration
+    /// history as actual from the given height.
+    pub(crate) fn set_actual_config(&mut self, config: Config, actual_from: Height) {
+        // Keep the configuration which has been actual before the history was recorded.
+        if self.config_history.is_empty() {
+            if let Some(previous) = self.actual_config.get() {
+                self.config_history.push(ConfigChange {
+                    actual_from: 0,
+                    config: previous,
+                });
+            }
+        }
+        self.config_history.push(ConfigChange {
+            actual_from: actual_from.0,
+            config: config.clone(),
+        });
+        self.actual_config.set(config);
+    }
+
+    /// Appends the transaction to the anchoring chain and updates the indices.
+    pub(crate) fn append_anchoring_transaction(&mut self, tx: Transaction) {
+        // Remember the index of the first transaction which sends funds to a new address.
+        let is_new_address = match self.transactions_chain.last() {
+            Some(last_tx) => last_tx.0.output[0].script_pubkey != tx.0.output[0].script_pubkey,
+            None => true,
+        };
+        if is_new_address {
+            self.address_changes.push(self.transactions_chain.len());
+        }
+        self.transaction_indices
+            .put(&tx.id(), self.transactions_chain.len());
+        self.transactions_by_wtxid
+            .put(&tx.wtxid(), self.transactions_chain.len());
+        let anchored_height = tx
+            .anchoring_payload_with(&StrictPayloadCodec)
+            .expect(
+                "Expected payload in the anchoring transaction. \
+                 If this error occurs, inform the service authors about it.",
+            )
+            .block_height;
+        self.anchored_heights
+            .put(&anchored_height.0, self.transactions_chain.len());
         self.transactions_chain.push(tx);
     }
 
+    /// Verifies the continuity and the proof of work in the given network of the Bitcoin
+    /// block headers which follow the stored header with the preceding height. If the chain
+    /// is empty, the first header is verified as a checkpoint at the given height.
+    ///
+    /// On the networks with the difficulty adjustment the checkpoint should be at the retarget
+    /// boundary, so the first header of each following adjustment period is stored.
+    pub(crate) fn verify_bitcoin_headers(
+        &self,
+        network: bitcoin::Network,
+        first_height: u64,
+        headers: &[BlockHeader],
+    ) -> Result<(), HeaderChainError> {
+        let params = Params::new(network);
+        let interval = params.difficulty_adjustment_interval();
+        if self.bitcoin_headers.is_empty()
+            && !params.no_pow_retargeting
+            && first_height % interval != 0
+        {
+            return Err(HeaderChainError::UnalignedCheckpoint {
+                height: first_height,
+            });
+        }
+
+        let mut tip = None;
+        if let (Some(first), false) = (headers.first(), self.bitcoin_headers.is_empty()) {
+            let (prev_height, prev) = self.known_bitcoin_header(&first.prev_block_hash()).ok_or(
+                HeaderChainError::Discontinuity {
+                    height: first_height,
+                },
+            )?;
+            if first_height != prev_height + 1 {
+                return Err(HeaderChainError::UnexpectedHeight {
+                    expected: prev_height + 1,
+                    actual: first_height,
+                });
+            }
+            tip = Some(prev);
+        }
+        let mut last_regular = match tip {
+            Some(tip) => self.last_regular_bitcoin_header(first_height - 1, tip, network),
+            None => None,
+        };
+
+        for (height, header) in (first_height..).zip(headers) {
+            if let Some(tip) = tip {
+                if header.prev_block_hash() != tip.block_hash() {
+                    return Err(HeaderChainError::Discontinuity { height });
+                }
+            }
+            let period_start = match height.checked_sub(interval) {
+                Some(start) if height % interval == 0 => {
+                    if start >= first_height {
+                        Some(headers[(start - first_height) as usize])
+                    } else {
+                        self.bitcoin_header_ancestor(&headers[0].prev_block_hash(), start)
+                    }
+                }
+                _ => None,
+            };
+            header.verify_proof_of_work(
+                tip.as_ref(),
+                period_start.as_ref(),
+                last_regular.as_ref(),
+                height,
+                network,
+            )?;
+            tip = Some(*header);
+            if header.is_regular(height, network) {
+                last_regular = Some(*header);
+            }
+        }
+        Ok(())
+    }
+
+    /// Returns the latest regular header of the branch which ends with the given stored
+    /// header with the given height, i.e. the header which target is inherited by the blocks
+    /// without the minimal difficulty on the networks which allow such blocks.
+    fn last_regular_bitcoin_header(
+        &self,
+        mut height: u64,
+        mut header: BlockHeader,
+        network: bitcoin::Network,
+    ) -> Option<BlockHeader> {
+        while !header.is_regular(height, network) {
+            let (prev_height, prev) = self.known_bitcoin_header(&header.prev_block_hash())?;
+            height = prev_height;
+            header = prev;
+        }
+        Some(header)
+    }
+
+    /// Returns the header with the given height from the branch which ends with the stored
+    /// Bitcoin block with the given hash.
+    fn bitcoin_header_ancestor(&self, block_hash: &Sha256d, height: u64) -> Option<BlockHeader> {
+        let mut block_hash = *block_hash;
+        loop {
+            if let Some(tip_height) = self.bitcoin_header_height(&block_hash) {
+                // The rest of the branch belongs to the best chain.
+                return self.bitcoin_header(height).filter(|_| height <= tip_height);
+            }
+            let (tip_height, tip) = self.known_bitcoin_header(&block_hash)?;
+            if tip_height <= height {
+                return Some(tip).filter(|_| tip_height == height);
+            }
+            block_hash = tip.prev_block_hash();
+        }
+    }
+
+    /// Appends the given Bitcoin block headers to the stored header with the preceding
+    /// height after verifying their proof of work in the given network. The first header
+    /// added to the empty chain is used as a checkpoint at the given height, so it should
+    /// be agreed by the anchoring nodes beforehand.
+    ///
+    /// The headers may extend either the best chain or one of the competing branches.
+    /// The branch with the most cumulative work above the fork point becomes the best
+    /// chain; ties are resolved in favor of the current best chain. The stored headers
+    /// are left unchanged if any of the given ones is unsuitable.
+    pub(crate) fn append_bitcoin_headers(
+        &mut self,
+        network: bitcoin::Network,
+        first_height: u64,
+        headers: Vec<BlockHeader>,
+    ) -> Result<(), HeaderChainError> {
+        self.verify_bitcoin_headers(network, first_height, &headers)?;
+        if headers.is_empty() {
+            return Ok(());
+        }
+
+        // Collect the stored part of the branch which is extended by the given headers.
+        let mut branch = Vec::new();
+        let mut fork_hash = headers[0].prev_block_hash();
+        while let Some(header) = self.bitcoin_fork_headers.get(&fork_hash) {
+            fork_hash = header.prev_block_hash();
+            branch.push(header);
+        }
+        branch.reverse();
+        let stored_len = branch.len();
+        branch.extend_from_slice(&headers);
+
+        // Height of the first header above the fork point.
+        let branch_height = first_height - stored_len as u64;
+        let best_headers = if let Some((tip_height, _)) = self.bitcoin_chain_tip() {
+            (branch_height..=tip_height)
+                .filter_map(|height| self.bitcoin_header(height))
+                .collect()
+        } else {
+            self.bitcoin_headers_offset.set(first_height);
+            Vec::new()
+        };
+
+        for (height, header) in (first_height..).zip(&headers) {
+            self.bitcoin_header_heights
+                .put(&header.block_hash(), height);
+        }
+        if chain_work(&branch) > chain_work(&best_headers) {
+            let offset = self.bitcoin_headers_offset.get().unwrap_or(first_height);
+            self.bitcoin_headers.truncate(branch_height - offset);
+            for header in best_headers {
+                self.bitcoin_fork_headers.put(&header.block_hash(), header);
+            }
+            for header in &branch[..stored_len] {
+                self.bitcoin_fork_headers.remove(&header.block_hash());
+            }
+            self.bitcoin_headers.extend(branch);
+        } else {
+            for header in headers {
+                self.bitcoin_fork_headers.put(&header.block_hash(), header);
+            }
+        }
+        Ok(())
+    }
+
+    /// Changes the anchoring interval starting from the following anchoring height
+    /// after the given one. The change which has not yet taken effect is discarded.
+    pub(crate) fn update_anchoring_interval(&mut self, current_height: Height, interval: u64) {
+        if self.anchoring_schedule.is_empty() {
+            self.anchoring_schedule
+                .extend(self.anchoring_schedule().changes().to_vec());
+        }
+        // Discard the change which has not taken effect yet.
+        let last_change = self.anchoring_schedule.last().unwrap();
+        if self.anchoring_schedule.len() > 1 && last_change.effective_from > current_height.0 {
+            self.anchoring_schedule.pop();
+        }
+
+        let schedule = self.anchoring_schedule();
+        let last_change = schedule.changes().last().unwrap();
+        if last_change.interval != interval {
+            let effective_from = schedule.following_anchoring_height(current_height).0;
+            trace!(
+                "Anchoring interval will be changed to {} from the height {}.",
+                interval,
+                effective_from
+            );
+            self.anchoring_schedule.push(AnchoringIntervalChange {
+                effective_from,
+                interval,
+            });
+        }
+    }
+
+    /// Updates the identity of the actual anchoring transaction proposal at the given
+    /// height. If the proposal has been changed, e.g. due to the configuration change
+    /// or the new funds, or its lifetime has elapsed, the signatures of the previous
+    /// proposal are discarded.
+    pub(crate) fn update_proposal(&mut self, core_schema: CoreSchema<impl Access>, height: Height) {
+        let actual_state = self.actual_state();
+        let anchoring_height = self.following_anchoring_height();
+        let is_due = core_schema.block_hash_by_height(anchoring_height).is_some();
+        let proposal = self.proposed_anchoring_transaction(core_schema, &actual_state);
+        if is_due {
+            self.track_anchoring_delay(&proposal);
+        }
+
+        let proposal = proposal
+            .and_then(Result::ok)
+            .map(|(tx, inputs)| (tx.id(), inputs.len() as u32));
+        let previous = self.actual_proposal();
+        let expiration_height = self.proposal_expiration_height();
+
+        match (&previous, proposal) {
+            (None, None) => return,
+            (Some(info), Some((txid, _))) if info.txid == txid => match expiration_height {
+                Some(expiration_height) if height >= expiration_height => {
+                    trace!("Anchoring proposal {} has expired.", txid)
+                }
+                _ => return,
+            },
+            _ => {}
+        }
+
+        // Signatures of the finalized proposal are kept.
+        if let Some(info) = previous {
+            if self.transaction_index(&info.txid).is_none() {
+                for input in 0..info.inputs {
+                    self.transaction_signatures
+                        .remove(&TxInputId::new(info.txid, input));
+                }
+            }
+        }
+        match proposal {
+            Some((txid, inputs)) => self.actual_proposal.set(AnchoringProposalInfo {
+                txid,
+                inputs,
+                created_at: height.0,
+            }),
+            None => self.actual_proposal.remove(),
+        }
+    }
+
+    /// Accounts the block in which the anchoring of the due height is blocked.
+    fn track_anchoring_delay(
+        &mut self,
+        proposal: &Option<Result<(Transaction, Vec<Transaction>), BuilderError>>,
+    ) {
+        let mut delay = self.pending_anchoring_delay.get().unwrap_or_default();
+        match proposal {
+            _ if self.is_frozen() => delay.frozen_blocks += 1,
+            // The only reason not to create the due proposal is the transition.
+            None => delay.transition_blocks += 1,
+            Some(Err(_)) => delay.funding_blocks += 1,
+            Some(Ok(_)) => delay.signing_blocks += 1,
+        }
+        self.pending_anchoring_delay.set(delay);
+    }
+
+    /// Records the delay of the anchoring transaction finalized at the given height,
+    /// if the following anchoring height has passed by this moment.
+    ///
+    /// This method should be called before the transaction is added to the anchoring chain.
+    pub(crate) fn record_anchoring_delay(&mut self, tx: &Transaction, height: Height) {
+        let anchored_height = tx
+            .anchoring_payload_with(&StrictPayloadCodec)
+            .expect(
+                "Unable to find payload in the anchoring transaction. \
+                 If this error occurs, inform the service authors about it.",
+            )
+            .block_height;
+        // Transition transaction anchors the same height again, so the delay is
+        // accounted for the following one.
+        if self.latest_anchored_height() == Some(anchored_height) {
+            return;
+        }
+
+        let delay = self.pending_anchoring_delay.take().unwrap_or_default();
+        if self
+            .anchoring_schedule()
+            .following_anchoring_height(anchored_height)
+            < height
+        {
+            self.anchoring_delays.push(AnchoringDelay {
+                anchored_height: anchored_height.0,
+                finalized_at: height.0,
+                ..delay
+            });
+        }
+    }
+
+    /// Records the proof of inclusion of the anchoring or funding transaction in the Bitcoin
+    /// block reported by the anchoring node with the given Bitcoin key. The previous report
+    /// of this node for the same transaction is replaced.
+    ///
+    /// Reports for the final anchoring transactions and for the funding transactions
+    /// confirmed by the sufficient number of Bitcoin blocks are ignored.
+    pub(crate) fn report_inclusion(
+        &mut self,
+        txid: &Sha256d,
+        bitcoin_key: btc::PublicKey,
+        proof: MerkleBlock,
+    ) {
+        if self.final_transactions.contains(txid)
+            || self.funding_inclusion_depth(txid).unwrap_or(0) >= FINALITY_DEPTH
+        {
+            return;
+        }
+        let mut reports = self.inclusion_reports.get(txid).unwrap_or_default();
+        reports.0.insert(bitcoin_key, proof);
+        self.inclusion_reports.put(txid, reports);
+    }
+
+    /// Chooses the proofs of inclusion of the given reported transactions among the blocks
+    /// of the best stored Bitcoin chain. The block reported by the most anchoring nodes from
+    /// the given configuration is preferred, and the proofs which refer to the blocks
+    /// outside the best chain are discarded.
+    fn update_inclusion_proofs(&mut self, config: &Config, txids: &[Sha256d]) {
+        for txid in txids {
+            let reports = match self.inclusion_reports.get(txid) {
+                Some(reports) => reports,
+                None => continue,
+            };
+            let is_funding = self.transaction_index(txid).is_none();
+            let current = if is_funding {
+                self.funding_inclusion_proof(txid)
+            } else {
+                self.inclusion_proof(txid)
+            };
+            let current_hash = current.as_ref().map(|proof| proof.header().block_hash());
+            let best = reports
+                .0
+                .values()
+                .filter(|proof| {
+                    self.bitcoin_header_height(&proof.header().block_hash())
+                        .is_some()
+                })
+                .max_by_key(|proof| {
+                    let block_hash = proof.header().block_hash();
+                    (
+                        inclusion_votes(config, &reports, &block_hash),
+                        current_hash == Some(block_hash),
+                    )
+                })
+                .cloned();
+            if best == current {
+                continue;
+            }
+
+            let proofs = if is_funding {
+                &mut self.funding_inclusion_proofs
+            } else {
+                &mut self.inclusion_proofs
+            };
+            if let Some(proof) = best {
+                trace!(
+                    txid = %txid,
+                    block = %proof.header().block_hash(),
+                    "Transaction is included in the Bitcoin block."
+                );
+                proofs.put(txid, proof);
+            } else {
+                proofs.remove(txid);
+            }
+        }
+    }
+
+    /// Marks as final the given anchoring transactions which inclusion in the block of
+    /// the best stored Bitcoin chain has been reported by a byzantine majority of the anchoring
+    /// nodes from the given configuration and confirmed by the sufficient number of Bitcoin
+    /// blocks.
+    ///
+    /// The reports of the funding transactions are discarded as soon as the chosen proof
+    /// of inclusion is confirmed by the sufficient number of Bitcoin blocks.
+    pub(crate) fn update_finality(&mut self, config: &Config, height: Height, txids: &[Sha256d]) {
+        self.update_inclusion_proofs(config, txids);
+
+        let quorum = config.byzantine_quorum();
+        let mut finalized = Vec::new();
+        for txid in txids {
+            let reports = match self.inclusion_reports.get(txid) {
+                Some(reports) => reports,
+                None => continue,
+            };
+            // Only the anchoring transactions become final.
+            let index = match self.transaction_index(txid) {
+                Some(index) => index,
+                None => {
+                    if self.funding_inclusion_depth(txid).unwrap_or(0) >= FINALITY_DEPTH {
+                        self.inclusion_reports.remove(txid);
+                    }
+                    continue;
+                }
+            };
+            let block_hash = match self.inclusion_proof(txid) {
+                Some(proof) => proof.header().block_hash(),
+                None => continue,
+            };
+            let depth = self.inclusion_depth(txid).unwrap_or(0);
+            if inclusion_votes(config, &reports, &block_hash) >= quorum && depth >= FINALITY_DEPTH {
+                finalized.push((*txid, index));
+            }
+        }
+
+        for (txid, index) in finalized {
+            trace!("Anchoring transaction {} became final.", txid);
+            self.inclusion_reports.remove(&txid);
+            if let Some(finalized_at) = self.finalization_heights.get(&txid) {
+                let mut stats = self.anchoring_stats();
+                stats.record_attestation(height.0.saturating_sub(finalized_at.0));
+                self.anchoring_stats.set(stats);
+            }
+            self.final_transactions.put(&txid, height);
+            match self.latest_final_transaction() {
+                Some(latest) if latest >= index => {}
+                _ => self.latest_final_transaction.set(index),
+            }
+        }
+
+        // The transition is completed as soon as the transition transaction becomes final.
+        if let TransitionState::TransitionBroadcast { index, .. } = self.transition_state() {
+            if self.latest_final_transaction() >= Some(index) {
+                self.transition_state.set(TransitionState::Regular);
+            }
+        }
+    }
+
+    /// Updates the signing statistics of the anchoring nodes from the given configuration
+    /// after the proposal finalization. Participants are identified by their positions
+    /// in the anchoring keys.
+    pub(crate) fn update_signing_stats(&mut self, config: &Config, participants: &BTreeSet<u16>) {
+        for (id, keys) in config.anchoring_keys.iter().enumerate() {
+            let mut stats = self.signing_stats(&keys.service_key);
+            stats.record(participants.contains(&(id as u16)));
+            self.signing_stats.put(&keys.service_key, stats);
+        }
+    }
+
+    /// Updates aggregate statistics with the finalized anchoring transaction, which spends
+    /// the given inputs. The latency is accounted as soon as the transaction becomes final.
+    pub(crate) fn update_anchoring_stats(&mut self, tx: &Transaction, inputs: &[Transaction]) {
+        let mut stats = self.anchoring_stats();
+        stats.record(tx.fee(inputs).unwrap_or_default(), tx.size());
+        self.anchoring_stats.set(stats);
+    }
+
     /// Sets the given transaction as the current unspent funding transaction.
     pub(crate) fn set_funding_transaction(&mut self, transaction: btc::Transaction) {
         debug_assert!(
@@ -241,3 +1281,444 @@ where
         self.unspent_funding_transaction.set(transaction);
     }
 }
+
+/// Returns the number of the anchoring nodes from the given configuration which have reported
+/// the inclusion of the transaction in the Bitcoin block with the given hash.
+fn inclusion_votes(config: &Config, reports: &InclusionReports, block_hash: &Sha256d) -> usize {
+    config
+        .anchoring_keys
+        .iter()
+        .filter_map(|keys| reports.0.get(&keys.bitcoin_key))
+        .filter(|proof| proof.header().block_hash() == *block_hash)
+        .count()
+}
+
+/// Returns the cumulative proof of work of the given Bitcoin block headers.
+fn chain_work(headers: &[BlockHeader]) -> Uint256 {
+    headers
+        .iter()
+        .fold(Uint256::default(), |work, header| work + header.0.work())
+}
+
+/// Returns the index of the latest anchoring transaction which anchors the block with
+/// the given height, the index of the first transaction which anchors a greater height
+/// if there is no such transaction, or the index of the last transaction if all of them
+/// anchor lower heights.
+///
+/// The chain length must not be zero.
+fn find_transaction_index(
+    chain_len: u64,
+    height: Height,
+    anchored_height: impl Fn(u64) -> Height,
+) -> u64 {
+    // Handmade binary search.
+    let f = |index| -> Ordering {
+        // index is always in [0, size), that means index is >= 0 and < size.
+        // index >= 0: by definition
+        // index < size: index = size / 2 + size / 4 + size / 8 ...
+        anchored_height(index).cmp(&height)
+    };
+
+    let mut base = 0;
+    let mut size = chain_len;
+    while size > 1 {
+        let half = size / 2;
+        let mid = base + half;
+        let cmp = f(mid);
+        base = if cmp == Greater { base } else { mid };
+        size -= half;
+    }
+    // Don't forget to check base value.
+    let cmp = f(base);
+    if cmp == Equal {
+        base
+    } else {
+        cmp::min(base + (cmp == Less) as u64, chain_len - 1)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bitcoin::{
+        blockdata::{block, constants::genesis_block},
+        hash_types::{BlockHash, Txid},
+        util::{hash::BitcoinHash, merkleblock},
+        Network,
+    };
+    use bitcoin_hashes::Hash;
+    use exonum_merkledb::{access::Prefixed, Database, TemporaryDB};
+
+    use exonum::helpers::Height;
+
+    use exonum::crypto::{self, gen_keypair};
+
+    use super::{AnchoringIntervalChange, Schema};
+    use crate::{
+        blockchain::FINALITY_DEPTH,
+        btc::{
+            self, BlockHeader, BtcAnchoringTransactionBuilder, HeaderChainError, MerkleBlock,
+            Sha256d,
+        },
+        config::{AnchoringKeys, Config},
+        test_helpers::create_fake_funding_transaction,
+    };
+
+    fn gen_header(prev_blockhash: BlockHash, time: u32) -> block::BlockHeader {
+        mine_header(block::BlockHeader {
+            version: 1,
+            prev_blockhash,
+            merkle_root: Default::default(),
+            time,
+            bits: 0x207f_ffff,
+            nonce: 0,
+        })
+    }
+
+    fn mine_header(mut header: block::BlockHeader) -> block::BlockHeader {
+        while header.validate_pow(&header.target()).is_err() {
+            header.nonce += 1;
+        }
+        header
+    }
+
+    fn gen_headers(prev_blockhash: BlockHash, count: u32) -> Vec<BlockHeader> {
+        let mut prev_blockhash = prev_blockhash;
+        (0..count)
+            .map(|time| {
+                let header = gen_header(prev_blockhash, time);
+                prev_blockhash = header.bitcoin_hash();
+                BlockHeader(header)
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_bitcoin_headers_chain() {
+        let db = TemporaryDB::new();
+        let fork = db.fork();
+        let mut schema = Schema::new(Prefixed::new("anchoring", &fork));
+        assert_eq!(schema.bitcoin_chain_tip(), None);
+
+        // Build a Merkle proof for the transaction in the first block.
+        let txids = (0..4_u8).map(|i| Txid::hash(&[i])).collect::<Vec<_>>();
+        let tree = merkleblock::PartialMerkleTree::from_txids(&txids, &[false, true, false, false]);
+        let mut first_header = gen_header(BlockHash::default(), 100);
+        first_header.merkle_root = tree.extract_matches(&mut vec![], &mut vec![]).unwrap();
+        let first_header = mine_header(first_header);
+        let proof = MerkleBlock(merkleblock::MerkleBlock {
+            header: first_header,
+            txn: tree,
+        });
+
+        let mut headers = vec![BlockHeader(first_header)];
+        headers.extend(gen_headers(first_header.bitcoin_hash(), 2));
+        schema
+            .append_bitcoin_headers(Network::Regtest, 1000, headers.clone())
+            .unwrap();
+        assert_eq!(schema.bitcoin_chain_tip(), Some((1002, headers[2])));
+        assert_eq!(schema.bitcoin_header(999), None);
+        assert_eq!(schema.bitcoin_header(1001), Some(headers[1]));
+        assert_eq!(
+            schema.bitcoin_header_height(&headers[2].block_hash()),
+            Some(1002)
+        );
+
+        // Verify SPV proofs.
+        assert_eq!(
+            schema.verify_bitcoin_inclusion(&Sha256d::from(txids[1]), &proof),
+            Some(1000)
+        );
+        assert_eq!(
+            schema.verify_bitcoin_inclusion(&Sha256d::from(txids[0]), &proof),
+            None
+        );
+
+        // Try to append unsuitable headers.
+        let next_headers = gen_headers(headers[2].0.bitcoin_hash(), 2);
+        assert_eq!(
+            schema.append_bitcoin_headers(Network::Regtest, 1004, next_headers.clone()),
+            Err(HeaderChainError::UnexpectedHeight {
+                expected: 1003,
+                actual: 1004
+            })
+        );
+        assert_eq!(
+            schema.append_bitcoin_headers(Network::Regtest, 1003, vec![next_headers[1]]),
+            Err(HeaderChainError::Discontinuity { height: 1003 })
+        );
+
+        // Try to append headers with the broken proof of work.
+        let mut broken_header = next_headers[0];
+        while broken_header
+            .0
+            .validate_pow(&broken_header.0.target())
+            .is_ok()
+        {
+            broken_header.0.nonce += 1;
+        }
+        assert_eq!(
+            schema.append_bitcoin_headers(Network::Regtest, 1003, vec![broken_header]),
+            Err(HeaderChainError::InvalidProofOfWork { height: 1003 })
+        );
+        // Try to append headers with the changed target.
+        let mut harder_header = next_headers[0];
+        harder_header.0.bits = 0x1f00_ffff;
+        let harder_header = BlockHeader(mine_header(harder_header.0));
+        assert_eq!(
+            schema.append_bitcoin_headers(Network::Regtest, 1003, vec![harder_header]),
+            Err(HeaderChainError::UnexpectedTarget { height: 1003 })
+        );
+        assert_eq!(
+            schema.append_bitcoin_headers(Network::Testnet, 1003, next_headers.clone()),
+            Err(HeaderChainError::UnexpectedTarget { height: 1003 })
+        );
+        assert_eq!(schema.bitcoin_chain_tip(), Some((1002, headers[2])));
+
+        schema
+            .append_bitcoin_headers(Network::Regtest, 1003, next_headers.clone())
+            .unwrap();
+        assert_eq!(schema.bitcoin_chain_tip(), Some((1004, next_headers[1])));
+    }
+
+    #[test]
+    fn test_bitcoin_headers_fork_choice() {
+        let db = TemporaryDB::new();
+        let fork = db.fork();
+        let mut schema = Schema::new(Prefixed::new("anchoring", &fork));
+
+        let headers = gen_headers(BlockHash::default(), 3);
+        schema
+            .append_bitcoin_headers(Network::Regtest, 1000, headers.clone())
+            .unwrap();
+
+        // The competing branch with no more work doesn't replace the best chain.
+        let mut branch = vec![BlockHeader(gen_header(headers[0].0.bitcoin_hash(), 100))];
+        branch.push(BlockHeader(gen_header(branch[0].0.bitcoin_hash(), 101)));
+        schema
+            .append_bitcoin_headers(Network::Regtest, 1001, vec![branch[0]])
+            .unwrap();
+        schema
+            .append_bitcoin_headers(Network::Regtest, 1002, vec![branch[1]])
+            .unwrap();
+        assert_eq!(schema.bitcoin_chain_tip(), Some((1002, headers[2])));
+        assert!(schema.is_known_bitcoin_header(&branch[1].block_hash()));
+        assert_eq!(schema.bitcoin_header_height(&branch[1].block_hash()), None);
+
+        // The competing branch with more work becomes the best chain.
+        branch.push(BlockHeader(gen_header(branch[1].0.bitcoin_hash(), 102)));
+        schema
+            .append_bitcoin_headers(Network::Regtest, 1003, vec![branch[2]])
+            .unwrap();
+        assert_eq!(schema.bitcoin_chain_tip(), Some((1003, branch[2])));
+        assert_eq!(schema.bitcoin_header(1001), Some(branch[0]));
+        assert_eq!(schema.bitcoin_header(1000), Some(headers[0]));
+        assert_eq!(schema.bitcoin_header_height(&headers[2].block_hash()), None);
+        assert_eq!(
+            schema.bitcoin_header_height(&branch[1].block_hash()),
+            Some(1002)
+        );
+
+        // The former best chain may be extended and win back.
+        let next_headers = gen_headers(headers[2].0.bitcoin_hash(), 2);
+        schema
+            .append_bitcoin_headers(Network::Regtest, 1003, next_headers.clone())
+            .unwrap();
+        assert_eq!(schema.bitcoin_chain_tip(), Some((1004, next_headers[1])));
+        assert_eq!(schema.bitcoin_header(1001), Some(headers[1]));
+        assert_eq!(schema.bitcoin_fork_headers.iter().count(), 3);
+
+        // Headers cannot fork below the checkpoint.
+        let other_header = BlockHeader(gen_header(BlockHash::default(), 50));
+        assert_eq!(
+            schema.append_bitcoin_headers(Network::Regtest, 1000, vec![other_header]),
+            Err(HeaderChainError::Discontinuity { height: 1000 })
+        );
+    }
+
+    #[test]
+    fn test_bitcoin_checkpoint_alignment() {
+        let db = TemporaryDB::new();
+        let fork = db.fork();
+        let schema = Schema::new(Prefixed::new("anchoring", &fork));
+
+        // Genesis block of the testnet is a suitable checkpoint.
+        let genesis = BlockHeader(genesis_block(Network::Testnet).header);
+        assert_eq!(
+            schema.verify_bitcoin_headers(Network::Testnet, 0, &[genesis]),
+            Ok(())
+        );
+        // Retargets following the unaligned checkpoint cannot be verified.
+        assert_eq!(
+            schema.verify_bitcoin_headers(Network::Testnet, 1000, &[genesis]),
+            Err(HeaderChainError::UnalignedCheckpoint { height: 1000 })
+        );
+        // Regtest has no difficulty adjustment.
+        let header = gen_headers(BlockHash::default(), 1);
+        assert_eq!(
+            schema.verify_bitcoin_headers(Network::Regtest, 1000, &header),
+            Ok(())
+        );
+    }
+
+    #[test]
+    fn test_update_anchoring_interval() {
+        let db = TemporaryDB::new();
+        let fork = db.fork();
+        let mut schema = Schema::new(Prefixed::new("anchoring", &fork));
+        schema.actual_config.set(Config {
+            anchoring_interval: 5,
+            ..Config::default()
+        });
+        let change = |effective_from, interval| AnchoringIntervalChange {
+            effective_from,
+            interval,
+        };
+
+        schema.update_anchoring_interval(Height(5), 5);
+        assert_eq!(schema.anchoring_schedule().changes(), &[change(0, 5)]);
+        schema.update_anchoring_interval(Height(6), 3);
+        assert_eq!(
+            schema.anchoring_schedule().changes(),
+            &[change(0, 5), change(10, 3)]
+        );
+        // Pending change is replaced.
+        schema.update_anchoring_interval(Height(9), 7);
+        assert_eq!(
+            schema.anchoring_schedule().changes(),
+            &[change(0, 5), change(10, 7)]
+        );
+        // Pending change is reverted.
+        schema.update_anchoring_interval(Height(9), 5);
+        assert_eq!(schema.anchoring_schedule().changes(), &[change(0, 5)]);
+
+        schema.update_anchoring_interval(Height(9), 3);
+        schema.update_anchoring_interval(Height(10), 4);
+        assert_eq!(
+            schema.anchoring_schedule().changes(),
+            &[change(0, 5), change(10, 3), change(13, 4)]
+        );
+    }
+
+    #[test]
+    fn test_anchoring_transaction_index_fallback() {
+        let keys = (0..3).map(|_| AnchoringKeys {
+            bitcoin_key: btc::gen_keypair(Network::Testnet).0,
+            service_key: gen_keypair().0,
+        });
+        let config = Config::with_public_keys(Network::Testnet, keys).unwrap();
+        let redeem_script = config.redeem_script();
+        let address = config.anchoring_address();
+
+        let db = TemporaryDB::new();
+        let fork = db.fork();
+        let mut schema = Schema::new(Prefixed::new("anchoring", &fork));
+        assert_eq!(schema.anchoring_transaction_index(Height(0)), None);
+
+        for &height in &[0, 10, 10, 20] {
+            let mut builder = BtcAnchoringTransactionBuilder::new(&redeem_script);
+            builder
+                .additional_funds(create_fake_funding_transaction(&address, 100_000))
+                .unwrap();
+            builder.fee(1);
+            builder.payload(Height(height), crypto::Hash::zero());
+            schema.transactions_chain.push(builder.create().unwrap().0);
+        }
+
+        let expected = [
+            (0, Some((0, Height(0)))),
+            (5, Some((2, Height(10)))),
+            (10, Some((2, Height(10)))),
+            (15, Some((3, Height(20)))),
+            (21, None),
+        ];
+        let check = |schema: &Schema<_>| {
+            for &(height, index) in &expected {
+                assert_eq!(schema.anchoring_transaction_index(Height(height)), index);
+            }
+        };
+        // The index of the anchored heights is not filled at all.
+        check(&schema);
+        // The index contains only the latest transaction.
+        schema.anchored_heights.put(&20, 3);
+        check(&schema);
+        // The index is complete.
+        schema.anchored_heights.put(&0, 0);
+        schema.anchored_heights.put(&10, 2);
+        check(&schema);
+    }
+
+    #[test]
+    fn test_funding_inclusion_reports_pruning() {
+        let keys = (0..3)
+            .map(|_| AnchoringKeys {
+                bitcoin_key: btc::gen_keypair(Network::Regtest).0,
+                service_key: gen_keypair().0,
+            })
+            .collect::<Vec<_>>();
+        let config = Config::with_public_keys(Network::Regtest, keys.clone()).unwrap();
+
+        let db = TemporaryDB::new();
+        let fork = db.fork();
+        let mut schema = Schema::new(Prefixed::new("anchoring", &fork));
+        schema.actual_config.set(config.clone());
+        let funding_tx = create_fake_funding_transaction(&config.anchoring_address(), 100_000);
+        let txid = funding_tx.id();
+        schema.unspent_funding_transaction.set(funding_tx.clone());
+
+        // Build a Merkle proof for the funding transaction in the first block.
+        let txids = vec![Txid::hash(&[0]), funding_tx.0.txid()];
+        let tree = merkleblock::PartialMerkleTree::from_txids(&txids, &[false, true]);
+        let mut first_header = gen_header(BlockHash::default(), 100);
+        first_header.merkle_root = tree.extract_matches(&mut vec![], &mut vec![]).unwrap();
+        let first_header = mine_header(first_header);
+        let proof = MerkleBlock(merkleblock::MerkleBlock {
+            header: first_header,
+            txn: tree,
+        });
+
+        let mut headers = vec![BlockHeader(first_header)];
+        headers.extend(gen_headers(
+            first_header.bitcoin_hash(),
+            FINALITY_DEPTH as u32 - 2,
+        ));
+        schema
+            .append_bitcoin_headers(Network::Regtest, 1000, headers.clone())
+            .unwrap();
+        schema.report_inclusion(&txid, keys[0].bitcoin_key, proof.clone());
+        schema.update_finality(&config, Height(1), &[txid]);
+        assert_eq!(
+            schema
+                .funding_inclusion_proof(&txid)
+                .map(|proof| proof.header().block_hash()),
+            Some(proof.header().block_hash())
+        );
+        assert_eq!(
+            schema.funding_inclusion_depth(&txid),
+            Some(FINALITY_DEPTH - 1)
+        );
+        assert!(schema.inclusion_reports.contains(&txid));
+
+        // The reports are discarded as soon as the proof is deep enough.
+        let last_hash = headers.last().unwrap().0.bitcoin_hash();
+        schema
+            .append_bitcoin_headers(
+                Network::Regtest,
+                1000 + FINALITY_DEPTH - 1,
+                gen_headers(last_hash, 1),
+            )
+            .unwrap();
+        schema.update_finality(&config, Height(2), &[txid]);
+        assert_eq!(
+            schema
+                .funding_inclusion_proof(&txid)
+                .map(|proof| proof.header().block_hash()),
+            Some(proof.header().block_hash())
+        );
+        assert!(!schema.inclusion_reports.contains(&txid));
+        assert_eq!(schema.final_transactions.get(&txid), None);
+
+        // Subsequent reports are ignored.
+        schema.report_inclusion(&txid, keys[1].bitcoin_key, proof);
+        assert!(!schema.inclusion_reports.contains(&txid));
+    }
+}
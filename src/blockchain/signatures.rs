@@ -0,0 +1,293 @@
+// Copyright 2020 The Exonum Team
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Verification of the input signatures of the anchoring transaction proposal.
+
+use bitcoin::{
+    secp256k1::{Message, Secp256k1, Signature, VerifyOnly},
+    util::bip143::SighashComponents,
+};
+use btc_transaction_utils::{multisig::RedeemScript, InputSignatureRef};
+use crossbeam_utils::thread;
+use exonum::runtime::{ExecutionError, ExecutionFail};
+
+use std::sync::{Arc, Mutex};
+
+use super::{errors::Error, SignInput};
+use crate::btc;
+
+/// Minimum number of the signatures verified by a single thread, since spawning
+/// a thread is more expensive than a few signature verifications.
+const MIN_SIGNATURES_PER_THREAD: usize = 4;
+
+/// Sighashes of all inputs of the anchoring transaction proposal.
+#[derive(Debug)]
+pub(crate) struct ProposalSighashes {
+    context: Arc<Secp256k1<VerifyOnly>>,
+    txid: btc::Sha256d,
+    redeem_script: RedeemScript,
+    sighashes: Vec<Message>,
+}
+
+impl ProposalSighashes {
+    /// Computes the BIP-143 sighashes of the proposal inputs. The hashes of the proposal
+    /// inputs and outputs are computed once for all inputs.
+    fn new(
+        context: Arc<Secp256k1<VerifyOnly>>,
+        proposal: &btc::Transaction,
+        inputs: &[btc::Transaction],
+        redeem_script: RedeemScript,
+    ) -> Self {
+        let components = SighashComponents::new(proposal.as_ref());
+        let sighashes = proposal
+            .0
+            .input
+            .iter()
+            .zip(inputs)
+            .map(|(txin, input_transaction)| {
+                let value = input_transaction.0.output[txin.previous_output.vout as usize].value;
+                let sighash = components.sighash_all(txin, redeem_script.as_ref(), value);
+                Message::from_slice(&sighash[..]).unwrap()
+            })
+            .collect();
+        Self {
+            context,
+            txid: proposal.id(),
+            redeem_script,
+            sighashes,
+        }
+    }
+
+    fn matches(&self, proposal: &btc::Transaction, redeem_script: &RedeemScript) -> bool {
+        self.txid == proposal.id() && &self.redeem_script == redeem_script
+    }
+
+    /// Verifies the signature of the proposal input made by the given key.
+    pub fn verify(
+        &self,
+        public_key: &btc::PublicKey,
+        sign_input: &SignInput,
+    ) -> Result<(), ExecutionError> {
+        // Check that input with the specified index exist.
+        let sighash = self
+            .sighashes
+            .get(sign_input.input as usize)
+            .ok_or(Error::NoSuchInput)?;
+        let signature = InputSignatureRef::from(sign_input.input_signature.as_ref());
+        Signature::from_der(signature.content())
+            .and_then(|signature| self.context.verify(sighash, &signature, &public_key.0.key))
+            .map_err(|e| Error::InputVerificationFailed.with_description(e))
+    }
+
+    /// Verifies the signatures of the proposal inputs made by the given key. Large batches
+    /// are split between several threads. If several signatures are incorrect, the error
+    /// of the first one is returned, so the result doesn't depend on the number of threads.
+    pub fn verify_batch(
+        &self,
+        public_key: &btc::PublicKey,
+        sign_inputs: &[&SignInput],
+    ) -> Result<(), ExecutionError> {
+        let threads_count = (sign_inputs.len() / MIN_SIGNATURES_PER_THREAD)
+            .min(num_cpus::get())
+            .max(1);
+        if threads_count == 1 {
+            return sign_inputs
+                .iter()
+                .try_for_each(|sign_input| self.verify(public_key, sign_input));
+        }
+
+        let chunk_size = (sign_inputs.len() + threads_count - 1) / threads_count;
+        thread::scope(|scope| {
+            let handles = sign_inputs
+                .chunks(chunk_size)
+                .map(|chunk| {
+                    scope.spawn(move |_| {
+                        chunk
+                            .iter()
+                            .try_for_each(|sign_input| self.verify(public_key, sign_input))
+                    })
+                })
+                .collect::<Vec<_>>();
+            handles
+                .into_iter()
+                .try_for_each(|handle| handle.join().expect("Signature verification panicked"))
+        })
+        .expect("Signature verification panicked")
+    }
+}
+
+/// Cache of the sighashes of the latest anchoring transaction proposal.
+///
+/// All signatures in the block usually refer to the same proposal, so its sighashes
+/// are computed once instead of by each `SignInput` transaction. The cached sighashes
+/// are bound to the proposal identifier and the redeem script, thus the cache doesn't
+/// affect the execution results.
+///
+/// The cache also keeps the secp256k1 context, since the context creation is much more
+/// expensive than the signature verification itself.
+#[derive(Debug, Clone)]
+pub(crate) struct SighashCache {
+    context: Arc<Secp256k1<VerifyOnly>>,
+    latest: Arc<Mutex<Option<Arc<ProposalSighashes>>>>,
+}
+
+impl Default for SighashCache {
+    fn default() -> Self {
+        Self {
+            context: Arc::new(Secp256k1::verification_only()),
+            latest: Arc::default(),
+        }
+    }
+}
+
+impl SighashCache {
+    /// Returns the sighashes of the given proposal, computing them if they are not cached.
+    pub fn get(
+        &self,
+        proposal: &btc::Transaction,
+        inputs: &[btc::Transaction],
+        redeem_script: RedeemScript,
+    ) -> Arc<ProposalSighashes> {
+        let mut latest = self.latest.lock().unwrap();
+        match latest.as_ref() {
+            Some(sighashes) if sighashes.matches(proposal, &redeem_script) => sighashes.clone(),
+            _ => {
+                let sighashes = Arc::new(ProposalSighashes::new(
+                    self.context.clone(),
+                    proposal,
+                    inputs,
+                    redeem_script,
+                ));
+                *latest = Some(sighashes.clone());
+                sighashes
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bitcoin::network::constants::Network;
+    use btc_transaction_utils::{multisig::RedeemScriptBuilder, p2wsh, TxInRef};
+    use exonum::{crypto::Hash, helpers::Height, runtime::ErrorMatch};
+
+    use std::sync::Arc;
+
+    use super::{SighashCache, SignInput, MIN_SIGNATURES_PER_THREAD};
+    use crate::{
+        blockchain::errors::Error,
+        btc::{self, BtcAnchoringTransactionBuilder},
+        test_helpers::create_fake_funding_transaction,
+    };
+
+    #[test]
+    fn test_verify_signatures() {
+        let keypairs = (0..3)
+            .map(|_| btc::gen_keypair(Network::Testnet))
+            .collect::<Vec<_>>();
+        let redeem_script =
+            RedeemScriptBuilder::with_public_keys(keypairs.iter().map(|(pk, _)| pk.0))
+                .to_script()
+                .unwrap();
+        let address = p2wsh::address(&redeem_script, Network::Testnet).into();
+
+        let mut builder = BtcAnchoringTransactionBuilder::new(&redeem_script);
+        for _ in 0..5 {
+            let funding_tx = create_fake_funding_transaction(&address, 100_000);
+            builder.additional_funds(funding_tx).unwrap();
+        }
+        builder.fee(1);
+        builder.payload(Height(10), Hash::zero());
+        let (proposal, inputs) = builder.create().unwrap();
+
+        let (public_key, private_key) = &keypairs[0];
+        let mut signer = p2wsh::InputSigner::new(redeem_script.clone());
+        let mut sign_inputs = (0..inputs.len())
+            .map(|index| SignInput {
+                txid: proposal.id(),
+                input: index as u32,
+                input_signature: signer
+                    .sign_input(
+                        TxInRef::new(proposal.as_ref(), index),
+                        inputs[index].as_ref(),
+                        &private_key.0.key,
+                    )
+                    .unwrap()
+                    .into(),
+            })
+            .collect::<Vec<_>>();
+        // Signature of the other input and the nonexistent input.
+        sign_inputs[1].input_signature = sign_inputs[2].input_signature.clone();
+        sign_inputs.push(SignInput {
+            input: inputs.len() as u32,
+            ..sign_inputs[0].clone()
+        });
+
+        let cache = SighashCache::default();
+        let sighashes = cache.get(&proposal, &inputs, redeem_script.clone());
+        let results = sign_inputs
+            .iter()
+            .map(|sign_input| sighashes.verify(public_key, sign_input))
+            .collect::<Vec<_>>();
+
+        assert!(results[0].is_ok());
+        assert_eq!(
+            results[1].clone().unwrap_err(),
+            ErrorMatch::from_fail(&Error::InputVerificationFailed)
+                .with_description_containing("secp: signature failed verification")
+        );
+        assert!(results[2..5].iter().all(Result::is_ok));
+        assert_eq!(
+            results[5].clone().unwrap_err(),
+            ErrorMatch::from_fail(&Error::NoSuchInput)
+        );
+        // The signatures of the other node are rejected.
+        assert!(sighashes.verify(&keypairs[1].0, &sign_inputs[0]).is_err());
+
+        // The batch verification returns the error of the first incorrect signature
+        // regardless of the number of threads.
+        let batch = sign_inputs
+            .iter()
+            .cycle()
+            .skip(2)
+            .take(MIN_SIGNATURES_PER_THREAD * 4)
+            .collect::<Vec<_>>();
+        assert_eq!(
+            sighashes.verify_batch(public_key, &batch).unwrap_err(),
+            ErrorMatch::from_fail(&Error::NoSuchInput)
+        );
+        assert!(sighashes.verify_batch(public_key, &batch[..3]).is_ok());
+        assert!(sighashes
+            .verify_batch(
+                public_key,
+                &[&sign_inputs[0]; MIN_SIGNATURES_PER_THREAD * 4]
+            )
+            .is_ok());
+
+        // Sighashes are reused for the same proposal and redeem script only.
+        assert!(Arc::ptr_eq(
+            &sighashes,
+            &cache.get(&proposal, &inputs, redeem_script.clone())
+        ));
+        let other_script =
+            RedeemScriptBuilder::with_public_keys(keypairs.iter().map(|(pk, _)| pk.0))
+                .quorum(2)
+                .to_script()
+                .unwrap();
+        assert!(!Arc::ptr_eq(
+            &sighashes,
+            &cache.get(&proposal, &inputs, other_script)
+        ));
+    }
+}
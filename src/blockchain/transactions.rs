@@ -14,13 +14,18 @@
 
 //! BTC anchoring transactions.
 
-pub use crate::proto::{AddFunds, SignInput};
+pub use crate::proto::{
+    AccelerateAnchoring, AddFunds, RecoverAnchoring, ReportBitcoinHeaders, ReportInclusionProof,
+    SignInput, SignInputs, VoteFeeRate,
+};
 
-use btc_transaction_utils::{p2wsh::InputSigner, TxInRef};
+use btc_transaction_utils::p2wsh::InputSigner;
 use exonum::runtime::{CommonError, ExecutionError, ExecutionFail};
 use exonum_derive::{exonum_interface, interface_method};
 use exonum_rust_runtime::ExecutionContext;
-use log::{info, trace};
+use tracing::{debug_span, info, trace};
+
+use std::collections::BTreeSet;
 
 use crate::{btc, config::Config, BtcAnchoringService};
 
@@ -30,28 +35,6 @@ use super::{
     schema::{InputSignatures, Schema, TransactionConfirmations},
 };
 
-impl SignInput {
-    // Check that input signature is correct.
-    fn verify_signature(
-        &self,
-        input_signer: &InputSigner,
-        public_key: &btc::PublicKey,
-        proposal: &btc::Transaction,
-        inputs: &[btc::Transaction],
-    ) -> Result<(), ExecutionError> {
-        // Check that input with the specified index exist.
-        let input_transaction = inputs.get(self.input as usize).ok_or(Error::NoSuchInput)?;
-        input_signer
-            .verify_input(
-                TxInRef::new(proposal.as_ref(), self.input as usize),
-                input_transaction.as_ref(),
-                &public_key.0,
-                self.input_signature.as_ref(),
-            )
-            .map_err(|e| Error::InputVerificationFailed.with_description(e))
-    }
-}
-
 impl InputSignatures {
     /// Returns the number of elements in the map.
     fn len(&self) -> usize {
@@ -73,7 +56,7 @@ impl InputSignatures {
 
 impl TransactionConfirmations {
     /// Adds confirmation from the specified anchoring node.
-    fn confirm_by_node(&mut self, public_key: btc::PublicKey) {
+    pub(crate) fn confirm_by_node(&mut self, public_key: btc::PublicKey) {
         self.0.insert(public_key, ());
     }
 
@@ -91,6 +74,9 @@ pub trait BtcAnchoringInterface<Ctx> {
     type Output;
 
     /// Signs a single input of the anchoring transaction proposal.
+    ///
+    /// Re-sending the signature which is already stored is a no-op, even if the signed
+    /// transaction has been already finalized.
     #[interface_method(id = 0)]
     fn sign_input(&self, context: Ctx, arg: SignInput) -> Self::Output;
     /// Add funds via suitable funding transaction.
@@ -99,16 +85,73 @@ pub trait BtcAnchoringInterface<Ctx> {
     /// The transaction will be applied if 2/3+1 anchoring nodes sent it.
     #[interface_method(id = 1)]
     fn add_funds(&self, context: Ctx, arg: AddFunds) -> Self::Output;
+    /// Reports Bitcoin block headers which extend the stored headers chain or one of
+    /// its competing branches.
+    ///
+    /// The empty chain starts from the first reported header as soon as 2/3+1 anchoring
+    /// nodes have reported the same header at the same height. Headers that are already
+    /// stored are skipped, so several anchoring nodes may report the same headers.
+    #[interface_method(id = 2)]
+    fn report_bitcoin_headers(&self, context: Ctx, arg: ReportBitcoinHeaders) -> Self::Output;
+    /// Reports a proof of inclusion of anchoring or funding transactions in a Bitcoin block.
+    ///
+    /// The block header should be stored either in the best headers chain or in one of
+    /// the competing branches. The following report of the node replaces its previous one.
+    /// The proof is accepted for the block of the best chain reported by the most anchoring
+    /// nodes, and the anchoring transaction becomes final as soon as 2/3+1 anchoring nodes
+    /// have reported the same block buried under a sufficient number of blocks.
+    #[interface_method(id = 3)]
+    fn report_inclusion_proof(&self, context: Ctx, arg: ReportInclusionProof) -> Self::Output;
+    /// Votes for the fee rate of the anchoring transactions.
+    ///
+    /// The median of the votes is used instead of the configured fee as soon as 2/3+1
+    /// anchoring nodes have voted. The following vote of the node replaces its previous one.
+    #[interface_method(id = 4)]
+    fn vote_fee_rate(&self, context: Ctx, arg: VoteFeeRate) -> Self::Output;
+    /// Votes for the acceleration of the latest anchoring transaction stuck in the Bitcoin
+    /// mempool.
+    ///
+    /// As soon as 2/3+1 anchoring nodes have voted, the child transaction which spends
+    /// the output of the stuck transaction and anchors the same height is proposed. Its fee
+    /// is chosen so that both transactions pay the median of the voted fee rates.
+    #[interface_method(id = 5)]
+    fn accelerate_anchoring(&self, context: Ctx, arg: AccelerateAnchoring) -> Self::Output;
+    /// Signs several inputs of the anchoring transaction proposal at once.
+    ///
+    /// The signatures are verified and applied in the same way as in `sign_input`,
+    /// but a proposal with many inputs is signed by a single transaction. The whole
+    /// transaction fails if any of the signatures is invalid.
+    #[interface_method(id = 6)]
+    fn sign_inputs(&self, context: Ctx, arg: SignInputs) -> Self::Output;
+    /// Votes for the recovery of the anchoring chain, if the output of the latest anchoring
+    /// transaction is unusable, e.g. it is dust, stuck or stolen.
+    ///
+    /// As soon as 2/3+1 anchoring nodes have voted, the next anchoring transaction starts
+    /// a new chain segment. It spends only the unspent funding transaction, and its payload
+    /// refers to the abandoned transaction, which anchors the latest anchored height.
+    #[interface_method(id = 7)]
+    fn recover_anchoring(&self, context: Ctx, arg: RecoverAnchoring) -> Self::Output;
 }
 
 impl BtcAnchoringInterface<ExecutionContext<'_>> for BtcAnchoringService {
     type Output = Result<(), ExecutionError>;
 
     fn sign_input(&self, context: ExecutionContext<'_>, arg: SignInput) -> Self::Output {
+        self.sign_inputs(context, SignInputs { inputs: vec![arg] })
+    }
+
+    fn sign_inputs(&self, context: ExecutionContext<'_>, arg: SignInputs) -> Self::Output {
         let author = context
             .caller()
             .author()
             .ok_or(CommonError::UnauthorizedCaller)?;
+        let _span = debug_span!(
+            "sign_inputs",
+            instance = context.instance().name,
+            author = %author,
+            txid = ?arg.inputs.first().map(|sign_input| sign_input.txid),
+        )
+        .entered();
 
         let mut schema = Schema::new(context.service_data());
 
@@ -118,6 +161,28 @@ impl BtcAnchoringInterface<ExecutionContext<'_>> for BtcAnchoringService {
             .find_bitcoin_key(&author)
             .ok_or(Error::UnauthorizedAnchoringKey)?;
 
+        if schema.is_frozen() {
+            return Err(Error::AnchoringFrozen.into());
+        }
+        if schema.is_excluded_key(&public_key) {
+            return Err(Error::ExcludedAnchoringKey.into());
+        }
+
+        // All signatures in the batch should refer to the same transaction.
+        let txid = arg.inputs.first().ok_or(Error::NoInputSignatures)?.txid;
+        if arg.inputs.iter().any(|sign_input| sign_input.txid != txid) {
+            return Err(Error::UnexpectedProposalTxId.into());
+        }
+        // Signatures which are already stored are ignored, so the anchoring node may
+        // safely re-send them, e.g. after a restart of the sync utility.
+        if arg
+            .inputs
+            .iter()
+            .all(|sign_input| schema.has_input_signature(anchoring_node_id, sign_input))
+        {
+            return Ok(());
+        }
+
         // Check that there is an anchoring proposal for the actual blockchain state.
         let (proposal, expected_inputs) = if let Some(proposal) = schema
             .actual_proposed_anchoring_transaction(context.data().for_core())
@@ -134,7 +199,7 @@ impl BtcAnchoringInterface<ExecutionContext<'_>> for BtcAnchoringService {
                 // If the anchoring chain is not established, then the proposal must exist.
                 .unwrap()
                 .id();
-            if latest_anchoring_txid == arg.txid {
+            if latest_anchoring_txid == txid {
                 return Ok(());
             } else {
                 return Err(Error::UnexpectedProposalTxId.into());
@@ -142,36 +207,57 @@ impl BtcAnchoringInterface<ExecutionContext<'_>> for BtcAnchoringService {
         };
 
         // Make sure txid is equal to the identifier of the anchoring transaction proposal.
-        if proposal.id() != arg.txid {
+        if proposal.id() != txid {
             return Err(Error::UnexpectedProposalTxId.into());
         }
 
-        // Check that input signature is correct.
         let redeem_script = actual_config.redeem_script();
         let quorum = redeem_script.content().quorum;
-        let input_signer = InputSigner::new(redeem_script);
-        arg.verify_signature(&input_signer, &public_key, &proposal, &expected_inputs)?;
-
-        // All preconditions are correct and we can use this signature.
-        let input_id = TxInputId::new(proposal.id(), arg.input);
-        let mut input_signatures = schema.input_signatures(&input_id);
-        let mut input_signature_len = input_signatures.len();
-        // Check that we have not reached the quorum yet, otherwise we should not do anything.
-        if input_signature_len < quorum {
-            // Add signature to schema.
-            input_signatures.insert(anchoring_node_id, arg.input_signature);
-            schema
-                .transaction_signatures
-                .put(&input_id, input_signatures);
-            input_signature_len += 1;
-        } else {
-            return Ok(());
+        let sighashes = self
+            .sighash_cache
+            .get(&proposal, &expected_inputs, redeem_script.clone());
+        // Check that the new input signatures are correct.
+        let new_inputs = arg
+            .inputs
+            .iter()
+            .filter(|sign_input| !schema.has_input_signature(anchoring_node_id, sign_input))
+            .collect::<Vec<_>>();
+        sighashes.verify_batch(&public_key, &new_inputs)?;
+
+        let mut quorum_reached = false;
+        for sign_input in arg.inputs {
+            if schema.has_input_signature(anchoring_node_id, &sign_input) {
+                continue;
+            }
+            trace!(
+                input = sign_input.input,
+                node = anchoring_node_id,
+                "Input signature is accepted."
+            );
+
+            // All preconditions are correct and we can use this signature.
+            let input_id = TxInputId::new(proposal.id(), sign_input.input);
+            let mut input_signatures = schema.input_signatures(&input_id);
+            // Check that we have not reached the quorum yet, otherwise we should not do anything.
+            if input_signatures.len() < quorum {
+                // Add signature to schema.
+                input_signatures.insert(anchoring_node_id, sign_input.input_signature);
+                quorum_reached |= input_signatures.len() == quorum;
+                schema
+                    .transaction_signatures
+                    .put(&input_id, input_signatures);
+                schema
+                    .latest_signatures
+                    .put(&author, context.data().for_core().next_height());
+            }
         }
 
-        // If we have enough signatures for specific input we have to check that we also have
+        // If we have enough signatures for some input we have to check that we also have
         // sufficient signatures to finalize proposal transaction.
-        if input_signature_len == quorum {
+        if quorum_reached {
+            let input_signer = InputSigner::new(redeem_script);
             let mut finalized_tx: btc::Transaction = proposal.clone();
+            let mut participants = BTreeSet::new();
             // Make sure we reach a quorum for each input.
             for index in 0..expected_inputs.len() {
                 let input_id = TxInputId::new(proposal.id(), index as u32);
@@ -181,6 +267,7 @@ impl BtcAnchoringInterface<ExecutionContext<'_>> for BtcAnchoringService {
                 if signatures_for_input.len() != quorum {
                     return Ok(());
                 }
+                participants.extend(signatures_for_input.0.keys().copied());
 
                 input_signer.spend_input(
                     &mut finalized_tx.0.input[index],
@@ -188,17 +275,25 @@ impl BtcAnchoringInterface<ExecutionContext<'_>> for BtcAnchoringService {
                 );
             }
 
-            let payload = finalized_tx.anchoring_metadata().unwrap().1;
+            let payload = finalized_tx
+                .anchoring_payload_with(&btc::StrictPayloadCodec)
+                .unwrap();
 
-            info!("====== ANCHORING ======");
-            info!("txid: {}", finalized_tx.id().to_string());
-            info!("height: {}", payload.block_height);
-            info!("hash: {}", payload.block_hash.to_hex());
-            info!("balance: {}", finalized_tx.0.output[0].value);
-            trace!("Anchoring txhex: {}", finalized_tx.to_string());
+            info!(
+                txid = %finalized_tx.id(),
+                height = %payload.block_height,
+                hash = %payload.block_hash.to_hex(),
+                balance = finalized_tx.0.output[0].value,
+                "Anchoring transaction has been finalized."
+            );
+            trace!(txhex = %finalized_tx, "Finalized anchoring transaction.");
 
             // Add finalized transaction to the tail of anchoring transactions.
-            schema.push_anchoring_transaction(finalized_tx);
+            let height = context.data().for_core().next_height();
+            schema.update_signing_stats(&actual_config, &participants);
+            schema.record_anchoring_delay(&finalized_tx, height);
+            schema.update_anchoring_stats(&finalized_tx, &expected_inputs);
+            schema.push_anchoring_transaction(finalized_tx, height);
         }
         Ok(())
     }
@@ -208,6 +303,13 @@ impl BtcAnchoringInterface<ExecutionContext<'_>> for BtcAnchoringService {
             .caller()
             .author()
             .ok_or(CommonError::UnauthorizedCaller)?;
+        let _span = debug_span!(
+            "add_funds",
+            instance = context.instance().name,
+            author = %author,
+            txid = %arg.transaction.id(),
+        )
+        .entered();
         let mut schema = Schema::new(context.service_data());
 
         // Check that author is authorized to sign inputs of the anchoring proposal.
@@ -238,9 +340,11 @@ impl BtcAnchoringInterface<ExecutionContext<'_>> for BtcAnchoringService {
         // Set this transaction as unspent funding if there are enough confirmations
         // otherwise just write confirmation to the schema.
         if confirmations.has_enough_confirmations(&actual_config)? {
-            info!("====== ADD_FUNDS ======");
-            info!("txid: {}", arg.transaction.id().to_string());
-            info!("balance: {}", txout.value);
+            info!(
+                txid = %funding_txid,
+                balance = txout.value,
+                "Funding transaction has been confirmed."
+            );
 
             schema.set_funding_transaction(arg.transaction);
         } else {
@@ -250,4 +354,246 @@ impl BtcAnchoringInterface<ExecutionContext<'_>> for BtcAnchoringService {
         }
         Ok(())
     }
+
+    fn report_bitcoin_headers(
+        &self,
+        context: ExecutionContext<'_>,
+        arg: ReportBitcoinHeaders,
+    ) -> Self::Output {
+        let author = context
+            .caller()
+            .author()
+            .ok_or(CommonError::UnauthorizedCaller)?;
+        let mut schema = Schema::new(context.service_data());
+
+        // Check that author is an anchoring node.
+        let actual_config = schema.actual_config();
+        actual_config
+            .find_bitcoin_key(&author)
+            .ok_or(Error::UnauthorizedAnchoringKey)?;
+
+        // Skip headers which have been already reported.
+        let known_headers = arg
+            .headers
+            .iter()
+            .take_while(|header| schema.is_known_bitcoin_header(&header.block_hash()))
+            .count();
+        if known_headers == arg.headers.len() {
+            return Ok(());
+        }
+
+        let mut first_height = arg.first_height + known_headers as u64;
+        let mut headers = arg
+            .headers
+            .into_iter()
+            .skip(known_headers)
+            .collect::<Vec<_>>();
+        schema
+            .verify_bitcoin_headers(actual_config.network, first_height, &headers)
+            .map_err(Error::header_chain_error)?;
+
+        // A single anchoring node cannot choose the starting point of the chain, so
+        // the empty chain starts from the checkpoint agreed by the byzantine majority.
+        if schema.bitcoin_chain_tip().is_none() {
+            let vote = ReportBitcoinHeaders {
+                first_height,
+                headers: vec![headers[0]],
+            };
+            schema.bitcoin_checkpoint_votes.put(&author, vote);
+            let (checkpoint_height, checkpoint) =
+                if let Some(checkpoint) = schema.bitcoin_checkpoint(&actual_config) {
+                    checkpoint
+                } else {
+                    info!(
+                        instance = context.instance().name,
+                        author = %author,
+                        height = first_height,
+                        "Anchoring node has voted for the checkpoint of the Bitcoin headers chain."
+                    );
+                    return Ok(());
+                };
+            schema.bitcoin_checkpoint_votes.clear();
+            if (checkpoint_height, checkpoint) != (first_height, headers[0]) {
+                first_height = checkpoint_height;
+                headers = vec![checkpoint];
+            }
+        }
+
+        let last_height = first_height + headers.len() as u64 - 1;
+        schema
+            .append_bitcoin_headers(actual_config.network, first_height, headers)
+            .map_err(Error::header_chain_error)?;
+        // New headers may change the best chain and the depth of the reported transactions.
+        // The reports are kept only for the transactions which are not yet final, so
+        // the number of the updated transactions is bounded.
+        let pending_transactions = schema.inclusion_reports.keys().collect::<Vec<_>>();
+        schema.update_finality(
+            &actual_config,
+            context.data().for_core().next_height(),
+            &pending_transactions,
+        );
+
+        trace!(
+            instance = context.instance().name,
+            height = last_height,
+            "Bitcoin headers chain has been updated."
+        );
+        Ok(())
+    }
+
+    fn report_inclusion_proof(
+        &self,
+        context: ExecutionContext<'_>,
+        arg: ReportInclusionProof,
+    ) -> Self::Output {
+        let author = context
+            .caller()
+            .author()
+            .ok_or(CommonError::UnauthorizedCaller)?;
+        let mut schema = Schema::new(context.service_data());
+
+        // Check that author is an anchoring node.
+        let actual_config = schema.actual_config();
+        let (_, public_key) = actual_config
+            .find_bitcoin_key(&author)
+            .ok_or(Error::UnauthorizedAnchoringKey)?;
+
+        // Check that the proof refers to the stored Bitcoin headers.
+        let block_hash = arg.proof.header().block_hash();
+        if !schema.is_known_bitcoin_header(&block_hash) {
+            return Err(Error::InvalidInclusionProof
+                .with_description(format!("Unknown Bitcoin block {}", block_hash)));
+        }
+        let matched_transactions = arg.proof.matched_transactions().ok_or_else(|| {
+            Error::InvalidInclusionProof
+                .with_description("Merkle proof doesn't match the block header")
+        })?;
+        // Check that the proof refers to the anchoring or funding transactions.
+        let reported_transactions = matched_transactions
+            .into_iter()
+            .filter(|txid| {
+                schema.transaction_index(txid).is_some() || schema.is_funding_transaction(txid)
+            })
+            .collect::<Vec<_>>();
+        if reported_transactions.is_empty() {
+            return Err(Error::InvalidInclusionProof
+                .with_description("Proof doesn't contain anchoring or funding transactions"));
+        }
+
+        for txid in &reported_transactions {
+            trace!(
+                txid = %txid,
+                block = %block_hash,
+                "Anchoring node has reported the inclusion of the transaction in the Bitcoin block."
+            );
+            schema.report_inclusion(txid, public_key, arg.proof.clone());
+        }
+        // The proof affects only the reported transactions.
+        schema.update_finality(
+            &actual_config,
+            context.data().for_core().next_height(),
+            &reported_transactions,
+        );
+        Ok(())
+    }
+
+    fn vote_fee_rate(&self, context: ExecutionContext<'_>, arg: VoteFeeRate) -> Self::Output {
+        let author = context
+            .caller()
+            .author()
+            .ok_or(CommonError::UnauthorizedCaller)?;
+        let mut schema = Schema::new(context.service_data());
+
+        // Check that author is an anchoring node.
+        let actual_config = schema.actual_config();
+        actual_config
+            .find_bitcoin_key(&author)
+            .ok_or(Error::UnauthorizedAnchoringKey)?;
+
+        if arg.fee_rate < Config::MIN_TX_FEE {
+            return Err(Error::UnsuitableFeeRate.with_description(format!(
+                "Fee rate should be at least {} satoshis per byte",
+                Config::MIN_TX_FEE
+            )));
+        }
+
+        trace!(
+            instance = context.instance().name,
+            author = %author,
+            fee_rate = arg.fee_rate,
+            "Anchoring node has voted for the fee rate."
+        );
+        schema.fee_rate_votes.put(&author, arg.fee_rate);
+        Ok(())
+    }
+    fn accelerate_anchoring(
+        &self,
+        context: ExecutionContext<'_>,
+        arg: AccelerateAnchoring,
+    ) -> Self::Output {
+        let author = context
+            .caller()
+            .author()
+            .ok_or(CommonError::UnauthorizedCaller)?;
+        let mut schema = Schema::new(context.service_data());
+
+        // Check that author is an anchoring node.
+        let actual_config = schema.actual_config();
+        actual_config
+            .find_bitcoin_key(&author)
+            .ok_or(Error::UnauthorizedAnchoringKey)?;
+
+        let latest_txid = schema.transactions_chain.last().map(|tx| tx.id());
+        if latest_txid != Some(arg.txid) {
+            return Err(Error::UnsuitableAcceleratedTx.into());
+        }
+        if arg.fee_rate < Config::MIN_TX_FEE {
+            return Err(Error::UnsuitableFeeRate.with_description(format!(
+                "Fee rate should be at least {} satoshis per byte",
+                Config::MIN_TX_FEE
+            )));
+        }
+
+        trace!(
+            instance = context.instance().name,
+            author = %author,
+            txid = %arg.txid,
+            fee_rate = arg.fee_rate,
+            "Anchoring node has voted for the acceleration of the anchoring transaction."
+        );
+        schema.acceleration_votes.put(&author, arg);
+        Ok(())
+    }
+
+    fn recover_anchoring(
+        &self,
+        context: ExecutionContext<'_>,
+        arg: RecoverAnchoring,
+    ) -> Self::Output {
+        let author = context
+            .caller()
+            .author()
+            .ok_or(CommonError::UnauthorizedCaller)?;
+        let mut schema = Schema::new(context.service_data());
+
+        // Check that author is an anchoring node.
+        let actual_config = schema.actual_config();
+        actual_config
+            .find_bitcoin_key(&author)
+            .ok_or(Error::UnauthorizedAnchoringKey)?;
+
+        let latest_txid = schema.transactions_chain.last().map(|tx| tx.id());
+        if latest_txid != Some(arg.txid) {
+            return Err(Error::UnsuitableRecoveredTx.into());
+        }
+
+        info!(
+            instance = context.instance().name,
+            author = %author,
+            txid = %arg.txid,
+            "Anchoring node has voted for the recovery of the broken anchoring chain."
+        );
+        schema.recovery_votes.put(&author, arg);
+        Ok(())
+    }
 }
@@ -0,0 +1,227 @@
+// Copyright 2020 The Exonum Team
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Verification of the stored anchoring transactions chain.
+//!
+//! The verification doesn't trust any derived data of the service schema, so it can be
+//! used to audit the anchoring chain and to detect the storage corruption.
+
+use btc_transaction_utils::{
+    multisig::RedeemScript, p2wsh::InputSigner, InputSignatureRef, TxInRef,
+};
+use exonum::helpers::Height;
+use exonum_merkledb::access::Access;
+use thiserror::Error;
+
+use super::Schema;
+use crate::{btc, config::Config};
+
+/// Errors which indicate the inconsistency of the anchoring transactions chain.
+#[derive(Debug, Clone, PartialEq, Error)]
+pub enum ChainVerificationError {
+    /// Anchoring transaction doesn't contain a valid payload.
+    #[error("Anchoring transaction with index {0} has no valid payload")]
+    MalformedPayload(u64),
+    /// Anchoring transaction anchors the lower height than the previous one.
+    #[error(
+        "Anchoring transaction with index {index} anchors height {height}, which is lower \
+         than the previously anchored height {previous}"
+    )]
+    HeightDecreased {
+        /// Index of the anchoring transaction.
+        index: u64,
+        /// Anchored height.
+        height: Height,
+        /// Height anchored by the previous anchoring transaction.
+        previous: Height,
+    },
+    /// Anchoring transaction neither spends the previous anchoring transaction nor
+    /// refers to it as to the lost transactions chain.
+    #[error("Anchoring transaction with index {0} doesn't continue the previous one")]
+    BrokenChain(u64),
+    /// Output of the anchoring transaction doesn't belong to any of the anchoring
+    /// configurations.
+    #[error(
+        "Output of the anchoring transaction with index {0} doesn't match any \
+         anchoring configuration"
+    )]
+    UnknownOutputScript(u64),
+    /// Anchoring transaction sends funds to the configuration which precedes the one
+    /// used by the previous anchoring transaction.
+    #[error(
+        "Anchoring transaction with index {0} sends funds to an outdated anchoring \
+         configuration"
+    )]
+    OutdatedOutputScript(u64),
+    /// Transaction spent by the input of the anchoring transaction is unknown.
+    #[error(
+        "Transaction spent by input {input} of the anchoring transaction with index {index} \
+         is unknown"
+    )]
+    UnknownInput {
+        /// Index of the anchoring transaction.
+        index: u64,
+        /// Index of the input.
+        input: usize,
+    },
+    /// Input of the anchoring transaction doesn't spend the anchoring address.
+    #[error(
+        "Input {input} of the anchoring transaction with index {index} doesn't spend \
+         an anchoring address"
+    )]
+    UnknownInputScript {
+        /// Index of the anchoring transaction.
+        index: u64,
+        /// Index of the input.
+        input: usize,
+    },
+    /// Input of the anchoring transaction is not signed by the quorum of the anchoring nodes.
+    #[error(
+        "Input {input} of the anchoring transaction with index {index} has invalid signatures"
+    )]
+    InvalidSignatures {
+        /// Index of the anchoring transaction.
+        index: u64,
+        /// Index of the input.
+        input: usize,
+    },
+}
+
+/// Verifies the anchoring transactions chain stored in the data of the anchoring service
+/// instance and returns the number of the verified transactions.
+///
+/// The following properties are checked for each anchoring transaction:
+///
+/// - payload is valid and the anchored heights do not decrease;
+/// - transaction spends the previous anchoring transaction or refers to it
+///   in the payload as to the lost transactions chain;
+/// - output belongs to one of the anchoring configurations from the configuration
+///   history, and the configurations are used in the chronological order;
+/// - each input spends an anchoring address and is signed by the quorum of
+///   the corresponding anchoring keys.
+pub fn verify_anchoring_chain<T: Access>(access: T) -> Result<u64, ChainVerificationError> {
+    let schema = Schema::new(access);
+    let configs = schema
+        .config_history()
+        .into_iter()
+        .map(|change| change.config)
+        .collect::<Vec<_>>();
+
+    let mut previous: Option<(btc::Transaction, Height, usize)> = None;
+    for (index, tx) in schema.transactions_chain.iter().enumerate() {
+        let index = index as u64;
+        let payload = tx
+            .anchoring_payload_with(&btc::StrictPayloadCodec)
+            .ok_or(ChainVerificationError::MalformedPayload(index))?;
+        let config_index = configs
+            .iter()
+            .position(|config| config.anchoring_out_script() == tx.0.output[0].script_pubkey)
+            .ok_or(ChainVerificationError::UnknownOutputScript(index))?;
+
+        if let Some((prev_tx, prev_height, prev_config_index)) = previous {
+            // Accelerating transaction anchors the same height as the accelerated one.
+            if payload.block_height < prev_height {
+                return Err(ChainVerificationError::HeightDecreased {
+                    index,
+                    height: payload.block_height,
+                    previous: prev_height,
+                });
+            }
+            if config_index < prev_config_index {
+                return Err(ChainVerificationError::OutdatedOutputScript(index));
+            }
+            let spends_previous = tx.0.input[0].previous_output.txid == prev_tx.0.txid()
+                && tx.0.input[0].previous_output.vout == 0;
+            if !spends_previous && payload.prev_tx_chain != Some(prev_tx.id()) {
+                return Err(ChainVerificationError::BrokenChain(index));
+            }
+        }
+
+        verify_inputs(&schema, &configs, &tx, index)?;
+        previous = Some((tx, payload.block_height, config_index));
+    }
+    Ok(schema.transactions_chain.len())
+}
+
+/// Checks that each input of the anchoring transaction spends an anchoring address
+/// and is signed by the quorum of the corresponding anchoring keys.
+fn verify_inputs<T: Access>(
+    schema: &Schema<T>,
+    configs: &[Config],
+    tx: &btc::Transaction,
+    index: u64,
+) -> Result<(), ChainVerificationError> {
+    for (input, txin) in tx.0.input.iter().enumerate() {
+        let txid = btc::Sha256d::from(txin.previous_output.txid);
+        let spent_tx = schema
+            .transaction_index(&txid)
+            .and_then(|index| schema.transactions_chain.get(index))
+            // Indices of the transactions may be corrupted as well.
+            .filter(|spent_tx| spent_tx.id() == txid)
+            .or_else(|| schema.spent_funding_transactions.get(&txid))
+            .ok_or(ChainVerificationError::UnknownInput { index, input })?;
+        let spent_output = spent_tx
+            .0
+            .output
+            .get(txin.previous_output.vout as usize)
+            .ok_or(ChainVerificationError::UnknownInput { index, input })?;
+        let config = configs
+            .iter()
+            .find(|config| config.anchoring_out_script() == spent_output.script_pubkey)
+            .ok_or(ChainVerificationError::UnknownInputScript { index, input })?;
+
+        if !is_signed_by_quorum(config.redeem_script(), tx, input, &spent_tx) {
+            return Err(ChainVerificationError::InvalidSignatures { index, input });
+        }
+    }
+    Ok(())
+}
+
+/// Checks the witness of the input in the same way as `OP_CHECKMULTISIG`: the signatures
+/// must correspond to the quorum of the redeem script keys in the same order.
+fn is_signed_by_quorum(
+    redeem_script: RedeemScript,
+    tx: &btc::Transaction,
+    input: usize,
+    spent_tx: &btc::Transaction,
+) -> bool {
+    let witness = &tx.0.input[input].witness;
+    let (script, signatures) = match witness.split_last() {
+        Some((script, [dummy, signatures @ ..])) if dummy.is_empty() => (script, signatures),
+        _ => return false,
+    };
+    let content = redeem_script.content();
+    if script[..] != redeem_script.as_ref()[..] || signatures.len() != content.quorum {
+        return false;
+    }
+
+    let input_signer = InputSigner::new(redeem_script);
+    let mut public_keys = content.public_keys.iter();
+    signatures.iter().all(|signature| {
+        let signature = match InputSignatureRef::from_bytes(signature) {
+            Ok(signature) => signature,
+            Err(_) => return false,
+        };
+        public_keys.any(|public_key| {
+            input_signer
+                .verify_input(
+                    TxInRef::new(tx.as_ref(), input),
+                    spent_tx.as_ref(),
+                    public_key,
+                    signature,
+                )
+                .is_ok()
+        })
+    })
+}
@@ -0,0 +1,348 @@
+// Copyright 2020 The Exonum Team
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use bitcoin::{
+    blockdata::block,
+    consensus::params::Params,
+    network::constants::Network,
+    util::{hash::BitcoinHash, merkleblock, uint::Uint256},
+};
+use derive_more::{From, Into};
+use thiserror::Error;
+
+use super::Sha256d;
+
+/// Bitcoin block header wrapper.
+#[derive(Debug, Clone, Copy, From, Into, PartialEq)]
+pub struct BlockHeader(pub block::BlockHeader);
+
+impl_wrapper_for_bitcoin_type! { BlockHeader }
+
+/// Bitcoin Merkle block wrapper, i.e. a block header with the partial Merkle tree
+/// which proves inclusion of the matched transactions in the block.
+#[derive(Debug, Clone, From, Into, PartialEq)]
+pub struct MerkleBlock(pub merkleblock::MerkleBlock);
+
+impl_wrapper_for_bitcoin_type! { MerkleBlock }
+
+/// Errors which may occur while extending the stored chain of the Bitcoin block headers.
+#[derive(Debug, Copy, Clone, PartialEq, Error)]
+pub enum HeaderChainError {
+    /// The height of the first header doesn't follow the top of the stored chain.
+    #[error("Unexpected height of the Bitcoin block header, expected {expected}, got {actual}")]
+    UnexpectedHeight {
+        /// Expected height.
+        expected: u64,
+        /// Actual height.
+        actual: u64,
+    },
+    /// The header doesn't refer to the previous header in the chain.
+    #[error("Bitcoin block header with height {height} doesn't refer to the previous one")]
+    Discontinuity {
+        /// Height of the unsuitable header.
+        height: u64,
+    },
+    /// The hash of the header doesn't satisfy the target declared in it.
+    #[error("Bitcoin block header with height {height} has invalid proof of work")]
+    InvalidProofOfWork {
+        /// Height of the unsuitable header.
+        height: u64,
+    },
+    /// The target declared in the header doesn't follow the difficulty rules of the network.
+    #[error("Bitcoin block header with height {height} has unexpected target")]
+    UnexpectedTarget {
+        /// Height of the unsuitable header.
+        height: u64,
+    },
+    /// The checkpoint of the chain is not at the difficulty adjustment boundary, so
+    /// the following retargets cannot be verified.
+    #[error("Bitcoin checkpoint height {height} is not a multiple of the retarget interval")]
+    UnalignedCheckpoint {
+        /// Height of the checkpoint.
+        height: u64,
+    },
+}
+
+impl BlockHeader {
+    /// Return the hash of the block.
+    pub fn block_hash(&self) -> Sha256d {
+        Sha256d(self.0.bitcoin_hash().into())
+    }
+
+    /// Return the hash of the previous block in the chain.
+    pub fn prev_block_hash(&self) -> Sha256d {
+        Sha256d(self.0.prev_blockhash.into())
+    }
+
+    /// Verify the proof of work of the header with the given height in the specified
+    /// Bitcoin network. If the previous header is known, also check that the target of
+    /// this header follows the difficulty adjustment rules.
+    ///
+    /// On the retarget boundaries the target is computed from the timespan of the previous
+    /// adjustment period, so the first header of that period, i.e. the header with
+    /// the height `height - difficulty_adjustment_interval`, is required as well.
+    ///
+    /// On the networks which allow blocks with the minimal difficulty, the header which
+    /// doesn't use the minimal difficulty has to keep the target of the latest regular
+    /// header preceding it (see [`is_regular`]). If it is not given, the previous header
+    /// is assumed to be the regular one.
+    ///
+    /// [`is_regular`]: #method.is_regular
+    pub fn verify_proof_of_work(
+        &self,
+        prev: Option<&Self>,
+        period_start: Option<&Self>,
+        last_regular: Option<&Self>,
+        height: u64,
+        network: Network,
+    ) -> Result<(), HeaderChainError> {
+        let params = Params::new(network);
+        let target = self.0.target();
+        if target > params.pow_limit {
+            return Err(HeaderChainError::UnexpectedTarget { height });
+        }
+        self.0
+            .validate_pow(&target)
+            .map_err(|_| HeaderChainError::InvalidProofOfWork { height })?;
+
+        let prev = if let Some(prev) = prev {
+            prev
+        } else {
+            return Ok(());
+        };
+        let required_bits = Self::required_bits(
+            &params,
+            prev,
+            period_start,
+            last_regular,
+            self.0.time,
+            height,
+        );
+        if required_bits == Some(self.0.bits) {
+            Ok(())
+        } else {
+            Err(HeaderChainError::UnexpectedTarget { height })
+        }
+    }
+
+    /// Returns the compact target required for the block with the given height and timestamp
+    /// which follows the given previous header, or `None` if the first header of the previous
+    /// adjustment period is needed but not given. The computation follows
+    /// `GetNextWorkRequired` of Bitcoin Core.
+    fn required_bits(
+        params: &Params,
+        prev: &Self,
+        period_start: Option<&Self>,
+        last_regular: Option<&Self>,
+        time: u32,
+        height: u64,
+    ) -> Option<u32> {
+        if params.no_pow_retargeting {
+            Some(prev.0.bits)
+        } else if height % params.difficulty_adjustment_interval() == 0 {
+            period_start.map(|period_start| Self::retarget(params, prev, period_start))
+        } else if params.allow_min_difficulty_blocks {
+            // The block may have the minimal difficulty only if it has been mined more than
+            // twice the target spacing after the previous one, otherwise it keeps the target
+            // of the latest regular block.
+            if u64::from(time) > u64::from(prev.0.time) + params.pow_target_spacing * 2 {
+                Some(block::BlockHeader::compact_target_from_u256(
+                    &params.pow_limit,
+                ))
+            } else {
+                Some(last_regular.unwrap_or(prev).0.bits)
+            }
+        } else {
+            Some(prev.0.bits)
+        }
+    }
+
+    /// Returns `true` if the target of the header with the given height is inherited by
+    /// the following headers on the networks which allow blocks with the minimal difficulty,
+    /// i.e. the header either starts the adjustment period or doesn't have the minimal
+    /// difficulty. On the other networks all headers are regular.
+    pub fn is_regular(&self, height: u64, network: Network) -> bool {
+        let params = Params::new(network);
+        !params.allow_min_difficulty_blocks
+            || height % params.difficulty_adjustment_interval() == 0
+            || self.0.bits != block::BlockHeader::compact_target_from_u256(&params.pow_limit)
+    }
+
+    /// Returns the compact target of the first block of the adjustment period following
+    /// the period which starts with the given first header and ends with the given last
+    /// one. The computation follows `CalculateNextWorkRequired` of Bitcoin Core.
+    fn retarget(params: &Params, last: &Self, first: &Self) -> u32 {
+        let target_timespan = params.pow_target_timespan as i64;
+        let timespan = (i64::from(last.0.time) - i64::from(first.0.time))
+            .max(target_timespan / 4)
+            .min(target_timespan * 4);
+        let mut target = last.0.target().mul_u32(timespan as u32)
+            / Uint256::from_u64(params.pow_target_timespan).unwrap();
+        if target > params.pow_limit {
+            target = params.pow_limit;
+        }
+        block::BlockHeader::compact_target_from_u256(&target)
+    }
+}
+
+impl MerkleBlock {
+    /// Return the header of the block.
+    pub fn header(&self) -> BlockHeader {
+        BlockHeader(self.0.header)
+    }
+
+    /// Return identifiers of the transactions proven to be included in the block,
+    /// or `None` if the proof doesn't match the block header.
+    pub fn matched_transactions(&self) -> Option<Vec<Sha256d>> {
+        let mut matches = Vec::new();
+        let mut indexes = Vec::new();
+        self.0.extract_matches(&mut matches, &mut indexes).ok()?;
+        Some(matches.into_iter().map(Sha256d::from).collect())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bitcoin::{
+        blockdata::block, consensus::deserialize, consensus::params::Params, Block, Network,
+    };
+    use hex::FromHex;
+
+    use super::{BlockHeader, MerkleBlock};
+    use crate::btc::Sha256d;
+
+    // Block 80000 of the Bitcoin mainnet.
+    const BLOCK_80000: &str = "01000000ba8b9cda965dd8e536670f9ddec10e53aab14b20bacad27b913719\
+        0000000000190760b278fe7b8565fda3b968b918d5fd997f993b23674c0af3b6fde300b38f33a5914ce6ed5b\
+        1b01e32f570201000000010000000000000000000000000000000000000000000000000000000000000000ff\
+        ffffff0704e6ed5b1b014effffffff0100f2052a01000000434104b68a50eaa0287eff855189f949c1c6e5f5\
+        8b37c88231373d8a59809cbae83059cc6469d65c665ccfd1cfeb75c6e8e19413bba7fbff9bc762419a76d87b\
+        16086eac000000000100000001a6b97044d03da79c005b20ea9c0e1a6d9dc12d9f7b91a5911c9030a439eed8\
+        f5000000004948304502206e21798a42fae0e854281abd38bacd1aeed3ee3738d9e1446618c4571d1090db02\
+        2100e2ac980643b0b82c0e88ffdfec6b64e3e6ba35e7ba5fdd7d5d6cc8d25c6b241501ffffffff0100f2052a\
+        010000001976a914404371705fa9bd789a2fcd52d2c580b65d35549d88ac00000000";
+
+    #[test]
+    fn test_merkle_block_matched_transactions() {
+        let block: Block = deserialize(&Vec::from_hex(BLOCK_80000).unwrap()).unwrap();
+        let txid = block.txdata[1].txid();
+
+        let merkle_block = MerkleBlock(bitcoin::MerkleBlock::from_block(
+            &block,
+            &vec![txid].into_iter().collect(),
+        ));
+        assert_eq!(merkle_block.header(), BlockHeader(block.header));
+        assert_eq!(
+            merkle_block.matched_transactions(),
+            Some(vec![Sha256d::from(txid)])
+        );
+        assert_eq!(
+            merkle_block.header().block_hash().to_string(),
+            "000000000043a8c0fd1d6f726790caa2a406010d19efd2780db27bdbbd93baf6"
+        );
+
+        // Merkle root doesn't match the partial Merkle tree.
+        let mut corrupted_block = merkle_block;
+        corrupted_block.0.header.merkle_root = Default::default();
+        assert_eq!(corrupted_block.matched_transactions(), None);
+    }
+
+    #[test]
+    fn test_retarget() {
+        let params = Params::new(Network::Bitcoin);
+        let header = |time| {
+            BlockHeader(block::BlockHeader {
+                version: 1,
+                prev_blockhash: Default::default(),
+                merkle_root: Default::default(),
+                time,
+                bits: 0x1d00_ffff,
+                nonce: 0,
+            })
+        };
+        let first = header(1_000_000);
+        let timespan = params.pow_target_timespan as u32;
+
+        // The target is proportional to the timespan of the adjustment period.
+        let last = header(1_000_000 + timespan / 2);
+        assert_eq!(BlockHeader::retarget(&params, &last, &first), 0x1c7f_ff80);
+        // The target is changed at most by the factor of four.
+        let last = header(1_000_000 + timespan / 10);
+        assert_eq!(BlockHeader::retarget(&params, &last, &first), 0x1c3f_ffc0);
+        // The target cannot exceed the proof of work limit.
+        let last = header(1_000_000 + timespan * 2);
+        assert_eq!(BlockHeader::retarget(&params, &last, &first), 0x1d00_ffff);
+    }
+
+    #[test]
+    fn test_min_difficulty_blocks() {
+        let params = Params::new(Network::Testnet);
+        let min_difficulty_bits = 0x1d00_ffff;
+        let header = |time, bits| {
+            BlockHeader(block::BlockHeader {
+                version: 1,
+                prev_blockhash: Default::default(),
+                merkle_root: Default::default(),
+                time,
+                bits,
+                nonce: 0,
+            })
+        };
+        let spacing = params.pow_target_spacing as u32;
+        let height = params.difficulty_adjustment_interval() + 10;
+        let regular = header(1_000_000, 0x1a01_aa3d);
+        let required_bits = |prev: &BlockHeader, last_regular: &BlockHeader, time| {
+            BlockHeader::required_bits(&params, prev, None, Some(last_regular), time, height)
+        };
+
+        // The block mined in time keeps the target of the previous regular block.
+        assert_eq!(
+            required_bits(&regular, &regular, 1_000_000 + spacing),
+            Some(regular.0.bits)
+        );
+        // The block mined after twice the target spacing has the minimal difficulty.
+        assert_eq!(
+            required_bits(&regular, &regular, 1_000_001 + spacing * 2),
+            Some(min_difficulty_bits)
+        );
+        assert_eq!(
+            required_bits(&regular, &regular, 1_000_000 + spacing * 2),
+            Some(regular.0.bits)
+        );
+
+        // The block following the minimal difficulty one in time returns to the target
+        // of the latest regular block rather than keeps the minimal difficulty.
+        let min_difficulty = header(1_000_000 + spacing * 3, min_difficulty_bits);
+        assert_eq!(
+            required_bits(&min_difficulty, &regular, 1_000_000 + spacing * 4),
+            Some(regular.0.bits)
+        );
+        assert_eq!(
+            required_bits(&min_difficulty, &regular, 1_000_001 + spacing * 5),
+            Some(min_difficulty_bits)
+        );
+
+        // Only the headers at the retarget boundaries or without the minimal difficulty
+        // are regular.
+        assert!(regular.is_regular(height, Network::Testnet));
+        assert!(!min_difficulty.is_regular(height, Network::Testnet));
+        assert!(min_difficulty.is_regular(height - 10, Network::Testnet));
+        assert!(min_difficulty.is_regular(height, Network::Bitcoin));
+        // Retarget requires the first header of the previous adjustment period.
+        assert_eq!(
+            BlockHeader::required_bits(&params, &regular, None, None, 0, height - 10),
+            None
+        );
+    }
+}
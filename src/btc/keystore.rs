@@ -0,0 +1,164 @@
+// Copyright 2020 The Exonum Team
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use bitcoin::{network::constants::Network, secp256k1};
+use pwbox::{sodium::Sodium, ErasedPwBox, Eraser, Suite};
+use rand_core::OsRng;
+use serde_derive::{Deserialize, Serialize};
+use thiserror::Error;
+
+#[cfg(unix)]
+use std::os::unix::fs::OpenOptionsExt;
+use std::{
+    fs::{self, OpenOptions},
+    io::{self, Write},
+    path::Path,
+};
+
+use super::{PrivateKey, PublicKey};
+
+/// Bitcoin private key encrypted by the passphrase.
+///
+/// The key is sealed by the scrypt key derivation function and the XSalsa20-Poly1305
+/// cipher, in the same way as the master key of the Exonum node. The public key and
+/// the network are kept in the clear, so the encrypted key can be matched with
+/// the anchoring keys without the passphrase.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct EncryptedPrivateKey {
+    /// Public key corresponding to the encrypted private key.
+    pub public_key: PublicKey,
+    /// Bitcoin network of the private key.
+    #[serde(with = "serde_str")]
+    pub network: Network,
+    /// Encrypted content of the private key.
+    key: ErasedPwBox,
+}
+
+/// Errors that occur when handling the encrypted Bitcoin private keys.
+#[derive(Debug, Error)]
+pub enum KeystoreError {
+    /// Private key cannot be encrypted.
+    #[error("Unable to encrypt the private key.")]
+    Encryption,
+    /// Private key cannot be decrypted, most likely because of the wrong passphrase.
+    #[error("Unable to decrypt the private key {0}, perhaps the passphrase is wrong.")]
+    Decryption(PublicKey),
+    /// Decrypted private key doesn't correspond to the public key.
+    #[error("Decrypted private key doesn't correspond to the public key {0}.")]
+    KeyMismatch(PublicKey),
+    /// Keystore file cannot be read or written.
+    #[error("Unable to access the keystore file: {0}")]
+    Io(#[from] io::Error),
+    /// Keystore file is malformed.
+    #[error("Unable to parse the keystore file: {0}")]
+    Parse(#[from] toml::de::Error),
+    /// Keystore cannot be serialized.
+    #[error("Unable to serialize the keystore: {0}")]
+    Serialize(#[from] toml::ser::Error),
+}
+
+impl EncryptedPrivateKey {
+    /// Encrypts the given private key by the passphrase.
+    pub fn encrypt(
+        private_key: &PrivateKey,
+        passphrase: impl AsRef<[u8]>,
+    ) -> Result<Self, KeystoreError> {
+        let pwbox = Sodium::build_box(&mut OsRng)
+            .seal(passphrase, &private_key.0.key[..])
+            .map_err(|_| KeystoreError::Encryption)?;
+        let key = eraser()
+            .erase(&pwbox)
+            .map_err(|_| KeystoreError::Encryption)?;
+
+        Ok(Self {
+            public_key: private_key.public_key(),
+            network: private_key.0.network,
+            key,
+        })
+    }
+
+    /// Decrypts the private key by the passphrase.
+    pub fn decrypt(&self, passphrase: impl AsRef<[u8]>) -> Result<PrivateKey, KeystoreError> {
+        let decryption_error = || KeystoreError::Decryption(self.public_key);
+        let content = eraser()
+            .restore(&self.key)
+            .map_err(|_| decryption_error())?
+            .open(passphrase)
+            .map_err(|_| decryption_error())?;
+        let key = secp256k1::SecretKey::from_slice(&content).map_err(|_| decryption_error())?;
+
+        let private_key = PrivateKey(bitcoin::PrivateKey {
+            compressed: true,
+            network: self.network,
+            key,
+        });
+        if private_key.public_key() != self.public_key {
+            return Err(KeystoreError::KeyMismatch(self.public_key));
+        }
+        Ok(private_key)
+    }
+
+    /// Loads the encrypted private key from the keystore file.
+    pub fn load(path: impl AsRef<Path>) -> Result<Self, KeystoreError> {
+        let content = fs::read_to_string(path)?;
+        Ok(toml::from_str(&content)?)
+    }
+
+    /// Saves the encrypted private key to the keystore file, which is readable
+    /// only by its owner on Unix.
+    pub fn save(&self, path: impl AsRef<Path>) -> Result<(), KeystoreError> {
+        let content = toml::to_string_pretty(self)?;
+        let mut open_options = OpenOptions::new();
+        open_options.create(true).write(true).truncate(true);
+        // Use the same permissions as for the SSH private keys.
+        #[cfg(unix)]
+        open_options.mode(0o600);
+        open_options
+            .open(path)?
+            .write_all(content.as_bytes())
+            .map_err(From::from)
+    }
+}
+
+fn eraser() -> Eraser {
+    let mut eraser = Eraser::new();
+    eraser.add_suite::<Sodium>();
+    eraser
+}
+
+#[cfg(test)]
+mod tests {
+    use bitcoin::network::constants::Network;
+
+    use super::{EncryptedPrivateKey, KeystoreError};
+    use crate::btc::gen_keypair;
+
+    #[test]
+    fn encrypted_private_key_roundtrip() {
+        let (public_key, private_key) = gen_keypair(Network::Testnet);
+        let encrypted = EncryptedPrivateKey::encrypt(&private_key, "passphrase").unwrap();
+        assert_eq!(encrypted.public_key, public_key);
+        assert_eq!(encrypted.network, Network::Testnet);
+
+        let content = toml::to_string_pretty(&encrypted).unwrap();
+        assert!(!content.contains(&private_key.to_string()));
+        let encrypted: EncryptedPrivateKey = toml::from_str(&content).unwrap();
+        assert!(encrypted.decrypt("passphrase").unwrap() == private_key);
+
+        match encrypted.decrypt("wrong passphrase") {
+            Err(KeystoreError::Decryption(key)) => assert_eq!(key, public_key),
+            other => panic!("Unexpected decryption result: {:?}", other.map(|_| ())),
+        }
+    }
+}
@@ -16,8 +16,12 @@
 
 pub use btc_transaction_utils::test_data::{secp_gen_keypair, secp_gen_keypair_with_rng};
 
+#[cfg(feature = "sync-binary")]
+pub use self::keystore::{EncryptedPrivateKey, KeystoreError};
 pub use self::{
-    payload::Payload,
+    block::{BlockHeader, HeaderChainError, MerkleBlock},
+    payload::{DefaultPayloadCodec, Payload, PayloadCodec, PayloadFormat, StrictPayloadCodec},
+    psbt::{Psbt, PsbtError},
     transaction::{BtcAnchoringTransactionBuilder, BuilderError, Transaction},
 };
 
@@ -32,7 +36,11 @@ use serde_derive::{Deserialize, Serialize};
 #[macro_use]
 mod macros;
 
+pub(crate) mod block;
+#[cfg(feature = "sync-binary")]
+pub(crate) mod keystore;
 pub(crate) mod payload;
+pub(crate) mod psbt;
 pub(crate) mod transaction;
 
 /// Bitcoin ECDSA private key wrapper.
@@ -52,11 +60,35 @@ pub struct Address(pub address::Address);
 pub struct InputSignature(pub btc_transaction_utils::InputSignature);
 
 /// Bitcoin SHA256d hash.
+///
+/// Bitcoin tooling (RPC, block explorers) displays the transaction and block hashes
+/// in the reversed byte order, while the serialized Bitcoin data uses the internal one.
+/// `Sha256d` follows the same conventions:
+///
+/// - `Display`, `FromStr` and the human-readable serde formats (e.g. JSON in the HTTP API)
+///   use the reversed hex, the same as in the Bitcoin RPC;
+/// - [`new`], [`from_slice`] and the binary serde formats use the internal byte order.
+///
+/// Use [`from_internal_hex`] and [`to_internal_hex`] to handle the hex strings in
+/// the internal byte order, e.g. the raw data from the serialized transactions.
+///
+/// [`new`]: #method.new
+/// [`from_slice`]: #method.from_slice
+/// [`from_internal_hex`]: #method.from_internal_hex
+/// [`to_internal_hex`]: #method.to_internal_hex
 #[derive(
     Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Into, From, Serialize, Deserialize, Display,
 )]
 pub struct Sha256d(pub sha256d::Hash);
 
+impl PrivateKey {
+    /// Returns the public key corresponding to this private key.
+    pub fn public_key(&self) -> PublicKey {
+        let context = bitcoin::secp256k1::Secp256k1::signing_only();
+        PublicKey(self.0.public_key(&context))
+    }
+}
+
 impl ToString for PrivateKey {
     fn to_string(&self) -> String {
         self.0.to_string()
@@ -190,6 +222,47 @@ impl Sha256d {
         use bitcoin_hashes::Hash;
         sha256d::Hash::from_slice(slice).ok().map(Self)
     }
+
+    /// Creates a new instance from bytes array in the reversed (display) byte order.
+    pub fn from_display_bytes(mut bytes: [u8; Self::LEN]) -> Self {
+        bytes.reverse();
+        Self::new(bytes)
+    }
+
+    /// Returns the hash bytes in the internal byte order.
+    pub fn to_internal_bytes(&self) -> [u8; Self::LEN] {
+        use bitcoin_hashes::Hash;
+        self.0.into_inner()
+    }
+
+    /// Returns the hash bytes in the reversed (display) byte order.
+    pub fn to_display_bytes(&self) -> [u8; Self::LEN] {
+        let mut bytes = self.to_internal_bytes();
+        bytes.reverse();
+        bytes
+    }
+
+    /// Parses the hash from the hex string in the internal byte order.
+    pub fn from_internal_hex(hex: &str) -> anyhow::Result<Self> {
+        let bytes = <[u8; Self::LEN]>::from_hex(hex)?;
+        Ok(Self::new(bytes))
+    }
+
+    /// Returns the hex string of the hash in the internal byte order.
+    pub fn to_internal_hex(&self) -> String {
+        self.to_internal_bytes().encode_hex()
+    }
+}
+
+impl std::str::FromStr for Sha256d {
+    type Err = anyhow::Error;
+
+    /// Parses the hash from the hex string in the reversed (display) byte order.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        <sha256d::Hash as bitcoin_hashes::hex::FromHex>::from_hex(s)
+            .map(Self)
+            .map_err(From::from)
+    }
 }
 
 impl AsRef<bitcoin_hashes::sha256d::Hash> for Sha256d {
@@ -232,3 +305,38 @@ pub fn gen_keypair(network: Network) -> (PublicKey, PrivateKey) {
     let (pk, sk) = secp_gen_keypair(network);
     (PublicKey(pk), PrivateKey(sk))
 }
+
+#[cfg(test)]
+mod tests {
+    use super::Sha256d;
+
+    /// Identifier of the coinbase transaction of the Bitcoin genesis block.
+    const GENESIS_TXID: &str = "4a5e1e4baab89f3a32518a88c31bc87f618f76673e2cc77ab2127b7afdeda33b";
+
+    #[test]
+    fn test_sha256d_byte_order() {
+        let txid: Sha256d = GENESIS_TXID.parse().unwrap();
+        assert_eq!(txid.to_string(), GENESIS_TXID);
+        // Internal byte order is reversed relative to the displayed one.
+        assert_eq!(txid.to_internal_bytes()[0], 0x3b);
+        assert_eq!(txid.to_display_bytes()[0], 0x4a);
+        assert_eq!(Sha256d::from_display_bytes(txid.to_display_bytes()), txid);
+        assert_eq!(Sha256d::new(txid.to_internal_bytes()), txid);
+        assert_eq!(
+            txid.to_internal_hex(),
+            "3ba3edfd7a7b12b27ac72c3e67768f617fc81bc3888a51323a9fb8aa4b1e5e4a"
+        );
+        assert_eq!(Sha256d::from_internal_hex(&txid.to_internal_hex()).unwrap(), txid);
+        // Bitcoin transaction identifier has the same byte order.
+        let bitcoin_txid: bitcoin::hash_types::Txid = txid.into();
+        assert_eq!(bitcoin_txid.to_string(), GENESIS_TXID);
+    }
+
+    #[test]
+    fn test_sha256d_serde() {
+        let txid: Sha256d = GENESIS_TXID.parse().unwrap();
+        let json = serde_json::to_string(&txid).unwrap();
+        assert_eq!(json, format!("\"{}\"", GENESIS_TXID));
+        assert_eq!(serde_json::from_str::<Sha256d>(&json).unwrap(), txid);
+    }
+}
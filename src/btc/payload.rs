@@ -14,13 +14,18 @@
 
 use exonum::{crypto::Hash, helpers::Height};
 
-use bitcoin::blockdata::{
-    opcodes::all::OP_RETURN,
-    script::{Builder, Instruction, Script},
+use bitcoin::{
+    blockdata::{
+        opcodes::all::OP_RETURN,
+        script::{Builder, Instruction, Script},
+    },
+    consensus::encode::{self, VarInt},
 };
 use byteorder::{ByteOrder, LittleEndian};
 use serde_derive::{Deserialize, Serialize};
 
+use std::{convert::TryFrom, fmt::Debug};
+
 use super::Sha256d;
 
 const PAYLOAD_PREFIX: &[u8] = b"EXONUM";
@@ -28,6 +33,20 @@ const PAYLOAD_HEADER_LEN: usize = 8;
 const PAYLOAD_V1: u8 = 1;
 const PAYLOAD_V1_KIND_REGULAR: u8 = 0;
 const PAYLOAD_V1_KIND_RECOVER: u8 = 1;
+const PAYLOAD_V1_KIND_GENESIS: u8 = 2;
+const PAYLOAD_V2: u8 = 2;
+const COMPACT_PAYLOAD_PREFIX: &[u8] = b"EX";
+const COMPACT_PAYLOAD_HEADER_LEN: usize = 4;
+const PAYLOAD_V3_COMPACT: u8 = 3;
+const PAYLOAD_V4_EXTENSIBLE: u8 = 4;
+const FIELD_BLOCK_HEIGHT: u8 = 0;
+const FIELD_BLOCK_HASH: u8 = 1;
+const FIELD_PREV_TX_CHAIN: u8 = 2;
+const FIELD_CONFIG_DIGEST: u8 = 3;
+const FIELD_EXTENSION_DIGESTS: u8 = 4;
+/// Maximum length of the data in the `OP_RETURN` output relayed by the Bitcoin nodes
+/// with the default policy.
+const MAX_PAYLOAD_CHUNK_LEN: usize = 80;
 
 /// Anchoring transaction payload.
 ///
@@ -37,12 +56,76 @@ const PAYLOAD_V1_KIND_RECOVER: u8 = 1;
 /// |-----------------------|---------------------------------------------------|
 /// | 0..6                  | ASCII-encoded prefix `EXONUM`                     |
 /// | 6                     | Version byte, currently is 1                      |
-/// | 7                     | Payload kind: (0 is regular, 1 is recover)        |
+/// | 7                     | Payload kind: (0 is regular, 1 is recover,        |
+/// |                       | 2 is genesis)                                     |
 /// | 8..16                 | Block height                                      |
 /// | 16..48                | Block hash                                        |
 /// | 48..80 (Optionally)   | Txid of previous tx chain (only for recover kind) |
+/// | 48..80 (Optionally)   | Initial config digest (only for genesis kind)     |
+///
+/// In this way the length of `regular` payload is 48, and for `recover` and `genesis`
+/// is 80.
+///
+/// `Payload` v.2 additionally commits the extension digests. Since such a payload
+/// may exceed the standard `OP_RETURN` limit, it is split into chunks of at most
+/// 80 bytes, which are placed into the consecutive `OP_RETURN` outputs:
+///
+/// | Position in bytes     | Description                                       |
+/// |-----------------------|---------------------------------------------------|
+/// | 0..6                  | ASCII-encoded prefix `EXONUM`                     |
+/// | 6                     | Version byte, is 2                                |
+/// | 7                     | Number of chunks                                  |
+/// | 8..                   | Payload v.1 data starting from the kind byte,     |
+/// |                       | followed by the 32-byte extension digests         |
+///
+/// Only the first chunk contains the header, the following chunks contain the
+/// continuation of the data.
+///
+/// Compact `Payload` v.3 has the same content as v.1, but uses the shorter prefix
+/// and encodes the block height as a Bitcoin `VarInt`:
+///
+/// | Position in bytes     | Description                                       |
+/// |-----------------------|---------------------------------------------------|
+/// | 0..2                  | ASCII-encoded prefix `EX`                         |
+/// | 2                     | Version byte, is 3                                |
+/// | 3                     | Payload kind, the same as for v.1                 |
+/// | 4..                   | Block height as `VarInt` (1 to 9 bytes), followed |
+/// |                       | by the block hash and the optional 32-byte field  |
+///
+/// The txid of the previous tx chain and the initial config digest are not truncated
+/// in v.3, since the verifiers need the whole values to link the recovered chain and
+/// to check the initial configuration. Thus the compact encoding saves only the bytes
+/// of the prefix and of the block height.
+///
+/// Extensible `Payload` v.4 consists of the typed fields, so the new fields can be
+/// added without breaking the existing verifiers. It is split into chunks in the same
+/// way as v.2:
+///
+/// | Position in bytes     | Description                                       |
+/// |-----------------------|---------------------------------------------------|
+/// | 0..6                  | ASCII-encoded prefix `EXONUM`                     |
+/// | 6                     | Version byte, is 4                                |
+/// | 7                     | Number of chunks                                  |
+/// | 8..                   | Fields in the ascending order of their types      |
+///
+/// Each field is encoded as the type byte, followed by the length of the value
+/// as a Bitcoin `VarInt` and the value itself:
+///
+/// | Field type            | Value                                             |
+/// |-----------------------|---------------------------------------------------|
+/// | 0                     | Block height as `VarInt`                          |
+/// | 1                     | Block hash                                        |
+/// | 2 (Optionally)        | Txid of previous tx chain                         |
+/// | 3 (Optionally)        | Initial config digest                             |
+/// | 4 (Optionally)        | Concatenated 32-byte extension digests            |
+///
+/// The fields of the unknown types are skipped by [`DefaultPayloadCodec`] and rejected
+/// by [`StrictPayloadCodec`].
 ///
-/// In this way the length of `regular` payload is 48, and for `recover` is 80.
+/// Verifiers recognize all payload versions by their headers.
+///
+/// [`DefaultPayloadCodec`]: struct.DefaultPayloadCodec.html
+/// [`StrictPayloadCodec`]: struct.StrictPayloadCodec.html
 #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct Payload {
     /// Anchored block height.
@@ -51,24 +134,89 @@ pub struct Payload {
     pub block_hash: Hash,
     /// `Txid` of previous transactions chain if it has been lost.
     pub prev_tx_chain: Option<Sha256d>,
+    /// Hash of the initial anchoring configuration, it is committed only
+    /// in the first transaction of the anchoring chain.
+    pub config_digest: Option<Hash>,
+    /// Additional digests committed in the payload v.2.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub extension_digests: Vec<Hash>,
+}
+
+/// Format of the anchoring transaction payload.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PayloadFormat {
+    /// Payload v.1, or v.2 if the payload contains the extension digests.
+    Standard,
+    /// Compact payload v.3, or v.2 if the payload contains the extension digests.
+    Compact,
+    /// Extensible payload v.4.
+    Extensible,
+}
+
+impl Default for PayloadFormat {
+    fn default() -> Self {
+        PayloadFormat::Standard
+    }
+}
+
+/// Codec of the anchoring transaction payload placed into the `OP_RETURN` outputs.
+///
+/// The codec is used both for building the anchoring transactions and for parsing
+/// their payloads, so a custom codec allows to embed the deployment-specific metadata
+/// into the anchoring transactions. [`DefaultPayloadCodec`] implements the standard
+/// payload format described in [`Payload`].
+///
+/// [`DefaultPayloadCodec`]: struct.DefaultPayloadCodec.html
+/// [`Payload`]: struct.Payload.html
+pub trait PayloadCodec: Debug + Send + Sync {
+    /// Encodes the payload into the scripts for the consecutive `OP_RETURN` outputs.
+    /// The `format` reflects the `compact_payload` and `extensible_payload` configuration
+    /// parameters and may be ignored by the codec.
+    fn encode(&self, payload: &Payload, format: PayloadFormat) -> Vec<Script>;
+    /// Tries to decode the payload from the scripts of the consecutive outputs following
+    /// the anchoring output.
+    fn decode(&self, scripts: &[&Script]) -> Option<Payload>;
 }
 
+/// Codec of the standard payload format.
+///
+/// The unknown fields of the extensible payload are skipped, so the payloads created
+/// by the newer versions of the service remain readable.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct DefaultPayloadCodec;
+
+/// Codec of the standard payload format, which rejects the extensible payloads with
+/// the unknown fields.
+///
+/// It is used by the consensus code, so the anchoring nodes never accept the commitments
+/// which they cannot verify.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct StrictPayloadCodec;
+
 #[derive(Debug)]
 enum PayloadV1 {
     Regular(Height, Hash),
     Recover(Height, Hash, Sha256d),
+    Genesis(Height, Hash, Hash),
+}
+
+#[derive(Debug)]
+struct PayloadV2 {
+    base: PayloadV1,
+    extension_digests: Vec<Hash>,
 }
 
 #[derive(Debug, Default)]
-pub struct PayloadV1Builder {
+pub struct PayloadBuilder {
     block_hash: Option<Hash>,
     block_height: Option<Height>,
     prev_tx_chain: Option<Sha256d>,
+    config_digest: Option<Hash>,
+    extension_digests: Vec<Hash>,
+    format: PayloadFormat,
 }
 
-pub type PayloadBuilder = PayloadV1Builder;
-
-#[cfg_attr(feature = "cargo-clippy", allow(clippy::len_without_is_empty))]
+#[allow(clippy::len_without_is_empty)]
 impl PayloadV1 {
     fn read(bytes: &[u8]) -> Option<Self> {
         let kind = bytes[0];
@@ -93,6 +241,20 @@ impl PayloadV1 {
                 let txid = Sha256d::from_slice(&data[40..72]).unwrap();
                 Some(PayloadV1::Recover(Height(block_height), block_hash, txid))
             }
+            PAYLOAD_V1_KIND_GENESIS => {
+                if data.len() != 72 {
+                    return None;
+                }
+
+                let block_height = LittleEndian::read_u64(&data[0..8]);
+                let block_hash = Hash::from_slice(&data[8..40]).unwrap();
+                let config_digest = Hash::from_slice(&data[40..72]).unwrap();
+                Some(PayloadV1::Genesis(
+                    Height(block_height),
+                    block_hash,
+                    config_digest,
+                ))
+            }
             _ => None,
         }
     }
@@ -114,13 +276,18 @@ impl PayloadV1 {
                 buf[8..40].copy_from_slice(hash.as_ref());
                 buf[40..72].copy_from_slice(&txid.0[..]);
             }
+            PayloadV1::Genesis(height, hash, config_digest) => {
+                LittleEndian::write_u64(&mut buf[0..8], height.0);
+                buf[8..40].copy_from_slice(hash.as_ref());
+                buf[40..72].copy_from_slice(config_digest.as_ref());
+            }
         };
     }
 
     fn len(&self) -> usize {
         match *self {
             PayloadV1::Regular(..) => 40,
-            PayloadV1::Recover(..) => 72,
+            PayloadV1::Recover(..) | PayloadV1::Genesis(..) => 72,
         }
     }
 
@@ -128,9 +295,36 @@ impl PayloadV1 {
         match *self {
             PayloadV1::Regular(..) => PAYLOAD_V1_KIND_REGULAR,
             PayloadV1::Recover(..) => PAYLOAD_V1_KIND_RECOVER,
+            PayloadV1::Genesis(..) => PAYLOAD_V1_KIND_GENESIS,
         }
     }
 
+    fn read_compact(bytes: &[u8]) -> Option<Self> {
+        let kind = *bytes.first()?;
+        let (height, height_len) = encode::deserialize_partial::<VarInt>(&bytes[1..]).ok()?;
+        // Restore the v.1 layout with the fixed-size block height.
+        let mut buf = vec![kind];
+        buf.extend_from_slice(&height.0.to_le_bytes());
+        buf.extend_from_slice(&bytes[1 + height_len..]);
+        Self::read(&buf)
+    }
+
+    fn into_compact_script(self) -> Script {
+        let mut buf = vec![0; self.len() + 1];
+        self.write(&mut buf);
+        let height = LittleEndian::read_u64(&buf[1..9]);
+
+        let mut data = COMPACT_PAYLOAD_PREFIX.to_vec();
+        data.push(PAYLOAD_V3_COMPACT);
+        data.push(buf[0]);
+        data.extend_from_slice(&encode::serialize(&VarInt(height)));
+        data.extend_from_slice(&buf[9..]);
+        Builder::new()
+            .push_opcode(OP_RETURN)
+            .push_slice(data.as_ref())
+            .into_script()
+    }
+
     fn into_script(self) -> Script {
         let len = self.len() + PAYLOAD_HEADER_LEN;
         let mut buf = vec![0; len];
@@ -146,13 +340,163 @@ impl PayloadV1 {
     }
 }
 
-impl PayloadV1Builder {
-    pub fn new() -> Self {
-        Self {
-            block_hash: None,
-            block_height: None,
-            prev_tx_chain: None,
+impl PayloadV2 {
+    fn read(bytes: &[u8]) -> Option<Self> {
+        let base_len = match *bytes.first()? {
+            PAYLOAD_V1_KIND_REGULAR => 41,
+            PAYLOAD_V1_KIND_RECOVER | PAYLOAD_V1_KIND_GENESIS => 73,
+            _ => return None,
+        };
+        if bytes.len() < base_len {
+            return None;
         }
+
+        let (base, digests) = bytes.split_at(base_len);
+        if digests.is_empty() || digests.len() % 32 != 0 {
+            return None;
+        }
+        let extension_digests = digests
+            .chunks(32)
+            .map(|digest| Hash::from_slice(digest).unwrap())
+            .collect();
+        Some(Self {
+            base: PayloadV1::read(base)?,
+            extension_digests,
+        })
+    }
+
+    fn into_scripts(self) -> Vec<Script> {
+        let base_len = self.base.len() + 1;
+        let mut data = vec![0; base_len + 32 * self.extension_digests.len()];
+        self.base.write(&mut data[..base_len]);
+        for (buf, digest) in data[base_len..].chunks_mut(32).zip(&self.extension_digests) {
+            buf.copy_from_slice(digest.as_ref());
+        }
+        chunked_scripts(PAYLOAD_V2, &data)
+    }
+}
+
+/// Splits the payload data into chunks, the first one also contains the header.
+fn chunked_scripts(version: u8, data: &[u8]) -> Vec<Script> {
+    let first_len = (MAX_PAYLOAD_CHUNK_LEN - PAYLOAD_HEADER_LEN).min(data.len());
+    let (first, rest) = data.split_at(first_len);
+    let chunks_count = 1 + rest.chunks(MAX_PAYLOAD_CHUNK_LEN).len();
+    assert!(
+        chunks_count <= usize::from(u8::MAX),
+        "Too many extension digests"
+    );
+
+    let mut head = Vec::with_capacity(PAYLOAD_HEADER_LEN + first.len());
+    head.extend_from_slice(PAYLOAD_PREFIX);
+    head.push(version);
+    head.push(chunks_count as u8);
+    head.extend_from_slice(first);
+    std::iter::once(head.as_ref())
+        .chain(rest.chunks(MAX_PAYLOAD_CHUNK_LEN))
+        .map(|chunk| {
+            Builder::new()
+                .push_opcode(OP_RETURN)
+                .push_slice(chunk)
+                .into_script()
+        })
+        .collect()
+}
+
+/// Appends the typed field of the extensible payload to the given buffer.
+fn write_field(buf: &mut Vec<u8>, field_type: u8, value: &[u8]) {
+    buf.push(field_type);
+    buf.extend_from_slice(&encode::serialize(&VarInt(value.len() as u64)));
+    buf.extend_from_slice(value);
+}
+
+/// Encodes the payload in the extensible format v.4.
+fn extensible_payload_scripts(payload: &Payload) -> Vec<Script> {
+    let mut data = Vec::new();
+    let block_height = encode::serialize(&VarInt(payload.block_height.0));
+    write_field(&mut data, FIELD_BLOCK_HEIGHT, &block_height);
+    write_field(&mut data, FIELD_BLOCK_HASH, payload.block_hash.as_ref());
+    if let Some(txid) = payload.prev_tx_chain {
+        write_field(&mut data, FIELD_PREV_TX_CHAIN, &txid.0[..]);
+    }
+    if let Some(digest) = payload.config_digest {
+        write_field(&mut data, FIELD_CONFIG_DIGEST, digest.as_ref());
+    }
+    if !payload.extension_digests.is_empty() {
+        let digests = payload
+            .extension_digests
+            .iter()
+            .flat_map(|digest| digest.as_ref().iter().copied())
+            .collect::<Vec<_>>();
+        write_field(&mut data, FIELD_EXTENSION_DIGESTS, &digests);
+    }
+    chunked_scripts(PAYLOAD_V4_EXTENSIBLE, &data)
+}
+
+/// Decodes the fields of the extensible payload v.4. The fields of the unknown types
+/// are skipped unless the `strict` mode is enabled.
+fn read_extensible_payload(bytes: &[u8], strict: bool) -> Option<Payload> {
+    let mut payload = Payload {
+        block_height: Height(0),
+        block_hash: Hash::zero(),
+        prev_tx_chain: None,
+        config_digest: None,
+        extension_digests: Vec::new(),
+    };
+    let (mut has_height, mut has_hash) = (false, false);
+
+    let mut previous_type = None;
+    let mut rest = bytes;
+    while let Some((&field_type, data)) = rest.split_first() {
+        // The fields must be sorted, so each payload has the only encoding.
+        if previous_type.map_or(false, |previous| previous >= field_type) {
+            return None;
+        }
+        previous_type = Some(field_type);
+
+        let (len, len_size) = encode::deserialize_partial::<VarInt>(data).ok()?;
+        let len = usize::try_from(len.0).ok()?;
+        let value = data.get(len_size..len_size.checked_add(len)?)?;
+        rest = &data[len_size + len..];
+
+        match field_type {
+            FIELD_BLOCK_HEIGHT => {
+                let (height, height_len) = encode::deserialize_partial::<VarInt>(value).ok()?;
+                if height_len != value.len() {
+                    return None;
+                }
+                payload.block_height = Height(height.0);
+                has_height = true;
+            }
+            FIELD_BLOCK_HASH => {
+                payload.block_hash = Hash::from_slice(value)?;
+                has_hash = true;
+            }
+            FIELD_PREV_TX_CHAIN => payload.prev_tx_chain = Some(Sha256d::from_slice(value)?),
+            FIELD_CONFIG_DIGEST => payload.config_digest = Some(Hash::from_slice(value)?),
+            FIELD_EXTENSION_DIGESTS => {
+                if value.is_empty() || value.len() % 32 != 0 {
+                    return None;
+                }
+                payload.extension_digests = value
+                    .chunks(32)
+                    .map(|digest| Hash::from_slice(digest).unwrap())
+                    .collect();
+            }
+            _ if strict => return None,
+            _ => {}
+        }
+    }
+
+    if has_height && has_hash {
+        Some(payload)
+    } else {
+        None
+    }
+}
+
+impl PayloadBuilder {
+    pub fn new() -> Self {
+        Self::default()
     }
 
     pub fn block_height(mut self, height: Height) -> Self {
@@ -170,49 +514,168 @@ impl PayloadV1Builder {
         self
     }
 
-    pub fn into_script(self) -> Script {
-        let block_height = self.block_height.expect("Block height is not set");
-        let block_hash = self.block_hash.expect("Block hash is not set");
+    pub fn config_digest(mut self, digest: Option<Hash>) -> Self {
+        self.config_digest = digest;
+        self
+    }
+
+    pub fn extension_digests(mut self, digests: Vec<Hash>) -> Self {
+        self.extension_digests = digests;
+        self
+    }
+
+    pub fn format(mut self, format: PayloadFormat) -> Self {
+        self.format = format;
+        self
+    }
+
+    /// Returns the payload scripts for the consecutive `OP_RETURN` outputs encoded
+    /// by the default codec.
+    #[cfg(test)]
+    pub fn into_scripts(self) -> Vec<Script> {
+        self.into_scripts_with(&DefaultPayloadCodec)
+    }
 
-        let payload = match self.prev_tx_chain {
-            Some(txid) => PayloadV1::Recover(block_height, block_hash, txid),
-            None => PayloadV1::Regular(block_height, block_hash),
+    /// Returns the payload scripts for the consecutive `OP_RETURN` outputs encoded
+    /// by the given codec.
+    pub fn into_scripts_with(self, codec: &dyn PayloadCodec) -> Vec<Script> {
+        let payload = Payload {
+            block_height: self.block_height.expect("Block height is not set"),
+            block_hash: self.block_hash.expect("Block hash is not set"),
+            prev_tx_chain: self.prev_tx_chain,
+            config_digest: self.config_digest,
+            extension_digests: self.extension_digests,
         };
-        payload.into_script()
+        codec.encode(&payload, self.format)
+    }
+
+    /// Returns the payload script, panics if the payload doesn't fit into the single
+    /// `OP_RETURN` output.
+    #[cfg(test)]
+    pub fn into_script(self) -> Script {
+        let mut scripts = self.into_scripts();
+        assert_eq!(scripts.len(), 1, "Payload doesn't fit into a single script");
+        scripts.remove(0)
+    }
+}
+
+/// Returns the data pushed into the `OP_RETURN` script.
+fn op_return_data(script: &Script) -> Option<&[u8]> {
+    // The single-byte chunks are pushed as is rather than as the small integer opcodes,
+    // so the minimal push encoding isn't enforced.
+    let mut instructions = script.iter(false);
+    if instructions.next()? != Instruction::Op(OP_RETURN) {
+        return None;
+    }
+    match instructions.next()? {
+        Instruction::PushBytes(bytes) => Some(bytes),
+        _ => None,
     }
 }
 
 impl Payload {
     /// Tries to extract payload from given `Script`.
     pub fn from_script(script: &Script) -> Option<Self> {
-        let mut instructions = script.iter(true);
-        instructions
-            .next()
-            .and_then(|instr| {
-                if instr == Instruction::Op(OP_RETURN) {
-                    instructions.next()
-                } else {
-                    None
-                }
-            })
-            .and_then(|instr| {
-                if let Instruction::PushBytes(bytes) = instr {
-                    if bytes.len() < PAYLOAD_HEADER_LEN {
-                        return None;
-                    }
-                    if &bytes[0..6] != PAYLOAD_PREFIX {
-                        return None;
-                    }
-                    // Parse metadata
-                    let version = bytes[6];
-                    match version {
-                        PAYLOAD_V1 => PayloadV1::read(&bytes[7..]).map(Self::from),
-                        _ => None,
-                    }
-                } else {
-                    None
-                }
-            })
+        Self::from_scripts(std::iter::once(script))
+    }
+
+    /// Tries to extract payload from the given scripts of the consecutive outputs
+    /// using the default codec.
+    /// The payload v.2 may be split across the several `OP_RETURN` outputs.
+    pub fn from_scripts<'a>(scripts: impl IntoIterator<Item = &'a Script>) -> Option<Self> {
+        DefaultPayloadCodec.decode(&scripts.into_iter().collect::<Vec<_>>())
+    }
+}
+
+impl PayloadCodec for DefaultPayloadCodec {
+    /// Extensible payload is encoded as v.4. Otherwise, payload without extension digests
+    /// is encoded in a single script as v.1 or as compact v.3, and payload with them
+    /// is encoded as v.2.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the payload contains both the txid of the previous tx chain and
+    /// the initial config digest, but the format is not extensible. The anchoring
+    /// transaction builder rejects such payloads with an error.
+    fn encode(&self, payload: &Payload, format: PayloadFormat) -> Vec<Script> {
+        if format == PayloadFormat::Extensible {
+            return extensible_payload_scripts(payload);
+        }
+
+        let (block_height, block_hash) = (payload.block_height, payload.block_hash);
+        let base = match (payload.prev_tx_chain, payload.config_digest) {
+            (Some(_), Some(_)) => {
+                panic!("Recover payload cannot contain the initial config digest")
+            }
+            (Some(txid), None) => PayloadV1::Recover(block_height, block_hash, txid),
+            (None, Some(digest)) => PayloadV1::Genesis(block_height, block_hash, digest),
+            (None, None) => PayloadV1::Regular(block_height, block_hash),
+        };
+
+        if !payload.extension_digests.is_empty() {
+            PayloadV2 {
+                base,
+                extension_digests: payload.extension_digests.clone(),
+            }
+            .into_scripts()
+        } else if format == PayloadFormat::Compact {
+            vec![base.into_compact_script()]
+        } else {
+            vec![base.into_script()]
+        }
+    }
+
+    fn decode(&self, scripts: &[&Script]) -> Option<Payload> {
+        decode_payload(scripts, false)
+    }
+}
+
+impl PayloadCodec for StrictPayloadCodec {
+    fn encode(&self, payload: &Payload, format: PayloadFormat) -> Vec<Script> {
+        DefaultPayloadCodec.encode(payload, format)
+    }
+
+    fn decode(&self, scripts: &[&Script]) -> Option<Payload> {
+        decode_payload(scripts, true)
+    }
+}
+
+/// Decodes the payload of any known version from the given scripts.
+fn decode_payload(scripts: &[&Script], strict: bool) -> Option<Payload> {
+    let mut chunks = scripts.iter().map(|script| op_return_data(script));
+    let bytes = chunks.next()??;
+    if bytes.len() >= COMPACT_PAYLOAD_HEADER_LEN
+        && &bytes[0..2] == COMPACT_PAYLOAD_PREFIX
+        && bytes[2] == PAYLOAD_V3_COMPACT
+    {
+        return PayloadV1::read_compact(&bytes[3..]).map(Payload::from);
+    }
+    if bytes.len() < PAYLOAD_HEADER_LEN {
+        return None;
+    }
+    if &bytes[0..6] != PAYLOAD_PREFIX {
+        return None;
+    }
+    // Parse metadata
+    let version = bytes[6];
+    match version {
+        PAYLOAD_V1 => PayloadV1::read(&bytes[7..]).map(Payload::from),
+        PAYLOAD_V2 | PAYLOAD_V4_EXTENSIBLE => {
+            let chunks_count = bytes[7];
+            if chunks_count == 0 {
+                return None;
+            }
+            let mut data = bytes[PAYLOAD_HEADER_LEN..].to_vec();
+            for _ in 1..chunks_count {
+                data.extend_from_slice(chunks.next()??);
+            }
+            if version == PAYLOAD_V2 {
+                PayloadV2::read(&data).map(Payload::from)
+            } else {
+                read_extensible_payload(&data, strict)
+            }
+        }
+        _ => None,
     }
 }
 
@@ -223,27 +686,54 @@ impl From<PayloadV1> for Payload {
                 block_height: height,
                 block_hash: hash,
                 prev_tx_chain: None,
+                config_digest: None,
+                extension_digests: Vec::new(),
             },
             PayloadV1::Recover(height, hash, txid) => Self {
                 block_height: height,
                 block_hash: hash,
                 prev_tx_chain: Some(txid),
+                config_digest: None,
+                extension_digests: Vec::new(),
+            },
+            PayloadV1::Genesis(height, hash, config_digest) => Self {
+                block_height: height,
+                block_hash: hash,
+                prev_tx_chain: None,
+                config_digest: Some(config_digest),
+                extension_digests: Vec::new(),
             },
         }
     }
 }
 
+impl From<PayloadV2> for Payload {
+    fn from(v2: PayloadV2) -> Self {
+        Self {
+            extension_digests: v2.extension_digests,
+            ..Self::from(v2.base)
+        }
+    }
+}
+
 #[cfg(test)]
 mod tests {
-    use exonum::crypto::hash;
+    use exonum::crypto::{hash, Hash};
     use exonum::helpers::Height;
 
-    use bitcoin::blockdata::script::Script;
+    use bitcoin::blockdata::{
+        opcodes::all::OP_RETURN,
+        script::{Builder, Script},
+    };
     use hex;
+    use proptest::{collection::vec, option, prelude::*, prop_assert_eq, prop_oneof, proptest};
 
     use crate::btc::Sha256d;
 
-    use super::{Payload, PayloadBuilder};
+    use super::{
+        DefaultPayloadCodec, Payload, PayloadBuilder, PayloadCodec, PayloadFormat,
+        StrictPayloadCodec,
+    };
 
     trait HexValue {
         fn from_hex(hex: impl AsRef<[u8]>) -> Self;
@@ -326,6 +816,197 @@ mod tests {
         assert_eq!(payload.prev_tx_chain, Some(prev_txid));
     }
 
+    #[test]
+    fn test_payload_genesis_roundtrip() {
+        let block_hash = hash(&[]);
+        let config_digest = hash(&[1, 2, 3]);
+        let payload_script = PayloadBuilder::new()
+            .block_hash(block_hash)
+            .block_height(Height(0))
+            .config_digest(Some(config_digest))
+            .into_script();
+        assert_eq!(payload_script.len(), 83);
+
+        let payload = Payload::from_script(&payload_script).unwrap();
+        assert_eq!(payload.block_hash, block_hash);
+        assert_eq!(payload.block_height, Height(0));
+        assert_eq!(payload.prev_tx_chain, None);
+        assert_eq!(payload.config_digest, Some(config_digest));
+    }
+
+    #[test]
+    fn test_payload_extension_digests_roundtrip() {
+        let block_hash = hash(&[]);
+        let config_digest = hash(&[1, 2, 3]);
+        let extension_digests = vec![hash(&[4]), hash(&[5]), hash(&[6])];
+        let payload_scripts = PayloadBuilder::new()
+            .block_hash(block_hash)
+            .block_height(Height(1234))
+            .config_digest(Some(config_digest))
+            .extension_digests(extension_digests.clone())
+            .into_scripts();
+        // 8 bytes of header, 73 bytes of base data and 96 bytes of extension digests.
+        assert_eq!(payload_scripts.len(), 3);
+        assert!(payload_scripts.iter().all(|script| script.len() <= 83));
+
+        let payload = Payload::from_scripts(&payload_scripts).unwrap();
+        assert_eq!(payload.block_hash, block_hash);
+        assert_eq!(payload.block_height, Height(1234));
+        assert_eq!(payload.config_digest, Some(config_digest));
+        assert_eq!(payload.extension_digests, extension_digests);
+        // Payload without some chunks is incorrect.
+        assert_eq!(Payload::from_scripts(&payload_scripts[..2]), None);
+        assert_eq!(Payload::from_script(&payload_scripts[0]), None);
+    }
+
+    #[test]
+    fn test_payload_compact_roundtrip() {
+        let block_hash = hash(&[]);
+        let prev_txid = Sha256d::from_slice(hash(&[1, 2, 3]).as_ref()).unwrap();
+        let payload_script = PayloadBuilder::new()
+            .block_hash(block_hash)
+            .block_height(Height(1234))
+            .format(PayloadFormat::Compact)
+            .into_script();
+        assert_eq!(
+            payload_script.to_hex(),
+            "6a2745580300fdd204e3b0c44298fc1c149afbf4c8996fb92427ae41e4649b934ca495991b7852b855"
+        );
+
+        let payload = Payload::from_script(&payload_script).unwrap();
+        assert_eq!(payload.block_hash, block_hash);
+        assert_eq!(payload.block_height, Height(1234));
+        assert_eq!(payload.prev_tx_chain, None);
+
+        let payload_script = PayloadBuilder::new()
+            .block_hash(block_hash)
+            .block_height(Height(u64::MAX))
+            .prev_tx_chain(Some(prev_txid))
+            .format(PayloadFormat::Compact)
+            .into_script();
+        let payload = Payload::from_script(&payload_script).unwrap();
+        assert_eq!(payload.block_height, Height(u64::MAX));
+        assert_eq!(payload.prev_tx_chain, Some(prev_txid));
+    }
+
+    #[test]
+    fn test_payload_extensible_roundtrip() {
+        let payload = Payload {
+            block_height: Height(1234),
+            block_hash: hash(&[]),
+            prev_tx_chain: Some(Sha256d::from_slice(hash(&[1, 2, 3]).as_ref()).unwrap()),
+            config_digest: None,
+            extension_digests: vec![hash(&[4]), hash(&[5])],
+        };
+        let payload_scripts = DefaultPayloadCodec.encode(&payload, PayloadFormat::Extensible);
+        assert_eq!(payload_scripts.len(), 2);
+        assert_eq!(
+            &super::op_return_data(&payload_scripts[0]).unwrap()[..8],
+            b"EXONUM\x04\x02"
+        );
+        assert_eq!(
+            Payload::from_scripts(&payload_scripts),
+            Some(payload.clone())
+        );
+
+        let scripts = payload_scripts.iter().collect::<Vec<_>>();
+        assert_eq!(StrictPayloadCodec.decode(&scripts), Some(payload));
+        // Incomplete payload cannot be decoded.
+        assert_eq!(Payload::from_script(&payload_scripts[0]), None);
+
+        // Regular payload fits into a single output.
+        let payload_script = PayloadBuilder::new()
+            .block_hash(hash(&[]))
+            .block_height(Height(1234))
+            .format(PayloadFormat::Extensible)
+            .into_script();
+        assert_eq!(
+            payload_script.to_hex(),
+            "6a2f45584f4e554d04010003fdd2040120e3b0c44298fc1c149afbf4c8996fb92427ae41e4649b934ca\
+             495991b7852b855"
+        );
+    }
+
+    #[test]
+    fn test_payload_extensible_unknown_fields() {
+        let extensible_script = |fields: &[(u8, &[u8])]| {
+            let mut data = Vec::new();
+            for (field_type, value) in fields {
+                super::write_field(&mut data, *field_type, value);
+            }
+            super::chunked_scripts(super::PAYLOAD_V4_EXTENSIBLE, &data)
+        };
+        let block_hash = hash(&[]);
+
+        // Unknown fields are skipped by the default codec, but rejected in the strict mode.
+        let scripts = extensible_script(&[(0, &[42]), (1, block_hash.as_ref()), (7, b"timestamp")]);
+        let payload = Payload::from_scripts(&scripts).unwrap();
+        assert_eq!(payload.block_height, Height(42));
+        assert_eq!(payload.block_hash, block_hash);
+        let scripts = scripts.iter().collect::<Vec<_>>();
+        assert_eq!(StrictPayloadCodec.decode(&scripts), None);
+
+        let malformed_payloads = [
+            // Fields are not sorted.
+            extensible_script(&[(1, block_hash.as_ref()), (0, &[42])]),
+            // Duplicate fields.
+            extensible_script(&[(0, &[42]), (0, &[42]), (1, block_hash.as_ref())]),
+            // Block hash is missing.
+            extensible_script(&[(0, &[42])]),
+            // Block hash has incorrect length.
+            extensible_script(&[(0, &[42]), (1, &block_hash.as_ref()[1..])]),
+            // Block height has trailing bytes.
+            extensible_script(&[(0, &[42, 0]), (1, block_hash.as_ref())]),
+        ];
+        for scripts in &malformed_payloads {
+            assert_eq!(Payload::from_scripts(scripts), None);
+        }
+    }
+
+    /// Codec which appends the deployment tag to the standard payload.
+    #[derive(Debug)]
+    struct TaggedPayloadCodec(&'static [u8]);
+
+    impl PayloadCodec for TaggedPayloadCodec {
+        fn encode(&self, payload: &Payload, format: PayloadFormat) -> Vec<Script> {
+            let mut scripts = DefaultPayloadCodec.encode(payload, format);
+            scripts.push(
+                Builder::new()
+                    .push_opcode(OP_RETURN)
+                    .push_slice(self.0)
+                    .into_script(),
+            );
+            scripts
+        }
+
+        fn decode(&self, scripts: &[&Script]) -> Option<Payload> {
+            let (tag, payload) = scripts.split_last()?;
+            if super::op_return_data(tag)? != self.0 {
+                return None;
+            }
+            DefaultPayloadCodec.decode(payload)
+        }
+    }
+
+    #[test]
+    fn test_payload_custom_codec() {
+        let block_hash = hash(&[]);
+        let codec = TaggedPayloadCodec(b"deployment");
+        let payload_scripts = PayloadBuilder::new()
+            .block_hash(block_hash)
+            .block_height(Height(1234))
+            .into_scripts_with(&codec);
+        assert_eq!(payload_scripts.len(), 2);
+
+        let scripts = payload_scripts.iter().collect::<Vec<_>>();
+        let payload = codec.decode(&scripts).unwrap();
+        assert_eq!(payload.block_hash, block_hash);
+        assert_eq!(payload.block_height, Height(1234));
+        // Default codec recognizes the standard part of the payload.
+        assert_eq!(Payload::from_scripts(payload_scripts.iter()), Some(payload));
+        assert_eq!(TaggedPayloadCodec(b"other").decode(&scripts), None);
+    }
+
     #[test]
     fn test_payload_incorrect_deserialize() {
         // Payload from old anchoring transaction
@@ -342,4 +1023,69 @@ mod tests {
         let script_pubkey = Script::from_hex("a91472b7506704dc074fa46359251052e781d96f939a87");
         assert_eq!(Payload::from_script(&script_pubkey), None);
     }
+
+    /// Generates arbitrary payloads along with the format they can be encoded in.
+    fn payload() -> impl Strategy<Value = (Payload, PayloadFormat)> {
+        let hash = any::<[u8; 32]>().prop_map(|bytes| Hash::from_slice(&bytes).unwrap());
+        let format = prop_oneof![
+            Just(PayloadFormat::Standard),
+            Just(PayloadFormat::Compact),
+            Just(PayloadFormat::Extensible),
+        ];
+        (
+            any::<u64>(),
+            hash.clone(),
+            option::of(any::<[u8; 32]>().prop_map(Sha256d::new)),
+            option::of(hash.clone()),
+            vec(hash, 0..4),
+            format,
+        )
+            .prop_filter(
+                "Recover payload cannot contain the initial config digest",
+                |(_, _, prev_tx_chain, config_digest, _, format)| {
+                    *format == PayloadFormat::Extensible
+                        || prev_tx_chain.is_none()
+                        || config_digest.is_none()
+                },
+            )
+            .prop_map(
+                |(height, block_hash, prev_tx_chain, config_digest, extension_digests, format)| {
+                    let payload = Payload {
+                        block_height: Height(height),
+                        block_hash,
+                        prev_tx_chain,
+                        config_digest,
+                        extension_digests,
+                    };
+                    (payload, format)
+                },
+            )
+    }
+
+    proptest! {
+        #[test]
+        fn test_payload_roundtrip((payload, format) in payload()) {
+            let scripts = DefaultPayloadCodec.encode(&payload, format);
+            let scripts = scripts.iter().collect::<Vec<_>>();
+            prop_assert_eq!(DefaultPayloadCodec.decode(&scripts), Some(payload.clone()));
+            prop_assert_eq!(StrictPayloadCodec.decode(&scripts), Some(payload));
+        }
+
+        #[test]
+        fn test_payload_arbitrary_data(chunks in vec(vec(any::<u8>(), 0..100), 1..4)) {
+            // Parsing of the arbitrary `OP_RETURN` data must not panic.
+            let scripts = chunks
+                .iter()
+                .map(|data| Builder::new().push_opcode(OP_RETURN).push_slice(data).into_script())
+                .collect::<Vec<_>>();
+            let scripts = scripts.iter().collect::<Vec<_>>();
+            DefaultPayloadCodec.decode(&scripts);
+            StrictPayloadCodec.decode(&scripts);
+        }
+
+        #[test]
+        fn test_payload_arbitrary_script(bytes in vec(any::<u8>(), 0..100)) {
+            let _ = Payload::from_script(&Script::from(bytes));
+        }
+    }
 }
@@ -0,0 +1,148 @@
+// Copyright 2020 The Exonum Team
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use bitcoin::{
+    blockdata::script::{Builder, Script},
+    consensus::encode,
+    util::psbt::PartiallySignedTransaction,
+    SigHashType,
+};
+use btc_transaction_utils::multisig::RedeemScript;
+use derive_more::{From, Into};
+use thiserror::Error;
+
+use super::{InputSignature, PublicKey, Sha256d, Transaction};
+
+/// Partially signed Bitcoin transaction as defined in [BIP-174].
+///
+/// It is used to sign the anchoring proposal outside of the anchoring node, e.g. by
+/// an air-gapped wallet or HSM. The string representation is the base64 encoding used
+/// by the Bitcoin wallets.
+///
+/// [BIP-174]: https://github.com/bitcoin/bips/blob/master/bip-0174.mediawiki
+#[derive(Debug, Clone, PartialEq, From, Into)]
+pub struct Psbt(pub PartiallySignedTransaction);
+
+/// Errors that occur when handling the partially signed anchoring transactions.
+#[derive(Debug, Error)]
+pub enum PsbtError {
+    /// Spent transactions don't correspond to the inputs of the anchoring transaction.
+    #[error("Spent transactions don't correspond to the inputs of the anchoring transaction.")]
+    UnexpectedInputs,
+    /// Partial signature of the input is malformed.
+    #[error("Partial signature of the input {0} is malformed.")]
+    MalformedSignature(u32),
+    /// PSBT cannot be decoded.
+    #[error("Unable to decode PSBT: {0}")]
+    Encoding(#[from] encode::Error),
+    /// PSBT string is not a valid base64.
+    #[error("Unable to decode PSBT: {0}")]
+    Base64(#[from] base64::DecodeError),
+}
+
+impl Psbt {
+    /// Creates an unsigned PSBT for the given anchoring transaction, which spends
+    /// the outputs of the given transactions locked by the given redeem script.
+    pub fn from_proposal(
+        proposal: &Transaction,
+        inputs: &[Transaction],
+        redeem_script: &RedeemScript,
+    ) -> Result<Self, PsbtError> {
+        if proposal.0.input.len() != inputs.len() {
+            return Err(PsbtError::UnexpectedInputs);
+        }
+
+        let mut unsigned_tx = proposal.0.clone();
+        for input in &mut unsigned_tx.input {
+            input.script_sig = Script::default();
+            input.witness.clear();
+        }
+        let mut psbt = PartiallySignedTransaction::from_unsigned_tx(unsigned_tx)
+            .expect("Transaction without the signatures");
+
+        let spent_outputs = proposal.0.input.iter().zip(inputs).map(|(input, prev_tx)| {
+            let outpoint = input.previous_output;
+            if prev_tx.0.txid() != outpoint.txid {
+                return None;
+            }
+            prev_tx.0.output.get(outpoint.vout as usize).cloned()
+        });
+        for (psbt_input, spent_output) in psbt.inputs.iter_mut().zip(spent_outputs) {
+            let spent_output = spent_output.ok_or(PsbtError::UnexpectedInputs)?;
+            // Witness program is the redeem script of the P2SH-wrapped output.
+            if spent_output.script_pubkey.is_p2sh() {
+                psbt_input.redeem_script = Some(redeem_script.as_ref().to_v0_p2wsh());
+            }
+            psbt_input.witness_utxo = Some(spent_output);
+            psbt_input.witness_script = Some(redeem_script.as_ref().clone());
+            psbt_input.sighash_type = Some(SigHashType::All);
+        }
+        Ok(Self(psbt))
+    }
+
+    /// Returns the identifier of the partially signed transaction.
+    ///
+    /// The `script_sig` of the P2SH-wrapped inputs doesn't depend on the signatures, so
+    /// it is taken into account, and the identifier is the same as the identifier of
+    /// the anchoring transaction.
+    pub fn txid(&self) -> Sha256d {
+        let mut tx = self.0.global.unsigned_tx.clone();
+        for (input, psbt_input) in tx.input.iter_mut().zip(&self.0.inputs) {
+            if let Some(witness_program) = &psbt_input.redeem_script {
+                input.script_sig = Builder::new()
+                    .push_slice(witness_program.as_bytes())
+                    .into_script();
+            }
+        }
+        tx.txid().into()
+    }
+
+    /// Returns the partial signatures made by the given key along with the indices
+    /// of the signed inputs.
+    pub fn input_signatures(
+        &self,
+        public_key: &PublicKey,
+    ) -> Result<Vec<(u32, InputSignature)>, PsbtError> {
+        self.0
+            .inputs
+            .iter()
+            .enumerate()
+            .filter_map(|(index, input)| {
+                let index = index as u32;
+                input.partial_sigs.get(&public_key.0).map(|signature| {
+                    btc_transaction_utils::InputSignature::from_bytes(signature.clone())
+                        .map(|signature| (index, signature.into()))
+                        .map_err(|_| PsbtError::MalformedSignature(index))
+                })
+            })
+            .collect()
+    }
+}
+
+impl std::fmt::Display for Psbt {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "{}", base64::encode(encode::serialize(&self.0)))
+    }
+}
+
+impl std::str::FromStr for Psbt {
+    type Err = PsbtError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let bytes = base64::decode(s)?;
+        Ok(Self(encode::deserialize(&bytes)?))
+    }
+}
+
+impl_serde_str! { Psbt }
@@ -10,14 +10,18 @@
 use exonum::{crypto::Hash, helpers::Height};
 
 use bitcoin::blockdata::{
-    script::Script,
+    script::{Builder, Script},
     transaction::{self, OutPoint, TxIn, TxOut},
 };
 use btc_transaction_utils::multisig::RedeemScript;
 use derive_more::{From, Into};
 use thiserror::Error;
 
-use super::{payload::PayloadBuilder, Payload, Sha256d};
+use std::cmp;
+
+use super::{
+    payload::PayloadBuilder, DefaultPayloadCodec, Payload, PayloadCodec, PayloadFormat, Sha256d,
+};
 
 /// Bitcoin transaction wrapper.
 #[derive(Debug, Clone, From, Into, PartialEq)]
@@ -37,6 +41,11 @@ impl Transaction {
         self.0.txid().into()
     }
 
+    /// Return the Bitcoin witness transaction identifier.
+    pub fn wtxid(&self) -> Sha256d {
+        Sha256d(self.0.wtxid().into())
+    }
+
     /// Return the previous anchoring transaction identifier.
     pub fn prev_tx_id(&self) -> Sha256d {
         self.0.input[0].previous_output.txid.into()
@@ -53,14 +62,31 @@ impl Transaction {
 
     /// Return the anchoring payload for the transaction if it is the anchoring transaction.
     pub fn anchoring_payload(&self) -> Option<Payload> {
-        let out = self.0.output.get(1)?;
-        Payload::from_script(&out.script_pubkey)
+        self.anchoring_payload_with(&DefaultPayloadCodec)
+    }
+
+    /// Return the anchoring payload decoded by the given codec for the transaction
+    /// if it is the anchoring transaction.
+    pub fn anchoring_payload_with(&self, codec: &dyn PayloadCodec) -> Option<Payload> {
+        let outputs = self.0.output.get(1..)?;
+        codec.decode(
+            &outputs
+                .iter()
+                .map(|out| &out.script_pubkey)
+                .collect::<Vec<_>>(),
+        )
     }
 
     /// Return the complete meta information for the transaction
     /// if it is the anchoring transaction.
     pub fn anchoring_metadata(&self) -> Option<(&Script, Payload)> {
-        let payload = self.anchoring_payload()?;
+        self.anchoring_metadata_with(&DefaultPayloadCodec)
+    }
+
+    /// Return the complete meta information with the payload decoded by the given codec
+    /// for the transaction if it is the anchoring transaction.
+    pub fn anchoring_metadata_with(&self, codec: &dyn PayloadCodec) -> Option<(&Script, Payload)> {
+        let payload = self.anchoring_payload_with(codec)?;
         let script_pubkey = self.0.output.get(0).map(|out| &out.script_pubkey)?;
         Some((script_pubkey, payload))
     }
@@ -70,18 +96,60 @@ impl Transaction {
     pub fn unspent_value(&self) -> Option<u64> {
         self.0.output.get(0).map(|out| out.value)
     }
+
+    /// Return the fee paid by the transaction, given the transactions spent by its inputs
+    /// in the same order as the inputs.
+    pub fn fee(&self, inputs: &[Transaction]) -> Option<u64> {
+        if inputs.len() != self.0.input.len() {
+            return None;
+        }
+
+        let input_value = self
+            .0
+            .input
+            .iter()
+            .zip(inputs)
+            .map(|(txin, tx)| {
+                tx.0.output
+                    .get(txin.previous_output.vout as usize)
+                    .map(|out| out.value)
+            })
+            .sum::<Option<u64>>()?;
+        let output_value = self.0.output.iter().map(|out| out.value).sum::<u64>();
+        input_value.checked_sub(output_value)
+    }
+
+    /// Return the size of the serialized transaction in bytes.
+    pub fn size(&self) -> u64 {
+        ::bitcoin::consensus::serialize(&self.0).len() as u64
+    }
 }
 
 /// Builder for the anchoring transactions.
+///
+/// Inputs and outputs of the created transactions have the canonical order:
+///
+/// - the first input spends the anchoring output of the previous anchoring transaction,
+///   if any; it is followed by the inputs spending the funding transactions outputs
+///   sorted by the transaction identifiers and output indexes;
+/// - the first output is the anchoring output; it is followed by the `OP_RETURN`
+///   outputs with the payload.
 #[derive(Debug)]
 pub struct BtcAnchoringTransactionBuilder {
+    witness_program: Script,
     script_pubkey: Script,
+    script_sig: Script,
     transit_to: Option<Script>,
     prev_tx: Option<Transaction>,
     recovery_tx: Option<Sha256d>,
     additional_funds: Vec<(usize, Transaction)>,
     fee: Option<u64>,
+    unconfirmed_parent: Option<(u64, u64)>,
     payload: Option<(Height, Hash)>,
+    config_digest: Option<Hash>,
+    extension_digests: Vec<Hash>,
+    payload_format: PayloadFormat,
+    payload_codec: Box<dyn PayloadCodec>,
 }
 
 /// Anchoring transaction builder errors.
@@ -107,19 +175,50 @@ pub enum BuilderError {
     /// Funding transaction doesn't contains outputs to the anchoring address.
     #[error("Funding transaction doesn't contains outputs to the anchoring address.")]
     UnsuitableFundingTx,
+    /// Payload of the recovery transaction cannot contain the initial config digest
+    /// in the non-extensible format.
+    #[error(
+        "Payload of the recovery transaction cannot contain the initial config digest \
+         in the non-extensible format."
+    )]
+    UnsuitablePayload,
 }
 
 impl BtcAnchoringTransactionBuilder {
     /// Creates a new btc anchoring transaction builder for the given redeem script.
     pub fn new(redeem_script: &RedeemScript) -> BtcAnchoringTransactionBuilder {
+        let witness_program = redeem_script.as_ref().to_v0_p2wsh();
         Self {
-            script_pubkey: redeem_script.as_ref().to_v0_p2wsh(),
+            script_pubkey: witness_program.clone(),
+            witness_program,
+            script_sig: Script::default(),
             transit_to: None,
             prev_tx: None,
             recovery_tx: None,
             additional_funds: Vec::default(),
             fee: None,
+            unconfirmed_parent: None,
             payload: None,
+            config_digest: None,
+            extension_digests: Vec::default(),
+            payload_format: PayloadFormat::default(),
+            payload_codec: Box::new(DefaultPayloadCodec),
+        }
+    }
+
+    /// Uses the P2SH-wrapped P2WSH outputs instead of the native segwit ones. The inputs
+    /// spending such outputs contain the witness program in the `script_sig`, which doesn't
+    /// depend on the signatures, thus the transaction identifier doesn't change after
+    /// the signing.
+    pub fn p2sh_wrapped(&mut self, wrapped: bool) {
+        if wrapped {
+            self.script_pubkey = self.witness_program.to_p2sh();
+            self.script_sig = Builder::new()
+                .push_slice(self.witness_program.as_bytes())
+                .into_script();
+        } else {
+            self.script_pubkey = self.witness_program.clone();
+            self.script_sig = Script::default();
         }
     }
 
@@ -131,7 +230,8 @@ impl BtcAnchoringTransactionBuilder {
     /// Sets an transaction which corresponding unspent output will use
     /// as an input for the following anchoring transaction.
     pub fn prev_tx(&mut self, tx: Transaction) -> Result<(), BuilderError> {
-        if tx.anchoring_metadata().unwrap().0 != &self.script_pubkey {
+        // Payload is not decoded here, since it may be encoded by a custom codec.
+        if tx.0.output.first().map(|out| &out.script_pubkey) != Some(&self.script_pubkey) {
             Err(BuilderError::UnsuitableOutput)
         } else {
             self.prev_tx = Some(tx);
@@ -161,11 +261,41 @@ impl BtcAnchoringTransactionBuilder {
         self.fee = Some(fee);
     }
 
+    /// Makes the transaction pay for its unconfirmed parent with the given size in bytes
+    /// and fee, so the fee per byte of both transactions corresponds to the set one.
+    /// The fee of the transaction itself doesn't go below the set fee per byte.
+    pub fn pay_for_parent(&mut self, parent_size: u64, parent_fee: u64) {
+        self.unconfirmed_parent = Some((parent_size, parent_fee));
+    }
+
     /// Sets the anchoring transaction payload.
     pub fn payload(&mut self, block_height: Height, block_hash: Hash) {
         self.payload = Some((block_height, block_hash));
     }
 
+    /// Sets the digest of the initial anchoring configuration, which should be committed
+    /// in the first anchoring transaction.
+    pub fn config_digest(&mut self, digest: Hash) {
+        self.config_digest = Some(digest);
+    }
+
+    /// Sets the additional digests which should be committed in the payload.
+    /// The payload may be split across the several `OP_RETURN` outputs.
+    pub fn extension_digests(&mut self, digests: Vec<Hash>) {
+        self.extension_digests = digests;
+    }
+
+    /// Sets the payload encoding format, the standard format is used otherwise.
+    pub fn payload_format(&mut self, format: PayloadFormat) {
+        self.payload_format = format;
+    }
+
+    /// Sets the codec used to encode the payload, the default payload format is used
+    /// otherwise.
+    pub fn payload_codec(&mut self, codec: impl PayloadCodec + 'static) {
+        self.payload_codec = Box::new(codec);
+    }
+
     /// Finalizes the anchoring transaction and returns
     /// it with the list of input transactions.
     pub fn create(mut self) -> Result<(Transaction, Vec<Transaction>), BuilderError> {
@@ -175,6 +305,8 @@ impl BtcAnchoringTransactionBuilder {
             let mut input_transactions = Vec::new();
             let mut balance = 0;
 
+            self.additional_funds
+                .sort_by_key(|(out_index, tx)| (tx.id(), *out_index));
             let tx_iter = self
                 .prev_tx
                 .into_iter()
@@ -186,7 +318,7 @@ impl BtcAnchoringTransactionBuilder {
                         txid: tx.0.txid(),
                         vout: out_index as u32,
                     },
-                    script_sig: Script::default(),
+                    script_sig: self.script_sig.clone(),
                     sequence: 0xFFFF_FFFF,
                     witness: Vec::default(),
                 };
@@ -201,14 +333,24 @@ impl BtcAnchoringTransactionBuilder {
         if input_transactions.is_empty() {
             return Err(BuilderError::NoInputs);
         }
+        // Only the extensible payload has room for both optional fields.
+        if self.recovery_tx.is_some()
+            && self.config_digest.is_some()
+            && self.payload_format != PayloadFormat::Extensible
+        {
+            return Err(BuilderError::UnsuitablePayload);
+        }
 
-        // Compute payload script.
+        // Compute payload scripts.
         let (block_height, block_hash) = self.payload.take().expect("Payload isn't set.");
-        let payload_script = PayloadBuilder::new()
+        let payload_scripts = PayloadBuilder::new()
             .block_hash(block_hash)
             .block_height(block_height)
             .prev_tx_chain(self.recovery_tx)
-            .into_script();
+            .config_digest(self.config_digest)
+            .extension_digests(self.extension_digests)
+            .format(self.payload_format)
+            .into_scripts_with(self.payload_codec.as_ref());
         let output = match self.transit_to {
             Some(script) => script,
             _ => self.script_pubkey,
@@ -219,16 +361,15 @@ impl BtcAnchoringTransactionBuilder {
             version: 2,
             lock_time: 0,
             input,
-            output: vec![
-                TxOut {
-                    value: balance,
-                    script_pubkey: output,
-                },
-                TxOut {
-                    value: 0,
-                    script_pubkey: payload_script,
-                },
-            ],
+            output: std::iter::once(TxOut {
+                value: balance,
+                script_pubkey: output,
+            })
+            .chain(payload_scripts.into_iter().map(|script_pubkey| TxOut {
+                value: 0,
+                script_pubkey,
+            }))
+            .collect(),
         });
 
         // Compute a total fee value.
@@ -236,7 +377,12 @@ impl BtcAnchoringTransactionBuilder {
             let bytes = ::bitcoin::consensus::serialize(&transaction.0);
             bytes.len() as u64
         };
-        let total_fee = self.fee.expect("Fee per byte isn't set.") * size_in_bytes;
+        let fee = self.fee.expect("Fee per byte isn't set.");
+        let mut total_fee = fee * size_in_bytes;
+        if let Some((parent_size, parent_fee)) = self.unconfirmed_parent {
+            let package_fee = fee * (size_in_bytes + parent_size);
+            total_fee = cmp::max(total_fee, package_fee.saturating_sub(parent_fee));
+        }
         if total_fee > balance {
             return Err(BuilderError::InsufficientFunds { total_fee, balance });
         }
@@ -265,11 +411,14 @@ mod tests {
     use bitcoin_hashes::{sha256d::Hash as Sha256dHash, Hash as BitcoinHash};
     use btc_transaction_utils::multisig::RedeemScriptBuilder;
     use hex::FromHex;
-    use proptest::proptest;
+    use proptest::{collection::vec, prelude::*, prop_assert_eq, prop_oneof, proptest};
 
     use std::borrow::Cow;
 
-    use crate::btc::PublicKey;
+    use crate::{
+        btc::{Payload, PayloadFormat, PublicKey},
+        test_helpers::create_fake_funding_transaction,
+    };
 
     use super::{BtcAnchoringTransactionBuilder, BuilderError, Transaction};
 
@@ -381,6 +530,80 @@ mod tests {
         }
     }
 
+    /// Generates anchoring transactions spending the arbitrary funding transactions
+    /// along with their inputs and payload.
+    fn anchoring_transaction() -> impl Strategy<Value = (Transaction, Vec<Transaction>, Payload)> {
+        let format = prop_oneof![
+            Just(PayloadFormat::Standard),
+            Just(PayloadFormat::Compact),
+            Just(PayloadFormat::Extensible),
+        ];
+        (
+            vec(10_000_u64..1_000_000_000, 1..4),
+            1_u64..100,
+            any::<u64>(),
+            any::<[u8; 32]>(),
+            format,
+        )
+            .prop_map(|(funds, fee, height, block_hash, format)| {
+                let keys = [
+                    "038b782f94d19f34536a96e12e0bad99e6f82c838fa16a4234572f5f132d95ba29",
+                    "020ae2216f42575c4196864eda0252c75c61273065f691b32be9a99cb2a3c9b4d1",
+                    "02536d5e1464b961562da57207e4a46edb7dade9b92aa29712ca8309c8aba5be5b",
+                ]
+                .iter()
+                .map(|h| PublicKey::from_hex(h).unwrap().0)
+                .collect::<Vec<_>>();
+                let redeem_script = RedeemScriptBuilder::with_public_keys(keys)
+                    .to_script()
+                    .unwrap();
+                let address: crate::btc::Address =
+                    btc_transaction_utils::p2wsh::address(&redeem_script, Network::Testnet).into();
+
+                let block_hash = Hash::from_slice(&block_hash).unwrap();
+                let mut builder = BtcAnchoringTransactionBuilder::new(&redeem_script);
+                for value in funds {
+                    let funding_tx = create_fake_funding_transaction(&address, value);
+                    builder.additional_funds(funding_tx).unwrap();
+                }
+                builder.fee(fee);
+                builder.payload(Height(height), block_hash);
+                builder.payload_format(format);
+                let (tx, inputs) = builder.create().unwrap();
+                let payload = Payload {
+                    block_height: Height(height),
+                    block_hash,
+                    prev_tx_chain: None,
+                    config_digest: None,
+                    extension_digests: Vec::new(),
+                };
+                (tx, inputs, payload)
+            })
+    }
+
+    proptest! {
+        #[test]
+        fn test_anchoring_transaction_roundtrip(
+            (tx, inputs, payload) in anchoring_transaction()
+        ) {
+            let bytes = tx.clone().into_bytes();
+            let parsed = <Transaction as BinaryValue>::from_bytes(Cow::Borrowed(&bytes)).unwrap();
+            prop_assert_eq!(&parsed, &tx);
+            prop_assert_eq!(parsed.anchoring_payload(), Some(payload));
+            prop_assert_eq!(parsed.prev_tx_id(), inputs[0].id());
+            prop_assert_eq!(parsed.0.input.len(), inputs.len());
+            prop_assert!(parsed.fee(&inputs).is_some());
+        }
+
+        #[test]
+        fn test_transaction_arbitrary_bytes(bytes in vec(any::<u8>(), 0..512)) {
+            // Parsing of the arbitrary data must not panic.
+            if let Ok(tx) = <Transaction as BinaryValue>::from_bytes(Cow::Borrowed(&bytes)) {
+                let _ = tx.anchoring_payload();
+            }
+        }
+    }
+
     #[test]
     #[should_panic(expected = "unsupported segwit version: 16")]
     fn test_transaction_exonum_field_invalid_segwit_flag() {
@@ -447,6 +670,8 @@ mod tests {
 
         assert_eq!(funding_tx, inputs[0]);
         assert_eq!(tx.0.version, 2);
+        // Fee per byte is equal to one satoshi.
+        assert_eq!(tx.fee(&inputs), Some(tx.size()));
 
         let inputs = tx.0.input;
         assert_eq!(inputs.len(), 1);
@@ -461,6 +686,138 @@ mod tests {
         assert_eq!(out_1.value, 0);
     }
 
+    #[test]
+    fn test_anchoring_transaction_builder_recover_with_config_digest() {
+        let keys = [
+            "038b782f94d19f34536a96e12e0bad99e6f82c838fa16a4234572f5f132d95ba29",
+            "020ae2216f42575c4196864eda0252c75c61273065f691b32be9a99cb2a3c9b4d1",
+            "02536d5e1464b961562da57207e4a46edb7dade9b92aa29712ca8309c8aba5be5b",
+        ]
+        .iter()
+        .map(|h| PublicKey::from_hex(h).unwrap().0)
+        .collect::<Vec<_>>();
+        let redeem_script = RedeemScriptBuilder::with_public_keys(keys)
+            .to_script()
+            .unwrap();
+        let address: crate::btc::Address =
+            btc_transaction_utils::p2wsh::address(&redeem_script, Network::Testnet).into();
+        let funding_tx = create_fake_funding_transaction(&address, 100_000);
+        let prev_tx_chain = create_fake_funding_transaction(&address, 100_000).id();
+
+        let create = |format| {
+            let mut builder = BtcAnchoringTransactionBuilder::new(&redeem_script);
+            builder.additional_funds(funding_tx.clone()).unwrap();
+            builder.fee(1);
+            builder.payload(Height::zero(), funding_tx.object_hash());
+            builder.recover(prev_tx_chain);
+            builder.config_digest(funding_tx.object_hash());
+            builder.payload_format(format);
+            builder.create()
+        };
+
+        for &format in &[PayloadFormat::Standard, PayloadFormat::Compact] {
+            assert_eq!(create(format).unwrap_err(), BuilderError::UnsuitablePayload);
+        }
+        let (tx, _) = create(PayloadFormat::Extensible).unwrap();
+        let payload = tx.anchoring_payload().unwrap();
+        assert_eq!(payload.prev_tx_chain, Some(prev_tx_chain));
+        assert_eq!(payload.config_digest, Some(funding_tx.object_hash()));
+    }
+
+    #[test]
+    fn test_anchoring_transaction_builder_pay_for_parent() {
+        let funding_tx: Transaction = Transaction::from_hex(
+            "02000000000101b651818fe3855d0d5d74de1cf72b56503c16f808519440e842b6\
+             dc2dd570c4930100000000feffffff02deaa7b0000000000160014923904449829\
+             cd865cdfb72abdba0806ce9e48911027000000000000220020e9bb049fdff8f8d3\
+             b33b7335978b1dbb268833a32a69906f9e500e4103151bef02483045022100ddc7\
+             eb1193529a8d0e48cf24f536d5fbb5de3b67d2f56c98190ea8585d58a156022075\
+             e33981f1a7d78ce2915402d4b9b38b8d5311e0aef2e3ccf9284d2ce602968d0121\
+             021d0478acd223fb9b2ad7485f06f12914a1b7effc78390a08c50bfe53b3b24815\
+             062c1400",
+        )
+        .unwrap();
+        let keys = [
+            "038b782f94d19f34536a96e12e0bad99e6f82c838fa16a4234572f5f132d95ba29",
+            "020ae2216f42575c4196864eda0252c75c61273065f691b32be9a99cb2a3c9b4d1",
+            "02536d5e1464b961562da57207e4a46edb7dade9b92aa29712ca8309c8aba5be5b",
+        ]
+        .iter()
+        .map(|h| PublicKey::from_hex(h).unwrap().0)
+        .collect::<Vec<_>>();
+        let redeem_script = RedeemScriptBuilder::with_public_keys(keys)
+            .to_script()
+            .unwrap();
+        let create_tx = |parent_size, parent_fee| {
+            let mut builder = BtcAnchoringTransactionBuilder::new(&redeem_script);
+            builder.additional_funds(funding_tx.clone()).unwrap();
+            builder.fee(2);
+            builder.pay_for_parent(parent_size, parent_fee);
+            builder.payload(Height::zero(), funding_tx.object_hash());
+            builder.create().unwrap()
+        };
+
+        // The fee covers the deficit of the parent transaction fee.
+        let (tx, inputs) = create_tx(300, 100);
+        assert_eq!(tx.fee(&inputs), Some(2 * (tx.size() + 300) - 100));
+        // The parent transaction with the sufficient fee doesn't reduce the fee.
+        let (tx, inputs) = create_tx(300, 1000);
+        assert_eq!(tx.fee(&inputs), Some(2 * tx.size()));
+    }
+
+    #[test]
+    fn test_anchoring_transaction_builder_p2sh_wrapped() {
+        let keys = [
+            "038b782f94d19f34536a96e12e0bad99e6f82c838fa16a4234572f5f132d95ba29",
+            "020ae2216f42575c4196864eda0252c75c61273065f691b32be9a99cb2a3c9b4d1",
+            "02536d5e1464b961562da57207e4a46edb7dade9b92aa29712ca8309c8aba5be5b",
+        ]
+        .iter()
+        .map(|h| PublicKey::from_hex(h).unwrap().0)
+        .collect::<Vec<_>>();
+        let redeem_script = RedeemScriptBuilder::with_public_keys(keys)
+            .to_script()
+            .unwrap();
+        let witness_program = redeem_script.as_ref().to_v0_p2wsh();
+        let funding_tx = Transaction::from(transaction::Transaction {
+            version: 2,
+            lock_time: 0,
+            input: vec![TxIn {
+                previous_output: OutPoint::default(),
+                script_sig: Script::default(),
+                sequence: 0xFFFF_FFFF,
+                witness: Vec::default(),
+            }],
+            output: vec![TxOut {
+                value: 100_000,
+                script_pubkey: witness_program.to_p2sh(),
+            }],
+        });
+
+        // Native segwit outputs are expected by default.
+        let mut builder = BtcAnchoringTransactionBuilder::new(&redeem_script);
+        assert_eq!(
+            builder.additional_funds(funding_tx.clone()).unwrap_err(),
+            BuilderError::UnsuitableFundingTx
+        );
+
+        let mut builder = BtcAnchoringTransactionBuilder::new(&redeem_script);
+        builder.p2sh_wrapped(true);
+        builder.additional_funds(funding_tx.clone()).unwrap();
+        builder.fee(1);
+        builder.payload(Height::zero(), funding_tx.object_hash());
+        let (tx, inputs) = builder.create().unwrap();
+
+        assert_eq!(inputs, [funding_tx]);
+        assert_eq!(tx.0.output[0].script_pubkey, witness_program.to_p2sh());
+        // Input contains the witness program, so the fee covers it.
+        let expected_script_sig = Builder::new()
+            .push_slice(witness_program.as_bytes())
+            .into_script();
+        assert_eq!(tx.0.input[0].script_sig, expected_script_sig);
+        assert_eq!(tx.fee(&inputs), Some(tx.size()));
+    }
+
     #[test]
     fn test_anchoring_transaction_builder_funds() {
         let funding_tx0: Transaction = Transaction::from_hex(
@@ -29,6 +29,8 @@ use exonum::{
 
 use crate::btc::{self, Address};
 
+use std::{cmp, collections::BTreeSet, ops::RangeInclusive};
+
 impl Default for Config {
     fn default() -> Self {
         Self {
@@ -36,6 +38,16 @@ impl Default for Config {
             anchoring_keys: vec![],
             anchoring_interval: 5_000,
             transaction_fee: 10,
+            frozen: false,
+            compact_payload: false,
+            proposal_lifetime: 0,
+            validators_only: false,
+            excluded_keys: vec![],
+            min_fee_rate: 0,
+            max_fee_rate: 0,
+            p2sh_wrapped: false,
+            extensible_payload: false,
+            funding_confirmations: 0,
         }
     }
 }
@@ -49,10 +61,13 @@ impl Config {
     /// https://bitcoin.stackexchange.com/questions/1195/how-to-calculate-transaction-size-before-sending-legacy-non-segwit-p2pkh-p2sh     
     const MIN_TX_LEN: u64 = 10 + 146 + 33 + 81;
     /// Minimal enough transaction fee per byte.
-    const MIN_TX_FEE: u64 = Self::MIN_TOTAL_TX_FEE / Self::MIN_TX_LEN + 1; // Round up.
+    pub(crate) const MIN_TX_FEE: u64 = Self::MIN_TOTAL_TX_FEE / Self::MIN_TX_LEN + 1; // Round up.
 
     /// Creates Bitcoin anchoring config instance with default parameters for the
     /// given Bitcoin network and public keys of participants.
+    ///
+    /// The anchoring keys are sorted in the canonical order, see
+    /// [`sort_anchoring_keys`](#method.sort_anchoring_keys).
     pub fn with_public_keys(
         network: Network,
         keys: impl IntoIterator<Item = AnchoringKeys>,
@@ -62,11 +77,36 @@ impl Config {
             return Err(RedeemScriptError::NotEnoughPublicKeys);
         }
 
-        Ok(Self {
+        let mut config = Self {
             network,
             anchoring_keys,
             ..Self::default()
-        })
+        };
+        config.sort_anchoring_keys();
+        Ok(config)
+    }
+
+    /// Sorts the anchoring keys in the canonical order, that is in the lexicographical
+    /// order of the serialized Bitcoin keys as in [BIP-67]. The public keys in the
+    /// redeem script follow the order of the anchoring keys, thus the anchoring
+    /// address of the config with the given set of keys is always the same.
+    ///
+    /// Configurations which change the anchoring address should have the sorted keys.
+    /// The already deployed configurations keep their order of the keys, since sorting
+    /// would change their anchoring address.
+    ///
+    /// [BIP-67]: https://github.com/bitcoin/bips/blob/master/bip-0067.mediawiki
+    pub fn sort_anchoring_keys(&mut self) {
+        self.anchoring_keys
+            .sort_by_key(|keys| keys.bitcoin_key.0.to_bytes());
+    }
+
+    /// Returns `true` if the anchoring keys are sorted in the canonical order, see
+    /// [`sort_anchoring_keys`](#method.sort_anchoring_keys).
+    pub fn has_sorted_anchoring_keys(&self) -> bool {
+        self.anchoring_keys
+            .windows(2)
+            .all(|pair| pair[0].bitcoin_key.0.to_bytes() <= pair[1].bitcoin_key.0.to_bytes())
     }
 
     /// Tries to find bitcoin public key corresponding with the given service key.
@@ -82,7 +122,11 @@ impl Config {
 
     /// Returns the corresponding Bitcoin address.
     pub fn anchoring_address(&self) -> Address {
-        p2wsh::address(&self.redeem_script(), self.network).into()
+        if self.p2sh_wrapped {
+            bitcoin::Address::p2shwsh(self.redeem_script().as_ref(), self.network).into()
+        } else {
+            p2wsh::address(&self.redeem_script(), self.network).into()
+        }
     }
 
     /// Returns the corresponding redeem script.
@@ -93,21 +137,62 @@ impl Config {
             .unwrap()
     }
 
-    /// Computes the P2WSH output corresponding to the actual redeem script.
+    /// Computes the P2WSH output corresponding to the actual redeem script, which is
+    /// wrapped into P2SH if the [`p2sh_wrapped`](#structfield.p2sh_wrapped) option is set.
     pub fn anchoring_out_script(&self) -> bitcoin::Script {
-        self.redeem_script().as_ref().to_v0_p2wsh()
+        let witness_program = self.redeem_script().as_ref().to_v0_p2wsh();
+        if self.p2sh_wrapped {
+            witness_program.to_p2sh()
+        } else {
+            witness_program
+        }
     }
 
     /// Returns the latest height below the given height which must be anchored.
+    ///
+    /// This method assumes that the anchoring interval has never been changed, use
+    /// `Schema::anchoring_schedule` to take the interval changes into account.
     pub fn previous_anchoring_height(&self, current_height: Height) -> Height {
         Height(current_height.0 - current_height.0 % self.anchoring_interval)
     }
 
     /// Returns the nearest height above the given height which must be anchored.
+    ///
+    /// This method assumes that the anchoring interval has never been changed, use
+    /// `Schema::anchoring_schedule` to take the interval changes into account.
     pub fn following_anchoring_height(&self, current_height: Height) -> Height {
         Height(self.previous_anchoring_height(current_height).0 + self.anchoring_interval)
     }
 
+    /// Returns the range of the fee rates allowed for the anchoring transactions.
+    pub fn fee_rate_bounds(&self) -> RangeInclusive<u64> {
+        let min_fee_rate = cmp::max(self.min_fee_rate, Self::MIN_TX_FEE);
+        let max_fee_rate = if self.max_fee_rate == 0 {
+            u64::MAX
+        } else {
+            self.max_fee_rate
+        };
+        min_fee_rate..=max_fee_rate
+    }
+
+    /// Bounds the given fee rate by the allowed range, see
+    /// [`fee_rate_bounds`](#method.fee_rate_bounds).
+    pub fn bound_fee_rate(&self, fee_rate: u64) -> u64 {
+        let bounds = self.fee_rate_bounds();
+        cmp::max(fee_rate, *bounds.start()).min(*bounds.end())
+    }
+
+    /// Returns the format of the anchoring transaction payloads.
+    pub fn payload_format(&self) -> btc::PayloadFormat {
+        if self.extensible_payload {
+            btc::PayloadFormat::Extensible
+        } else if self.compact_payload {
+            btc::PayloadFormat::Compact
+        } else {
+            btc::PayloadFormat::Standard
+        }
+    }
+
     /// Returns sufficient number of votes for the given anchoring nodes number.
     pub fn byzantine_quorum(&self) -> usize {
         exonum::helpers::byzantine_quorum(self.anchoring_keys.len())
@@ -136,6 +221,37 @@ impl ValidateInput for Config {
             "Transaction fee should be greater than {}",
             Self::MIN_TX_FEE
         );
+        ensure!(
+            self.max_fee_rate == 0 || self.max_fee_rate >= self.min_fee_rate,
+            "Maximal fee rate should not be less than the minimal one."
+        );
+        ensure!(
+            self.fee_rate_bounds().contains(&self.transaction_fee),
+            "Transaction fee should be within the fee rate bounds."
+        );
+        ensure!(
+            !(self.compact_payload && self.extensible_payload),
+            "Compact and extensible payloads cannot be enabled simultaneously."
+        );
+
+        // The order of the keys is not checked, since the already deployed configurations
+        // may have the keys in the arbitrary order.
+        let bitcoin_keys = self
+            .anchoring_keys
+            .iter()
+            .map(|keys| keys.bitcoin_key.0.to_bytes())
+            .collect::<BTreeSet<_>>();
+        ensure!(
+            bitcoin_keys.len() == self.anchoring_keys.len(),
+            "Anchoring keys must be unique."
+        );
+
+        ensure!(
+            self.anchoring_keys
+                .iter()
+                .all(|keys| !self.excluded_keys.contains(&keys.bitcoin_key)),
+            "Excluded Bitcoin keys must not be used as the anchoring keys."
+        );
 
         // Verify that the redeem script is suitable.
         RedeemScriptBuilder::with_public_keys(self.anchoring_keys.iter().map(|x| x.bitcoin_key.0))
@@ -155,6 +271,11 @@ mod tests {
     use bitcoin::network::constants::Network;
     use btc_transaction_utils::test_data::secp_gen_keypair;
 
+    use exonum_merkledb::BinaryValue;
+    use proptest::{collection::vec, prelude::*, proptest};
+
+    use std::borrow::Cow;
+
     use crate::proto::AnchoringKeys;
 
     use super::Config;
@@ -180,6 +301,54 @@ mod tests {
         assert_eq!(config2, config);
     }
 
+    #[test]
+    fn config_canonical_keys_order() {
+        let mut public_keys = gen_anchoring_keys(Network::Bitcoin, 4);
+        let config = Config::with_public_keys(Network::Bitcoin, public_keys.clone()).unwrap();
+        config.validate().unwrap();
+        assert!(config.has_sorted_anchoring_keys());
+
+        public_keys.reverse();
+        let reversed_config = Config::with_public_keys(Network::Bitcoin, public_keys).unwrap();
+        assert_eq!(reversed_config, config);
+        assert_eq!(
+            reversed_config.anchoring_address(),
+            config.anchoring_address()
+        );
+
+        // Configurations with the keys in the other order remain valid and keep their address.
+        let mut unsorted_config = config.clone();
+        unsorted_config.anchoring_keys.reverse();
+        unsorted_config.validate().unwrap();
+        assert!(!unsorted_config.has_sorted_anchoring_keys());
+        assert_ne!(
+            unsorted_config.anchoring_address(),
+            config.anchoring_address()
+        );
+    }
+
+    #[test]
+    fn config_p2sh_wrapped_address() {
+        let public_keys = gen_anchoring_keys(Network::Testnet, 4);
+        let config = Config::with_public_keys(Network::Testnet, public_keys).unwrap();
+        let wrapped_config = Config {
+            p2sh_wrapped: true,
+            ..config.clone()
+        };
+
+        assert_eq!(wrapped_config.redeem_script(), config.redeem_script());
+        assert!(config.anchoring_out_script().is_v0_p2wsh());
+        assert!(wrapped_config.anchoring_out_script().is_p2sh());
+        assert_eq!(
+            wrapped_config.anchoring_address().0.script_pubkey(),
+            wrapped_config.anchoring_out_script()
+        );
+        assert!(wrapped_config
+            .anchoring_address()
+            .to_string()
+            .starts_with('2'));
+    }
+
     #[test]
     fn config_anchoring_height() {
         let public_keys = gen_anchoring_keys(Network::Bitcoin, 4);
@@ -244,11 +413,83 @@ mod tests {
                 },
                 "Transaction fee should be greater than",
             ),
+            (
+                Config {
+                    anchoring_keys: gen_anchoring_keys(bitcoin::Network::Regtest, 4),
+                    min_fee_rate: 20,
+                    max_fee_rate: 10,
+                    ..Config::default()
+                },
+                "Maximal fee rate should not be less than the minimal one",
+            ),
+            (
+                Config {
+                    anchoring_keys: gen_anchoring_keys(bitcoin::Network::Regtest, 4),
+                    min_fee_rate: 20,
+                    ..Config::default()
+                },
+                "Transaction fee should be within the fee rate bounds",
+            ),
+            (
+                Config {
+                    anchoring_keys: gen_anchoring_keys(bitcoin::Network::Regtest, 4),
+                    max_fee_rate: 5,
+                    ..Config::default()
+                },
+                "Transaction fee should be within the fee rate bounds",
+            ),
+            (
+                Config {
+                    anchoring_keys: gen_anchoring_keys(bitcoin::Network::Regtest, 4),
+                    compact_payload: true,
+                    extensible_payload: true,
+                    ..Config::default()
+                },
+                "Compact and extensible payloads cannot be enabled simultaneously",
+            ),
         ];
 
+        let mut anchoring_keys = gen_anchoring_keys(bitcoin::Network::Regtest, 4);
+        anchoring_keys[3] = anchoring_keys[0].clone();
+        let duplicated_config = Config {
+            anchoring_keys,
+            ..Config::default()
+        };
+        let mut test_cases = test_cases.to_vec();
+        test_cases.push((duplicated_config, "Anchoring keys must be unique"));
+
         for (config, expected_err) in &test_cases {
             let actual_err = config.validate().unwrap_err().to_string();
             assert!(actual_err.contains(expected_err), actual_err);
         }
     }
+
+    proptest! {
+        #[test]
+        fn config_arbitrary_bytes(bytes in vec(any::<u8>(), 0..512)) {
+            // Parsing of the arbitrary data must not panic.
+            if let Ok(config) = Config::from_bytes(Cow::Owned(bytes)) {
+                let _ = config.validate();
+            }
+        }
+
+        #[test]
+        fn config_corrupted_bytes(
+            mut bytes in Just(
+                Config::with_public_keys(Network::Testnet, gen_anchoring_keys(Network::Testnet, 4))
+                    .unwrap()
+                    .into_bytes()
+            ),
+            index in any::<usize>(),
+            mask in 1_u8..,
+        ) {
+            let index = index % bytes.len();
+            bytes[index] ^= mask;
+            if let Ok(config) = Config::from_bytes(Cow::Owned(bytes)) {
+                if config.validate().is_ok() {
+                    let _ = config.anchoring_address();
+                }
+            }
+        }
+    }
 }
@@ -0,0 +1,1078 @@
+// Copyright 2020 The Exonum Team
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! gRPC variant of the private anchoring API.
+//!
+//! [`PrivateApiServer`] serves the private API over gRPC by means of any [`PrivateApi`]
+//! implementation, e.g. the HTTP client of the node private API running on the same host.
+//! [`PrivateApiClient`] implements [`PrivateApi`] itself, so the sync utility can be run
+//! on a separate host which communicates with the node only through the gRPC server.
+//!
+//! The service and its messages are described in the `grpc.proto` file.
+//!
+//! Errors of the anchoring API are mapped to the gRPC status codes, while the exact
+//! [`ApiErrorKind`] is passed in the `anchoring-error-code` metadata entry.
+//!
+//! The same file describes the `Signer` service of the remote signing service,
+//! which is served by [`SignerServer`] and consumed by [`SignerClient`].
+//!
+//! [`PrivateApiServer`]: struct.PrivateApiServer.html
+//! [`PrivateApiClient`]: struct.PrivateApiClient.html
+//! [`SignerServer`]: struct.SignerServer.html
+//! [`SignerClient`]: struct.SignerClient.html
+//! [`PrivateApi`]: ../api/trait.PrivateApi.html
+//! [`ApiErrorKind`]: ../api/enum.ApiErrorKind.html
+
+use anyhow::anyhow;
+use async_trait::async_trait;
+use btc_transaction_utils::multisig::RedeemScript;
+use bytes::{Buf, BufMut};
+use exonum::{crypto::Hash, helpers::Height, merkledb::BinaryValue};
+use exonum_proto::ProtobufConvert;
+use exonum_supervisor::ConfigPropose;
+use protobuf::{well_known_types::Empty, Message};
+use tonic::{
+    body::BoxBody,
+    codec::{Codec, DecodeBuf, Decoder, EncodeBuf, Encoder},
+    codegen::{http, BoxFuture, Context, Never, Poll, Service},
+    metadata::MetadataValue,
+    transport::{Body, Channel, Endpoint, NamedService},
+    Code, Request, Response, Status,
+};
+
+use std::{future::Future, marker::PhantomData, sync::Arc};
+
+use crate::{
+    api::{
+        AnchoringChainLength, AnchoringProposalState, AnchoringStatistics,
+        AnchoringTransactionsPage, ApiErrorKind, IndexQuery, InputSigningProgress,
+        KeyReplacementQuery, PrivateApi, SigningSession, TransactionsRangeQuery,
+    },
+    blockchain::{
+        AccelerateAnchoring, AddFunds, RecoverAnchoring, ReportInclusionProof, SignInput,
+        SignInputs, VoteFeeRate,
+    },
+    btc,
+    config::Config,
+    proto::grpc as proto,
+    sync::{ApiClientError, Signer, SigningRequest, SigningResponse},
+};
+
+/// Full name of the gRPC service.
+const SERVICE_NAME: &str = "exonum.service.btc_anchoring.grpc.PrivateApi";
+/// Full name of the gRPC service of the remote signer.
+const SIGNER_SERVICE_NAME: &str = "exonum.service.btc_anchoring.grpc.Signer";
+/// Metadata key with the numeric code of the anchoring API error kind.
+const ERROR_CODE_KEY: &str = "anchoring-error-code";
+
+/// gRPC server of the private anchoring API, which serves the requests by means
+/// of the wrapped private API implementation.
+///
+/// # Examples
+///
+/// ```no_run
+/// use exonum_btc_anchoring::{api::PrivateApi, grpc::PrivateApiServer, sync::ApiClientError};
+/// use tonic::transport::Server;
+///
+/// async fn serve<T>(api: T) -> anyhow::Result<()>
+/// where
+///     T: PrivateApi + Send + Sync + 'static,
+///     T::Error: ApiClientError + Send,
+/// {
+///     Server::builder()
+///         .add_service(PrivateApiServer::new(api))
+///         .serve("127.0.0.1:8090".parse()?)
+///         .await?;
+///     Ok(())
+/// }
+/// ```
+#[derive(Debug)]
+pub struct PrivateApiServer<T> {
+    api: Arc<T>,
+}
+
+impl<T> PrivateApiServer<T> {
+    /// Creates a gRPC server for the given private API implementation.
+    pub fn new(api: T) -> Self {
+        Self { api: Arc::new(api) }
+    }
+}
+
+impl<T> Clone for PrivateApiServer<T> {
+    fn clone(&self) -> Self {
+        Self {
+            api: Arc::clone(&self.api),
+        }
+    }
+}
+
+impl<T> NamedService for PrivateApiServer<T> {
+    const NAME: &'static str = SERVICE_NAME;
+}
+
+impl<T> Service<http::Request<Body>> for PrivateApiServer<T>
+where
+    T: PrivateApi + Send + Sync + 'static,
+    T::Error: ApiClientError + Send,
+{
+    type Response = http::Response<BoxBody>;
+    type Error = Never;
+    type Future = BoxFuture<Self::Response, Self::Error>;
+
+    fn poll_ready(&mut self, _cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        Poll::Ready(Ok(()))
+    }
+
+    fn call(&mut self, request: http::Request<Body>) -> Self::Future {
+        let api = Arc::clone(&self.api);
+        let method = request
+            .uri()
+            .path()
+            .trim_start_matches('/')
+            .trim_start_matches(SERVICE_NAME)
+            .trim_start_matches('/')
+            .to_owned();
+
+        match method.as_str() {
+            "SignInput" => unary(request, move |sign_input: SignInput| async move {
+                api.sign_input(sign_input).await
+            }),
+            "SignInputs" => unary(request, move |sign_inputs: SignInputs| async move {
+                api.sign_inputs(sign_inputs.inputs).await
+            }),
+            "AddFunds" => unary(request, move |add_funds: AddFunds| async move {
+                api.add_funds(add_funds.transaction).await
+            }),
+            "AnchoringProposal" => {
+                unary(
+                    request,
+                    move |()| async move { api.anchoring_proposal().await },
+                )
+            }
+            "AnchoringProposalPsbt" => unary(request, move |()| async move {
+                api.anchoring_proposal_psbt().await.map(OptionalPsbt)
+            }),
+            "ImportPsbt" => unary(request, move |psbt| async move {
+                api.import_psbt(psbt).await.map(Hashes)
+            }),
+            "Config" => unary(request, move |()| async move { api.config().await }),
+            "FollowingConfig" => unary(request, move |()| async move {
+                api.following_config().await.map(OptionalConfig)
+            }),
+            "TransactionWithIndex" => unary(request, move |query: IndexQuery| async move {
+                api.transaction_with_index(query.index)
+                    .await
+                    .map(OptionalTransaction)
+            }),
+            "TransactionsCount" => {
+                unary(
+                    request,
+                    move |()| async move { api.transactions_count().await },
+                )
+            }
+            "Stats" => unary(request, move |()| async move { api.stats().await }),
+            "Transactions" => unary(request, move |query: TransactionsRangeQuery| async move {
+                api.transactions(query.from, query.count).await
+            }),
+            "SigningSession" => unary(request, move |()| async move {
+                api.signing_session().await.map(OptionalSigningSession)
+            }),
+            "ReportInclusionProof" => {
+                unary(request, move |report: ReportInclusionProof| async move {
+                    api.report_inclusion_proof(report.proof).await
+                })
+            }
+            "VoteFeeRate" => unary(request, move |vote: VoteFeeRate| async move {
+                api.vote_fee_rate(vote.fee_rate).await
+            }),
+            "AccelerateAnchoring" => unary(request, move |vote: AccelerateAnchoring| async move {
+                api.accelerate_anchoring(vote.txid, vote.fee_rate).await
+            }),
+            "RecoverAnchoring" => unary(request, move |vote: RecoverAnchoring| async move {
+                api.recover_anchoring(vote.txid).await
+            }),
+            "KeyReplacementProposal" => unary(request, move |query| async move {
+                api.key_replacement_proposal(query)
+                    .await
+                    .map(SupervisorProposal)
+            }),
+            _ => Box::pin(async move {
+                Ok(Status::unimplemented(format!("Unknown method {}", method)).to_http())
+            }),
+        }
+    }
+}
+
+/// Handles the unary gRPC request by the given handler.
+fn unary<Req, Resp, E, F, R>(
+    request: http::Request<Body>,
+    handler: F,
+) -> BoxFuture<http::Response<BoxBody>, Never>
+where
+    Req: ProtobufConvert + Send + 'static,
+    Req::ProtoStruct: Message,
+    Resp: ProtobufConvert + Send + Sync + 'static,
+    Resp::ProtoStruct: Message,
+    E: ApiClientError,
+    F: FnOnce(Req) -> R + Send + 'static,
+    R: Future<Output = Result<Resp, E>> + Send + 'static,
+{
+    let handler = UnaryHandler(Some(Box::new(move |request| {
+        let response = handler(request);
+        Box::pin(async move { response.await.map_err(to_status) }) as BoxFuture<_, _>
+    })));
+    Box::pin(async move {
+        let mut grpc = tonic::server::Grpc::new(ProtobufCodec::<Resp, Req>::default());
+        Ok(grpc.unary(handler, request).await)
+    })
+}
+
+type BoxHandler<Req, Resp> = Box<dyn FnOnce(Req) -> BoxFuture<Resp, Status> + Send>;
+
+/// Service which handles a single unary gRPC request.
+struct UnaryHandler<Req, Resp>(Option<BoxHandler<Req, Resp>>);
+
+impl<Req, Resp> Service<Request<Req>> for UnaryHandler<Req, Resp>
+where
+    Resp: Send + 'static,
+{
+    type Response = Response<Resp>;
+    type Error = Status;
+    type Future = BoxFuture<Self::Response, Self::Error>;
+
+    fn poll_ready(&mut self, _cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        Poll::Ready(Ok(()))
+    }
+
+    fn call(&mut self, request: Request<Req>) -> Self::Future {
+        let handler = self
+            .0
+            .take()
+            .expect("Unary request handler is called more than once");
+        let response = handler(request.into_inner());
+        Box::pin(async move { response.await.map(Response::new) })
+    }
+}
+
+/// Converts the error of the wrapped private API into the gRPC status.
+fn to_status(e: impl ApiClientError) -> Status {
+    let kind = e.api_error_kind();
+    let code = match kind {
+        Some(ApiErrorKind::BadRequest) => Code::InvalidArgument,
+        Some(ApiErrorKind::NotValidator) => Code::FailedPrecondition,
+        Some(ApiErrorKind::Timeout) => Code::DeadlineExceeded,
+        Some(ApiErrorKind::PayloadTooLarge) => Code::ResourceExhausted,
+        Some(ApiErrorKind::Broadcast) => Code::Unavailable,
+        Some(ApiErrorKind::Internal) => Code::Internal,
+        None if e.is_retriable() => Code::Unavailable,
+        None => Code::Unknown,
+    };
+
+    let mut status = Status::new(code, e.to_string());
+    if let Some(kind) = kind {
+        status
+            .metadata_mut()
+            .insert(ERROR_CODE_KEY, MetadataValue::from(u16::from(kind.code())));
+    }
+    status
+}
+
+impl ApiClientError for Status {
+    fn api_error_kind(&self) -> Option<ApiErrorKind> {
+        let code = self.metadata().get(ERROR_CODE_KEY)?.to_str().ok()?;
+        ApiErrorKind::from_code(code.parse().ok()?)
+    }
+
+    fn is_retriable(&self) -> bool {
+        match self.api_error_kind() {
+            Some(kind) => kind.is_transient(),
+            None => matches!(
+                self.code(),
+                Code::Unavailable | Code::DeadlineExceeded | Code::Aborted
+            ),
+        }
+    }
+}
+
+/// gRPC client of the private anchoring API.
+#[derive(Debug, Clone)]
+pub struct PrivateApiClient {
+    inner: tonic::client::Grpc<Channel>,
+}
+
+impl PrivateApiClient {
+    /// Creates a client which sends the requests through the given channel.
+    pub fn new(channel: Channel) -> Self {
+        Self {
+            inner: tonic::client::Grpc::new(channel),
+        }
+    }
+
+    /// Connects to the gRPC server with the given address.
+    /// Address should be in form `{http|https}://{address}:{port}`.
+    pub async fn connect(address: impl AsRef<str>) -> Result<Self, tonic::transport::Error> {
+        let channel = Endpoint::new(address.as_ref().to_owned())?
+            .connect()
+            .await?;
+        Ok(Self::new(channel))
+    }
+
+    async fn unary<Req, Resp>(&self, method: &str, request: Req) -> Result<Resp, Status>
+    where
+        Req: ProtobufConvert + Send + Sync + 'static,
+        Req::ProtoStruct: Message,
+        Resp: ProtobufConvert + Send + Sync + 'static,
+        Resp::ProtoStruct: Message,
+    {
+        call_unary(&self.inner, SERVICE_NAME, method, request).await
+    }
+}
+
+/// Sends the unary gRPC request to the given method of the service.
+async fn call_unary<Req, Resp>(
+    inner: &tonic::client::Grpc<Channel>,
+    service: &str,
+    method: &str,
+    request: Req,
+) -> Result<Resp, Status>
+where
+    Req: ProtobufConvert + Send + Sync + 'static,
+    Req::ProtoStruct: Message,
+    Resp: ProtobufConvert + Send + Sync + 'static,
+    Resp::ProtoStruct: Message,
+{
+    let mut inner = inner.clone();
+    inner
+        .ready()
+        .await
+        .map_err(|e| Status::unavailable(format!("Service is not ready: {}", e)))?;
+    let path = format!("/{}/{}", service, method)
+        .parse()
+        .map_err(|e| Status::internal(format!("Invalid method path: {}", e)))?;
+    inner
+        .unary(Request::new(request), path, ProtobufCodec::default())
+        .await
+        .map(Response::into_inner)
+}
+
+#[async_trait]
+impl PrivateApi for PrivateApiClient {
+    type Error = Status;
+
+    async fn sign_input(&self, sign_input: SignInput) -> Result<Hash, Self::Error> {
+        self.unary("SignInput", sign_input).await
+    }
+
+    async fn sign_inputs(&self, sign_inputs: Vec<SignInput>) -> Result<Hash, Self::Error> {
+        let sign_inputs = SignInputs {
+            inputs: sign_inputs,
+        };
+        self.unary("SignInputs", sign_inputs).await
+    }
+
+    async fn add_funds(&self, transaction: btc::Transaction) -> Result<Hash, Self::Error> {
+        self.unary("AddFunds", AddFunds { transaction }).await
+    }
+
+    async fn anchoring_proposal(&self) -> Result<AnchoringProposalState, Self::Error> {
+        self.unary("AnchoringProposal", ()).await
+    }
+
+    async fn anchoring_proposal_psbt(&self) -> Result<Option<btc::Psbt>, Self::Error> {
+        self.unary("AnchoringProposalPsbt", ())
+            .await
+            .map(|OptionalPsbt(psbt)| psbt)
+    }
+
+    async fn import_psbt(&self, psbt: btc::Psbt) -> Result<Vec<Hash>, Self::Error> {
+        self.unary("ImportPsbt", psbt)
+            .await
+            .map(|Hashes(hashes)| hashes)
+    }
+
+    async fn config(&self) -> Result<Config, Self::Error> {
+        self.unary("Config", ()).await
+    }
+
+    async fn following_config(&self) -> Result<Option<Config>, Self::Error> {
+        self.unary("FollowingConfig", ())
+            .await
+            .map(|OptionalConfig(config)| config)
+    }
+
+    async fn transaction_with_index(
+        &self,
+        index: u64,
+    ) -> Result<Option<btc::Transaction>, Self::Error> {
+        self.unary("TransactionWithIndex", IndexQuery { index })
+            .await
+            .map(|OptionalTransaction(transaction)| transaction)
+    }
+
+    async fn transactions_count(&self) -> Result<AnchoringChainLength, Self::Error> {
+        self.unary("TransactionsCount", ()).await
+    }
+
+    async fn stats(&self) -> Result<AnchoringStatistics, Self::Error> {
+        self.unary("Stats", ()).await
+    }
+
+    async fn transactions(
+        &self,
+        from: u64,
+        count: u64,
+    ) -> Result<AnchoringTransactionsPage, Self::Error> {
+        self.unary("Transactions", TransactionsRangeQuery { from, count })
+            .await
+    }
+
+    async fn signing_session(&self) -> Result<Option<SigningSession>, Self::Error> {
+        self.unary("SigningSession", ())
+            .await
+            .map(|OptionalSigningSession(session)| session)
+    }
+
+    async fn report_inclusion_proof(&self, proof: btc::MerkleBlock) -> Result<Hash, Self::Error> {
+        self.unary("ReportInclusionProof", ReportInclusionProof { proof })
+            .await
+    }
+
+    async fn vote_fee_rate(&self, fee_rate: u64) -> Result<Hash, Self::Error> {
+        self.unary("VoteFeeRate", VoteFeeRate { fee_rate }).await
+    }
+
+    async fn accelerate_anchoring(
+        &self,
+        txid: btc::Sha256d,
+        fee_rate: u64,
+    ) -> Result<Hash, Self::Error> {
+        self.unary(
+            "AccelerateAnchoring",
+            AccelerateAnchoring { txid, fee_rate },
+        )
+        .await
+    }
+
+    async fn recover_anchoring(&self, txid: btc::Sha256d) -> Result<Hash, Self::Error> {
+        self.unary("RecoverAnchoring", RecoverAnchoring { txid })
+            .await
+    }
+
+    async fn key_replacement_proposal(
+        &self,
+        query: KeyReplacementQuery,
+    ) -> Result<ConfigPropose, Self::Error> {
+        self.unary("KeyReplacementProposal", query)
+            .await
+            .map(|SupervisorProposal(propose)| propose)
+    }
+}
+
+/// gRPC server of the remote signer, which signs the anchoring proposals by means
+/// of the wrapped signer.
+///
+/// # Examples
+///
+/// ```no_run
+/// use exonum_btc_anchoring::{grpc::SignerServer, sync::LocalSigner};
+/// use tonic::transport::Server;
+///
+/// async fn serve(signer: LocalSigner) -> anyhow::Result<()> {
+///     Server::builder()
+///         .add_service(SignerServer::new(signer))
+///         .serve("127.0.0.1:50051".parse()?)
+///         .await?;
+///     Ok(())
+/// }
+/// ```
+#[derive(Debug)]
+pub struct SignerServer<S> {
+    signer: Arc<S>,
+}
+
+impl<S> SignerServer<S> {
+    /// Creates a gRPC server for the given signer.
+    pub fn new(signer: S) -> Self {
+        Self {
+            signer: Arc::new(signer),
+        }
+    }
+}
+
+impl<S> Clone for SignerServer<S> {
+    fn clone(&self) -> Self {
+        Self {
+            signer: Arc::clone(&self.signer),
+        }
+    }
+}
+
+impl<S> NamedService for SignerServer<S> {
+    const NAME: &'static str = SIGNER_SERVICE_NAME;
+}
+
+impl<S> Service<http::Request<Body>> for SignerServer<S>
+where
+    S: Signer + 'static,
+{
+    type Response = http::Response<BoxBody>;
+    type Error = Never;
+    type Future = BoxFuture<Self::Response, Self::Error>;
+
+    fn poll_ready(&mut self, _cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        Poll::Ready(Ok(()))
+    }
+
+    fn call(&mut self, request: http::Request<Body>) -> Self::Future {
+        let signer = Arc::clone(&self.signer);
+        let method = request
+            .uri()
+            .path()
+            .trim_start_matches('/')
+            .trim_start_matches(SIGNER_SERVICE_NAME)
+            .trim_start_matches('/')
+            .to_owned();
+
+        match method.as_str() {
+            "Sign" => unary(request, move |request: SigningRequest| async move {
+                if !signer.has_key(&request.bitcoin_key) {
+                    return Err(Status::not_found(format!(
+                        "Unknown Bitcoin key {}",
+                        request.bitcoin_key
+                    )));
+                }
+                signer
+                    .sign(request)
+                    .await
+                    .map_err(|e| Status::internal(e.to_string()))
+            }),
+            _ => Box::pin(async move {
+                Ok(Status::unimplemented(format!("Unknown method {}", method)).to_http())
+            }),
+        }
+    }
+}
+
+/// gRPC client of the remote signer.
+#[derive(Debug, Clone)]
+pub struct SignerClient {
+    inner: tonic::client::Grpc<Channel>,
+    bitcoin_keys: Vec<btc::PublicKey>,
+}
+
+impl SignerClient {
+    /// Creates a client which sends the requests through the given channel to the signer
+    /// keeping the given Bitcoin keys.
+    pub fn new(channel: Channel, bitcoin_keys: Vec<btc::PublicKey>) -> Self {
+        Self {
+            inner: tonic::client::Grpc::new(channel),
+            bitcoin_keys,
+        }
+    }
+
+    /// Connects to the gRPC server with the given address.
+    /// Address should be in form `{http|https}://{address}:{port}`.
+    pub async fn connect(
+        address: impl AsRef<str>,
+        bitcoin_keys: Vec<btc::PublicKey>,
+    ) -> Result<Self, tonic::transport::Error> {
+        let channel = Endpoint::new(address.as_ref().to_owned())?
+            .connect()
+            .await?;
+        Ok(Self::new(channel, bitcoin_keys))
+    }
+
+    /// Creates a client which connects to the gRPC server with the given address
+    /// on the first request.
+    pub fn connect_lazy(
+        address: impl AsRef<str>,
+        bitcoin_keys: Vec<btc::PublicKey>,
+    ) -> Result<Self, tonic::transport::Error> {
+        let channel = Endpoint::new(address.as_ref().to_owned())?.connect_lazy()?;
+        Ok(Self::new(channel, bitcoin_keys))
+    }
+}
+
+#[async_trait]
+impl Signer for SignerClient {
+    fn has_key(&self, bitcoin_key: &btc::PublicKey) -> bool {
+        self.bitcoin_keys.contains(bitcoin_key)
+    }
+
+    async fn sign(&self, request: SigningRequest) -> anyhow::Result<SigningResponse> {
+        call_unary(&self.inner, SIGNER_SERVICE_NAME, "Sign", request)
+            .await
+            .map_err(|e| anyhow!("Signing service responded with {}", e))
+    }
+}
+
+/// gRPC codec of the messages with the rust-protobuf representation.
+struct ProtobufCodec<E, D>(PhantomData<fn(E) -> D>);
+
+impl<E, D> Default for ProtobufCodec<E, D> {
+    fn default() -> Self {
+        Self(PhantomData)
+    }
+}
+
+impl<E, D> Codec for ProtobufCodec<E, D>
+where
+    E: ProtobufConvert + Send + 'static,
+    E::ProtoStruct: Message,
+    D: ProtobufConvert + Send + 'static,
+    D::ProtoStruct: Message,
+{
+    type Encode = E;
+    type Decode = D;
+    type Encoder = ProtobufEncoder<E>;
+    type Decoder = ProtobufDecoder<D>;
+
+    fn encoder(&mut self) -> Self::Encoder {
+        ProtobufEncoder(PhantomData)
+    }
+
+    fn decoder(&mut self) -> Self::Decoder {
+        ProtobufDecoder(PhantomData)
+    }
+}
+
+struct ProtobufEncoder<T>(PhantomData<fn(T)>);
+
+impl<T> Encoder for ProtobufEncoder<T>
+where
+    T: ProtobufConvert,
+    T::ProtoStruct: Message,
+{
+    type Item = T;
+    type Error = Status;
+
+    fn encode(&mut self, item: Self::Item, dst: &mut EncodeBuf<'_>) -> Result<(), Self::Error> {
+        let bytes = item
+            .to_pb()
+            .write_to_bytes()
+            .map_err(|e| Status::internal(e.to_string()))?;
+        dst.put_slice(&bytes);
+        Ok(())
+    }
+}
+
+struct ProtobufDecoder<T>(PhantomData<fn() -> T>);
+
+impl<T> Decoder for ProtobufDecoder<T>
+where
+    T: ProtobufConvert,
+    T::ProtoStruct: Message,
+{
+    type Item = T;
+    type Error = Status;
+
+    fn decode(&mut self, src: &mut DecodeBuf<'_>) -> Result<Option<Self::Item>, Self::Error> {
+        let bytes = src.to_bytes();
+        let pb = T::ProtoStruct::parse_from_bytes(&bytes)
+            .map_err(|e| Status::internal(e.to_string()))?;
+        T::from_pb(pb)
+            .map(Some)
+            .map_err(|e| Status::invalid_argument(e.to_string()))
+    }
+}
+
+/// Optional partially signed transaction.
+struct OptionalPsbt(Option<btc::Psbt>);
+
+impl ProtobufConvert for OptionalPsbt {
+    type ProtoStruct = proto::OptionalPsbt;
+
+    fn to_pb(&self) -> Self::ProtoStruct {
+        Self::ProtoStruct {
+            psbt: self.0.as_ref().map(ProtobufConvert::to_pb).into(),
+            ..Self::ProtoStruct::default()
+        }
+    }
+
+    fn from_pb(pb: Self::ProtoStruct) -> anyhow::Result<Self> {
+        pb.psbt
+            .into_option()
+            .map(ProtobufConvert::from_pb)
+            .transpose()
+            .map(Self)
+    }
+}
+
+/// Hashes of the sent Exonum transactions.
+struct Hashes(Vec<Hash>);
+
+impl ProtobufConvert for Hashes {
+    type ProtoStruct = proto::Hashes;
+
+    fn to_pb(&self) -> Self::ProtoStruct {
+        let mut proto_struct = Self::ProtoStruct::default();
+        proto_struct.set_hashes(self.0.to_pb().into());
+        proto_struct
+    }
+
+    fn from_pb(mut pb: Self::ProtoStruct) -> anyhow::Result<Self> {
+        ProtobufConvert::from_pb(pb.take_hashes().into_vec()).map(Self)
+    }
+}
+
+/// Optional anchoring configuration.
+struct OptionalConfig(Option<Config>);
+
+impl ProtobufConvert for OptionalConfig {
+    type ProtoStruct = proto::OptionalConfig;
+
+    fn to_pb(&self) -> Self::ProtoStruct {
+        Self::ProtoStruct {
+            config: self.0.as_ref().map(ProtobufConvert::to_pb).into(),
+            ..Self::ProtoStruct::default()
+        }
+    }
+
+    fn from_pb(pb: Self::ProtoStruct) -> anyhow::Result<Self> {
+        pb.config
+            .into_option()
+            .map(ProtobufConvert::from_pb)
+            .transpose()
+            .map(Self)
+    }
+}
+
+/// Optional Bitcoin transaction.
+struct OptionalTransaction(Option<btc::Transaction>);
+
+impl ProtobufConvert for OptionalTransaction {
+    type ProtoStruct = proto::OptionalTransaction;
+
+    fn to_pb(&self) -> Self::ProtoStruct {
+        Self::ProtoStruct {
+            transaction: self.0.as_ref().map(ProtobufConvert::to_pb).into(),
+            ..Self::ProtoStruct::default()
+        }
+    }
+
+    fn from_pb(pb: Self::ProtoStruct) -> anyhow::Result<Self> {
+        pb.transaction
+            .into_option()
+            .map(ProtobufConvert::from_pb)
+            .transpose()
+            .map(Self)
+    }
+}
+
+/// Optional signing session.
+struct OptionalSigningSession(Option<SigningSession>);
+
+impl ProtobufConvert for OptionalSigningSession {
+    type ProtoStruct = proto::OptionalSigningSession;
+
+    fn to_pb(&self) -> Self::ProtoStruct {
+        Self::ProtoStruct {
+            session: self.0.as_ref().map(ProtobufConvert::to_pb).into(),
+            ..Self::ProtoStruct::default()
+        }
+    }
+
+    fn from_pb(pb: Self::ProtoStruct) -> anyhow::Result<Self> {
+        pb.session
+            .into_option()
+            .map(ProtobufConvert::from_pb)
+            .transpose()
+            .map(Self)
+    }
+}
+
+/// Configuration proposal of the supervisor service.
+struct SupervisorProposal(ConfigPropose);
+
+impl ProtobufConvert for SupervisorProposal {
+    type ProtoStruct = proto::ConfigPropose;
+
+    fn to_pb(&self) -> Self::ProtoStruct {
+        let mut proto_struct = Self::ProtoStruct::default();
+        proto_struct.set_data(self.0.to_bytes());
+        proto_struct
+    }
+
+    fn from_pb(mut pb: Self::ProtoStruct) -> anyhow::Result<Self> {
+        ConfigPropose::from_bytes(pb.take_data().into()).map(Self)
+    }
+}
+
+fn optional_u64_to_pb(value: Option<u64>) -> protobuf::SingularPtrField<proto::OptionalUint64> {
+    value
+        .map(|value| {
+            let mut proto_struct = proto::OptionalUint64::default();
+            proto_struct.set_value(value);
+            proto_struct
+        })
+        .into()
+}
+
+fn optional_u64_from_pb(pb: protobuf::SingularPtrField<proto::OptionalUint64>) -> Option<u64> {
+    pb.into_option().map(|pb| pb.get_value())
+}
+
+impl ProtobufConvert for AnchoringProposalState {
+    type ProtoStruct = proto::AnchoringProposalState;
+
+    fn to_pb(&self) -> Self::ProtoStruct {
+        let mut proto_struct = Self::ProtoStruct::default();
+        match self {
+            AnchoringProposalState::None => proto_struct.set_none(Empty::default()),
+            AnchoringProposalState::Frozen => proto_struct.set_frozen(Empty::default()),
+            AnchoringProposalState::Available {
+                transaction,
+                inputs,
+            } => {
+                let mut available = proto::AvailableProposal::default();
+                available.set_transaction(transaction.to_pb());
+                available.set_inputs(inputs.to_pb().into());
+                proto_struct.set_available(available);
+            }
+            AnchoringProposalState::InsufficientFunds { total_fee, balance } => {
+                let mut funds = proto::InsufficientFunds::default();
+                funds.set_total_fee(*total_fee);
+                funds.set_balance(*balance);
+                proto_struct.set_insufficient_funds(funds);
+            }
+            AnchoringProposalState::NoInitialFunds => {
+                proto_struct.set_no_initial_funds(Empty::default())
+            }
+        }
+        proto_struct
+    }
+
+    fn from_pb(pb: Self::ProtoStruct) -> anyhow::Result<Self> {
+        use proto::AnchoringProposalState_oneof_state as State;
+
+        let state = pb
+            .state
+            .ok_or_else(|| anyhow!("Anchoring proposal state is absent"))?;
+        Ok(match state {
+            State::none(_) => AnchoringProposalState::None,
+            State::frozen(_) => AnchoringProposalState::Frozen,
+            State::available(mut available) => AnchoringProposalState::Available {
+                transaction: ProtobufConvert::from_pb(available.take_transaction())?,
+                inputs: ProtobufConvert::from_pb(available.take_inputs().into_vec())?,
+            },
+            State::insufficient_funds(funds) => AnchoringProposalState::InsufficientFunds {
+                total_fee: funds.get_total_fee(),
+                balance: funds.get_balance(),
+            },
+            State::no_initial_funds(_) => AnchoringProposalState::NoInitialFunds,
+        })
+    }
+}
+
+impl ProtobufConvert for IndexQuery {
+    type ProtoStruct = proto::IndexQuery;
+
+    fn to_pb(&self) -> Self::ProtoStruct {
+        let mut proto_struct = Self::ProtoStruct::default();
+        proto_struct.set_index(self.index);
+        proto_struct
+    }
+
+    fn from_pb(pb: Self::ProtoStruct) -> anyhow::Result<Self> {
+        Ok(Self {
+            index: pb.get_index(),
+        })
+    }
+}
+
+impl ProtobufConvert for AnchoringChainLength {
+    type ProtoStruct = proto::AnchoringChainLength;
+
+    fn to_pb(&self) -> Self::ProtoStruct {
+        let mut proto_struct = Self::ProtoStruct::default();
+        proto_struct.set_value(self.value);
+        proto_struct
+    }
+
+    fn from_pb(pb: Self::ProtoStruct) -> anyhow::Result<Self> {
+        Ok(Self::from(pb.get_value()))
+    }
+}
+
+impl ProtobufConvert for AnchoringStatistics {
+    type ProtoStruct = proto::AnchoringStatistics;
+
+    fn to_pb(&self) -> Self::ProtoStruct {
+        let mut proto_struct = Self::ProtoStruct::default();
+        proto_struct.set_transactions_count(self.transactions_count);
+        proto_struct.set_total_fee(self.total_fee);
+        proto_struct.set_average_fee(self.average_fee);
+        proto_struct.set_average_size(self.average_size);
+        proto_struct.set_average_latency(self.average_latency);
+        proto_struct.current_lag = optional_u64_to_pb(self.current_lag);
+        proto_struct.latest_final_transaction = optional_u64_to_pb(self.latest_final_transaction);
+        proto_struct.set_balance(self.balance);
+        proto_struct
+    }
+
+    fn from_pb(pb: Self::ProtoStruct) -> anyhow::Result<Self> {
+        Ok(Self {
+            transactions_count: pb.get_transactions_count(),
+            total_fee: pb.get_total_fee(),
+            average_fee: pb.get_average_fee(),
+            average_size: pb.get_average_size(),
+            average_latency: pb.get_average_latency(),
+            balance: pb.get_balance(),
+            current_lag: optional_u64_from_pb(pb.current_lag),
+            latest_final_transaction: optional_u64_from_pb(pb.latest_final_transaction),
+        })
+    }
+}
+
+impl ProtobufConvert for TransactionsRangeQuery {
+    type ProtoStruct = proto::TransactionsRangeQuery;
+
+    fn to_pb(&self) -> Self::ProtoStruct {
+        let mut proto_struct = Self::ProtoStruct::default();
+        proto_struct.set_from(self.from);
+        proto_struct.set_count(self.count);
+        proto_struct
+    }
+
+    fn from_pb(pb: Self::ProtoStruct) -> anyhow::Result<Self> {
+        Ok(Self {
+            from: pb.get_from(),
+            count: pb.get_count(),
+        })
+    }
+}
+
+impl ProtobufConvert for AnchoringTransactionsPage {
+    type ProtoStruct = proto::AnchoringTransactionsPage;
+
+    fn to_pb(&self) -> Self::ProtoStruct {
+        let mut proto_struct = Self::ProtoStruct::default();
+        proto_struct.set_total(self.total);
+        proto_struct.set_transactions(self.transactions.to_pb().into());
+        proto_struct
+    }
+
+    fn from_pb(mut pb: Self::ProtoStruct) -> anyhow::Result<Self> {
+        Ok(Self {
+            total: pb.get_total(),
+            transactions: ProtobufConvert::from_pb(pb.take_transactions().into_vec())?,
+        })
+    }
+}
+
+impl ProtobufConvert for InputSigningProgress {
+    type ProtoStruct = proto::InputSigningProgress;
+
+    fn to_pb(&self) -> Self::ProtoStruct {
+        let mut proto_struct = Self::ProtoStruct::default();
+        proto_struct.set_input(self.input);
+        proto_struct.set_signed(self.signed.to_pb().into());
+        proto_struct.set_missing(self.missing.to_pb().into());
+        proto_struct.set_signatures_left(self.signatures_left as u64);
+        proto_struct
+    }
+
+    fn from_pb(mut pb: Self::ProtoStruct) -> anyhow::Result<Self> {
+        Ok(Self {
+            input: pb.get_input(),
+            signed: ProtobufConvert::from_pb(pb.take_signed().into_vec())?,
+            missing: ProtobufConvert::from_pb(pb.take_missing().into_vec())?,
+            signatures_left: pb.get_signatures_left() as usize,
+        })
+    }
+}
+
+impl ProtobufConvert for SigningSession {
+    type ProtoStruct = proto::SigningSession;
+
+    fn to_pb(&self) -> Self::ProtoStruct {
+        let mut proto_struct = Self::ProtoStruct::default();
+        proto_struct.set_txid(self.txid.to_pb());
+        proto_struct.set_quorum(self.quorum as u64);
+        proto_struct.expires_at = optional_u64_to_pb(self.expires_at.map(|height| height.0));
+        proto_struct.set_inputs(self.inputs.to_pb().into());
+        proto_struct.set_signatures_left(self.signatures_left as u64);
+        proto_struct
+    }
+
+    fn from_pb(mut pb: Self::ProtoStruct) -> anyhow::Result<Self> {
+        Ok(Self {
+            txid: ProtobufConvert::from_pb(pb.take_txid())?,
+            quorum: pb.get_quorum() as usize,
+            expires_at: optional_u64_from_pb(std::mem::take(&mut pb.expires_at)).map(Height),
+            inputs: ProtobufConvert::from_pb(pb.take_inputs().into_vec())?,
+            signatures_left: pb.get_signatures_left() as usize,
+        })
+    }
+}
+
+impl ProtobufConvert for KeyReplacementQuery {
+    type ProtoStruct = proto::KeyReplacementQuery;
+
+    fn to_pb(&self) -> Self::ProtoStruct {
+        let mut proto_struct = Self::ProtoStruct::default();
+        proto_struct.set_bitcoin_key(self.bitcoin_key.to_pb());
+        proto_struct.set_new_bitcoin_key(self.new_bitcoin_key.to_pb());
+        proto_struct.set_configuration_number(self.configuration_number);
+        proto_struct.set_actual_from(self.actual_from.0);
+        proto_struct
+    }
+
+    fn from_pb(mut pb: Self::ProtoStruct) -> anyhow::Result<Self> {
+        Ok(Self {
+            bitcoin_key: ProtobufConvert::from_pb(pb.take_bitcoin_key())?,
+            new_bitcoin_key: ProtobufConvert::from_pb(pb.take_new_bitcoin_key())?,
+            configuration_number: pb.get_configuration_number(),
+            actual_from: Height(pb.get_actual_from()),
+        })
+    }
+}
+
+impl ProtobufConvert for SigningRequest {
+    type ProtoStruct = proto::SigningRequest;
+
+    fn to_pb(&self) -> Self::ProtoStruct {
+        let mut proto_struct = Self::ProtoStruct::default();
+        proto_struct.set_bitcoin_key(self.bitcoin_key.to_pb());
+        proto_struct.set_redeem_script(self.redeem_script.as_ref().to_bytes());
+        proto_struct.set_proposal(self.proposal.to_pb());
+        proto_struct.set_inputs(self.inputs.to_pb().into());
+        proto_struct
+    }
+
+    fn from_pb(mut pb: Self::ProtoStruct) -> anyhow::Result<Self> {
+        Ok(Self {
+            bitcoin_key: ProtobufConvert::from_pb(pb.take_bitcoin_key())?,
+            redeem_script: RedeemScript::from_script(pb.take_redeem_script().into())?,
+            proposal: ProtobufConvert::from_pb(pb.take_proposal())?,
+            inputs: ProtobufConvert::from_pb(pb.take_inputs().into_vec())?,
+        })
+    }
+}
+
+impl ProtobufConvert for SigningResponse {
+    type ProtoStruct = proto::SigningResponse;
+
+    fn to_pb(&self) -> Self::ProtoStruct {
+        let mut proto_struct = Self::ProtoStruct::default();
+        proto_struct.set_signatures(self.signatures.to_pb().into());
+        proto_struct
+    }
+
+    fn from_pb(mut pb: Self::ProtoStruct) -> anyhow::Result<Self> {
+        Ok(Self {
+            signatures: ProtobufConvert::from_pb(pb.take_signatures().into_vec())?,
+        })
+    }
+}
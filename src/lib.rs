@@ -44,7 +44,9 @@
 //! async fn main() -> anyhow::Result<()> {
 //!     exonum::helpers::init_logger()?;
 //!     NodeBuilder::new()
-//!         .with(Spec::new(exonum_btc_anchoring::BtcAnchoringService))
+//!         .with(Spec::migrating(
+//!             exonum_btc_anchoring::BtcAnchoringService::default(),
+//!         ))
 //!         .run()
 //!         .await
 //! }
@@ -58,12 +60,14 @@
     bare_trait_objects
 )]
 
-pub use crate::service::BtcAnchoringService;
+pub use crate::service::{BtcAnchoringService, DEFAULT_INSTANCE_ID, DEFAULT_INSTANCE_NAME};
 
 pub mod api;
 pub mod blockchain;
 pub mod btc;
 pub mod config;
+#[cfg(feature = "grpc")]
+pub mod grpc;
 pub mod sync;
 pub mod test_helpers;
 
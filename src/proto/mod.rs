@@ -19,6 +19,7 @@ pub use binary_map::BinaryMap;
 use anyhow::anyhow;
 use exonum::{
     crypto::{proto::*, Hash, PublicKey},
+    helpers::Height,
     merkledb::{
         impl_object_hash_for_binary_value, impl_serde_hex_for_binary_value, BinaryKey, BinaryValue,
         ObjectHash,
@@ -26,7 +27,7 @@ use exonum::{
 };
 use exonum_derive::{BinaryValue, ObjectHash};
 use exonum_proto::ProtobufConvert;
-use protobuf::Message;
+use protobuf::{well_known_types::Empty, Message};
 use serde_derive::{Deserialize, Serialize};
 
 use std::borrow::Cow;
@@ -68,6 +69,54 @@ impl ProtobufConvert for btc::Transaction {
     }
 }
 
+impl ProtobufConvert for btc::BlockHeader {
+    type ProtoStruct = btc_types::BlockHeader;
+
+    fn to_pb(&self) -> Self::ProtoStruct {
+        let bytes = bitcoin::consensus::serialize(&self.0);
+        let mut proto_struct = Self::ProtoStruct::default();
+        proto_struct.set_data(bytes);
+        proto_struct
+    }
+
+    fn from_pb(pb: Self::ProtoStruct) -> anyhow::Result<Self> {
+        let bytes = pb.get_data();
+        Ok(Self(bitcoin::consensus::deserialize(bytes)?))
+    }
+}
+
+impl ProtobufConvert for btc::MerkleBlock {
+    type ProtoStruct = btc_types::MerkleBlock;
+
+    fn to_pb(&self) -> Self::ProtoStruct {
+        let bytes = bitcoin::consensus::serialize(&self.0);
+        let mut proto_struct = Self::ProtoStruct::default();
+        proto_struct.set_data(bytes);
+        proto_struct
+    }
+
+    fn from_pb(pb: Self::ProtoStruct) -> anyhow::Result<Self> {
+        let bytes = pb.get_data();
+        Ok(Self(bitcoin::consensus::deserialize(bytes)?))
+    }
+}
+
+impl ProtobufConvert for btc::Psbt {
+    type ProtoStruct = btc_types::Psbt;
+
+    fn to_pb(&self) -> Self::ProtoStruct {
+        let bytes = bitcoin::consensus::serialize(&self.0);
+        let mut proto_struct = Self::ProtoStruct::default();
+        proto_struct.set_data(bytes);
+        proto_struct
+    }
+
+    fn from_pb(pb: Self::ProtoStruct) -> anyhow::Result<Self> {
+        let bytes = pb.get_data();
+        Ok(Self(bitcoin::consensus::deserialize(bytes)?))
+    }
+}
+
 impl ProtobufConvert for btc::InputSignature {
     type ProtoStruct = btc_types::InputSignature;
 
@@ -126,6 +175,16 @@ pub struct SignInput {
     pub input_signature: btc::InputSignature,
 }
 
+/// Exonum message with the signatures for several inputs of a new anchoring transaction.
+///
+/// All signatures should refer to the same anchoring proposal.
+#[derive(Debug, Clone, PartialEq, ProtobufConvert, BinaryValue, ObjectHash)]
+#[protobuf_convert(source = "self::service::SignInputs")]
+pub struct SignInputs {
+    /// Signatures of the inputs.
+    pub inputs: Vec<SignInput>,
+}
+
 /// Exonum message with the unspent funding transaction.
 #[derive(Debug, Clone, PartialEq, ProtobufConvert, BinaryValue, ObjectHash)]
 #[protobuf_convert(source = "self::service::AddFunds")]
@@ -134,6 +193,59 @@ pub struct AddFunds {
     pub transaction: btc::Transaction,
 }
 
+/// Exonum message with the Bitcoin block headers following the stored headers chain.
+#[derive(Debug, Clone, PartialEq, ProtobufConvert, BinaryValue, ObjectHash)]
+#[protobuf_convert(source = "self::service::ReportBitcoinHeaders")]
+pub struct ReportBitcoinHeaders {
+    /// Height of the first block header.
+    pub first_height: u64,
+    /// Bitcoin block headers in ascending order of heights.
+    pub headers: Vec<btc::BlockHeader>,
+}
+
+/// Exonum message with the proof of inclusion of anchoring or funding transactions
+/// in a Bitcoin block.
+#[derive(Debug, Clone, PartialEq, ProtobufConvert, BinaryValue, ObjectHash)]
+#[protobuf_convert(source = "self::service::ReportInclusionProof")]
+pub struct ReportInclusionProof {
+    /// Bitcoin Merkle block with the anchoring or funding transactions.
+    pub proof: btc::MerkleBlock,
+}
+
+/// Exonum message with the fee rate of the anchoring transactions voted by the anchoring node.
+#[derive(
+    Serialize, Deserialize, Debug, Clone, PartialEq, ProtobufConvert, BinaryValue, ObjectHash,
+)]
+#[protobuf_convert(source = "self::service::VoteFeeRate")]
+pub struct VoteFeeRate {
+    /// Fee per byte in satoshis.
+    pub fee_rate: u64,
+}
+
+/// Exonum message with the fee rate of the child transaction which accelerates
+/// the stuck anchoring transaction voted by the anchoring node.
+#[derive(
+    Serialize, Deserialize, Debug, Clone, PartialEq, ProtobufConvert, BinaryValue, ObjectHash,
+)]
+#[protobuf_convert(source = "self::service::AccelerateAnchoring")]
+pub struct AccelerateAnchoring {
+    /// Identifier of the accelerated anchoring transaction.
+    pub txid: btc::Sha256d,
+    /// Fee per byte in satoshis of the accelerated transaction and its child.
+    pub fee_rate: u64,
+}
+
+/// Exonum message with the vote of the anchoring node for the recovery of the anchoring
+/// chain, which abandons the unusable output of the latest anchoring transaction.
+#[derive(
+    Serialize, Deserialize, Debug, Clone, PartialEq, ProtobufConvert, BinaryValue, ObjectHash,
+)]
+#[protobuf_convert(source = "self::service::RecoverAnchoring")]
+pub struct RecoverAnchoring {
+    /// Identifier of the abandoned anchoring transaction.
+    pub txid: btc::Sha256d,
+}
+
 /// Consensus parameters in the BTC anchoring.
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq, BinaryValue, ObjectHash)]
 pub struct Config {
@@ -145,6 +257,65 @@ pub struct Config {
     pub anchoring_interval: u64,
     /// Fee per byte in satoshis.
     pub transaction_fee: u64,
+    /// Emergency freeze of the anchoring transactions signing. If set, the service stops
+    /// creating anchoring proposals and accepting signatures until the flag is cleared.
+    ///
+    /// Unlike other parameters, this flag takes effect immediately, even if the new
+    /// configuration changes the anchoring address.
+    #[serde(default)]
+    pub frozen: bool,
+    /// Use the compact payload encoding in the anchoring transactions. Verifiers
+    /// recognize both encodings, so the option can be changed at any time.
+    #[serde(default)]
+    pub compact_payload: bool,
+    /// Number of blocks after which the not finalized anchoring proposal expires
+    /// and its signatures are discarded. Zero means that proposals never expire
+    /// by the number of blocks.
+    #[serde(default)]
+    pub proposal_lifetime: u64,
+    /// Require the service keys of the anchoring nodes to belong to the validators
+    /// from the actual consensus configuration. If set, the configuration which
+    /// gives the anchoring keys to the other nodes is rejected.
+    #[serde(default)]
+    pub validators_only: bool,
+    /// Bitcoin keys of the previous anchoring nodes which signatures are not accepted
+    /// anymore, e.g. because the keys have been compromised.
+    ///
+    /// Unlike other parameters, the exclusion takes effect immediately: the signatures
+    /// made by these keys are rejected during the transition to the new anchoring address.
+    #[serde(default)]
+    pub excluded_keys: Vec<btc::PublicKey>,
+    /// Minimal fee per byte in satoshis of the anchoring transactions. The fee rate
+    /// is never lower than the minimal relayable one, even if this value is less.
+    #[serde(default)]
+    pub min_fee_rate: u64,
+    /// Maximal fee per byte in satoshis of the anchoring transactions. Zero means
+    /// that the fee rate is not limited.
+    ///
+    /// Fee rates voted by the anchoring nodes are bounded by these values, so a faulty
+    /// estimation cannot drain the anchoring wallet.
+    #[serde(default)]
+    pub max_fee_rate: u64,
+    /// Use the P2SH-wrapped P2WSH anchoring address instead of the native segwit one,
+    /// e.g. for the custodians which cannot fund bech32 addresses.
+    ///
+    /// The redeem script remains the same, but the change of this value moves
+    /// the anchoring chain to the new address like any other change of the address.
+    #[serde(default)]
+    pub p2sh_wrapped: bool,
+    /// Use the extensible payload encoding in the anchoring transactions, which allows
+    /// to add new fields to the payload without breaking the existing verifiers.
+    /// It cannot be combined with the `compact_payload` option.
+    #[serde(default)]
+    pub extensible_payload: bool,
+    /// Minimal number of Bitcoin confirmations of the funding transaction required
+    /// for the sync utility to vote for it. Zero means that the funding transactions
+    /// are voted for regardless of their confirmations.
+    ///
+    /// Spending an unconfirmed funding transaction, which may be dropped or reorganized
+    /// out of the Bitcoin blockchain, invalidates the whole anchoring chain after it.
+    #[serde(default)]
+    pub funding_confirmations: u32,
 }
 
 impl ProtobufConvert for Config {
@@ -157,6 +328,16 @@ impl ProtobufConvert for Config {
         proto_struct.set_anchoring_keys(self.anchoring_keys.to_pb().into());
         proto_struct.set_anchoring_interval(self.anchoring_interval.to_pb());
         proto_struct.set_transaction_fee(self.transaction_fee.to_pb());
+        proto_struct.set_frozen(self.frozen);
+        proto_struct.set_compact_payload(self.compact_payload);
+        proto_struct.set_proposal_lifetime(self.proposal_lifetime);
+        proto_struct.set_validators_only(self.validators_only);
+        proto_struct.set_excluded_keys(self.excluded_keys.to_pb().into());
+        proto_struct.set_min_fee_rate(self.min_fee_rate);
+        proto_struct.set_max_fee_rate(self.max_fee_rate);
+        proto_struct.set_p2sh_wrapped(self.p2sh_wrapped);
+        proto_struct.set_extensible_payload(self.extensible_payload);
+        proto_struct.set_funding_confirmations(self.funding_confirmations);
         proto_struct
     }
 
@@ -169,11 +350,226 @@ impl ProtobufConvert for Config {
             anchoring_keys: ProtobufConvert::from_pb(pb.take_anchoring_keys().into_vec())?,
             anchoring_interval: ProtobufConvert::from_pb(pb.get_anchoring_interval())?,
             transaction_fee: ProtobufConvert::from_pb(pb.get_transaction_fee())?,
+            frozen: pb.get_frozen(),
+            compact_payload: pb.get_compact_payload(),
+            proposal_lifetime: pb.get_proposal_lifetime(),
+            validators_only: pb.get_validators_only(),
+            excluded_keys: ProtobufConvert::from_pb(pb.take_excluded_keys().into_vec())?,
+            min_fee_rate: pb.get_min_fee_rate(),
+            max_fee_rate: pb.get_max_fee_rate(),
+            p2sh_wrapped: pb.get_p2sh_wrapped(),
+            extensible_payload: pb.get_extensible_payload(),
+            funding_confirmations: pb.get_funding_confirmations(),
+        })
+    }
+}
+
+/// Statistics of the anchoring node participation in signing of the anchoring
+/// transaction proposals.
+#[derive(
+    Serialize,
+    Deserialize,
+    Debug,
+    Clone,
+    Copy,
+    Default,
+    PartialEq,
+    ProtobufConvert,
+    BinaryValue,
+    ObjectHash,
+)]
+#[protobuf_convert(source = "self::internal::SigningStats")]
+pub struct SigningStats {
+    /// Bit mask of the participation in the latest finalized proposals,
+    /// the least significant bit corresponds to the latest proposal.
+    pub recent_participation: u64,
+    /// Total number of finalized proposals.
+    pub total_proposals: u64,
+    /// Total number of finalized proposals signed by the node.
+    pub signed_proposals: u64,
+}
+
+/// Aggregate statistics of the anchoring transactions chain.
+#[derive(
+    Serialize,
+    Deserialize,
+    Debug,
+    Clone,
+    Copy,
+    Default,
+    PartialEq,
+    ProtobufConvert,
+    BinaryValue,
+    ObjectHash,
+)]
+#[protobuf_convert(source = "self::internal::AnchoringStats")]
+pub struct AnchoringStats {
+    /// Total number of anchoring transactions.
+    pub transactions_count: u64,
+    /// Total fee paid by anchoring transactions in satoshis.
+    pub total_fee: u64,
+    /// Total size of anchoring transactions in bytes.
+    pub total_size: u64,
+    /// Total number of blocks between finalization of the anchoring transactions and
+    /// the attestation of their inclusion in Bitcoin blocks by a byzantine majority of
+    /// the anchoring nodes.
+    pub total_latency: u64,
+    /// Total number of anchoring transactions which became final.
+    pub final_transactions_count: u64,
+}
+
+/// Delay of the anchoring transaction, which has been finalized after the following
+/// anchoring height has passed.
+///
+/// The numbers of blocks are counted for each reason that has blocked the anchoring
+/// since the block to anchor has been committed.
+#[derive(
+    Serialize,
+    Deserialize,
+    Debug,
+    Clone,
+    Copy,
+    Default,
+    PartialEq,
+    ProtobufConvert,
+    BinaryValue,
+    ObjectHash,
+)]
+#[protobuf_convert(source = "self::internal::AnchoringDelay")]
+pub struct AnchoringDelay {
+    /// Height of the anchored block.
+    pub anchored_height: u64,
+    /// Height of the block in which the anchoring transaction has been finalized.
+    pub finalized_at: u64,
+    /// Number of blocks in which the anchoring has been frozen.
+    pub frozen_blocks: u64,
+    /// Number of blocks in which the proposal could not be created, e.g. due to
+    /// insufficient funds.
+    pub funding_blocks: u64,
+    /// Number of blocks in which the anchoring has been waiting for the following
+    /// configuration.
+    pub transition_blocks: u64,
+    /// Number of blocks in which the proposal has been waiting for the signatures.
+    pub signing_blocks: u64,
+}
+
+/// Identity of the anchoring transaction proposal which is being signed.
+#[derive(
+    Serialize, Deserialize, Debug, Clone, PartialEq, ProtobufConvert, BinaryValue, ObjectHash,
+)]
+#[protobuf_convert(source = "self::internal::AnchoringProposalInfo")]
+pub struct AnchoringProposalInfo {
+    /// Identifier of the unsigned anchoring transaction proposal.
+    pub txid: btc::Sha256d,
+    /// Number of the proposal inputs.
+    pub inputs: u32,
+    /// Height of the block in which the proposal has been created.
+    pub created_at: u64,
+}
+
+/// Change of the interval between anchored blocks.
+#[derive(
+    Serialize, Deserialize, Debug, Clone, Copy, PartialEq, ProtobufConvert, BinaryValue, ObjectHash,
+)]
+#[protobuf_convert(source = "self::internal::AnchoringIntervalChange")]
+pub struct AnchoringIntervalChange {
+    /// Height of the first anchoring block with the new interval.
+    pub effective_from: u64,
+    /// Interval in blocks between anchored blocks.
+    pub interval: u64,
+}
+
+/// Anchoring configuration along with the height from which it is actual.
+#[derive(
+    Serialize, Deserialize, Debug, Clone, PartialEq, ProtobufConvert, BinaryValue, ObjectHash,
+)]
+#[protobuf_convert(source = "self::internal::ConfigChange")]
+pub struct ConfigChange {
+    /// Height of the first block in which the configuration is actual.
+    pub actual_from: u64,
+    /// Anchoring configuration.
+    pub config: Config,
+}
+
+/// State of the transition of the anchoring chain from the actual anchoring address
+/// to the following one.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, BinaryValue, ObjectHash)]
+pub enum TransitionState {
+    /// The anchoring chain sends funds to the actual anchoring address.
+    Regular,
+    /// The following configuration with the new anchoring address has been accepted,
+    /// and the anchoring nodes are going to sign the transition transaction.
+    TransitionPending {
+        /// Height of the block in which the following configuration has been accepted.
+        started_at: Height,
+    },
+    /// The transition transaction has been signed and the following configuration has
+    /// become actual. The transaction is being broadcast by the anchoring nodes until
+    /// it reaches the finality depth in the Bitcoin blockchain.
+    TransitionBroadcast {
+        /// Height of the block in which the following configuration has been accepted.
+        started_at: Height,
+        /// Height of the block in which the transition transaction has been finalized.
+        finalized_at: Height,
+        /// Identifier of the transition transaction.
+        txid: btc::Sha256d,
+        /// Index of the transition transaction in the anchoring chain.
+        index: u64,
+    },
+}
+
+impl ProtobufConvert for TransitionState {
+    type ProtoStruct = internal::TransitionState;
+
+    fn to_pb(&self) -> Self::ProtoStruct {
+        let mut proto_struct = Self::ProtoStruct::default();
+        match self {
+            TransitionState::Regular => proto_struct.set_regular(Empty::default()),
+            TransitionState::TransitionPending { started_at } => {
+                let mut pending = internal::TransitionPending::default();
+                pending.set_started_at(started_at.0);
+                proto_struct.set_pending(pending);
+            }
+            TransitionState::TransitionBroadcast {
+                started_at,
+                finalized_at,
+                txid,
+                index,
+            } => {
+                let mut broadcast = internal::TransitionBroadcast::default();
+                broadcast.set_started_at(started_at.0);
+                broadcast.set_finalized_at(finalized_at.0);
+                broadcast.set_txid(txid.to_pb());
+                broadcast.set_index(*index);
+                proto_struct.set_broadcast(broadcast);
+            }
+        }
+        proto_struct
+    }
+
+    fn from_pb(pb: Self::ProtoStruct) -> anyhow::Result<Self> {
+        use internal::TransitionState_oneof_state as State;
+
+        let state = pb
+            .state
+            .ok_or_else(|| anyhow!("Transition state is absent"))?;
+        Ok(match state {
+            State::regular(_) => TransitionState::Regular,
+            State::pending(pending) => TransitionState::TransitionPending {
+                started_at: Height(pending.get_started_at()),
+            },
+            State::broadcast(mut broadcast) => TransitionState::TransitionBroadcast {
+                started_at: Height(broadcast.get_started_at()),
+                finalized_at: Height(broadcast.get_finalized_at()),
+                txid: ProtobufConvert::from_pb(broadcast.take_txid())?,
+                index: broadcast.get_index(),
+            },
         })
     }
 }
 
 impl_serde_hex_for_binary_value! { SignInput }
+impl_serde_hex_for_binary_value! { SignInputs }
 
 impl BinaryValue for btc::Sha256d {
     fn to_bytes(&self) -> Vec<u8> {
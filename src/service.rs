@@ -13,26 +13,93 @@
 // limitations under the License.
 
 use exonum::{
+    blockchain::config::InstanceInitParams,
+    crypto::KeyPair,
     helpers::ValidateInput,
     merkledb::BinaryValue,
-    runtime::{CommonError, ExecutionContext, ExecutionError},
+    runtime::{
+        migrations::{
+            InitMigrationError, LinearMigrations, MigrateData, MigrationContext, MigrationError,
+            MigrationScript,
+        },
+        versioning::Version,
+        CommonError, ExecutionContext, ExecutionError, ExecutionFail, InstanceId,
+    },
 };
 use exonum_derive::{ServiceDispatcher, ServiceFactory};
-use exonum_rust_runtime::{api::ServiceApiBuilder, Service};
+use exonum_rust_runtime::{api::ServiceApiBuilder, Service, ServiceFactory};
 use exonum_supervisor::Configure;
+use tracing::warn;
 
 use crate::{
     api,
-    blockchain::{BtcAnchoringInterface, Schema},
+    blockchain::{
+        data_layout::{AnchoringIntervalChange, ConfigChange},
+        errors::Error,
+        signatures::SighashCache,
+        BtcAnchoringInterface, Schema,
+    },
+    btc::Sha256d,
     config::Config,
     proto,
 };
 
 /// Bitcoin anchoring service implementation for the Exonum blockchain.
-#[derive(ServiceFactory, ServiceDispatcher, Debug, Clone, Copy)]
+#[derive(ServiceFactory, ServiceDispatcher, Debug, Clone, Default)]
 #[service_dispatcher(implements("BtcAnchoringInterface", raw = "Configure<Params = Config>"))]
-#[service_factory(proto_sources = "proto")]
-pub struct BtcAnchoringService;
+#[service_factory(
+    proto_sources = "proto",
+    service_constructor = "BtcAnchoringService::create_instance"
+)]
+pub struct BtcAnchoringService {
+    api_signing_keypair: Option<KeyPair>,
+    api_config: api::ApiConfig,
+    pub(crate) sighash_cache: SighashCache,
+}
+
+/// Default identifier of the anchoring service instance.
+pub const DEFAULT_INSTANCE_ID: InstanceId = 14;
+/// Default name of the anchoring service instance.
+pub const DEFAULT_INSTANCE_NAME: &str = "btc_anchoring";
+
+impl BtcAnchoringService {
+    /// Creates an anchoring service which signs the responses of the proof endpoints
+    /// with the given key pair, usually the service key pair of the node. Signed
+    /// responses can be relayed through untrusted proxies and still be attributed
+    /// to the node.
+    pub fn with_api_signing_key(keypair: KeyPair) -> Self {
+        Self {
+            api_signing_keypair: Some(keypair),
+            ..Self::default()
+        }
+    }
+
+    /// Sets the limits applied to the requests of the service HTTP API.
+    pub fn with_api_config(mut self, config: api::ApiConfig) -> Self {
+        self.api_config = config;
+        self
+    }
+
+    fn create_instance(&self) -> Box<dyn Service> {
+        Box::new(self.clone())
+    }
+
+    /// Returns the initialization parameters of the anchoring service instance with
+    /// the default identifier and name, which can be used in the genesis config.
+    ///
+    /// Use [`Config::with_public_keys`] to create a configuration with the default
+    /// parameters for the given set of anchoring keys.
+    ///
+    /// [`Config::with_public_keys`]: config/struct.Config.html#method.with_public_keys
+    pub fn default_instance(config: Config) -> InstanceInitParams {
+        InstanceInitParams::new(
+            DEFAULT_INSTANCE_ID,
+            DEFAULT_INSTANCE_NAME,
+            Self::default().artifact_id(),
+            config,
+        )
+    }
+}
 
 impl Service for BtcAnchoringService {
     fn initialize(
@@ -45,17 +112,97 @@ impl Service for BtcAnchoringService {
             .and_then(ValidateInput::into_validated)
             .map_err(CommonError::malformed_arguments)?;
 
-        Schema::new(context.service_data())
-            .actual_config
-            .set(config);
+        let height = context.data().for_core().next_height();
+        let mut schema = Schema::new(context.service_data());
+        schema.frozen.set(config.frozen);
+        schema.set_actual_config(config, height);
+        Ok(())
+    }
+
+    fn after_transactions(&self, context: ExecutionContext<'_>) -> Result<(), ExecutionError> {
+        let height = context.data().for_core().next_height();
+        Schema::new(context.service_data()).update_proposal(context.data().for_core(), height);
         Ok(())
     }
 
     fn wire_api(&self, builder: &mut ServiceApiBuilder) {
-        api::wire(builder);
+        api::wire(builder, self.api_signing_keypair.as_ref(), self.api_config);
     }
 }
 
+/// Data of the anchoring service can be migrated only from the `1.x` releases.
+///
+/// Deployments of the LECT-based anchoring service of the Exonum `0.x` releases cannot
+/// be upgraded in place, since the `0.x` nodes keep the data in the database format
+/// which is incompatible with the Exonum `1.x` nodes. Such deployments should launch
+/// a new anchoring chain and transfer the funds to its address instead.
+impl MigrateData for BtcAnchoringService {
+    fn migration_scripts(
+        &self,
+        start_version: &Version,
+    ) -> Result<Vec<MigrationScript>, InitMigrationError> {
+        let latest_version = env!("CARGO_PKG_VERSION").parse().unwrap();
+        LinearMigrations::new(latest_version)
+            .set_min_version(Version::new(1, 0, 0))
+            .add_script(Version::new(1, 1, 0), migrate_to_1_1)
+            .select(start_version)
+    }
+}
+
+/// Migrates the data of the `1.0` releases, which keep only the anchoring chain itself,
+/// the funding transactions and the configuration.
+///
+/// The indices of the anchoring chain, the funding transactions spenders and the aggregate
+/// statistics are rebuilt from the anchoring transactions. The migration fails if the inputs
+/// of some anchoring transaction are neither in the chain nor among the spent funding
+/// transactions. The latencies of the transactions
+/// anchored before the migration are unknown, so they are not accounted in the statistics.
+/// The anchoring schedule and the configuration history start with the actual configuration.
+fn migrate_to_1_1(ctx: &mut MigrationContext) -> Result<(), MigrationError> {
+    let old_schema = Schema::new(ctx.helper.old_data());
+    let mut new_schema = Schema::new(ctx.helper.new_data());
+
+    for tx in &old_schema.transactions_chain {
+        let index = new_schema.transactions_chain.len();
+        // Inputs of the anchoring transactions are always known to the service, so the data
+        // is corrupted otherwise.
+        let inputs =
+            tx.0.input
+                .iter()
+                .map(|input| {
+                    let txid = Sha256d::from(input.previous_output.txid);
+                    if let Some(funding_tx) = old_schema.spent_funding_transactions.get(&txid) {
+                        new_schema.funding_transaction_spenders.put(&txid, index);
+                        Some(funding_tx)
+                    } else {
+                        new_schema
+                            .transaction_index(&txid)
+                            .and_then(|index| new_schema.transactions_chain.get(index))
+                    }
+                })
+                .collect::<Option<Vec<_>>>()
+                .ok_or_else(|| {
+                    MigrationError::new(format!(
+                        "Unable to find the inputs of the anchoring transaction {}",
+                        tx.id()
+                    ))
+                })?;
+        new_schema.update_anchoring_stats(&tx, &inputs);
+        new_schema.append_anchoring_transaction(tx);
+    }
+
+    let config = old_schema.actual_config();
+    new_schema.anchoring_schedule.push(AnchoringIntervalChange {
+        effective_from: 0,
+        interval: config.anchoring_interval,
+    });
+    new_schema.config_history.push(ConfigChange {
+        actual_from: 0,
+        config,
+    });
+    Ok(())
+}
+
 impl Configure for BtcAnchoringService {
     type Params = Config;
 
@@ -69,7 +216,48 @@ impl Configure for BtcAnchoringService {
             .as_supervisor()
             .ok_or(CommonError::UnauthorizedCaller)?;
 
-        params.validate().map_err(CommonError::malformed_arguments)
+        params
+            .validate()
+            .map_err(CommonError::malformed_arguments)?;
+        if params.validators_only {
+            let validator_keys = context.data().for_core().consensus_config().validator_keys;
+            if let Some(keys) = params.anchoring_keys.iter().find(|keys| {
+                !validator_keys
+                    .iter()
+                    .any(|validator| validator.service_key == keys.service_key)
+            }) {
+                return Err(Error::NonValidatorAnchoringKey.with_description(format!(
+                    "Service key {} does not belong to any validator",
+                    keys.service_key
+                )));
+            }
+        }
+        // The already deployed configurations may have the keys in the arbitrary order, but
+        // the new anchoring address should be derived from the keys in the canonical order.
+        let actual_config = Schema::new(context.service_data()).actual_config();
+        if params.anchoring_address() != actual_config.anchoring_address()
+            && !params.has_sorted_anchoring_keys()
+        {
+            return Err(Error::UnsortedAnchoringKeys.with_description(
+                "Anchoring keys must be sorted in the lexicographical order of the Bitcoin keys",
+            ));
+        }
+        // The transition transaction is signed by the actual anchoring nodes, so the remaining
+        // nodes should be able to reach the quorum without the excluded ones.
+        let remaining_keys = actual_config
+            .anchoring_keys
+            .iter()
+            .filter(|keys| !params.excluded_keys.contains(&keys.bitcoin_key))
+            .count();
+        if remaining_keys < actual_config.byzantine_quorum() {
+            return Err(Error::InsufficientAnchoringKeys.with_description(format!(
+                "Only {} of {} anchoring nodes remain after the exclusion, but {} are required",
+                remaining_keys,
+                actual_config.anchoring_keys.len(),
+                actual_config.byzantine_quorum()
+            )));
+        }
+        Ok(())
     }
 
     fn apply_config(
@@ -83,14 +271,28 @@ impl Configure for BtcAnchoringService {
             .ok_or(CommonError::UnauthorizedCaller)?;
 
         let mut schema = Schema::new(context.service_data());
+        // Freeze flag is applied immediately regardless of the anchoring address change.
+        if schema.is_frozen() != params.frozen {
+            warn!(
+                "Anchoring has been {}.",
+                if params.frozen { "frozen" } else { "unfrozen" }
+            );
+        }
+        schema.frozen.set(params.frozen);
+        // Change of the anchoring interval takes effect from the following anchoring
+        // height, so the already scheduled anchored heights are not affected.
+        schema.update_anchoring_interval(
+            context.data().for_core().next_height(),
+            params.anchoring_interval,
+        );
         if schema.actual_config().anchoring_address() == params.anchoring_address() {
             // There are no changes in the anchoring address, so we just apply the config
             // immediately.
-            schema.actual_config.set(params);
+            schema.set_actual_config(params, context.data().for_core().next_height());
         } else {
             // Set the config as the next one, which will become an actual after the transition
             // of the anchoring chain to the following address.
-            schema.following_config.set(params);
+            schema.start_transition(params, context.data().for_core().next_height());
         }
         Ok(())
     }
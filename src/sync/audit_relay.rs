@@ -0,0 +1,291 @@
+// Copyright 2020 The Exonum Team
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Audit log of the Bitcoin relay calls.
+
+use async_trait::async_trait;
+use exonum::{
+    crypto::{self, Hash},
+    merkledb::BinaryValue,
+};
+use serde_derive::{Deserialize, Serialize};
+
+use std::{
+    fmt::Display,
+    fs::{self, File, OpenOptions},
+    future::Future,
+    io::Write,
+    path::{Path, PathBuf},
+    sync::{Arc, Mutex},
+    time::{Instant, SystemTime, UNIX_EPOCH},
+};
+
+use super::{BitcoinRelay, TransactionStatus};
+use crate::btc;
+
+/// Record of a single Bitcoin relay call.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RelayAuditRecord {
+    /// Time of the call in milliseconds since the Unix epoch.
+    pub timestamp: u64,
+    /// Name of the called `BitcoinRelay` method.
+    pub method: String,
+    /// Identifier of the transaction the call refers to, if any.
+    pub txid: Option<btc::Sha256d>,
+    /// Hash of the call arguments, e.g. of the raw transaction sent to the relay.
+    pub payload_hash: Hash,
+    /// Description of the relay response, if the call has succeeded.
+    pub response: Option<String>,
+    /// Error of the relay, if the call has failed.
+    pub error: Option<String>,
+    /// Duration of the call in milliseconds.
+    pub latency: u64,
+}
+
+/// Append-only file with the records of the Bitcoin relay calls, one JSON object per line.
+///
+/// The default log is disabled and drops the records, which allows to keep the relay
+/// type the same whether the audit is enabled or not.
+#[derive(Debug, Clone, Default)]
+pub struct RelayAuditLog {
+    path: Option<PathBuf>,
+    file: Option<Arc<Mutex<File>>>,
+}
+
+impl RelayAuditLog {
+    /// Opens the log file for appending, creating it if necessary.
+    pub fn open(path: impl Into<PathBuf>) -> anyhow::Result<Self> {
+        let path = path.into();
+        let file = OpenOptions::new().create(true).append(true).open(&path)?;
+        Ok(Self {
+            path: Some(path),
+            file: Some(Arc::new(Mutex::new(file))),
+        })
+    }
+
+    /// Returns the path of the log file, or `None` if the log is disabled.
+    pub fn path(&self) -> Option<&Path> {
+        self.path.as_deref()
+    }
+
+    /// Appends the record to the log.
+    pub fn append(&self, record: &RelayAuditRecord) -> anyhow::Result<()> {
+        if let Some(file) = &self.file {
+            let mut line = serde_json::to_vec(record)?;
+            line.push(b'\n');
+            // The whole line is written at once, so the records of the concurrent
+            // calls don't interleave.
+            file.lock().unwrap().write_all(&line)?;
+        }
+        Ok(())
+    }
+
+    /// Reads all records from the log file.
+    pub fn read(path: impl AsRef<Path>) -> anyhow::Result<Vec<RelayAuditRecord>> {
+        fs::read_to_string(path)?
+            .lines()
+            .filter(|line| !line.is_empty())
+            .map(|line| serde_json::from_str(line).map_err(From::from))
+            .collect()
+    }
+}
+
+/// Bitcoin relay which records every call of the underlying relay, including its
+/// response, latency and error, to the audit log.
+///
+/// The audit log allows to find out after an incident what the Bitcoin relay has
+/// answered about the anchoring transaction, e.g. whether the transaction has been
+/// accepted by the relay at all.
+#[derive(Debug, Clone)]
+pub struct AuditRelay<R> {
+    inner: R,
+    log: RelayAuditLog,
+}
+
+impl<R> AuditRelay<R>
+where
+    R: BitcoinRelay,
+    R::Error: Display,
+{
+    /// Creates a relay which records the calls of the given relay to the log.
+    pub fn new(inner: R, log: RelayAuditLog) -> Self {
+        Self { inner, log }
+    }
+
+    /// Returns the underlying Bitcoin relay.
+    pub fn inner(&self) -> &R {
+        &self.inner
+    }
+
+    async fn audit<T, F>(
+        &self,
+        method: &str,
+        txid: Option<btc::Sha256d>,
+        payload: &[u8],
+        call: F,
+    ) -> Result<T, R::Error>
+    where
+        T: AuditResponse,
+        F: Future<Output = Result<T, R::Error>>,
+    {
+        let timestamp = SystemTime::now();
+        let start = Instant::now();
+        let result = call.await;
+        let record = RelayAuditRecord {
+            timestamp: timestamp
+                .duration_since(UNIX_EPOCH)
+                .map_or(0, |time| time.as_millis() as u64),
+            method: method.to_owned(),
+            txid,
+            payload_hash: crypto::hash(payload),
+            response: result.as_ref().ok().map(AuditResponse::describe),
+            error: result.as_ref().err().map(ToString::to_string),
+            latency: start.elapsed().as_millis() as u64,
+        };
+        // Failure of the audit doesn't affect the anchoring.
+        if let Err(e) = self.log.append(&record) {
+            tracing::warn!(error = %e, "Unable to write the Bitcoin relay audit record.");
+        }
+        result
+    }
+}
+
+/// Description of the relay response in the audit record.
+trait AuditResponse {
+    fn describe(&self) -> String;
+}
+
+impl AuditResponse for btc::Sha256d {
+    fn describe(&self) -> String {
+        self.to_string()
+    }
+}
+
+impl AuditResponse for TransactionStatus {
+    fn describe(&self) -> String {
+        format!("{:?}", self)
+    }
+}
+
+impl AuditResponse for Option<btc::MerkleBlock> {
+    fn describe(&self) -> String {
+        self.as_ref().map_or_else(
+            || "None".to_owned(),
+            |proof| format!("MerkleBlock({})", proof.header().block_hash()),
+        )
+    }
+}
+
+impl AuditResponse for Option<btc::Transaction> {
+    fn describe(&self) -> String {
+        self.as_ref()
+            .map_or_else(|| "None".to_owned(), ToString::to_string)
+    }
+}
+
+impl AuditResponse for Option<bool> {
+    fn describe(&self) -> String {
+        format!("{:?}", self)
+    }
+}
+
+impl AuditResponse for Option<u64> {
+    fn describe(&self) -> String {
+        format!("{:?}", self)
+    }
+}
+
+#[async_trait]
+impl<R> BitcoinRelay for AuditRelay<R>
+where
+    R: BitcoinRelay + Send + Sync,
+    R::Error: Display + Send,
+{
+    type Error = R::Error;
+
+    async fn send_transaction(
+        &self,
+        transaction: &btc::Transaction,
+    ) -> Result<btc::Sha256d, Self::Error> {
+        self.audit(
+            "send_transaction",
+            Some(transaction.id()),
+            &transaction.to_bytes(),
+            self.inner.send_transaction(transaction),
+        )
+        .await
+    }
+
+    async fn transaction_status(&self, id: btc::Sha256d) -> Result<TransactionStatus, Self::Error> {
+        self.audit(
+            "transaction_status",
+            Some(id),
+            &id.to_internal_bytes(),
+            self.inner.transaction_status(id),
+        )
+        .await
+    }
+
+    async fn transaction_proof(
+        &self,
+        id: btc::Sha256d,
+    ) -> Result<Option<btc::MerkleBlock>, Self::Error> {
+        self.audit(
+            "transaction_proof",
+            Some(id),
+            &id.to_internal_bytes(),
+            self.inner.transaction_proof(id),
+        )
+        .await
+    }
+
+    async fn raw_transaction(
+        &self,
+        id: btc::Sha256d,
+    ) -> Result<Option<btc::Transaction>, Self::Error> {
+        self.audit(
+            "raw_transaction",
+            Some(id),
+            &id.to_internal_bytes(),
+            self.inner.raw_transaction(id),
+        )
+        .await
+    }
+
+    async fn is_output_spent(
+        &self,
+        txid: btc::Sha256d,
+        vout: u32,
+    ) -> Result<Option<bool>, Self::Error> {
+        let mut payload = txid.to_internal_bytes().to_vec();
+        payload.extend_from_slice(&vout.to_le_bytes());
+        self.audit(
+            "is_output_spent",
+            Some(txid),
+            &payload,
+            self.inner.is_output_spent(txid, vout),
+        )
+        .await
+    }
+
+    async fn fee_rate(&self, target_blocks: u16) -> Result<Option<u64>, Self::Error> {
+        self.audit(
+            "fee_rate",
+            None,
+            &target_blocks.to_le_bytes(),
+            self.inner.fee_rate(target_blocks),
+        )
+        .await
+    }
+}
@@ -0,0 +1,319 @@
+// Copyright 2020 The Exonum Team
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Notifications about new Bitcoin blocks and transactions published by the Bitcoin Core
+//! node via ZMQ.
+//!
+//! Only the subset of the ZMTP 3.0 protocol required to subscribe to the `bitcoind`
+//! publisher over TCP with the `NULL` security mechanism is implemented.
+
+use futures::future::{AbortHandle, Abortable};
+use serde_derive::{Deserialize, Serialize};
+use tokio::{
+    io::{AsyncReadExt, AsyncWriteExt},
+    net::TcpStream,
+    sync::mpsc,
+    time::delay_for,
+};
+
+use std::{
+    convert::TryFrom,
+    io::{self, ErrorKind},
+    time::Duration,
+};
+
+use crate::btc;
+
+/// Topic of the notifications about the new blocks.
+const RAW_BLOCK_TOPIC: &str = "rawblock";
+/// Topic of the notifications about the new transactions.
+const RAW_TX_TOPIC: &str = "rawtx";
+/// Maximum size of the received frame, which is much more than the maximum block size.
+const MAX_FRAME_SIZE: u64 = 64 * 1024 * 1024;
+
+const FLAG_MORE: u8 = 0x01;
+const FLAG_LONG: u8 = 0x02;
+const FLAG_COMMAND: u8 = 0x04;
+
+/// Configuration of the Bitcoin Core ZMQ notifications.
+///
+/// Endpoints correspond to the `zmqpubrawblock` and `zmqpubrawtx` options of the
+/// Bitcoin Core node, e.g. `tcp://127.0.0.1:28332`. Only TCP endpoints are supported.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct ZmqNotificationsConfig {
+    /// Endpoint of the notifications about the new blocks.
+    #[serde(default)]
+    pub rawblock: Option<String>,
+    /// Endpoint of the notifications about the new transactions.
+    #[serde(default)]
+    pub rawtx: Option<String>,
+}
+
+/// Notification received from the Bitcoin node.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum BitcoinNotification {
+    /// New block with the given hash has been connected to the best chain.
+    Block(btc::Sha256d),
+    /// Transaction with the given identifier has been accepted to the mempool
+    /// or connected in a block.
+    Transaction(btc::Sha256d),
+}
+
+impl BitcoinNotification {
+    /// Decodes notification from the multipart message published by the Bitcoin node.
+    /// Returns `None` if the message has an unknown topic or is malformed.
+    fn from_message(parts: &[Vec<u8>]) -> Option<Self> {
+        let (topic, body) = match parts {
+            [topic, body, ..] => (topic.as_slice(), body.as_slice()),
+            _ => return None,
+        };
+
+        if topic == RAW_BLOCK_TOPIC.as_bytes() {
+            let (header, _) =
+                bitcoin::consensus::deserialize_partial::<bitcoin::BlockHeader>(body).ok()?;
+            Some(BitcoinNotification::Block(
+                btc::BlockHeader(header).block_hash(),
+            ))
+        } else if topic == RAW_TX_TOPIC.as_bytes() {
+            let transaction = bitcoin::consensus::deserialize::<bitcoin::Transaction>(body).ok()?;
+            Some(BitcoinNotification::Transaction(transaction.txid().into()))
+        } else {
+            None
+        }
+    }
+}
+
+/// ZMQ subscriber socket connected to the single publisher.
+#[derive(Debug)]
+pub struct ZmqSubscriber {
+    stream: TcpStream,
+}
+
+impl ZmqSubscriber {
+    /// Connects to the publisher on the given endpoint and subscribes to the given topic.
+    pub async fn connect(endpoint: &str, topic: &str) -> io::Result<Self> {
+        let address = endpoint.strip_prefix("tcp://").unwrap_or(endpoint);
+        if address.contains("://") {
+            return Err(io::Error::new(
+                ErrorKind::InvalidInput,
+                format!("Unsupported ZMQ endpoint: {}", endpoint),
+            ));
+        }
+
+        let mut subscriber = Self {
+            stream: TcpStream::connect(address).await?,
+        };
+        subscriber.handshake().await?;
+        // Subscription is a message which consists of the `0x01` byte and the topic.
+        let mut subscription = vec![0x01];
+        subscription.extend_from_slice(topic.as_bytes());
+        subscriber.write_frame(0, &subscription).await?;
+        Ok(subscriber)
+    }
+
+    /// Receives the next multipart message from the publisher.
+    pub async fn receive(&mut self) -> io::Result<Vec<Vec<u8>>> {
+        let mut parts = Vec::new();
+        loop {
+            let (flags, body) = self.read_frame().await?;
+            // Commands may be sent by the publisher at any time, but none of them
+            // is meaningful for the subscriber.
+            if flags & FLAG_COMMAND != 0 {
+                continue;
+            }
+            parts.push(body);
+            if flags & FLAG_MORE == 0 {
+                return Ok(parts);
+            }
+        }
+    }
+
+    async fn handshake(&mut self) -> io::Result<()> {
+        let mut greeting = [0_u8; 64];
+        greeting[0] = 0xFF;
+        greeting[9] = 0x7F;
+        // Protocol version 3.0.
+        greeting[10] = 3;
+        greeting[12..16].copy_from_slice(b"NULL");
+        self.stream.write_all(&greeting).await?;
+
+        let mut peer_greeting = [0_u8; 64];
+        self.stream.read_exact(&mut peer_greeting).await?;
+        if peer_greeting[0] != 0xFF || peer_greeting[9] != 0x7F || peer_greeting[10] < 3 {
+            return Err(invalid_data("Peer does not support ZMTP 3.0"));
+        }
+        if &peer_greeting[12..17] != b"NULL\0" {
+            return Err(invalid_data("Peer requires unsupported security mechanism"));
+        }
+
+        let mut ready = Vec::new();
+        push_short_string(&mut ready, "READY");
+        push_short_string(&mut ready, "Socket-Type");
+        ready.extend_from_slice(&3_u32.to_be_bytes());
+        ready.extend_from_slice(b"SUB");
+        self.write_frame(FLAG_COMMAND, &ready).await?;
+
+        let (flags, body) = self.read_frame().await?;
+        if flags & FLAG_COMMAND == 0 || !body.starts_with(b"\x05READY") {
+            return Err(invalid_data("Peer has not sent the READY command"));
+        }
+        Ok(())
+    }
+
+    async fn write_frame(&mut self, flags: u8, body: &[u8]) -> io::Result<()> {
+        let mut frame = Vec::with_capacity(body.len() + 9);
+        match u8::try_from(body.len()) {
+            Ok(len) => frame.extend_from_slice(&[flags, len]),
+            Err(_) => {
+                frame.push(flags | FLAG_LONG);
+                frame.extend_from_slice(&(body.len() as u64).to_be_bytes());
+            }
+        }
+        frame.extend_from_slice(body);
+        self.stream.write_all(&frame).await
+    }
+
+    async fn read_frame(&mut self) -> io::Result<(u8, Vec<u8>)> {
+        let flags = self.stream.read_u8().await?;
+        let len = if flags & FLAG_LONG == 0 {
+            u64::from(self.stream.read_u8().await?)
+        } else {
+            self.stream.read_u64().await?
+        };
+        if len > MAX_FRAME_SIZE {
+            return Err(invalid_data("Frame is too large"));
+        }
+
+        let mut body = vec![0_u8; len as usize];
+        self.stream.read_exact(&mut body).await?;
+        Ok((flags, body))
+    }
+}
+
+fn push_short_string(buffer: &mut Vec<u8>, s: &str) {
+    buffer.push(s.len() as u8);
+    buffer.extend_from_slice(s.as_bytes());
+}
+
+fn invalid_data(message: &str) -> io::Error {
+    io::Error::new(ErrorKind::InvalidData, message)
+}
+
+/// Stream of the notifications about new Bitcoin blocks and transactions.
+///
+/// Each configured endpoint is served by a separate task which reconnects to the
+/// Bitcoin node after the given interval if the connection is lost or cannot be
+/// established. ZMQ doesn't guarantee the delivery of notifications, so this stream
+/// should only speed up the reaction to events, while the periodic polling
+/// of the Bitcoin node remains the fallback.
+///
+/// Tasks are stopped when the stream is dropped.
+#[derive(Debug)]
+pub struct BitcoinNotifications {
+    receiver: mpsc::Receiver<BitcoinNotification>,
+    // Keeps the channel open even if there are no configured endpoints.
+    _sender: mpsc::Sender<BitcoinNotification>,
+    abort_handles: Vec<AbortHandle>,
+}
+
+impl BitcoinNotifications {
+    /// Spawns the subscription tasks for the configured endpoints. This method must
+    /// be called within the Tokio runtime.
+    pub fn spawn(config: &ZmqNotificationsConfig, reconnect_interval: Duration) -> Self {
+        let (sender, receiver) = mpsc::channel(16);
+        let endpoints = [
+            (&config.rawblock, RAW_BLOCK_TOPIC),
+            (&config.rawtx, RAW_TX_TOPIC),
+        ];
+
+        let abort_handles = endpoints
+            .iter()
+            .filter_map(|(endpoint, topic)| endpoint.as_ref().map(|endpoint| (endpoint, topic)))
+            .map(|(endpoint, topic)| {
+                let (abort_handle, registration) = AbortHandle::new_pair();
+                let task = subscribe(endpoint.clone(), topic, sender.clone(), reconnect_interval);
+                tokio::spawn(Abortable::new(task, registration));
+                abort_handle
+            })
+            .collect();
+
+        Self {
+            receiver,
+            _sender: sender,
+            abort_handles,
+        }
+    }
+
+    /// Waits for the next notification. If there are no configured endpoints,
+    /// the returned future never resolves.
+    pub async fn next(&mut self) -> BitcoinNotification {
+        // The channel cannot be closed since its sender is kept by this stream.
+        self.receiver
+            .recv()
+            .await
+            .expect("Notifications channel is closed")
+    }
+}
+
+impl Drop for BitcoinNotifications {
+    fn drop(&mut self) {
+        for handle in &self.abort_handles {
+            handle.abort();
+        }
+    }
+}
+
+async fn subscribe(
+    endpoint: String,
+    topic: &'static str,
+    mut sender: mpsc::Sender<BitcoinNotification>,
+    reconnect_interval: Duration,
+) {
+    loop {
+        match ZmqSubscriber::connect(&endpoint, topic).await {
+            Ok(mut subscriber) => {
+                tracing::info!("Subscribed to `{}` notifications on {}", topic, endpoint);
+                let e = loop {
+                    let message = match subscriber.receive().await {
+                        Ok(message) => message,
+                        Err(e) => break e,
+                    };
+                    if let Some(notification) = BitcoinNotification::from_message(&message) {
+                        // Notifications only trigger the sync, so the ones which have not
+                        // been handled yet make the new ones redundant.
+                        if let Err(mpsc::error::TrySendError::Closed(_)) =
+                            sender.try_send(notification)
+                        {
+                            return;
+                        }
+                    }
+                };
+                tracing::warn!(
+                    "Connection to the `{}` notifications on {} is lost. {}",
+                    topic,
+                    endpoint,
+                    e
+                );
+            }
+            Err(e) => tracing::warn!(
+                "Unable to subscribe to the `{}` notifications on {}, \
+                 only polling will be used. {}",
+                topic,
+                endpoint,
+                e
+            ),
+        }
+        delay_for(reconnect_interval).await;
+    }
+}
@@ -15,8 +15,12 @@
 //! Collections of helpers for synchronization with the Bitcoin network.
 
 use async_trait::async_trait;
-use bitcoincore_rpc::RpcApi;
+use bitcoincore_rpc::{json::EstimateSmartFeeResult, RpcApi};
+use futures::future::join_all;
 use jsonrpc::Error as JsonRpcError;
+use thiserror::Error;
+
+use std::fmt::Display;
 
 use crate::btc;
 
@@ -60,8 +64,58 @@ pub trait BitcoinRelay {
     ) -> Result<btc::Sha256d, Self::Error>;
     /// Gets status for the transaction with the specified identifier.
     async fn transaction_status(&self, id: btc::Sha256d) -> Result<TransactionStatus, Self::Error>;
+    /// Gets the proof of inclusion in a Bitcoin block for the transaction with the specified
+    /// identifier, if the transaction is committed.
+    async fn transaction_proof(
+        &self,
+        id: btc::Sha256d,
+    ) -> Result<Option<btc::MerkleBlock>, Self::Error>;
+    /// Gets the transaction with the specified identifier as it is known in the Bitcoin
+    /// network, including the witness data.
+    async fn raw_transaction(
+        &self,
+        id: btc::Sha256d,
+    ) -> Result<Option<btc::Transaction>, Self::Error>;
+    /// Checks whether the output of the transaction with the specified identifier is spent
+    /// by a transaction known to the relay, including the memory pool ones. Returns `None`
+    /// if the transaction is unknown.
+    async fn is_output_spent(
+        &self,
+        txid: btc::Sha256d,
+        vout: u32,
+    ) -> Result<Option<bool>, Self::Error>;
+    /// Estimates the fee per byte in satoshis required for the transaction to be committed
+    /// within the given number of blocks. Returns `None` if the Bitcoin node doesn't have
+    /// enough data for the estimation.
+    async fn fee_rate(&self, target_blocks: u16) -> Result<Option<u64>, Self::Error>;
 }
 
+/// Converts the fee rate in BTC per kilobyte, as it is returned by the Bitcoin nodes,
+/// to the satoshis per byte. Non-positive rates mean that the fee cannot be estimated.
+pub(super) fn fee_rate_from_btc_per_kb(rate: f64) -> Option<u64> {
+    if rate <= 0.0 {
+        return None;
+    }
+    let sat_per_kb = bitcoin::Amount::from_btc(rate).ok()?.as_sat();
+    Some((sat_per_kb + 999) / 1000)
+}
+
+/// Estimates the fee rate via the `estimatesmartfee` RPC of the Bitcoin Core node.
+pub(super) fn rpc_fee_rate(
+    client: &impl RpcApi,
+    target_blocks: u16,
+) -> bitcoincore_rpc::Result<Option<u64>> {
+    let estimation: EstimateSmartFeeResult =
+        client.call("estimatesmartfee", &[target_blocks.into()])?;
+    Ok(estimation
+        .feerate
+        .and_then(|rate| rate.as_f64())
+        .and_then(fee_rate_from_btc_per_kb))
+}
+
+/// Blocking implementation, which stalls the runtime thread until the RPC request
+/// is completed. Use [`BitcoindRelay`](struct.BitcoindRelay.html) to drive several
+/// sync tasks from the single runtime.
 #[async_trait]
 impl BitcoinRelay for bitcoincore_rpc::Client {
     type Error = bitcoincore_rpc::Error;
@@ -90,4 +144,230 @@ impl BitcoinRelay for bitcoincore_rpc::Client {
             Err(e) => Err(e),
         }
     }
+
+    async fn transaction_proof(
+        &self,
+        id: btc::Sha256d,
+    ) -> Result<Option<btc::MerkleBlock>, Self::Error> {
+        match self.get_tx_out_proof(&[id.into()], None) {
+            Ok(bytes) => bitcoin::consensus::deserialize(&bytes)
+                .map(|merkle_block| Some(btc::MerkleBlock(merkle_block)))
+                .map_err(bitcoincore_rpc::Error::BitcoinSerialization),
+            // The transaction is not yet in a block.
+            Err(bitcoincore_rpc::Error::JsonRpc(JsonRpcError::Rpc(_))) => Ok(None),
+            Err(e) => Err(e),
+        }
+    }
+
+    async fn raw_transaction(
+        &self,
+        id: btc::Sha256d,
+    ) -> Result<Option<btc::Transaction>, Self::Error> {
+        match self.get_raw_transaction(&id.into(), None) {
+            Ok(transaction) => Ok(Some(transaction.into())),
+            // The transaction is unknown.
+            Err(bitcoincore_rpc::Error::JsonRpc(JsonRpcError::Rpc(_))) => Ok(None),
+            Err(e) => Err(e),
+        }
+    }
+
+    async fn is_output_spent(
+        &self,
+        txid: btc::Sha256d,
+        vout: u32,
+    ) -> Result<Option<bool>, Self::Error> {
+        if self.get_tx_out(&txid.into(), vout, Some(true))?.is_some() {
+            return Ok(Some(false));
+        }
+        // The output is absent in the UTXO set, either if it is spent or if
+        // the transaction is unknown.
+        let is_known = self.transaction_status(txid).await?.is_known();
+        Ok(if is_known { Some(true) } else { None })
+    }
+
+    async fn fee_rate(&self, target_blocks: u16) -> Result<Option<u64>, Self::Error> {
+        rpc_fee_rate(self, target_blocks)
+    }
+}
+
+#[async_trait]
+impl<T> BitcoinRelay for Box<T>
+where
+    T: BitcoinRelay + Send + Sync + ?Sized,
+{
+    type Error = T::Error;
+
+    async fn send_transaction(
+        &self,
+        transaction: &btc::Transaction,
+    ) -> Result<btc::Sha256d, Self::Error> {
+        self.as_ref().send_transaction(transaction).await
+    }
+
+    async fn transaction_status(&self, id: btc::Sha256d) -> Result<TransactionStatus, Self::Error> {
+        self.as_ref().transaction_status(id).await
+    }
+
+    async fn transaction_proof(
+        &self,
+        id: btc::Sha256d,
+    ) -> Result<Option<btc::MerkleBlock>, Self::Error> {
+        self.as_ref().transaction_proof(id).await
+    }
+
+    async fn raw_transaction(
+        &self,
+        id: btc::Sha256d,
+    ) -> Result<Option<btc::Transaction>, Self::Error> {
+        self.as_ref().raw_transaction(id).await
+    }
+
+    async fn is_output_spent(
+        &self,
+        txid: btc::Sha256d,
+        vout: u32,
+    ) -> Result<Option<bool>, Self::Error> {
+        self.as_ref().is_output_spent(txid, vout).await
+    }
+
+    async fn fee_rate(&self, target_blocks: u16) -> Result<Option<u64>, Self::Error> {
+        self.as_ref().fee_rate(target_blocks).await
+    }
+}
+
+/// Errors of the [`BroadcastAllRelay`](struct.BroadcastAllRelay.html).
+#[derive(Debug, Error)]
+pub enum BroadcastError<E: Display> {
+    /// There are no underlying Bitcoin relays.
+    #[error("No Bitcoin relays are specified")]
+    NoRelays,
+    /// All of the underlying Bitcoin relays have failed, the errors are in the order
+    /// of the relays.
+    #[error("All Bitcoin relays have failed, the first error: {}", .0[0])]
+    AllFailed(Vec<E>),
+}
+
+/// Bitcoin relay which sends the transactions to all of the underlying relays
+/// simultaneously and succeeds if any of them accepts the transaction.
+///
+/// Requests for the transaction status and proof are also sent to all relays,
+/// and the most advanced answer is returned, so a single relay which has not yet
+/// seen the transaction doesn't stall the anchoring. Relays of different kinds
+/// can be combined as the boxed trait objects with the common error type.
+#[derive(Debug, Clone)]
+pub struct BroadcastAllRelay<R> {
+    relays: Vec<R>,
+}
+
+impl<R: BitcoinRelay> BroadcastAllRelay<R> {
+    /// Creates a relay over the given Bitcoin relays.
+    pub fn new(relays: impl IntoIterator<Item = R>) -> Self {
+        Self {
+            relays: relays.into_iter().collect(),
+        }
+    }
+
+    /// Returns the underlying Bitcoin relays.
+    pub fn relays(&self) -> &[R] {
+        &self.relays
+    }
+}
+
+/// Returns the successful results of the given relay responses or all the errors
+/// if there are no successful responses.
+fn successful<T, E: Display>(responses: Vec<Result<T, E>>) -> Result<Vec<T>, BroadcastError<E>> {
+    if responses.is_empty() {
+        return Err(BroadcastError::NoRelays);
+    }
+
+    let (ok, errors): (Vec<_>, Vec<_>) = responses.into_iter().partition(Result::is_ok);
+    if ok.is_empty() {
+        return Err(BroadcastError::AllFailed(
+            errors.into_iter().filter_map(Result::err).collect(),
+        ));
+    }
+    for e in errors.into_iter().filter_map(Result::err) {
+        tracing::warn!("Bitcoin relay request failed: {}", e);
+    }
+    Ok(ok.into_iter().filter_map(Result::ok).collect())
+}
+
+#[async_trait]
+impl<R> BitcoinRelay for BroadcastAllRelay<R>
+where
+    R: BitcoinRelay + Send + Sync,
+    R::Error: Display + Send,
+{
+    type Error = BroadcastError<R::Error>;
+
+    async fn send_transaction(
+        &self,
+        transaction: &btc::Transaction,
+    ) -> Result<btc::Sha256d, Self::Error> {
+        let responses = join_all(
+            self.relays
+                .iter()
+                .map(|relay| relay.send_transaction(transaction)),
+        )
+        .await;
+        Ok(successful(responses)?[0])
+    }
+
+    async fn transaction_status(&self, id: btc::Sha256d) -> Result<TransactionStatus, Self::Error> {
+        let responses =
+            join_all(self.relays.iter().map(|relay| relay.transaction_status(id))).await;
+        let status = successful(responses)?
+            .into_iter()
+            .max_by_key(|status| match status {
+                TransactionStatus::Unknown => (0, 0),
+                TransactionStatus::Mempool => (1, 0),
+                TransactionStatus::Committed(confirmations) => (2, *confirmations),
+            })
+            .unwrap();
+        Ok(status)
+    }
+
+    async fn transaction_proof(
+        &self,
+        id: btc::Sha256d,
+    ) -> Result<Option<btc::MerkleBlock>, Self::Error> {
+        let responses = join_all(self.relays.iter().map(|relay| relay.transaction_proof(id))).await;
+        Ok(successful(responses)?.into_iter().flatten().next())
+    }
+
+    async fn raw_transaction(
+        &self,
+        id: btc::Sha256d,
+    ) -> Result<Option<btc::Transaction>, Self::Error> {
+        let responses = join_all(self.relays.iter().map(|relay| relay.raw_transaction(id))).await;
+        Ok(successful(responses)?.into_iter().flatten().next())
+    }
+
+    /// Returns the most advanced answer, so the output is considered spent if any of
+    /// the relays has seen the spending transaction.
+    async fn is_output_spent(
+        &self,
+        txid: btc::Sha256d,
+        vout: u32,
+    ) -> Result<Option<bool>, Self::Error> {
+        let responses = join_all(
+            self.relays
+                .iter()
+                .map(|relay| relay.is_output_spent(txid, vout)),
+        )
+        .await;
+        Ok(successful(responses)?.into_iter().max().flatten())
+    }
+
+    /// Returns the highest of the estimated fee rates, so the transaction is not stuck
+    /// if some of the relays underestimate the fee.
+    async fn fee_rate(&self, target_blocks: u16) -> Result<Option<u64>, Self::Error> {
+        let responses = join_all(
+            self.relays
+                .iter()
+                .map(|relay| relay.fee_rate(target_blocks)),
+        )
+        .await;
+        Ok(successful(responses)?.into_iter().flatten().max())
+    }
 }
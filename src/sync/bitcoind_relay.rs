@@ -0,0 +1,471 @@
+// Copyright 2020 The Exonum Team
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Bitcoin relay over the Bitcoin Core JSON-RPC.
+
+use async_trait::async_trait;
+use bitcoincore_rpc::{Auth, Client, RpcApi};
+use jsonrpc::{error::RpcError, Error as JsonRpcError};
+use serde::de::DeserializeOwned;
+use serde_derive::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use thiserror::Error;
+
+use std::{
+    fs,
+    io::{self, ErrorKind, Read, Write},
+    path::PathBuf,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc,
+    },
+    time::Duration,
+};
+
+use super::{
+    bitcoin_relay::rpc_fee_rate,
+    funding_wallet::{wallet_balance, wallet_create_transaction, wallet_send_to_address},
+    recover_from_bitcoin::{rpc_transaction, rpc_unspent_transactions, BitcoinDataSource},
+    BitcoinRelay, FundingWallet, ProxyConfig, TransactionStatus,
+};
+use crate::btc;
+
+/// Configuration of the connection to the Bitcoin Core JSON-RPC.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct BitcoindRelayConfig {
+    /// Bitcoin RPC url, e.g. `http://localhost:18332`.
+    pub host: String,
+    /// Bitcoin RPC username.
+    #[serde(default)]
+    pub user: Option<String>,
+    /// Bitcoin RPC password.
+    #[serde(default)]
+    pub password: Option<String>,
+    /// Path to the Bitcoin Core cookie file, which is used instead of
+    /// the username and password if specified.
+    #[serde(default)]
+    pub cookie_file: Option<PathBuf>,
+    /// Maximum time in milliseconds to process a single request.
+    #[serde(default)]
+    pub request_timeout: Option<u64>,
+    /// SOCKS5 proxy to connect to the Bitcoin RPC through.
+    #[serde(default)]
+    pub proxy: Option<ProxyConfig>,
+}
+
+impl BitcoindRelayConfig {
+    /// Returns the authentication method for the Bitcoin RPC.
+    pub fn auth(&self) -> Auth {
+        if let Some(cookie_file) = &self.cookie_file {
+            Auth::CookieFile(cookie_file.clone())
+        } else if let Some(user) = &self.user {
+            Auth::UserPass(user.clone(), self.password.clone().unwrap_or_default())
+        } else {
+            Auth::None
+        }
+    }
+
+    /// Creates a blocking Bitcoin RPC client without the request timeout.
+    pub fn client(&self) -> Result<BitcoindRpcClient, bitcoincore_rpc::Error> {
+        let transport = match &self.proxy {
+            Some(proxy) => RpcTransport::Proxy(ProxyRpcClient::new(self, proxy.clone())?),
+            None => RpcTransport::Direct(Client::new(self.host.clone(), self.auth())?),
+        };
+        Ok(BitcoindRpcClient(transport))
+    }
+}
+
+/// Blocking Bitcoin RPC client, which connects to the node directly or via
+/// the SOCKS5 proxy.
+#[derive(Debug)]
+pub struct BitcoindRpcClient(RpcTransport);
+
+#[derive(Debug)]
+enum RpcTransport {
+    Direct(Client),
+    Proxy(ProxyRpcClient),
+}
+
+impl RpcApi for BitcoindRpcClient {
+    fn call<T: for<'a> serde::de::Deserialize<'a>>(
+        &self,
+        cmd: &str,
+        args: &[Value],
+    ) -> bitcoincore_rpc::Result<T> {
+        match &self.0 {
+            RpcTransport::Direct(client) => client.call(cmd, args),
+            RpcTransport::Proxy(client) => client.call(cmd, args),
+        }
+    }
+}
+
+/// JSON-RPC client over HTTP, which connects to the node via the SOCKS5 proxy.
+/// A new connection is established for each request.
+#[derive(Debug)]
+struct ProxyRpcClient {
+    proxy: ProxyConfig,
+    /// Target `host:port` of the Bitcoin RPC.
+    target: String,
+    path: String,
+    authorization: Option<String>,
+    nonce: AtomicU64,
+}
+
+impl ProxyRpcClient {
+    fn new(config: &BitcoindRelayConfig, proxy: ProxyConfig) -> bitcoincore_rpc::Result<Self> {
+        let url = config.host.trim_start_matches("http://");
+        let (target, path) = match url.find('/') {
+            Some(pos) => (&url[..pos], &url[pos..]),
+            None => (url, "/"),
+        };
+        let target = if target.contains(':') && !target.ends_with(']') {
+            target.to_owned()
+        } else {
+            format!("{}:80", target)
+        };
+
+        let credentials = match config.auth() {
+            Auth::None => None,
+            Auth::UserPass(user, password) => Some(format!("{}:{}", user, password)),
+            Auth::CookieFile(path) => {
+                let cookie = fs::read_to_string(path)?;
+                if !cookie.contains(':') {
+                    return Err(bitcoincore_rpc::Error::InvalidCookieFile);
+                }
+                Some(cookie.trim().to_owned())
+            }
+        };
+        Ok(Self {
+            proxy,
+            target,
+            path: path.to_owned(),
+            authorization: credentials.map(|credentials| base64::encode(&credentials)),
+            nonce: AtomicU64::new(0),
+        })
+    }
+
+    fn call<T: DeserializeOwned>(&self, cmd: &str, args: &[Value]) -> bitcoincore_rpc::Result<T> {
+        let id = self.nonce.fetch_add(1, Ordering::Relaxed);
+        let request = json!({
+            "jsonrpc": "2.0",
+            "id": id,
+            "method": cmd,
+            "params": args,
+        });
+        let (status, body) = self.post(&serde_json::to_vec(&request)?)?;
+        // Bitcoin Core responds with the JSON-RPC error and the corresponding HTTP status.
+        let mut response: Value = serde_json::from_slice(&body).map_err(|e| {
+            if status == 200 {
+                bitcoincore_rpc::Error::Json(e)
+            } else {
+                bitcoincore_rpc::Error::Io(io::Error::new(
+                    io::ErrorKind::Other,
+                    format!("Bitcoin RPC responded with HTTP status {}", status),
+                ))
+            }
+        })?;
+        if !response["error"].is_null() {
+            let error = &response["error"];
+            return Err(JsonRpcError::Rpc(RpcError {
+                code: error["code"].as_i64().unwrap_or_default() as i32,
+                message: error["message"].as_str().unwrap_or_default().to_owned(),
+                data: error.get("data").cloned(),
+            })
+            .into());
+        }
+        Ok(serde_json::from_value(response["result"].take())?)
+    }
+
+    /// Sends the HTTP POST request and returns the status and the body of the response.
+    fn post(&self, body: &[u8]) -> io::Result<(u16, Vec<u8>)> {
+        let mut stream = self.proxy.connect(&self.target)?;
+        let mut request = format!(
+            "POST {} HTTP/1.1\r\nHost: {}\r\nContent-Type: application/json\r\n\
+             Content-Length: {}\r\nConnection: close\r\n",
+            self.path,
+            self.target,
+            body.len()
+        );
+        if let Some(authorization) = &self.authorization {
+            request.push_str(&format!("Authorization: Basic {}\r\n", authorization));
+        }
+        request.push_str("\r\n");
+        stream.write_all(request.as_bytes())?;
+        stream.write_all(body)?;
+
+        let mut response = Vec::new();
+        stream.read_to_end(&mut response)?;
+        parse_http_response(&response)
+    }
+}
+
+/// Parses the HTTP response which is terminated by closing the connection.
+fn parse_http_response(response: &[u8]) -> io::Result<(u16, Vec<u8>)> {
+    let invalid_response = || io::Error::new(ErrorKind::InvalidData, "Malformed HTTP response");
+    let head_len = response
+        .windows(4)
+        .position(|window| window == b"\r\n\r\n")
+        .ok_or_else(invalid_response)?;
+    let head = std::str::from_utf8(&response[..head_len]).map_err(|_| invalid_response())?;
+    let mut lines = head.split("\r\n");
+    let status = lines
+        .next()
+        .and_then(|status_line| status_line.split(' ').nth(1))
+        .and_then(|status| status.parse().ok())
+        .ok_or_else(invalid_response)?;
+    let is_chunked = lines.any(|line| {
+        let line = line.to_ascii_lowercase();
+        line.starts_with("transfer-encoding:") && line.contains("chunked")
+    });
+
+    let mut body = &response[head_len + 4..];
+    if !is_chunked {
+        return Ok((status, body.to_vec()));
+    }
+    let mut decoded = Vec::new();
+    loop {
+        let size_len = body
+            .windows(2)
+            .position(|window| window == b"\r\n")
+            .ok_or_else(invalid_response)?;
+        let size = std::str::from_utf8(&body[..size_len])
+            .ok()
+            .and_then(|size| usize::from_str_radix(size.split(';').next()?.trim(), 16).ok())
+            .ok_or_else(invalid_response)?;
+        if size == 0 {
+            return Ok((status, decoded));
+        }
+        let chunk = body
+            .get(size_len + 2..size_len + 2 + size)
+            .ok_or_else(invalid_response)?;
+        decoded.extend_from_slice(chunk);
+        body = body
+            .get(size_len + 4 + size..)
+            .ok_or_else(invalid_response)?;
+    }
+}
+
+/// Errors of the [`BitcoindRelay`](struct.BitcoindRelay.html).
+#[derive(Debug, Error)]
+pub enum BitcoindRelayError {
+    /// Bitcoin RPC request failed.
+    #[error("Bitcoin RPC request failed: {}", .0)]
+    Rpc(#[from] bitcoincore_rpc::Error),
+    /// Bitcoin RPC request has not been processed in time.
+    #[error("Bitcoin RPC request has not been processed in {} ms", .0.as_millis())]
+    Timeout(Duration),
+    /// Bitcoin RPC request has been aborted.
+    #[error("Bitcoin RPC request has been aborted: {}", .0)]
+    Aborted(String),
+}
+
+/// Bitcoin relay which communicates with the Bitcoin Core node via JSON-RPC.
+///
+/// The RPC client is blocking, so requests are performed on the blocking thread pool
+/// of the runtime. The request which exceeds the timeout is abandoned, but its
+/// thread is released only after the underlying HTTP request is completed.
+///
+/// The relay also serves as the non-blocking [`FundingWallet`] and [`BitcoinDataSource`],
+/// so several anchoring instances can be driven from the single runtime.
+///
+/// Transaction statuses are taken from `getrawtransaction`. If the Bitcoin node is not
+/// run with the `-txindex` option, the status of the committed transaction is taken
+/// from its first unspent output via `gettxout`.
+///
+/// [`FundingWallet`]: trait.FundingWallet.html
+/// [`BitcoinDataSource`]: recover_from_bitcoin/trait.BitcoinDataSource.html
+#[derive(Debug, Clone)]
+pub struct BitcoindRelay {
+    client: Arc<BitcoindRpcClient>,
+    request_timeout: Option<Duration>,
+}
+
+impl BitcoindRelay {
+    /// Creates a relay with the given configuration.
+    pub fn new(config: &BitcoindRelayConfig) -> Result<Self, bitcoincore_rpc::Error> {
+        Ok(Self {
+            client: Arc::new(config.client()?),
+            request_timeout: config.request_timeout.map(Duration::from_millis),
+        })
+    }
+
+    /// Returns the underlying Bitcoin RPC client.
+    pub fn client(&self) -> &BitcoindRpcClient {
+        &self.client
+    }
+
+    async fn call<T, F>(&self, request: F) -> Result<T, BitcoindRelayError>
+    where
+        T: Send + 'static,
+        F: FnOnce(&BitcoindRpcClient) -> Result<T, bitcoincore_rpc::Error> + Send + 'static,
+    {
+        let client = self.client.clone();
+        let response = tokio::task::spawn_blocking(move || request(&client));
+        let response = match self.request_timeout {
+            Some(timeout) => tokio::time::timeout(timeout, response)
+                .await
+                .map_err(|_| BitcoindRelayError::Timeout(timeout))?,
+            None => response.await,
+        };
+        response
+            .map_err(|e| BitcoindRelayError::Aborted(e.to_string()))?
+            .map_err(BitcoindRelayError::from)
+    }
+}
+
+/// Returns `true` if the Bitcoin node has rejected the request, e.g. because
+/// of the unknown transaction.
+fn is_rejected(error: &bitcoincore_rpc::Error) -> bool {
+    matches!(error, bitcoincore_rpc::Error::JsonRpc(JsonRpcError::Rpc(_)))
+}
+
+#[async_trait]
+impl BitcoinRelay for BitcoindRelay {
+    type Error = BitcoindRelayError;
+
+    async fn send_transaction(
+        &self,
+        transaction: &btc::Transaction,
+    ) -> Result<btc::Sha256d, Self::Error> {
+        let transaction = transaction.to_string();
+        self.call(move |client| client.send_raw_transaction(transaction))
+            .await
+            .map(|txid| btc::Sha256d(txid.into()))
+    }
+
+    async fn transaction_status(&self, id: btc::Sha256d) -> Result<TransactionStatus, Self::Error> {
+        self.call(move |client| {
+            match client.get_raw_transaction_verbose(&id.into(), None) {
+                Ok(info) => {
+                    return Ok(match info.confirmations {
+                        None => TransactionStatus::Mempool,
+                        Some(num) => TransactionStatus::Committed(num),
+                    })
+                }
+                Err(e) if !is_rejected(&e) => return Err(e),
+                Err(_) => {}
+            }
+            // The transaction may be unavailable without the transaction index.
+            let status = match client.get_tx_out(&id.into(), 0, Some(true))? {
+                Some(out) if out.confirmations == 0 => TransactionStatus::Mempool,
+                Some(out) => TransactionStatus::Committed(out.confirmations),
+                None => TransactionStatus::Unknown,
+            };
+            Ok(status)
+        })
+        .await
+    }
+
+    async fn transaction_proof(
+        &self,
+        id: btc::Sha256d,
+    ) -> Result<Option<btc::MerkleBlock>, Self::Error> {
+        self.call(
+            move |client| match client.get_tx_out_proof(&[id.into()], None) {
+                Ok(bytes) => bitcoin::consensus::deserialize(&bytes)
+                    .map(|merkle_block| Some(btc::MerkleBlock(merkle_block)))
+                    .map_err(bitcoincore_rpc::Error::BitcoinSerialization),
+                // The transaction is not yet in a block.
+                Err(ref e) if is_rejected(e) => Ok(None),
+                Err(e) => Err(e),
+            },
+        )
+        .await
+    }
+
+    async fn raw_transaction(
+        &self,
+        id: btc::Sha256d,
+    ) -> Result<Option<btc::Transaction>, Self::Error> {
+        self.call(
+            move |client| match client.get_raw_transaction(&id.into(), None) {
+                Ok(transaction) => Ok(Some(transaction.into())),
+                // The transaction is unknown.
+                Err(ref e) if is_rejected(e) => Ok(None),
+                Err(e) => Err(e),
+            },
+        )
+        .await
+    }
+
+    async fn is_output_spent(
+        &self,
+        txid: btc::Sha256d,
+        vout: u32,
+    ) -> Result<Option<bool>, Self::Error> {
+        if self
+            .call(move |client| client.get_tx_out(&txid.into(), vout, Some(true)))
+            .await?
+            .is_some()
+        {
+            return Ok(Some(false));
+        }
+        // The output is absent in the UTXO set, either if it is spent or if
+        // the transaction is unknown.
+        let is_known = self.transaction_status(txid).await?.is_known();
+        Ok(if is_known { Some(true) } else { None })
+    }
+
+    async fn fee_rate(&self, target_blocks: u16) -> Result<Option<u64>, Self::Error> {
+        self.call(move |client| rpc_fee_rate(client, target_blocks))
+            .await
+    }
+}
+
+#[async_trait]
+impl FundingWallet for BitcoindRelay {
+    type Error = BitcoindRelayError;
+
+    async fn balance(&self) -> Result<u64, Self::Error> {
+        self.call(wallet_balance).await
+    }
+
+    async fn send_to_address(
+        &self,
+        address: &btc::Address,
+        amount: u64,
+    ) -> Result<btc::Transaction, Self::Error> {
+        let address = address.clone();
+        self.call(move |client| wallet_send_to_address(client, &address, amount))
+            .await
+    }
+
+    async fn create_transaction(
+        &self,
+        address: &btc::Address,
+        amount: u64,
+    ) -> Result<btc::Transaction, Self::Error> {
+        let address = address.clone();
+        self.call(move |client| wallet_create_transaction(client, &address, amount))
+            .await
+    }
+}
+
+#[async_trait]
+impl BitcoinDataSource for BitcoindRelay {
+    type Error = BitcoindRelayError;
+
+    async fn transaction(&self, id: btc::Sha256d) -> Result<Option<btc::Transaction>, Self::Error> {
+        self.call(move |client| rpc_transaction(client, id)).await
+    }
+
+    async fn unspent_transactions(
+        &self,
+        address: &btc::Address,
+    ) -> Result<Vec<btc::Sha256d>, Self::Error> {
+        let address = address.clone();
+        self.call(move |client| rpc_unspent_transactions(client, &address))
+            .await
+    }
+}
@@ -0,0 +1,184 @@
+// Copyright 2020 The Exonum Team
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Durable queue of the anchoring transactions which have failed to broadcast.
+
+use serde_derive::{Deserialize, Serialize};
+
+use std::{
+    fs,
+    path::{Path, PathBuf},
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+use crate::btc;
+
+/// Anchoring transaction waiting for the next broadcast attempt.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct QueuedTransaction {
+    /// Index of the transaction in the anchoring chain.
+    pub index: u64,
+    /// Anchoring transaction.
+    pub transaction: btc::Transaction,
+    /// Number of the failed broadcast attempts.
+    pub attempts: u32,
+    /// Error of the latest broadcast attempt.
+    pub last_error: String,
+    /// Time of the next broadcast attempt in seconds since the Unix epoch.
+    pub next_attempt: u64,
+}
+
+/// Queue of the anchoring transactions which have failed to broadcast, e.g. because
+/// the Bitcoin relay is down or its memory pool is full. Broadcasting of the queued
+/// transactions is retried with the exponential backoff.
+///
+/// The queue is saved to the file after each change, if the file is specified,
+/// so the queued transactions survive the restart of the sync utility.
+#[derive(Debug, Clone)]
+pub struct BroadcastQueue {
+    path: Option<PathBuf>,
+    initial_delay: Duration,
+    max_delay: Duration,
+    entries: Vec<QueuedTransaction>,
+}
+
+impl Default for BroadcastQueue {
+    fn default() -> Self {
+        Self {
+            path: None,
+            initial_delay: Self::DEFAULT_INITIAL_DELAY,
+            max_delay: Self::DEFAULT_MAX_DELAY,
+            entries: Vec::new(),
+        }
+    }
+}
+
+impl BroadcastQueue {
+    /// Default delay before the second broadcast attempt.
+    pub const DEFAULT_INITIAL_DELAY: Duration = Duration::from_secs(10);
+    /// Default maximum delay between the broadcast attempts.
+    pub const DEFAULT_MAX_DELAY: Duration = Duration::from_secs(3600);
+
+    /// Loads the queue from the given file or creates an empty one if the file
+    /// does not exist.
+    pub fn load(path: impl Into<PathBuf>) -> anyhow::Result<Self> {
+        let path = path.into();
+        let entries = if path.exists() {
+            serde_json::from_slice(&fs::read(&path)?)?
+        } else {
+            Vec::new()
+        };
+        Ok(Self {
+            path: Some(path),
+            entries,
+            ..Self::default()
+        })
+    }
+
+    /// Sets the delay before the second broadcast attempt and the maximum delay
+    /// between the attempts.
+    pub fn with_backoff(mut self, initial_delay: Duration, max_delay: Duration) -> Self {
+        self.initial_delay = initial_delay;
+        self.max_delay = max_delay;
+        self
+    }
+
+    /// Returns the path of the queue file, if any.
+    pub fn path(&self) -> Option<&Path> {
+        self.path.as_deref()
+    }
+
+    /// Returns the queued transactions.
+    pub fn entries(&self) -> &[QueuedTransaction] {
+        &self.entries
+    }
+
+    /// Checks if the transaction with the given identifier is queued.
+    pub fn contains(&self, txid: btc::Sha256d) -> bool {
+        self.entries
+            .iter()
+            .any(|entry| entry.transaction.id() == txid)
+    }
+
+    /// Returns the queued transactions which should be broadcast at the given time.
+    pub fn due(&self, now: SystemTime) -> Vec<QueuedTransaction> {
+        let now = unix_secs(now);
+        self.entries
+            .iter()
+            .filter(|entry| entry.next_attempt <= now)
+            .cloned()
+            .collect()
+    }
+
+    /// Records the failed broadcast attempt of the given transaction and schedules
+    /// the next one.
+    pub fn record_failure(
+        &mut self,
+        index: u64,
+        transaction: btc::Transaction,
+        error: impl ToString,
+        now: SystemTime,
+    ) -> anyhow::Result<()> {
+        let txid = transaction.id();
+        let position = self
+            .entries
+            .iter()
+            .position(|entry| entry.transaction.id() == txid);
+        let entry = match position {
+            Some(position) => &mut self.entries[position],
+            None => {
+                self.entries.push(QueuedTransaction {
+                    index,
+                    transaction,
+                    attempts: 0,
+                    last_error: String::new(),
+                    next_attempt: 0,
+                });
+                self.entries.last_mut().unwrap()
+            }
+        };
+        entry.attempts += 1;
+        entry.last_error = error.to_string();
+        let max_delay = self.max_delay;
+        let delay = self
+            .initial_delay
+            .checked_mul(1 << (entry.attempts - 1).min(31))
+            .map_or(max_delay, |delay| delay.min(max_delay));
+        entry.next_attempt = unix_secs(now + delay);
+        self.save()
+    }
+
+    /// Removes the transaction with the given identifier from the queue.
+    pub fn remove(&mut self, txid: btc::Sha256d) -> anyhow::Result<()> {
+        let len = self.entries.len();
+        self.entries.retain(|entry| entry.transaction.id() != txid);
+        if self.entries.len() != len {
+            self.save()?;
+        }
+        Ok(())
+    }
+
+    fn save(&self) -> anyhow::Result<()> {
+        if let Some(path) = &self.path {
+            fs::write(path, serde_json::to_vec_pretty(&self.entries)?)?;
+        }
+        Ok(())
+    }
+}
+
+fn unix_secs(time: SystemTime) -> u64 {
+    time.duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs()
+}
@@ -0,0 +1,772 @@
+// Copyright 2020 The Exonum Team
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Bitcoin relay over the Electrum server protocol.
+
+use async_trait::async_trait;
+use bitcoin::consensus::encode::{serialize, VarInt};
+use bitcoin_hashes::{hex::ToHex, sha256, Hash};
+use serde_derive::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use thiserror::Error;
+use tokio::{
+    io::{AsyncBufReadExt, AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt, BufReader},
+    net::TcpStream,
+    sync::Mutex,
+};
+
+use std::{
+    collections::{BTreeMap, HashMap},
+    fmt, io,
+    time::Duration,
+};
+
+use super::{
+    bitcoin_relay::fee_rate_from_btc_per_kb, BitcoinRelay, ProxyConfig, TransactionStatus,
+};
+use crate::btc;
+
+/// Name of the client reported to the Electrum server.
+const CLIENT_NAME: &str = "exonum-btc-anchoring";
+/// Supported version of the Electrum protocol.
+const PROTOCOL_VERSION: &str = "1.4";
+/// Maximum number of the cached script hashes of the transactions.
+const SCRIPT_HASHES_CACHE_SIZE: usize = 1024;
+/// Maximum length in bytes of a single message received from the Electrum server.
+const MAX_MESSAGE_LENGTH: u64 = 16 * 1024 * 1024;
+
+/// Configuration of the connection to the Electrum server.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct ElectrumRelayConfig {
+    /// Address of the Electrum server, e.g. `electrum.blockstream.info:60002`.
+    pub server: String,
+    /// Whether to connect to the server via SSL.
+    #[serde(default)]
+    pub ssl: bool,
+    /// Whether to accept the invalid SSL certificates, e.g. the self-signed ones.
+    #[serde(default)]
+    pub accept_invalid_certs: bool,
+    /// Maximum time in milliseconds to process a single request.
+    #[serde(default)]
+    pub request_timeout: Option<u64>,
+    /// SOCKS5 proxy to connect to the server through.
+    #[serde(default)]
+    pub proxy: Option<ProxyConfig>,
+}
+
+/// Errors of the [`ElectrumRelay`](struct.ElectrumRelay.html).
+#[derive(Debug, Error)]
+pub enum ElectrumError {
+    /// Connection to the Electrum server failed.
+    #[error("Electrum server connection failed: {}", .0)]
+    Io(#[from] io::Error),
+    /// SSL connection to the Electrum server failed.
+    #[error("Electrum server SSL connection failed: {}", .0)]
+    Tls(#[from] native_tls::Error),
+    /// Electrum server has rejected the request.
+    #[error("Electrum server error {}: {}", code, message)]
+    Server {
+        /// Error code.
+        code: i64,
+        /// Error message.
+        message: String,
+    },
+    /// Electrum server response is malformed.
+    #[error("Malformed Electrum server response: {}", .0)]
+    InvalidResponse(String),
+    /// Electrum request has not been processed in time.
+    #[error("Electrum request has not been processed in {} ms", .0.as_millis())]
+    Timeout(Duration),
+}
+
+impl ElectrumError {
+    fn invalid_response(error: impl fmt::Display) -> Self {
+        ElectrumError::InvalidResponse(error.to_string())
+    }
+
+    fn server(error: &Value) -> Self {
+        ElectrumError::Server {
+            code: error["code"].as_i64().unwrap_or_default(),
+            message: error["message"]
+                .as_str()
+                .map_or_else(|| error.to_string(), ToOwned::to_owned),
+        }
+    }
+}
+
+trait ElectrumStream: AsyncRead + AsyncWrite + Unpin + Send {}
+
+impl<T: AsyncRead + AsyncWrite + Unpin + Send> ElectrumStream for T {}
+
+/// Entry of the script hash history.
+#[derive(Debug, Clone, Deserialize)]
+struct HistoryItem {
+    tx_hash: btc::Sha256d,
+    /// Height of the block with the transaction, zero or negative for the mempool
+    /// transactions.
+    height: i64,
+}
+
+/// Unspent output of the script hash.
+#[derive(Debug, Deserialize)]
+struct UnspentItem {
+    tx_hash: btc::Sha256d,
+    tx_pos: u32,
+}
+
+/// Merkle branch of the transaction in the block.
+#[derive(Debug, Deserialize)]
+struct MerkleBranch {
+    block_height: u64,
+    merkle: Vec<btc::Sha256d>,
+    pos: u32,
+}
+
+/// Script hash subscription.
+#[derive(Debug, Default)]
+struct Subscription {
+    /// Latest reported status of the script hash.
+    status: Option<String>,
+    /// History of the script hash, which corresponds to the latest status.
+    history: Option<Vec<HistoryItem>>,
+}
+
+#[derive(Default)]
+struct ElectrumState {
+    connection: Option<BufReader<Box<dyn ElectrumStream>>>,
+    next_id: u64,
+    tip_height: Option<u64>,
+    subscriptions: HashMap<String, Subscription>,
+    script_hashes: BTreeMap<btc::Sha256d, String>,
+}
+
+/// Bitcoin relay which communicates with the Electrum server, so the anchoring
+/// can be performed without a full Bitcoin node.
+///
+/// The relay connects to the server on the first request and reconnects if the
+/// connection is lost. Transaction statuses are taken from the history of the script
+/// of the first transaction output. Histories of the scripts subscribed via
+/// [`subscribe_address`] are cached until the server reports a change, so the
+/// anchoring address should be subscribed to reduce the number of requests.
+///
+/// [`subscribe_address`]: #method.subscribe_address
+pub struct ElectrumRelay {
+    config: ElectrumRelayConfig,
+    state: Mutex<ElectrumState>,
+}
+
+impl fmt::Debug for ElectrumRelay {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ElectrumRelay")
+            .field("config", &self.config)
+            .finish()
+    }
+}
+
+impl ElectrumRelay {
+    /// Creates a relay with the given configuration.
+    pub fn new(config: ElectrumRelayConfig) -> Self {
+        Self {
+            config,
+            state: Mutex::default(),
+        }
+    }
+
+    /// Returns the relay configuration.
+    pub fn config(&self) -> &ElectrumRelayConfig {
+        &self.config
+    }
+
+    /// Subscribes to the changes of the given Bitcoin address. The subscription
+    /// is restored after reconnection.
+    pub async fn subscribe_address(&self, address: &btc::Address) -> Result<(), ElectrumError> {
+        let script_hash = script_hash(&address.0.script_pubkey());
+        let mut state = self.state.lock().await;
+        if state.subscriptions.contains_key(&script_hash) {
+            return Ok(());
+        }
+        state
+            .subscriptions
+            .insert(script_hash.clone(), Subscription::default());
+        let status = self
+            .request(
+                &mut state,
+                "blockchain.scripthash.subscribe",
+                json!([script_hash]),
+            )
+            .await?;
+        state.subscriptions.insert(
+            script_hash,
+            Subscription {
+                status: status.as_str().map(ToOwned::to_owned),
+                history: None,
+            },
+        );
+        Ok(())
+    }
+
+    async fn connect(&self) -> Result<BufReader<Box<dyn ElectrumStream>>, ElectrumError> {
+        let stream = match &self.config.proxy {
+            Some(proxy) => proxy.connect_async(&self.config.server).await?,
+            None => TcpStream::connect(self.config.server.as_str()).await?,
+        };
+        let stream: Box<dyn ElectrumStream> = if self.config.ssl {
+            let connector = native_tls::TlsConnector::builder()
+                .danger_accept_invalid_certs(self.config.accept_invalid_certs)
+                .build()?;
+            let domain = self
+                .config
+                .server
+                .rsplitn(2, ':')
+                .last()
+                .unwrap_or_default();
+            Box::new(
+                tokio_tls::TlsConnector::from(connector)
+                    .connect(domain, stream)
+                    .await?,
+            )
+        } else {
+            Box::new(stream)
+        };
+        Ok(BufReader::new(stream))
+    }
+
+    /// Establishes a new connection and restores the subscriptions.
+    async fn reconnect(&self, state: &mut ElectrumState) -> Result<(), ElectrumError> {
+        state.connection = Some(self.connect().await?);
+        Self::call(
+            state,
+            "server.version",
+            json!([CLIENT_NAME, PROTOCOL_VERSION]),
+        )
+        .await?;
+        let tip = Self::call(state, "blockchain.headers.subscribe", json!([])).await?;
+        state.tip_height = tip["height"].as_u64();
+
+        let script_hashes = state.subscriptions.keys().cloned().collect::<Vec<_>>();
+        for script_hash in script_hashes {
+            let status = Self::call(
+                state,
+                "blockchain.scripthash.subscribe",
+                json!([script_hash]),
+            )
+            .await?;
+            state.subscriptions.insert(
+                script_hash,
+                Subscription {
+                    status: status.as_str().map(ToOwned::to_owned),
+                    history: None,
+                },
+            );
+        }
+        Ok(())
+    }
+
+    /// Sends the request via the established connection and waits for the response.
+    /// Notifications received before the response are applied to the state.
+    async fn call(
+        state: &mut ElectrumState,
+        method: &str,
+        params: Value,
+    ) -> Result<Value, ElectrumError> {
+        let ElectrumState {
+            connection,
+            next_id,
+            tip_height,
+            subscriptions,
+            ..
+        } = state;
+        let connection = connection
+            .as_mut()
+            .ok_or_else(|| io::Error::from(io::ErrorKind::NotConnected))?;
+
+        *next_id += 1;
+        let id = *next_id;
+        let mut request = json!({
+            "jsonrpc": "2.0",
+            "id": id,
+            "method": method,
+            "params": params,
+        })
+        .to_string();
+        request.push('\n');
+        connection.get_mut().write_all(request.as_bytes()).await?;
+
+        loop {
+            let mut line = String::new();
+            let read = (&mut *connection)
+                .take(MAX_MESSAGE_LENGTH)
+                .read_line(&mut line)
+                .await?;
+            if read == 0 {
+                return Err(io::Error::from(io::ErrorKind::UnexpectedEof).into());
+            }
+            if read as u64 == MAX_MESSAGE_LENGTH && !line.ends_with('\n') {
+                return Err(ElectrumError::invalid_response(format!(
+                    "message exceeds {} bytes",
+                    MAX_MESSAGE_LENGTH
+                )));
+            }
+            let mut message: Value =
+                serde_json::from_str(&line).map_err(ElectrumError::invalid_response)?;
+            if message["id"] == id {
+                if !message["error"].is_null() {
+                    return Err(ElectrumError::server(&message["error"]));
+                }
+                return Ok(message["result"].take());
+            }
+
+            match message["method"].as_str() {
+                Some("blockchain.headers.subscribe") => {
+                    if let Some(height) = message["params"][0]["height"].as_u64() {
+                        *tip_height = Some(height);
+                    }
+                }
+                Some("blockchain.scripthash.subscribe") => {
+                    let status = message["params"][1].as_str().map(ToOwned::to_owned);
+                    let subscription = message["params"][0]
+                        .as_str()
+                        .and_then(|script_hash| subscriptions.get_mut(script_hash));
+                    if let Some(subscription) = subscription {
+                        if subscription.status != status {
+                            subscription.status = status;
+                            subscription.history = None;
+                        }
+                    }
+                }
+                _ => tracing::trace!("Unexpected Electrum server message: {}", line.trim()),
+            }
+        }
+    }
+
+    async fn call_with_reconnect(
+        &self,
+        state: &mut ElectrumState,
+        method: &str,
+        params: Value,
+    ) -> Result<Value, ElectrumError> {
+        let mut is_retry = false;
+        loop {
+            if state.connection.is_none() {
+                self.reconnect(state).await?;
+            }
+            match Self::call(state, method, params.clone()).await {
+                Err(ElectrumError::Io(e)) if !is_retry => {
+                    tracing::warn!(
+                        "Connection to the Electrum server {} has been lost: {}",
+                        self.config.server,
+                        e
+                    );
+                    state.connection = None;
+                    is_retry = true;
+                }
+                response => return response,
+            }
+        }
+    }
+
+    async fn request(
+        &self,
+        state: &mut ElectrumState,
+        method: &str,
+        params: Value,
+    ) -> Result<Value, ElectrumError> {
+        let response = match self.config.request_timeout.map(Duration::from_millis) {
+            Some(timeout) => {
+                tokio::time::timeout(timeout, self.call_with_reconnect(state, method, params))
+                    .await
+                    .unwrap_or(Err(ElectrumError::Timeout(timeout)))
+            }
+            None => self.call_with_reconnect(state, method, params).await,
+        };
+        // The connection may contain the partially read response.
+        if let Err(ElectrumError::Io(_)) | Err(ElectrumError::Timeout(_)) = response {
+            state.connection = None;
+        }
+        response
+    }
+
+    async fn transaction(
+        &self,
+        state: &mut ElectrumState,
+        id: btc::Sha256d,
+    ) -> Result<Option<btc::Transaction>, ElectrumError> {
+        match self
+            .request(state, "blockchain.transaction.get", json!([id]))
+            .await
+        {
+            Ok(transaction) => serde_json::from_value(transaction)
+                .map(Some)
+                .map_err(ElectrumError::invalid_response),
+            // The transaction is unknown.
+            Err(ElectrumError::Server { .. }) => Ok(None),
+            Err(e) => Err(e),
+        }
+    }
+
+    /// Returns the history of the script of the first transaction output.
+    async fn transaction_history(
+        &self,
+        state: &mut ElectrumState,
+        id: btc::Sha256d,
+    ) -> Result<Option<Vec<HistoryItem>>, ElectrumError> {
+        let script_hash = if let Some(script_hash) = state.script_hashes.get(&id) {
+            script_hash.clone()
+        } else {
+            let transaction = if let Some(transaction) = self.transaction(state, id).await? {
+                transaction
+            } else {
+                return Ok(None);
+            };
+            let script_hash = if let Some(output) = transaction.0.output.first() {
+                script_hash(&output.script_pubkey)
+            } else {
+                return Ok(None);
+            };
+            if state.script_hashes.len() >= SCRIPT_HASHES_CACHE_SIZE {
+                state.script_hashes.clear();
+            }
+            state.script_hashes.insert(id, script_hash.clone());
+            script_hash
+        };
+
+        if state.subscriptions.contains_key(&script_hash) {
+            // Make sure that the pending notifications are applied.
+            self.request(state, "server.ping", json!([])).await?;
+            if let Some(history) = &state.subscriptions[&script_hash].history {
+                return Ok(Some(history.clone()));
+            }
+        }
+        let history: Vec<HistoryItem> = serde_json::from_value(
+            self.request(
+                state,
+                "blockchain.scripthash.get_history",
+                json!([script_hash]),
+            )
+            .await?,
+        )
+        .map_err(ElectrumError::invalid_response)?;
+        if let Some(subscription) = state.subscriptions.get_mut(&script_hash) {
+            subscription.history = Some(history.clone());
+        }
+        Ok(Some(history))
+    }
+
+    /// Returns the height of the block with the transaction, if it is committed.
+    async fn transaction_height(
+        &self,
+        state: &mut ElectrumState,
+        id: btc::Sha256d,
+    ) -> Result<Option<i64>, ElectrumError> {
+        Ok(self
+            .transaction_history(state, id)
+            .await?
+            .and_then(|history| history.into_iter().find(|item| item.tx_hash == id))
+            .map(|item| item.height))
+    }
+
+    async fn tip_height(&self, state: &mut ElectrumState) -> Result<u64, ElectrumError> {
+        if let Some(height) = state.tip_height {
+            return Ok(height);
+        }
+        let tip = self
+            .request(state, "blockchain.headers.subscribe", json!([]))
+            .await?;
+        let height = tip["height"]
+            .as_u64()
+            .ok_or_else(|| ElectrumError::invalid_response("Unknown blockchain tip height"))?;
+        state.tip_height = Some(height);
+        Ok(height)
+    }
+}
+
+/// Returns the script hash used by the Electrum protocol to identify the script.
+fn script_hash(script: &bitcoin::Script) -> String {
+    let mut hash = sha256::Hash::hash(script.as_bytes()).into_inner();
+    hash.reverse();
+    hash.to_hex()
+}
+
+/// Builds the Merkle block which proves the inclusion of the transaction with
+/// the given position into the block from the Merkle branch of the transaction.
+///
+/// The exact number of transactions in the block is unknown, so the proof
+/// uses the minimal number of transactions, for which the branch is consistent.
+fn merkle_block_from_branch(
+    header: &btc::BlockHeader,
+    txid: btc::Sha256d,
+    pos: u32,
+    branch: &[btc::Sha256d],
+) -> Option<btc::MerkleBlock> {
+    let tree_height = branch.len();
+    if tree_height >= 32 || u64::from(pos) >> tree_height != 0 {
+        return None;
+    }
+
+    // Find the minimal number of transactions for the tree of the given height,
+    // in which every sibling from the branch is present. If the sibling is absent,
+    // its hash is equal to the hash of the node itself.
+    let mut num_transactions = u64::from(pos) + 1;
+    if tree_height > 0 {
+        num_transactions = num_transactions.max((1 << (tree_height - 1)) + 1);
+    }
+    let mut node = txid.0;
+    for (height, sibling) in branch.iter().enumerate() {
+        let index = u64::from(pos) >> height;
+        let mut data = Vec::with_capacity(2 * btc::Sha256d::LEN);
+        if index % 2 == 0 {
+            if sibling.0 != node {
+                num_transactions = num_transactions.max(((index + 1) << height) + 1);
+            }
+            data.extend_from_slice(&node[..]);
+            data.extend_from_slice(&sibling.0[..]);
+        } else {
+            data.extend_from_slice(&sibling.0[..]);
+            data.extend_from_slice(&node[..]);
+        }
+        node = bitcoin_hashes::sha256d::Hash::hash(&data);
+    }
+
+    // Traverse the partial Merkle tree in the depth-first order.
+    let width = |height: usize| (num_transactions + (1 << height) - 1) >> height;
+    let mut bits = Vec::new();
+    let mut hashes = Vec::new();
+    let mut stack = vec![(tree_height, 0)];
+    while let Some((height, index)) = stack.pop() {
+        let is_parent_of_match = index == u64::from(pos) >> height;
+        bits.push(is_parent_of_match);
+        if !is_parent_of_match {
+            hashes.push(branch[height]);
+        } else if height == 0 {
+            hashes.push(txid);
+        } else {
+            if 2 * index + 1 < width(height - 1) {
+                stack.push((height - 1, 2 * index + 1));
+            }
+            stack.push((height - 1, 2 * index));
+        }
+    }
+
+    let mut flags = vec![0_u8; (bits.len() + 7) / 8];
+    for (i, bit) in bits.into_iter().enumerate() {
+        flags[i / 8] |= (bit as u8) << (i % 8);
+    }
+    let mut bytes = serialize(&header.0);
+    bytes.extend(serialize(&(num_transactions as u32)));
+    bytes.extend(serialize(&VarInt(hashes.len() as u64)));
+    for hash in hashes {
+        bytes.extend_from_slice(&hash.to_internal_bytes());
+    }
+    bytes.extend(serialize(&flags));
+
+    let merkle_block = btc::MerkleBlock(bitcoin::consensus::deserialize(&bytes).ok()?);
+    if merkle_block.matched_transactions()? == [txid] {
+        Some(merkle_block)
+    } else {
+        None
+    }
+}
+
+#[async_trait]
+impl BitcoinRelay for ElectrumRelay {
+    type Error = ElectrumError;
+
+    async fn send_transaction(
+        &self,
+        transaction: &btc::Transaction,
+    ) -> Result<btc::Sha256d, Self::Error> {
+        let mut state = self.state.lock().await;
+        let txid = self
+            .request(
+                &mut state,
+                "blockchain.transaction.broadcast",
+                json!([transaction.to_string()]),
+            )
+            .await?;
+        serde_json::from_value(txid).map_err(ElectrumError::invalid_response)
+    }
+
+    async fn transaction_status(&self, id: btc::Sha256d) -> Result<TransactionStatus, Self::Error> {
+        let mut state = self.state.lock().await;
+        let status = match self.transaction_height(&mut state, id).await? {
+            None => TransactionStatus::Unknown,
+            Some(height) if height <= 0 => TransactionStatus::Mempool,
+            Some(height) => {
+                let tip_height = self.tip_height(&mut state).await?;
+                let confirmations = (tip_height + 1).saturating_sub(height as u64);
+                TransactionStatus::Committed(confirmations as u32)
+            }
+        };
+        Ok(status)
+    }
+
+    async fn transaction_proof(
+        &self,
+        id: btc::Sha256d,
+    ) -> Result<Option<btc::MerkleBlock>, Self::Error> {
+        let mut state = self.state.lock().await;
+        let height = match self.transaction_height(&mut state, id).await? {
+            Some(height) if height > 0 => height,
+            // The transaction is not yet in a block.
+            _ => return Ok(None),
+        };
+
+        let branch: MerkleBranch = serde_json::from_value(
+            self.request(
+                &mut state,
+                "blockchain.transaction.get_merkle",
+                json!([id, height]),
+            )
+            .await?,
+        )
+        .map_err(ElectrumError::invalid_response)?;
+        let header: btc::BlockHeader = serde_json::from_value(
+            self.request(
+                &mut state,
+                "blockchain.block.header",
+                json!([branch.block_height]),
+            )
+            .await?,
+        )
+        .map_err(ElectrumError::invalid_response)?;
+        merkle_block_from_branch(&header, id, branch.pos, &branch.merkle)
+            .map(Some)
+            .ok_or_else(|| {
+                ElectrumError::invalid_response("Merkle branch doesn't match the block header")
+            })
+    }
+
+    async fn raw_transaction(
+        &self,
+        id: btc::Sha256d,
+    ) -> Result<Option<btc::Transaction>, Self::Error> {
+        let mut state = self.state.lock().await;
+        self.transaction(&mut state, id).await
+    }
+
+    async fn is_output_spent(
+        &self,
+        txid: btc::Sha256d,
+        vout: u32,
+    ) -> Result<Option<bool>, Self::Error> {
+        let mut state = self.state.lock().await;
+        let script_pubkey = match self.transaction(&mut state, txid).await? {
+            Some(transaction) => match transaction.0.output.get(vout as usize) {
+                Some(output) => output.script_pubkey.clone(),
+                None => return Ok(None),
+            },
+            None => return Ok(None),
+        };
+        let unspent: Vec<UnspentItem> = serde_json::from_value(
+            self.request(
+                &mut state,
+                "blockchain.scripthash.listunspent",
+                json!([script_hash(&script_pubkey)]),
+            )
+            .await?,
+        )
+        .map_err(ElectrumError::invalid_response)?;
+        let is_unspent = unspent
+            .iter()
+            .any(|item| item.tx_hash == txid && item.tx_pos == vout);
+        Ok(Some(!is_unspent))
+    }
+
+    async fn fee_rate(&self, target_blocks: u16) -> Result<Option<u64>, Self::Error> {
+        let mut state = self.state.lock().await;
+        let rate = self
+            .request(&mut state, "blockchain.estimatefee", json!([target_blocks]))
+            .await?;
+        // The server returns `-1` if it has not enough data for the estimation.
+        let rate = rate
+            .as_f64()
+            .ok_or_else(|| ElectrumError::invalid_response("Fee rate is not a number"))?;
+        Ok(fee_rate_from_btc_per_kb(rate))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bitcoin::{
+        blockdata::block::BlockHeader, hash_types::Txid, util::merkleblock::PartialMerkleTree,
+    };
+    use bitcoin_hashes::{sha256d, Hash};
+
+    use super::merkle_block_from_branch;
+    use crate::btc;
+
+    /// Returns the Merkle branch of the transaction in the format of the Electrum protocol.
+    fn merkle_branch(txids: &[Txid], pos: usize) -> Vec<btc::Sha256d> {
+        let mut level = txids
+            .iter()
+            .map(|txid| sha256d::Hash::from_inner(txid.into_inner()))
+            .collect::<Vec<_>>();
+        let mut index = pos;
+        let mut branch = Vec::new();
+        while level.len() > 1 {
+            if level.len() % 2 == 1 {
+                level.push(*level.last().unwrap());
+            }
+            branch.push(btc::Sha256d(level[index ^ 1]));
+            level = level
+                .chunks(2)
+                .map(|pair| {
+                    let mut data = pair[0][..].to_vec();
+                    data.extend_from_slice(&pair[1][..]);
+                    sha256d::Hash::hash(&data)
+                })
+                .collect();
+            index /= 2;
+        }
+        branch
+    }
+
+    #[test]
+    fn test_merkle_block_from_branch() {
+        for num_transactions in 1..=20 {
+            let txids = (0..num_transactions)
+                .map(|i| Txid::hash(&[i as u8]))
+                .collect::<Vec<_>>();
+            let mut matches = vec![false; txids.len()];
+            matches[0] = true;
+            let merkle_root = PartialMerkleTree::from_txids(&txids, &matches)
+                .extract_matches(&mut Vec::new(), &mut Vec::new())
+                .unwrap();
+            let header = btc::BlockHeader(BlockHeader {
+                version: 1,
+                prev_blockhash: Default::default(),
+                merkle_root,
+                time: 0,
+                bits: 0,
+                nonce: 0,
+            });
+
+            for (pos, txid) in txids.iter().enumerate() {
+                let txid = btc::Sha256d::from(*txid);
+                let branch = merkle_branch(&txids, pos);
+                let merkle_block =
+                    merkle_block_from_branch(&header, txid, pos as u32, &branch).unwrap();
+                assert_eq!(merkle_block.header(), header);
+                assert_eq!(merkle_block.matched_transactions().unwrap(), vec![txid]);
+
+                // The branch of the other transaction doesn't prove the inclusion.
+                let other_pos = (pos + 1) % txids.len();
+                if other_pos != pos {
+                    let branch = merkle_branch(&txids, other_pos);
+                    assert!(merkle_block_from_branch(&header, txid, pos as u32, &branch).is_none());
+                }
+            }
+        }
+    }
+}
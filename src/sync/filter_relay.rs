@@ -0,0 +1,750 @@
+// Copyright 2020 The Exonum Team
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Light Bitcoin relay based on the compact block filters (BIP157/158).
+
+use async_trait::async_trait;
+use bitcoin::{
+    blockdata::constants::genesis_block,
+    consensus::{
+        encode::{self, deserialize, serialize},
+        params::Params,
+    },
+    hash_types::BlockHash,
+    network::{
+        address::Address as PeerAddress,
+        constants::ServiceFlags,
+        message::{NetworkMessage, RawNetworkMessage},
+        message_blockdata::{GetHeadersMessage, Inventory},
+        message_filter::GetCFilters,
+        message_network::VersionMessage,
+    },
+    util::{bip158::BlockFilter, merkleblock},
+};
+use bitcoin_hashes::Hash;
+use serde_derive::{Deserialize, Serialize};
+use thiserror::Error;
+use tokio::{
+    io::{AsyncReadExt, AsyncWriteExt},
+    net::TcpStream,
+    sync::Mutex,
+};
+
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    convert::TryInto,
+    fmt, io,
+    time::{Duration, SystemTime},
+};
+
+use super::{BitcoinRelay, ProxyConfig, TransactionStatus};
+use crate::btc;
+
+/// Name of the client reported to the Bitcoin node.
+const USER_AGENT: &str = concat!("/exonum-btc-anchoring:", env!("CARGO_PKG_VERSION"), "/");
+/// Version of the Bitcoin P2P protocol with the compact block filters support.
+const PROTOCOL_VERSION: u32 = 70016;
+/// Type of the basic block filter.
+const BASIC_FILTER_TYPE: u8 = 0;
+/// Maximum number of the headers in the `headers` message.
+const MAX_HEADERS: usize = 2000;
+/// Maximum number of the filters which can be requested via the single `getcfilters` message.
+const MAX_FILTERS: u64 = 1000;
+/// Maximum size of the P2P message payload.
+const MAX_PAYLOAD_SIZE: usize = 32 * 1024 * 1024;
+/// Size of the P2P message header.
+const MESSAGE_HEADER_SIZE: usize = 24;
+
+/// Trusted Bitcoin block header from which the synchronization starts.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct HeaderCheckpoint {
+    /// Height of the block.
+    pub height: u64,
+    /// Header of the block.
+    pub header: btc::BlockHeader,
+}
+
+/// Configuration of the light relay based on the compact block filters.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct CompactFilterRelayConfig {
+    /// Address of the Bitcoin node which serves the compact block filters, e.g.
+    /// `127.0.0.1:18333`. Bitcoin Core serves them if it is run with the
+    /// `-blockfilterindex` and `-peerblockfilters` options.
+    pub peer: String,
+    /// Bitcoin network type.
+    pub network: bitcoin::Network,
+    /// Trusted block header from which the headers chain is synchronized. It should
+    /// precede the first anchoring transaction and, unless the network has no difficulty
+    /// adjustment, be at the retarget boundary. If it is not specified, the chain is
+    /// synchronized from the genesis block.
+    #[serde(default)]
+    pub checkpoint: Option<HeaderCheckpoint>,
+    /// Maximum time in milliseconds to wait for a single response of the Bitcoin node.
+    #[serde(default)]
+    pub request_timeout: Option<u64>,
+    /// SOCKS5 proxy to connect to the Bitcoin node through.
+    #[serde(default)]
+    pub proxy: Option<ProxyConfig>,
+}
+
+/// Errors of the [`CompactFilterRelay`](struct.CompactFilterRelay.html).
+#[derive(Debug, Error)]
+pub enum CompactFilterError {
+    /// Connection to the Bitcoin node failed.
+    #[error("Bitcoin node connection failed: {}", .0)]
+    Io(#[from] io::Error),
+    /// Bitcoin node has sent the message which cannot be decoded.
+    #[error("Unable to decode Bitcoin node message: {}", .0)]
+    Encode(#[from] encode::Error),
+    /// Bitcoin node doesn't serve the compact block filters.
+    #[error("Bitcoin node doesn't serve compact block filters")]
+    UnsupportedPeer,
+    /// Block headers sent by the Bitcoin node are invalid.
+    #[error("Invalid Bitcoin block headers: {}", .0)]
+    HeaderChain(#[from] btc::HeaderChainError),
+    /// Bitcoin node response is malformed.
+    #[error("Malformed Bitcoin node response: {}", .0)]
+    InvalidResponse(String),
+    /// Bitcoin node has not responded in time.
+    #[error("Bitcoin node has not responded in {} ms", .0.as_millis())]
+    Timeout(Duration),
+}
+
+impl CompactFilterError {
+    fn invalid_response(error: impl fmt::Display) -> Self {
+        CompactFilterError::InvalidResponse(error.to_string())
+    }
+}
+
+/// Transaction found in the synchronized blocks.
+#[derive(Debug)]
+struct ConfirmedTransaction {
+    height: u64,
+    transaction: btc::Transaction,
+    proof: btc::MerkleBlock,
+}
+
+#[derive(Debug)]
+struct ChainState {
+    connection: Option<TcpStream>,
+    /// Headers of the best chain starting from the checkpoint.
+    headers: Vec<btc::BlockHeader>,
+    /// Scripts of the watched addresses and of the outputs of the sent transactions.
+    watched_scripts: BTreeSet<Vec<u8>>,
+    /// Height of the latest block which filter has been checked.
+    scanned_height: Option<u64>,
+    /// Watched transactions found in the blocks.
+    confirmed: BTreeMap<btc::Sha256d, ConfirmedTransaction>,
+    /// Transactions sent via this relay or disconnected from the chain during
+    /// the reorganization, which are not found in the blocks yet.
+    sent: BTreeMap<btc::Sha256d, btc::Transaction>,
+}
+
+/// Bitcoin relay which synchronizes the block headers and the compact block filters
+/// from the Bitcoin node via the P2P protocol, so the anchoring transactions can be
+/// tracked without a full node and without trusting a third-party API.
+///
+/// Headers are checked for the proof of work and the difficulty rules, and the
+/// transactions are taken from the blocks which match the filters and whose Merkle
+/// root is consistent with the header. Thus the Bitcoin node is unable to forge
+/// a confirmation, but it can conceal one.
+///
+/// Filters match the transactions by their scripts, so the anchoring address should
+/// be watched via [`watch_address`]. Outputs of the transactions sent via this relay
+/// are watched automatically. The Bitcoin node doesn't report whether the sent
+/// transaction is accepted, so such transaction is considered to be in the mempool
+/// until it is found in a block.
+///
+/// [`watch_address`]: #method.watch_address
+pub struct CompactFilterRelay {
+    config: CompactFilterRelayConfig,
+    base_height: u64,
+    state: Mutex<ChainState>,
+}
+
+impl fmt::Debug for CompactFilterRelay {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("CompactFilterRelay")
+            .field("config", &self.config)
+            .finish()
+    }
+}
+
+impl CompactFilterRelay {
+    /// Creates a relay with the given configuration.
+    pub fn new(config: CompactFilterRelayConfig) -> Self {
+        let (base_height, base_header) = match &config.checkpoint {
+            Some(checkpoint) => (checkpoint.height, checkpoint.header),
+            None => (0, btc::BlockHeader(genesis_block(config.network).header)),
+        };
+        Self {
+            config,
+            base_height,
+            state: Mutex::new(ChainState {
+                connection: None,
+                headers: vec![base_header],
+                watched_scripts: BTreeSet::new(),
+                scanned_height: None,
+                confirmed: BTreeMap::new(),
+                sent: BTreeMap::new(),
+            }),
+        }
+    }
+
+    /// Returns the relay configuration.
+    pub fn config(&self) -> &CompactFilterRelayConfig {
+        &self.config
+    }
+
+    /// Starts watching the transactions of the given Bitcoin address. The filters
+    /// are checked again from the checkpoint during the next synchronization.
+    pub async fn watch_address(&self, address: &btc::Address) {
+        let mut state = self.state.lock().await;
+        Self::watch_script(&mut state, address.0.script_pubkey().into_bytes());
+    }
+
+    /// Synchronizes the headers chain and the watched transactions with the Bitcoin node
+    /// and returns the height of the best known block.
+    pub async fn sync(&self) -> Result<u64, CompactFilterError> {
+        let mut state = self.state.lock().await;
+        self.sync_with_reconnect(&mut state).await?;
+        Ok(self.tip_height(&state))
+    }
+
+    fn watch_script(state: &mut ChainState, script: Vec<u8>) {
+        if state.watched_scripts.insert(script) {
+            state.scanned_height = None;
+        }
+    }
+
+    fn tip_height(&self, state: &ChainState) -> u64 {
+        self.base_height + state.headers.len() as u64 - 1
+    }
+
+    fn header(&self, state: &ChainState, height: u64) -> btc::BlockHeader {
+        state.headers[(height - self.base_height) as usize]
+    }
+
+    async fn connect(&self) -> Result<TcpStream, CompactFilterError> {
+        let mut stream = match &self.config.proxy {
+            Some(proxy) => proxy.connect_async(&self.config.peer).await?,
+            None => TcpStream::connect(self.config.peer.as_str()).await?,
+        };
+        let timestamp = SystemTime::now()
+            .duration_since(SystemTime::UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_secs();
+        let version = VersionMessage {
+            version: PROTOCOL_VERSION,
+            ..VersionMessage::new(
+                ServiceFlags::NONE,
+                timestamp as i64,
+                PeerAddress::new(&stream.peer_addr()?, ServiceFlags::NONE),
+                PeerAddress::new(&stream.local_addr()?, ServiceFlags::NONE),
+                rand::random(),
+                USER_AGENT.to_owned(),
+                0,
+            )
+        };
+        self.send(&mut stream, NetworkMessage::Version(version))
+            .await?;
+
+        let (mut has_version, mut has_verack) = (false, false);
+        while !(has_version && has_verack) {
+            match self.receive(&mut stream).await? {
+                NetworkMessage::Version(version) => {
+                    if !version.services.has(ServiceFlags::COMPACT_FILTERS) {
+                        return Err(CompactFilterError::UnsupportedPeer);
+                    }
+                    self.send(&mut stream, NetworkMessage::Verack).await?;
+                    has_version = true;
+                }
+                NetworkMessage::Verack => has_verack = true,
+                _ => {}
+            }
+        }
+        Ok(stream)
+    }
+
+    async fn send(
+        &self,
+        stream: &mut TcpStream,
+        payload: NetworkMessage,
+    ) -> Result<(), CompactFilterError> {
+        let message = RawNetworkMessage {
+            magic: self.config.network.magic(),
+            payload,
+        };
+        stream.write_all(&serialize(&message)).await?;
+        Ok(())
+    }
+
+    /// Receives the next message from the Bitcoin node. Pings are answered and the
+    /// messages unknown to this relay are skipped.
+    async fn receive(&self, stream: &mut TcpStream) -> Result<NetworkMessage, CompactFilterError> {
+        loop {
+            let message = match self.config.request_timeout.map(Duration::from_millis) {
+                Some(timeout) => tokio::time::timeout(timeout, read_message(stream))
+                    .await
+                    .map_err(|_| CompactFilterError::Timeout(timeout))??,
+                None => read_message(stream).await?,
+            };
+
+            let message = match deserialize::<RawNetworkMessage>(&message) {
+                Ok(message) => message,
+                Err(encode::Error::UnrecognizedNetworkCommand(command)) => {
+                    tracing::trace!("Unexpected Bitcoin node message: {}", command);
+                    continue;
+                }
+                Err(encode::Error::UnknownInventoryType(_)) => continue,
+                Err(e) => return Err(e.into()),
+            };
+            if message.magic != self.config.network.magic() {
+                return Err(CompactFilterError::invalid_response(
+                    "Bitcoin node belongs to another network",
+                ));
+            }
+
+            match message.payload {
+                NetworkMessage::Ping(nonce) => {
+                    self.send(stream, NetworkMessage::Pong(nonce)).await?
+                }
+                payload => return Ok(payload),
+            }
+        }
+    }
+
+    async fn request(
+        &self,
+        state: &mut ChainState,
+        payload: NetworkMessage,
+    ) -> Result<(), CompactFilterError> {
+        let connection = state
+            .connection
+            .as_mut()
+            .ok_or_else(|| io::Error::from(io::ErrorKind::NotConnected))?;
+        self.send(connection, payload).await
+    }
+
+    async fn response(&self, state: &mut ChainState) -> Result<NetworkMessage, CompactFilterError> {
+        let connection = state
+            .connection
+            .as_mut()
+            .ok_or_else(|| io::Error::from(io::ErrorKind::NotConnected))?;
+        self.receive(connection).await
+    }
+
+    async fn sync_with_reconnect(&self, state: &mut ChainState) -> Result<(), CompactFilterError> {
+        let mut is_retry = false;
+        loop {
+            let result = self.synchronize(state).await;
+            // The connection may contain the partially read response.
+            if result.is_err() {
+                state.connection = None;
+            }
+            match result {
+                Err(CompactFilterError::Io(e)) if !is_retry => {
+                    tracing::warn!(
+                        "Connection to the Bitcoin node {} has been lost: {}",
+                        self.config.peer,
+                        e
+                    );
+                    is_retry = true;
+                }
+                result => return result,
+            }
+        }
+    }
+
+    async fn synchronize(&self, state: &mut ChainState) -> Result<(), CompactFilterError> {
+        if state.connection.is_none() {
+            state.connection = Some(self.connect().await?);
+        }
+        self.sync_headers(state).await?;
+        self.scan_filters(state).await
+    }
+
+    async fn sync_headers(&self, state: &mut ChainState) -> Result<(), CompactFilterError> {
+        loop {
+            let message = GetHeadersMessage::new(locator(&state.headers), BlockHash::default());
+            self.request(state, NetworkMessage::GetHeaders(message))
+                .await?;
+            let headers = loop {
+                if let NetworkMessage::Headers(headers) = self.response(state).await? {
+                    break headers;
+                }
+            };
+
+            let is_last_batch = headers.len() < MAX_HEADERS;
+            if !self.connect_headers(state, headers)? || is_last_batch {
+                return Ok(());
+            }
+        }
+    }
+
+    /// Appends the headers to the chain if they are valid. If the headers belong to
+    /// a fork, they replace the stored ones only if the fork is longer.
+    ///
+    /// Returns `false` if the stored chain has not been changed.
+    fn connect_headers(
+        &self,
+        state: &mut ChainState,
+        headers: Vec<bitcoin::BlockHeader>,
+    ) -> Result<bool, CompactFilterError> {
+        let headers = headers
+            .into_iter()
+            .map(btc::BlockHeader)
+            .collect::<Vec<_>>();
+        let first = if let Some(first) = headers.first() {
+            first
+        } else {
+            return Ok(false);
+        };
+        let fork_index = state
+            .headers
+            .iter()
+            .rposition(|header| header.block_hash() == first.prev_block_hash())
+            .ok_or_else(|| {
+                CompactFilterError::invalid_response("Headers don't connect to the known chain")
+            })?;
+        if fork_index + headers.len() < state.headers.len() {
+            return Ok(false);
+        }
+
+        let fork_height = self.base_height + fork_index as u64;
+        let interval = Params::new(self.config.network).difficulty_adjustment_interval();
+        // The regular header defines the target of the following blocks which don't have
+        // the minimal difficulty, so look for it among the stored headers.
+        let mut last_regular = state.headers[..=fork_index]
+            .iter()
+            .enumerate()
+            .rev()
+            .find(|(i, header)| {
+                header.is_regular(self.base_height + *i as u64, self.config.network)
+            })
+            .map(|(_, header)| header);
+        for (i, header) in headers.iter().enumerate() {
+            let height = fork_height + i as u64 + 1;
+            let prev = if i == 0 {
+                &state.headers[fork_index]
+            } else {
+                &headers[i - 1]
+            };
+            if header.prev_block_hash() != prev.block_hash() {
+                return Err(btc::HeaderChainError::Discontinuity { height }.into());
+            }
+            let period_start = height
+                .checked_sub(interval)
+                .filter(|_| height % interval == 0)
+                .and_then(|start| {
+                    if start > fork_height {
+                        headers.get((start - fork_height - 1) as usize)
+                    } else {
+                        state
+                            .headers
+                            .get(start.checked_sub(self.base_height)? as usize)
+                    }
+                });
+            header.verify_proof_of_work(
+                Some(prev),
+                period_start,
+                last_regular,
+                height,
+                self.config.network,
+            )?;
+            if header.is_regular(height, self.config.network) {
+                last_regular = Some(header);
+            }
+        }
+
+        if fork_index + 1 < state.headers.len() {
+            tracing::warn!("Bitcoin chain reorganization at height {}", fork_height);
+            state.headers.truncate(fork_index + 1);
+            // Transactions of the disconnected blocks return to the mempool.
+            let disconnected = state
+                .confirmed
+                .iter()
+                .filter(|(_, tx)| tx.height > fork_height)
+                .map(|(txid, _)| *txid)
+                .collect::<Vec<_>>();
+            for txid in disconnected {
+                let tx = state.confirmed.remove(&txid).unwrap();
+                state.sent.insert(txid, tx.transaction);
+            }
+            state.scanned_height = state.scanned_height.map(|h| h.min(fork_height));
+        }
+        state.headers.extend(headers);
+        Ok(true)
+    }
+
+    /// Checks the filters of the blocks which have not been checked yet and downloads
+    /// the matching blocks.
+    async fn scan_filters(&self, state: &mut ChainState) -> Result<(), CompactFilterError> {
+        let tip_height = self.tip_height(state);
+        if state.watched_scripts.is_empty() {
+            state.scanned_height = Some(tip_height);
+            return Ok(());
+        }
+
+        let mut start_height = state
+            .scanned_height
+            .map_or(self.base_height, |height| height + 1);
+        while start_height <= tip_height {
+            let stop_height = tip_height.min(start_height + MAX_FILTERS - 1);
+            let stop_hash = self.header(state, stop_height).block_hash();
+            let message = GetCFilters {
+                filter_type: BASIC_FILTER_TYPE,
+                start_height: start_height as u32,
+                stop_hash: BlockHash::from_inner(stop_hash.0.into_inner()),
+            };
+            self.request(state, NetworkMessage::GetCFilters(message))
+                .await?;
+
+            let mut matched_heights = Vec::new();
+            for height in start_height..=stop_height {
+                let filter = loop {
+                    if let NetworkMessage::CFilter(filter) = self.response(state).await? {
+                        break filter;
+                    }
+                };
+                let block_hash = self.header(state, height).block_hash();
+                if btc::Sha256d(filter.block_hash.as_hash()) != block_hash {
+                    return Err(CompactFilterError::invalid_response(format!(
+                        "Unexpected filter for the block with height {}",
+                        height
+                    )));
+                }
+
+                let mut scripts = state.watched_scripts.iter().map(Vec::as_slice);
+                if BlockFilter::new(&filter.filter)
+                    .match_any(&filter.block_hash, &mut scripts)
+                    .map_err(CompactFilterError::invalid_response)?
+                {
+                    matched_heights.push(height);
+                }
+            }
+
+            for height in matched_heights {
+                self.scan_block(state, height).await?;
+            }
+            state.scanned_height = Some(stop_height);
+            start_height = stop_height + 1;
+        }
+        Ok(())
+    }
+
+    /// Downloads the block with the given height and stores its watched transactions.
+    async fn scan_block(
+        &self,
+        state: &mut ChainState,
+        height: u64,
+    ) -> Result<(), CompactFilterError> {
+        let block_hash = self.header(state, height).block_hash();
+        let inventory = Inventory::WitnessBlock(BlockHash::from_inner(block_hash.0.into_inner()));
+        self.request(state, NetworkMessage::GetData(vec![inventory]))
+            .await?;
+        let block = loop {
+            match self.response(state).await? {
+                NetworkMessage::Block(block)
+                    if btc::BlockHeader(block.header).block_hash() == block_hash =>
+                {
+                    break block
+                }
+                NetworkMessage::NotFound(_) => {
+                    return Err(CompactFilterError::invalid_response(format!(
+                        "Block {} is not found",
+                        block_hash
+                    )))
+                }
+                _ => {}
+            }
+        };
+        if !block.check_merkle_root() {
+            return Err(CompactFilterError::invalid_response(format!(
+                "Block {} doesn't match its Merkle root",
+                block_hash
+            )));
+        }
+
+        for transaction in &block.txdata {
+            let txid = btc::Sha256d::from(transaction.txid());
+            let is_watched = state.sent.contains_key(&txid)
+                || transaction.output.iter().any(|output| {
+                    state
+                        .watched_scripts
+                        .contains(output.script_pubkey.as_bytes())
+                })
+                || transaction.input.iter().any(|input| {
+                    state
+                        .confirmed
+                        .contains_key(&input.previous_output.txid.into())
+                });
+            if !is_watched {
+                continue;
+            }
+
+            let matches = std::iter::once(transaction.txid()).collect();
+            let proof = merkleblock::MerkleBlock::from_block(&block, &matches);
+            state.sent.remove(&txid);
+            state.confirmed.insert(
+                txid,
+                ConfirmedTransaction {
+                    height,
+                    transaction: transaction.clone().into(),
+                    proof: btc::MerkleBlock(proof),
+                },
+            );
+        }
+        Ok(())
+    }
+}
+
+/// Reads the next raw P2P message from the stream.
+async fn read_message(stream: &mut TcpStream) -> Result<Vec<u8>, CompactFilterError> {
+    let mut message = vec![0_u8; MESSAGE_HEADER_SIZE];
+    stream.read_exact(&mut message).await?;
+    let payload_size = u32::from_le_bytes(message[16..20].try_into().unwrap()) as usize;
+    if payload_size > MAX_PAYLOAD_SIZE {
+        return Err(CompactFilterError::invalid_response("Message is too large"));
+    }
+    message.resize(MESSAGE_HEADER_SIZE + payload_size, 0);
+    stream
+        .read_exact(&mut message[MESSAGE_HEADER_SIZE..])
+        .await?;
+    Ok(message)
+}
+
+/// Returns the block locator for the given chain: hashes of the latest blocks followed
+/// by the exponentially sparser ones and the first block.
+fn locator(headers: &[btc::BlockHeader]) -> Vec<BlockHash> {
+    let mut locator = Vec::new();
+    let mut index = headers.len() - 1;
+    let mut step = 1;
+    loop {
+        let hash = headers[index].block_hash();
+        locator.push(BlockHash::from_inner(hash.0.into_inner()));
+        if index == 0 {
+            return locator;
+        }
+        if locator.len() >= 10 {
+            step *= 2;
+        }
+        index = index.saturating_sub(step);
+    }
+}
+
+#[async_trait]
+impl BitcoinRelay for CompactFilterRelay {
+    type Error = CompactFilterError;
+
+    async fn send_transaction(
+        &self,
+        transaction: &btc::Transaction,
+    ) -> Result<btc::Sha256d, Self::Error> {
+        let mut state = self.state.lock().await;
+        let mut is_retry = false;
+        loop {
+            if state.connection.is_none() {
+                state.connection = Some(self.connect().await?);
+            }
+            match self
+                .request(&mut state, NetworkMessage::Tx(transaction.0.clone()))
+                .await
+            {
+                Err(CompactFilterError::Io(_)) if !is_retry => {
+                    state.connection = None;
+                    is_retry = true;
+                }
+                result => break result?,
+            }
+        }
+
+        for output in &transaction.0.output {
+            Self::watch_script(&mut state, output.script_pubkey.clone().into_bytes());
+        }
+        let txid = transaction.id();
+        if !state.confirmed.contains_key(&txid) {
+            state.sent.insert(txid, transaction.clone());
+        }
+        Ok(txid)
+    }
+
+    async fn transaction_status(&self, id: btc::Sha256d) -> Result<TransactionStatus, Self::Error> {
+        let mut state = self.state.lock().await;
+        self.sync_with_reconnect(&mut state).await?;
+        Ok(if let Some(transaction) = state.confirmed.get(&id) {
+            TransactionStatus::Committed((self.tip_height(&state) - transaction.height + 1) as u32)
+        } else if state.sent.contains_key(&id) {
+            TransactionStatus::Mempool
+        } else {
+            TransactionStatus::Unknown
+        })
+    }
+
+    async fn transaction_proof(
+        &self,
+        id: btc::Sha256d,
+    ) -> Result<Option<btc::MerkleBlock>, Self::Error> {
+        let mut state = self.state.lock().await;
+        self.sync_with_reconnect(&mut state).await?;
+        Ok(state
+            .confirmed
+            .get(&id)
+            .map(|transaction| transaction.proof.clone()))
+    }
+
+    async fn raw_transaction(
+        &self,
+        id: btc::Sha256d,
+    ) -> Result<Option<btc::Transaction>, Self::Error> {
+        let mut state = self.state.lock().await;
+        self.sync_with_reconnect(&mut state).await?;
+        Ok(state
+            .confirmed
+            .get(&id)
+            .map(|transaction| transaction.transaction.clone())
+            .or_else(|| state.sent.get(&id).cloned()))
+    }
+
+    /// Only the spending transactions sent via this relay or matched by the filters
+    /// of the watched scripts and transactions are known.
+    async fn is_output_spent(
+        &self,
+        txid: btc::Sha256d,
+        vout: u32,
+    ) -> Result<Option<bool>, Self::Error> {
+        let mut state = self.state.lock().await;
+        self.sync_with_reconnect(&mut state).await?;
+        if !state.confirmed.contains_key(&txid) && !state.sent.contains_key(&txid) {
+            return Ok(None);
+        }
+        let confirmed = state
+            .confirmed
+            .values()
+            .map(|transaction| &transaction.transaction);
+        let is_spent = confirmed.chain(state.sent.values()).any(|transaction| {
+            transaction.0.input.iter().any(|input| {
+                btc::Sha256d::from(input.previous_output.txid) == txid
+                    && input.previous_output.vout == vout
+            })
+        });
+        Ok(Some(is_spent))
+    }
+
+    /// Fee estimation is not provided by the Bitcoin peer-to-peer protocol, so this relay
+    /// should be combined with another one to estimate fees.
+    async fn fee_rate(&self, _target_blocks: u16) -> Result<Option<u64>, Self::Error> {
+        Ok(None)
+    }
+}
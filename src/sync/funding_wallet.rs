@@ -0,0 +1,129 @@
+// Copyright 2020 The Exonum Team
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Wallet with the operational Bitcoins used to fund the anchoring.
+
+use async_trait::async_trait;
+use bitcoin::Amount;
+use bitcoincore_rpc::RpcApi;
+use jsonrpc::error::RpcError;
+
+use std::collections::HashMap;
+
+use crate::btc;
+
+/// Describes the wallet which holds the operational Bitcoins outside of the anchoring
+/// multisig address and is able to construct funding transactions on demand.
+#[async_trait]
+pub trait FundingWallet {
+    /// Error type for the current wallet implementation.
+    type Error;
+    /// Returns the confirmed balance of the wallet in satoshis.
+    async fn balance(&self) -> Result<u64, Self::Error>;
+    /// Creates, signs and broadcasts the transaction which sends the specified amount
+    /// of satoshis to the given address.
+    async fn send_to_address(
+        &self,
+        address: &btc::Address,
+        amount: u64,
+    ) -> Result<btc::Transaction, Self::Error>;
+    /// Creates and signs the transaction which sends the specified amount of satoshis
+    /// to the given address, but doesn't broadcast it to the Bitcoin network.
+    async fn create_transaction(
+        &self,
+        address: &btc::Address,
+        amount: u64,
+    ) -> Result<btc::Transaction, Self::Error>;
+}
+
+/// Wallet of the `bitcoind` node, e.g. the descriptor wallet. To use the specific
+/// wallet, the client should be connected to the corresponding wallet endpoint,
+/// like `http://localhost:18332/wallet/anchoring`.
+///
+/// The implementation is blocking, use [`BitcoindRelay`](struct.BitcoindRelay.html)
+/// connected to the wallet endpoint to avoid stalling the runtime thread.
+#[async_trait]
+impl FundingWallet for bitcoincore_rpc::Client {
+    type Error = bitcoincore_rpc::Error;
+
+    async fn balance(&self) -> Result<u64, Self::Error> {
+        wallet_balance(self)
+    }
+
+    async fn send_to_address(
+        &self,
+        address: &btc::Address,
+        amount: u64,
+    ) -> Result<btc::Transaction, Self::Error> {
+        wallet_send_to_address(self, address, amount)
+    }
+
+    async fn create_transaction(
+        &self,
+        address: &btc::Address,
+        amount: u64,
+    ) -> Result<btc::Transaction, Self::Error> {
+        wallet_create_transaction(self, address, amount)
+    }
+}
+
+pub(super) fn wallet_balance(client: &impl RpcApi) -> Result<u64, bitcoincore_rpc::Error> {
+    client.get_balance(Some(1), None).map(Amount::as_sat)
+}
+
+pub(super) fn wallet_send_to_address(
+    client: &impl RpcApi,
+    address: &btc::Address,
+    amount: u64,
+) -> Result<btc::Transaction, bitcoincore_rpc::Error> {
+    let txid = client.send_to_address(
+        &address.0,
+        Amount::from_sat(amount),
+        None,
+        None,
+        None,
+        None,
+        None,
+        None,
+    )?;
+    client
+        .get_raw_transaction(&txid, None)
+        .map(btc::Transaction::from)
+}
+
+pub(super) fn wallet_create_transaction(
+    client: &impl RpcApi,
+    address: &btc::Address,
+    amount: u64,
+) -> Result<btc::Transaction, bitcoincore_rpc::Error> {
+    let mut outputs = HashMap::new();
+    outputs.insert(address.0.to_string(), Amount::from_sat(amount));
+    let transaction = client.create_raw_transaction(&[], &outputs, None, None)?;
+    // The wallet selects the inputs and adds the change output.
+    let funded = client.fund_raw_transaction(&transaction, None, None)?;
+    let signed = client.sign_raw_transaction_with_wallet(&funded.hex, None, None)?;
+    if !signed.complete {
+        // The same code is used by `bitcoind` for the wallet errors.
+        return Err(jsonrpc::Error::Rpc(RpcError {
+            code: -4,
+            message: "Funding transaction cannot be completely signed by the wallet".to_owned(),
+            data: None,
+        })
+        .into());
+    }
+    signed
+        .transaction()
+        .map(btc::Transaction::from)
+        .map_err(From::from)
+}
@@ -0,0 +1,143 @@
+// Copyright 2020 The Exonum Team
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Signing of the anchoring proposals by the hardware wallets via [HWI].
+//!
+//! [HWI]: https://github.com/bitcoin-core/HWI
+
+use anyhow::{anyhow, bail, ensure};
+use async_trait::async_trait;
+use bitcoin::{
+    util::bip32::{DerivationPath, Fingerprint},
+    Network,
+};
+use serde_derive::{Deserialize, Serialize};
+
+use std::{path::PathBuf, process::Command};
+
+use super::PsbtSigner;
+use crate::btc;
+
+/// Configuration of the hardware wallet, e.g. Ledger or Trezor, accessed via the HWI
+/// command line tool.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct HwiConfig {
+    /// Path to the `hwi` executable.
+    #[serde(default = "HwiConfig::default_hwi_path")]
+    pub hwi_path: PathBuf,
+    /// Fingerprint of the device master key, e.g. `8038ecd9`.
+    #[serde(with = "serde_str")]
+    pub fingerprint: Fingerprint,
+    /// Derivation path of the anchoring key, e.g. `m/48'/1'/0'/2'/0/0`.
+    #[serde(with = "serde_str")]
+    pub derivation_path: DerivationPath,
+    /// Bitcoin public key of the anchoring node derived by the path above.
+    pub bitcoin_key: btc::PublicKey,
+}
+
+impl HwiConfig {
+    fn default_hwi_path() -> PathBuf {
+        PathBuf::from("hwi")
+    }
+}
+
+/// Response of the HWI command.
+#[derive(Debug, Deserialize)]
+struct HwiResponse {
+    psbt: Option<String>,
+    error: Option<String>,
+    code: Option<i64>,
+}
+
+/// Signer which delegates the signing of the anchoring proposals to the hardware wallet,
+/// so the Bitcoin private key never leaves the device.
+///
+/// The device may require a confirmation of each proposal from the operator, thus
+/// signing may take a while.
+#[derive(Debug, Clone)]
+pub struct HwiSigner {
+    config: HwiConfig,
+}
+
+impl HwiSigner {
+    /// Creates a new signer with the given configuration.
+    pub fn new(config: HwiConfig) -> Self {
+        Self { config }
+    }
+
+    async fn run(&self, args: Vec<String>) -> anyhow::Result<HwiResponse> {
+        let hwi_path = self.config.hwi_path.clone();
+        let output =
+            tokio::task::spawn_blocking(move || Command::new(hwi_path).args(args).output())
+                .await?
+                .map_err(|e| anyhow!("Unable to run HWI: {}", e))?;
+
+        let response = serde_json::from_slice::<HwiResponse>(&output.stdout).map_err(|_| {
+            anyhow!(
+                "Unexpected HWI output: {}",
+                String::from_utf8_lossy(&output.stderr)
+            )
+        })?;
+        if let Some(error) = response.error {
+            bail!(
+                "HWI error with code {}: {}",
+                response.code.unwrap_or_default(),
+                error
+            );
+        }
+        Ok(response)
+    }
+}
+
+#[async_trait]
+impl PsbtSigner for HwiSigner {
+    fn public_key(&self) -> btc::PublicKey {
+        self.config.bitcoin_key
+    }
+
+    async fn sign_psbt(&self, mut psbt: btc::Psbt, network: Network) -> anyhow::Result<btc::Psbt> {
+        // Devices find the key to sign with by its derivation path.
+        let key_source = (self.config.fingerprint, self.config.derivation_path.clone());
+        for input in &mut psbt.0.inputs {
+            input
+                .hd_keypaths
+                .insert(self.config.bitcoin_key.0, key_source.clone());
+        }
+
+        let chain = match network {
+            Network::Bitcoin => "main",
+            Network::Testnet => "test",
+            Network::Regtest => "regtest",
+        };
+        let args = vec![
+            "--fingerprint".to_owned(),
+            self.config.fingerprint.to_string(),
+            "--chain".to_owned(),
+            chain.to_owned(),
+            "signtx".to_owned(),
+            psbt.to_string(),
+        ];
+        let signed_psbt = self
+            .run(args)
+            .await?
+            .psbt
+            .ok_or_else(|| anyhow!("HWI has not returned the signed PSBT"))?
+            .parse::<btc::Psbt>()?;
+        ensure!(
+            signed_psbt.txid() == psbt.txid(),
+            "HWI has returned PSBT for the different transaction"
+        );
+        Ok(signed_psbt)
+    }
+}
@@ -0,0 +1,166 @@
+// Copyright 2020 The Exonum Team
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Metrics of the anchoring process exposed in the [Prometheus] text format.
+//!
+//! [Prometheus]: https://prometheus.io/docs/instrumenting/exposition_formats/
+
+use std::{
+    fmt::Write,
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+
+use crate::api::AnchoringStatistics;
+
+/// Metrics collected by the sync tasks.
+///
+/// The metrics are shared between the clones, so the same instance can be passed to
+/// several tasks and to the HTTP endpoint which exposes them.
+#[derive(Debug, Clone, Default)]
+pub struct SyncMetrics {
+    inner: Arc<Mutex<MetricsState>>,
+}
+
+#[derive(Debug, Default)]
+struct MetricsState {
+    /// Latest statistics of the anchoring service instance.
+    stats: Option<AnchoringStatistics>,
+    /// Total number of failed Bitcoin relay requests.
+    relay_errors: u64,
+    /// Total number of the anchoring proposals signed by this node.
+    signed_proposals: u64,
+    /// Total time spent on signing of the anchoring proposals.
+    sign_latency: Duration,
+    /// Number of the anchoring outputs spent by the transactions absent in
+    /// the anchoring chain, found by the latest check.
+    external_spends: u64,
+}
+
+impl SyncMetrics {
+    /// Creates an empty set of metrics.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Updates the metrics of the anchoring service instance.
+    pub fn update_stats(&self, stats: AnchoringStatistics) {
+        self.inner.lock().unwrap().stats = Some(stats);
+    }
+
+    /// Records the failed Bitcoin relay request.
+    pub fn record_relay_error(&self) {
+        self.inner.lock().unwrap().relay_errors += 1;
+    }
+
+    /// Records the signed anchoring proposal along with the time spent on its signing,
+    /// including the submission of the signatures.
+    pub fn record_signing(&self, latency: Duration) {
+        let mut state = self.inner.lock().unwrap();
+        state.signed_proposals += 1;
+        state.sign_latency += latency;
+    }
+
+    /// Records the number of the anchoring outputs spent by the transactions absent
+    /// in the anchoring chain.
+    pub fn record_external_spends(&self, count: usize) {
+        self.inner.lock().unwrap().external_spends = count as u64;
+    }
+
+    /// Returns the number of the anchoring outputs spent by the transactions absent
+    /// in the anchoring chain, found by the latest check.
+    pub fn external_spends(&self) -> u64 {
+        self.inner.lock().unwrap().external_spends
+    }
+
+    /// Returns the total number of failed Bitcoin relay requests.
+    pub fn relay_errors(&self) -> u64 {
+        self.inner.lock().unwrap().relay_errors
+    }
+
+    /// Returns the total number of the anchoring proposals signed by this node.
+    pub fn signed_proposals(&self) -> u64 {
+        self.inner.lock().unwrap().signed_proposals
+    }
+
+    /// Encodes the metrics in the Prometheus text format.
+    pub fn encode(&self) -> String {
+        let state = self.inner.lock().unwrap();
+        let mut out = String::new();
+        if let Some(stats) = &state.stats {
+            if let Some(lag) = stats.current_lag {
+                write_metric(
+                    &mut out,
+                    "btc_anchoring_lag_blocks",
+                    "gauge",
+                    "Number of Exonum blocks committed after the latest anchored block.",
+                    lag,
+                );
+            }
+            write_metric(
+                &mut out,
+                "btc_anchoring_chain_length",
+                "gauge",
+                "Total number of anchoring transactions.",
+                stats.transactions_count,
+            );
+            write_metric(
+                &mut out,
+                "btc_anchoring_balance_satoshis",
+                "gauge",
+                "Balance of the anchoring wallet.",
+                stats.balance,
+            );
+            write_metric(
+                &mut out,
+                "btc_anchoring_fees_paid_satoshis_total",
+                "counter",
+                "Total fee paid by anchoring transactions.",
+                stats.total_fee,
+            );
+        }
+        write_metric(
+            &mut out,
+            "btc_anchoring_relay_errors_total",
+            "counter",
+            "Total number of failed Bitcoin relay requests.",
+            state.relay_errors,
+        );
+        write_metric(
+            &mut out,
+            "btc_anchoring_external_spends",
+            "gauge",
+            "Number of anchoring outputs spent by transactions absent in the anchoring chain.",
+            state.external_spends,
+        );
+
+        let name = "btc_anchoring_sign_latency_seconds";
+        writeln!(
+            out,
+            "# HELP {} Time spent on signing of the anchoring proposals.",
+            name
+        )
+        .unwrap();
+        writeln!(out, "# TYPE {} summary", name).unwrap();
+        writeln!(out, "{}_sum {}", name, state.sign_latency.as_secs_f64()).unwrap();
+        writeln!(out, "{}_count {}", name, state.signed_proposals).unwrap();
+        out
+    }
+}
+
+fn write_metric(out: &mut String, name: &str, kind: &str, help: &str, value: u64) {
+    writeln!(out, "# HELP {} {}", name, help).unwrap();
+    writeln!(out, "# TYPE {} {}", name, kind).unwrap();
+    writeln!(out, "{} {}", name, value).unwrap();
+}
@@ -14,26 +14,105 @@
 
 //! Building blocks of the anchoring sync utility.
 
-pub use self::bitcoin_relay::{BitcoinRelay, TransactionStatus};
+#[cfg(feature = "electrum")]
+pub use self::electrum_relay::{ElectrumError, ElectrumRelay, ElectrumRelayConfig};
+#[cfg(feature = "pkcs11")]
+pub use self::pkcs11_signer::{Pkcs11Config, Pkcs11KeyConfig, Pkcs11Signer};
+pub use self::{
+    audit_relay::{AuditRelay, RelayAuditLog, RelayAuditRecord},
+    bitcoin_notifications::{
+        BitcoinNotification, BitcoinNotifications, ZmqNotificationsConfig, ZmqSubscriber,
+    },
+    bitcoin_relay::{BitcoinRelay, BroadcastAllRelay, BroadcastError, TransactionStatus},
+    bitcoind_relay::{BitcoindRelay, BitcoindRelayConfig, BitcoindRelayError, BitcoindRpcClient},
+    broadcast_queue::{BroadcastQueue, QueuedTransaction},
+    filter_relay::{
+        CompactFilterError, CompactFilterRelay, CompactFilterRelayConfig, HeaderCheckpoint,
+    },
+    funding_wallet::FundingWallet,
+    hwi_signer::{HwiConfig, HwiSigner},
+    metrics::SyncMetrics,
+    proxy::ProxyConfig,
+    runner::{SyncRunner, SyncRunnerConfig, SyncRunnerHandle},
+    signer::{
+        HttpSigner, LocalSigner, RemoteSignerConfig, RemoteSignerProtocol, Signer, SigningRequest,
+        SigningResponse,
+    },
+    sync_state::{SyncState, SyncStateStore},
+};
 
-use anyhow::anyhow;
-use btc_transaction_utils::{p2wsh, TxInRef};
+use anyhow::{anyhow, ensure};
+use async_trait::async_trait;
+use exonum::{crypto::Hash, helpers::Height};
+use exonum_rust_runtime::api;
+use serde_derive::{Deserialize, Serialize};
 
-use std::{collections::HashMap, fmt::Display, sync::Arc};
+use std::{
+    cmp,
+    fmt::{Debug, Display},
+    sync::Mutex,
+    time::{Instant, SystemTime},
+};
 
 use crate::{
-    api::{AnchoringProposalState, PrivateApi},
-    blockchain::SignInput,
+    api::{AnchoringProposalState, ApiErrorKind, PrivateApi},
+    blockchain::{AddFunds, SignInput},
     btc,
     config::Config,
 };
 
+pub mod recover_from_bitcoin;
+
+mod audit_relay;
+mod bitcoin_notifications;
 mod bitcoin_relay;
+mod bitcoind_relay;
+mod broadcast_queue;
+#[cfg(feature = "electrum")]
+mod electrum_relay;
+mod filter_relay;
+mod funding_wallet;
+mod hwi_signer;
+mod metrics;
+#[cfg(feature = "pkcs11")]
+mod pkcs11_signer;
+mod proxy;
+mod runner;
+mod signer;
+mod sync_state;
 
 /// Anchoring transaction with its index in the anchoring chain.
 pub type TransactionWithIndex = (btc::Transaction, u64);
 
-type KeyPool = Arc<HashMap<btc::PublicKey, btc::PrivateKey>>;
+/// Error of the anchoring API client, which may carry the typed error code
+/// returned by the anchoring API.
+pub trait ApiClientError: Display {
+    /// Returns the kind of the error returned by the anchoring API, or `None`
+    /// if the request has failed before reaching the API, e.g. due to network problems.
+    fn api_error_kind(&self) -> Option<ApiErrorKind>;
+
+    /// Returns `true` if the failed request may succeed if retried later.
+    fn is_retriable(&self) -> bool {
+        match self.api_error_kind() {
+            Some(kind) => kind.is_transient(),
+            None => true,
+        }
+    }
+}
+
+impl ApiClientError for api::Error {
+    fn api_error_kind(&self) -> Option<ApiErrorKind> {
+        ApiErrorKind::of(self)
+    }
+
+    fn is_retriable(&self) -> bool {
+        // Errors of other services do not carry the anchoring error codes.
+        self.api_error_kind().map_or(
+            !self.http_code.is_client_error(),
+            ApiErrorKind::is_transient,
+        )
+    }
+}
 
 /// Errors that occur when updating the anchoring chain.
 #[derive(Debug)]
@@ -53,6 +132,125 @@ pub enum ChainUpdateError<C: Display> {
     Internal(anyhow::Error),
 }
 
+impl<C: ApiClientError> ChainUpdateError<C> {
+    /// Returns `true` if the anchoring chain update may succeed if retried later.
+    pub fn is_retriable(&self) -> bool {
+        match self {
+            ChainUpdateError::Client(e) => e.is_retriable(),
+            ChainUpdateError::InsufficientFunds { .. } | ChainUpdateError::NoInitialFunds => true,
+            ChainUpdateError::Internal(_) => false,
+        }
+    }
+}
+
+/// Policy of the fee rate voting by the anchoring node.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct FeeRatePolicy {
+    /// Number of Bitcoin blocks within which the anchoring transaction should be committed.
+    pub target_blocks: u16,
+    /// Deviation of the estimated fee rate from the fee rate of the anchoring proposal
+    /// in percents, which doesn't require a new vote.
+    pub tolerance: u64,
+}
+
+impl FeeRatePolicy {
+    /// Checks that the given fee rates differ by more than the tolerated deviation.
+    fn is_deviated(&self, fee_rate: u64, reference: u64) -> bool {
+        let deviation = cmp::max(fee_rate, reference) - cmp::min(fee_rate, reference);
+        deviation * 100 > reference * self.tolerance
+    }
+}
+
+/// Source of the fee rate estimations with the erased error type.
+#[async_trait]
+trait FeeRateSource: Debug + Send + Sync {
+    async fn estimate(&self, target_blocks: u16) -> anyhow::Result<Option<u64>>;
+}
+
+#[async_trait]
+impl<R> FeeRateSource for R
+where
+    R: BitcoinRelay + Debug + Send + Sync,
+    R::Error: Display,
+{
+    async fn estimate(&self, target_blocks: u16) -> anyhow::Result<Option<u64>> {
+        self.fee_rate(target_blocks)
+            .await
+            .map_err(|e| anyhow!("{}", e))
+    }
+}
+
+#[derive(Debug)]
+struct FeeEstimation {
+    source: Box<dyn FeeRateSource>,
+    policy: FeeRatePolicy,
+    /// The latest fee rate voted by this node.
+    latest_vote: Mutex<Option<u64>>,
+}
+
+impl FeeEstimation {
+    /// Returns the estimated fee rate if it deviates from the fee rate of the proposal.
+    async fn deviated_fee_rate(&self, proposal_fee_rate: u64) -> Option<u64> {
+        let fee_rate = match self.source.estimate(self.policy.target_blocks).await {
+            Ok(Some(fee_rate)) => cmp::max(fee_rate, Config::MIN_TX_FEE),
+            Ok(None) => return None,
+            Err(e) => {
+                tracing::warn!(error = %e, "Unable to estimate the anchoring transaction fee.");
+                return None;
+            }
+        };
+        Some(fee_rate).filter(|&fee_rate| self.policy.is_deviated(fee_rate, proposal_fee_rate))
+    }
+}
+
+/// Signer of the anchoring proposals in the PSBT format, which keeps the Bitcoin private
+/// key outside of the anchoring node, e.g. a hardware wallet.
+#[async_trait]
+pub trait PsbtSigner: Debug + Send + Sync {
+    /// Returns the Bitcoin public key of the signer.
+    fn public_key(&self) -> btc::PublicKey;
+    /// Signs all inputs of the given PSBT for the given Bitcoin network.
+    async fn sign_psbt(
+        &self,
+        psbt: btc::Psbt,
+        network: bitcoin::Network,
+    ) -> anyhow::Result<btc::Psbt>;
+}
+
+#[derive(Debug)]
+struct PsbtSigning {
+    signer: Box<dyn PsbtSigner>,
+    /// Identifier of the latest proposal signed by the signer.
+    latest_signed: Mutex<Option<btc::Sha256d>>,
+}
+
+/// Source of the signatures for the anchoring proposal.
+enum ProposalSigner<'a> {
+    Signer(&'a dyn Signer, btc::PublicKey),
+    Psbt(&'a PsbtSigning),
+}
+
+/// Report of the anchoring chain update performed without submitting the signatures.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct DryRunReport {
+    /// Anchoring transaction proposal.
+    pub proposal: btc::Transaction,
+    /// Height of the Exonum block anchored by the proposal.
+    pub block_height: Height,
+    /// Fee of the proposal in satoshis.
+    pub fee: u64,
+    /// Fee rate of the proposal in satoshis per byte.
+    pub fee_rate: u64,
+    /// Estimated fee rate which the node would vote for, if the fee estimation is enabled
+    /// and the estimated fee rate deviates from the fee rate of the proposal.
+    pub fee_rate_vote: Option<u64>,
+    /// Bitcoin key by which the node would sign the proposal, or `None` if the node
+    /// is unable to sign it.
+    pub bitcoin_key: Option<btc::PublicKey>,
+    /// Indices of the proposal inputs which the node would sign.
+    pub signed_inputs: Vec<u32>,
+}
+
 /// Signs the inputs of the anchoring transaction proposal by the corresponding
 /// Bitcoin private keys.
 #[derive(Debug)]
@@ -60,8 +258,11 @@ pub struct AnchoringChainUpdateTask<T>
 where
     T: PrivateApi + 'static,
 {
-    key_pool: KeyPool,
+    signers: Vec<Box<dyn Signer>>,
     api_client: T,
+    fee_estimation: Option<FeeEstimation>,
+    psbt_signing: Option<PsbtSigning>,
+    metrics: Option<SyncMetrics>,
 }
 
 impl<T> AnchoringChainUpdateTask<T>
@@ -69,26 +270,76 @@ where
     T: PrivateApi + 'static,
     T::Error: Display,
 {
-    /// Creates a new anchoring chain updater instance.
+    /// Creates a new anchoring chain updater instance, which signs the proposals by
+    /// the given Bitcoin keys kept in memory.
     pub fn new(
         keys: impl IntoIterator<Item = (btc::PublicKey, btc::PrivateKey)>,
         api_client: T,
     ) -> Self {
         Self {
-            key_pool: Arc::new(keys.into_iter().collect()),
+            signers: vec![Box::new(LocalSigner::new(keys))],
             api_client,
+            fee_estimation: None,
+            psbt_signing: None,
+            metrics: None,
         }
     }
 
+    /// Records the signing latency of the anchoring proposals to the given metrics.
+    pub fn with_metrics(mut self, metrics: SyncMetrics) -> Self {
+        self.metrics = Some(metrics);
+        self
+    }
+
+    /// Adds the signer of the anchoring proposals, e.g. the remote signing service, which
+    /// is used if the previously added signers, including the key pool, are unable to sign
+    /// by the Bitcoin key of the anchoring node.
+    pub fn with_signer(mut self, signer: impl Signer + 'static) -> Self {
+        self.signers.push(Box::new(signer));
+        self
+    }
+
+    /// Delegates the signing of the anchoring proposals to the given signer, e.g. a hardware
+    /// wallet, if none of the signers is able to sign by the Bitcoin key of the anchoring node.
+    ///
+    /// Each proposal is passed to the signer only once, since the signer may require
+    /// the confirmation from the operator.
+    pub fn with_psbt_signer(mut self, signer: impl PsbtSigner + 'static) -> Self {
+        self.psbt_signing = Some(PsbtSigning {
+            signer: Box::new(signer),
+            latest_signed: Mutex::default(),
+        });
+        self
+    }
+
+    /// Enables voting for the fee rate of the anchoring transactions estimated by the given
+    /// Bitcoin relay.
+    ///
+    /// The fee rate of the anchoring transactions is agreed by the anchoring nodes, so
+    /// the estimated fee rate is voted for if it deviates from the fee rate of the actual
+    /// proposal, while the proposal is still signed. The proposal is replaced by the one
+    /// with the new fee rate as soon as a byzantine majority of the anchoring nodes has voted.
+    pub fn with_fee_estimation<R>(mut self, btc_relay: R, policy: FeeRatePolicy) -> Self
+    where
+        R: BitcoinRelay + Debug + Send + Sync + 'static,
+        R::Error: Display,
+    {
+        self.fee_estimation = Some(FeeEstimation {
+            source: Box::new(btc_relay),
+            policy,
+            latest_vote: Mutex::default(),
+        });
+        self
+    }
+
     /// Returns an actual anchoring configuration.
     pub async fn anchoring_config(&self) -> Result<Config, T::Error> {
         self.api_client.config().await
     }
 
     /// Performs one attempt to sign an anchoring proposal, if any.
+    #[tracing::instrument(name = "chain_update", level = "debug", skip(self))]
     pub async fn process(&self) -> Result<(), ChainUpdateError<T::Error>> {
-        log::trace!("Perform an anchoring chain update");
-
         match self
             .api_client
             .anchoring_proposal()
@@ -96,6 +347,10 @@ where
             .map_err(ChainUpdateError::Client)?
         {
             AnchoringProposalState::None => Ok(()),
+            AnchoringProposalState::Frozen => {
+                tracing::warn!("Anchoring is frozen, the proposals are not signed.");
+                Ok(())
+            }
             AnchoringProposalState::Available {
                 transaction,
                 inputs,
@@ -113,77 +368,273 @@ where
         }
     }
 
+    /// Computes the actual anchoring proposal, its fee and the inputs which this node
+    /// would sign, without submitting the signatures and the fee rate vote to the anchoring
+    /// node. Returns `None` if there is no proposal to sign.
+    ///
+    /// The signatures are created to make sure that the signers work, except for the PSBT
+    /// signer, which may require the confirmation from the operator.
+    #[tracing::instrument(name = "chain_update_dry_run", level = "debug", skip(self))]
+    pub async fn process_dry_run(
+        &self,
+    ) -> Result<Option<DryRunReport>, ChainUpdateError<T::Error>> {
+        let (proposal, inputs) = match self
+            .api_client
+            .anchoring_proposal()
+            .await
+            .map_err(ChainUpdateError::Client)?
+        {
+            AnchoringProposalState::None | AnchoringProposalState::Frozen => return Ok(None),
+            AnchoringProposalState::Available {
+                transaction,
+                inputs,
+            } => (transaction, inputs),
+            AnchoringProposalState::InsufficientFunds { balance, total_fee } => {
+                return Err(ChainUpdateError::InsufficientFunds { balance, total_fee })
+            }
+            AnchoringProposalState::NoInitialFunds => return Err(ChainUpdateError::NoInitialFunds),
+        };
+        let config = self
+            .anchoring_config()
+            .await
+            .map_err(ChainUpdateError::Client)?;
+
+        let block_height = Self::block_height(&proposal)?;
+        let fee = proposal.fee(&inputs).unwrap_or_default();
+        let fee_rate = fee / proposal.size();
+        let fee_rate_vote = match &self.fee_estimation {
+            Some(estimation) => estimation.deviated_fee_rate(fee_rate).await,
+            None => None,
+        };
+        let (bitcoin_key, signed_inputs) = match self.proposal_signer(&config) {
+            Some(ProposalSigner::Signer(signer, bitcoin_key)) => {
+                let request = SigningRequest {
+                    bitcoin_key,
+                    redeem_script: config.redeem_script(),
+                    proposal: proposal.clone(),
+                    inputs,
+                };
+                let sign_inputs = Self::sign(signer, request)
+                    .await
+                    .map_err(ChainUpdateError::Internal)?;
+                let signed_inputs = sign_inputs.iter().map(|sign_input| sign_input.input);
+                (Some(bitcoin_key), signed_inputs.collect())
+            }
+            Some(ProposalSigner::Psbt(signing)) => (
+                Some(signing.signer.public_key()),
+                (0..inputs.len() as u32).collect(),
+            ),
+            None => (None, Vec::new()),
+        };
+
+        Ok(Some(DryRunReport {
+            proposal,
+            block_height,
+            fee,
+            fee_rate,
+            fee_rate_vote,
+            bitcoin_key,
+            signed_inputs,
+        }))
+    }
+
+    #[tracing::instrument(
+        name = "anchoring_proposal",
+        level = "debug",
+        skip_all,
+        fields(proposal = %proposal.id())
+    )]
     async fn handle_proposal(
         &self,
         config: Config,
         proposal: btc::Transaction,
         inputs: Vec<btc::Transaction>,
     ) -> Result<(), ChainUpdateError<T::Error>> {
-        log::trace!("Got an anchoring proposal: {:?}", proposal);
-        // Find among the keys one from which we have a private part.
-        // TODO What we have to do if we find more than one key? [ECR-3222]
-        let keypair = if let Some(keypair) =
-            self.find_private_key(config.anchoring_keys.iter().map(|x| x.bitcoin_key))
-        {
-            keypair
-        } else {
-            return Ok(());
+        tracing::trace!(?proposal, "Got an anchoring proposal.");
+        let signer = match self.proposal_signer(&config) {
+            Some(signer) => signer,
+            None => return Ok(()),
         };
-        // Create `SignInput` transactions.
+        // Create input signatures.
         let redeem_script = config.redeem_script();
-        let block_height = match proposal.anchoring_payload() {
-            Some(payload) => payload.block_height,
-            None => {
-                return Err(ChainUpdateError::Internal(anyhow!(
-                    "Incorrect anchoring proposal found: {:?}",
-                    proposal
-                )))
+        let block_height = Self::block_height(&proposal)?;
+
+        tracing::info!(
+            height = %block_height,
+            "Found a new unfinished anchoring transaction proposal."
+        );
+        if let Some(estimation) = &self.fee_estimation {
+            self.vote_fee_rate(estimation, &proposal, &inputs).await?;
+        }
+
+        let signing_start = Instant::now();
+        let sign_input_messages = match &signer {
+            ProposalSigner::Signer(signer, bitcoin_key) => {
+                let request = SigningRequest {
+                    bitcoin_key: *bitcoin_key,
+                    redeem_script,
+                    proposal: proposal.clone(),
+                    inputs,
+                };
+                Self::sign(*signer, request)
+                    .await
+                    .map_err(ChainUpdateError::Internal)?
+            }
+            ProposalSigner::Psbt(signing) => {
+                if *signing.latest_signed.lock().unwrap() == Some(proposal.id()) {
+                    return Ok(());
+                }
+                Self::sign_psbt(signing, &config, &proposal, &inputs)
+                    .await
+                    .map_err(ChainUpdateError::Internal)?
             }
         };
+        // Send all signatures to the Exonum node in a single transaction.
+        self.api_client
+            .sign_inputs(sign_input_messages)
+            .await
+            .map_err(ChainUpdateError::Client)?;
+        if let ProposalSigner::Psbt(signing) = signer {
+            *signing.latest_signed.lock().unwrap() = Some(proposal.id());
+        }
+        if let Some(metrics) = &self.metrics {
+            metrics.record_signing(signing_start.elapsed());
+        }
+        Ok(())
+    }
 
-        log::info!(
-            "Found a new unfinished anchoring transaction proposal for height: {}",
-            block_height
+    /// Signs the inputs of the anchoring proposal by the signer.
+    async fn sign(signer: &dyn Signer, request: SigningRequest) -> anyhow::Result<Vec<SignInput>> {
+        let txid = request.proposal.id();
+        let response = signer.sign(request.clone()).await?;
+        // Signatures of the remote signers are checked to not send the invalid ones
+        // to the anchoring node.
+        request.verify(&response.signatures)?;
+        tracing::debug!(
+            bitcoin_key = %request.bitcoin_key,
+            inputs = response.signatures.len(),
+            "Inputs of the anchoring proposal have been signed."
         );
-
-        let mut signer = p2wsh::InputSigner::new(redeem_script);
-        let sign_input_messages = inputs
-            .iter()
+        Ok(response
+            .signatures
+            .into_iter()
             .enumerate()
-            .map(|(index, proposal_input)| {
-                let signature = signer.sign_input(
-                    TxInRef::new(proposal.as_ref(), index),
-                    proposal_input.as_ref(),
-                    &(keypair.1).0.key,
-                )?;
-
-                Ok(SignInput {
-                    input: index as u32,
-                    input_signature: signature.into(),
-                    txid: proposal.id(),
-                })
+            .map(|(input, input_signature)| SignInput {
+                txid,
+                input: input as u32,
+                input_signature,
             })
-            .collect::<anyhow::Result<Vec<_>>>()
-            .map_err(ChainUpdateError::Internal)?;
-        // Send sign input transactions to the Exonum node.
-        for sign_input in sign_input_messages {
-            self.api_client
-                .sign_input(sign_input)
-                .await
-                .map_err(ChainUpdateError::Client)?;
+            .collect())
+    }
+
+    /// Signs the inputs of the anchoring proposal by the PSBT signer.
+    async fn sign_psbt(
+        signing: &PsbtSigning,
+        config: &Config,
+        proposal: &btc::Transaction,
+        inputs: &[btc::Transaction],
+    ) -> anyhow::Result<Vec<SignInput>> {
+        let psbt = btc::Psbt::from_proposal(proposal, inputs, &config.redeem_script())?;
+        let psbt = signing.signer.sign_psbt(psbt, config.network).await?;
+
+        let txid = proposal.id();
+        ensure!(
+            psbt.txid() == txid,
+            "Signer has returned PSBT for the different transaction"
+        );
+        let sign_inputs = psbt
+            .input_signatures(&signing.signer.public_key())?
+            .into_iter()
+            .map(|(input, input_signature)| SignInput {
+                txid,
+                input,
+                input_signature,
+            })
+            .collect::<Vec<_>>();
+        ensure!(
+            sign_inputs.len() == inputs.len(),
+            "Signer has not signed all inputs of the anchoring proposal {}",
+            txid
+        );
+        Ok(sign_inputs)
+    }
+
+    /// Votes for the estimated fee rate if it deviates from the fee rate of the proposal.
+    async fn vote_fee_rate(
+        &self,
+        estimation: &FeeEstimation,
+        proposal: &btc::Transaction,
+        inputs: &[btc::Transaction],
+    ) -> Result<(), ChainUpdateError<T::Error>> {
+        let policy = estimation.policy;
+        let proposal_fee_rate = proposal.fee(inputs).unwrap_or_default() / proposal.size();
+        let fee_rate = match estimation.deviated_fee_rate(proposal_fee_rate).await {
+            Some(fee_rate) => fee_rate,
+            None => return Ok(()),
+        };
+        // Don't vote again until the estimation changes, since other nodes may not have
+        // voted yet.
+        let latest_vote = *estimation.latest_vote.lock().unwrap();
+        if let Some(latest_vote) = latest_vote {
+            if !policy.is_deviated(fee_rate, latest_vote) {
+                return Ok(());
+            }
         }
+
+        tracing::info!(
+            fee_rate,
+            proposal_fee_rate,
+            "Vote for the fee rate of the anchoring transactions."
+        );
+        self.api_client
+            .vote_fee_rate(fee_rate)
+            .await
+            .map_err(ChainUpdateError::Client)?;
+        *estimation.latest_vote.lock().unwrap() = Some(fee_rate);
         Ok(())
     }
 
-    fn find_private_key(
+    /// Finds the signer of the proposal by one of the anchoring keys.
+    fn proposal_signer(&self, config: &Config) -> Option<ProposalSigner<'_>> {
+        // Find among the keys one from which we have a private part.
+        // TODO What we have to do if we find more than one key? [ECR-3222]
+        match self.find_signer(config.anchoring_keys.iter().map(|x| x.bitcoin_key)) {
+            Some((signer, bitcoin_key)) => Some(ProposalSigner::Signer(signer, bitcoin_key)),
+            None => self
+                .psbt_signing
+                .as_ref()
+                .filter(|signing| {
+                    let public_key = signing.signer.public_key();
+                    config
+                        .anchoring_keys
+                        .iter()
+                        .any(|keys| keys.bitcoin_key == public_key)
+                })
+                .map(ProposalSigner::Psbt),
+        }
+    }
+
+    /// Returns the height of the block anchored by the proposal.
+    fn block_height(proposal: &btc::Transaction) -> Result<Height, ChainUpdateError<T::Error>> {
+        match proposal.anchoring_payload() {
+            Some(payload) => Ok(payload.block_height),
+            None => Err(ChainUpdateError::Internal(anyhow!(
+                "Incorrect anchoring proposal found: {:?}",
+                proposal
+            ))),
+        }
+    }
+
+    fn find_signer(
         &self,
-        anchoring_keys: impl IntoIterator<Item = btc::PublicKey>,
-    ) -> Option<(btc::PublicKey, btc::PrivateKey)> {
-        anchoring_keys.into_iter().find_map(|public_key| {
-            self.key_pool
-                .get(&public_key)
-                .cloned()
-                .map(|private_key| (public_key, private_key))
+        anchoring_keys: impl IntoIterator<Item = btc::PublicKey> + Clone,
+    ) -> Option<(&dyn Signer, btc::PublicKey)> {
+        self.signers.iter().find_map(|signer| {
+            anchoring_keys
+                .clone()
+                .into_iter()
+                .find(|public_key| signer.has_key(public_key))
+                .map(|public_key| (signer.as_ref(), public_key))
         })
     }
 }
@@ -197,8 +648,51 @@ pub enum SyncWithBitcoinError<C: Display, R: Display> {
     Relay(R),
     /// Internal error.
     Internal(anyhow::Error),
-    /// Initial funding transaction is unconfirmed.
+    /// Funding transaction is unconfirmed or has less confirmations than required
+    /// by the anchoring configuration.
     UnconfirmedFundingTransaction(btc::Sha256d),
+    /// Transaction reported as confirmed by the Bitcoin relay differs from the anchoring
+    /// transaction with the same identifier, e.g. its witness has been malleated,
+    /// or the relay is lying.
+    TransactionMismatch {
+        /// Index of the anchoring transaction.
+        index: u64,
+        /// Transaction returned by the Bitcoin relay, if any.
+        relayed: Option<btc::Transaction>,
+    },
+}
+
+impl<C: ApiClientError, R: Display> SyncWithBitcoinError<C, R> {
+    /// Returns `true` if the sync with Bitcoin may succeed if retried later.
+    pub fn is_retriable(&self) -> bool {
+        match self {
+            SyncWithBitcoinError::Client(e) => e.is_retriable(),
+            SyncWithBitcoinError::Relay(_) => true,
+            SyncWithBitcoinError::Internal(_)
+            | SyncWithBitcoinError::UnconfirmedFundingTransaction(_)
+            | SyncWithBitcoinError::TransactionMismatch { .. } => false,
+        }
+    }
+}
+
+/// Anchoring transaction synchronized with the Bitcoin network along with the number
+/// of its confirmations.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SyncedTransaction {
+    /// Index of the transaction in the anchoring chain.
+    pub index: u64,
+    /// Identifier of the transaction.
+    pub txid: btc::Sha256d,
+    /// Number of the transaction confirmations in the Bitcoin blockchain.
+    pub confirmations: u32,
+}
+
+/// Next step of the sync with Bitcoin task.
+enum NextTransaction {
+    /// All anchoring transactions up to the given one are known in the Bitcoin network.
+    Synced(Option<u64>),
+    /// Transaction with the given index should be sent to the Bitcoin network.
+    Send(u64, btc::Transaction),
 }
 
 /// Pushes anchoring transactions to the Bitcoin blockchain.
@@ -210,6 +704,18 @@ where
 {
     btc_relay: R,
     api_client: T,
+    /// The latest acceleration of the anchoring transaction voted by this node.
+    acceleration_vote: Mutex<Option<(btc::Sha256d, u64)>>,
+    /// Index of the latest anchoring transaction observed as confirmed in the Bitcoin
+    /// blockchain.
+    latest_confirmed: Mutex<Option<u64>>,
+    /// The latest anchoring transaction checked by the task.
+    latest_synced: Mutex<Option<SyncedTransaction>>,
+    /// Number of confirmations required to send the following anchoring transaction.
+    min_confirmations: u32,
+    /// Store of the sync progress, if any.
+    state_store: Option<SyncStateStore>,
+    metrics: Option<SyncMetrics>,
 }
 
 impl<T, R> SyncWithBitcoinTask<T, R>
@@ -224,23 +730,393 @@ where
         Self {
             api_client,
             btc_relay,
+            acceleration_vote: Mutex::default(),
+            latest_confirmed: Mutex::default(),
+            latest_synced: Mutex::default(),
+            min_confirmations: 0,
+            state_store: None,
+            metrics: None,
+        }
+    }
+
+    /// Records the failed Bitcoin relay requests to the given metrics.
+    pub fn with_metrics(mut self, metrics: SyncMetrics) -> Self {
+        self.metrics = Some(metrics);
+        self
+    }
+
+    /// Makes the task send the following anchoring transaction only after the previous one
+    /// has the given number of confirmations in the Bitcoin blockchain. By default,
+    /// it is enough for the previous transaction to be known to the Bitcoin relay, so
+    /// the long chains of the unconfirmed transactions can be broadcast.
+    pub fn with_min_confirmations(mut self, confirmations: u32) -> Self {
+        self.min_confirmations = confirmations;
+        self
+    }
+
+    /// Saves the sync progress to the given store after each sync attempt, so it can be
+    /// resumed after the restart via [`restore_state`](#method.restore_state).
+    pub fn with_state_store(mut self, store: SyncStateStore) -> Self {
+        self.state_store = Some(store);
+        self
+    }
+
+    /// Restores the sync progress from the state store, if any. Returns the index of
+    /// the latest synchronized anchoring transaction, which should be passed to
+    /// [`process`](#method.process) instead of `None`, so the task doesn't check
+    /// the whole anchoring chain again.
+    ///
+    /// The stored state is ignored if it doesn't match the actual anchoring chain,
+    /// e.g. if the sync utility has been switched to another anchoring instance.
+    pub async fn restore_state(
+        &self,
+    ) -> Result<Option<u64>, SyncWithBitcoinError<T::Error, R::Error>> {
+        let store = match &self.state_store {
+            Some(store) => store,
+            None => return Ok(None),
+        };
+        let state = store.load().map_err(SyncWithBitcoinError::Internal)?;
+        let index = match state.latest_synced_tx_index {
+            Some(index) => index,
+            None => return Ok(None),
+        };
+        let txid = self
+            .api_client
+            .transaction_with_index(index)
+            .await
+            .map_err(SyncWithBitcoinError::Client)?
+            .map(|tx| tx.id());
+        if txid.is_none() || txid != state.latest_synced_txid {
+            tracing::warn!(
+                path = %store.path().display(),
+                "Sync state doesn't match the anchoring chain and is ignored"
+            );
+            return Ok(None);
         }
+
+        tracing::info!(
+            index,
+            "Resume the sync with Bitcoin from the anchoring transaction"
+        );
+        *self.latest_confirmed.lock().unwrap() = state.latest_confirmed_tx_index;
+        Ok(Some(index))
+    }
+
+    /// Returns the latest anchoring transaction checked by this task along with
+    /// the number of its confirmations, if any.
+    pub fn latest_synced(&self) -> Option<SyncedTransaction> {
+        *self.latest_synced.lock().unwrap()
+    }
+
+    /// Returns the index of the latest anchoring transaction observed as confirmed
+    /// in the Bitcoin blockchain by this task, if any.
+    pub fn latest_confirmed_index(&self) -> Option<u64> {
+        *self.latest_confirmed.lock().unwrap()
     }
 
     /// Performs one attempt to send the first uncommitted anchoring transaction into the Bitcoin network, if any.
     /// sign an anchoring proposal, if any. Returns an index of the last committed transaction.
+    ///
+    /// If the previously confirmed anchoring transaction has lost its confirmations,
+    /// e.g. due to the Bitcoin chain reorganization or the mempool eviction, the affected
+    /// suffix of the anchoring chain is broadcast again.
+    #[tracing::instrument(name = "sync_with_bitcoin", level = "debug", skip(self))]
     pub async fn process(
         &self,
         latest_committed_tx_index: Option<u64>,
     ) -> Result<Option<u64>, SyncWithBitcoinError<T::Error, R::Error>> {
-        log::trace!("Perform syncing with the Bitcoin network");
+        let index = self.send_next(latest_committed_tx_index).await?;
+        self.save_state(index).await?;
+        Ok(index)
+    }
+
+    async fn send_next(
+        &self,
+        latest_committed_tx_index: Option<u64>,
+    ) -> Result<Option<u64>, SyncWithBitcoinError<T::Error, R::Error>> {
+        let (index, transaction) = match self.next_transaction(latest_committed_tx_index).await? {
+            NextTransaction::Synced(index) => return Ok(index),
+            NextTransaction::Send(index, transaction) => (index, transaction),
+        };
+
+        // Send an actual uncommitted transaction into the Bitcoin network.
+        self.btc_relay
+            .send_transaction(&transaction)
+            .await
+            .map_err(|e| self.relay_error(e))?;
+
+        tracing::info!(
+            index,
+            txid = %transaction.id(),
+            "Sent transaction to the Bitcoin network."
+        );
+
+        Ok(Some(index))
+    }
+
+    /// Same as [`process`](#method.process), but the transactions which have failed
+    /// to broadcast are put into the given queue. The due queued transactions are
+    /// retried before sending the next uncommitted transaction, while the ones
+    /// waiting for the retry are not sent again until their backoff elapses.
+    #[tracing::instrument(
+        name = "sync_with_bitcoin",
+        level = "debug",
+        skip(self, queue),
+        fields(queued = queue.entries().len())
+    )]
+    pub async fn process_with_queue(
+        &self,
+        latest_committed_tx_index: Option<u64>,
+        queue: &mut BroadcastQueue,
+    ) -> Result<Option<u64>, SyncWithBitcoinError<T::Error, R::Error>> {
+        let index = self
+            .send_next_with_queue(latest_committed_tx_index, queue)
+            .await?;
+        self.save_state(index).await?;
+        Ok(index)
+    }
+
+    async fn send_next_with_queue(
+        &self,
+        latest_committed_tx_index: Option<u64>,
+        queue: &mut BroadcastQueue,
+    ) -> Result<Option<u64>, SyncWithBitcoinError<T::Error, R::Error>> {
+        self.retry_queued(queue).await?;
+
+        let (index, transaction) = match self.next_transaction(latest_committed_tx_index).await? {
+            NextTransaction::Synced(index) => return Ok(index),
+            NextTransaction::Send(index, transaction) => (index, transaction),
+        };
+        if queue.contains(transaction.id()) {
+            tracing::trace!(
+                index,
+                txid = %transaction.id(),
+                "Transaction is waiting for the broadcast retry."
+            );
+            return Ok(latest_committed_tx_index);
+        }
+
+        match self.btc_relay.send_transaction(&transaction).await {
+            Ok(_) => {
+                tracing::info!(
+                    index,
+                    txid = %transaction.id(),
+                    "Sent transaction to the Bitcoin network."
+                );
+                Ok(Some(index))
+            }
+            Err(e) => {
+                tracing::warn!(
+                    index,
+                    txid = %transaction.id(),
+                    error = %e,
+                    "Unable to send transaction to the Bitcoin network, \
+                     it is queued for the retry."
+                );
+                queue
+                    .record_failure(index, transaction, &e, SystemTime::now())
+                    .map_err(SyncWithBitcoinError::Internal)?;
+                Err(self.relay_error(e))
+            }
+        }
+    }
+
+    /// Votes for the given funding transaction on behalf of this node, if it has at least
+    /// the number of Bitcoin confirmations required by the `funding_confirmations` parameter
+    /// of the anchoring configuration. Returns the hash of the `AddFunds` transaction.
+    pub async fn add_funds(
+        &self,
+        transaction: btc::Transaction,
+    ) -> Result<Hash, SyncWithBitcoinError<T::Error, R::Error>> {
+        let required = self
+            .api_client
+            .config()
+            .await
+            .map_err(SyncWithBitcoinError::Client)?
+            .funding_confirmations;
+        let txid = transaction.id();
+        let confirmations = self
+            .transaction_status(txid)
+            .await?
+            .confirmations()
+            .unwrap_or_default();
+        if confirmations < required {
+            tracing::warn!(
+                %txid,
+                confirmations,
+                required,
+                "Funding transaction has not enough confirmations"
+            );
+            return Err(SyncWithBitcoinError::UnconfirmedFundingTransaction(txid));
+        }
+
+        self.api_client
+            .add_funds(transaction)
+            .await
+            .map_err(SyncWithBitcoinError::Client)
+    }
+
+    /// Votes for the acceleration of the latest anchoring transaction, if it is stuck in
+    /// the Bitcoin mempool because its fee rate is lower than the estimated one by more than
+    /// the tolerated deviation. Returns the voted fee rate, if any.
+    ///
+    /// Transactions spent by the anchoring transaction should be available in the Bitcoin
+    /// relay to calculate its fee rate.
+    pub async fn accelerate_stuck_transaction(
+        &self,
+        policy: FeeRatePolicy,
+    ) -> Result<Option<u64>, SyncWithBitcoinError<T::Error, R::Error>> {
+        let count = self
+            .api_client
+            .transactions_count()
+            .await
+            .map_err(SyncWithBitcoinError::Client)?
+            .value;
+        if count == 0 {
+            return Ok(None);
+        }
+        let transaction = self.get_transaction(count - 1).await?;
+        let txid = transaction.id();
+        if self.transaction_status(txid).await? != TransactionStatus::Mempool {
+            return Ok(None);
+        }
+
+        let fee_rate = match self
+            .btc_relay
+            .fee_rate(policy.target_blocks)
+            .await
+            .map_err(|e| self.relay_error(e))?
+        {
+            Some(fee_rate) => cmp::max(fee_rate, Config::MIN_TX_FEE),
+            None => return Ok(None),
+        };
+        let mut inputs = Vec::with_capacity(transaction.0.input.len());
+        for input in &transaction.0.input {
+            let prev_txid = btc::Sha256d::from(input.previous_output.txid);
+            match self
+                .btc_relay
+                .raw_transaction(prev_txid)
+                .await
+                .map_err(|e| self.relay_error(e))?
+            {
+                Some(prev_tx) => inputs.push(prev_tx),
+                None => {
+                    tracing::warn!(
+                        %txid,
+                        spent_txid = %prev_txid,
+                        "Unable to calculate the fee rate of the anchoring transaction, \
+                         since the spent transaction is unknown"
+                    );
+                    return Ok(None);
+                }
+            }
+        }
+        let tx_fee_rate = transaction.fee(&inputs).unwrap_or_default() / transaction.size();
+        if fee_rate <= tx_fee_rate || !policy.is_deviated(fee_rate, tx_fee_rate) {
+            return Ok(None);
+        }
+        // Don't vote again until the estimation changes, since other nodes may not have
+        // voted yet.
+        let latest_vote = *self.acceleration_vote.lock().unwrap();
+        if let Some((voted_txid, voted_fee_rate)) = latest_vote {
+            if voted_txid == txid && !policy.is_deviated(fee_rate, voted_fee_rate) {
+                return Ok(None);
+            }
+        }
+
+        tracing::info!(
+            %txid,
+            fee_rate,
+            tx_fee_rate,
+            "Vote for the acceleration of the stuck anchoring transaction"
+        );
+        self.api_client
+            .accelerate_anchoring(txid, fee_rate)
+            .await
+            .map_err(SyncWithBitcoinError::Client)?;
+        *self.acceleration_vote.lock().unwrap() = Some((txid, fee_rate));
+        Ok(Some(fee_rate))
+    }
+
+    /// Retries broadcasting of the due transactions from the queue.
+    /// Records the failed Bitcoin relay request to the metrics, if any.
+    fn relay_error(&self, e: R::Error) -> SyncWithBitcoinError<T::Error, R::Error> {
+        if let Some(metrics) = &self.metrics {
+            metrics.record_relay_error();
+        }
+        SyncWithBitcoinError::Relay(e)
+    }
+
+    async fn retry_queued(
+        &self,
+        queue: &mut BroadcastQueue,
+    ) -> Result<(), SyncWithBitcoinError<T::Error, R::Error>> {
+        for entry in queue.due(SystemTime::now()) {
+            let txid = entry.transaction.id();
+            // The transaction may have been sent by another anchoring node.
+            let is_known = match self.btc_relay.transaction_status(txid).await {
+                Ok(status) => status.is_known(),
+                Err(_) => false,
+            };
+            let result = if is_known {
+                Ok(())
+            } else {
+                self.btc_relay
+                    .send_transaction(&entry.transaction)
+                    .await
+                    .map(drop)
+            };
+            match result {
+                Ok(()) => {
+                    tracing::info!(index = entry.index, txid = %txid, "Queued transaction has been broadcast.");
+                    queue.remove(txid)
+                }
+                Err(e) => {
+                    if let Some(metrics) = &self.metrics {
+                        metrics.record_relay_error();
+                    }
+                    tracing::warn!(
+                        index = entry.index,
+                        txid = %txid,
+                        attempt = entry.attempts + 1,
+                        error = %e,
+                        "Broadcast attempt of the queued transaction failed."
+                    );
+                    queue.record_failure(entry.index, entry.transaction, e, SystemTime::now())
+                }
+            }
+            .map_err(SyncWithBitcoinError::Internal)?;
+        }
+        Ok(())
+    }
+
+    async fn next_transaction(
+        &self,
+        latest_committed_tx_index: Option<u64>,
+    ) -> Result<NextTransaction, SyncWithBitcoinError<T::Error, R::Error>> {
         // Try to find a suitable transaction for sending to the Bitcoin network.
-        let (index, transaction) = if let Some(index) = latest_committed_tx_index {
+        if let Some(index) = latest_committed_tx_index {
             // Check that the latest committed transaction was really sent into
             // the Bitcoin network.
             let transaction = self.get_transaction(index).await?;
             let status = self.transaction_status(transaction.id()).await?;
+            self.verify_confirmed(index, &transaction, status).await?;
+            self.record_synced(index, transaction.id(), status);
+            if status.confirmations().is_some() {
+                let mut latest_confirmed = self.latest_confirmed.lock().unwrap();
+                *latest_confirmed = cmp::max(*latest_confirmed, Some(index));
+            } else if let Some(confirmed) = self.find_reorganized(index, status).await? {
+                self.rebroadcast_chain(confirmed, index).await?;
+                return Ok(NextTransaction::Synced(Some(index)));
+            }
             if status.is_known() {
+                if !self.is_deep_enough(status) {
+                    tracing::trace!(
+                        txid = %transaction.id(),
+                        "Wait for the confirmations of the anchoring transaction"
+                    );
+                    return Ok(NextTransaction::Synced(Some(index)));
+                }
                 let chain_len = self
                     .api_client
                     .transactions_count()
@@ -249,34 +1125,140 @@ where
                     .value;
 
                 if index + 1 == chain_len {
-                    return Ok(Some(index));
+                    return Ok(NextTransaction::Synced(Some(index)));
                 }
                 let index = index + 1;
-                (index, self.get_transaction(index).await?)
+                Ok(NextTransaction::Send(
+                    index,
+                    self.get_transaction(index).await?,
+                ))
             } else {
-                (index, transaction)
+                Ok(NextTransaction::Send(index, transaction))
             }
         }
         // Perform to find the actual uncommitted transaction.
         else if let Some((transaction, index)) = self.find_first_uncommitted_transaction().await?
         {
-            (index, transaction)
+            if index > 0 && self.min_confirmations > 0 {
+                let prev_txid = transaction.prev_tx_id();
+                let status = self.transaction_status(prev_txid).await?;
+                self.record_synced(index - 1, prev_txid, status);
+                if !self.is_deep_enough(status) {
+                    return Ok(NextTransaction::Synced(Some(index - 1)));
+                }
+            }
+            Ok(NextTransaction::Send(index, transaction))
         } else {
-            return Ok(None);
+            Ok(NextTransaction::Synced(None))
+        }
+    }
+
+    /// Saves the sync progress to the state store, if any.
+    async fn save_state(
+        &self,
+        index: Option<u64>,
+    ) -> Result<(), SyncWithBitcoinError<T::Error, R::Error>> {
+        let store = match &self.state_store {
+            Some(store) => store,
+            None => return Ok(()),
+        };
+        let latest_synced_txid = match index {
+            Some(index) => Some(self.get_transaction(index).await?.id()),
+            None => None,
+        };
+        let state = SyncState {
+            latest_synced_tx_index: index,
+            latest_synced_txid,
+            latest_confirmed_tx_index: self.latest_confirmed_index(),
         };
+        store.save(&state).map_err(SyncWithBitcoinError::Internal)
+    }
 
-        // Send an actual uncommitted transaction into the Bitcoin network.
-        self.btc_relay
-            .send_transaction(&transaction)
-            .await
-            .map_err(SyncWithBitcoinError::Relay)?;
+    /// Returns `true` if the transaction with the given status has enough confirmations
+    /// to send the following anchoring transaction.
+    fn is_deep_enough(&self, status: TransactionStatus) -> bool {
+        self.min_confirmations == 0
+            || status.confirmations().unwrap_or_default() >= self.min_confirmations
+    }
 
-        log::info!(
-            "Sent transaction to the Bitcoin network: {}",
-            transaction.id()
-        );
+    fn record_synced(&self, index: u64, txid: btc::Sha256d, status: TransactionStatus) {
+        *self.latest_synced.lock().unwrap() = Some(SyncedTransaction {
+            index,
+            txid,
+            confirmations: status.confirmations().unwrap_or_default(),
+        });
+    }
 
-        Ok(Some(index))
+    /// Checks whether the latest confirmed anchoring transaction has lost its confirmations,
+    /// given the status of the transaction with the specified index. Returns the index of
+    /// the unconfirmed transaction, which was previously confirmed, if any.
+    async fn find_reorganized(
+        &self,
+        index: u64,
+        status: TransactionStatus,
+    ) -> Result<Option<u64>, SyncWithBitcoinError<T::Error, R::Error>> {
+        let confirmed = match self.latest_confirmed_index() {
+            Some(confirmed) => confirmed,
+            None => return Ok(None),
+        };
+        if confirmed >= index {
+            return Ok(Some(index));
+        }
+        // Descendants of the evicted transaction are evicted too, so the confirmations
+        // of the latest confirmed transaction are checked only if the given one is unknown.
+        if status.is_known() {
+            return Ok(None);
+        }
+        let transaction = self.get_transaction(confirmed).await?;
+        let status = self.transaction_status(transaction.id()).await?;
+        if status.confirmations().is_some() {
+            Ok(None)
+        } else {
+            Ok(Some(confirmed))
+        }
+    }
+
+    /// Broadcasts again the anchoring transactions up to the given index, which have
+    /// been dropped from the Bitcoin network, starting from the first unconfirmed one
+    /// before the reorganized transaction.
+    async fn rebroadcast_chain(
+        &self,
+        reorganized: u64,
+        index: u64,
+    ) -> Result<(), SyncWithBitcoinError<T::Error, R::Error>> {
+        let mut from = 0;
+        for i in (1..=reorganized).rev() {
+            let previous_tx = self.get_transaction(i - 1).await?;
+            let status = self.transaction_status(previous_tx.id()).await?;
+            if status.confirmations().is_some() {
+                from = i;
+                break;
+            }
+        }
+
+        tracing::warn!(
+            index = reorganized,
+            from,
+            "Anchoring transaction has lost its confirmations in the Bitcoin network, \
+             rebroadcasting the following transactions."
+        );
+        for i in from..=index {
+            let transaction = self.get_transaction(i).await?;
+            if self.transaction_status(transaction.id()).await?.is_known() {
+                continue;
+            }
+            self.btc_relay
+                .send_transaction(&transaction)
+                .await
+                .map_err(|e| self.relay_error(e))?;
+            tracing::info!(
+                index = i,
+                txid = %transaction.id(),
+                "Rebroadcast transaction to the Bitcoin network."
+            );
+        }
+        *self.latest_confirmed.lock().unwrap() = from.checked_sub(1);
+        Ok(())
     }
 
     /// Finds the first anchoring transaction and its index, which was not committed into
@@ -300,16 +1282,18 @@ where
         // Check that the tail of anchoring chain is committed to the Bitcoin.
         let transaction = self.get_transaction(last_index).await?;
         let status = self.transaction_status(transaction.id()).await?;
+        self.verify_confirmed(last_index, &transaction, status)
+            .await?;
         if status.is_known() {
             return Ok(None);
         }
         // Try to find the first of uncommitted transactions.
         for index in (1..=last_index).rev() {
             let transaction = self.get_transaction(index).await?;
-            log::trace!(
-                "Checking for transaction with index {} and id {}",
+            tracing::trace!(
                 index,
-                transaction.id()
+                txid = %transaction.id(),
+                "Checking for transaction"
             );
 
             let previous_tx_id = transaction.prev_tx_id();
@@ -318,7 +1302,7 @@ where
             // so scenario when all the transactions are committed is not possible).
             let status = self.transaction_status(previous_tx_id).await?;
             if status.is_known() {
-                log::trace!("Found committed transaction");
+                tracing::trace!("Found committed transaction");
                 // Note that we were checking the previous transaction to be committed, so
                 // we return this transaction as the first not committed.
                 return Ok(Some((transaction, index)));
@@ -330,9 +1314,9 @@ where
         // is funding. This is special case and should be handled in specific way in order
         // to check the initial funding transaction confirmations.
         let transaction = self.get_transaction(0).await?;
-        log::trace!(
-            "Checking for initial anchoring transaction with id {}",
-            transaction.id()
+        tracing::trace!(
+            txid = %transaction.id(),
+            "Checking for initial anchoring transaction"
         );
         let status = self.transaction_status(transaction.prev_tx_id()).await?;
         if status.confirmations().is_none() {
@@ -370,6 +1354,496 @@ where
         self.btc_relay
             .transaction_status(txid)
             .await
-            .map_err(SyncWithBitcoinError::Relay)
+            .map_err(|e| self.relay_error(e))
+    }
+
+    /// Checks that the anchoring transaction reported as confirmed by the Bitcoin relay
+    /// is byte-for-byte equal to the one from the anchoring chain.
+    async fn verify_confirmed(
+        &self,
+        index: u64,
+        transaction: &btc::Transaction,
+        status: TransactionStatus,
+    ) -> Result<(), SyncWithBitcoinError<T::Error, R::Error>> {
+        if status.confirmations().is_none() {
+            return Ok(());
+        }
+
+        let relayed = self
+            .btc_relay
+            .raw_transaction(transaction.id())
+            .await
+            .map_err(|e| self.relay_error(e))?;
+        if relayed.as_ref() == Some(transaction) {
+            Ok(())
+        } else {
+            Err(SyncWithBitcoinError::TransactionMismatch { index, relayed })
+        }
+    }
+}
+
+/// Output of the anchoring multisig, which has been spent in the Bitcoin network by
+/// a transaction absent in the anchoring chain.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ExternalSpend {
+    /// Identifier of the transaction with the spent output.
+    pub txid: btc::Sha256d,
+    /// Index of the spent output.
+    pub vout: u32,
+}
+
+/// Monitors the outputs of the anchoring multisig for the spends absent in the anchoring
+/// chain, e.g. if the quorum of the anchoring keys has been compromised and the funds
+/// have been spent out-of-band.
+#[derive(Debug)]
+pub struct ExternalSpendWatchdog<T, R>
+where
+    T: PrivateApi + 'static,
+    R: BitcoinRelay + 'static,
+{
+    btc_relay: R,
+    api_client: T,
+    metrics: Option<SyncMetrics>,
+}
+
+impl<T, R> ExternalSpendWatchdog<T, R>
+where
+    T: PrivateApi + 'static,
+    R: BitcoinRelay + 'static,
+    T::Error: Display,
+    R::Error: Display,
+{
+    /// Creates a new watchdog instance.
+    pub fn new(btc_relay: R, api_client: T) -> Self {
+        Self {
+            btc_relay,
+            api_client,
+            metrics: None,
+        }
+    }
+
+    /// Records the found external spends and the failed Bitcoin relay requests
+    /// to the given metrics.
+    pub fn with_metrics(mut self, metrics: SyncMetrics) -> Self {
+        self.metrics = Some(metrics);
+        self
+    }
+
+    /// Checks the outputs which can be spent only by the next anchoring transaction,
+    /// i.e. the change output of the latest anchoring transaction and the outputs spent
+    /// by the anchoring proposal, and returns the ones spent in the Bitcoin network.
+    pub async fn process(
+        &self,
+    ) -> Result<Vec<ExternalSpend>, SyncWithBitcoinError<T::Error, R::Error>> {
+        let chain_len = self.transactions_count().await?;
+        let mut outputs = Vec::new();
+        if chain_len > 0 {
+            let transaction = self
+                .api_client
+                .transaction_with_index(chain_len - 1)
+                .await
+                .map_err(SyncWithBitcoinError::Client)?
+                .ok_or_else(|| {
+                    SyncWithBitcoinError::Internal(anyhow!(
+                        "Transaction with index {} is absent in the anchoring chain",
+                        chain_len - 1
+                    ))
+                })?;
+            outputs.push(ExternalSpend {
+                txid: transaction.id(),
+                vout: 0,
+            });
+        }
+        let proposal = self
+            .api_client
+            .anchoring_proposal()
+            .await
+            .map_err(SyncWithBitcoinError::Client)?;
+        if let AnchoringProposalState::Available { transaction, .. } = proposal {
+            for input in &transaction.0.input {
+                let output = ExternalSpend {
+                    txid: input.previous_output.txid.into(),
+                    vout: input.previous_output.vout,
+                };
+                if !outputs.contains(&output) {
+                    outputs.push(output);
+                }
+            }
+        }
+
+        let mut spends = Vec::new();
+        for output in outputs {
+            let is_spent = self
+                .btc_relay
+                .is_output_spent(output.txid, output.vout)
+                .await
+                .map_err(|e| {
+                    if let Some(metrics) = &self.metrics {
+                        metrics.record_relay_error();
+                    }
+                    SyncWithBitcoinError::Relay(e)
+                })?;
+            if is_spent == Some(true) {
+                spends.push(output);
+            }
+        }
+        // The next anchoring transaction may have been completed and broadcast
+        // in the meantime.
+        if !spends.is_empty() && self.transactions_count().await? != chain_len {
+            return Ok(Vec::new());
+        }
+
+        for spend in &spends {
+            tracing::error!(
+                txid = %spend.txid,
+                vout = spend.vout,
+                "Output of the transaction has been spent by a transaction absent \
+                 in the anchoring chain. The anchoring keys may be compromised."
+            );
+        }
+        if let Some(metrics) = &self.metrics {
+            metrics.record_external_spends(spends.len());
+        }
+        Ok(spends)
+    }
+
+    async fn transactions_count(&self) -> Result<u64, SyncWithBitcoinError<T::Error, R::Error>> {
+        self.api_client
+            .transactions_count()
+            .await
+            .map(|len| len.value)
+            .map_err(SyncWithBitcoinError::Client)
+    }
+}
+
+/// Errors that occur when funding the anchoring from the managed wallet.
+#[derive(Debug)]
+pub enum FundingError<C: Display, W: Display> {
+    /// Error occurred in the private API client.
+    Client(C),
+    /// Error occurred in the funding wallet.
+    Wallet(W),
+    /// Wallet balance is insufficient to send the requested amount.
+    InsufficientBalance {
+        /// Requested amount.
+        amount: u64,
+        /// Available balance.
+        balance: u64,
+    },
+}
+
+/// Funds the anchoring address from the managed wallet and registers the funding
+/// transaction via the private API.
+#[derive(Debug)]
+pub struct AnchoringFundingTask<T, W>
+where
+    T: PrivateApi + 'static,
+    W: FundingWallet + 'static,
+{
+    wallet: W,
+    api_client: T,
+}
+
+impl<T, W> AnchoringFundingTask<T, W>
+where
+    T: PrivateApi + 'static,
+    W: FundingWallet + 'static,
+    T::Error: Display,
+    W::Error: Display,
+{
+    /// Creates a new anchoring funding task instance.
+    pub fn new(wallet: W, api_client: T) -> Self {
+        Self { wallet, api_client }
+    }
+
+    /// Returns the confirmed balance of the funding wallet in satoshis.
+    pub async fn balance(&self) -> Result<u64, W::Error> {
+        self.wallet.balance().await
+    }
+
+    /// Sends the specified amount of satoshis to the actual anchoring address and votes
+    /// for the created funding transaction on behalf of this node. Other anchoring nodes
+    /// should vote for the same transaction via the `add-funds` private API method.
+    ///
+    /// If the anchoring configuration requires confirmations of the funding transactions,
+    /// the created transaction is not voted for, since it is not confirmed yet. In this
+    /// case all anchoring nodes should vote for it via
+    /// [`SyncWithBitcoinTask::add_funds`](struct.SyncWithBitcoinTask.html#method.add_funds)
+    /// once it is confirmed.
+    pub async fn fund(
+        &self,
+        amount: u64,
+    ) -> Result<btc::Transaction, FundingError<T::Error, W::Error>> {
+        let transaction = self.send(amount).await?;
+        let required = self
+            .api_client
+            .config()
+            .await
+            .map_err(FundingError::Client)?
+            .funding_confirmations;
+        if required == 0 {
+            self.register(transaction.clone()).await?;
+        }
+        Ok(transaction)
+    }
+
+    /// Creates the funding transaction which pays the specified amount of satoshis to
+    /// the actual anchoring address and returns the `AddFunds` transaction payload,
+    /// which can be submitted by each anchoring node.
+    ///
+    /// If `broadcast` is `false`, the signed funding transaction is not sent to
+    /// the Bitcoin network, so it can be reviewed and broadcast by other means.
+    pub async fn prepare(
+        &self,
+        amount: u64,
+        broadcast: bool,
+    ) -> Result<AddFunds, FundingError<T::Error, W::Error>> {
+        let transaction = if broadcast {
+            self.send(amount).await?
+        } else {
+            let address = self.checked_address(amount).await?;
+            let transaction = self
+                .wallet
+                .create_transaction(&address, amount)
+                .await
+                .map_err(FundingError::Wallet)?;
+            tracing::info!(
+                txid = %transaction.id(),
+                amount,
+                %address,
+                "Created funding transaction to the anchoring address"
+            );
+            transaction
+        };
+        Ok(AddFunds { transaction })
+    }
+
+    /// Sends the specified amount of satoshis to the actual anchoring address.
+    async fn send(
+        &self,
+        amount: u64,
+    ) -> Result<btc::Transaction, FundingError<T::Error, W::Error>> {
+        let address = self.checked_address(amount).await?;
+        let transaction = self
+            .wallet
+            .send_to_address(&address, amount)
+            .await
+            .map_err(FundingError::Wallet)?;
+        tracing::info!(
+            txid = %transaction.id(),
+            amount,
+            %address,
+            "Sent funding transaction to the anchoring address"
+        );
+        Ok(transaction)
+    }
+
+    /// Checks that the wallet balance is enough to send the specified amount of satoshis
+    /// and returns the actual anchoring address.
+    async fn checked_address(
+        &self,
+        amount: u64,
+    ) -> Result<btc::Address, FundingError<T::Error, W::Error>> {
+        let balance = self.balance().await.map_err(FundingError::Wallet)?;
+        if balance < amount {
+            return Err(FundingError::InsufficientBalance { amount, balance });
+        }
+
+        self.api_client
+            .config()
+            .await
+            .map(|config| config.anchoring_address())
+            .map_err(FundingError::Client)
+    }
+
+    /// Votes for the funding transaction on behalf of this node.
+    async fn register(
+        &self,
+        transaction: btc::Transaction,
+    ) -> Result<(), FundingError<T::Error, W::Error>> {
+        self.api_client
+            .add_funds(transaction)
+            .await
+            .map(drop)
+            .map_err(FundingError::Client)
+    }
+}
+
+/// Policy of the automatic anchoring funding top-up.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TopUpPolicy {
+    /// Minimum number of the anchoring transactions which the anchoring balance should
+    /// be able to pay for. A new funding transaction is created if the projected capacity
+    /// drops below this threshold.
+    pub min_capacity: u64,
+    /// Amount of satoshis sent in each funding transaction.
+    pub amount: u64,
+    /// Number of confirmations of the funding transaction required for its registration.
+    /// The `funding_confirmations` parameter of the anchoring configuration takes
+    /// precedence if it is greater.
+    pub confirmations: u32,
+}
+
+/// Funding transaction created by the top-up policy, which has not been spent
+/// by the anchoring transactions yet.
+#[derive(Debug, Clone, PartialEq)]
+pub enum PendingTopUp {
+    /// Transaction is waiting for the required number of confirmations.
+    Unconfirmed(btc::Transaction),
+    /// Transaction has been registered via the private API.
+    Registered(btc::Transaction),
+}
+
+/// Errors that occur when performing the automatic funding top-up.
+#[derive(Debug)]
+pub enum TopUpError<C: Display, W: Display, R: Display> {
+    /// Error occurred while funding the anchoring.
+    Funding(FundingError<C, W>),
+    /// Error occurred in the Bitcoin relay.
+    Relay(R),
+}
+
+/// Automatically tops up the anchoring balance from the managed wallet according
+/// to the given policy.
+#[derive(Debug)]
+pub struct AnchoringTopUpTask<T, W, R>
+where
+    T: PrivateApi + 'static,
+    W: FundingWallet + 'static,
+    R: BitcoinRelay + 'static,
+{
+    funding: AnchoringFundingTask<T, W>,
+    btc_relay: R,
+    policy: TopUpPolicy,
+}
+
+impl<T, W, R> AnchoringTopUpTask<T, W, R>
+where
+    T: PrivateApi + 'static,
+    W: FundingWallet + 'static,
+    R: BitcoinRelay + 'static,
+    T::Error: Display,
+    W::Error: Display,
+    R::Error: Display,
+{
+    /// Creates a new anchoring top-up task instance.
+    pub fn new(funding: AnchoringFundingTask<T, W>, btc_relay: R, policy: TopUpPolicy) -> Self {
+        Self {
+            funding,
+            btc_relay,
+            policy,
+        }
+    }
+
+    /// Returns the number of the anchoring transactions which the actual anchoring balance
+    /// is able to pay for.
+    pub async fn projected_capacity(&self) -> Result<u64, T::Error> {
+        let api_client = &self.funding.api_client;
+        let chain_len = api_client.transactions_count().await?.value;
+        let latest_tx = match chain_len.checked_sub(1) {
+            Some(index) => api_client.transaction_with_index(index).await?,
+            None => None,
+        };
+        let latest_tx = match latest_tx {
+            Some(tx) => tx,
+            None => return Ok(0),
+        };
+
+        let fee_per_tx = api_client.config().await?.transaction_fee * latest_tx.size();
+        let balance = latest_tx.unspent_value().unwrap_or_default();
+        Ok(balance.checked_div(fee_per_tx).unwrap_or(u64::MAX))
+    }
+
+    /// Performs one step of the top-up policy. Takes the pending top-up transaction,
+    /// if any, and returns the updated one.
+    pub async fn process(
+        &self,
+        pending: Option<PendingTopUp>,
+    ) -> Result<Option<PendingTopUp>, TopUpError<T::Error, W::Error, R::Error>> {
+        tracing::trace!("Perform an anchoring funding top-up");
+
+        match pending {
+            None => {
+                let capacity = self
+                    .projected_capacity()
+                    .await
+                    .map_err(|e| TopUpError::Funding(FundingError::Client(e)))?;
+                if capacity >= self.policy.min_capacity {
+                    return Ok(None);
+                }
+
+                tracing::info!(
+                    capacity,
+                    min_capacity = self.policy.min_capacity,
+                    "Projected anchoring capacity is below the minimal one"
+                );
+                let transaction = self
+                    .funding
+                    .send(self.policy.amount)
+                    .await
+                    .map_err(TopUpError::Funding)?;
+                Ok(Some(PendingTopUp::Unconfirmed(transaction)))
+            }
+
+            Some(PendingTopUp::Unconfirmed(transaction)) => {
+                let confirmations = self
+                    .btc_relay
+                    .transaction_status(transaction.id())
+                    .await
+                    .map_err(TopUpError::Relay)?
+                    .confirmations()
+                    .unwrap_or_default();
+                let required = self
+                    .funding
+                    .api_client
+                    .config()
+                    .await
+                    .map_err(|e| TopUpError::Funding(FundingError::Client(e)))?
+                    .funding_confirmations;
+                if confirmations < cmp::max(self.policy.confirmations, required) {
+                    return Ok(Some(PendingTopUp::Unconfirmed(transaction)));
+                }
+
+                self.funding
+                    .register(transaction.clone())
+                    .await
+                    .map_err(TopUpError::Funding)?;
+                Ok(Some(PendingTopUp::Registered(transaction)))
+            }
+
+            Some(PendingTopUp::Registered(transaction)) => {
+                // The top-up is completed when the funding transaction is spent
+                // by the latest anchoring transaction.
+                let api_client = &self.funding.api_client;
+                let chain_len = api_client
+                    .transactions_count()
+                    .await
+                    .map_err(|e| TopUpError::Funding(FundingError::Client(e)))?
+                    .value;
+                let latest_tx = match chain_len.checked_sub(1) {
+                    Some(index) => api_client
+                        .transaction_with_index(index)
+                        .await
+                        .map_err(|e| TopUpError::Funding(FundingError::Client(e)))?,
+                    None => None,
+                };
+
+                let txid = transaction.id();
+                let is_spent = match latest_tx {
+                    Some(tx) => {
+                        tx.0.input
+                            .iter()
+                            .any(|input| btc::Sha256d::from(input.previous_output.txid) == txid)
+                    }
+                    None => false,
+                };
+                if is_spent {
+                    tracing::info!(%txid, "Funding transaction has been spent");
+                    Ok(None)
+                } else {
+                    Ok(Some(PendingTopUp::Registered(transaction)))
+                }
+            }
+        }
     }
 }
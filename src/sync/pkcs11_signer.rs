@@ -0,0 +1,435 @@
+// Copyright 2020 The Exonum Team
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Signing of the anchoring proposals inside a [PKCS#11] token, e.g. YubiHSM 2 accessed
+//! via the `yubihsm_pkcs11` module, so the Bitcoin private key is never exported.
+//!
+//! [PKCS#11]: http://docs.oasis-open.org/pkcs11/pkcs11-base/v2.40/pkcs11-base-v2.40.html
+
+// PKCS#11 module is a C library loaded at runtime.
+#![allow(unsafe_code)]
+
+use anyhow::{anyhow, ensure};
+use async_trait::async_trait;
+use bitcoin::SigHashType;
+use btc_transaction_utils::{p2wsh, InputSignature, TxInRef};
+use libloading::{Library, Symbol};
+use serde_derive::{Deserialize, Serialize};
+
+use std::{
+    collections::HashMap,
+    env, fmt,
+    os::raw::c_void,
+    path::{Path, PathBuf},
+    ptr,
+    sync::{Arc, Mutex},
+};
+
+use self::ffi::{Attribute, CkRv, CkUlong, FunctionList, Mechanism};
+use super::{Signer, SigningRequest, SigningResponse};
+use crate::btc;
+
+/// Configuration of the Bitcoin key kept in the PKCS#11 token.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Pkcs11KeyConfig {
+    /// Bitcoin public key of the anchoring node corresponding to the token key.
+    pub bitcoin_key: btc::PublicKey,
+    /// Identifier (`CKA_ID`) of the secp256k1 private key object in hex, e.g. `0064`
+    /// for the YubiHSM 2 object with the ID 100.
+    pub key_id: String,
+}
+
+/// Configuration of the PKCS#11 token which keeps the Bitcoin keys of the anchoring node.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Pkcs11Config {
+    /// Path to the PKCS#11 module of the token,
+    /// e.g. `/usr/lib/x86_64-linux-gnu/pkcs11/yubihsm_pkcs11.so`.
+    pub module_path: PathBuf,
+    /// Identifier of the token slot.
+    #[serde(default)]
+    pub slot: u64,
+    /// Name of the environment variable with the user PIN of the token. The PIN of
+    /// YubiHSM 2 consists of the authentication key ID in hex and its password,
+    /// e.g. `0001password`.
+    #[serde(default = "Pkcs11Config::default_pin_env")]
+    pub pin_env: String,
+    /// Bitcoin keys of the anchoring node kept in the token.
+    pub keys: Vec<Pkcs11KeyConfig>,
+}
+
+impl Pkcs11Config {
+    fn default_pin_env() -> String {
+        "EXONUM_PKCS11_PIN".to_owned()
+    }
+}
+
+/// Signer which performs ECDSA signing of the anchoring proposal inputs inside
+/// the PKCS#11 token.
+///
+/// The token computes the raw ECDSA signatures of the input sighashes, thus the token
+/// should support the `CKM_ECDSA` mechanism for the secp256k1 keys.
+#[derive(Debug, Clone)]
+pub struct Pkcs11Signer {
+    token: Arc<Mutex<Token>>,
+    keys: HashMap<btc::PublicKey, CkUlong>,
+}
+
+impl Pkcs11Signer {
+    /// Opens the session with the token described by the given configuration and
+    /// finds the configured keys in it.
+    pub fn open(config: &Pkcs11Config) -> anyhow::Result<Self> {
+        let pin = env::var(&config.pin_env).map_err(|_| {
+            anyhow!(
+                "PIN of the PKCS#11 token is not set in the `{}` environment variable",
+                config.pin_env
+            )
+        })?;
+        let token = Token::open(&config.module_path, config.slot, &pin)?;
+        let keys = config
+            .keys
+            .iter()
+            .map(|key| {
+                let id = hex::decode(&key.key_id)
+                    .map_err(|e| anyhow!("Invalid key ID {}: {}", key.key_id, e))?;
+                Ok((key.bitcoin_key, token.find_private_key(&id)?))
+            })
+            .collect::<anyhow::Result<_>>()?;
+
+        Ok(Self {
+            token: Arc::new(Mutex::new(token)),
+            keys,
+        })
+    }
+}
+
+#[async_trait]
+impl Signer for Pkcs11Signer {
+    fn has_key(&self, bitcoin_key: &btc::PublicKey) -> bool {
+        self.keys.contains_key(bitcoin_key)
+    }
+
+    async fn sign(&self, request: SigningRequest) -> anyhow::Result<SigningResponse> {
+        let key = *self
+            .keys
+            .get(&request.bitcoin_key)
+            .ok_or_else(|| anyhow!("Unknown Bitcoin key {}", request.bitcoin_key))?;
+
+        let SigningRequest {
+            redeem_script,
+            proposal,
+            inputs,
+            ..
+        } = request;
+        let mut signer = p2wsh::InputSigner::new(redeem_script);
+        let sighashes = inputs
+            .iter()
+            .enumerate()
+            .map(|(index, input)| {
+                signer.signature_hash(TxInRef::new(proposal.as_ref(), index), input.as_ref())
+            })
+            .collect::<Vec<_>>();
+
+        // Token functions are blocking.
+        let token = Arc::clone(&self.token);
+        let signatures = tokio::task::spawn_blocking(move || {
+            let token = token.lock().unwrap();
+            sighashes
+                .iter()
+                .map(|sighash| {
+                    let signature = token.sign_digest(key, &sighash[..])?;
+                    let signature =
+                        InputSignature::new(signature.serialize_der().to_vec(), SigHashType::All);
+                    Ok(btc::InputSignature(signature))
+                })
+                .collect::<anyhow::Result<Vec<_>>>()
+        })
+        .await??;
+        Ok(SigningResponse { signatures })
+    }
+}
+
+/// Logged in session with the PKCS#11 token.
+struct Token {
+    module_path: PathBuf,
+    functions: *const FunctionList,
+    session: CkUlong,
+    // Library must outlive the function list.
+    _library: Library,
+}
+
+// PKCS#11 functions may be called from any thread, while the access to the session
+// is serialized by the mutex.
+unsafe impl Send for Token {}
+
+impl Token {
+    fn open(module_path: &Path, slot: u64, pin: &str) -> anyhow::Result<Self> {
+        let library = unsafe { Library::new(module_path) }.map_err(|e| {
+            anyhow!(
+                "Unable to load PKCS#11 module {}: {}",
+                module_path.display(),
+                e
+            )
+        })?;
+
+        let mut functions = ptr::null();
+        unsafe {
+            let get_function_list: Symbol<unsafe extern "C" fn(*mut *const FunctionList) -> CkRv> =
+                library.get(b"C_GetFunctionList\0")?;
+            check(get_function_list(&mut functions), "C_GetFunctionList")?;
+        }
+        ensure!(!functions.is_null(), "PKCS#11 module has no function list");
+        let functions_ref = unsafe { &*functions };
+
+        let rv = unsafe { function(functions_ref.initialize, "C_Initialize")?(ptr::null_mut()) };
+        // The module may have already been initialized by the previous signer.
+        if rv != ffi::CKR_CRYPTOKI_ALREADY_INITIALIZED {
+            check(rv, "C_Initialize")?;
+        }
+
+        let mut session = 0;
+        unsafe {
+            check(
+                function(functions_ref.open_session, "C_OpenSession")?(
+                    slot as CkUlong,
+                    ffi::CKF_SERIAL_SESSION,
+                    ptr::null_mut(),
+                    ptr::null(),
+                    &mut session,
+                ),
+                "C_OpenSession",
+            )?;
+        }
+        let token = Self {
+            module_path: module_path.to_owned(),
+            functions,
+            session,
+            _library: library,
+        };
+
+        let rv = unsafe {
+            function(functions_ref.login, "C_Login")?(
+                session,
+                ffi::CKU_USER,
+                pin.as_ptr(),
+                pin.len() as CkUlong,
+            )
+        };
+        // Login state is shared by all sessions of the application.
+        if rv != ffi::CKR_USER_ALREADY_LOGGED_IN {
+            check(rv, "C_Login")?;
+        }
+        Ok(token)
+    }
+
+    fn functions(&self) -> &FunctionList {
+        unsafe { &*self.functions }
+    }
+
+    fn find_private_key(&self, id: &[u8]) -> anyhow::Result<CkUlong> {
+        let functions = self.functions();
+        let mut class = ffi::CKO_PRIVATE_KEY;
+        let mut template = [
+            Attribute {
+                kind: ffi::CKA_CLASS,
+                value: &mut class as *mut CkUlong as *mut c_void,
+                value_len: std::mem::size_of::<CkUlong>() as CkUlong,
+            },
+            Attribute {
+                kind: ffi::CKA_ID,
+                value: id.as_ptr() as *mut c_void,
+                value_len: id.len() as CkUlong,
+            },
+        ];
+
+        let mut objects = [0; 2];
+        let mut count = 0;
+        unsafe {
+            check(
+                function(functions.find_objects_init, "C_FindObjectsInit")?(
+                    self.session,
+                    template.as_mut_ptr(),
+                    template.len() as CkUlong,
+                ),
+                "C_FindObjectsInit",
+            )?;
+            let rv = function(functions.find_objects, "C_FindObjects")?(
+                self.session,
+                objects.as_mut_ptr(),
+                objects.len() as CkUlong,
+                &mut count,
+            );
+            check(
+                function(functions.find_objects_final, "C_FindObjectsFinal")?(self.session),
+                "C_FindObjectsFinal",
+            )?;
+            check(rv, "C_FindObjects")?;
+        }
+        ensure!(
+            count == 1,
+            "Expected one private key with ID {} in the PKCS#11 token, but {} found",
+            hex::encode(id),
+            count
+        );
+        Ok(objects[0])
+    }
+
+    fn sign_digest(&self, key: CkUlong, digest: &[u8]) -> anyhow::Result<secp256k1::Signature> {
+        let functions = self.functions();
+        let mut mechanism = Mechanism {
+            kind: ffi::CKM_ECDSA,
+            parameter: ptr::null_mut(),
+            parameter_len: 0,
+        };
+        // Raw ECDSA signature is a concatenation of `r` and `s`.
+        let mut signature = [0_u8; 64];
+        let mut signature_len = signature.len() as CkUlong;
+        unsafe {
+            check(
+                function(functions.sign_init, "C_SignInit")?(self.session, &mut mechanism, key),
+                "C_SignInit",
+            )?;
+            check(
+                function(functions.sign, "C_Sign")?(
+                    self.session,
+                    digest.as_ptr(),
+                    digest.len() as CkUlong,
+                    signature.as_mut_ptr(),
+                    &mut signature_len,
+                ),
+                "C_Sign",
+            )?;
+        }
+        ensure!(
+            signature_len as usize == signature.len(),
+            "Unexpected length of the ECDSA signature: {}",
+            signature_len
+        );
+
+        let mut signature = secp256k1::Signature::from_compact(&signature)?;
+        // Bitcoin accepts only the signatures with the low `s` value.
+        signature.normalize_s();
+        Ok(signature)
+    }
+}
+
+impl fmt::Debug for Token {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Token")
+            .field("module_path", &self.module_path)
+            .field("session", &self.session)
+            .finish()
+    }
+}
+
+impl Drop for Token {
+    fn drop(&mut self) {
+        // The module is not finalized, since it may be used by another signer.
+        if let Some(close_session) = self.functions().close_session {
+            unsafe {
+                close_session(self.session);
+            }
+        }
+    }
+}
+
+fn function<F>(function: Option<F>, name: &str) -> anyhow::Result<F> {
+    function.ok_or_else(|| anyhow!("PKCS#11 module doesn't provide {}", name))
+}
+
+fn check(rv: CkRv, function: &str) -> anyhow::Result<()> {
+    ensure!(
+        rv == ffi::CKR_OK,
+        "PKCS#11 function {} has failed with error 0x{:x}",
+        function,
+        rv
+    );
+    Ok(())
+}
+
+/// Subset of the PKCS#11 interface used by the signer.
+mod ffi {
+    use std::os::raw::{c_uchar, c_ulong, c_void};
+
+    pub type CkUlong = c_ulong;
+    pub type CkRv = CkUlong;
+
+    pub const CKR_OK: CkRv = 0x0;
+    pub const CKR_USER_ALREADY_LOGGED_IN: CkRv = 0x100;
+    pub const CKR_CRYPTOKI_ALREADY_INITIALIZED: CkRv = 0x191;
+    pub const CKF_SERIAL_SESSION: CkUlong = 0x4;
+    pub const CKU_USER: CkUlong = 0x1;
+    pub const CKA_CLASS: CkUlong = 0x0;
+    pub const CKA_ID: CkUlong = 0x102;
+    pub const CKO_PRIVATE_KEY: CkUlong = 0x3;
+    pub const CKM_ECDSA: CkUlong = 0x1041;
+
+    #[repr(C)]
+    pub struct Attribute {
+        pub kind: CkUlong,
+        pub value: *mut c_void,
+        pub value_len: CkUlong,
+    }
+
+    #[repr(C)]
+    pub struct Mechanism {
+        pub kind: CkUlong,
+        pub parameter: *mut c_void,
+        pub parameter_len: CkUlong,
+    }
+
+    type Unused = *const c_void;
+
+    /// `CK_FUNCTION_LIST` up to the `C_Sign` function, the rest of the functions
+    /// are not used.
+    #[repr(C)]
+    pub struct FunctionList {
+        _version: [u8; 2],
+        pub initialize: Option<unsafe extern "C" fn(*mut c_void) -> CkRv>,
+        _finalize: Unused,
+        // From `C_GetInfo` to `C_SetPIN`.
+        _slot_and_token_management: [Unused; 10],
+        pub open_session: Option<
+            unsafe extern "C" fn(
+                CkUlong,
+                CkUlong,
+                *mut c_void,
+                *const c_void,
+                *mut CkUlong,
+            ) -> CkRv,
+        >,
+        pub close_session: Option<unsafe extern "C" fn(CkUlong) -> CkRv>,
+        // From `C_CloseAllSessions` to `C_SetOperationState`.
+        _session_management: [Unused; 4],
+        pub login: Option<unsafe extern "C" fn(CkUlong, CkUlong, *const c_uchar, CkUlong) -> CkRv>,
+        // From `C_Logout` to `C_SetAttributeValue`.
+        _object_management: [Unused; 7],
+        pub find_objects_init:
+            Option<unsafe extern "C" fn(CkUlong, *mut Attribute, CkUlong) -> CkRv>,
+        pub find_objects:
+            Option<unsafe extern "C" fn(CkUlong, *mut CkUlong, CkUlong, *mut CkUlong) -> CkRv>,
+        pub find_objects_final: Option<unsafe extern "C" fn(CkUlong) -> CkRv>,
+        // From `C_EncryptInit` to `C_DigestFinal`.
+        _encryption_and_digesting: [Unused; 13],
+        pub sign_init: Option<unsafe extern "C" fn(CkUlong, *mut Mechanism, CkUlong) -> CkRv>,
+        pub sign: Option<
+            unsafe extern "C" fn(
+                CkUlong,
+                *const c_uchar,
+                CkUlong,
+                *mut c_uchar,
+                *mut CkUlong,
+            ) -> CkRv,
+        >,
+    }
+}
@@ -0,0 +1,179 @@
+// Copyright 2020 The Exonum Team
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! SOCKS5 proxy support of the Bitcoin relays.
+
+use serde_derive::{Deserialize, Serialize};
+
+use std::{
+    io::{self, ErrorKind, Read, Write},
+    net::{IpAddr, TcpStream},
+};
+
+const SOCKS_VERSION: u8 = 0x05;
+const NO_AUTHENTICATION: u8 = 0x00;
+const USERNAME_PASSWORD: u8 = 0x02;
+const CONNECT_COMMAND: u8 = 0x01;
+
+/// Configuration of the SOCKS5 proxy, e.g. of the Tor daemon.
+///
+/// Host names of the Bitcoin nodes are resolved by the proxy, so the `.onion`
+/// addresses can be used and DNS requests don't reveal the anchoring node.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct ProxyConfig {
+    /// Proxy host, e.g. `127.0.0.1`.
+    pub host: String,
+    /// Proxy port, e.g. `9050` for the Tor daemon.
+    pub port: u16,
+    /// Username for the proxy authentication. Tor isolates the connections with
+    /// the different credentials to the different circuits.
+    #[serde(default)]
+    pub username: Option<String>,
+    /// Password for the proxy authentication.
+    #[serde(default)]
+    pub password: Option<String>,
+}
+
+impl ProxyConfig {
+    /// Connects to the given `host:port` target via the proxy.
+    pub fn connect(&self, target: &str) -> io::Result<TcpStream> {
+        let (host, port) = split_target(target)?;
+        let mut stream = TcpStream::connect((self.host.as_str(), self.port))?;
+
+        let method = if self.username.is_some() {
+            USERNAME_PASSWORD
+        } else {
+            NO_AUTHENTICATION
+        };
+        stream.write_all(&[SOCKS_VERSION, 1, method])?;
+        let mut reply = [0_u8; 2];
+        stream.read_exact(&mut reply)?;
+        if reply != [SOCKS_VERSION, method] {
+            return Err(proxy_error(
+                "Proxy doesn't support the authentication method",
+            ));
+        }
+        if method == USERNAME_PASSWORD {
+            self.authenticate(&mut stream)?;
+        }
+
+        let mut request = vec![SOCKS_VERSION, CONNECT_COMMAND, 0];
+        match host.parse::<IpAddr>() {
+            Ok(IpAddr::V4(ip)) => {
+                request.push(0x01);
+                request.extend_from_slice(&ip.octets());
+            }
+            Ok(IpAddr::V6(ip)) => {
+                request.push(0x04);
+                request.extend_from_slice(&ip.octets());
+            }
+            Err(_) => {
+                request.push(0x03);
+                push_field(&mut request, host)?;
+            }
+        }
+        request.extend_from_slice(&port.to_be_bytes());
+        stream.write_all(&request)?;
+
+        let mut reply = [0_u8; 4];
+        stream.read_exact(&mut reply)?;
+        if reply[0] != SOCKS_VERSION {
+            return Err(proxy_error("Unexpected proxy reply"));
+        }
+        if reply[1] != 0 {
+            return Err(proxy_error(&format!(
+                "Proxy has refused to connect to {} with code {}",
+                target, reply[1]
+            )));
+        }
+        // The bound address is not used, but it should be read out of the stream.
+        let address_len = match reply[3] {
+            0x01 => 4,
+            0x04 => 16,
+            0x03 => {
+                let mut len = [0_u8; 1];
+                stream.read_exact(&mut len)?;
+                usize::from(len[0])
+            }
+            _ => return Err(proxy_error("Unexpected proxy reply")),
+        };
+        let mut bound_address = vec![0_u8; address_len + 2];
+        stream.read_exact(&mut bound_address)?;
+        Ok(stream)
+    }
+
+    /// Connects to the given `host:port` target via the proxy without blocking
+    /// the runtime thread.
+    pub async fn connect_async(&self, target: &str) -> io::Result<tokio::net::TcpStream> {
+        let config = self.clone();
+        let target = target.to_owned();
+        let stream = tokio::task::spawn_blocking(move || config.connect(&target))
+            .await
+            .map_err(|e| io::Error::new(io::ErrorKind::Other, e))??;
+        stream.set_nonblocking(true)?;
+        tokio::net::TcpStream::from_std(stream)
+    }
+
+    fn authenticate(&self, stream: &mut TcpStream) -> io::Result<()> {
+        let mut request = vec![0x01];
+        push_field(&mut request, self.username.as_deref().unwrap_or_default())?;
+        push_field(&mut request, self.password.as_deref().unwrap_or_default())?;
+        stream.write_all(&request)?;
+
+        let mut reply = [0_u8; 2];
+        stream.read_exact(&mut reply)?;
+        if reply[1] != 0 {
+            return Err(io::Error::new(
+                ErrorKind::PermissionDenied,
+                "Proxy authentication failed",
+            ));
+        }
+        Ok(())
+    }
+}
+
+/// Splits the `host:port` target, the IPv6 host may be enclosed in brackets.
+fn split_target(target: &str) -> io::Result<(&str, u16)> {
+    let invalid_target = || {
+        io::Error::new(
+            ErrorKind::InvalidInput,
+            format!("Invalid proxy target: {}", target),
+        )
+    };
+    let separator = target.rfind(':').ok_or_else(invalid_target)?;
+    let port = target[separator + 1..]
+        .parse()
+        .map_err(|_| invalid_target())?;
+    let host = target[..separator]
+        .trim_start_matches('[')
+        .trim_end_matches(']');
+    Ok((host, port))
+}
+
+/// Appends the field prefixed with its length to the SOCKS message.
+fn push_field(message: &mut Vec<u8>, field: &str) -> io::Result<()> {
+    if field.len() > usize::from(u8::MAX) {
+        return Err(io::Error::new(
+            ErrorKind::InvalidInput,
+            "SOCKS field should not exceed 255 bytes",
+        ));
+    }
+    message.push(field.len() as u8);
+    message.extend_from_slice(field.as_bytes());
+    Ok(())
+}
+
+fn proxy_error(message: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::Other, message)
+}
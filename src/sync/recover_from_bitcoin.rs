@@ -0,0 +1,234 @@
+// Copyright 2020 The Exonum Team
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Reconstruction of the anchoring transactions chain from the Bitcoin data only.
+//!
+//! The chain is recovered backwards from the unspent output of the latest anchoring
+//! transaction, so the Bitcoin data source must be able to look up any transaction
+//! by its identifier (e.g. `bitcoind` with the enabled `txindex` option).
+
+use async_trait::async_trait;
+use bitcoincore_rpc::RpcApi;
+use exonum::crypto::Hash;
+use exonum_merkledb::ObjectHash;
+use jsonrpc::Error as JsonRpcError;
+use serde_derive::Deserialize;
+use thiserror::Error;
+
+use std::collections::HashSet;
+
+use crate::{
+    btc::{self, Payload},
+    config::Config,
+};
+
+/// Source of the Bitcoin blockchain data.
+#[async_trait]
+pub trait BitcoinDataSource {
+    /// Error type for the current data source implementation.
+    type Error;
+    /// Returns the transaction with the specified identifier, if it is known.
+    async fn transaction(&self, id: btc::Sha256d) -> Result<Option<btc::Transaction>, Self::Error>;
+    /// Returns the identifiers of the transactions with the unspent outputs sending funds
+    /// to the given address.
+    async fn unspent_transactions(
+        &self,
+        address: &btc::Address,
+    ) -> Result<Vec<btc::Sha256d>, Self::Error>;
+}
+
+/// Errors which may occur while recovering the anchoring chain.
+#[derive(Debug, Error)]
+pub enum RecoveryError<E: std::fmt::Display> {
+    /// Error occurred in the Bitcoin data source.
+    #[error("Bitcoin data source error: {0}")]
+    DataSource(E),
+    /// There are no unspent anchoring transactions on the given anchoring addresses.
+    #[error("Unable to find the latest anchoring transaction")]
+    NoAnchoringTransactions,
+}
+
+/// Anchoring transactions chain recovered from the Bitcoin blockchain.
+#[derive(Debug, Clone, PartialEq)]
+pub struct RecoveredChain {
+    /// Anchoring transactions in the chronological order.
+    pub transactions: Vec<btc::Transaction>,
+}
+
+impl RecoveredChain {
+    /// Returns the payloads of the recovered anchoring transactions.
+    pub fn payloads(&self) -> impl Iterator<Item = Payload> + '_ {
+        self.transactions
+            .iter()
+            .filter_map(btc::Transaction::anchoring_payload)
+    }
+
+    /// Returns the configuration committed in the first anchoring transaction,
+    /// if it is among the given configurations.
+    pub fn genesis_config<'a>(&self, configs: &'a [Config]) -> Option<&'a Config> {
+        let digest: Hash = self
+            .transactions
+            .first()?
+            .anchoring_payload()?
+            .config_digest?;
+        configs.iter().find(|config| config.object_hash() == digest)
+    }
+
+    /// Compares the recovered chain with the given anchoring transactions, e.g. from
+    /// the Exonum storage, and returns the index of the first mismatched transaction.
+    pub fn first_mismatch(&self, transactions: &[btc::Transaction]) -> Option<u64> {
+        let len = self.transactions.len().max(transactions.len());
+        (0..len)
+            .find(|&index| self.transactions.get(index) != transactions.get(index))
+            .map(|index| index as u64)
+    }
+}
+
+/// Recovers the anchoring transactions chain using the given history of the anchoring
+/// configurations. The chain ends with the latest unspent anchoring transaction
+/// on the addresses of the given configurations.
+pub async fn recover_chain<T>(
+    source: &T,
+    configs: &[Config],
+) -> Result<RecoveredChain, RecoveryError<T::Error>>
+where
+    T: BitcoinDataSource,
+    T::Error: std::fmt::Display,
+{
+    let anchoring_scripts = configs
+        .iter()
+        .map(Config::anchoring_out_script)
+        .collect::<HashSet<_>>();
+    let is_anchoring_tx = |tx: &btc::Transaction| {
+        tx.anchoring_payload().is_some()
+            && anchoring_scripts.contains(&tx.0.output[0].script_pubkey)
+    };
+
+    // Find the latest anchoring transaction among the unspent ones.
+    let mut latest: Option<btc::Transaction> = None;
+    for config in configs {
+        let txids = source
+            .unspent_transactions(&config.anchoring_address())
+            .await
+            .map_err(RecoveryError::DataSource)?;
+        for txid in txids {
+            let tx = source
+                .transaction(txid)
+                .await
+                .map_err(RecoveryError::DataSource)?;
+            if let Some(tx) = tx.filter(is_anchoring_tx) {
+                let height = tx.anchoring_payload().unwrap().block_height;
+                let is_later = match latest
+                    .as_ref()
+                    .and_then(btc::Transaction::anchoring_payload)
+                {
+                    Some(payload) => payload.block_height < height,
+                    None => true,
+                };
+                if is_later {
+                    latest = Some(tx);
+                }
+            }
+        }
+    }
+    let mut tx = latest.ok_or(RecoveryError::NoAnchoringTransactions)?;
+
+    // Go back through the first inputs to the first anchoring transaction.
+    let mut transactions = Vec::new();
+    loop {
+        let prev_output = tx.0.input[0].previous_output;
+        let prev_tx_chain = tx.anchoring_payload().unwrap().prev_tx_chain;
+        transactions.push(tx);
+        let mut prev_tx = None;
+        if prev_output.vout == 0 {
+            prev_tx = source
+                .transaction(prev_output.txid.into())
+                .await
+                .map_err(RecoveryError::DataSource)?
+                .filter(is_anchoring_tx);
+        }
+        // The transaction which recovers the broken chain spends a funding transaction
+        // and refers to the latest transaction of the broken chain in the payload.
+        if let (None, Some(txid)) = (&prev_tx, prev_tx_chain) {
+            prev_tx = source
+                .transaction(txid)
+                .await
+                .map_err(RecoveryError::DataSource)?
+                .filter(is_anchoring_tx);
+        }
+        match prev_tx {
+            Some(prev_tx) => tx = prev_tx,
+            // The first anchoring transaction spends a funding transaction.
+            None => break,
+        }
+    }
+    transactions.reverse();
+    Ok(RecoveredChain { transactions })
+}
+
+#[derive(Debug, Deserialize)]
+struct ScanTxOutSetResult {
+    unspents: Vec<ScanTxOutSetEntry>,
+}
+
+#[derive(Debug, Deserialize)]
+struct ScanTxOutSetEntry {
+    txid: bitcoin::Txid,
+}
+
+/// Blocking implementation, which stalls the runtime thread until the RPC request
+/// is completed. Use [`BitcoindRelay`](../struct.BitcoindRelay.html) to avoid this.
+#[async_trait]
+impl BitcoinDataSource for bitcoincore_rpc::Client {
+    type Error = bitcoincore_rpc::Error;
+
+    async fn transaction(&self, id: btc::Sha256d) -> Result<Option<btc::Transaction>, Self::Error> {
+        rpc_transaction(self, id)
+    }
+
+    async fn unspent_transactions(
+        &self,
+        address: &btc::Address,
+    ) -> Result<Vec<btc::Sha256d>, Self::Error> {
+        rpc_unspent_transactions(self, address)
+    }
+}
+
+pub(super) fn rpc_transaction(
+    client: &impl RpcApi,
+    id: btc::Sha256d,
+) -> Result<Option<btc::Transaction>, bitcoincore_rpc::Error> {
+    match client.get_raw_transaction(&id.into(), None) {
+        Ok(tx) => Ok(Some(tx.into())),
+        // The transaction is unknown.
+        Err(bitcoincore_rpc::Error::JsonRpc(JsonRpcError::Rpc(_))) => Ok(None),
+        Err(e) => Err(e),
+    }
+}
+
+pub(super) fn rpc_unspent_transactions(
+    client: &impl RpcApi,
+    address: &btc::Address,
+) -> Result<Vec<btc::Sha256d>, bitcoincore_rpc::Error> {
+    let descriptor = format!("addr({})", address);
+    let result: ScanTxOutSetResult = client.call(
+        "scantxoutset",
+        &["start".into(), serde_json::json!([descriptor])],
+    )?;
+    Ok(result
+        .unspents
+        .into_iter()
+        .map(|entry| entry.txid.into())
+        .collect())
+}
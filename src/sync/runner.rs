@@ -0,0 +1,329 @@
+// Copyright 2020 The Exonum Team
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Scheduler of the anchoring sync tasks.
+
+use anyhow::anyhow;
+use rand::{thread_rng, Rng};
+use tokio::{sync::mpsc, time::delay_for};
+
+use std::{
+    cmp,
+    time::{Duration, Instant},
+};
+
+use super::{
+    AnchoringChainUpdateTask, ApiClientError, BitcoinNotification, BitcoinNotifications,
+    BitcoinRelay, ChainUpdateError, SyncWithBitcoinError, SyncWithBitcoinTask,
+};
+use crate::api::PrivateApi;
+
+/// Intervals of the sync tasks and the backoff of their retries.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SyncRunnerConfig {
+    /// Interval between the updates of the anchoring chain.
+    pub chain_update_interval: Duration,
+    /// Interval between the syncs with the Bitcoin network.
+    pub bitcoin_sync_interval: Duration,
+    /// Delay before the first retry of the task failed due to the API client
+    /// or the Bitcoin relay error.
+    pub initial_backoff: Duration,
+    /// Maximum delay between the retries of the failed task.
+    pub max_backoff: Duration,
+}
+
+impl Default for SyncRunnerConfig {
+    fn default() -> Self {
+        Self {
+            chain_update_interval: Duration::from_secs(5),
+            bitcoin_sync_interval: Duration::from_secs(5),
+            initial_backoff: Duration::from_secs(1),
+            max_backoff: Duration::from_secs(300),
+        }
+    }
+}
+
+impl SyncRunnerConfig {
+    /// Returns the delay before the retry of the task which has failed the given number
+    /// of times in a row. The delay grows exponentially up to the maximum one and is
+    /// randomized within its upper half, so the anchoring nodes don't retry in lockstep.
+    pub fn backoff(&self, failures: u32) -> Duration {
+        let delay = self
+            .initial_backoff
+            .checked_mul(1 << failures.saturating_sub(1).min(31))
+            .map_or(self.max_backoff, |delay| cmp::min(delay, self.max_backoff));
+        let millis = delay.as_millis() as u64;
+        Duration::from_millis(thread_rng().gen_range(millis / 2, millis + 1))
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum RunnerCommand {
+    Trigger,
+    Shutdown,
+}
+
+/// Handle of the running [`SyncRunner`](struct.SyncRunner.html), which can be cloned
+/// and used from the other tasks.
+#[derive(Debug, Clone)]
+pub struct SyncRunnerHandle {
+    commands: mpsc::UnboundedSender<RunnerCommand>,
+}
+
+impl SyncRunnerHandle {
+    /// Requests an immediate run of the sync tasks, e.g. when a new Exonum block
+    /// is committed. Tasks waiting for the retry after a failure are not affected.
+    pub fn trigger(&self) {
+        let _ = self.commands.send(RunnerCommand::Trigger);
+    }
+
+    /// Requests the runner to stop. The running iteration is completed before that.
+    pub fn shutdown(&self) {
+        let _ = self.commands.send(RunnerCommand::Shutdown);
+    }
+}
+
+/// Schedule of a single sync task.
+#[derive(Debug)]
+struct Schedule {
+    next_run: Instant,
+    failures: u32,
+}
+
+impl Schedule {
+    fn new() -> Self {
+        Self {
+            next_run: Instant::now(),
+            failures: 0,
+        }
+    }
+
+    fn is_due(&self, now: Instant) -> bool {
+        self.next_run <= now
+    }
+
+    fn succeeded(&mut self, now: Instant, interval: Duration) {
+        self.failures = 0;
+        self.next_run = now + interval;
+    }
+
+    fn failed(&mut self, now: Instant, config: &SyncRunnerConfig) {
+        self.failures += 1;
+        self.next_run = now + config.backoff(self.failures);
+    }
+
+    fn trigger(&mut self, now: Instant) {
+        if self.failures == 0 {
+            self.next_run = cmp::min(self.next_run, now);
+        }
+    }
+}
+
+/// Runs the anchoring chain update and the sync with Bitcoin tasks on their intervals,
+/// retries them with the exponential backoff on the API client and the Bitcoin relay
+/// errors and stops on the [`SyncRunnerHandle::shutdown`] request.
+///
+/// The runner stops with an error if a task fails in a way that requires the operator
+/// intervention, e.g. if the anchoring chain refers to an unconfirmed funding transaction.
+///
+/// [`SyncRunnerHandle::shutdown`]: struct.SyncRunnerHandle.html#method.shutdown
+#[derive(Debug)]
+pub struct SyncRunner<T, R>
+where
+    T: PrivateApi + 'static,
+    R: BitcoinRelay + 'static,
+{
+    chain_updater: Option<AnchoringChainUpdateTask<T>>,
+    bitcoin_sync: Option<SyncWithBitcoinTask<T, R>>,
+    notifications: Option<BitcoinNotifications>,
+    config: SyncRunnerConfig,
+    handle: SyncRunnerHandle,
+    commands: mpsc::UnboundedReceiver<RunnerCommand>,
+}
+
+impl<T, R> SyncRunner<T, R>
+where
+    T: PrivateApi + 'static,
+    R: BitcoinRelay + 'static,
+    T::Error: ApiClientError,
+    R::Error: std::fmt::Display,
+{
+    /// Creates a new runner of the anchoring chain update task.
+    pub fn new(chain_updater: AnchoringChainUpdateTask<T>, config: SyncRunnerConfig) -> Self {
+        Self::with_tasks(Some(chain_updater), None, config)
+    }
+
+    /// Creates a new runner of the sync with Bitcoin task alone. Such a runner never signs
+    /// the anchoring proposals, so it doesn't require any Bitcoin keys and is suitable
+    /// for the nodes which only rebroadcast and monitor the anchoring chain.
+    pub fn watch_only(bitcoin_sync: SyncWithBitcoinTask<T, R>, config: SyncRunnerConfig) -> Self {
+        Self::with_tasks(None, Some(bitcoin_sync), config)
+    }
+
+    fn with_tasks(
+        chain_updater: Option<AnchoringChainUpdateTask<T>>,
+        bitcoin_sync: Option<SyncWithBitcoinTask<T, R>>,
+        config: SyncRunnerConfig,
+    ) -> Self {
+        let (commands_tx, commands) = mpsc::unbounded_channel();
+        Self {
+            chain_updater,
+            bitcoin_sync,
+            notifications: None,
+            config,
+            handle: SyncRunnerHandle {
+                commands: commands_tx,
+            },
+            commands,
+        }
+    }
+
+    /// Runs the given sync with Bitcoin task along with the anchoring chain update.
+    /// The sync progress is restored from the state store of the task, if any.
+    pub fn with_bitcoin_sync(mut self, task: SyncWithBitcoinTask<T, R>) -> Self {
+        self.bitcoin_sync = Some(task);
+        self
+    }
+
+    /// Runs the sync with Bitcoin as soon as a new Bitcoin block is received,
+    /// in addition to the regular interval.
+    pub fn with_notifications(mut self, notifications: BitcoinNotifications) -> Self {
+        self.notifications = Some(notifications);
+        self
+    }
+
+    /// Returns the handle of this runner.
+    pub fn handle(&self) -> SyncRunnerHandle {
+        self.handle.clone()
+    }
+
+    /// Runs the sync tasks until the shutdown is requested.
+    pub async fn run(mut self) -> anyhow::Result<()> {
+        let mut chain_update = Schedule::new();
+        let mut bitcoin_sync = Schedule::new();
+        let mut latest_synced_tx_index = match &self.bitcoin_sync {
+            Some(task) => match task.restore_state().await {
+                Ok(index) => index,
+                Err(SyncWithBitcoinError::Client(e)) => {
+                    tracing::warn!("Unable to restore the sync state. {}", e);
+                    None
+                }
+                Err(SyncWithBitcoinError::Internal(e)) => {
+                    tracing::warn!("Unable to restore the sync state. {}", e);
+                    None
+                }
+                Err(_) => None,
+            },
+            None => None,
+        };
+
+        loop {
+            let now = Instant::now();
+            let chain_updater = self.chain_updater.as_ref();
+            if let Some(chain_updater) = chain_updater.filter(|_| chain_update.is_due(now)) {
+                match chain_updater.process().await {
+                    Ok(()) => chain_update.succeeded(now, self.config.chain_update_interval),
+                    Err(ChainUpdateError::Client(e)) => {
+                        tracing::error!("An error in the anchoring API client occurred. {}", e);
+                        chain_update.failed(now, &self.config);
+                    }
+                    Err(ChainUpdateError::InsufficientFunds { total_fee, balance }) => {
+                        tracing::warn!(
+                            "Insufficient funds to construct a new anchoring transaction, \
+                             total fee is {}, total balance is {}",
+                            total_fee,
+                            balance
+                        );
+                        chain_update.succeeded(now, self.config.chain_update_interval);
+                    }
+                    Err(ChainUpdateError::NoInitialFunds) => {
+                        tracing::warn!("Initial funding transaction is absent.");
+                        chain_update.succeeded(now, self.config.chain_update_interval);
+                    }
+                    Err(ChainUpdateError::Internal(e)) => return Err(e),
+                }
+            }
+
+            if let Some(task) = &self.bitcoin_sync {
+                if bitcoin_sync.is_due(now) {
+                    match task.process(latest_synced_tx_index).await {
+                        Ok(index) => {
+                            latest_synced_tx_index = index;
+                            bitcoin_sync.succeeded(now, self.config.bitcoin_sync_interval);
+                        }
+                        Err(SyncWithBitcoinError::Client(e)) => {
+                            tracing::error!("An error in the anchoring API client occurred. {}", e);
+                            bitcoin_sync.failed(now, &self.config);
+                        }
+                        Err(SyncWithBitcoinError::Relay(e)) => {
+                            tracing::error!("An error in the Bitcoin relay occurred. {}", e);
+                            bitcoin_sync.failed(now, &self.config);
+                        }
+                        Err(SyncWithBitcoinError::UnconfirmedFundingTransaction(id)) => {
+                            return Err(anyhow!(
+                                "Funding transaction with id {} is unconfirmed by Bitcoin \
+                                 network.",
+                                id
+                            ));
+                        }
+                        Err(SyncWithBitcoinError::TransactionMismatch { index, .. }) => {
+                            return Err(anyhow!(
+                                "Anchoring transaction with index {} differs from the one \
+                                 confirmed in the Bitcoin network.",
+                                index
+                            ));
+                        }
+                        Err(SyncWithBitcoinError::Internal(e)) => return Err(e),
+                    }
+                }
+            }
+
+            let next_run = match (&self.chain_updater, &self.bitcoin_sync) {
+                (Some(_), Some(_)) => cmp::min(chain_update.next_run, bitcoin_sync.next_run),
+                (Some(_), None) => chain_update.next_run,
+                (None, _) => bitcoin_sync.next_run,
+            };
+            let delay = next_run.saturating_duration_since(Instant::now());
+            tokio::select! {
+                _ = delay_for(delay) => {}
+                command = self.commands.recv() => match command {
+                    Some(RunnerCommand::Trigger) => {
+                        let now = Instant::now();
+                        chain_update.trigger(now);
+                        bitcoin_sync.trigger(now);
+                    }
+                    // The handle is kept by the runner, so the channel is never closed.
+                    Some(RunnerCommand::Shutdown) | None => return Ok(()),
+                },
+                hash = next_block(&mut self.notifications) => {
+                    tracing::info!("Sync with Bitcoin has been triggered by the new block {}.", hash);
+                    bitcoin_sync.trigger(Instant::now());
+                }
+            }
+        }
+    }
+}
+
+/// Waits for the next Bitcoin block notification, if the notifications are enabled.
+async fn next_block(notifications: &mut Option<BitcoinNotifications>) -> crate::btc::Sha256d {
+    let notifications = match notifications {
+        Some(notifications) => notifications,
+        None => return futures::future::pending().await,
+    };
+    loop {
+        if let BitcoinNotification::Block(hash) = notifications.next().await {
+            return hash;
+        }
+    }
+}
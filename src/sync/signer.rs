@@ -0,0 +1,243 @@
+// Copyright 2020 The Exonum Team
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Signers of the anchoring transaction proposals.
+//!
+//! [`LocalSigner`] keeps the Bitcoin private keys in memory, while [`HttpSigner`] and
+//! [`grpc::SignerClient`] delegate signing to the remote signing service, e.g. a daemon
+//! which keeps the keys inside a hardware security module. The gRPC client is available
+//! with the `grpc` feature.
+//!
+//! [`LocalSigner`]: struct.LocalSigner.html
+//! [`HttpSigner`]: struct.HttpSigner.html
+//! [`grpc::SignerClient`]: ../grpc/struct.SignerClient.html
+
+use anyhow::{anyhow, bail, ensure};
+use async_trait::async_trait;
+use btc_transaction_utils::{multisig::RedeemScript, p2wsh, TxInRef};
+use serde_derive::{Deserialize, Serialize};
+
+use std::{collections::HashMap, fmt::Debug, sync::Arc};
+
+use crate::btc;
+#[cfg(feature = "grpc")]
+use crate::grpc::SignerClient;
+
+/// Request to sign all inputs of the anchoring transaction proposal.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SigningRequest {
+    /// Bitcoin public key of the anchoring node which should sign the proposal.
+    pub bitcoin_key: btc::PublicKey,
+    /// Redeem script of the anchoring address spent by the proposal.
+    pub redeem_script: RedeemScript,
+    /// Anchoring transaction proposal.
+    pub proposal: btc::Transaction,
+    /// Transactions spent by the proposal in the order of its inputs.
+    pub inputs: Vec<btc::Transaction>,
+}
+
+impl SigningRequest {
+    /// Checks that the given signatures are made by the requested key and there is
+    /// a signature for each input of the proposal.
+    pub fn verify(&self, signatures: &[btc::InputSignature]) -> anyhow::Result<()> {
+        ensure!(
+            signatures.len() == self.inputs.len(),
+            "Expected {} signatures, but {} are given",
+            self.inputs.len(),
+            signatures.len()
+        );
+
+        let signer = p2wsh::InputSigner::new(self.redeem_script.clone());
+        for (index, (input, signature)) in self.inputs.iter().zip(signatures).enumerate() {
+            signer
+                .verify_input(
+                    TxInRef::new(self.proposal.as_ref(), index),
+                    input.as_ref(),
+                    &self.bitcoin_key.0,
+                    &signature.0,
+                )
+                .map_err(|e| anyhow!("Invalid signature for input {}: {}", index, e))?;
+        }
+        Ok(())
+    }
+}
+
+/// Response of the signer with the signatures for each input of the anchoring
+/// transaction proposal.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SigningResponse {
+    /// Input signatures in the order of the proposal inputs.
+    pub signatures: Vec<btc::InputSignature>,
+}
+
+/// Source of the signatures for the anchoring transaction proposals.
+#[async_trait]
+pub trait Signer: Debug + Send + Sync {
+    /// Returns `true` if the signer is able to sign by the given Bitcoin key.
+    fn has_key(&self, bitcoin_key: &btc::PublicKey) -> bool;
+
+    /// Signs all inputs of the anchoring transaction proposal.
+    async fn sign(&self, request: SigningRequest) -> anyhow::Result<SigningResponse>;
+}
+
+#[async_trait]
+impl<S: Signer + ?Sized> Signer for Box<S> {
+    fn has_key(&self, bitcoin_key: &btc::PublicKey) -> bool {
+        (**self).has_key(bitcoin_key)
+    }
+
+    async fn sign(&self, request: SigningRequest) -> anyhow::Result<SigningResponse> {
+        (**self).sign(request).await
+    }
+}
+
+/// Signer which keeps the Bitcoin private keys in memory.
+#[derive(Debug, Clone, Default)]
+pub struct LocalSigner {
+    keys: Arc<HashMap<btc::PublicKey, btc::PrivateKey>>,
+}
+
+impl LocalSigner {
+    /// Creates a signer with the given Bitcoin key pairs.
+    pub fn new(keys: impl IntoIterator<Item = (btc::PublicKey, btc::PrivateKey)>) -> Self {
+        Self {
+            keys: Arc::new(keys.into_iter().collect()),
+        }
+    }
+}
+
+#[async_trait]
+impl Signer for LocalSigner {
+    fn has_key(&self, bitcoin_key: &btc::PublicKey) -> bool {
+        self.keys.contains_key(bitcoin_key)
+    }
+
+    async fn sign(&self, request: SigningRequest) -> anyhow::Result<SigningResponse> {
+        let private_key = self
+            .keys
+            .get(&request.bitcoin_key)
+            .ok_or_else(|| anyhow!("Unknown Bitcoin key {}", request.bitcoin_key))?;
+
+        let SigningRequest {
+            redeem_script,
+            proposal,
+            inputs,
+            ..
+        } = request;
+        let mut signer = p2wsh::InputSigner::new(redeem_script);
+        let signatures = inputs
+            .iter()
+            .enumerate()
+            .map(|(index, input)| {
+                signer
+                    .sign_input(
+                        TxInRef::new(proposal.as_ref(), index),
+                        input.as_ref(),
+                        &private_key.0.key,
+                    )
+                    .map(btc::InputSignature)
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok(SigningResponse { signatures })
+    }
+}
+
+/// Protocol of the remote signing service.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum RemoteSignerProtocol {
+    /// `SigningRequest` is sent as JSON in the body of the `POST` request, and
+    /// `SigningResponse` is expected as JSON in the response body.
+    Http,
+    /// `Signer` service described in the `grpc.proto` file. Requires the `grpc` feature.
+    Grpc,
+}
+
+/// Configuration of the remote signing service.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RemoteSignerConfig {
+    /// URL of the signing service, e.g. `https://127.0.0.1:8443/sign` for the HTTP
+    /// signer or `http://127.0.0.1:50051` for the gRPC one.
+    pub url: String,
+    /// Protocol of the signing service, `http` by default.
+    #[serde(default = "RemoteSignerConfig::default_protocol")]
+    pub protocol: RemoteSignerProtocol,
+    /// Bitcoin public keys, private parts of which are kept by the signing service.
+    pub bitcoin_keys: Vec<btc::PublicKey>,
+}
+
+impl RemoteSignerConfig {
+    fn default_protocol() -> RemoteSignerProtocol {
+        RemoteSignerProtocol::Http
+    }
+
+    /// Creates a client of the signing service described by this configuration.
+    ///
+    /// The connection is established on the first signing request.
+    pub fn signer(&self) -> anyhow::Result<Box<dyn Signer>> {
+        Ok(match self.protocol {
+            RemoteSignerProtocol::Http => Box::new(HttpSigner::new(self.clone())),
+            #[cfg(feature = "grpc")]
+            RemoteSignerProtocol::Grpc => Box::new(SignerClient::connect_lazy(
+                &self.url,
+                self.bitcoin_keys.clone(),
+            )?),
+            #[cfg(not(feature = "grpc"))]
+            RemoteSignerProtocol::Grpc => {
+                bail!("gRPC signing service requires the `grpc` feature to be enabled")
+            }
+        })
+    }
+}
+
+/// Client of the remote signing service with JSON over HTTP(S) API.
+#[derive(Debug, Clone)]
+pub struct HttpSigner {
+    config: RemoteSignerConfig,
+    client: reqwest::Client,
+}
+
+impl HttpSigner {
+    /// Creates a client of the signing service with the given configuration.
+    pub fn new(config: RemoteSignerConfig) -> Self {
+        Self {
+            config,
+            client: reqwest::Client::new(),
+        }
+    }
+}
+
+#[async_trait]
+impl Signer for HttpSigner {
+    fn has_key(&self, bitcoin_key: &btc::PublicKey) -> bool {
+        self.config.bitcoin_keys.contains(bitcoin_key)
+    }
+
+    async fn sign(&self, request: SigningRequest) -> anyhow::Result<SigningResponse> {
+        let response = self
+            .client
+            .post(&self.config.url)
+            .json(&request)
+            .send()
+            .await?;
+        if !response.status().is_success() {
+            bail!(
+                "Signing service responded with {}: {}",
+                response.status(),
+                response.text().await.unwrap_or_default()
+            );
+        }
+        Ok(response.json().await?)
+    }
+}
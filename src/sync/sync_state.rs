@@ -0,0 +1,77 @@
+// Copyright 2020 The Exonum Team
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Durable state of the sync with Bitcoin task.
+
+use serde_derive::{Deserialize, Serialize};
+
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
+
+use crate::btc;
+
+/// Progress of the sync with Bitcoin task, which allows to resume the sync after
+/// the restart without checking the whole anchoring chain.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SyncState {
+    /// Index of the latest anchoring transaction synchronized with the Bitcoin network.
+    pub latest_synced_tx_index: Option<u64>,
+    /// Identifier of the latest synchronized anchoring transaction. It is used to check
+    /// that the state belongs to the same anchoring chain.
+    pub latest_synced_txid: Option<btc::Sha256d>,
+    /// Index of the latest anchoring transaction observed as confirmed in the Bitcoin
+    /// blockchain, which is used to detect the Bitcoin chain reorganizations.
+    pub latest_confirmed_tx_index: Option<u64>,
+}
+
+/// File which keeps the state of the sync with Bitcoin task.
+///
+/// The state is written to a temporary file first and then moved to the target one,
+/// so the interrupted write doesn't corrupt the previous state.
+#[derive(Debug, Clone)]
+pub struct SyncStateStore {
+    path: PathBuf,
+}
+
+impl SyncStateStore {
+    /// Creates a store backed by the given file.
+    pub fn new(path: impl Into<PathBuf>) -> Self {
+        Self { path: path.into() }
+    }
+
+    /// Returns the path of the state file.
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Loads the state from the file or returns the empty one if the file
+    /// does not exist.
+    pub fn load(&self) -> anyhow::Result<SyncState> {
+        if self.path.exists() {
+            Ok(serde_json::from_slice(&fs::read(&self.path)?)?)
+        } else {
+            Ok(SyncState::default())
+        }
+    }
+
+    /// Saves the given state to the file.
+    pub fn save(&self, state: &SyncState) -> anyhow::Result<()> {
+        let tmp_path = self.path.with_extension("tmp");
+        fs::write(&tmp_path, serde_json::to_vec_pretty(state)?)?;
+        fs::rename(tmp_path, &self.path)?;
+        Ok(())
+    }
+}
@@ -0,0 +1,300 @@
+// Copyright 2020 The Exonum Team
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Fake Bitcoin relay which models a small Bitcoin chain.
+
+use anyhow::{anyhow, bail, ensure};
+use async_trait::async_trait;
+
+use std::sync::{Arc, Mutex};
+
+use super::create_fake_merkle_block;
+use crate::{
+    btc,
+    sync::{BitcoinRelay, TransactionStatus},
+};
+
+/// Fake Bitcoin relay with its own chain of blocks and memory pool.
+///
+/// The sent transactions enter the memory pool and are confirmed by the blocks mined
+/// by the test. The test can also roll back the latest blocks, evict transactions from
+/// the memory pool or make the relay unreachable, which allows to check how the sync
+/// tasks handle the reorganizations and the lost transactions.
+///
+/// Clones of the relay share the same chain.
+#[derive(Debug, Clone, Default)]
+pub struct FakeBitcoinRelay {
+    inner: Arc<Mutex<FakeChain>>,
+}
+
+#[derive(Debug, Default)]
+struct FakeChain {
+    blocks: Vec<FakeBlock>,
+    mempool: Vec<btc::Transaction>,
+    sent: Vec<btc::Sha256d>,
+    fee_rate: Option<u64>,
+    unreachable: bool,
+}
+
+#[derive(Debug)]
+struct FakeBlock {
+    transactions: Vec<btc::Transaction>,
+    proof: btc::MerkleBlock,
+}
+
+impl FakeChain {
+    fn tip_hash(&self) -> btc::Sha256d {
+        self.blocks.last().map_or_else(
+            || btc::Sha256d::new([0_u8; 32]),
+            |block| block.proof.header().block_hash(),
+        )
+    }
+
+    fn push_block(&mut self, transactions: Vec<btc::Transaction>) -> u64 {
+        let txids = transactions
+            .iter()
+            .map(btc::Transaction::id)
+            .collect::<Vec<_>>();
+        let proof = create_fake_merkle_block(self.tip_hash(), &txids);
+        self.blocks.push(FakeBlock {
+            transactions,
+            proof,
+        });
+        self.blocks.len() as u64
+    }
+
+    /// Returns the index of the block with the given transaction.
+    fn find_block(&self, txid: btc::Sha256d) -> Option<(usize, &FakeBlock)> {
+        self.blocks
+            .iter()
+            .enumerate()
+            .find(|(_, block)| block.transactions.iter().any(|tx| tx.id() == txid))
+    }
+
+    fn transactions(&self) -> impl Iterator<Item = &btc::Transaction> {
+        self.blocks
+            .iter()
+            .flat_map(|block| &block.transactions)
+            .chain(&self.mempool)
+    }
+
+    fn find_transaction(&self, txid: btc::Sha256d) -> Option<&btc::Transaction> {
+        self.transactions().find(|tx| tx.id() == txid)
+    }
+
+    /// Returns the known transaction which spends the same outputs as the given one.
+    fn find_conflict(&self, transaction: &btc::Transaction) -> Option<btc::Sha256d> {
+        let txid = transaction.id();
+        self.transactions()
+            .filter(|tx| tx.id() != txid)
+            .find(|tx| {
+                tx.0.input.iter().any(|input| {
+                    transaction
+                        .0
+                        .input
+                        .iter()
+                        .any(|other| input.previous_output == other.previous_output)
+                })
+            })
+            .map(btc::Transaction::id)
+    }
+
+    fn ensure_reachable(&self) -> anyhow::Result<()> {
+        ensure!(!self.unreachable, "Relay is unreachable");
+        Ok(())
+    }
+}
+
+impl FakeBitcoinRelay {
+    /// Creates a relay with the empty chain.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    fn chain(&self) -> std::sync::MutexGuard<'_, FakeChain> {
+        self.inner.lock().unwrap()
+    }
+
+    /// Returns the number of the mined blocks.
+    pub fn height(&self) -> u64 {
+        self.chain().blocks.len() as u64
+    }
+
+    /// Returns identifiers of the transactions in the memory pool.
+    pub fn mempool(&self) -> Vec<btc::Sha256d> {
+        self.chain()
+            .mempool
+            .iter()
+            .map(btc::Transaction::id)
+            .collect()
+    }
+
+    /// Returns identifiers of all transactions sent to the relay including the repeated ones.
+    pub fn sent_transactions(&self) -> Vec<btc::Sha256d> {
+        self.chain().sent.clone()
+    }
+
+    /// Mines a block with all transactions from the memory pool and returns its height.
+    pub fn mine_block(&self) -> u64 {
+        let mut chain = self.chain();
+        let transactions = std::mem::take(&mut chain.mempool);
+        chain.push_block(transactions)
+    }
+
+    /// Mines the given number of blocks and returns the height of the last one.
+    pub fn mine_blocks(&self, count: u64) -> u64 {
+        for _ in 0..count {
+            self.mine_block();
+        }
+        self.height()
+    }
+
+    /// Mines a block with the given transaction bypassing the memory pool, e.g. to confirm
+    /// the funding transaction, and returns its height.
+    pub fn confirm_transaction(&self, transaction: btc::Transaction) -> u64 {
+        let mut chain = self.chain();
+        let txid = transaction.id();
+        chain.mempool.retain(|tx| tx.id() != txid);
+        chain.push_block(vec![transaction])
+    }
+
+    /// Rolls back the given number of the latest blocks. Their transactions return
+    /// into the memory pool as it happens in the Bitcoin node.
+    pub fn reorg(&self, depth: u64) {
+        let mut chain = self.chain();
+        assert!(
+            depth <= chain.blocks.len() as u64,
+            "Reorganization is deeper than the chain"
+        );
+        let new_len = chain.blocks.len() - depth as usize;
+        let mut transactions = chain
+            .blocks
+            .drain(new_len..)
+            .flat_map(|block| block.transactions)
+            .collect::<Vec<_>>();
+        transactions.append(&mut chain.mempool);
+        chain.mempool = transactions;
+    }
+
+    /// Removes the transaction from the memory pool, e.g. as the one with
+    /// the insufficient fee. Returns `false` if the transaction is not in the memory pool.
+    pub fn evict_transaction(&self, txid: btc::Sha256d) -> bool {
+        let mut chain = self.chain();
+        let len = chain.mempool.len();
+        chain.mempool.retain(|tx| tx.id() != txid);
+        chain.mempool.len() != len
+    }
+
+    /// Sets the fee rate estimation returned by the relay.
+    pub fn set_fee_rate(&self, fee_rate: Option<u64>) {
+        self.chain().fee_rate = fee_rate;
+    }
+
+    /// Makes all requests to the relay fail or restores the relay.
+    pub fn set_unreachable(&self, unreachable: bool) {
+        self.chain().unreachable = unreachable;
+    }
+}
+
+#[async_trait]
+impl BitcoinRelay for FakeBitcoinRelay {
+    type Error = anyhow::Error;
+
+    async fn send_transaction(
+        &self,
+        transaction: &btc::Transaction,
+    ) -> Result<btc::Sha256d, Self::Error> {
+        let mut chain = self.chain();
+        chain.ensure_reachable()?;
+
+        let txid = transaction.id();
+        chain.sent.push(txid);
+        if chain.find_transaction(txid).is_some() {
+            return Ok(txid);
+        }
+        if let Some(conflict) = chain.find_conflict(transaction) {
+            bail!(
+                "Transaction {} conflicts with transaction {}",
+                txid,
+                conflict
+            );
+        }
+        chain.mempool.push(transaction.clone());
+        Ok(txid)
+    }
+
+    async fn transaction_status(&self, id: btc::Sha256d) -> Result<TransactionStatus, Self::Error> {
+        let chain = self.chain();
+        chain.ensure_reachable()?;
+
+        Ok(if let Some((index, _)) = chain.find_block(id) {
+            TransactionStatus::Committed((chain.blocks.len() - index) as u32)
+        } else if chain.mempool.iter().any(|tx| tx.id() == id) {
+            TransactionStatus::Mempool
+        } else {
+            TransactionStatus::Unknown
+        })
+    }
+
+    async fn transaction_proof(
+        &self,
+        id: btc::Sha256d,
+    ) -> Result<Option<btc::MerkleBlock>, Self::Error> {
+        let chain = self.chain();
+        chain.ensure_reachable()?;
+        Ok(chain.find_block(id).map(|(_, block)| block.proof.clone()))
+    }
+
+    async fn raw_transaction(
+        &self,
+        id: btc::Sha256d,
+    ) -> Result<Option<btc::Transaction>, Self::Error> {
+        let chain = self.chain();
+        chain.ensure_reachable()?;
+        Ok(chain.find_transaction(id).cloned())
+    }
+
+    async fn is_output_spent(
+        &self,
+        txid: btc::Sha256d,
+        vout: u32,
+    ) -> Result<Option<bool>, Self::Error> {
+        let chain = self.chain();
+        chain.ensure_reachable()?;
+
+        let transaction = match chain.find_transaction(txid) {
+            Some(transaction) => transaction,
+            None => return Ok(None),
+        };
+        if vout as usize >= transaction.0.output.len() {
+            return Err(anyhow!("Transaction {} has no output {}", txid, vout));
+        }
+        let outpoint = bitcoin::OutPoint {
+            txid: txid.into(),
+            vout,
+        };
+        let is_spent = chain.transactions().any(|tx| {
+            tx.0.input
+                .iter()
+                .any(|input| input.previous_output == outpoint)
+        });
+        Ok(Some(is_spent))
+    }
+
+    async fn fee_rate(&self, _target_blocks: u16) -> Result<Option<u64>, Self::Error> {
+        let chain = self.chain();
+        chain.ensure_reachable()?;
+        Ok(chain.fee_rate)
+    }
+}
@@ -15,8 +15,8 @@
 //! Set of helpers for btc anchoring testing.
 
 use async_trait::async_trait;
-use bitcoin::{self, network::constants::Network};
-use bitcoin_hashes::{sha256d::Hash as Sha256dHash, Hash as BitcoinHash};
+use bitcoin::{self, network::constants::Network, util::merkleblock::PartialMerkleTree};
+use bitcoin_hashes::Hash as BitcoinHash;
 use btc_transaction_utils::{p2wsh, TxInRef};
 use exonum::{
     crypto::{Hash, KeyPair, PublicKey},
@@ -31,24 +31,55 @@ use exonum_supervisor::{ConfigPropose, Supervisor, SupervisorInterface};
 use exonum_testkit::{ApiKind, Spec, TestKit, TestKitApiClient, TestKitBuilder, TestNode};
 use rand::{thread_rng, Rng};
 
-use std::collections::BTreeMap;
+use std::{
+    collections::BTreeMap,
+    sync::{Arc, Mutex},
+};
 
 use crate::{
     api::{
-        AnchoringChainLength, AnchoringProposalState, FindTransactionQuery, IndexQuery, PrivateApi,
-        PublicApi, TransactionProof,
+        AnchoredHeightIndex, AnchoringAddressRange, AnchoringChainLength, AnchoringCostReport,
+        AnchoringGap, AnchoringProposalState, AnchoringStatistics, AnchoringStatus,
+        AnchoringTransactionIds, AnchoringTransactionsPage, ApiConfig, BlockAnchorProof,
+        BlockHashQuery, BlockHeaderProof, BlockHeightQuery, CostReportQuery,
+        ExonumTransactionProof, FindTransactionQuery, FundingHistory, FundingInclusionProof,
+        IndexQuery, KeyReplacementQuery, NodeInputSignature, NodeParticipation, NodeSigningStats,
+        PrivateApi, PublicApi, SignedResponse, SigningSession, TransactionHashQuery,
+        TransactionInclusionProof, TransactionProof, TransactionsRangeQuery, TransitionProgress,
+        TxInputQuery, TxidQuery, WtxidQuery,
+    },
+    blockchain::{
+        backup::AnchoringArchive,
+        data_layout::{ConfigChange, TransitionState},
+        AccelerateAnchoring, AddFunds, BtcAnchoringInterface, RecoverAnchoring,
+        ReportBitcoinHeaders, Schema, SignInput, SignInputs, VoteFeeRate,
     },
-    blockchain::{AddFunds, BtcAnchoringInterface, Schema, SignInput},
     btc,
     config::Config,
     proto::AnchoringKeys,
     BtcAnchoringService,
 };
 
+use self::node_api::NodeApiPlugin;
+pub use self::{
+    fake_relay::FakeBitcoinRelay,
+    node_api::{NodeApiKind, NodePrivateApi},
+};
+
+#[cfg(feature = "regtest")]
+pub mod regtest;
+pub mod vectors;
+
+mod fake_relay;
+mod node_api;
+
 /// Default anchoring instance ID.
-pub const ANCHORING_INSTANCE_ID: InstanceId = 14;
+pub const ANCHORING_INSTANCE_ID: InstanceId = crate::DEFAULT_INSTANCE_ID;
 /// Default anchoring instance name.
-pub const ANCHORING_INSTANCE_NAME: &str = "btc_anchoring";
+pub const ANCHORING_INSTANCE_NAME: &str = crate::DEFAULT_INSTANCE_NAME;
+
+/// Compact representation of the maximum proof of work target in the regtest network.
+const REGTEST_COMPACT_TARGET: u32 = 0x207f_ffff;
 
 /// Generates a fake funding transaction.
 pub fn create_fake_funding_transaction(address: &btc::Address, value: u64) -> btc::Transaction {
@@ -56,14 +87,22 @@ pub fn create_fake_funding_transaction(address: &btc::Address, value: u64) -> bt
     let mut rng = thread_rng();
     let mut data = [0_u8; 32];
     rng.fill(&mut data);
-    // Create fake funding transaction.
+    funding_transaction(address, value, btc::Sha256d::new(data))
+}
+
+/// Creates a funding transaction spending the output of the given transaction.
+fn funding_transaction(
+    address: &btc::Address,
+    value: u64,
+    prev_txid: btc::Sha256d,
+) -> btc::Transaction {
     bitcoin::Transaction {
         version: 2,
         lock_time: 0,
         input: vec![bitcoin::TxIn {
             previous_output: bitcoin::OutPoint {
                 vout: 0,
-                txid: Sha256dHash::from_slice(&data).unwrap().into(),
+                txid: prev_txid.into(),
             },
             script_sig: bitcoin::Script::new(),
             sequence: 0,
@@ -77,6 +116,69 @@ pub fn create_fake_funding_transaction(address: &btc::Address, value: u64) -> bt
     .into()
 }
 
+/// Generates a chain of fake Bitcoin block headers with the regtest difficulty
+/// following the block with the given hash.
+pub fn create_fake_bitcoin_headers(
+    prev_block_hash: btc::Sha256d,
+    count: usize,
+) -> Vec<btc::BlockHeader> {
+    let mut prev_block_hash = prev_block_hash;
+    (0..count)
+        .map(|_| {
+            let header = mine_fake_bitcoin_header(prev_block_hash, Default::default());
+            prev_block_hash = header.block_hash();
+            header
+        })
+        .collect()
+}
+
+/// Generates a fake Bitcoin block with the regtest difficulty following the block with
+/// the given hash and returns a proof of inclusion of the given transactions in it.
+pub fn create_fake_merkle_block(
+    prev_block_hash: btc::Sha256d,
+    txids: &[btc::Sha256d],
+) -> btc::MerkleBlock {
+    // Add a random coinbase transaction to the block.
+    let coinbase_txid = bitcoin::Txid::from_slice(&thread_rng().gen::<[u8; 32]>()).unwrap();
+    let block_txids = std::iter::once(coinbase_txid)
+        .chain(txids.iter().map(|&txid| txid.into()))
+        .collect::<Vec<_>>();
+    let matches = block_txids
+        .iter()
+        .map(|txid| txid != &coinbase_txid)
+        .collect::<Vec<_>>();
+
+    let txn = PartialMerkleTree::from_txids(&block_txids, &matches);
+    let merkle_root = txn.extract_matches(&mut vec![], &mut vec![]).unwrap();
+    let header = mine_fake_bitcoin_header(prev_block_hash, merkle_root);
+    let merkle_block = bitcoin::MerkleBlock {
+        header: header.0,
+        txn,
+    };
+    // Make a round trip to obtain the canonical representation of the partial Merkle tree.
+    let bytes = bitcoin::consensus::serialize(&merkle_block);
+    btc::MerkleBlock(bitcoin::consensus::deserialize(&bytes).unwrap())
+}
+
+fn mine_fake_bitcoin_header(
+    prev_block_hash: btc::Sha256d,
+    merkle_root: bitcoin::TxMerkleNode,
+) -> btc::BlockHeader {
+    let mut header = bitcoin::BlockHeader {
+        version: 1,
+        prev_blockhash: bitcoin::BlockHash::from_slice(&prev_block_hash.0[..]).unwrap(),
+        merkle_root,
+        time: thread_rng().gen(),
+        bits: REGTEST_COMPACT_TARGET,
+        nonce: 0,
+    };
+    // Find a nonce which satisfies the proof of work.
+    while header.validate_pow(&header.target()).is_err() {
+        header.nonce += 1;
+    }
+    btc::BlockHeader(header)
+}
+
 fn gen_validator_keys() -> Keys {
     let consensus_keypair = KeyPair::random();
     let service_keypair = KeyPair::random();
@@ -106,13 +208,16 @@ impl AnchoringNodes {
     }
 
     fn anchoring_keys(&self) -> Vec<AnchoringKeys> {
-        self.inner
+        let mut anchoring_keys = self
+            .inner
             .iter()
             .map(|(&service_key, &bitcoin_key)| AnchoringKeys {
                 bitcoin_key,
                 service_key,
             })
-            .collect()
+            .collect::<Vec<_>>();
+        anchoring_keys.sort_by_key(|keys| keys.bitcoin_key.0.to_bytes());
+        anchoring_keys
     }
 
     fn anchoring_keypairs(&self) -> Vec<(btc::PublicKey, btc::PrivateKey)> {
@@ -134,6 +239,8 @@ pub struct AnchoringTestKit {
     /// Underlying testkit instance.
     pub inner: TestKit,
     anchoring_nodes: AnchoringNodes,
+    /// Service key pairs of the nodes which the anchoring service API is served for.
+    api_nodes: Arc<Mutex<Vec<KeyPair>>>,
 }
 
 /// Returns an anchoring schema instance used in Testkit.
@@ -145,12 +252,41 @@ impl AnchoringTestKit {
     /// Creates an anchoring testkit instance for the specified number of anchoring nodes,
     /// and interval between anchors.
     pub fn new(nodes_num: u16, anchoring_interval: u64) -> Self {
+        Self::with_network(nodes_num, anchoring_interval, Network::Testnet)
+    }
+
+    /// Creates an anchoring testkit instance for the specified number of anchoring nodes,
+    /// and interval between anchors in the given Bitcoin network.
+    pub fn with_network(nodes_num: u16, anchoring_interval: u64, network: Network) -> Self {
+        Self::build(nodes_num, anchoring_interval, network, ApiConfig::default())
+    }
+
+    /// Creates an anchoring testkit instance for the specified number of anchoring nodes,
+    /// and interval between anchors with the given limits of the service HTTP API.
+    pub fn with_api_config(nodes_num: u16, anchoring_interval: u64, api_config: ApiConfig) -> Self {
+        Self::build(nodes_num, anchoring_interval, Network::Testnet, api_config)
+    }
+
+    fn build(
+        nodes_num: u16,
+        anchoring_interval: u64,
+        network: Network,
+        api_config: ApiConfig,
+    ) -> Self {
         let validator_keys = (0..nodes_num)
             .map(|_| gen_validator_keys())
             .collect::<Vec<_>>();
 
-        let network = Network::Testnet;
-        let anchoring_nodes = AnchoringNodes::from_keys(Network::Testnet, &validator_keys);
+        let anchoring_nodes = AnchoringNodes::from_keys(network, &validator_keys);
+        let api_nodes = Arc::new(Mutex::new(
+            validator_keys
+                .iter()
+                .map(|keys| keys.service.clone())
+                .collect(),
+        ));
+        // Responses of the proof endpoints are signed by the service key of the first node.
+        let service = BtcAnchoringService::with_api_signing_key(validator_keys[0].service.clone())
+            .with_api_config(api_config);
 
         let anchoring_config = Config {
             network,
@@ -162,16 +298,18 @@ impl AnchoringTestKit {
         let inner = TestKitBuilder::validator()
             .with_keys(validator_keys)
             .with(Supervisor::simple())
-            .with(Spec::new(BtcAnchoringService).with_instance(
+            .with(Spec::migrating(service).with_instance(
                 ANCHORING_INSTANCE_ID,
                 ANCHORING_INSTANCE_NAME,
                 anchoring_config,
             ))
+            .with_plugin(NodeApiPlugin::new(api_nodes.clone(), api_config))
             .build();
 
         Self {
             inner,
             anchoring_nodes,
+            api_nodes,
         }
     }
 
@@ -292,6 +430,31 @@ impl AnchoringTestKit {
             .collect()
     }
 
+    /// Creates the reports of the given Bitcoin block headers from all anchoring nodes, so
+    /// that the empty headers chain starts from the first of them.
+    pub fn create_report_bitcoin_headers_txs(
+        &self,
+        first_height: u64,
+        headers: Vec<btc::BlockHeader>,
+    ) -> Vec<Verified<AnyTx>> {
+        let report = ReportBitcoinHeaders {
+            first_height,
+            headers,
+        };
+        self.actual_anchoring_config()
+            .anchoring_keys
+            .into_iter()
+            .map(move |anchoring_keys| {
+                let node_keypair = self
+                    .find_node_by_service_key(anchoring_keys.service_key)
+                    .expect("Unable to find node by service key")
+                    .service_keypair();
+
+                node_keypair.report_bitcoin_headers(ANCHORING_INSTANCE_ID, report.clone())
+            })
+            .collect()
+    }
+
     /// Creates configuration change transaction for simple supervisor.
     pub fn create_config_change_tx(&self, proposal: ConfigPropose) -> Verified<AnyTx> {
         let initiator_id = self.inner.network().us().validator_id().unwrap();
@@ -301,12 +464,9 @@ impl AnchoringTestKit {
 
     /// Adds a new auditor node to the testkit network and create Bitcoin keypair for it.
     pub fn add_node(&mut self) -> AnchoringKeys {
-        let service_key = self
-            .inner
-            .network_mut()
-            .add_node()
-            .service_keypair()
-            .public_key();
+        let service_keypair = self.inner.network_mut().add_node().service_keypair();
+        let service_key = service_keypair.public_key();
+        self.api_nodes.lock().unwrap().push(service_keypair);
         let bitcoin_key = self
             .anchoring_nodes
             .add_node(self.actual_anchoring_config().network, service_key);
@@ -346,6 +506,25 @@ impl AnchoringTestKit {
         self.anchoring_nodes.anchoring_keypairs()
     }
 
+    /// Returns the private API of the anchoring node with the specified bitcoin key,
+    /// which uses the given testkit API client.
+    ///
+    /// The testkit API serves the anchoring service endpoints on behalf of each node,
+    /// so the transactions are signed by the service key of the node by the service API
+    /// itself. The endpoints of the nodes added by `add_node` are served by the testkit
+    /// API created after the next update of the service endpoints.
+    pub fn private_api_for_node(
+        &self,
+        client: TestKitApiClient,
+        bitcoin_key: &btc::PublicKey,
+    ) -> Option<NodePrivateApi> {
+        let service_key = self
+            .find_anchoring_node(bitcoin_key)?
+            .service_keypair()
+            .public_key();
+        Some(NodePrivateApi::new(client, service_key))
+    }
+
     /// Finds anchoring node with the specified bitcoin key.
     pub fn find_anchoring_node(&self, bitcoin_key: &btc::PublicKey) -> Option<&TestNode> {
         self.anchoring_nodes
@@ -412,6 +591,207 @@ impl PublicApi for TestKitApiClient {
             .get("config")
             .await
     }
+
+    async fn config_history(&self) -> api::Result<Vec<ConfigChange>> {
+        self.public(ApiKind::Service(ANCHORING_INSTANCE_NAME))
+            .get("config-history")
+            .await
+    }
+
+    async fn proposal_participation(&self) -> api::Result<Vec<NodeParticipation>> {
+        self.public(ApiKind::Service(ANCHORING_INSTANCE_NAME))
+            .get("proposal-participation")
+            .await
+    }
+
+    async fn signing_session(&self) -> api::Result<Option<SigningSession>> {
+        self.public(ApiKind::Service(ANCHORING_INSTANCE_NAME))
+            .get("signing-session")
+            .await
+    }
+
+    async fn signing_stats(&self) -> api::Result<Vec<NodeSigningStats>> {
+        self.public(ApiKind::Service(ANCHORING_INSTANCE_NAME))
+            .get("signing-stats")
+            .await
+    }
+
+    async fn stats(&self) -> api::Result<AnchoringStatistics> {
+        self.public(ApiKind::Service(ANCHORING_INSTANCE_NAME))
+            .get("stats")
+            .await
+    }
+
+    async fn status(&self) -> api::Result<AnchoringStatus> {
+        self.public(ApiKind::Service(ANCHORING_INSTANCE_NAME))
+            .get("status")
+            .await
+    }
+
+    async fn address_history(&self) -> api::Result<Vec<AnchoringAddressRange>> {
+        self.public(ApiKind::Service(ANCHORING_INSTANCE_NAME))
+            .get("address/history")
+            .await
+    }
+
+    async fn transaction_ids(&self, index: u64) -> api::Result<Option<AnchoringTransactionIds>> {
+        self.public(ApiKind::Service(ANCHORING_INSTANCE_NAME))
+            .query(&IndexQuery { index })
+            .get("transaction-ids")
+            .await
+    }
+
+    async fn transaction_index_by_height(
+        &self,
+        height: Height,
+    ) -> api::Result<Option<AnchoredHeightIndex>> {
+        self.public(ApiKind::Service(ANCHORING_INSTANCE_NAME))
+            .query(&BlockHeightQuery { height })
+            .get("transaction-index-by-height")
+            .await
+    }
+
+    async fn find_transaction_by_wtxid(
+        &self,
+        wtxid: btc::Sha256d,
+    ) -> api::Result<Option<TransactionProof>> {
+        self.public(ApiKind::Service(ANCHORING_INSTANCE_NAME))
+            .query(&WtxidQuery { wtxid })
+            .get("find-transaction-by-wtxid")
+            .await
+    }
+
+    async fn transactions(&self, from: u64, count: u64) -> api::Result<AnchoringTransactionsPage> {
+        self.public(ApiKind::Service(ANCHORING_INSTANCE_NAME))
+            .query(&TransactionsRangeQuery { from, count })
+            .get("transactions")
+            .await
+    }
+
+    async fn nearest_anchoring_transaction(
+        &self,
+        height: Height,
+    ) -> api::Result<Option<TransactionProof>> {
+        self.public(ApiKind::Service(ANCHORING_INSTANCE_NAME))
+            .query(&BlockHeightQuery { height })
+            .get("nearest-anchoring-transaction")
+            .await
+    }
+
+    async fn find_transaction_by_block_hash(
+        &self,
+        block_hash: Hash,
+    ) -> api::Result<Option<BlockAnchorProof>> {
+        self.public(ApiKind::Service(ANCHORING_INSTANCE_NAME))
+            .query(&BlockHashQuery { block_hash })
+            .get("find-transaction-by-block-hash")
+            .await
+    }
+
+    async fn exonum_transaction_proof(
+        &self,
+        tx_hash: Hash,
+    ) -> api::Result<Option<ExonumTransactionProof>> {
+        self.public(ApiKind::Service(ANCHORING_INSTANCE_NAME))
+            .query(&TransactionHashQuery { tx_hash })
+            .get("exonum-transaction-proof")
+            .await
+    }
+
+    async fn block_header_proof(&self, height: Height) -> api::Result<Option<BlockHeaderProof>> {
+        self.public(ApiKind::Service(ANCHORING_INSTANCE_NAME))
+            .query(&BlockHeightQuery { height })
+            .get("block-header-proof")
+            .await
+    }
+
+    async fn transaction_inclusion_proof(
+        &self,
+        index: u64,
+    ) -> api::Result<Option<TransactionInclusionProof>> {
+        self.public(ApiKind::Service(ANCHORING_INSTANCE_NAME))
+            .query(&IndexQuery { index })
+            .get("transaction-inclusion-proof")
+            .await
+    }
+
+    async fn funding_inclusion_proof(
+        &self,
+        txid: btc::Sha256d,
+    ) -> api::Result<Option<FundingInclusionProof>> {
+        self.public(ApiKind::Service(ANCHORING_INSTANCE_NAME))
+            .query(&TxidQuery { txid })
+            .get("funding-inclusion-proof")
+            .await
+    }
+
+    async fn funding_history(&self) -> api::Result<FundingHistory> {
+        self.public(ApiKind::Service(ANCHORING_INSTANCE_NAME))
+            .get("funding-history")
+            .await
+    }
+
+    async fn input_signatures(
+        &self,
+        txid: btc::Sha256d,
+        input: u32,
+    ) -> api::Result<Vec<NodeInputSignature>> {
+        self.public(ApiKind::Service(ANCHORING_INSTANCE_NAME))
+            .query(&TxInputQuery { txid, input })
+            .get("input-signatures")
+            .await
+    }
+
+    async fn backup(&self) -> api::Result<AnchoringArchive> {
+        self.public(ApiKind::Service(ANCHORING_INSTANCE_NAME))
+            .get("backup")
+            .await
+    }
+
+    async fn cost_report(&self, query: CostReportQuery) -> api::Result<AnchoringCostReport> {
+        self.public(ApiKind::Service(ANCHORING_INSTANCE_NAME))
+            .query(&query)
+            .get("cost-report")
+            .await
+    }
+
+    async fn anchoring_gaps(&self) -> api::Result<Vec<AnchoringGap>> {
+        self.public(ApiKind::Service(ANCHORING_INSTANCE_NAME))
+            .get("anchoring-gaps")
+            .await
+    }
+
+    async fn transition_progress(&self) -> api::Result<Option<TransitionProgress>> {
+        self.public(ApiKind::Service(ANCHORING_INSTANCE_NAME))
+            .get("transition-progress")
+            .await
+    }
+
+    async fn transition_state(&self) -> api::Result<TransitionState> {
+        self.public(ApiKind::Service(ANCHORING_INSTANCE_NAME))
+            .get("transition-state")
+            .await
+    }
+
+    async fn signed_find_transaction(
+        &self,
+        height: Option<Height>,
+    ) -> api::Result<SignedResponse<TransactionProof>> {
+        self.public(ApiKind::Service(ANCHORING_INSTANCE_NAME))
+            .query(&FindTransactionQuery { height })
+            .get("signed/find-transaction")
+            .await
+    }
+
+    async fn signed_transaction_inclusion_proof(
+        &self,
+        index: u64,
+    ) -> api::Result<SignedResponse<Option<TransactionInclusionProof>>> {
+        self.public(ApiKind::Service(ANCHORING_INSTANCE_NAME))
+            .query(&IndexQuery { index })
+            .get("signed/transaction-inclusion-proof")
+            .await
+    }
 }
 
 #[async_trait]
@@ -425,6 +805,15 @@ impl PrivateApi for TestKitApiClient {
             .await
     }
 
+    async fn sign_inputs(&self, sign_inputs: Vec<SignInput>) -> api::Result<Hash> {
+        self.private(ApiKind::Service(ANCHORING_INSTANCE_NAME))
+            .query(&SignInputs {
+                inputs: sign_inputs,
+            })
+            .post("sign-inputs")
+            .await
+    }
+
     async fn add_funds(&self, transaction: btc::Transaction) -> api::Result<Hash> {
         self.private(ApiKind::Service(ANCHORING_INSTANCE_NAME))
             .query(&transaction)
@@ -438,12 +827,31 @@ impl PrivateApi for TestKitApiClient {
             .await
     }
 
+    async fn anchoring_proposal_psbt(&self) -> api::Result<Option<btc::Psbt>> {
+        self.private(ApiKind::Service(ANCHORING_INSTANCE_NAME))
+            .get("anchoring-proposal-psbt")
+            .await
+    }
+
+    async fn import_psbt(&self, psbt: btc::Psbt) -> api::Result<Vec<Hash>> {
+        self.private(ApiKind::Service(ANCHORING_INSTANCE_NAME))
+            .query(&psbt)
+            .post("import-psbt")
+            .await
+    }
+
     async fn config(&self) -> api::Result<Config> {
         self.private(ApiKind::Service(ANCHORING_INSTANCE_NAME))
             .get("config")
             .await
     }
 
+    async fn following_config(&self) -> api::Result<Option<Config>> {
+        self.private(ApiKind::Service(ANCHORING_INSTANCE_NAME))
+            .get("following-config")
+            .await
+    }
+
     async fn transaction_with_index(&self, index: u64) -> api::Result<Option<btc::Transaction>> {
         self.private(ApiKind::Service(ANCHORING_INSTANCE_NAME))
             .query(&IndexQuery { index })
@@ -456,6 +864,63 @@ impl PrivateApi for TestKitApiClient {
             .get("transactions-count")
             .await
     }
+
+    async fn transactions(&self, from: u64, count: u64) -> api::Result<AnchoringTransactionsPage> {
+        self.private(ApiKind::Service(ANCHORING_INSTANCE_NAME))
+            .query(&TransactionsRangeQuery { from, count })
+            .get("transactions")
+            .await
+    }
+
+    async fn stats(&self) -> api::Result<AnchoringStatistics> {
+        self.private(ApiKind::Service(ANCHORING_INSTANCE_NAME))
+            .get("stats")
+            .await
+    }
+
+    async fn signing_session(&self) -> api::Result<Option<SigningSession>> {
+        self.private(ApiKind::Service(ANCHORING_INSTANCE_NAME))
+            .get("signing-session")
+            .await
+    }
+
+    async fn report_inclusion_proof(&self, proof: btc::MerkleBlock) -> api::Result<Hash> {
+        self.private(ApiKind::Service(ANCHORING_INSTANCE_NAME))
+            .query(&proof)
+            .post("report-inclusion-proof")
+            .await
+    }
+
+    async fn vote_fee_rate(&self, fee_rate: u64) -> api::Result<Hash> {
+        self.private(ApiKind::Service(ANCHORING_INSTANCE_NAME))
+            .query(&VoteFeeRate { fee_rate })
+            .post("vote-fee-rate")
+            .await
+    }
+
+    async fn accelerate_anchoring(&self, txid: btc::Sha256d, fee_rate: u64) -> api::Result<Hash> {
+        self.private(ApiKind::Service(ANCHORING_INSTANCE_NAME))
+            .query(&AccelerateAnchoring { txid, fee_rate })
+            .post("accelerate-anchoring")
+            .await
+    }
+
+    async fn recover_anchoring(&self, txid: btc::Sha256d) -> api::Result<Hash> {
+        self.private(ApiKind::Service(ANCHORING_INSTANCE_NAME))
+            .query(&RecoverAnchoring { txid })
+            .post("recover-anchoring")
+            .await
+    }
+
+    async fn key_replacement_proposal(
+        &self,
+        query: KeyReplacementQuery,
+    ) -> api::Result<ConfigPropose> {
+        self.private(ApiKind::Service(ANCHORING_INSTANCE_NAME))
+            .query(&query)
+            .get("key-replacement-proposal")
+            .await
+    }
 }
 
 /// Proof validation extension.
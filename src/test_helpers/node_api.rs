@@ -0,0 +1,326 @@
+// Copyright 2020 The Exonum Team
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! HTTP API of the anchoring service on behalf of the arbitrary testkit nodes.
+
+use async_trait::async_trait;
+use exonum::{
+    blockchain::Blockchain,
+    crypto::{Hash, KeyPair, PublicKey},
+    merkledb::{self, Database, Patch, Snapshot},
+    runtime::{InstanceState, Runtime, SnapshotExt},
+};
+use exonum_api::ApiBuilder;
+use exonum_node::{NodePlugin, PluginApiContext};
+use exonum_rust_runtime::{api, RustRuntimeBuilder};
+use exonum_supervisor::ConfigPropose;
+use exonum_testkit::TestKitApiClient;
+use futures::{channel::mpsc, FutureExt, StreamExt};
+
+use std::{
+    fmt,
+    sync::{Arc, Mutex},
+};
+
+use super::ANCHORING_INSTANCE_NAME;
+use crate::{
+    api::{
+        AnchoringChainLength, AnchoringProposalState, AnchoringStatistics,
+        AnchoringTransactionsPage, ApiConfig, IndexQuery, KeyReplacementQuery, PrivateApi,
+        SigningSession, TransactionsRangeQuery,
+    },
+    blockchain::{AccelerateAnchoring, RecoverAnchoring, SignInput, SignInputs, VoteFeeRate},
+    btc,
+    config::Config,
+    BtcAnchoringService,
+};
+
+/// Read-only access to the database of the testkit.
+///
+/// The API of the services never modifies the database directly, the transactions are
+/// sent to the testkit through the API sender instead.
+struct TestKitDatabase(Blockchain);
+
+impl Database for TestKitDatabase {
+    fn snapshot(&self) -> Box<dyn Snapshot> {
+        self.0.snapshot()
+    }
+
+    fn merge(&self, _patch: Patch) -> merkledb::Result<()> {
+        Err(merkledb::Error::new(
+            "The database of the node API is read-only",
+        ))
+    }
+
+    fn merge_sync(&self, patch: Patch) -> merkledb::Result<()> {
+        self.merge(patch)
+    }
+}
+
+/// Testkit plugin which serves the HTTP API of the anchoring service on behalf of
+/// the nodes with the given service keys.
+///
+/// The anchoring service of each node is wired to the blockchain with the service key pair
+/// of this node, so the private API signs the transactions exactly as the API of this node
+/// would do. The endpoints of the node are available under the `api/nodes/{service_key}`
+/// prefix of the testkit API.
+#[derive(Debug)]
+pub(crate) struct NodeApiPlugin {
+    nodes: Arc<Mutex<Vec<KeyPair>>>,
+    api_config: ApiConfig,
+}
+
+impl NodeApiPlugin {
+    /// Creates a plugin for the shared list of the nodes, so the nodes added later are
+    /// served as soon as the testkit API endpoints are updated.
+    pub fn new(nodes: Arc<Mutex<Vec<KeyPair>>>, api_config: ApiConfig) -> Self {
+        Self { nodes, api_config }
+    }
+
+    fn node_endpoints(
+        &self,
+        blockchain: &Blockchain,
+        snapshot: &dyn Snapshot,
+        instance: &InstanceState,
+        keypair: KeyPair,
+    ) -> Option<(String, ApiBuilder)> {
+        let path = NodeApiKind(keypair.public_key()).path();
+        let node_blockchain = Blockchain::new(
+            Arc::new(TestKitDatabase(blockchain.clone())) as Arc<dyn Database>,
+            keypair.clone(),
+            blockchain.sender().clone(),
+        );
+        // Responses of the proof endpoints are signed by the service key of the node.
+        let service =
+            BtcAnchoringService::with_api_signing_key(keypair).with_api_config(self.api_config);
+
+        // The Rust runtime wires the service API as soon as the service is resumed.
+        let (api_notifier, mut api_updates) = mpsc::channel(1);
+        let mut runtime = RustRuntimeBuilder::new()
+            .with_factory(service)
+            .build(api_notifier);
+        runtime.initialize(&node_blockchain);
+        runtime.deploy_artifact(instance.spec.artifact.clone(), Vec::new());
+        runtime.update_service_status(snapshot, instance);
+        runtime.on_resume();
+
+        let service_path = ["services/", ANCHORING_INSTANCE_NAME].concat();
+        api_updates
+            .next()
+            .now_or_never()??
+            .into_endpoints()
+            .into_iter()
+            .find(|(endpoints_path, _)| *endpoints_path == service_path)
+            .map(|(_, builder)| (path, builder))
+    }
+}
+
+impl NodePlugin for NodeApiPlugin {
+    fn wire_api(&self, context: PluginApiContext<'_>) -> Vec<(String, ApiBuilder)> {
+        let blockchain = context.blockchain();
+        let snapshot = blockchain.snapshot();
+        let instance = match snapshot
+            .for_dispatcher()
+            .get_instance(ANCHORING_INSTANCE_NAME)
+        {
+            Some(instance) => instance,
+            None => return Vec::new(),
+        };
+
+        let nodes = self.nodes.lock().unwrap().clone();
+        nodes
+            .into_iter()
+            .filter_map(|keypair| {
+                self.node_endpoints(blockchain, snapshot.as_ref(), &instance, keypair)
+            })
+            .collect()
+    }
+}
+
+/// Endpoints of the anchoring service served on behalf of the node with the given
+/// service key.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct NodeApiKind(pub PublicKey);
+
+impl NodeApiKind {
+    fn path(self) -> String {
+        format!(
+            "nodes/{}/services/{}",
+            self.0.to_hex(),
+            ANCHORING_INSTANCE_NAME
+        )
+    }
+}
+
+impl fmt::Display for NodeApiKind {
+    fn fmt(&self, formatter: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(formatter, "api/{}", self.path())
+    }
+}
+
+/// Private API of an arbitrary anchoring node.
+///
+/// The requests are handled by the anchoring service API wired on behalf of the node,
+/// so the transactions are signed by the service key of this node.
+#[derive(Debug, Clone)]
+pub struct NodePrivateApi {
+    client: TestKitApiClient,
+    kind: NodeApiKind,
+}
+
+impl NodePrivateApi {
+    /// Creates the private API of the node with the given service key, which uses
+    /// the given testkit API client.
+    pub fn new(client: TestKitApiClient, service_key: PublicKey) -> Self {
+        Self {
+            client,
+            kind: NodeApiKind(service_key),
+        }
+    }
+}
+
+#[async_trait]
+impl PrivateApi for NodePrivateApi {
+    type Error = api::Error;
+
+    async fn sign_input(&self, sign_input: SignInput) -> api::Result<Hash> {
+        self.client
+            .private(self.kind)
+            .query(&sign_input)
+            .post("sign-input")
+            .await
+    }
+
+    async fn sign_inputs(&self, sign_inputs: Vec<SignInput>) -> api::Result<Hash> {
+        self.client
+            .private(self.kind)
+            .query(&SignInputs {
+                inputs: sign_inputs,
+            })
+            .post("sign-inputs")
+            .await
+    }
+
+    async fn add_funds(&self, transaction: btc::Transaction) -> api::Result<Hash> {
+        self.client
+            .private(self.kind)
+            .query(&transaction)
+            .post("add-funds")
+            .await
+    }
+
+    async fn anchoring_proposal(&self) -> api::Result<AnchoringProposalState> {
+        self.client
+            .private(self.kind)
+            .get("anchoring-proposal")
+            .await
+    }
+
+    async fn anchoring_proposal_psbt(&self) -> api::Result<Option<btc::Psbt>> {
+        self.client
+            .private(self.kind)
+            .get("anchoring-proposal-psbt")
+            .await
+    }
+
+    async fn import_psbt(&self, psbt: btc::Psbt) -> api::Result<Vec<Hash>> {
+        self.client
+            .private(self.kind)
+            .query(&psbt)
+            .post("import-psbt")
+            .await
+    }
+
+    async fn config(&self) -> api::Result<Config> {
+        self.client.private(self.kind).get("config").await
+    }
+
+    async fn following_config(&self) -> api::Result<Option<Config>> {
+        self.client.private(self.kind).get("following-config").await
+    }
+
+    async fn transaction_with_index(&self, index: u64) -> api::Result<Option<btc::Transaction>> {
+        self.client
+            .private(self.kind)
+            .query(&IndexQuery { index })
+            .get("transaction")
+            .await
+    }
+
+    async fn transactions_count(&self) -> api::Result<AnchoringChainLength> {
+        self.client
+            .private(self.kind)
+            .get("transactions-count")
+            .await
+    }
+
+    async fn transactions(&self, from: u64, count: u64) -> api::Result<AnchoringTransactionsPage> {
+        self.client
+            .private(self.kind)
+            .query(&TransactionsRangeQuery { from, count })
+            .get("transactions")
+            .await
+    }
+
+    async fn stats(&self) -> api::Result<AnchoringStatistics> {
+        self.client.private(self.kind).get("stats").await
+    }
+
+    async fn signing_session(&self) -> api::Result<Option<SigningSession>> {
+        self.client.private(self.kind).get("signing-session").await
+    }
+
+    async fn report_inclusion_proof(&self, proof: btc::MerkleBlock) -> api::Result<Hash> {
+        self.client
+            .private(self.kind)
+            .query(&proof)
+            .post("report-inclusion-proof")
+            .await
+    }
+
+    async fn vote_fee_rate(&self, fee_rate: u64) -> api::Result<Hash> {
+        self.client
+            .private(self.kind)
+            .query(&VoteFeeRate { fee_rate })
+            .post("vote-fee-rate")
+            .await
+    }
+
+    async fn accelerate_anchoring(&self, txid: btc::Sha256d, fee_rate: u64) -> api::Result<Hash> {
+        self.client
+            .private(self.kind)
+            .query(&AccelerateAnchoring { txid, fee_rate })
+            .post("accelerate-anchoring")
+            .await
+    }
+
+    async fn recover_anchoring(&self, txid: btc::Sha256d) -> api::Result<Hash> {
+        self.client
+            .private(self.kind)
+            .query(&RecoverAnchoring { txid })
+            .post("recover-anchoring")
+            .await
+    }
+
+    async fn key_replacement_proposal(
+        &self,
+        query: KeyReplacementQuery,
+    ) -> api::Result<ConfigPropose> {
+        self.client
+            .private(self.kind)
+            .query(&query)
+            .get("key-replacement-proposal")
+            .await
+    }
+}
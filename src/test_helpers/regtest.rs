@@ -0,0 +1,217 @@
+// Copyright 2020 The Exonum Team
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Regtest Bitcoin node for the end-to-end tests of the sync tasks.
+//!
+//! The node is spawned from the `bitcoind` executable, which is looked up in `PATH`
+//! or taken from the `BITCOIND_EXE` environment variable.
+
+use anyhow::{anyhow, ensure};
+use bitcoin::Amount;
+use bitcoincore_rpc::RpcApi;
+
+use std::{
+    env, fs,
+    net::TcpListener,
+    path::PathBuf,
+    process::{Child, Command, Stdio},
+    thread,
+    time::{Duration, Instant},
+};
+
+use super::AnchoringTestKit;
+use crate::{
+    btc,
+    sync::{BitcoindRelay, BitcoindRelayConfig, BitcoindRpcClient},
+};
+
+/// Maximum time to wait for the RPC of the spawned node.
+const STARTUP_TIMEOUT: Duration = Duration::from_secs(30);
+/// Maximum time to wait for the node to shut down gracefully.
+const SHUTDOWN_TIMEOUT: Duration = Duration::from_secs(10);
+/// Number of blocks to mine to make the coinbase outputs spendable.
+const COINBASE_MATURITY: u64 = 101;
+
+/// Bitcoin node in the regtest mode with a wallet of the mined coins.
+///
+/// The node is stopped and its data directory is removed on drop.
+#[derive(Debug)]
+pub struct RegtestNode {
+    process: Child,
+    datadir: PathBuf,
+    rpc_config: BitcoindRelayConfig,
+    client: BitcoindRpcClient,
+    mining_address: bitcoin::Address,
+}
+
+impl RegtestNode {
+    /// Spawns a new node and mines the blocks with the spendable coins to its wallet.
+    pub fn spawn() -> anyhow::Result<Self> {
+        let rpc_port = free_port()?;
+        let datadir = env::temp_dir().join(format!(
+            "anchoring-regtest-{}-{}",
+            std::process::id(),
+            rpc_port
+        ));
+        fs::create_dir_all(&datadir)?;
+
+        let bitcoind = env::var_os("BITCOIND_EXE").unwrap_or_else(|| "bitcoind".into());
+        let mut process = Command::new(&bitcoind)
+            .arg("-regtest")
+            .arg("-server")
+            .arg("-listen=0")
+            .arg("-txindex")
+            .arg("-fallbackfee=0.0001")
+            .arg("-rpcuser=anchoring")
+            .arg("-rpcpassword=anchoring")
+            .arg(format!("-rpcport={}", rpc_port))
+            .arg(format!("-datadir={}", datadir.display()))
+            .stdout(Stdio::null())
+            .spawn()
+            .map_err(|e| anyhow!("Unable to spawn {:?}: {}", bitcoind, e))?;
+
+        let rpc_config = BitcoindRelayConfig {
+            host: format!("http://127.0.0.1:{}", rpc_port),
+            user: Some("anchoring".to_owned()),
+            password: Some("anchoring".to_owned()),
+            ..BitcoindRelayConfig::default()
+        };
+        let client = rpc_config.client()?;
+        let mining_address = match init_wallet(&mut process, &client) {
+            Ok(address) => address,
+            Err(e) => {
+                let _ = process.kill();
+                let _ = process.wait();
+                let _ = fs::remove_dir_all(&datadir);
+                return Err(e);
+            }
+        };
+
+        let node = Self {
+            process,
+            datadir,
+            rpc_config,
+            client,
+            mining_address,
+        };
+        node.mine(COINBASE_MATURITY)?;
+        Ok(node)
+    }
+
+    /// Returns the configuration of the node RPC.
+    pub fn rpc_config(&self) -> BitcoindRelayConfig {
+        self.rpc_config.clone()
+    }
+
+    /// Returns the RPC client of the node.
+    pub fn client(&self) -> &BitcoindRpcClient {
+        &self.client
+    }
+
+    /// Creates a Bitcoin relay connected to the node.
+    pub fn relay(&self) -> anyhow::Result<BitcoindRelay> {
+        BitcoindRelay::new(&self.rpc_config).map_err(From::from)
+    }
+
+    /// Mines the given number of blocks.
+    pub fn mine(&self, blocks: u64) -> anyhow::Result<()> {
+        self.client
+            .generate_to_address(blocks, &self.mining_address)?;
+        Ok(())
+    }
+
+    /// Sends the given amount to the address from the node wallet and confirms
+    /// the transaction by a new block.
+    pub fn fund(&self, address: &btc::Address, satoshis: u64) -> anyhow::Result<btc::Transaction> {
+        let txid = self.client.send_to_address(
+            &address.0,
+            Amount::from_sat(satoshis),
+            None,
+            None,
+            None,
+            None,
+            None,
+            None,
+        )?;
+        self.mine(1)?;
+        let transaction = self.client.get_raw_transaction(&txid, None)?;
+        Ok(btc::Transaction(transaction))
+    }
+}
+
+impl Drop for RegtestNode {
+    fn drop(&mut self) {
+        if self.client.stop().is_ok() {
+            let start = Instant::now();
+            while start.elapsed() < SHUTDOWN_TIMEOUT {
+                if let Ok(Some(_)) = self.process.try_wait() {
+                    break;
+                }
+                thread::sleep(Duration::from_millis(100));
+            }
+        }
+        let _ = self.process.kill();
+        let _ = self.process.wait();
+        let _ = fs::remove_dir_all(&self.datadir);
+    }
+}
+
+impl AnchoringTestKit {
+    /// Funds the actual anchoring address from the regtest node wallet and commits
+    /// the funding confirmation transactions of all anchoring nodes.
+    pub fn fund_from_regtest(
+        &mut self,
+        node: &RegtestNode,
+        satoshis: u64,
+    ) -> anyhow::Result<btc::Transaction> {
+        let address = self.actual_anchoring_config().anchoring_address();
+        let transaction = node.fund(&address, satoshis)?;
+        self.inner.create_block_with_transactions(
+            self.create_funding_confirmation_txs_with(transaction.clone()),
+        );
+        Ok(transaction)
+    }
+}
+
+/// Waits for the RPC of the spawned node and returns a new address of its wallet.
+fn init_wallet(
+    process: &mut Child,
+    client: &BitcoindRpcClient,
+) -> anyhow::Result<bitcoin::Address> {
+    let start = Instant::now();
+    loop {
+        if let Some(status) = process.try_wait()? {
+            return Err(anyhow!("Bitcoin node has exited with {}", status));
+        }
+        match client.get_blockchain_info() {
+            Ok(_) => break,
+            Err(e) => ensure!(
+                start.elapsed() < STARTUP_TIMEOUT,
+                "Bitcoin node RPC is unavailable: {}",
+                e
+            ),
+        }
+        thread::sleep(Duration::from_millis(100));
+    }
+
+    // Since Bitcoin Core 0.21 the wallet isn't created by default, while the previous
+    // versions don't support the wallet creation with the existing name.
+    let _ = client.create_wallet("anchoring", None);
+    client.get_new_address(None, None).map_err(From::from)
+}
+
+fn free_port() -> anyhow::Result<u16> {
+    let listener = TcpListener::bind("127.0.0.1:0")?;
+    Ok(listener.local_addr()?.port())
+}
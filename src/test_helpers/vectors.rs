@@ -0,0 +1,248 @@
+// Copyright 2020 The Exonum Team
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Canonical test vectors of the anchoring protocol.
+//!
+//! Test vectors are generated from the fixed keys and transactions, so they can be
+//! stored as JSON fixtures and used to validate the independent implementations
+//! of the anchoring transactions verifiers byte-for-byte.
+
+use bitcoin::{network::constants::Network, Script};
+use btc_transaction_utils::{multisig::RedeemScript, p2wsh, TxInRef};
+use exonum::{
+    crypto::{self, Hash, KeyPair, Seed, SEED_LENGTH},
+    helpers::Height,
+};
+use exonum_merkledb::ObjectHash;
+use serde_derive::{Deserialize, Serialize};
+
+use crate::{
+    btc::{self, BtcAnchoringTransactionBuilder, Payload, PayloadFormat},
+    config::Config,
+    proto::AnchoringKeys,
+};
+
+/// Value of the funding transaction output in satoshis.
+const FUNDING_VALUE: u64 = 100_000;
+
+/// Test vector of the first anchoring transaction for the specific configuration.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct TestVector {
+    /// Short description of the test case.
+    pub description: String,
+    /// Anchoring configuration.
+    pub config: Config,
+    /// Object hash of the anchoring configuration.
+    pub config_digest: Hash,
+    /// Redeem script of the anchoring address.
+    pub redeem_script: RedeemScript,
+    /// Anchoring address.
+    pub anchoring_address: btc::Address,
+    /// Funding transaction spent by the anchoring transaction.
+    pub funding_transaction: btc::Transaction,
+    /// Payload of the anchoring transaction.
+    pub payload: Payload,
+    /// Hex-encoded `OP_RETURN` scripts with the encoded payload.
+    pub payload_scripts: Vec<String>,
+    /// Anchoring transaction without the witness data.
+    pub unsigned_transaction: btc::Transaction,
+    /// BIP-143 signature hashes of the anchoring transaction inputs.
+    pub sighashes: Vec<btc::Sha256d>,
+}
+
+struct TestCase {
+    description: &'static str,
+    network: Network,
+    nodes_num: u8,
+    payload_format: PayloadFormat,
+    extension_digests: Vec<Hash>,
+}
+
+impl TestCase {
+    fn config(&self) -> Config {
+        let anchoring_keys = (1..=self.nodes_num).map(|seed| {
+            let service_key = KeyPair::from_seed(&Seed::new([seed; SEED_LENGTH])).public_key();
+            let bitcoin_key = bitcoin_key(self.network, seed);
+            AnchoringKeys {
+                bitcoin_key,
+                service_key,
+            }
+        });
+        Config {
+            compact_payload: self.payload_format == PayloadFormat::Compact,
+            extensible_payload: self.payload_format == PayloadFormat::Extensible,
+            ..Config::with_public_keys(self.network, anchoring_keys).unwrap()
+        }
+    }
+
+    fn into_vector(self) -> TestVector {
+        let config = self.config();
+        let config_digest = config.object_hash();
+        let redeem_script = config.redeem_script();
+        let anchoring_address = config.anchoring_address();
+        let funding_transaction = super::funding_transaction(
+            &anchoring_address,
+            FUNDING_VALUE,
+            btc::Sha256d::new([self.nodes_num; btc::Sha256d::LEN]),
+        );
+
+        let block_height = Height(0);
+        let block_hash = crypto::hash(self.description.as_bytes());
+        let mut builder = BtcAnchoringTransactionBuilder::new(&redeem_script);
+        builder
+            .additional_funds(funding_transaction.clone())
+            .unwrap();
+        builder.fee(config.transaction_fee);
+        builder.payload(block_height, block_hash);
+        builder.config_digest(config_digest);
+        builder.extension_digests(self.extension_digests);
+        builder.payload_format(config.payload_format());
+        let (unsigned_transaction, inputs) = builder.create().unwrap();
+
+        let payload = unsigned_transaction.anchoring_payload().unwrap();
+        let payload_scripts = unsigned_transaction.0.output[1..]
+            .iter()
+            .map(|output| hex::encode(output.script_pubkey.as_bytes()))
+            .collect();
+        let mut signer = p2wsh::InputSigner::new(redeem_script.clone());
+        let sighashes = inputs
+            .iter()
+            .enumerate()
+            .map(|(index, input)| {
+                signer
+                    .signature_hash(
+                        TxInRef::new(unsigned_transaction.as_ref(), index),
+                        input.as_ref(),
+                    )
+                    .into()
+            })
+            .collect();
+
+        TestVector {
+            description: self.description.to_owned(),
+            config,
+            config_digest,
+            redeem_script,
+            anchoring_address,
+            funding_transaction,
+            payload,
+            payload_scripts,
+            unsigned_transaction,
+            sighashes,
+        }
+    }
+}
+
+fn bitcoin_key(network: Network, seed: u8) -> btc::PublicKey {
+    let secret_key = secp256k1::SecretKey::from_slice(&[seed; 32]).unwrap();
+    let private_key = bitcoin::PrivateKey {
+        compressed: true,
+        network,
+        key: secret_key,
+    };
+    btc::PublicKey(private_key.public_key(&secp256k1::Secp256k1::signing_only()))
+}
+
+/// Generates the canonical test vectors.
+pub fn generate_test_vectors() -> Vec<TestVector> {
+    let test_cases = vec![
+        TestCase {
+            description: "Single anchoring node in the testnet",
+            network: Network::Testnet,
+            nodes_num: 1,
+            payload_format: PayloadFormat::Standard,
+            extension_digests: vec![],
+        },
+        TestCase {
+            description: "Four anchoring nodes in the mainnet",
+            network: Network::Bitcoin,
+            nodes_num: 4,
+            payload_format: PayloadFormat::Standard,
+            extension_digests: vec![],
+        },
+        TestCase {
+            description: "Seven anchoring nodes with the compact payload",
+            network: Network::Testnet,
+            nodes_num: 7,
+            payload_format: PayloadFormat::Compact,
+            extension_digests: vec![],
+        },
+        TestCase {
+            description: "Four anchoring nodes with the extension digests",
+            network: Network::Regtest,
+            nodes_num: 4,
+            payload_format: PayloadFormat::Standard,
+            extension_digests: (0..4_u8).map(|i| crypto::hash(&[i])).collect(),
+        },
+        TestCase {
+            description: "Four anchoring nodes with the extensible payload",
+            network: Network::Testnet,
+            nodes_num: 4,
+            payload_format: PayloadFormat::Extensible,
+            extension_digests: vec![crypto::hash(&[0])],
+        },
+    ];
+    test_cases.into_iter().map(TestCase::into_vector).collect()
+}
+
+/// Checks that the given test vector is consistent with this implementation.
+pub fn verify_test_vector(vector: &TestVector) -> anyhow::Result<()> {
+    anyhow::ensure!(
+        vector.config.object_hash() == vector.config_digest,
+        "Config digest mismatch"
+    );
+    anyhow::ensure!(
+        vector.config.redeem_script() == vector.redeem_script,
+        "Redeem script mismatch"
+    );
+    anyhow::ensure!(
+        vector.config.anchoring_address() == vector.anchoring_address,
+        "Anchoring address mismatch"
+    );
+    let scripts = vector
+        .payload_scripts
+        .iter()
+        .map(|script| hex::decode(script).map(Script::from))
+        .collect::<Result<Vec<_>, _>>()?;
+    anyhow::ensure!(
+        Payload::from_scripts(&scripts).as_ref() == Some(&vector.payload),
+        "Payload scripts mismatch"
+    );
+    anyhow::ensure!(
+        vector.unsigned_transaction.anchoring_payload().as_ref() == Some(&vector.payload),
+        "Anchoring transaction payload mismatch"
+    );
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_vectors_are_deterministic() {
+        let vectors = generate_test_vectors();
+        assert_eq!(vectors, generate_test_vectors());
+        for vector in &vectors {
+            verify_test_vector(vector).unwrap();
+            assert_eq!(vector.sighashes.len(), 1);
+            assert_eq!(vector.payload.config_digest, Some(vector.config_digest));
+        }
+
+        // Round trip through the JSON fixture.
+        let json = serde_json::to_string_pretty(&vectors).unwrap();
+        let vectors_from_json: Vec<TestVector> = serde_json::from_str(&json).unwrap();
+        assert_eq!(vectors_from_json, vectors);
+    }
+}
@@ -12,16 +12,30 @@
 // limitations under the License.
 
 use btc_transaction_utils::{p2wsh, TxInRef};
-use exonum::helpers::Height;
+use exonum::{crypto::Hash, helpers::Height};
 use exonum_btc_anchoring::{
-    api::{AnchoringProposalState, PrivateApi, PublicApi},
-    blockchain::SignInput,
+    api::{
+        AnchoredHeightIndex, AnchoringAddressRange, AnchoringProofBundle, AnchoringProposalState,
+        ApiConfig, ApiErrorKind, CostReportQuery, FundingHistory, FundingInclusionProof,
+        FundingTransactionInfo, KeyReplacementQuery, NodeInputSignature, PrivateApi, PublicApi,
+        TransactionInclusionProof, TransactionsRangeQuery, TransitionStage,
+    },
+    blockchain::{
+        backup::{AnchoringArchive, AnchoringBackup},
+        data_layout::{AnchoringDelayReason, TransitionState},
+        BtcAnchoringInterface, ReportBitcoinHeaders, ReportInclusionProof, Schema, SignInput,
+        FINALITY_DEPTH,
+    },
     btc,
+    sync::ApiClientError,
     test_helpers::{
-        create_fake_funding_transaction, get_anchoring_schema, AnchoringTestKit, ValidateProof,
-        ANCHORING_INSTANCE_ID,
+        create_fake_bitcoin_headers, create_fake_funding_transaction, create_fake_merkle_block,
+        get_anchoring_schema, AnchoringTestKit, ValidateProof, ANCHORING_INSTANCE_ID,
+        ANCHORING_INSTANCE_NAME,
     },
 };
+use exonum_merkledb::{access::Prefixed, Database, ObjectHash, TemporaryDB};
+use exonum_rust_runtime::api::{self, HttpStatusCode};
 use exonum_supervisor::ConfigPropose;
 use exonum_testkit::TestKitApi;
 
@@ -107,6 +121,7 @@ async fn following_address() {
     // Add an anchoring node.
     let mut new_cfg = anchoring_testkit.actual_anchoring_config();
     new_cfg.anchoring_keys.push(anchoring_testkit.add_node());
+    new_cfg.sort_anchoring_keys();
     let following_address = new_cfg.anchoring_address();
 
     // Commit configuration with without last anchoring node.
@@ -120,19 +135,23 @@ async fn following_address() {
 
     assert_eq!(
         anchoring_api.client().following_address().await.unwrap(),
-        Some(following_address)
+        Some(following_address.clone())
     );
+    let following_config = PrivateApi::following_config(anchoring_api.client())
+        .await
+        .unwrap()
+        .unwrap();
+    assert_eq!(following_config.anchoring_address(), following_address);
 }
 
 #[tokio::test]
-async fn find_transaction_regular() {
+async fn backup() {
     let (mut anchoring_testkit, anchoring_api) = init_testkit();
     let anchoring_interval = anchoring_testkit
         .actual_anchoring_config()
         .anchoring_interval;
-
-    // Create a several anchoring transactions
-    for i in 1..=5 {
+    // Establish anchoring transactions chain.
+    for height in 1..=2 {
         anchoring_testkit.inner.create_block_with_transactions(
             anchoring_testkit
                 .create_signature_txs()
@@ -141,61 +160,99 @@ async fn find_transaction_regular() {
         );
         anchoring_testkit
             .inner
-            .create_blocks_until(Height(anchoring_interval * i));
+            .create_blocks_until(Height(anchoring_interval * height));
     }
 
-    let snapshot = anchoring_testkit.inner.snapshot();
-    let anchoring_schema = get_anchoring_schema(&snapshot);
-    let tx_chain = anchoring_schema.transactions_chain;
-    // Find transactions by height.
-    assert_eq!(
-        find_transaction(&anchoring_testkit, &anchoring_api, Some(Height(0)))
-            .await
-            .unwrap(),
-        tx_chain.get(0).unwrap()
-    );
+    let archive = anchoring_api.client().backup().await.unwrap();
+    assert_eq!(archive.state.transactions.len(), 2);
     assert_eq!(
-        find_transaction(&anchoring_testkit, &anchoring_api, Some(Height(3)))
-            .await
-            .unwrap(),
-        tx_chain.get(1).unwrap()
+        archive.state.actual_config,
+        anchoring_testkit.actual_anchoring_config()
     );
-    assert_eq!(
-        find_transaction(&anchoring_testkit, &anchoring_api, Some(Height(4)))
-            .await
-            .unwrap(),
-        tx_chain.get(1).unwrap()
+    assert_eq!(archive.state.spent_funding_transactions.len(), 1);
+
+    // Make a round trip through the archive file format.
+    let mut bytes = Vec::new();
+    archive.write_to(&mut bytes).unwrap();
+    let archive = AnchoringArchive::read_from(bytes.as_slice()).unwrap();
+
+    // Import the anchoring state into an empty database.
+    let db = TemporaryDB::new();
+    let fork = db.fork();
+    let mut schema = Schema::new(Prefixed::new("anchoring", &fork));
+    archive.state.clone().import(&mut schema).unwrap();
+    assert_eq!(AnchoringBackup::export(&schema), archive.state);
+    let tx = anchoring_testkit.last_anchoring_tx().unwrap();
+    assert_eq!(schema.transaction_index(&tx.id()), Some(1));
+    // Non-empty schema cannot be overwritten.
+    assert!(archive.state.clone().import(&mut schema).is_err());
+
+    // Tampered archive is rejected.
+    let mut tampered = archive;
+    tampered.state.transactions.pop();
+    let mut bytes = Vec::new();
+    tampered.write_to(&mut bytes).unwrap();
+    assert!(AnchoringArchive::read_from(bytes.as_slice()).is_err());
+}
+
+#[tokio::test]
+async fn signed_proof_responses() {
+    let (mut anchoring_testkit, anchoring_api) = init_testkit();
+    let anchoring_interval = anchoring_testkit
+        .actual_anchoring_config()
+        .anchoring_interval;
+    anchoring_testkit.inner.create_block_with_transactions(
+        anchoring_testkit
+            .create_signature_txs()
+            .into_iter()
+            .flatten(),
     );
+    anchoring_testkit
+        .inner
+        .create_blocks_until(Height(anchoring_interval));
+
+    let node_key = anchoring_testkit.inner.us().service_keypair().public_key();
+    let client = anchoring_api.client();
+
+    let signed = client.signed_find_transaction(None).await.unwrap();
+    let proof = signed.verify(&node_key).unwrap();
+    let unsigned = client.find_transaction(None).await.unwrap();
     assert_eq!(
-        find_transaction(&anchoring_testkit, &anchoring_api, Some(Height(1000)))
-            .await
-            .unwrap(),
-        tx_chain.get(4).unwrap()
+        serde_json::to_value(&proof).unwrap(),
+        serde_json::to_value(&unsigned).unwrap()
     );
+
+    let signed = client.signed_transaction_inclusion_proof(0).await.unwrap();
     assert_eq!(
-        find_transaction(&anchoring_testkit, &anchoring_api, None)
-            .await
-            .unwrap(),
-        tx_chain.last().unwrap()
+        signed.verify(&node_key).unwrap(),
+        client.transaction_inclusion_proof(0).await.unwrap()
     );
-    // Find transactions by index.
-    for i in 0..=tx_chain.len() {
-        assert_eq!(
-            transaction_with_index(&anchoring_api, i).await,
-            tx_chain.get(i)
-        );
-    }
+
+    // The response cannot be verified with another key.
+    let other_key = exonum::crypto::KeyPair::random().public_key();
+    assert!(signed.verify(&other_key).is_err());
+    // Tampered payload is rejected.
+    let mut tampered = signed;
+    tampered.payload.push(' ');
+    assert!(tampered.verify(&node_key).is_err());
 }
 
-// Check come edge cases in the find_transaction api method.
 #[tokio::test]
-async fn find_transaction_configuration_change() {
+async fn address_history() {
     let (mut anchoring_testkit, anchoring_api) = init_testkit();
     let anchoring_interval = anchoring_testkit
         .actual_anchoring_config()
         .anchoring_interval;
+    let initial_address = anchoring_testkit
+        .actual_anchoring_config()
+        .anchoring_address();
 
-    assert!(anchoring_testkit.last_anchoring_tx().is_none());
+    assert!(anchoring_api
+        .client()
+        .address_history()
+        .await
+        .unwrap()
+        .is_empty());
     // Establish anchoring transactions chain.
     anchoring_testkit.inner.create_block_with_transactions(
         anchoring_testkit
@@ -212,8 +269,9 @@ async fn find_transaction_configuration_change() {
     // Add an anchoring node.
     let mut new_cfg = anchoring_testkit.actual_anchoring_config();
     new_cfg.anchoring_keys.push(anchoring_testkit.add_node());
+    new_cfg.sort_anchoring_keys();
+    let following_address = new_cfg.anchoring_address();
 
-    // Commit configuration with without last anchoring node.
     anchoring_testkit.inner.create_block_with_transaction(
         anchoring_testkit.create_config_change_tx(
             ConfigPropose::new(0, anchoring_testkit.inner.height().next())
@@ -229,23 +287,7 @@ async fn find_transaction_configuration_change() {
             .into_iter()
             .flatten(),
     );
-
-    let snapshot = anchoring_testkit.inner.snapshot();
-    let anchoring_schema = get_anchoring_schema(&snapshot);
-    assert_eq!(
-        find_transaction(&anchoring_testkit, &anchoring_api, Some(Height(0))).await,
-        anchoring_schema.transactions_chain.get(1)
-    );
-    assert_eq!(
-        find_transaction(&anchoring_testkit, &anchoring_api, Some(Height(1))).await,
-        anchoring_schema.transactions_chain.get(1)
-    );
-    assert_eq!(
-        find_transaction(&anchoring_testkit, &anchoring_api, None).await,
-        anchoring_schema.transactions_chain.get(1)
-    );
-
-    // Resume regular anchoring (anchors block on height 5).
+    // Resume regular anchoring.
     anchoring_testkit
         .inner
         .create_blocks_until(Height(anchoring_interval * 2));
@@ -256,132 +298,1573 @@ async fn find_transaction_configuration_change() {
             .flatten(),
     );
 
-    let snapshot = anchoring_testkit.inner.snapshot();
-    let anchoring_schema = get_anchoring_schema(&snapshot);
     assert_eq!(
-        find_transaction(&anchoring_testkit, &anchoring_api, Some(Height(0))).await,
-        anchoring_schema.transactions_chain.get(1)
-    );
-    assert_eq!(
-        find_transaction(&anchoring_testkit, &anchoring_api, Some(Height(1))).await,
-        anchoring_schema.transactions_chain.get(2)
+        anchoring_api.client().address_history().await.unwrap(),
+        vec![
+            AnchoringAddressRange {
+                address: initial_address,
+                first_transaction: 0,
+                last_transaction: 0,
+            },
+            AnchoringAddressRange {
+                address: following_address,
+                first_transaction: 1,
+                last_transaction: 2,
+            },
+        ]
     );
+}
+
+#[tokio::test]
+async fn transition_progress() {
+    let (mut anchoring_testkit, anchoring_api) = init_testkit();
     assert_eq!(
-        find_transaction(&anchoring_testkit, &anchoring_api, Some(Height(10))).await,
-        anchoring_schema.transactions_chain.get(2)
+        anchoring_api.client().transition_progress().await.unwrap(),
+        None
     );
     assert_eq!(
-        find_transaction(&anchoring_testkit, &anchoring_api, None).await,
-        anchoring_schema.transactions_chain.get(2)
+        anchoring_api.client().transition_state().await.unwrap(),
+        TransitionState::Regular
     );
-
-    // Anchors block on height 10.
+    // Establish anchoring transactions chain.
     anchoring_testkit.inner.create_block_with_transactions(
         anchoring_testkit
             .create_signature_txs()
             .into_iter()
             .flatten(),
     );
+    let last_anchoring_tx = anchoring_testkit.last_anchoring_tx().unwrap();
 
-    let snapshot = anchoring_testkit.inner.snapshot();
-    let anchoring_schema = get_anchoring_schema(&snapshot);
-    assert_eq!(
-        find_transaction(&anchoring_testkit, &anchoring_api, Some(Height(0))).await,
-        anchoring_schema.transactions_chain.get(1)
+    // Add an anchoring node.
+    let mut new_cfg = anchoring_testkit.actual_anchoring_config();
+    new_cfg.anchoring_keys.push(anchoring_testkit.add_node());
+    new_cfg.sort_anchoring_keys();
+    let started_at = anchoring_testkit.inner.height().next();
+    anchoring_testkit.inner.create_block_with_transaction(
+        anchoring_testkit.create_config_change_tx(
+            ConfigPropose::new(0, started_at)
+                .service_config(ANCHORING_INSTANCE_ID, new_cfg.clone()),
+        ),
     );
     assert_eq!(
-        find_transaction(&anchoring_testkit, &anchoring_api, Some(Height(1))).await,
-        anchoring_schema.transactions_chain.get(2)
+        anchoring_api.client().transition_state().await.unwrap(),
+        TransitionState::TransitionPending { started_at }
     );
+
+    // Transition transaction is being signed.
+    let (proposal, _) = anchoring_testkit.anchoring_transaction_proposal().unwrap();
+    let progress = anchoring_api
+        .client()
+        .transition_progress()
+        .await
+        .unwrap()
+        .unwrap();
+    assert_eq!(progress.following_config, new_cfg);
+    assert_eq!(progress.stage, TransitionStage::Signing);
+    assert_eq!(progress.txid, Some(proposal.id()));
+    assert_eq!(progress.index, None);
+    assert_eq!(progress.signing_session.unwrap().txid, proposal.id());
     assert_eq!(
-        find_transaction(&anchoring_testkit, &anchoring_api, Some(Height(5))).await,
-        anchoring_schema.transactions_chain.get(2)
+        progress.remaining_funds,
+        last_anchoring_tx.0.output[0].value
     );
-    assert_eq!(
-        find_transaction(&anchoring_testkit, &anchoring_api, Some(Height(6))).await,
-        anchoring_schema.transactions_chain.get(3)
+
+    // Transit to the new address.
+    let finalized_at = anchoring_testkit.inner.height().next();
+    anchoring_testkit.inner.create_block_with_transactions(
+        anchoring_testkit
+            .create_signature_txs()
+            .into_iter()
+            .flatten(),
     );
     assert_eq!(
-        find_transaction(&anchoring_testkit, &anchoring_api, None).await,
-        anchoring_schema.transactions_chain.get(3)
+        anchoring_api.client().transition_state().await.unwrap(),
+        TransitionState::TransitionBroadcast {
+            started_at,
+            finalized_at,
+            txid: proposal.id(),
+            index: 1,
+        }
     );
+    let progress = anchoring_api
+        .client()
+        .transition_progress()
+        .await
+        .unwrap()
+        .unwrap();
+    assert_eq!(progress.following_config, new_cfg);
+    assert_eq!(progress.stage, TransitionStage::Signed);
+    assert_eq!(progress.txid, Some(proposal.id()));
+    assert_eq!(progress.index, Some(1));
+    assert_eq!(progress.signing_session, None);
+    assert_eq!(progress.remaining_funds, 0);
 }
 
 #[tokio::test]
-async fn actual_config() {
-    let (anchoring_testkit, anchoring_api) = init_testkit();
-
-    let cfg = anchoring_testkit.actual_anchoring_config();
+async fn config_history() {
+    let (mut anchoring_testkit, anchoring_api) = init_testkit();
+    let initial_cfg = anchoring_testkit.actual_anchoring_config();
+    let history = anchoring_api.client().config_history().await.unwrap();
+    assert_eq!(history.len(), 1);
+    assert_eq!(history[0].actual_from, 0);
+    assert_eq!(history[0].config, initial_cfg);
 
-    let client = anchoring_api.client();
-    assert_eq!(PublicApi::config(client).await.unwrap(), cfg);
-    assert_eq!(PrivateApi::config(client).await.unwrap(), cfg);
-}
+    anchoring_testkit.inner.create_block_with_transactions(
+        anchoring_testkit
+            .create_signature_txs()
+            .into_iter()
+            .flatten(),
+    );
 
-#[tokio::test]
-async fn anchoring_proposal_ok() {
-    let (anchoring_testkit, anchoring_api) = init_testkit();
-    let proposal = anchoring_testkit.anchoring_transaction_proposal().unwrap();
+    // Configuration without the address change becomes actual immediately.
+    let mut fee_cfg = initial_cfg.clone();
+    fee_cfg.transaction_fee += 1;
+    let actual_from = anchoring_testkit.inner.height().next();
+    anchoring_testkit.inner.create_block_with_transaction(
+        anchoring_testkit.create_config_change_tx(
+            ConfigPropose::new(0, actual_from)
+                .service_config(ANCHORING_INSTANCE_ID, fee_cfg.clone()),
+        ),
+    );
+    let history = anchoring_api.client().config_history().await.unwrap();
+    assert_eq!(history.len(), 2);
+    assert_eq!(history[1].actual_from, actual_from.0);
+    assert_eq!(history[1].config, fee_cfg);
 
+    // Configuration with the new address becomes actual after the transition.
+    let mut new_cfg = fee_cfg.clone();
+    new_cfg.anchoring_keys.push(anchoring_testkit.add_node());
+    new_cfg.sort_anchoring_keys();
+    anchoring_testkit.inner.create_block_with_transaction(
+        anchoring_testkit.create_config_change_tx(
+            ConfigPropose::new(1, anchoring_testkit.inner.height().next())
+                .service_config(ANCHORING_INSTANCE_ID, new_cfg.clone()),
+        ),
+    );
     assert_eq!(
-        anchoring_api.client().anchoring_proposal().await.unwrap(),
-        AnchoringProposalState::Available {
-            transaction: proposal.0,
-            inputs: proposal.1,
-        }
+        anchoring_api.client().config_history().await.unwrap().len(),
+        2
     );
+
+    let actual_from = anchoring_testkit.inner.height().next();
+    anchoring_testkit.inner.create_block_with_transactions(
+        anchoring_testkit
+            .create_signature_txs()
+            .into_iter()
+            .flatten(),
+    );
+    let history = anchoring_api.client().config_history().await.unwrap();
+    assert_eq!(history.len(), 3);
+    assert_eq!(history[2].actual_from, actual_from.0);
+    assert_eq!(history[2].config, new_cfg);
 }
 
 #[tokio::test]
-async fn anchoring_proposal_none() {
-    let (mut anchoring_testkit, anchoring_api) = init_testkit();
-
-    // Establish anchoring transactions chain.
+async fn transaction_inclusion_proof() {
+    // Fake Bitcoin blocks are mined with the regtest difficulty.
+    let mut anchoring_testkit = AnchoringTestKit::with_network(4, 5, bitcoin::Network::Regtest);
+    anchoring_testkit.inner.create_block_with_transactions(
+        anchoring_testkit.create_funding_confirmation_txs(700_000).0,
+    );
+    let anchoring_api = anchoring_testkit.inner.api();
     anchoring_testkit.inner.create_block_with_transactions(
         anchoring_testkit
             .create_signature_txs()
             .into_iter()
             .flatten(),
     );
-
+    let finalized_at = anchoring_testkit.inner.height();
+    let tx = anchoring_testkit.last_anchoring_tx().unwrap();
     assert_eq!(
-        anchoring_api.client().anchoring_proposal().await.unwrap(),
-        AnchoringProposalState::None
+        anchoring_api
+            .client()
+            .transaction_inclusion_proof(0)
+            .await
+            .unwrap(),
+        None
     );
-}
-
-#[tokio::test]
-async fn anchoring_proposal_err_without_initial_funds() {
-    let mut anchoring_testkit = AnchoringTestKit::new(4, 5);
 
-    let api = anchoring_testkit.inner.api();
-    let state = api.client().anchoring_proposal().await.unwrap();
-    assert_eq!(state, AnchoringProposalState::NoInitialFunds);
-}
+    // Report the Bitcoin block with the anchoring transaction.
+    let merkle_block = create_fake_merkle_block(btc::Sha256d::new([0; 32]), &[tx.id()]);
+    anchoring_testkit.inner.create_block_with_transactions(
+        anchoring_testkit.create_report_bitcoin_headers_txs(100, vec![merkle_block.header()]),
+    );
+    anchoring_api
+        .client()
+        .report_inclusion_proof(merkle_block.clone())
+        .await
+        .unwrap();
+    anchoring_testkit.inner.create_block();
 
-#[tokio::test]
-async fn anchoring_proposal_err_insufficient_funds() {
-    let mut anchoring_testkit = AnchoringTestKit::new(4, 5);
+    assert_eq!(
+        anchoring_api
+            .client()
+            .transaction_inclusion_proof(0)
+            .await
+            .unwrap(),
+        Some(TransactionInclusionProof {
+            block_height: 100,
+            merkle_block: merkle_block.clone(),
+            confirmations: 1,
+            is_final: false,
+        })
+    );
 
-    // Add an initial funding transaction to enable anchoring.
+    // Bury the block with the anchoring transaction under the sufficient number of blocks.
+    let headers = create_fake_bitcoin_headers(
+        merkle_block.header().block_hash(),
+        FINALITY_DEPTH as usize - 1,
+    );
+    let report = ReportBitcoinHeaders {
+        first_height: 101,
+        headers,
+    };
+    let report_tx = anchoring_testkit
+        .inner
+        .us()
+        .service_keypair()
+        .report_bitcoin_headers(ANCHORING_INSTANCE_ID, report);
     anchoring_testkit
         .inner
-        .create_block_with_transactions(anchoring_testkit.create_funding_confirmation_txs(20).0);
-
-    let api = anchoring_testkit.inner.api();
-    let state = api.client().anchoring_proposal().await.unwrap();
+        .create_block_with_transaction(report_tx);
+    let proof = anchoring_api
+        .client()
+        .transaction_inclusion_proof(0)
+        .await
+        .unwrap()
+        .unwrap();
+    assert_eq!(proof.confirmations, FINALITY_DEPTH);
+    // Inclusion is attested only by the single anchoring node.
+    assert!(!proof.is_final);
     assert_eq!(
-        state,
-        AnchoringProposalState::InsufficientFunds {
-            total_fee: 1530,
-            balance: 20
-        }
+        PublicApi::stats(anchoring_api.client())
+            .await
+            .unwrap()
+            .latest_final_transaction,
+        None
     );
+
+    // Other anchoring nodes attest the inclusion of the anchoring transaction.
+    let report_txs = anchoring_testkit.inner.network().validators()[1..3]
+        .iter()
+        .map(|node| {
+            node.service_keypair().report_inclusion_proof(
+                ANCHORING_INSTANCE_ID,
+                ReportInclusionProof {
+                    proof: merkle_block.clone(),
+                },
+            )
+        })
+        .collect::<Vec<_>>();
+    anchoring_testkit
+        .inner
+        .create_block_with_transactions(report_txs);
+    let proof = anchoring_api
+        .client()
+        .transaction_inclusion_proof(0)
+        .await
+        .unwrap()
+        .unwrap();
+    assert!(proof.is_final);
+    let stats = PublicApi::stats(anchoring_api.client()).await.unwrap();
+    assert_eq!(stats.latest_final_transaction, Some(0));
+    // Latency is measured from the finalization to the attestation of the inclusion.
+    let attested_at = anchoring_testkit.inner.height();
+    assert_eq!(stats.average_latency, attested_at.0 - finalized_at.0);
 }
 
 #[tokio::test]
-async fn sign_input() {
-    let (mut anchoring_testkit, anchoring_api) = init_testkit();
+async fn funding_inclusion_proof() {
+    // Fake Bitcoin blocks are mined with the regtest difficulty.
+    let mut anchoring_testkit = AnchoringTestKit::with_network(4, 5, bitcoin::Network::Regtest);
+    let (confirmation_txs, funding_tx) = anchoring_testkit.create_funding_confirmation_txs(700_000);
+    anchoring_testkit
+        .inner
+        .create_block_with_transactions(confirmation_txs);
+    let anchoring_api = anchoring_testkit.inner.api();
+    assert_eq!(
+        anchoring_api
+            .client()
+            .funding_inclusion_proof(funding_tx.id())
+            .await
+            .unwrap(),
+        None
+    );
+
+    // Report the Bitcoin block with the funding transaction.
+    let merkle_block = create_fake_merkle_block(btc::Sha256d::new([0; 32]), &[funding_tx.id()]);
+    anchoring_testkit.inner.create_block_with_transactions(
+        anchoring_testkit.create_report_bitcoin_headers_txs(100, vec![merkle_block.header()]),
+    );
+    anchoring_api
+        .client()
+        .report_inclusion_proof(merkle_block.clone())
+        .await
+        .unwrap();
+    anchoring_testkit.inner.create_block();
+
+    assert_eq!(
+        anchoring_api
+            .client()
+            .funding_inclusion_proof(funding_tx.id())
+            .await
+            .unwrap(),
+        Some(FundingInclusionProof {
+            transaction: funding_tx.clone(),
+            block_height: 100,
+            merkle_block: merkle_block.clone(),
+            confirmations: 1,
+            is_spent: false,
+        })
+    );
+    // The funding transaction proof does not affect the anchoring transactions.
+    let snapshot = anchoring_testkit.inner.snapshot();
+    assert_eq!(
+        get_anchoring_schema(&snapshot).inclusion_proof(&funding_tx.id()),
+        None
+    );
+
+    // The proof is kept after the funding transaction has been spent.
+    anchoring_testkit.inner.create_block_with_transactions(
+        anchoring_testkit
+            .create_signature_txs()
+            .into_iter()
+            .flatten(),
+    );
+    let proof = anchoring_api
+        .client()
+        .funding_inclusion_proof(funding_tx.id())
+        .await
+        .unwrap()
+        .unwrap();
+    assert!(proof.is_spent);
+    assert_eq!(proof.merkle_block, merkle_block);
+
+    // Anchoring transactions have no funding inclusion proofs.
+    let tx = anchoring_testkit.last_anchoring_tx().unwrap();
+    assert_eq!(
+        anchoring_api
+            .client()
+            .funding_inclusion_proof(tx.id())
+            .await
+            .unwrap(),
+        None
+    );
+}
+
+#[tokio::test]
+async fn funding_history() {
+    let (mut anchoring_testkit, anchoring_api) = init_testkit();
+    let anchoring_script = anchoring_testkit
+        .actual_anchoring_config()
+        .anchoring_out_script();
+    let snapshot = anchoring_testkit.inner.snapshot();
+    let initial_funding_tx = get_anchoring_schema(&snapshot)
+        .unspent_funding_transaction()
+        .unwrap();
+    let initial_amount = initial_funding_tx
+        .find_out(&anchoring_script)
+        .unwrap()
+        .1
+        .value;
+
+    let history = anchoring_api.client().funding_history().await.unwrap();
+    assert_eq!(
+        history,
+        FundingHistory {
+            transactions: vec![FundingTransactionInfo {
+                txid: initial_funding_tx.id(),
+                amount: initial_amount,
+                is_spent: false,
+                spent_by: None,
+            }],
+            total_received: initial_amount,
+            total_spent: 0,
+        }
+    );
+
+    // Spend the initial funding transaction by the first anchoring transaction.
+    anchoring_testkit.inner.create_block_with_transactions(
+        anchoring_testkit
+            .create_signature_txs()
+            .into_iter()
+            .flatten(),
+    );
+    // Add another funding transaction.
+    let (confirmation_txs, funding_tx) = anchoring_testkit.create_funding_confirmation_txs(70_000);
+    anchoring_testkit
+        .inner
+        .create_block_with_transactions(confirmation_txs);
+
+    let history = anchoring_api.client().funding_history().await.unwrap();
+    assert_eq!(
+        history,
+        FundingHistory {
+            transactions: vec![
+                FundingTransactionInfo {
+                    txid: initial_funding_tx.id(),
+                    amount: initial_amount,
+                    is_spent: true,
+                    spent_by: Some(0),
+                },
+                FundingTransactionInfo {
+                    txid: funding_tx.id(),
+                    amount: 70_000,
+                    is_spent: false,
+                    spent_by: None,
+                },
+            ],
+            total_received: initial_amount + 70_000,
+            total_spent: initial_amount,
+        }
+    );
+}
+
+#[tokio::test]
+async fn find_transaction_regular() {
+    let (mut anchoring_testkit, anchoring_api) = init_testkit();
+    let anchoring_interval = anchoring_testkit
+        .actual_anchoring_config()
+        .anchoring_interval;
+
+    // Create a several anchoring transactions
+    for i in 1..=5 {
+        anchoring_testkit.inner.create_block_with_transactions(
+            anchoring_testkit
+                .create_signature_txs()
+                .into_iter()
+                .flatten(),
+        );
+        anchoring_testkit
+            .inner
+            .create_blocks_until(Height(anchoring_interval * i));
+    }
+
+    let snapshot = anchoring_testkit.inner.snapshot();
+    let anchoring_schema = get_anchoring_schema(&snapshot);
+    let tx_chain = anchoring_schema.transactions_chain;
+    // Find transactions by height.
+    assert_eq!(
+        find_transaction(&anchoring_testkit, &anchoring_api, Some(Height(0)))
+            .await
+            .unwrap(),
+        tx_chain.get(0).unwrap()
+    );
+    assert_eq!(
+        find_transaction(&anchoring_testkit, &anchoring_api, Some(Height(3)))
+            .await
+            .unwrap(),
+        tx_chain.get(1).unwrap()
+    );
+    assert_eq!(
+        find_transaction(&anchoring_testkit, &anchoring_api, Some(Height(4)))
+            .await
+            .unwrap(),
+        tx_chain.get(1).unwrap()
+    );
+    assert_eq!(
+        find_transaction(&anchoring_testkit, &anchoring_api, Some(Height(1000)))
+            .await
+            .unwrap(),
+        tx_chain.get(4).unwrap()
+    );
+    assert_eq!(
+        find_transaction(&anchoring_testkit, &anchoring_api, None)
+            .await
+            .unwrap(),
+        tx_chain.last().unwrap()
+    );
+    // Find transactions by index.
+    for i in 0..=tx_chain.len() {
+        assert_eq!(
+            transaction_with_index(&anchoring_api, i).await,
+            tx_chain.get(i)
+        );
+    }
+}
+
+#[tokio::test]
+async fn transactions_range() {
+    let (mut anchoring_testkit, anchoring_api) = init_testkit();
+    let anchoring_interval = anchoring_testkit
+        .actual_anchoring_config()
+        .anchoring_interval;
+
+    // Create a several anchoring transactions
+    for i in 1..=3 {
+        anchoring_testkit.inner.create_block_with_transactions(
+            anchoring_testkit
+                .create_signature_txs()
+                .into_iter()
+                .flatten(),
+        );
+        anchoring_testkit
+            .inner
+            .create_blocks_until(Height(anchoring_interval * i));
+    }
+
+    let snapshot = anchoring_testkit.inner.snapshot();
+    let tx_chain = get_anchoring_schema(&snapshot)
+        .transactions_chain
+        .iter()
+        .collect::<Vec<_>>();
+    assert_eq!(tx_chain.len(), 3);
+
+    let client = anchoring_api.client();
+    for &(from, count) in &[(0, 3), (0, 10), (1, 1), (2, 5), (3, 1), (10, 1), (1, 0)] {
+        let expected = tx_chain
+            .iter()
+            .skip(from as usize)
+            .take(count as usize)
+            .cloned()
+            .collect::<Vec<_>>();
+
+        let page = PublicApi::transactions(client, from, count).await.unwrap();
+        assert_eq!(page.total, 3);
+        assert_eq!(page.transactions, expected);
+        assert_eq!(
+            PrivateApi::transactions(client, from, count).await.unwrap(),
+            page
+        );
+    }
+
+    // Too large pages are rejected.
+    let err = PublicApi::transactions(client, 0, TransactionsRangeQuery::MAX_COUNT + 1)
+        .await
+        .expect_err("Request must fail");
+    assert_eq!(err.http_code, HttpStatusCode::BAD_REQUEST);
+}
+
+#[tokio::test]
+async fn find_transaction_by_wtxid() {
+    let (mut anchoring_testkit, anchoring_api) = init_testkit();
+    let anchoring_interval = anchoring_testkit
+        .actual_anchoring_config()
+        .anchoring_interval;
+
+    // Create a several anchoring transactions
+    for i in 1..=3 {
+        anchoring_testkit.inner.create_block_with_transactions(
+            anchoring_testkit
+                .create_signature_txs()
+                .into_iter()
+                .flatten(),
+        );
+        anchoring_testkit
+            .inner
+            .create_blocks_until(Height(anchoring_interval * i));
+    }
+
+    let validator_keys = anchoring_testkit
+        .inner
+        .consensus_config()
+        .validator_keys
+        .into_iter()
+        .map(|key| key.consensus_key)
+        .collect::<Vec<_>>();
+    let snapshot = anchoring_testkit.inner.snapshot();
+    let tx_chain = get_anchoring_schema(&snapshot).transactions_chain;
+    for (index, tx) in tx_chain.iter().enumerate() {
+        let index = index as u64;
+        // Anchoring transactions have witnesses, so their identifiers differ.
+        assert_ne!(tx.id(), tx.wtxid());
+
+        let ids = anchoring_api
+            .client()
+            .transaction_ids(index)
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(ids.index, index);
+        assert_eq!(ids.txid, tx.id());
+        assert_eq!(ids.wtxid, tx.wtxid());
+
+        let proof = anchoring_api
+            .client()
+            .find_transaction_by_wtxid(tx.wtxid())
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(
+            proof.validate(&validator_keys).unwrap(),
+            Some((index, tx.clone()))
+        );
+    }
+
+    let len = tx_chain.len();
+    assert_eq!(
+        anchoring_api.client().transaction_ids(len).await.unwrap(),
+        None
+    );
+    let unknown_wtxid = tx_chain.get(0).unwrap().id();
+    assert!(anchoring_api
+        .client()
+        .find_transaction_by_wtxid(unknown_wtxid)
+        .await
+        .unwrap()
+        .is_none());
+}
+
+#[tokio::test]
+async fn nearest_anchoring_transaction() {
+    let (mut anchoring_testkit, anchoring_api) = init_testkit();
+    let anchoring_interval = anchoring_testkit
+        .actual_anchoring_config()
+        .anchoring_interval;
+
+    // There are no anchoring transactions.
+    assert!(anchoring_api
+        .client()
+        .nearest_anchoring_transaction(Height(0))
+        .await
+        .unwrap()
+        .is_none());
+
+    // Create a several anchoring transactions
+    for i in 1..=2 {
+        anchoring_testkit.inner.create_block_with_transactions(
+            anchoring_testkit
+                .create_signature_txs()
+                .into_iter()
+                .flatten(),
+        );
+        anchoring_testkit
+            .inner
+            .create_blocks_until(Height(anchoring_interval * i));
+    }
+
+    let validator_keys = anchoring_testkit
+        .inner
+        .consensus_config()
+        .validator_keys
+        .into_iter()
+        .map(|key| key.consensus_key)
+        .collect::<Vec<_>>();
+    for (height, expected_index) in &[(0, 0), (1, 1), (anchoring_interval, 1)] {
+        let (index, tx) = anchoring_api
+            .client()
+            .nearest_anchoring_transaction(Height(*height))
+            .await
+            .unwrap()
+            .unwrap()
+            .validate(&validator_keys)
+            .unwrap()
+            .unwrap();
+        assert_eq!(index, *expected_index);
+        assert_eq!(
+            tx.anchoring_payload().unwrap().block_height,
+            Height(anchoring_interval * index)
+        );
+    }
+
+    // The block is not anchored yet.
+    assert!(anchoring_api
+        .client()
+        .nearest_anchoring_transaction(Height(anchoring_interval + 1))
+        .await
+        .unwrap()
+        .is_none());
+}
+
+#[tokio::test]
+async fn transaction_index_by_height() {
+    let (mut anchoring_testkit, anchoring_api) = init_testkit();
+    let anchoring_interval = anchoring_testkit
+        .actual_anchoring_config()
+        .anchoring_interval;
+
+    // There are no anchoring transactions.
+    assert!(anchoring_api
+        .client()
+        .transaction_index_by_height(Height(0))
+        .await
+        .unwrap()
+        .is_none());
+
+    for i in 1..=2 {
+        anchoring_testkit.inner.create_block_with_transactions(
+            anchoring_testkit
+                .create_signature_txs()
+                .into_iter()
+                .flatten(),
+        );
+        anchoring_testkit
+            .inner
+            .create_blocks_until(Height(anchoring_interval * i));
+    }
+
+    for (height, expected_index) in &[(0, 0), (1, 1), (anchoring_interval, 1)] {
+        let index = anchoring_api
+            .client()
+            .transaction_index_by_height(Height(*height))
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(
+            index,
+            AnchoredHeightIndex {
+                index: *expected_index,
+                anchored_height: Height(anchoring_interval * expected_index),
+            }
+        );
+    }
+
+    // The block is not anchored yet.
+    assert!(anchoring_api
+        .client()
+        .transaction_index_by_height(Height(anchoring_interval + 1))
+        .await
+        .unwrap()
+        .is_none());
+}
+
+#[tokio::test]
+async fn find_transaction_by_block_hash() {
+    let (mut anchoring_testkit, anchoring_api) = init_testkit();
+    let anchoring_interval = anchoring_testkit
+        .actual_anchoring_config()
+        .anchoring_interval;
+
+    // Create a several anchoring transactions
+    for i in 1..=2 {
+        anchoring_testkit.inner.create_block_with_transactions(
+            anchoring_testkit
+                .create_signature_txs()
+                .into_iter()
+                .flatten(),
+        );
+        anchoring_testkit
+            .inner
+            .create_blocks_until(Height(anchoring_interval * i));
+    }
+
+    let validator_keys = anchoring_testkit
+        .inner
+        .consensus_config()
+        .validator_keys
+        .into_iter()
+        .map(|key| key.consensus_key)
+        .collect::<Vec<_>>();
+    for height in 0..=anchoring_interval {
+        let block_hash = anchoring_testkit.block_hash_on_height(Height(height));
+        let proof = anchoring_api
+            .client()
+            .find_transaction_by_block_hash(block_hash)
+            .await
+            .unwrap()
+            .unwrap();
+        // The genesis block is anchored by the first transaction, and the following
+        // ones are anchored by the second.
+        let expected_index = if height == 0 { 0 } else { 1 };
+        let (index, tx) = proof
+            .transaction_proof
+            .validate(&validator_keys)
+            .unwrap()
+            .unwrap();
+        assert_eq!(proof.index, expected_index);
+        assert_eq!(index, expected_index);
+        assert_eq!(proof.block_height, Height(height));
+
+        // Blocks are linked from the requested one to the anchored one.
+        let blocks = &proof.anchored_blocks;
+        assert_eq!(blocks.first().unwrap().object_hash(), block_hash);
+        for pair in blocks.windows(2) {
+            assert_eq!(pair[1].prev_hash, pair[0].object_hash());
+        }
+        let payload = tx.anchoring_payload().unwrap();
+        assert_eq!(blocks.last().unwrap().height, payload.block_height);
+        assert_eq!(blocks.last().unwrap().object_hash(), payload.block_hash);
+    }
+
+    // The block is not anchored yet.
+    let block_hash = anchoring_testkit.block_hash_on_height(Height(anchoring_interval + 1));
+    assert!(anchoring_api
+        .client()
+        .find_transaction_by_block_hash(block_hash)
+        .await
+        .unwrap()
+        .is_none());
+    // The block is unknown.
+    assert!(anchoring_api
+        .client()
+        .find_transaction_by_block_hash(Hash::zero())
+        .await
+        .unwrap()
+        .is_none());
+}
+
+#[tokio::test]
+async fn exonum_transaction_proof() {
+    let (mut anchoring_testkit, anchoring_api) = init_testkit();
+    let anchoring_interval = anchoring_testkit
+        .actual_anchoring_config()
+        .anchoring_interval;
+
+    // Exonum transactions to prove are the signatures of the first anchoring transaction.
+    let signature_txs = anchoring_testkit
+        .create_signature_txs()
+        .into_iter()
+        .flatten()
+        .collect::<Vec<_>>();
+    let tx_hash = signature_txs[0].object_hash();
+    anchoring_testkit
+        .inner
+        .create_block_with_transactions(signature_txs);
+    let block_height = anchoring_testkit.inner.height();
+    // The block with the transaction is not anchored yet.
+    assert!(anchoring_api
+        .client()
+        .exonum_transaction_proof(tx_hash)
+        .await
+        .unwrap()
+        .is_none());
+
+    anchoring_testkit
+        .inner
+        .create_blocks_until(Height(anchoring_interval));
+    anchoring_testkit.inner.create_block_with_transactions(
+        anchoring_testkit
+            .create_signature_txs()
+            .into_iter()
+            .flatten(),
+    );
+
+    let validator_keys = anchoring_testkit
+        .inner
+        .consensus_config()
+        .validator_keys
+        .into_iter()
+        .map(|key| key.consensus_key)
+        .collect::<Vec<_>>();
+    let proof = anchoring_api
+        .client()
+        .exonum_transaction_proof(tx_hash)
+        .await
+        .unwrap()
+        .unwrap();
+    assert_eq!(proof.transaction.object_hash(), tx_hash);
+    proof.block_proof.verify(&validator_keys).unwrap();
+    let block = &proof.block_proof.block;
+    assert_eq!(block.height, block_height);
+    let checked_proof = proof
+        .transaction_proof
+        .check_against_hash(block.tx_hash)
+        .unwrap();
+    assert_eq!(checked_proof.entries()[0].1, tx_hash);
+    assert_eq!(
+        proof.anchor_proof.anchored_blocks[0].object_hash(),
+        block.object_hash()
+    );
+    assert_eq!(proof.anchor_proof.index, 1);
+    let (index, _) = proof
+        .anchor_proof
+        .transaction_proof
+        .validate(&validator_keys)
+        .unwrap()
+        .unwrap();
+    assert_eq!(index, 1);
+    assert_eq!(proof.inclusion_proof, None);
+
+    // The transaction is unknown.
+    assert!(anchoring_api
+        .client()
+        .exonum_transaction_proof(Hash::zero())
+        .await
+        .unwrap()
+        .is_none());
+}
+
+#[tokio::test]
+async fn block_header_proof() {
+    let (mut anchoring_testkit, anchoring_api) = init_testkit();
+    let anchoring_interval = anchoring_testkit
+        .actual_anchoring_config()
+        .anchoring_interval;
+
+    anchoring_testkit.inner.create_block_with_transactions(
+        anchoring_testkit
+            .create_signature_txs()
+            .into_iter()
+            .flatten(),
+    );
+    anchoring_testkit
+        .inner
+        .create_blocks_until(Height(anchoring_interval));
+    anchoring_testkit.inner.create_block_with_transactions(
+        anchoring_testkit
+            .create_signature_txs()
+            .into_iter()
+            .flatten(),
+    );
+
+    let validator_keys = anchoring_testkit
+        .inner
+        .consensus_config()
+        .validator_keys
+        .into_iter()
+        .map(|key| key.consensus_key)
+        .collect::<Vec<_>>();
+    let height = Height(2);
+    let proof = anchoring_api
+        .client()
+        .block_header_proof(height)
+        .await
+        .unwrap()
+        .unwrap();
+    proof.block_proof.verify(&validator_keys).unwrap();
+    let block = &proof.block_proof.block;
+    assert_eq!(block.height, height);
+    assert_eq!(
+        block.object_hash(),
+        anchoring_testkit.block_hash_on_height(height)
+    );
+    // The block is anchored by the second anchoring transaction.
+    assert_eq!(proof.anchor_proof.block_height, height);
+    assert_eq!(
+        proof.anchor_proof.anchored_blocks[0].object_hash(),
+        block.object_hash()
+    );
+    let (index, tx) = proof
+        .anchor_proof
+        .transaction_proof
+        .validate(&validator_keys)
+        .unwrap()
+        .unwrap();
+    assert_eq!(index, 1);
+    assert_eq!(
+        proof
+            .anchor_proof
+            .anchored_blocks
+            .last()
+            .unwrap()
+            .object_hash(),
+        tx.anchoring_payload().unwrap().block_hash
+    );
+    assert_eq!(proof.inclusion_proof, None);
+
+    // The block is not anchored yet.
+    let height = anchoring_testkit.inner.height();
+    assert!(anchoring_api
+        .client()
+        .block_header_proof(height)
+        .await
+        .unwrap()
+        .is_none());
+    // There is no block at the given height.
+    assert!(anchoring_api
+        .client()
+        .block_header_proof(height.next())
+        .await
+        .unwrap()
+        .is_none());
+}
+
+#[tokio::test]
+async fn anchoring_proof_bundle() {
+    let (mut anchoring_testkit, anchoring_api) = init_testkit();
+    let anchoring_interval = anchoring_testkit
+        .actual_anchoring_config()
+        .anchoring_interval;
+    for height in &[0, anchoring_interval] {
+        anchoring_testkit.inner.create_blocks_until(Height(*height));
+        anchoring_testkit.inner.create_block_with_transactions(
+            anchoring_testkit
+                .create_signature_txs()
+                .into_iter()
+                .flatten(),
+        );
+    }
+
+    let validator_keys = anchoring_testkit
+        .inner
+        .consensus_config()
+        .validator_keys
+        .into_iter()
+        .map(|key| key.consensus_key)
+        .collect::<Vec<_>>();
+    let proof = anchoring_api
+        .client()
+        .block_header_proof(Height(2))
+        .await
+        .unwrap()
+        .unwrap();
+    let bundle =
+        AnchoringProofBundle::new(ANCHORING_INSTANCE_NAME, validator_keys.clone(), proof).unwrap();
+    assert_eq!(bundle.height, Height(2));
+    assert_eq!(
+        Some(bundle.anchoring_transaction.clone()),
+        anchoring_testkit.last_anchoring_tx()
+    );
+
+    // The bundle remains valid after the serialization roundtrip.
+    let content = serde_json::to_string(&bundle).unwrap();
+    let mut bundle: AnchoringProofBundle = serde_json::from_str(&content).unwrap();
+    bundle.verify(&validator_keys).unwrap();
+
+    // The bundle is verified against the trusted validator keys.
+    let other_keys = vec![exonum::crypto::gen_keypair().0];
+    bundle.verify(&other_keys).unwrap_err();
+    // The bundle is bound to the anchoring instance.
+    bundle.instance_name = "other".to_owned();
+    bundle.verify(&validator_keys).unwrap_err();
+    bundle.instance_name = ANCHORING_INSTANCE_NAME.to_owned();
+    // The anchoring transaction must match the proven one.
+    bundle.anchoring_transaction = create_fake_funding_transaction(
+        &anchoring_testkit
+            .actual_anchoring_config()
+            .anchoring_address(),
+        1,
+    );
+    bundle.verify(&validator_keys).unwrap_err();
+}
+
+// Check come edge cases in the find_transaction api method.
+#[tokio::test]
+async fn find_transaction_configuration_change() {
+    let (mut anchoring_testkit, anchoring_api) = init_testkit();
+    let anchoring_interval = anchoring_testkit
+        .actual_anchoring_config()
+        .anchoring_interval;
+
+    assert!(anchoring_testkit.last_anchoring_tx().is_none());
+    // Establish anchoring transactions chain.
+    anchoring_testkit.inner.create_block_with_transactions(
+        anchoring_testkit
+            .create_signature_txs()
+            .into_iter()
+            .flatten(),
+    );
+
+    // Skip the next anchoring height.
+    anchoring_testkit
+        .inner
+        .create_blocks_until(Height(anchoring_interval * 2));
+
+    // Add an anchoring node.
+    let mut new_cfg = anchoring_testkit.actual_anchoring_config();
+    new_cfg.anchoring_keys.push(anchoring_testkit.add_node());
+    new_cfg.sort_anchoring_keys();
+
+    // Commit configuration with without last anchoring node.
+    anchoring_testkit.inner.create_block_with_transaction(
+        anchoring_testkit.create_config_change_tx(
+            ConfigPropose::new(0, anchoring_testkit.inner.height().next())
+                .service_config(ANCHORING_INSTANCE_ID, new_cfg),
+        ),
+    );
+    anchoring_testkit.inner.create_block();
+
+    // Transit to the new address.
+    anchoring_testkit.inner.create_block_with_transactions(
+        anchoring_testkit
+            .create_signature_txs()
+            .into_iter()
+            .flatten(),
+    );
+
+    let snapshot = anchoring_testkit.inner.snapshot();
+    let anchoring_schema = get_anchoring_schema(&snapshot);
+    assert_eq!(
+        find_transaction(&anchoring_testkit, &anchoring_api, Some(Height(0))).await,
+        anchoring_schema.transactions_chain.get(1)
+    );
+    assert_eq!(
+        find_transaction(&anchoring_testkit, &anchoring_api, Some(Height(1))).await,
+        anchoring_schema.transactions_chain.get(1)
+    );
+    assert_eq!(
+        find_transaction(&anchoring_testkit, &anchoring_api, None).await,
+        anchoring_schema.transactions_chain.get(1)
+    );
+
+    // Resume regular anchoring (anchors block on height 5).
+    anchoring_testkit
+        .inner
+        .create_blocks_until(Height(anchoring_interval * 2));
+    anchoring_testkit.inner.create_block_with_transactions(
+        anchoring_testkit
+            .create_signature_txs()
+            .into_iter()
+            .flatten(),
+    );
+
+    let snapshot = anchoring_testkit.inner.snapshot();
+    let anchoring_schema = get_anchoring_schema(&snapshot);
+    assert_eq!(
+        find_transaction(&anchoring_testkit, &anchoring_api, Some(Height(0))).await,
+        anchoring_schema.transactions_chain.get(1)
+    );
+    assert_eq!(
+        find_transaction(&anchoring_testkit, &anchoring_api, Some(Height(1))).await,
+        anchoring_schema.transactions_chain.get(2)
+    );
+    assert_eq!(
+        find_transaction(&anchoring_testkit, &anchoring_api, Some(Height(10))).await,
+        anchoring_schema.transactions_chain.get(2)
+    );
+    assert_eq!(
+        find_transaction(&anchoring_testkit, &anchoring_api, None).await,
+        anchoring_schema.transactions_chain.get(2)
+    );
+
+    // Anchors block on height 10.
+    anchoring_testkit.inner.create_block_with_transactions(
+        anchoring_testkit
+            .create_signature_txs()
+            .into_iter()
+            .flatten(),
+    );
+
+    let snapshot = anchoring_testkit.inner.snapshot();
+    let anchoring_schema = get_anchoring_schema(&snapshot);
+    assert_eq!(
+        find_transaction(&anchoring_testkit, &anchoring_api, Some(Height(0))).await,
+        anchoring_schema.transactions_chain.get(1)
+    );
+    assert_eq!(
+        find_transaction(&anchoring_testkit, &anchoring_api, Some(Height(1))).await,
+        anchoring_schema.transactions_chain.get(2)
+    );
+    assert_eq!(
+        find_transaction(&anchoring_testkit, &anchoring_api, Some(Height(5))).await,
+        anchoring_schema.transactions_chain.get(2)
+    );
+    assert_eq!(
+        find_transaction(&anchoring_testkit, &anchoring_api, Some(Height(6))).await,
+        anchoring_schema.transactions_chain.get(3)
+    );
+    assert_eq!(
+        find_transaction(&anchoring_testkit, &anchoring_api, None).await,
+        anchoring_schema.transactions_chain.get(3)
+    );
+}
+
+#[tokio::test]
+async fn actual_config() {
+    let (anchoring_testkit, anchoring_api) = init_testkit();
+
+    let cfg = anchoring_testkit.actual_anchoring_config();
+
+    let client = anchoring_api.client();
+    assert_eq!(PublicApi::config(client).await.unwrap(), cfg);
+    assert_eq!(PrivateApi::config(client).await.unwrap(), cfg);
+}
+
+#[tokio::test]
+async fn anchoring_proposal_ok() {
+    let (anchoring_testkit, anchoring_api) = init_testkit();
+    let proposal = anchoring_testkit.anchoring_transaction_proposal().unwrap();
+
+    assert_eq!(
+        anchoring_api.client().anchoring_proposal().await.unwrap(),
+        AnchoringProposalState::Available {
+            transaction: proposal.0,
+            inputs: proposal.1,
+        }
+    );
+}
+
+#[tokio::test]
+async fn anchoring_proposal_none() {
+    let (mut anchoring_testkit, anchoring_api) = init_testkit();
+
+    // Establish anchoring transactions chain.
+    anchoring_testkit.inner.create_block_with_transactions(
+        anchoring_testkit
+            .create_signature_txs()
+            .into_iter()
+            .flatten(),
+    );
+
+    assert_eq!(
+        anchoring_api.client().anchoring_proposal().await.unwrap(),
+        AnchoringProposalState::None
+    );
+}
+
+#[tokio::test]
+async fn anchoring_proposal_err_without_initial_funds() {
+    let mut anchoring_testkit = AnchoringTestKit::new(4, 5);
+
+    let api = anchoring_testkit.inner.api();
+    let state = api.client().anchoring_proposal().await.unwrap();
+    assert_eq!(state, AnchoringProposalState::NoInitialFunds);
+}
+
+#[tokio::test]
+async fn anchoring_proposal_err_insufficient_funds() {
+    let mut anchoring_testkit = AnchoringTestKit::new(4, 5);
+
+    // Add an initial funding transaction to enable anchoring.
+    anchoring_testkit
+        .inner
+        .create_block_with_transactions(anchoring_testkit.create_funding_confirmation_txs(20).0);
+
+    let api = anchoring_testkit.inner.api();
+    let state = api.client().anchoring_proposal().await.unwrap();
+    assert_eq!(
+        state,
+        AnchoringProposalState::InsufficientFunds {
+            total_fee: 1860,
+            balance: 20
+        }
+    );
+}
+
+#[tokio::test]
+async fn signing_session() {
+    let (mut anchoring_testkit, anchoring_api) = init_testkit();
+
+    let config = anchoring_testkit.actual_anchoring_config();
+    let (proposal, proposal_inputs) = anchoring_testkit.anchoring_transaction_proposal().unwrap();
+
+    let session = PublicApi::signing_session(anchoring_api.client())
+        .await
+        .unwrap()
+        .unwrap();
+    assert_eq!(session.txid, proposal.id());
+    assert_eq!(session.quorum, config.byzantine_quorum());
+    assert_eq!(session.inputs.len(), proposal_inputs.len());
+    assert!(session.inputs[0].signed.is_empty());
+    assert_eq!(session.inputs[0].missing, config.anchoring_keys);
+    assert_eq!(
+        session.signatures_left,
+        config.byzantine_quorum() * proposal_inputs.len()
+    );
+    // Private API returns the same session.
+    assert_eq!(
+        PrivateApi::signing_session(anchoring_api.client())
+            .await
+            .unwrap(),
+        Some(session)
+    );
+
+    // Sign the proposal by the first anchoring node.
+    let signatures = anchoring_testkit.create_signature_txs();
+    anchoring_testkit
+        .inner
+        .create_block_with_transactions(signatures[0].clone());
+
+    let session = PublicApi::signing_session(anchoring_api.client())
+        .await
+        .unwrap()
+        .unwrap();
+    let input = &session.inputs[0];
+    assert_eq!(input.signed, vec![config.anchoring_keys[0].clone()]);
+    assert_eq!(input.missing, config.anchoring_keys[1..].to_vec());
+    assert_eq!(input.signatures_left, config.byzantine_quorum() - 1);
+    assert_eq!(
+        session.signatures_left,
+        (config.byzantine_quorum() - 1) * proposal_inputs.len()
+    );
+
+    // Finalize the proposal.
+    anchoring_testkit
+        .inner
+        .create_block_with_transactions(signatures.into_iter().skip(1).flatten());
+    assert_eq!(
+        PublicApi::signing_session(anchoring_api.client())
+            .await
+            .unwrap(),
+        None
+    );
+}
+
+#[tokio::test]
+async fn proposal_participation() {
+    let (mut anchoring_testkit, anchoring_api) = init_testkit();
+    let config = anchoring_testkit.actual_anchoring_config();
+
+    let participation = anchoring_api
+        .client()
+        .proposal_participation()
+        .await
+        .unwrap();
+    assert_eq!(participation.len(), config.anchoring_keys.len());
+    assert!(participation
+        .iter()
+        .all(|node| !node.signed && node.latest_signature_height.is_none()));
+
+    // Sign the proposal by the first anchoring node.
+    let signatures = anchoring_testkit.create_signature_txs();
+    anchoring_testkit
+        .inner
+        .create_block_with_transactions(signatures[0].clone());
+    let signature_height = anchoring_testkit.inner.height();
+
+    let participation = anchoring_api
+        .client()
+        .proposal_participation()
+        .await
+        .unwrap();
+    assert_eq!(participation[0].anchoring_keys, config.anchoring_keys[0]);
+    assert!(participation[0].signed);
+    assert_eq!(
+        participation[0].latest_signature_height,
+        Some(signature_height)
+    );
+    assert!(participation[1..].iter().all(|node| !node.signed));
+
+    // Finalize the proposal.
+    anchoring_testkit
+        .inner
+        .create_block_with_transactions(signatures.into_iter().skip(1).flatten());
+    assert!(anchoring_api
+        .client()
+        .proposal_participation()
+        .await
+        .unwrap()
+        .is_empty());
+}
+
+#[tokio::test]
+async fn signing_stats() {
+    let (mut anchoring_testkit, anchoring_api) = init_testkit();
+    let config = anchoring_testkit.actual_anchoring_config();
+
+    // Finalize the proposal without the last anchoring node.
+    let quorum = config.byzantine_quorum();
+    let signatures = anchoring_testkit.create_signature_txs();
+    anchoring_testkit
+        .inner
+        .create_block_with_transactions(signatures.into_iter().take(quorum).flatten());
+
+    let stats = anchoring_api.client().signing_stats().await.unwrap();
+    assert_eq!(stats.len(), config.anchoring_keys.len());
+    for (i, node_stats) in stats.iter().enumerate() {
+        let signed = if i < quorum { 1 } else { 0 };
+        assert_eq!(node_stats.anchoring_keys, config.anchoring_keys[i]);
+        assert_eq!(node_stats.window_proposals, 1);
+        assert_eq!(node_stats.window_signed_proposals, signed);
+        assert_eq!(node_stats.total_proposals, 1);
+        assert_eq!(node_stats.signed_proposals, signed);
+    }
+}
+
+#[tokio::test]
+async fn anchoring_stats() {
+    let (mut anchoring_testkit, anchoring_api) = init_testkit();
+
+    let stats = PublicApi::stats(anchoring_api.client()).await.unwrap();
+    assert_eq!(stats.transactions_count, 0);
+    assert_eq!(stats.current_lag, None);
+
+    // Establish anchoring transactions chain.
+    let (proposal, inputs) = anchoring_testkit.anchoring_transaction_proposal().unwrap();
+    anchoring_testkit.inner.create_block_with_transactions(
+        anchoring_testkit
+            .create_signature_txs()
+            .into_iter()
+            .flatten(),
+    );
+    let anchoring_tx = anchoring_testkit.last_anchoring_tx().unwrap();
+
+    let stats = PublicApi::stats(anchoring_api.client()).await.unwrap();
+    assert_eq!(stats.transactions_count, 1);
+    assert_eq!(stats.total_fee, proposal.fee(&inputs).unwrap());
+    assert_eq!(stats.average_fee, stats.total_fee);
+    assert_eq!(stats.average_size, anchoring_tx.size());
+    // Inclusion of the anchoring transaction in the Bitcoin blockchain is not attested yet.
+    assert_eq!(stats.average_latency, 0);
+    assert_eq!(stats.current_lag, Some(2));
+}
+
+#[tokio::test]
+async fn anchoring_status() {
+    let (mut anchoring_testkit, anchoring_api) = init_testkit();
+    let funding_tx = get_anchoring_schema(&anchoring_testkit.inner.snapshot())
+        .unspent_funding_transaction()
+        .unwrap();
+    let config = anchoring_testkit.actual_anchoring_config();
+
+    let status = anchoring_api.client().status().await.unwrap();
+    assert_eq!(status.config, config);
+    assert_eq!(status.latest_anchored_height, None);
+    assert_eq!(status.latest_txid, None);
+    assert_eq!(status.following_anchoring_height, Height(0));
+    assert!(!status.transition_in_progress);
+    assert_eq!(
+        status.remaining_funds,
+        funding_tx
+            .find_out(&config.anchoring_out_script())
+            .unwrap()
+            .1
+            .value
+    );
+
+    // Establish anchoring transactions chain.
+    anchoring_testkit.inner.create_block_with_transactions(
+        anchoring_testkit
+            .create_signature_txs()
+            .into_iter()
+            .flatten(),
+    );
+    let anchoring_tx = anchoring_testkit.last_anchoring_tx().unwrap();
+
+    let status = anchoring_api.client().status().await.unwrap();
+    assert_eq!(status.latest_anchored_height, Some(Height(0)));
+    assert_eq!(status.latest_txid, Some(anchoring_tx.id()));
+    assert_eq!(
+        status.following_anchoring_height,
+        Height(config.anchoring_interval)
+    );
+    assert_eq!(
+        status.remaining_funds,
+        anchoring_tx.unspent_value().unwrap()
+    );
+
+    // Add an anchoring node.
+    let mut new_cfg = anchoring_testkit.actual_anchoring_config();
+    new_cfg.anchoring_keys.push(anchoring_testkit.add_node());
+    new_cfg.sort_anchoring_keys();
+    anchoring_testkit.inner.create_block_with_transaction(
+        anchoring_testkit.create_config_change_tx(
+            ConfigPropose::new(0, anchoring_testkit.inner.height().next())
+                .service_config(ANCHORING_INSTANCE_ID, new_cfg),
+        ),
+    );
+    let status = anchoring_api.client().status().await.unwrap();
+    assert!(status.transition_in_progress);
+}
+
+#[tokio::test]
+async fn cost_report() {
+    let (mut anchoring_testkit, anchoring_api) = init_testkit();
+    let anchoring_interval = anchoring_testkit
+        .actual_anchoring_config()
+        .anchoring_interval;
+
+    // Establish anchoring transactions chain.
+    let mut fees = Vec::new();
+    for i in 0..3 {
+        anchoring_testkit
+            .inner
+            .create_blocks_until(Height(anchoring_interval * i));
+        let (proposal, inputs) = anchoring_testkit.anchoring_transaction_proposal().unwrap();
+        fees.push(proposal.fee(&inputs).unwrap());
+        anchoring_testkit.inner.create_block_with_transactions(
+            anchoring_testkit
+                .create_signature_txs()
+                .into_iter()
+                .flatten(),
+        );
+    }
+
+    let report = anchoring_api
+        .client()
+        .cost_report(CostReportQuery::default())
+        .await
+        .unwrap();
+    assert_eq!(
+        report
+            .transactions
+            .iter()
+            .map(|tx| tx.fee)
+            .collect::<Vec<_>>(),
+        fees
+    );
+    assert_eq!(
+        report.total_fee,
+        PublicApi::stats(anchoring_api.client())
+            .await
+            .unwrap()
+            .total_fee
+    );
+    for (index, cost) in report.transactions.iter().enumerate() {
+        let tx = transaction_with_index(&anchoring_api, index as u64)
+            .await
+            .unwrap();
+        assert_eq!(cost.index, index as u64);
+        assert_eq!(cost.txid, tx.id());
+        assert_eq!(
+            cost.anchored_height,
+            Height(anchoring_interval * index as u64)
+        );
+        assert_eq!(cost.size, tx.size());
+        assert_eq!(cost.block_time, None);
+    }
+
+    // Filter transactions by the anchored heights.
+    let report = anchoring_api
+        .client()
+        .cost_report(CostReportQuery {
+            from_height: Some(Height(1)),
+            to_height: Some(Height(anchoring_interval)),
+            ..CostReportQuery::default()
+        })
+        .await
+        .unwrap();
+    assert_eq!(report.transactions.len(), 1);
+    assert_eq!(report.transactions[0].index, 1);
+    assert_eq!(report.total_fee, fees[1]);
+
+    // Transactions without the reported inclusion are not matched by the time range.
+    let report = anchoring_api
+        .client()
+        .cost_report(CostReportQuery {
+            from_time: Some(0),
+            ..CostReportQuery::default()
+        })
+        .await
+        .unwrap();
+    assert!(report.transactions.is_empty());
+    assert_eq!(report.total_fee, 0);
+}
+
+#[tokio::test]
+async fn anchoring_gaps() {
+    let (mut anchoring_testkit, anchoring_api) = init_testkit();
+    let anchoring_interval = anchoring_testkit
+        .actual_anchoring_config()
+        .anchoring_interval;
+
+    // Anchor the genesis block in time.
+    anchoring_testkit.inner.create_block_with_transactions(
+        anchoring_testkit
+            .create_signature_txs()
+            .into_iter()
+            .flatten(),
+    );
+    assert!(anchoring_api
+        .client()
+        .anchoring_gaps()
+        .await
+        .unwrap()
+        .is_empty());
+
+    // Sign the following proposal only after two more anchoring heights have passed.
+    anchoring_testkit
+        .inner
+        .create_blocks_until(Height(anchoring_interval * 3));
+    anchoring_testkit.inner.create_block_with_transactions(
+        anchoring_testkit
+            .create_signature_txs()
+            .into_iter()
+            .flatten(),
+    );
+
+    let gaps = anchoring_api.client().anchoring_gaps().await.unwrap();
+    assert_eq!(gaps.len(), 1);
+    let gap = &gaps[0];
+    assert_eq!(gap.anchored_height, Height(anchoring_interval));
+    assert_eq!(gap.finalized_at, Height(anchoring_interval * 3 + 1));
+    assert_eq!(
+        gap.skipped_heights,
+        vec![
+            Height(anchoring_interval * 2),
+            Height(anchoring_interval * 3)
+        ]
+    );
+    assert_eq!(gap.reason, AnchoringDelayReason::Signing);
+    assert_eq!(gap.delay.frozen_blocks, 0);
+    assert_eq!(gap.delay.funding_blocks, 0);
+    assert_eq!(gap.delay.transition_blocks, 0);
+    assert!(gap.delay.signing_blocks > 0);
+}
+
+#[tokio::test]
+async fn sign_input() {
+    let (mut anchoring_testkit, anchoring_api) = init_testkit();
 
     let config = anchoring_testkit.actual_anchoring_config();
     let bitcoin_public_key = config
@@ -429,6 +1912,211 @@ async fn sign_input() {
         .expect("Transaction should be successful");
 }
 
+#[tokio::test]
+async fn sign_inputs() {
+    let mut anchoring_testkit = AnchoringTestKit::new(1, 5);
+    let anchoring_api = anchoring_testkit.inner.api();
+
+    // Establish anchoring transactions chain.
+    let (txs, _) = anchoring_testkit.create_funding_confirmation_txs(20_000);
+    anchoring_testkit.inner.create_block_with_transactions(txs);
+    anchoring_testkit.inner.create_block_with_transactions(
+        anchoring_testkit
+            .create_signature_txs()
+            .into_iter()
+            .flatten(),
+    );
+    // Add one more funding transaction to get a proposal with several inputs.
+    let (txs, _) = anchoring_testkit.create_funding_confirmation_txs(30_000);
+    anchoring_testkit.inner.create_block_with_transactions(txs);
+    anchoring_testkit.inner.create_blocks_until(Height(5));
+
+    let config = anchoring_testkit.actual_anchoring_config();
+    let bitcoin_public_key = config.anchoring_keys[0].bitcoin_key;
+    let bitcoin_private_key = anchoring_testkit.node_private_key(&bitcoin_public_key);
+    let (proposal, proposal_inputs) = anchoring_testkit.anchoring_transaction_proposal().unwrap();
+    assert_eq!(proposal_inputs.len(), 2);
+
+    let mut signer = p2wsh::InputSigner::new(config.redeem_script());
+    let sign_inputs = proposal_inputs
+        .iter()
+        .enumerate()
+        .map(|(index, proposal_input)| {
+            let signature = signer
+                .sign_input(
+                    TxInRef::new(proposal.as_ref(), index),
+                    proposal_input.as_ref(),
+                    &bitcoin_private_key.0.key,
+                )
+                .unwrap();
+            SignInput {
+                input: index as u32,
+                input_signature: signature.into(),
+                txid: proposal.id(),
+            }
+        })
+        .collect::<Vec<_>>();
+
+    // Empty batch is rejected.
+    let err = anchoring_api
+        .client()
+        .sign_inputs(vec![])
+        .await
+        .expect_err("Sign inputs must fail");
+    assert_eq!(err.http_code, HttpStatusCode::BAD_REQUEST);
+    assert_eq!(
+        err.body.title,
+        "Sign inputs request verification has failed"
+    );
+
+    // All inputs are signed by the single transaction.
+    let tx_hash = anchoring_api
+        .client()
+        .sign_inputs(sign_inputs.clone())
+        .await
+        .unwrap();
+    anchoring_testkit
+        .inner
+        .create_block_with_tx_hashes(&[tx_hash])[0]
+        .status()
+        .expect("Transaction should be successful");
+
+    let anchoring_tx = anchoring_testkit.last_anchoring_tx().unwrap();
+    assert_eq!(anchoring_tx.id(), proposal.id());
+
+    // Signatures of the finalized transaction are kept.
+    for sign_input in sign_inputs {
+        let signatures = anchoring_api
+            .client()
+            .input_signatures(proposal.id(), sign_input.input)
+            .await
+            .unwrap();
+        assert_eq!(
+            signatures,
+            vec![NodeInputSignature {
+                anchoring_node: 0,
+                input_signature: sign_input.input_signature,
+            }]
+        );
+    }
+    let signatures = anchoring_api
+        .client()
+        .input_signatures(proposal.id(), 2)
+        .await
+        .unwrap();
+    assert!(signatures.is_empty());
+}
+
+#[tokio::test]
+async fn import_psbt() {
+    let (mut anchoring_testkit, anchoring_api) = init_testkit();
+
+    let config = anchoring_testkit.actual_anchoring_config();
+    let bitcoin_public_key = config
+        .find_bitcoin_key(&anchoring_testkit.inner.us().service_keypair().public_key())
+        .unwrap()
+        .1;
+    let bitcoin_private_key = anchoring_testkit.node_private_key(&bitcoin_public_key);
+
+    let (proposal, _) = anchoring_testkit.anchoring_transaction_proposal().unwrap();
+    let psbt = anchoring_api
+        .client()
+        .anchoring_proposal_psbt()
+        .await
+        .unwrap()
+        .unwrap();
+    assert_eq!(psbt.txid(), proposal.id());
+    assert_eq!(psbt.to_string().parse::<btc::Psbt>().unwrap(), psbt);
+
+    // PSBT without the signatures of this node is rejected.
+    let err = anchoring_api
+        .client()
+        .import_psbt(psbt.clone())
+        .await
+        .expect_err("Import PSBT must fail");
+    assert_eq!(err.http_code, HttpStatusCode::BAD_REQUEST);
+    assert_eq!(err.body.title, "PSBT verification has failed");
+
+    // Sign the PSBT as an external wallet.
+    let mut psbt = psbt;
+    let unsigned_tx = psbt.0.global.unsigned_tx.clone();
+    let mut signer = p2wsh::InputSigner::new(config.redeem_script());
+    for (index, input) in psbt.0.inputs.iter_mut().enumerate() {
+        let value = input.witness_utxo.as_ref().unwrap().value;
+        let signature = signer
+            .sign_input(
+                TxInRef::new(&unsigned_tx, index),
+                value,
+                &bitcoin_private_key.0.key,
+            )
+            .unwrap();
+        input
+            .partial_sigs
+            .insert(bitcoin_public_key.0, signature.into());
+    }
+
+    let tx_hashes = anchoring_api
+        .client()
+        .import_psbt(psbt.clone())
+        .await
+        .unwrap();
+    assert_eq!(tx_hashes.len(), psbt.0.inputs.len());
+    for tx in anchoring_testkit
+        .inner
+        .create_block_with_tx_hashes(&tx_hashes)
+        .iter()
+    {
+        tx.status().expect("Transaction should be successful");
+    }
+}
+
+#[tokio::test]
+async fn key_replacement_proposal() {
+    let (mut anchoring_testkit, anchoring_api) = init_testkit();
+    let old_cfg = anchoring_testkit.actual_anchoring_config();
+    let bitcoin_key = old_cfg.anchoring_keys[0].bitcoin_key;
+    let new_bitcoin_key = anchoring_testkit.gen_bitcoin_key();
+
+    let query = KeyReplacementQuery {
+        bitcoin_key,
+        new_bitcoin_key,
+        configuration_number: 0,
+        actual_from: anchoring_testkit.inner.height().next(),
+    };
+    let proposal = anchoring_api
+        .client()
+        .key_replacement_proposal(query)
+        .await
+        .unwrap();
+    anchoring_testkit
+        .inner
+        .create_block_with_transaction(anchoring_testkit.create_config_change_tx(proposal))[0]
+        .status()
+        .expect("Transaction should be successful");
+    anchoring_testkit.inner.create_block();
+
+    let following_cfg = get_anchoring_schema(&anchoring_testkit.inner.snapshot())
+        .following_config()
+        .unwrap();
+    let mut expected_cfg = old_cfg;
+    expected_cfg.anchoring_keys[0].bitcoin_key = new_bitcoin_key;
+    expected_cfg.sort_anchoring_keys();
+    expected_cfg.excluded_keys = vec![bitcoin_key];
+    assert_eq!(following_cfg, expected_cfg);
+
+    // Unknown key cannot be replaced.
+    let err = anchoring_api
+        .client()
+        .key_replacement_proposal(KeyReplacementQuery {
+            bitcoin_key: new_bitcoin_key,
+            new_bitcoin_key: bitcoin_key,
+            ..query
+        })
+        .await
+        .unwrap_err();
+    assert_eq!(err.http_code, HttpStatusCode::BAD_REQUEST);
+}
+
 #[tokio::test]
 async fn add_funds_ok() {
     let anchoring_interval = 5;
@@ -493,3 +2181,170 @@ async fn add_funds_err_unsuitable() {
         .await
         .expect_err("Add funds must fail");
 }
+
+#[tokio::test]
+async fn vote_fee_rate_err_unsuitable() {
+    let mut anchoring_testkit = AnchoringTestKit::new(4, 5);
+    let anchoring_api = anchoring_testkit.inner.api();
+
+    let err = anchoring_api
+        .client()
+        .vote_fee_rate(1)
+        .await
+        .expect_err("Vote fee rate must fail");
+    assert_eq!(err.http_code, HttpStatusCode::BAD_REQUEST);
+    assert_eq!(err.body.title, "Unsuitable fee rate");
+    assert_eq!(err.body.error_code, Some(ApiErrorKind::BadRequest.code()));
+    assert_eq!(err.api_error_kind(), Some(ApiErrorKind::BadRequest));
+    assert!(!err.is_retriable());
+}
+
+#[tokio::test]
+async fn private_api_for_node() {
+    let mut anchoring_testkit = AnchoringTestKit::new(4, 5);
+    let anchoring_api = anchoring_testkit.inner.api();
+    let fee_rate = anchoring_testkit.actual_anchoring_config().transaction_fee;
+
+    // Each node votes through its own private API.
+    let mut authors = Vec::new();
+    let mut tx_hashes = Vec::new();
+    for (bitcoin_key, _) in anchoring_testkit.anchoring_keypairs() {
+        let node_api = anchoring_testkit
+            .private_api_for_node(anchoring_api.client().clone(), &bitcoin_key)
+            .unwrap();
+        tx_hashes.push(node_api.vote_fee_rate(fee_rate).await.unwrap());
+        authors.push(
+            anchoring_testkit
+                .find_anchoring_node(&bitcoin_key)
+                .unwrap()
+                .service_keypair()
+                .public_key(),
+        );
+    }
+
+    // The transactions are signed by the service keys of the nodes.
+    let block = anchoring_testkit
+        .inner
+        .create_block_with_tx_hashes(&tx_hashes);
+    let mut signers = block
+        .iter()
+        .map(|tx| {
+            tx.status().expect("Transaction should be successful");
+            tx.message().author()
+        })
+        .collect::<Vec<_>>();
+    signers.sort();
+    authors.sort();
+    assert_eq!(signers, authors);
+}
+
+#[tokio::test]
+async fn accelerate_anchoring_err_unsuitable() {
+    let mut anchoring_testkit = AnchoringTestKit::default();
+    let anchoring_api = anchoring_testkit.inner.api();
+
+    // There is no anchoring transaction to accelerate.
+    let config = anchoring_testkit.actual_anchoring_config();
+    let txid = create_fake_funding_transaction(&config.anchoring_address(), 10_000).id();
+    let err = anchoring_api
+        .client()
+        .accelerate_anchoring(txid, 20)
+        .await
+        .expect_err("Accelerate anchoring must fail");
+    assert_eq!(err.http_code, HttpStatusCode::BAD_REQUEST);
+    assert_eq!(err.body.title, "Unsuitable accelerated transaction");
+
+    anchoring_testkit.inner.create_block_with_transactions(
+        anchoring_testkit
+            .create_signature_txs()
+            .into_iter()
+            .flatten(),
+    );
+    let txid = anchoring_testkit.last_anchoring_tx().unwrap().id();
+    let err = anchoring_api
+        .client()
+        .accelerate_anchoring(txid, 1)
+        .await
+        .expect_err("Accelerate anchoring must fail");
+    assert_eq!(err.http_code, HttpStatusCode::BAD_REQUEST);
+    assert_eq!(err.body.title, "Unsuitable fee rate");
+}
+
+#[tokio::test]
+async fn recover_anchoring() {
+    let mut anchoring_testkit = AnchoringTestKit::default();
+    let anchoring_api = anchoring_testkit.inner.api();
+
+    // There is no anchoring transaction to abandon.
+    let config = anchoring_testkit.actual_anchoring_config();
+    let txid = create_fake_funding_transaction(&config.anchoring_address(), 10_000).id();
+    let err = anchoring_api
+        .client()
+        .recover_anchoring(txid)
+        .await
+        .expect_err("Recover anchoring must fail");
+    assert_eq!(err.http_code, HttpStatusCode::BAD_REQUEST);
+    assert_eq!(err.body.title, "Unsuitable recovered transaction");
+
+    anchoring_testkit.inner.create_block_with_transactions(
+        anchoring_testkit
+            .create_signature_txs()
+            .into_iter()
+            .flatten(),
+    );
+    let txid = anchoring_testkit.last_anchoring_tx().unwrap().id();
+    let hash = anchoring_api
+        .client()
+        .recover_anchoring(txid)
+        .await
+        .unwrap();
+    let block = anchoring_testkit.inner.create_block();
+    assert_eq!(block[0].message().object_hash(), hash);
+    block[0].status().unwrap();
+}
+
+#[tokio::test]
+async fn add_funds_err_payload_too_large() {
+    let api_config = ApiConfig {
+        max_body_size: Some(64),
+        ..ApiConfig::default()
+    };
+    let mut anchoring_testkit = AnchoringTestKit::with_api_config(1, 5, api_config);
+    let anchoring_api = anchoring_testkit.inner.api();
+
+    let config = anchoring_testkit.actual_anchoring_config();
+    let funding_transaction = create_fake_funding_transaction(&config.anchoring_address(), 10_000);
+
+    let err = anchoring_api
+        .client()
+        .add_funds(funding_transaction)
+        .await
+        .expect_err("Add funds must fail");
+    assert_eq!(err.http_code, HttpStatusCode::PAYLOAD_TOO_LARGE);
+    assert_eq!(err.api_error_kind(), Some(ApiErrorKind::PayloadTooLarge));
+    // Other endpoints are not affected by the limit.
+    PublicApi::config(anchoring_api.client()).await.unwrap();
+}
+
+#[test]
+fn api_error_kinds() {
+    let kinds = [
+        ApiErrorKind::BadRequest,
+        ApiErrorKind::NotValidator,
+        ApiErrorKind::Timeout,
+        ApiErrorKind::PayloadTooLarge,
+        ApiErrorKind::Broadcast,
+        ApiErrorKind::Internal,
+    ];
+    for &kind in &kinds {
+        assert_eq!(ApiErrorKind::from_code(kind.code()), Some(kind));
+        let err = kind.error();
+        assert_eq!(err.http_code, kind.http_code());
+        assert_eq!(err.api_error_kind(), Some(kind));
+        assert_eq!(err.is_retriable(), kind.is_transient());
+    }
+    assert_eq!(ApiErrorKind::from_code(0), None);
+    // Errors without the code are classified by the HTTP status.
+    assert!(!api::Error::bad_request().is_retriable());
+    assert!(api::Error::new(HttpStatusCode::SERVICE_UNAVAILABLE).is_retriable());
+}
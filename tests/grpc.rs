@@ -0,0 +1,170 @@
+// Copyright 2020 The Exonum Team
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use exonum::helpers::Height;
+use exonum_btc_anchoring::{
+    api::{AnchoringProposalState, ApiErrorKind, PrivateApi},
+    grpc::{PrivateApiClient, PrivateApiServer, SignerClient, SignerServer},
+    sync::{AnchoringChainUpdateTask, ApiClientError, LocalSigner},
+    test_helpers::{create_fake_funding_transaction, AnchoringTestKit},
+};
+use exonum_testkit::TestKitApi;
+use tokio::net::TcpListener;
+use tonic::{transport::Server, Code};
+
+/// Serves the private API of the testkit over gRPC and returns a connected client.
+async fn grpc_client(api: &TestKitApi) -> PrivateApiClient {
+    let mut listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let address = listener.local_addr().unwrap();
+    let server = PrivateApiServer::new(api.client().clone());
+    tokio::spawn(async move {
+        Server::builder()
+            .add_service(server)
+            .serve_with_incoming(listener.incoming())
+            .await
+            .unwrap();
+    });
+
+    PrivateApiClient::connect(format!("http://{}", address))
+        .await
+        .unwrap()
+}
+
+#[tokio::test]
+async fn grpc_read_requests() {
+    let mut anchoring_testkit = AnchoringTestKit::default();
+    let api = anchoring_testkit.inner.api();
+    let client = grpc_client(&api).await;
+
+    let (proposal, inputs) = anchoring_testkit.anchoring_transaction_proposal().unwrap();
+    assert_eq!(
+        client.anchoring_proposal().await.unwrap(),
+        AnchoringProposalState::Available {
+            transaction: proposal,
+            inputs,
+        }
+    );
+    assert_eq!(
+        client.anchoring_proposal_psbt().await.unwrap(),
+        api.client().anchoring_proposal_psbt().await.unwrap()
+    );
+    assert_eq!(
+        client.config().await.unwrap(),
+        anchoring_testkit.actual_anchoring_config()
+    );
+    assert_eq!(client.following_config().await.unwrap(), None);
+    assert_eq!(
+        client.signing_session().await.unwrap(),
+        PrivateApi::signing_session(api.client()).await.unwrap()
+    );
+    assert_eq!(
+        client.stats().await.unwrap(),
+        api.client().stats().await.unwrap()
+    );
+    assert_eq!(client.transactions_count().await.unwrap().value, 0);
+    assert_eq!(client.transaction_with_index(0).await.unwrap(), None);
+    assert_eq!(client.transactions(0, 10).await.unwrap().total, 0);
+}
+
+#[tokio::test]
+async fn grpc_chain_update_task() {
+    let anchoring_interval = 5;
+    let mut anchoring_testkit = AnchoringTestKit::new(1, anchoring_interval);
+    let api = anchoring_testkit.inner.api();
+    let client = grpc_client(&api).await;
+
+    // Add funds to the anchoring wallet through the gRPC client.
+    let config = anchoring_testkit.actual_anchoring_config();
+    let funding_transaction = create_fake_funding_transaction(&config.anchoring_address(), 70_000);
+    let tx_hash = client.add_funds(funding_transaction).await.unwrap();
+    anchoring_testkit
+        .inner
+        .create_block_with_tx_hashes(&[tx_hash])[0]
+        .status()
+        .expect("Transaction should be successful");
+
+    anchoring_testkit
+        .inner
+        .create_blocks_until(Height(anchoring_interval));
+    AnchoringChainUpdateTask::new(anchoring_testkit.anchoring_keypairs(), client.clone())
+        .process()
+        .await
+        .unwrap();
+    anchoring_testkit.inner.create_block();
+
+    assert_eq!(client.transactions_count().await.unwrap().value, 1);
+    assert_eq!(
+        client.transaction_with_index(0).await.unwrap(),
+        anchoring_testkit.last_anchoring_tx()
+    );
+}
+
+#[tokio::test]
+async fn grpc_remote_signer() {
+    let anchoring_interval = 5;
+    let mut anchoring_testkit = AnchoringTestKit::new(1, anchoring_interval);
+    let api = anchoring_testkit.inner.api();
+    let keypairs = anchoring_testkit
+        .anchoring_keypairs()
+        .into_iter()
+        .collect::<Vec<_>>();
+    let bitcoin_keys = keypairs.iter().map(|keypair| keypair.0).collect::<Vec<_>>();
+
+    // Serve the Bitcoin keys of the anchoring node by the remote signer.
+    let mut listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let address = listener.local_addr().unwrap();
+    let server = SignerServer::new(LocalSigner::new(keypairs));
+    tokio::spawn(async move {
+        Server::builder()
+            .add_service(server)
+            .serve_with_incoming(listener.incoming())
+            .await
+            .unwrap();
+    });
+    let signer = SignerClient::connect(format!("http://{}", address), bitcoin_keys)
+        .await
+        .unwrap();
+
+    let funding_txs = anchoring_testkit.create_funding_confirmation_txs(70_000).0;
+    anchoring_testkit
+        .inner
+        .create_block_with_transactions(funding_txs);
+    anchoring_testkit
+        .inner
+        .create_blocks_until(Height(anchoring_interval));
+    AnchoringChainUpdateTask::new(vec![], api.client().clone())
+        .with_signer(signer)
+        .process()
+        .await
+        .unwrap();
+    anchoring_testkit.inner.create_block();
+
+    let client = grpc_client(&api).await;
+    assert_eq!(client.transactions_count().await.unwrap().value, 1);
+}
+
+#[tokio::test]
+async fn grpc_error_kinds() {
+    let mut anchoring_testkit = AnchoringTestKit::new(4, 5);
+    let api = anchoring_testkit.inner.api();
+    let client = grpc_client(&api).await;
+
+    let err = client
+        .vote_fee_rate(1)
+        .await
+        .expect_err("Vote fee rate must fail");
+    assert_eq!(err.code(), Code::InvalidArgument);
+    assert_eq!(err.api_error_kind(), Some(ApiErrorKind::BadRequest));
+    assert!(!err.is_retriable());
+}
@@ -0,0 +1,171 @@
+// Copyright 2020 The Exonum Team
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Performance regression tests on the long anchoring chains.
+//!
+//! The tests are slow, so they are built only with the `long-chain` feature. Run them with
+//!
+//! ```text
+//! cargo test --release --features long-chain --test long_chain
+//! ```
+//!
+//! The length of the anchoring chain can be set via the `ANCHORING_CHAIN_LENGTH`
+//! environment variable.
+
+use exonum::helpers::Height;
+use exonum_btc_anchoring::{
+    api::PublicApi,
+    blockchain::data_layout::TxInputId,
+    btc,
+    test_helpers::{get_anchoring_schema, AnchoringTestKit},
+};
+
+use std::{
+    env,
+    time::{Duration, Instant},
+};
+
+/// Default length of the anchoring chain.
+const DEFAULT_CHAIN_LENGTH: u64 = 10_000;
+/// Number of chunks in which the anchoring chain is built.
+const CHUNKS_COUNT: u64 = 10;
+/// Number of the API requests used to measure the average latency.
+const API_REQUESTS_COUNT: u64 = 100;
+/// Maximum allowed slowdown of the operations on the long chain relative
+/// to the short one.
+const MAX_SLOWDOWN: u32 = 3;
+
+fn chain_length() -> u64 {
+    env::var("ANCHORING_CHAIN_LENGTH")
+        .map(|value| value.parse().expect("Invalid anchoring chain length"))
+        .unwrap_or(DEFAULT_CHAIN_LENGTH)
+}
+
+/// Extends the anchoring chain by the given number of transactions and returns
+/// the time spent.
+fn extend_anchoring_chain(anchoring_testkit: &mut AnchoringTestKit, count: u64) -> Duration {
+    let target_len = anchoring_chain_len(anchoring_testkit) + count;
+    let start = Instant::now();
+    while anchoring_chain_len(anchoring_testkit) < target_len {
+        anchoring_testkit.inner.create_block_with_transactions(
+            anchoring_testkit
+                .create_signature_txs()
+                .into_iter()
+                .flatten(),
+        );
+    }
+    start.elapsed()
+}
+
+fn anchoring_chain_len(anchoring_testkit: &AnchoringTestKit) -> u64 {
+    get_anchoring_schema(&anchoring_testkit.inner.snapshot())
+        .transactions_chain
+        .len()
+}
+
+/// Returns the average latency of the `find-transaction` requests for the heights
+/// evenly distributed over the blockchain.
+async fn find_transaction_latency(anchoring_testkit: &mut AnchoringTestKit) -> Duration {
+    // Idle connections of the previously created API client may be already closed
+    // by the server while the chain is being built.
+    let anchoring_api = anchoring_testkit.inner.api();
+    let height = anchoring_testkit.inner.height().0;
+    let start = Instant::now();
+    for i in 0..API_REQUESTS_COUNT {
+        let height = Height(height * i / API_REQUESTS_COUNT);
+        anchoring_api
+            .client()
+            .find_transaction(Some(height))
+            .await
+            .unwrap();
+    }
+    start.elapsed() / API_REQUESTS_COUNT as u32
+}
+
+fn assert_no_slowdown(operation: &str, short_chain: Duration, long_chain: Duration) {
+    assert!(
+        long_chain <= short_chain * MAX_SLOWDOWN,
+        "{} on the long chain takes {:?}, which is more than {} times slower than {:?} \
+         on the short chain",
+        operation,
+        long_chain,
+        MAX_SLOWDOWN,
+        short_chain
+    );
+}
+
+#[tokio::test]
+async fn long_anchoring_chain() {
+    let chain_length = chain_length();
+    let chunk_len = chain_length / CHUNKS_COUNT;
+    // Anchor every block to build the chain faster.
+    let mut anchoring_testkit = AnchoringTestKit::new(4, 1);
+    anchoring_testkit.inner.create_block_with_transactions(
+        anchoring_testkit
+            .create_funding_confirmation_txs(u64::from(u32::MAX))
+            .0,
+    );
+
+    // Build the first chunk of the chain to measure the baseline.
+    let first_chunk_time = extend_anchoring_chain(&mut anchoring_testkit, chunk_len);
+    let short_chain_latency = find_transaction_latency(&mut anchoring_testkit).await;
+
+    let mut last_chunk_time = first_chunk_time;
+    for _ in 1..CHUNKS_COUNT {
+        last_chunk_time = extend_anchoring_chain(&mut anchoring_testkit, chunk_len);
+    }
+    let long_chain_latency = find_transaction_latency(&mut anchoring_testkit).await;
+
+    assert_no_slowdown("Anchoring chunk", first_chunk_time, last_chunk_time);
+    assert_no_slowdown(
+        "Find transaction request",
+        short_chain_latency,
+        long_chain_latency,
+    );
+
+    // Storage consumed by every anchoring transaction is bounded: the transactions
+    // do not grow and only the quorum of signatures is kept for every input.
+    let snapshot = anchoring_testkit.inner.snapshot();
+    let schema = get_anchoring_schema(&snapshot);
+    let quorum = schema.actual_config().byzantine_quorum();
+    assert_eq!(schema.transactions_chain.len(), chunk_len * CHUNKS_COUNT);
+    // The first transaction also spends the funding transaction. Sizes of the
+    // DER-encoded signatures may differ, so only the stripped transactions are compared.
+    let stripped_size = |tx: &btc::Transaction| {
+        let mut tx = tx.clone();
+        tx.0.input
+            .iter_mut()
+            .for_each(|input| input.witness.clear());
+        tx.size()
+    };
+    let regular_size = stripped_size(&schema.transactions_chain.get(1).unwrap());
+    for tx in schema.transactions_chain.iter().skip(1) {
+        assert_eq!(stripped_size(&tx), regular_size);
+        assert_eq!(tx.0.input.len(), 1);
+        for input in 0..tx.0.input.len() {
+            let signatures = schema.input_signatures(&TxInputId::new(tx.id(), input as u32));
+            assert_eq!(signatures.0.len(), quorum);
+        }
+    }
+
+    // Every anchoring transaction is reachable via the API.
+    let stats = anchoring_testkit
+        .inner
+        .api()
+        .client()
+        .stats()
+        .await
+        .unwrap();
+    assert_eq!(stats.transactions_count, chunk_len * CHUNKS_COUNT);
+}
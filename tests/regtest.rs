@@ -0,0 +1,107 @@
+// Copyright 2020 The Exonum Team
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! End-to-end tests of the sync tasks against a regtest Bitcoin node.
+//!
+//! Run them with `cargo test --features regtest --test regtest`.
+
+use bitcoin::Network;
+use exonum::helpers::Height;
+use exonum_btc_anchoring::{
+    sync::{AnchoringChainUpdateTask, BitcoinRelay, SyncWithBitcoinTask, TransactionStatus},
+    test_helpers::{get_anchoring_schema, regtest::RegtestNode, AnchoringTestKit},
+};
+
+#[tokio::test]
+async fn funding_transaction_roundtrip() {
+    let node = RegtestNode::spawn().unwrap();
+    let mut testkit = AnchoringTestKit::with_network(1, 5, Network::Regtest);
+    let funding_tx = testkit.fund_from_regtest(&node, 1_000_000).unwrap();
+    assert_eq!(
+        get_anchoring_schema(&testkit.inner.snapshot()).unspent_funding_transaction(),
+        Some(funding_tx.clone())
+    );
+
+    let relay = node.relay().unwrap();
+    assert_eq!(
+        relay.transaction_status(funding_tx.id()).await.unwrap(),
+        TransactionStatus::Committed(1)
+    );
+    assert_eq!(
+        relay.raw_transaction(funding_tx.id()).await.unwrap(),
+        Some(funding_tx.clone())
+    );
+    let proof = relay
+        .transaction_proof(funding_tx.id())
+        .await
+        .unwrap()
+        .unwrap();
+    assert_eq!(proof.matched_transactions(), Some(vec![funding_tx.id()]));
+}
+
+#[tokio::test]
+async fn anchoring_chain_in_regtest() {
+    let node = RegtestNode::spawn().unwrap();
+    let mut testkit = AnchoringTestKit::with_network(1, 5, Network::Regtest);
+    let funding_tx = testkit.fund_from_regtest(&node, 1_000_000).unwrap();
+    let anchoring_interval = testkit.actual_anchoring_config().anchoring_interval;
+    let api = testkit.inner.api();
+
+    let relay = node.relay().unwrap();
+    let chain_updater =
+        AnchoringChainUpdateTask::new(testkit.anchoring_keypairs(), api.client().clone());
+    let sync = SyncWithBitcoinTask::new(relay.clone(), api.client().clone());
+
+    let mut latest_synced_tx_index = None;
+    for index in 0..2 {
+        testkit
+            .inner
+            .create_blocks_until(Height(anchoring_interval * (index + 1)));
+        chain_updater.process().await.unwrap();
+        testkit.inner.create_block();
+        let tx = testkit.last_anchoring_tx().unwrap();
+
+        // The anchoring transaction is accepted by the Bitcoin node and then mined.
+        latest_synced_tx_index = sync.process(latest_synced_tx_index).await.unwrap();
+        assert_eq!(latest_synced_tx_index, Some(index));
+        assert_eq!(
+            relay.transaction_status(tx.id()).await.unwrap(),
+            TransactionStatus::Mempool
+        );
+        node.mine(1).unwrap();
+        assert_eq!(
+            relay.transaction_status(tx.id()).await.unwrap(),
+            TransactionStatus::Committed(1)
+        );
+        assert_eq!(relay.raw_transaction(tx.id()).await.unwrap(), Some(tx));
+    }
+
+    // The funding transaction has been spent by the first anchoring transaction.
+    let anchoring_script = testkit.actual_anchoring_config().anchoring_out_script();
+    let vout = funding_tx
+        .0
+        .output
+        .iter()
+        .position(|output| output.script_pubkey == anchoring_script)
+        .unwrap();
+    assert_eq!(
+        relay
+            .is_output_spent(funding_tx.id(), vout as u32)
+            .await
+            .unwrap(),
+        Some(true)
+    );
+    // The whole chain is synchronized.
+    assert_eq!(sync.process(latest_synced_tx_index).await.unwrap(), Some(1));
+}
@@ -12,30 +12,66 @@
 // limitations under the License.
 
 use async_trait::async_trait;
-use exonum::{
-    blockchain::ApiSender,
-    crypto::{Hash, KeyPair},
-    helpers::Height,
-    merkledb::ObjectHash,
-    messages::{AnyTx, Verified},
+use bitcoin::{
+    blockdata::constants::genesis_block,
+    consensus::encode::{deserialize, serialize},
+    network::{
+        address::Address as PeerAddress,
+        constants::ServiceFlags,
+        message::{NetworkMessage, RawNetworkMessage},
+        message_blockdata::Inventory,
+        message_filter::CFilter,
+        message_network::VersionMessage,
+    },
+    util::{bip158::BlockFilter, hash::BitcoinHash},
 };
+#[cfg(feature = "electrum")]
+use bitcoin_hashes::Hash as _;
+use btc_transaction_utils::{multisig::RedeemScript, p2wsh, TxInRef};
+use exonum::helpers::Height;
+#[cfg(feature = "electrum")]
+use exonum_btc_anchoring::sync::{ElectrumRelay, ElectrumRelayConfig};
+#[cfg(feature = "pkcs11")]
+use exonum_btc_anchoring::sync::{Pkcs11Config, Pkcs11KeyConfig, Pkcs11Signer};
 use exonum_btc_anchoring::{
-    api::{AnchoringChainLength, AnchoringProposalState, PrivateApi},
-    blockchain::{AddFunds, BtcAnchoringInterface, SignInput},
+    api::{AnchoringProposalState, PrivateApi},
     btc,
-    config::Config,
     sync::{
-        AnchoringChainUpdateTask, BitcoinRelay, ChainUpdateError, SyncWithBitcoinError,
-        SyncWithBitcoinTask, TransactionStatus,
+        recover_from_bitcoin::{recover_chain, BitcoinDataSource, RecoveryError},
+        AnchoringChainUpdateTask, AnchoringFundingTask, AnchoringTopUpTask, AuditRelay,
+        BitcoinNotification, BitcoinNotifications, BitcoinRelay, BitcoindRelay,
+        BitcoindRelayConfig, BitcoindRelayError, BroadcastAllRelay, BroadcastError, BroadcastQueue,
+        ChainUpdateError, CompactFilterRelay, CompactFilterRelayConfig, ExternalSpend,
+        ExternalSpendWatchdog, FeeRatePolicy, FundingError, FundingWallet, HwiConfig, HwiSigner,
+        PendingTopUp, ProxyConfig, PsbtSigner, RelayAuditLog, SyncMetrics, SyncRunner,
+        SyncRunnerConfig, SyncState, SyncStateStore, SyncWithBitcoinError, SyncWithBitcoinTask,
+        SyncedTransaction, TopUpPolicy, TransactionStatus, ZmqNotificationsConfig,
+    },
+    test_helpers::{
+        create_fake_funding_transaction, get_anchoring_schema, AnchoringTestKit, FakeBitcoinRelay,
+        ANCHORING_INSTANCE_ID,
     },
-    test_helpers::{get_anchoring_schema, AnchoringTestKit, ANCHORING_INSTANCE_ID},
 };
-use exonum_rust_runtime::api;
+use exonum_supervisor::ConfigPropose;
 use exonum_testkit::TestKitApiClient;
+use futures::future::join_all;
+use serde_json::{json, Value};
+#[cfg(feature = "electrum")]
+use tokio::io::{AsyncBufReadExt, BufReader};
+use tokio::{
+    io::{AsyncReadExt, AsyncWriteExt},
+    net::{TcpListener as AsyncTcpListener, TcpStream as AsyncTcpStream},
+};
 
 use std::{
-    collections::VecDeque,
-    sync::{Arc, Mutex},
+    collections::{BTreeMap, VecDeque},
+    convert::{Infallible, TryInto},
+    net::TcpListener,
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc, Mutex,
+    },
+    time::Duration,
 };
 
 #[derive(Debug, Clone)]
@@ -44,21 +80,36 @@ enum FakeRelayRequest {
         request: btc::Transaction,
         response: btc::Sha256d,
     },
+    RejectTransaction {
+        request: btc::Transaction,
+    },
     TransactionStatus {
         request: btc::Sha256d,
         response: TransactionStatus,
     },
+    RawTransaction {
+        request: btc::Sha256d,
+        response: Option<btc::Transaction>,
+    },
+    OutputSpent {
+        request: (btc::Sha256d, u32),
+        response: Option<bool>,
+    },
+    FeeRate {
+        request: u16,
+        response: Option<u64>,
+    },
 }
 
 impl FakeRelayRequest {
-    fn into_send_transaction(self) -> (btc::Transaction, btc::Sha256d) {
-        if let FakeRelayRequest::SendTransaction { request, response } = self {
-            (request, response)
-        } else {
-            panic!(
+    fn into_send_transaction(self) -> (btc::Transaction, Option<btc::Sha256d>) {
+        match self {
+            FakeRelayRequest::SendTransaction { request, response } => (request, Some(response)),
+            FakeRelayRequest::RejectTransaction { request } => (request, None),
+            _ => panic!(
                 "Expected response for the `send_transaction` request. But got {:?}",
                 self
-            )
+            ),
         }
     }
 
@@ -72,14 +123,48 @@ impl FakeRelayRequest {
             )
         }
     }
+
+    fn into_raw_transaction(self) -> (btc::Sha256d, Option<btc::Transaction>) {
+        if let FakeRelayRequest::RawTransaction { request, response } = self {
+            (request, response)
+        } else {
+            panic!(
+                "Expected response for the `raw_transaction` request. But got {:?}",
+                self
+            )
+        }
+    }
+
+    fn into_output_spent(self) -> ((btc::Sha256d, u32), Option<bool>) {
+        if let FakeRelayRequest::OutputSpent { request, response } = self {
+            (request, response)
+        } else {
+            panic!(
+                "Expected response for the `is_output_spent` request. But got {:?}",
+                self
+            )
+        }
+    }
+
+    fn into_fee_rate(self) -> (u16, Option<u64>) {
+        if let FakeRelayRequest::FeeRate { request, response } = self {
+            (request, response)
+        } else {
+            panic!(
+                "Expected response for the `fee_rate` request. But got {:?}",
+                self
+            )
+        }
+    }
 }
 
+/// Bitcoin relay which answers the expected requests in the given order.
 #[derive(Debug, Clone, Default)]
-struct FakeBitcoinRelay {
+struct ScriptedBitcoinRelay {
     requests: Arc<Mutex<VecDeque<FakeRelayRequest>>>,
 }
 
-impl FakeBitcoinRelay {
+impl ScriptedBitcoinRelay {
     fn enqueue_requests(&self, requests: impl IntoIterator<Item = FakeRelayRequest>) {
         self.requests.lock().unwrap().extend(requests)
     }
@@ -93,7 +178,7 @@ impl FakeBitcoinRelay {
     }
 }
 
-impl Drop for FakeBitcoinRelay {
+impl Drop for ScriptedBitcoinRelay {
     fn drop(&mut self) {
         if !std::thread::panicking() {
             assert!(
@@ -106,7 +191,7 @@ impl Drop for FakeBitcoinRelay {
 }
 
 #[async_trait]
-impl BitcoinRelay for FakeBitcoinRelay {
+impl BitcoinRelay for ScriptedBitcoinRelay {
     type Error = anyhow::Error;
 
     async fn send_transaction(
@@ -115,7 +200,7 @@ impl BitcoinRelay for FakeBitcoinRelay {
     ) -> Result<btc::Sha256d, Self::Error> {
         let (expected_request, response) = self.dequeue_request().into_send_transaction();
         assert_eq!(&expected_request, transaction, "Unexpected data in request");
-        Ok(response)
+        response.ok_or_else(|| anyhow::anyhow!("Transaction is rejected"))
     }
 
     async fn transaction_status(&self, id: btc::Sha256d) -> Result<TransactionStatus, Self::Error> {
@@ -123,84 +208,93 @@ impl BitcoinRelay for FakeBitcoinRelay {
         assert_eq!(expected_request, id, "Unexpected data in request");
         Ok(response)
     }
-}
 
-/// TODO Implement creating TestkitApi for an arbitrary TestNode. [ECR-3222]
-#[derive(Debug)]
-struct FakePrivateApi {
-    service_keypair: KeyPair,
-    client: TestKitApiClient,
-    broadcaster: ApiSender,
-}
-
-impl FakePrivateApi {
-    fn for_anchoring_node(
-        testkit: &AnchoringTestKit,
-        client: TestKitApiClient,
-        bitcoin_key: &btc::PublicKey,
-    ) -> Self {
-        let service_keypair = testkit
-            .find_anchoring_node(bitcoin_key)
-            .unwrap()
-            .service_keypair();
+    async fn transaction_proof(
+        &self,
+        _id: btc::Sha256d,
+    ) -> Result<Option<btc::MerkleBlock>, Self::Error> {
+        unimplemented!("Inclusion proofs are not requested by the sync tasks")
+    }
 
-        Self {
-            service_keypair,
-            client,
-            broadcaster: testkit.inner.blockchain().sender().clone(),
-        }
+    async fn raw_transaction(
+        &self,
+        id: btc::Sha256d,
+    ) -> Result<Option<btc::Transaction>, Self::Error> {
+        let (expected_request, response) = self.dequeue_request().into_raw_transaction();
+        assert_eq!(expected_request, id, "Unexpected data in request");
+        Ok(response)
     }
 
-    async fn send<T>(&self, transaction: T)
-    where
-        T: Into<Verified<AnyTx>>,
-    {
-        self.broadcaster
-            .broadcast_transaction(transaction.into())
-            .await
-            .expect("Cannot broadcast transaction");
+    async fn is_output_spent(
+        &self,
+        txid: btc::Sha256d,
+        vout: u32,
+    ) -> Result<Option<bool>, Self::Error> {
+        let (expected_request, response) = self.dequeue_request().into_output_spent();
+        assert_eq!(expected_request, (txid, vout), "Unexpected data in request");
+        Ok(response)
+    }
+
+    async fn fee_rate(&self, target_blocks: u16) -> Result<Option<u64>, Self::Error> {
+        let (expected_request, response) = self.dequeue_request().into_fee_rate();
+        assert_eq!(
+            expected_request, target_blocks,
+            "Unexpected data in request"
+        );
+        Ok(response)
     }
 }
 
+#[derive(Debug)]
+struct FakeFundingWallet {
+    balance: Mutex<u64>,
+}
+
 #[async_trait]
-impl PrivateApi for FakePrivateApi {
-    type Error = api::Error;
+impl FundingWallet for FakeFundingWallet {
+    type Error = Infallible;
 
-    async fn sign_input(&self, sign_input: SignInput) -> Result<Hash, Self::Error> {
-        let signed_tx = self
-            .service_keypair
-            .sign_input(ANCHORING_INSTANCE_ID, sign_input);
-        let hash = signed_tx.object_hash();
-        self.send(signed_tx).await;
-        Ok(hash)
+    async fn balance(&self) -> Result<u64, Self::Error> {
+        Ok(*self.balance.lock().unwrap())
     }
 
-    async fn add_funds(&self, transaction: btc::Transaction) -> Result<Hash, Self::Error> {
-        let signed_tx = self
-            .service_keypair
-            .add_funds(ANCHORING_INSTANCE_ID, AddFunds { transaction });
-        let hash = signed_tx.object_hash();
-        self.send(signed_tx).await;
-        Ok(hash)
+    async fn send_to_address(
+        &self,
+        address: &btc::Address,
+        amount: u64,
+    ) -> Result<btc::Transaction, Self::Error> {
+        *self.balance.lock().unwrap() -= amount;
+        Ok(create_fake_funding_transaction(address, amount))
     }
 
-    async fn anchoring_proposal(&self) -> Result<AnchoringProposalState, Self::Error> {
-        self.client.anchoring_proposal().await
+    async fn create_transaction(
+        &self,
+        address: &btc::Address,
+        amount: u64,
+    ) -> Result<btc::Transaction, Self::Error> {
+        Ok(create_fake_funding_transaction(address, amount))
     }
+}
 
-    async fn config(&self) -> Result<Config, Self::Error> {
-        self.client.config().await
-    }
+#[derive(Debug, Default)]
+struct FakeBitcoinDataSource {
+    transactions: BTreeMap<btc::Sha256d, btc::Transaction>,
+    unspent: Vec<btc::Sha256d>,
+}
 
-    async fn transaction_with_index(
-        &self,
-        index: u64,
-    ) -> Result<Option<btc::Transaction>, Self::Error> {
-        self.client.transaction_with_index(index).await
+#[async_trait]
+impl BitcoinDataSource for FakeBitcoinDataSource {
+    type Error = Infallible;
+
+    async fn transaction(&self, id: btc::Sha256d) -> Result<Option<btc::Transaction>, Self::Error> {
+        Ok(self.transactions.get(&id).cloned())
     }
 
-    async fn transactions_count(&self) -> Result<AnchoringChainLength, Self::Error> {
-        self.client.transactions_count().await
+    async fn unspent_transactions(
+        &self,
+        _address: &btc::Address,
+    ) -> Result<Vec<btc::Sha256d>, Self::Error> {
+        Ok(self.unspent.clone())
     }
 }
 
@@ -224,8 +318,9 @@ async fn chain_updater_normal() {
     // Perform a several anchoring chain updates.
     for i in 0..2 {
         for keypair in testkit.anchoring_keypairs() {
-            let private_api =
-                FakePrivateApi::for_anchoring_node(&testkit, api.client().clone(), &keypair.0);
+            let private_api = testkit
+                .private_api_for_node(api.client().clone(), &keypair.0)
+                .unwrap();
 
             AnchoringChainUpdateTask::new(vec![keypair], private_api)
                 .process()
@@ -243,6 +338,241 @@ async fn chain_updater_normal() {
     }
 }
 
+/// PSBT signer which signs the proposals by the given Bitcoin key instead of the device.
+#[derive(Debug)]
+struct FakePsbtSigner {
+    keypair: (btc::PublicKey, btc::PrivateKey),
+    redeem_script: RedeemScript,
+    calls: Arc<AtomicUsize>,
+}
+
+#[async_trait]
+impl PsbtSigner for FakePsbtSigner {
+    fn public_key(&self) -> btc::PublicKey {
+        self.keypair.0
+    }
+
+    async fn sign_psbt(
+        &self,
+        mut psbt: btc::Psbt,
+        _network: bitcoin::Network,
+    ) -> anyhow::Result<btc::Psbt> {
+        self.calls.fetch_add(1, Ordering::SeqCst);
+
+        let mut signer = p2wsh::InputSigner::new(self.redeem_script.clone());
+        let unsigned_tx = psbt.0.global.unsigned_tx.clone();
+        for (index, input) in psbt.0.inputs.iter_mut().enumerate() {
+            let value = input.witness_utxo.as_ref().unwrap().value;
+            let signature = signer.sign_input(
+                TxInRef::new(&unsigned_tx, index),
+                value,
+                &(self.keypair.1).0.key,
+            )?;
+            input
+                .partial_sigs
+                .insert((self.keypair.0).0, signature.into());
+        }
+        Ok(psbt)
+    }
+}
+
+#[tokio::test]
+async fn chain_updater_dry_run() {
+    let mut testkit = AnchoringTestKit::new(1, 5);
+    testkit
+        .inner
+        .create_block_with_transactions(testkit.create_funding_confirmation_txs(700_000).0);
+    let api = testkit.inner.api();
+    let keypair = testkit.anchoring_keypairs().into_iter().next().unwrap();
+    let anchoring_interval = testkit.actual_anchoring_config().anchoring_interval;
+    testkit
+        .inner
+        .create_blocks_until(Height(anchoring_interval));
+    let private_api = testkit
+        .private_api_for_node(api.client().clone(), &keypair.0)
+        .unwrap();
+
+    let report = AnchoringChainUpdateTask::new(vec![keypair.clone()], private_api.clone())
+        .process_dry_run()
+        .await
+        .unwrap()
+        .unwrap();
+    assert_eq!(report.block_height, Height(0));
+    assert_eq!(report.bitcoin_key, Some(keypair.0));
+    assert_eq!(report.signed_inputs, vec![0]);
+    assert_eq!(report.fee_rate_vote, None);
+    assert!(report.fee > 0);
+    assert_eq!(report.fee_rate, report.fee / report.proposal.size());
+    // Signatures are not submitted.
+    testkit.inner.create_block();
+    assert_eq!(testkit.last_anchoring_tx(), None);
+
+    // Node without the anchoring keys is unable to sign the proposal.
+    let unsigned_report = AnchoringChainUpdateTask::new(vec![], private_api.clone())
+        .process_dry_run()
+        .await
+        .unwrap()
+        .unwrap();
+    assert_eq!(unsigned_report.bitcoin_key, None);
+    assert!(unsigned_report.signed_inputs.is_empty());
+
+    // The same proposal is signed for real.
+    AnchoringChainUpdateTask::new(vec![keypair], private_api)
+        .process()
+        .await
+        .unwrap();
+    testkit.inner.create_block();
+    assert_eq!(
+        testkit.last_anchoring_tx().map(|tx| tx.id()),
+        Some(report.proposal.id())
+    );
+}
+
+#[tokio::test]
+async fn chain_updater_psbt_signer() {
+    let mut testkit = AnchoringTestKit::default();
+    let api = testkit.inner.api();
+
+    let anchoring_interval = testkit.actual_anchoring_config().anchoring_interval;
+    let redeem_script = testkit.actual_anchoring_config().redeem_script();
+    testkit
+        .inner
+        .create_blocks_until(Height(anchoring_interval));
+
+    let calls = Arc::new(AtomicUsize::new(0));
+    let tasks = testkit
+        .anchoring_keypairs()
+        .into_iter()
+        .map(|keypair| {
+            let private_api = testkit
+                .private_api_for_node(api.client().clone(), &keypair.0)
+                .unwrap();
+            AnchoringChainUpdateTask::new(vec![], private_api).with_psbt_signer(FakePsbtSigner {
+                keypair,
+                redeem_script: redeem_script.clone(),
+                calls: calls.clone(),
+            })
+        })
+        .collect::<Vec<_>>();
+    // Each proposal should be signed by the signer only once.
+    for _ in 0..2 {
+        for task in &tasks {
+            task.process().await.unwrap();
+        }
+    }
+    assert_eq!(calls.load(Ordering::SeqCst), tasks.len());
+
+    testkit.inner.create_block();
+    // Make sure the anchoring proposal has been finalized.
+    assert_eq!(
+        anchoring_transaction_payload(&testkit, 0)
+            .unwrap()
+            .block_height,
+        Height(0)
+    );
+}
+
+/// Creates a fake `hwi` executable with the given shell script body.
+#[cfg(unix)]
+fn fake_hwi_executable(name: &str, body: &str) -> std::path::PathBuf {
+    use std::os::unix::fs::PermissionsExt;
+
+    let path = std::env::temp_dir().join(format!("{}-{}", name, std::process::id()));
+    std::fs::write(&path, format!("#!/bin/sh\n{}\n", body)).unwrap();
+    std::fs::set_permissions(&path, std::fs::Permissions::from_mode(0o755)).unwrap();
+    path
+}
+
+#[cfg(unix)]
+#[tokio::test]
+async fn hwi_signer() {
+    let testkit = AnchoringTestKit::default();
+    let config = testkit.actual_anchoring_config();
+    let (proposal, inputs) = testkit.anchoring_transaction_proposal().unwrap();
+    let psbt = btc::Psbt::from_proposal(&proposal, &inputs, &config.redeem_script()).unwrap();
+
+    let bitcoin_key = config.anchoring_keys[0].bitcoin_key;
+    let hwi_config = |hwi_path| HwiConfig {
+        hwi_path,
+        fingerprint: "8038ecd9".parse().unwrap(),
+        derivation_path: "m/48'/1'/0'/2'/0/0".parse().unwrap(),
+        bitcoin_key,
+    };
+
+    // The device echoes back the given PSBT if the arguments are expected.
+    let echo_hwi = fake_hwi_executable(
+        "fake-hwi-echo",
+        r#"[ "$2" = "8038ecd9" ] && [ "$4" = "test" ] && [ "$5" = "signtx" ] \
+            && echo "{\"psbt\": \"$6\"}""#,
+    );
+    let signed_psbt = HwiSigner::new(hwi_config(echo_hwi))
+        .sign_psbt(psbt.clone(), bitcoin::Network::Testnet)
+        .await
+        .unwrap();
+    assert_eq!(signed_psbt.txid(), psbt.txid());
+    // Signer should specify the key derivation path for each input.
+    for input in &signed_psbt.0.inputs {
+        let (fingerprint, path) = &input.hd_keypaths[&bitcoin_key.0];
+        assert_eq!(fingerprint.to_string(), "8038ecd9");
+        assert_eq!(path.to_string(), "m/48'/1'/0'/2'/0/0");
+    }
+
+    let error_hwi = fake_hwi_executable(
+        "fake-hwi-error",
+        r#"echo '{"error": "No device found", "code": -3}'"#,
+    );
+    let e = HwiSigner::new(hwi_config(error_hwi))
+        .sign_psbt(psbt, bitcoin::Network::Testnet)
+        .await
+        .unwrap_err();
+    assert_eq!(e.to_string(), "HWI error with code -3: No device found");
+}
+
+#[tokio::test]
+async fn chain_updaters_share_runtime() {
+    let mut testkits = vec![AnchoringTestKit::default(), AnchoringTestKit::default()];
+    let anchoring_interval = testkits[0].actual_anchoring_config().anchoring_interval;
+    for testkit in &mut testkits {
+        testkit
+            .inner
+            .create_blocks_until(Height(anchoring_interval));
+    }
+
+    // Sync tasks of the both anchoring instances are driven concurrently.
+    let apis = testkits
+        .iter_mut()
+        .map(|testkit| testkit.inner.api())
+        .collect::<Vec<_>>();
+    let tasks = testkits
+        .iter()
+        .zip(&apis)
+        .flat_map(|(testkit, api)| {
+            testkit
+                .anchoring_keypairs()
+                .into_iter()
+                .map(move |keypair| {
+                    let private_api = testkit
+                        .private_api_for_node(api.client().clone(), &keypair.0)
+                        .unwrap();
+                    AnchoringChainUpdateTask::new(vec![keypair], private_api)
+                })
+        })
+        .collect::<Vec<_>>();
+    for result in join_all(tasks.iter().map(AnchoringChainUpdateTask::process)).await {
+        result.unwrap();
+    }
+
+    for testkit in &mut testkits {
+        testkit.inner.create_block();
+        assert_eq!(
+            anchoring_transaction_payload(testkit, 0)
+                .unwrap()
+                .block_height,
+            Height(0)
+        );
+    }
+}
+
 #[tokio::test]
 async fn chain_updater_no_initial_funds() {
     let anchoring_interval = 5;
@@ -258,6 +588,7 @@ async fn chain_updater_no_initial_funds() {
         .await
         .unwrap_err();
 
+    assert!(e.is_retriable());
     match e {
         ChainUpdateError::NoInitialFunds => {}
         e => panic!("Unexpected error occurred: {:?}", e),
@@ -288,12 +619,95 @@ async fn chain_updater_insufficient_funds() {
     match e {
         ChainUpdateError::InsufficientFunds { balance, total_fee } => {
             assert_eq!(balance, 200);
-            assert_eq!(total_fee, 1530);
+            assert_eq!(total_fee, 1860);
         }
         e => panic!("Unexpected error occurred: {:?}", e),
     }
 }
 
+async fn proposal_fee_rate(client: &TestKitApiClient) -> u64 {
+    match client.anchoring_proposal().await.unwrap() {
+        AnchoringProposalState::Available {
+            transaction,
+            inputs,
+        } => transaction.fee(&inputs).unwrap() / transaction.size(),
+        state => panic!("Unexpected anchoring proposal state: {:?}", state),
+    }
+}
+
+#[tokio::test]
+async fn chain_updater_fee_estimation() {
+    let mut testkit = AnchoringTestKit::default();
+    let api = testkit.inner.api();
+    let policy = FeeRatePolicy {
+        target_blocks: 6,
+        tolerance: 10,
+    };
+    let anchoring_interval = testkit.actual_anchoring_config().anchoring_interval;
+    testkit
+        .inner
+        .create_blocks_until(Height(anchoring_interval));
+    let keypairs = testkit.anchoring_keypairs().into_iter().collect::<Vec<_>>();
+    assert_eq!(proposal_fee_rate(api.client()).await, 10);
+
+    // Two anchoring nodes have voted for the higher fee rate.
+    for keypair in &keypairs[..2] {
+        testkit
+            .private_api_for_node(api.client().clone(), &keypair.0)
+            .unwrap()
+            .vote_fee_rate(20)
+            .await
+            .unwrap();
+    }
+    testkit.inner.create_block();
+    assert_eq!(proposal_fee_rate(api.client()).await, 10);
+
+    // The third node votes for the estimated fee rate, which changes the proposal.
+    let relay = ScriptedBitcoinRelay::default();
+    relay.enqueue_requests(vec![FakeRelayRequest::FeeRate {
+        request: 6,
+        response: Some(20),
+    }]);
+    let private_api = testkit
+        .private_api_for_node(api.client().clone(), &keypairs[2].0)
+        .unwrap();
+    AnchoringChainUpdateTask::new(vec![keypairs[2].clone()], private_api)
+        .with_fee_estimation(relay, policy)
+        .process()
+        .await
+        .unwrap();
+    testkit.inner.create_block();
+    assert_eq!(proposal_fee_rate(api.client()).await, 20);
+    assert!(testkit.last_anchoring_tx().is_none());
+
+    // The estimation within the tolerance doesn't require a new vote.
+    for keypair in keypairs {
+        let relay = ScriptedBitcoinRelay::default();
+        relay.enqueue_requests(vec![FakeRelayRequest::FeeRate {
+            request: 6,
+            response: Some(21),
+        }]);
+        let private_api = testkit
+            .private_api_for_node(api.client().clone(), &keypair.0)
+            .unwrap();
+        AnchoringChainUpdateTask::new(vec![keypair], private_api)
+            .with_fee_estimation(relay, policy)
+            .process()
+            .await
+            .unwrap();
+    }
+    testkit.inner.create_block();
+    let tx = testkit.last_anchoring_tx().unwrap();
+    let inputs = get_anchoring_schema(&testkit.inner.snapshot())
+        .spent_transactions(&tx)
+        .unwrap();
+    let mut unsigned_tx = tx.clone();
+    for input in &mut unsigned_tx.0.input {
+        input.witness.clear();
+    }
+    assert_eq!(tx.fee(&inputs).unwrap(), 20 * unsigned_tx.size());
+}
+
 #[tokio::test]
 async fn sync_with_bitcoin_normal() {
     let mut testkit = AnchoringTestKit::default();
@@ -314,7 +728,7 @@ async fn sync_with_bitcoin_normal() {
     let anchoring_schema = get_anchoring_schema(&snapshot);
     let tx_chain = anchoring_schema.transactions_chain;
 
-    let fake_relay = FakeBitcoinRelay::default();
+    let fake_relay = ScriptedBitcoinRelay::default();
     let api = testkit.inner.api();
     let sync = SyncWithBitcoinTask::new(fake_relay.clone(), api.client().clone());
     // Send first anchoring transaction.
@@ -375,49 +789,2175 @@ async fn sync_with_bitcoin_normal() {
 }
 
 #[tokio::test]
-async fn sync_with_bitcoin_empty_chain() {
-    let mut testkit = AnchoringTestKit::default();
-    let api = testkit.inner.api();
-    assert!(
-        SyncWithBitcoinTask::new(FakeBitcoinRelay::default(), api.client().clone())
-            .process(None)
-            .await
-            .unwrap()
-            .is_none()
-    );
-}
-
-#[tokio::test]
-async fn sync_with_bitcoin_err_unconfirmed_funding_tx() {
+async fn sync_with_bitcoin_reorg() {
     let mut testkit = AnchoringTestKit::default();
-    // Establish anchoring transactions chain.
-    testkit
-        .inner
-        .create_block_with_transactions(testkit.create_signature_txs().into_iter().flatten());
-    // Check that synchronization will cause an error if the funding transaction was not confirmed.
+    let anchoring_interval = testkit.actual_anchoring_config().anchoring_interval;
+    for i in 0..2 {
+        testkit
+            .inner
+            .create_blocks_until(Height(anchoring_interval * i));
+        testkit
+            .inner
+            .create_block_with_transactions(testkit.create_signature_txs().into_iter().flatten());
+    }
     let snapshot = testkit.inner.snapshot();
-    let anchoring_schema = get_anchoring_schema(&snapshot);
-    let tx_chain = anchoring_schema.transactions_chain;
+    let tx_chain = get_anchoring_schema(&snapshot).transactions_chain;
+    let (tx0, tx1) = (tx_chain.get(0).unwrap(), tx_chain.get(1).unwrap());
 
-    let fake_relay = FakeBitcoinRelay::default();
+    let fake_relay = ScriptedBitcoinRelay::default();
     let api = testkit.inner.api();
     let sync = SyncWithBitcoinTask::new(fake_relay.clone(), api.client().clone());
+    // First anchoring transaction is confirmed, the second one is sent.
     fake_relay.enqueue_requests(vec![
         FakeRelayRequest::TransactionStatus {
-            request: tx_chain.get(0).unwrap().id(),
+            request: tx0.id(),
+            response: TransactionStatus::Committed(1),
+        },
+        FakeRelayRequest::RawTransaction {
+            request: tx0.id(),
+            response: Some(tx0.clone()),
+        },
+        FakeRelayRequest::SendTransaction {
+            request: tx1.clone(),
+            response: tx1.id(),
+        },
+    ]);
+    assert_eq!(sync.process(Some(0)).await.unwrap(), Some(1));
+    assert_eq!(sync.latest_confirmed_index(), Some(0));
+
+    // First anchoring transaction returns to the mempool, and the second one is evicted.
+    fake_relay.enqueue_requests(vec![
+        FakeRelayRequest::TransactionStatus {
+            request: tx1.id(),
             response: TransactionStatus::Unknown,
         },
         FakeRelayRequest::TransactionStatus {
-            request: tx_chain.get(0).unwrap().prev_tx_id(),
+            request: tx0.id(),
+            response: TransactionStatus::Mempool,
+        },
+        FakeRelayRequest::TransactionStatus {
+            request: tx0.id(),
+            response: TransactionStatus::Mempool,
+        },
+        FakeRelayRequest::TransactionStatus {
+            request: tx1.id(),
             response: TransactionStatus::Unknown,
         },
+        FakeRelayRequest::SendTransaction {
+            request: tx1.clone(),
+            response: tx1.id(),
+        },
     ]);
+    assert_eq!(sync.process(Some(1)).await.unwrap(), Some(1));
+    assert_eq!(sync.latest_confirmed_index(), None);
 
-    let e = sync.process(None).await.unwrap_err();
-    match e {
-        SyncWithBitcoinError::UnconfirmedFundingTransaction(hash) => {
-            assert_eq!(hash, tx_chain.get(0).unwrap().prev_tx_id())
-        }
-        e => panic!("Unexpected error occurred: {:?}", e),
-    }
+    // Both transactions are confirmed.
+    fake_relay.enqueue_requests(vec![
+        FakeRelayRequest::TransactionStatus {
+            request: tx1.id(),
+            response: TransactionStatus::Committed(1),
+        },
+        FakeRelayRequest::RawTransaction {
+            request: tx1.id(),
+            response: Some(tx1.clone()),
+        },
+    ]);
+    assert_eq!(sync.process(Some(1)).await.unwrap(), Some(1));
+    assert_eq!(sync.latest_confirmed_index(), Some(1));
+
+    // Second anchoring transaction is reorganized out of the Bitcoin blockchain
+    // and evicted, while the first one is still confirmed.
+    fake_relay.enqueue_requests(vec![
+        FakeRelayRequest::TransactionStatus {
+            request: tx1.id(),
+            response: TransactionStatus::Unknown,
+        },
+        FakeRelayRequest::TransactionStatus {
+            request: tx0.id(),
+            response: TransactionStatus::Committed(3),
+        },
+        FakeRelayRequest::TransactionStatus {
+            request: tx1.id(),
+            response: TransactionStatus::Unknown,
+        },
+        FakeRelayRequest::SendTransaction {
+            request: tx1.clone(),
+            response: tx1.id(),
+        },
+    ]);
+    assert_eq!(sync.process(Some(1)).await.unwrap(), Some(1));
+    assert_eq!(sync.latest_confirmed_index(), Some(0));
+
+    // Confirmed transaction is not checked again, while the latest one is known.
+    fake_relay.enqueue_requests(vec![FakeRelayRequest::TransactionStatus {
+        request: tx1.id(),
+        response: TransactionStatus::Mempool,
+    }]);
+    assert_eq!(sync.process(Some(1)).await.unwrap(), Some(1));
+}
+
+#[tokio::test]
+async fn sync_with_bitcoin_fake_chain() {
+    let mut testkit = AnchoringTestKit::new(4, 5);
+    let (txs, funding_tx) = testkit.create_funding_confirmation_txs(700_000);
+    testkit.inner.create_block_with_transactions(txs);
+    let anchoring_interval = testkit.actual_anchoring_config().anchoring_interval;
+    for i in 0..2 {
+        testkit
+            .inner
+            .create_blocks_until(Height(anchoring_interval * i));
+        testkit
+            .inner
+            .create_block_with_transactions(testkit.create_signature_txs().into_iter().flatten());
+    }
+    let snapshot = testkit.inner.snapshot();
+    let tx_chain = get_anchoring_schema(&snapshot).transactions_chain;
+    let (tx0, tx1) = (tx_chain.get(0).unwrap(), tx_chain.get(1).unwrap());
+
+    let fake_relay = FakeBitcoinRelay::new();
+    fake_relay.confirm_transaction(funding_tx);
+    let api = testkit.inner.api();
+    let sync = SyncWithBitcoinTask::new(fake_relay.clone(), api.client().clone());
+
+    // Anchoring transactions are sent one by one as the previous one is mined.
+    assert_eq!(sync.process(None).await.unwrap(), Some(0));
+    assert_eq!(fake_relay.mempool(), vec![tx0.id()]);
+    fake_relay.mine_block();
+    assert_eq!(sync.process(Some(0)).await.unwrap(), Some(1));
+    assert_eq!(fake_relay.mempool(), vec![tx1.id()]);
+    fake_relay.mine_block();
+    assert_eq!(sync.process(Some(1)).await.unwrap(), Some(1));
+    assert_eq!(sync.latest_confirmed_index(), Some(1));
+    assert_eq!(
+        fake_relay.transaction_status(tx0.id()).await.unwrap(),
+        TransactionStatus::Committed(2)
+    );
+
+    // Second anchoring transaction is reorganized out and evicted from the mempool,
+    // so it is sent again.
+    fake_relay.reorg(1);
+    assert!(fake_relay.evict_transaction(tx1.id()));
+    assert_eq!(sync.process(Some(1)).await.unwrap(), Some(1));
+    assert_eq!(sync.latest_confirmed_index(), Some(0));
+    assert_eq!(fake_relay.mempool(), vec![tx1.id()]);
+    assert_eq!(
+        fake_relay.sent_transactions(),
+        vec![tx0.id(), tx1.id(), tx1.id()]
+    );
+
+    // Unreachable relay stops the sync.
+    fake_relay.set_unreachable(true);
+    sync.process(Some(1)).await.unwrap_err();
+    fake_relay.set_unreachable(false);
+    fake_relay.mine_block();
+    assert_eq!(sync.process(Some(1)).await.unwrap(), Some(1));
+    assert_eq!(sync.latest_confirmed_index(), Some(1));
+}
+
+#[tokio::test]
+async fn sync_with_bitcoin_min_confirmations() {
+    let mut testkit = AnchoringTestKit::default();
+    let anchoring_interval = testkit.actual_anchoring_config().anchoring_interval;
+    for i in 0..2 {
+        testkit
+            .inner
+            .create_blocks_until(Height(anchoring_interval * i));
+        testkit
+            .inner
+            .create_block_with_transactions(testkit.create_signature_txs().into_iter().flatten());
+    }
+    let snapshot = testkit.inner.snapshot();
+    let tx_chain = get_anchoring_schema(&snapshot).transactions_chain;
+    let (tx0, tx1) = (tx_chain.get(0).unwrap(), tx_chain.get(1).unwrap());
+
+    let fake_relay = ScriptedBitcoinRelay::default();
+    let api = testkit.inner.api();
+    let sync = SyncWithBitcoinTask::new(fake_relay.clone(), api.client().clone())
+        .with_min_confirmations(2);
+    assert_eq!(sync.latest_synced(), None);
+
+    // The first uncommitted transaction is found, but the previous one is not deep enough.
+    fake_relay.enqueue_requests(vec![
+        FakeRelayRequest::TransactionStatus {
+            request: tx1.id(),
+            response: TransactionStatus::Unknown,
+        },
+        FakeRelayRequest::TransactionStatus {
+            request: tx0.id(),
+            response: TransactionStatus::Committed(1),
+        },
+        FakeRelayRequest::TransactionStatus {
+            request: tx0.id(),
+            response: TransactionStatus::Committed(1),
+        },
+    ]);
+    assert_eq!(sync.process(None).await.unwrap(), Some(0));
+    assert_eq!(
+        sync.latest_synced(),
+        Some(SyncedTransaction {
+            index: 0,
+            txid: tx0.id(),
+            confirmations: 1,
+        })
+    );
+
+    // The following transaction is sent after the required confirmations.
+    fake_relay.enqueue_requests(vec![
+        FakeRelayRequest::TransactionStatus {
+            request: tx0.id(),
+            response: TransactionStatus::Committed(2),
+        },
+        FakeRelayRequest::RawTransaction {
+            request: tx0.id(),
+            response: Some(tx0.clone()),
+        },
+        FakeRelayRequest::SendTransaction {
+            request: tx1.clone(),
+            response: tx1.id(),
+        },
+    ]);
+    assert_eq!(sync.process(Some(0)).await.unwrap(), Some(1));
+    assert_eq!(sync.latest_synced().unwrap().confirmations, 2);
+}
+
+#[tokio::test]
+async fn external_spend_watchdog() {
+    let mut testkit = AnchoringTestKit::default();
+    testkit
+        .inner
+        .create_block_with_transactions(testkit.create_signature_txs().into_iter().flatten());
+    let anchoring_interval = testkit.actual_anchoring_config().anchoring_interval;
+    testkit
+        .inner
+        .create_blocks_until(Height(anchoring_interval));
+    let snapshot = testkit.inner.snapshot();
+    let tx = get_anchoring_schema(&snapshot)
+        .transactions_chain
+        .get(0)
+        .unwrap();
+    let (proposal, _) = testkit.anchoring_transaction_proposal().unwrap();
+    assert_eq!(proposal.0.input.len(), 1);
+
+    let fake_relay = ScriptedBitcoinRelay::default();
+    let api = testkit.inner.api();
+    let metrics = SyncMetrics::new();
+    let watchdog = ExternalSpendWatchdog::new(fake_relay.clone(), api.client().clone())
+        .with_metrics(metrics.clone());
+    // Output of the latest anchoring transaction is unspent.
+    fake_relay.enqueue_requests(vec![FakeRelayRequest::OutputSpent {
+        request: (tx.id(), 0),
+        response: Some(false),
+    }]);
+    assert_eq!(watchdog.process().await.unwrap(), vec![]);
+    // Output of the latest anchoring transaction is unknown to the relay.
+    fake_relay.enqueue_requests(vec![FakeRelayRequest::OutputSpent {
+        request: (tx.id(), 0),
+        response: None,
+    }]);
+    assert_eq!(watchdog.process().await.unwrap(), vec![]);
+    // Output is spent by a transaction absent in the anchoring chain.
+    fake_relay.enqueue_requests(vec![FakeRelayRequest::OutputSpent {
+        request: (tx.id(), 0),
+        response: Some(true),
+    }]);
+    let spend = ExternalSpend {
+        txid: tx.id(),
+        vout: 0,
+    };
+    assert_eq!(watchdog.process().await.unwrap(), vec![spend]);
+    assert_eq!(metrics.external_spends(), 1);
+    assert!(metrics.encode().contains("btc_anchoring_external_spends 1"));
+
+    // Output spent by the next anchoring transaction is not watched anymore.
+    testkit
+        .inner
+        .create_block_with_transactions(testkit.create_signature_txs().into_iter().flatten());
+    let next_tx = get_anchoring_schema(&testkit.inner.snapshot())
+        .transactions_chain
+        .last()
+        .unwrap();
+    assert_eq!(next_tx.prev_tx_id(), tx.id());
+    fake_relay.enqueue_requests(vec![FakeRelayRequest::OutputSpent {
+        request: (next_tx.id(), 0),
+        response: Some(false),
+    }]);
+    assert_eq!(watchdog.process().await.unwrap(), vec![]);
+    assert_eq!(metrics.external_spends(), 0);
+}
+
+#[tokio::test]
+async fn sync_with_bitcoin_state_store() {
+    let mut testkit = AnchoringTestKit::default();
+    testkit
+        .inner
+        .create_block_with_transactions(testkit.create_signature_txs().into_iter().flatten());
+    let tx = get_anchoring_schema(&testkit.inner.snapshot())
+        .transactions_chain
+        .get(0)
+        .unwrap();
+
+    let fake_relay = ScriptedBitcoinRelay::default();
+    let api = testkit.inner.api();
+    let state_path =
+        std::env::temp_dir().join(format!("anchoring-sync-state-{}", std::process::id()));
+    let store = SyncStateStore::new(&state_path);
+    let sync = SyncWithBitcoinTask::new(fake_relay.clone(), api.client().clone())
+        .with_state_store(store.clone());
+    assert_eq!(sync.restore_state().await.unwrap(), None);
+
+    // Sync progress is saved after each attempt.
+    fake_relay.enqueue_requests(vec![
+        FakeRelayRequest::TransactionStatus {
+            request: tx.id(),
+            response: TransactionStatus::Unknown,
+        },
+        FakeRelayRequest::TransactionStatus {
+            request: tx.prev_tx_id(),
+            response: TransactionStatus::Committed(10),
+        },
+        FakeRelayRequest::SendTransaction {
+            request: tx.clone(),
+            response: tx.id(),
+        },
+    ]);
+    assert_eq!(sync.process(None).await.unwrap(), Some(0));
+    fake_relay.enqueue_requests(vec![
+        FakeRelayRequest::TransactionStatus {
+            request: tx.id(),
+            response: TransactionStatus::Committed(1),
+        },
+        FakeRelayRequest::RawTransaction {
+            request: tx.id(),
+            response: Some(tx.clone()),
+        },
+    ]);
+    assert_eq!(sync.process(Some(0)).await.unwrap(), Some(0));
+    assert_eq!(
+        store.load().unwrap(),
+        SyncState {
+            latest_synced_tx_index: Some(0),
+            latest_synced_txid: Some(tx.id()),
+            latest_confirmed_tx_index: Some(0),
+        }
+    );
+
+    // Restarted task resumes from the saved state without asking the relay.
+    let sync = SyncWithBitcoinTask::new(fake_relay.clone(), api.client().clone())
+        .with_state_store(store.clone());
+    assert_eq!(sync.restore_state().await.unwrap(), Some(0));
+    assert_eq!(sync.latest_confirmed_index(), Some(0));
+
+    // State of another anchoring chain is ignored.
+    store
+        .save(&SyncState {
+            latest_synced_tx_index: Some(0),
+            latest_synced_txid: Some(tx.prev_tx_id()),
+            latest_confirmed_tx_index: Some(0),
+        })
+        .unwrap();
+    let sync = SyncWithBitcoinTask::new(fake_relay, api.client().clone()).with_state_store(store);
+    assert_eq!(sync.restore_state().await.unwrap(), None);
+    assert_eq!(sync.latest_confirmed_index(), None);
+
+    std::fs::remove_file(state_path).unwrap();
+}
+
+#[tokio::test]
+async fn sync_with_bitcoin_broadcast_queue() {
+    let mut testkit = AnchoringTestKit::default();
+    testkit
+        .inner
+        .create_block_with_transactions(testkit.create_signature_txs().into_iter().flatten());
+    let tx = get_anchoring_schema(&testkit.inner.snapshot())
+        .transactions_chain
+        .get(0)
+        .unwrap();
+    let first_uncommitted_requests = || {
+        vec![
+            FakeRelayRequest::TransactionStatus {
+                request: tx.id(),
+                response: TransactionStatus::Unknown,
+            },
+            FakeRelayRequest::TransactionStatus {
+                request: tx.prev_tx_id(),
+                response: TransactionStatus::Committed(10),
+            },
+        ]
+    };
+
+    let fake_relay = ScriptedBitcoinRelay::default();
+    let api = testkit.inner.api();
+    let sync = SyncWithBitcoinTask::new(fake_relay.clone(), api.client().clone());
+    let queue_path =
+        std::env::temp_dir().join(format!("anchoring-broadcast-queue-{}", std::process::id()));
+    let mut queue = BroadcastQueue::load(&queue_path).unwrap();
+
+    // Rejected transaction is queued.
+    fake_relay.enqueue_requests(first_uncommitted_requests());
+    fake_relay.enqueue_requests(vec![FakeRelayRequest::RejectTransaction {
+        request: tx.clone(),
+    }]);
+    match sync.process_with_queue(None, &mut queue).await {
+        Err(SyncWithBitcoinError::Relay(_)) => {}
+        other => panic!("Unexpected result: {:?}", other),
+    }
+    assert_eq!(queue.entries().len(), 1);
+    assert_eq!(queue.entries()[0].attempts, 1);
+    assert_eq!(queue.entries()[0].transaction, tx);
+
+    // Queued transaction is not sent again until the backoff elapses.
+    fake_relay.enqueue_requests(first_uncommitted_requests());
+    assert_eq!(
+        sync.process_with_queue(None, &mut queue).await.unwrap(),
+        None
+    );
+
+    // The queue survives the restart of the sync utility.
+    let queue = BroadcastQueue::load(&queue_path).unwrap();
+    assert_eq!(queue.entries().len(), 1);
+
+    // Due transaction is retried and removed from the queue.
+    let mut queue = queue.with_backoff(Duration::from_secs(0), Duration::from_secs(0));
+    queue
+        .record_failure(0, tx.clone(), "Relay is down", std::time::SystemTime::now())
+        .unwrap();
+    fake_relay.enqueue_requests(vec![
+        FakeRelayRequest::TransactionStatus {
+            request: tx.id(),
+            response: TransactionStatus::Unknown,
+        },
+        FakeRelayRequest::SendTransaction {
+            request: tx.clone(),
+            response: tx.id(),
+        },
+        FakeRelayRequest::TransactionStatus {
+            request: tx.id(),
+            response: TransactionStatus::Mempool,
+        },
+    ]);
+    assert_eq!(
+        sync.process_with_queue(None, &mut queue).await.unwrap(),
+        None
+    );
+    assert!(queue.entries().is_empty());
+}
+
+#[tokio::test]
+async fn sync_metrics() {
+    let mut testkit = AnchoringTestKit::default();
+    let api = testkit.inner.api();
+    let metrics = SyncMetrics::new();
+
+    // Signing of the proposal is recorded.
+    let keypair = testkit.anchoring_keypairs().into_iter().next().unwrap();
+    let private_api = testkit
+        .private_api_for_node(api.client().clone(), &keypair.0)
+        .unwrap();
+    AnchoringChainUpdateTask::new(vec![keypair], private_api)
+        .with_metrics(metrics.clone())
+        .process()
+        .await
+        .unwrap();
+    assert_eq!(metrics.signed_proposals(), 1);
+
+    // Rejected transaction is recorded as the relay error.
+    testkit
+        .inner
+        .create_block_with_transactions(testkit.create_signature_txs().into_iter().flatten());
+    let tx = get_anchoring_schema(&testkit.inner.snapshot())
+        .transactions_chain
+        .get(0)
+        .unwrap();
+    let fake_relay = ScriptedBitcoinRelay::default();
+    fake_relay.enqueue_requests(vec![
+        FakeRelayRequest::TransactionStatus {
+            request: tx.id(),
+            response: TransactionStatus::Unknown,
+        },
+        FakeRelayRequest::TransactionStatus {
+            request: tx.prev_tx_id(),
+            response: TransactionStatus::Committed(10),
+        },
+        FakeRelayRequest::RejectTransaction {
+            request: tx.clone(),
+        },
+    ]);
+    let sync =
+        SyncWithBitcoinTask::new(fake_relay, api.client().clone()).with_metrics(metrics.clone());
+    sync.process(None).await.unwrap_err();
+    assert_eq!(metrics.relay_errors(), 1);
+
+    // Statistics of the service are exposed along with the metrics of the sync tasks.
+    let stats = PrivateApi::stats(api.client()).await.unwrap();
+    assert_eq!(stats.balance, tx.unspent_value().unwrap());
+    metrics.update_stats(stats);
+    let encoded = metrics.encode();
+    let balance = format!(
+        "btc_anchoring_balance_satoshis {}",
+        tx.unspent_value().unwrap()
+    );
+    for line in &[
+        "btc_anchoring_chain_length 1",
+        &balance,
+        "btc_anchoring_relay_errors_total 1",
+        "btc_anchoring_sign_latency_seconds_count 1",
+    ] {
+        assert!(encoded.lines().any(|l| l == *line), "{}", encoded);
+    }
+}
+
+#[tokio::test]
+async fn sync_with_bitcoin_err_transaction_mismatch() {
+    let mut testkit = AnchoringTestKit::default();
+    testkit
+        .inner
+        .create_block_with_transactions(testkit.create_signature_txs().into_iter().flatten());
+    let tx = get_anchoring_schema(&testkit.inner.snapshot())
+        .transactions_chain
+        .get(0)
+        .unwrap();
+    // Transaction with the same id, but with the malleated witness.
+    let mut malleated_tx = tx.clone();
+    malleated_tx.0.input[0].witness[0].push(0);
+    assert_eq!(malleated_tx.id(), tx.id());
+
+    let fake_relay = ScriptedBitcoinRelay::default();
+    let api = testkit.inner.api();
+    let sync = SyncWithBitcoinTask::new(fake_relay.clone(), api.client().clone());
+    // Confirmed transaction is the same as the anchoring one.
+    fake_relay.enqueue_requests(vec![
+        FakeRelayRequest::TransactionStatus {
+            request: tx.id(),
+            response: TransactionStatus::Committed(1),
+        },
+        FakeRelayRequest::RawTransaction {
+            request: tx.id(),
+            response: Some(tx.clone()),
+        },
+    ]);
+    assert_eq!(sync.process(Some(0)).await.unwrap(), Some(0));
+    // Confirmed transaction differs from the anchoring one.
+    for response in &[Some(malleated_tx), None] {
+        fake_relay.enqueue_requests(vec![
+            FakeRelayRequest::TransactionStatus {
+                request: tx.id(),
+                response: TransactionStatus::Committed(1),
+            },
+            FakeRelayRequest::RawTransaction {
+                request: tx.id(),
+                response: response.clone(),
+            },
+        ]);
+        let e = sync.process(None).await.unwrap_err();
+        assert!(!e.is_retriable());
+        match e {
+            SyncWithBitcoinError::TransactionMismatch { index, relayed } => {
+                assert_eq!(index, 0);
+                assert_eq!(&relayed, response);
+            }
+            e => panic!("Unexpected error occurred: {:?}", e),
+        }
+    }
+}
+
+#[tokio::test]
+async fn sync_with_bitcoin_accelerate_stuck_transaction() {
+    let mut testkit = AnchoringTestKit::default();
+    let api = testkit.inner.api();
+    let policy = FeeRatePolicy {
+        target_blocks: 6,
+        tolerance: 10,
+    };
+    testkit
+        .inner
+        .create_block_with_transactions(testkit.create_signature_txs().into_iter().flatten());
+    let tx = testkit.last_anchoring_tx().unwrap();
+    let funding_tx = get_anchoring_schema(&testkit.inner.snapshot())
+        .spent_transactions(&tx)
+        .unwrap()
+        .remove(0);
+    let stuck_requests = |fee_rate| {
+        vec![
+            FakeRelayRequest::TransactionStatus {
+                request: tx.id(),
+                response: TransactionStatus::Mempool,
+            },
+            FakeRelayRequest::FeeRate {
+                request: 6,
+                response: Some(fee_rate),
+            },
+            FakeRelayRequest::RawTransaction {
+                request: funding_tx.id(),
+                response: Some(funding_tx.clone()),
+            },
+        ]
+    };
+
+    // Committed transaction is not accelerated.
+    let fake_relay = ScriptedBitcoinRelay::default();
+    let sync = SyncWithBitcoinTask::new(fake_relay.clone(), api.client().clone());
+    fake_relay.enqueue_requests(vec![FakeRelayRequest::TransactionStatus {
+        request: tx.id(),
+        response: TransactionStatus::Committed(1),
+    }]);
+    assert_eq!(
+        sync.accelerate_stuck_transaction(policy).await.unwrap(),
+        None
+    );
+    // The estimation within the tolerance doesn't require the acceleration.
+    let tx_fee_rate = tx.fee(std::slice::from_ref(&funding_tx)).unwrap() / tx.size();
+    let tolerant_policy = FeeRatePolicy {
+        tolerance: 100,
+        ..policy
+    };
+    fake_relay.enqueue_requests(stuck_requests(2 * tx_fee_rate));
+    assert_eq!(
+        sync.accelerate_stuck_transaction(tolerant_policy)
+            .await
+            .unwrap(),
+        None
+    );
+
+    for (bitcoin_key, _) in testkit.anchoring_keypairs() {
+        let fake_relay = ScriptedBitcoinRelay::default();
+        let private_api = testkit
+            .private_api_for_node(api.client().clone(), &bitcoin_key)
+            .unwrap();
+        let sync = SyncWithBitcoinTask::new(fake_relay.clone(), private_api);
+        fake_relay.enqueue_requests(stuck_requests(40));
+        assert_eq!(
+            sync.accelerate_stuck_transaction(policy).await.unwrap(),
+            Some(40)
+        );
+        // The node doesn't vote again until the estimation changes.
+        fake_relay.enqueue_requests(stuck_requests(42));
+        assert_eq!(
+            sync.accelerate_stuck_transaction(policy).await.unwrap(),
+            None
+        );
+    }
+    testkit.inner.create_block();
+
+    // The child transaction anchors the same height again.
+    let (proposal, inputs) = testkit.anchoring_transaction_proposal().unwrap();
+    assert_eq!(inputs[0], tx);
+    assert_eq!(
+        proposal.anchoring_payload().unwrap().block_height,
+        Height(0)
+    );
+}
+
+#[tokio::test]
+async fn sync_with_bitcoin_empty_chain() {
+    let mut testkit = AnchoringTestKit::default();
+    let api = testkit.inner.api();
+    assert!(
+        SyncWithBitcoinTask::new(ScriptedBitcoinRelay::default(), api.client().clone())
+            .process(None)
+            .await
+            .unwrap()
+            .is_none()
+    );
+}
+
+#[tokio::test]
+async fn sync_with_bitcoin_err_unconfirmed_funding_tx() {
+    let mut testkit = AnchoringTestKit::default();
+    // Establish anchoring transactions chain.
+    testkit
+        .inner
+        .create_block_with_transactions(testkit.create_signature_txs().into_iter().flatten());
+    // Check that synchronization will cause an error if the funding transaction was not confirmed.
+    let snapshot = testkit.inner.snapshot();
+    let anchoring_schema = get_anchoring_schema(&snapshot);
+    let tx_chain = anchoring_schema.transactions_chain;
+
+    let fake_relay = ScriptedBitcoinRelay::default();
+    let api = testkit.inner.api();
+    let sync = SyncWithBitcoinTask::new(fake_relay.clone(), api.client().clone());
+    fake_relay.enqueue_requests(vec![
+        FakeRelayRequest::TransactionStatus {
+            request: tx_chain.get(0).unwrap().id(),
+            response: TransactionStatus::Unknown,
+        },
+        FakeRelayRequest::TransactionStatus {
+            request: tx_chain.get(0).unwrap().prev_tx_id(),
+            response: TransactionStatus::Unknown,
+        },
+    ]);
+
+    let e = sync.process(None).await.unwrap_err();
+    match e {
+        SyncWithBitcoinError::UnconfirmedFundingTransaction(hash) => {
+            assert_eq!(hash, tx_chain.get(0).unwrap().prev_tx_id())
+        }
+        e => panic!("Unexpected error occurred: {:?}", e),
+    }
+}
+
+#[tokio::test]
+async fn recover_chain_from_bitcoin() {
+    let mut testkit = AnchoringTestKit::default();
+    let anchoring_interval = testkit.actual_anchoring_config().anchoring_interval;
+    let funding_tx = get_anchoring_schema(&testkit.inner.snapshot())
+        .unspent_funding_transaction()
+        .unwrap();
+    // Create a several anchoring transactions
+    for i in 0..3 {
+        testkit
+            .inner
+            .create_blocks_until(Height(anchoring_interval * i));
+
+        testkit
+            .inner
+            .create_block_with_transactions(testkit.create_signature_txs().into_iter().flatten());
+    }
+
+    let snapshot = testkit.inner.snapshot();
+    let anchoring_schema = get_anchoring_schema(&snapshot);
+    let tx_chain = anchoring_schema
+        .transactions_chain
+        .iter()
+        .collect::<Vec<_>>();
+    let configs = vec![anchoring_schema.actual_config()];
+
+    // There are no anchoring transactions in the Bitcoin blockchain yet.
+    let mut source = FakeBitcoinDataSource::default();
+    match recover_chain(&source, &configs).await.unwrap_err() {
+        RecoveryError::NoAnchoringTransactions => {}
+        e => panic!("Unexpected error occurred: {:?}", e),
+    }
+
+    // Publish the anchoring transactions with the funding one.
+    source.transactions = std::iter::once(funding_tx)
+        .chain(tx_chain.iter().cloned())
+        .map(|tx| (tx.id(), tx))
+        .collect();
+    source.unspent = vec![tx_chain.last().unwrap().id()];
+
+    let recovered = recover_chain(&source, &configs).await.unwrap();
+    assert_eq!(recovered.transactions, tx_chain);
+    assert_eq!(recovered.first_mismatch(&tx_chain), None);
+    assert_eq!(recovered.first_mismatch(&tx_chain[..2]), Some(2));
+    assert_eq!(recovered.genesis_config(&configs), Some(&configs[0]));
+    assert_eq!(
+        recovered
+            .payloads()
+            .map(|payload| payload.block_height)
+            .collect::<Vec<_>>(),
+        vec![
+            Height(0),
+            Height(anchoring_interval),
+            Height(anchoring_interval * 2)
+        ]
+    );
+}
+
+#[tokio::test]
+async fn funding_from_managed_wallet() {
+    let anchoring_interval = 5;
+    let mut testkit = AnchoringTestKit::new(1, anchoring_interval);
+    let api = testkit.inner.api();
+    let bitcoin_key = testkit.anchoring_keypairs().into_iter().next().unwrap().0;
+    let private_api = testkit
+        .private_api_for_node(api.client().clone(), &bitcoin_key)
+        .unwrap();
+    let funding = AnchoringFundingTask::new(
+        FakeFundingWallet {
+            balance: Mutex::new(100_000),
+        },
+        private_api,
+    );
+
+    // Wallet balance is insufficient.
+    match funding.fund(150_000).await.unwrap_err() {
+        FundingError::InsufficientBalance { amount, balance } => {
+            assert_eq!(amount, 150_000);
+            assert_eq!(balance, 100_000);
+        }
+        e => panic!("Unexpected error occurred: {:?}", e),
+    }
+
+    // Send funds to the anchoring address and register the funding transaction.
+    let transaction = funding.fund(70_000).await.unwrap();
+    assert_eq!(funding.balance().await.unwrap(), 30_000);
+    testkit.inner.create_block();
+
+    let snapshot = testkit.inner.snapshot();
+    let anchoring_schema = get_anchoring_schema(&snapshot);
+    assert_eq!(
+        anchoring_schema.unspent_funding_transaction(),
+        Some(transaction)
+    );
+}
+
+#[tokio::test]
+async fn funding_confirmations() {
+    let mut testkit = AnchoringTestKit::new(1, 5);
+    let mut new_cfg = testkit.actual_anchoring_config();
+    new_cfg.funding_confirmations = 3;
+    testkit.inner.create_block_with_transaction(
+        testkit.create_config_change_tx(
+            ConfigPropose::new(0, testkit.inner.height().next())
+                .service_config(ANCHORING_INSTANCE_ID, new_cfg),
+        ),
+    );
+    testkit.inner.create_block();
+
+    let api = testkit.inner.api();
+    let bitcoin_key = testkit.anchoring_keypairs().into_iter().next().unwrap().0;
+    let funding = AnchoringFundingTask::new(
+        FakeFundingWallet {
+            balance: Mutex::new(100_000),
+        },
+        testkit
+            .private_api_for_node(api.client().clone(), &bitcoin_key)
+            .unwrap(),
+    );
+    let fake_relay = ScriptedBitcoinRelay::default();
+    let sync = SyncWithBitcoinTask::new(
+        fake_relay.clone(),
+        testkit
+            .private_api_for_node(api.client().clone(), &bitcoin_key)
+            .unwrap(),
+    );
+
+    // Sent funding transaction is not registered until it is confirmed.
+    let transaction = funding.fund(70_000).await.unwrap();
+    testkit.inner.create_block();
+    assert_ne!(
+        get_anchoring_schema(&testkit.inner.snapshot()).unspent_funding_transaction(),
+        Some(transaction.clone())
+    );
+
+    fake_relay.enqueue_requests(vec![FakeRelayRequest::TransactionStatus {
+        request: transaction.id(),
+        response: TransactionStatus::Committed(2),
+    }]);
+    match sync.add_funds(transaction.clone()).await.unwrap_err() {
+        SyncWithBitcoinError::UnconfirmedFundingTransaction(txid) => {
+            assert_eq!(txid, transaction.id())
+        }
+        e => panic!("Unexpected error occurred: {:?}", e),
+    }
+
+    // Funding transaction is registered after the required confirmations.
+    fake_relay.enqueue_requests(vec![FakeRelayRequest::TransactionStatus {
+        request: transaction.id(),
+        response: TransactionStatus::Committed(3),
+    }]);
+    sync.add_funds(transaction.clone()).await.unwrap();
+    testkit.inner.create_block();
+    assert_eq!(
+        get_anchoring_schema(&testkit.inner.snapshot()).unspent_funding_transaction(),
+        Some(transaction)
+    );
+}
+
+#[tokio::test]
+async fn funding_transaction_payload() {
+    let mut testkit = AnchoringTestKit::new(1, 5);
+    let api = testkit.inner.api();
+    let bitcoin_key = testkit.anchoring_keypairs().into_iter().next().unwrap().0;
+    let private_api = testkit
+        .private_api_for_node(api.client().clone(), &bitcoin_key)
+        .unwrap();
+    let funding = AnchoringFundingTask::new(
+        FakeFundingWallet {
+            balance: Mutex::new(100_000),
+        },
+        private_api,
+    );
+
+    match funding.prepare(150_000, false).await.unwrap_err() {
+        FundingError::InsufficientBalance { amount, balance } => {
+            assert_eq!(amount, 150_000);
+            assert_eq!(balance, 100_000);
+        }
+        e => panic!("Unexpected error occurred: {:?}", e),
+    }
+
+    // Funding transaction is created, but it is neither broadcast nor registered.
+    let payload = funding.prepare(40_000, false).await.unwrap();
+    assert_eq!(funding.balance().await.unwrap(), 100_000);
+    let address = testkit.actual_anchoring_config().anchoring_address();
+    let output = &payload.transaction.0.output[0];
+    assert_eq!(output.value, 40_000);
+    assert_eq!(output.script_pubkey, address.0.script_pubkey());
+    testkit.inner.create_block();
+    assert_eq!(
+        get_anchoring_schema(&testkit.inner.snapshot()).unspent_funding_transaction(),
+        None
+    );
+
+    // The payload is ready to be submitted by the anchoring node.
+    let tx_hash = api
+        .client()
+        .add_funds(payload.transaction.clone())
+        .await
+        .unwrap();
+    testkit.inner.create_block_with_tx_hashes(&[tx_hash])[0]
+        .status()
+        .unwrap();
+    assert_eq!(
+        get_anchoring_schema(&testkit.inner.snapshot()).unspent_funding_transaction(),
+        Some(payload.transaction)
+    );
+
+    // Broadcast funding transaction is sent from the wallet.
+    funding.prepare(40_000, true).await.unwrap();
+    assert_eq!(funding.balance().await.unwrap(), 60_000);
+}
+
+#[tokio::test]
+async fn funding_top_up_policy() {
+    let anchoring_interval = 5;
+    let mut testkit = AnchoringTestKit::new(1, anchoring_interval);
+    let api = testkit.inner.api();
+    let keypair = testkit.anchoring_keypairs().into_iter().next().unwrap();
+    let private_api = testkit
+        .private_api_for_node(api.client().clone(), &keypair.0)
+        .unwrap();
+    let fake_relay = ScriptedBitcoinRelay::default();
+    let top_up = AnchoringTopUpTask::new(
+        AnchoringFundingTask::new(
+            FakeFundingWallet {
+                balance: Mutex::new(1_000_000),
+            },
+            private_api,
+        ),
+        fake_relay.clone(),
+        TopUpPolicy {
+            min_capacity: 10,
+            amount: 100_000,
+            confirmations: 2,
+        },
+    );
+
+    // There are no funds, so the top-up transaction should be sent.
+    assert_eq!(top_up.projected_capacity().await.unwrap(), 0);
+    let pending = top_up.process(None).await.unwrap();
+    let transaction = match pending.clone() {
+        Some(PendingTopUp::Unconfirmed(transaction)) => transaction,
+        pending => panic!("Unexpected pending top-up: {:?}", pending),
+    };
+    // Funding transaction has not enough confirmations yet.
+    fake_relay.enqueue_requests(vec![FakeRelayRequest::TransactionStatus {
+        request: transaction.id(),
+        response: TransactionStatus::Committed(1),
+    }]);
+    let pending = top_up.process(pending).await.unwrap();
+    assert_eq!(
+        pending,
+        Some(PendingTopUp::Unconfirmed(transaction.clone()))
+    );
+    // Funding transaction should be registered after the required confirmations.
+    fake_relay.enqueue_requests(vec![FakeRelayRequest::TransactionStatus {
+        request: transaction.id(),
+        response: TransactionStatus::Committed(2),
+    }]);
+    let pending = top_up.process(pending).await.unwrap();
+    assert_eq!(pending, Some(PendingTopUp::Registered(transaction.clone())));
+    testkit.inner.create_block();
+    assert_eq!(
+        get_anchoring_schema(&testkit.inner.snapshot()).unspent_funding_transaction(),
+        Some(transaction.clone())
+    );
+    // Top-up is pending until the funding transaction is spent.
+    let pending = top_up.process(pending).await.unwrap();
+    assert_eq!(pending, Some(PendingTopUp::Registered(transaction)));
+
+    // Create the anchoring transaction which spends the funding transaction.
+    testkit
+        .inner
+        .create_blocks_until(Height(anchoring_interval));
+    AnchoringChainUpdateTask::new(vec![keypair], api.client().clone())
+        .process()
+        .await
+        .unwrap();
+    testkit.inner.create_block();
+
+    // Top-up is completed and the anchoring capacity is sufficient now.
+    assert_eq!(top_up.process(pending).await.unwrap(), None);
+    assert!(top_up.projected_capacity().await.unwrap() >= 10);
+    assert_eq!(top_up.process(None).await.unwrap(), None);
+}
+
+#[derive(Debug)]
+struct FailingBitcoinRelay;
+
+#[async_trait]
+impl BitcoinRelay for FailingBitcoinRelay {
+    type Error = anyhow::Error;
+
+    async fn send_transaction(
+        &self,
+        _transaction: &btc::Transaction,
+    ) -> Result<btc::Sha256d, Self::Error> {
+        Err(anyhow::anyhow!("Transaction is rejected"))
+    }
+
+    async fn transaction_status(
+        &self,
+        _id: btc::Sha256d,
+    ) -> Result<TransactionStatus, Self::Error> {
+        Err(anyhow::anyhow!("Relay is unreachable"))
+    }
+
+    async fn transaction_proof(
+        &self,
+        _id: btc::Sha256d,
+    ) -> Result<Option<btc::MerkleBlock>, Self::Error> {
+        Err(anyhow::anyhow!("Relay is unreachable"))
+    }
+
+    async fn raw_transaction(
+        &self,
+        _id: btc::Sha256d,
+    ) -> Result<Option<btc::Transaction>, Self::Error> {
+        Err(anyhow::anyhow!("Relay is unreachable"))
+    }
+
+    async fn is_output_spent(
+        &self,
+        _txid: btc::Sha256d,
+        _vout: u32,
+    ) -> Result<Option<bool>, Self::Error> {
+        Err(anyhow::anyhow!("Relay is unreachable"))
+    }
+
+    async fn fee_rate(&self, _target_blocks: u16) -> Result<Option<u64>, Self::Error> {
+        Err(anyhow::anyhow!("Relay is unreachable"))
+    }
+}
+
+#[tokio::test]
+async fn sync_runner() {
+    let mut testkit = AnchoringTestKit::new(1, 5);
+    testkit
+        .inner
+        .create_block_with_transactions(testkit.create_funding_confirmation_txs(700_000).0);
+    let api = testkit.inner.api();
+    let keypair = testkit.anchoring_keypairs().into_iter().next().unwrap();
+    let anchoring_interval = testkit.actual_anchoring_config().anchoring_interval;
+    testkit
+        .inner
+        .create_blocks_until(Height(anchoring_interval));
+    let private_api = testkit
+        .private_api_for_node(api.client().clone(), &keypair.0)
+        .unwrap();
+
+    // The first iteration is performed before the shutdown request is handled.
+    let runner: SyncRunner<_, ScriptedBitcoinRelay> = SyncRunner::new(
+        AnchoringChainUpdateTask::new(vec![keypair.clone()], private_api.clone()),
+        SyncRunnerConfig::default(),
+    );
+    runner.handle().shutdown();
+    runner.run().await.unwrap();
+    testkit.inner.create_block();
+    let tx = get_anchoring_schema(&testkit.inner.snapshot())
+        .transactions_chain
+        .get(0)
+        .unwrap();
+
+    // Signed anchoring transaction is sent to the Bitcoin network.
+    let fake_relay = ScriptedBitcoinRelay::default();
+    fake_relay.enqueue_requests(vec![
+        FakeRelayRequest::TransactionStatus {
+            request: tx.id(),
+            response: TransactionStatus::Unknown,
+        },
+        FakeRelayRequest::TransactionStatus {
+            request: tx.prev_tx_id(),
+            response: TransactionStatus::Committed(10),
+        },
+        FakeRelayRequest::SendTransaction {
+            request: tx.clone(),
+            response: tx.id(),
+        },
+    ]);
+    let runner = SyncRunner::new(
+        AnchoringChainUpdateTask::new(vec![keypair.clone()], private_api.clone()),
+        SyncRunnerConfig::default(),
+    )
+    .with_bitcoin_sync(SyncWithBitcoinTask::new(fake_relay, private_api.clone()));
+    runner.handle().shutdown();
+    runner.run().await.unwrap();
+
+    // Relay errors don't stop the runner.
+    let runner = SyncRunner::new(
+        AnchoringChainUpdateTask::new(vec![keypair], private_api.clone()),
+        SyncRunnerConfig::default(),
+    )
+    .with_bitcoin_sync(SyncWithBitcoinTask::new(FailingBitcoinRelay, private_api));
+    runner.handle().shutdown();
+    runner.run().await.unwrap();
+}
+
+#[tokio::test]
+async fn sync_runner_watch_only() {
+    let mut testkit = AnchoringTestKit::default();
+    testkit
+        .inner
+        .create_block_with_transactions(testkit.create_signature_txs().into_iter().flatten());
+    let tx = get_anchoring_schema(&testkit.inner.snapshot())
+        .transactions_chain
+        .get(0)
+        .unwrap();
+
+    // Watch-only runner doesn't need any Bitcoin keys to send the anchoring transactions.
+    let fake_relay = ScriptedBitcoinRelay::default();
+    fake_relay.enqueue_requests(vec![
+        FakeRelayRequest::TransactionStatus {
+            request: tx.id(),
+            response: TransactionStatus::Unknown,
+        },
+        FakeRelayRequest::TransactionStatus {
+            request: tx.prev_tx_id(),
+            response: TransactionStatus::Committed(10),
+        },
+        FakeRelayRequest::SendTransaction {
+            request: tx.clone(),
+            response: tx.id(),
+        },
+    ]);
+    let api = testkit.inner.api();
+    let runner = SyncRunner::watch_only(
+        SyncWithBitcoinTask::new(fake_relay, api.client().clone()),
+        SyncRunnerConfig::default(),
+    );
+    runner.handle().shutdown();
+    runner.run().await.unwrap();
+}
+
+#[test]
+fn sync_runner_backoff() {
+    let config = SyncRunnerConfig::default();
+    for _ in 0..10 {
+        let delay = config.backoff(1);
+        assert!(delay >= config.initial_backoff / 2 && delay <= config.initial_backoff);
+        let delay = config.backoff(3);
+        assert!(delay >= config.initial_backoff * 2 && delay <= config.initial_backoff * 4);
+        let delay = config.backoff(100);
+        assert!(delay >= config.max_backoff / 2 && delay <= config.max_backoff);
+    }
+}
+
+type DynBitcoinRelay = Box<dyn BitcoinRelay<Error = anyhow::Error> + Send + Sync>;
+
+#[tokio::test]
+async fn broadcast_to_all_relays() {
+    let anchoring_testkit = AnchoringTestKit::default();
+    let address = anchoring_testkit
+        .actual_anchoring_config()
+        .anchoring_address();
+    let transaction = create_fake_funding_transaction(&address, 10_000);
+
+    // Transaction is accepted by one of the relays.
+    let fake_relay = ScriptedBitcoinRelay::default();
+    fake_relay.enqueue_requests(vec![FakeRelayRequest::SendTransaction {
+        request: transaction.clone(),
+        response: transaction.id(),
+    }]);
+    let relay = BroadcastAllRelay::new(vec![
+        Box::new(FailingBitcoinRelay) as DynBitcoinRelay,
+        Box::new(fake_relay),
+    ]);
+    assert_eq!(
+        relay.send_transaction(&transaction).await.unwrap(),
+        transaction.id()
+    );
+    drop(relay);
+
+    // The most advanced transaction status is returned.
+    let relays = vec![
+        TransactionStatus::Unknown,
+        TransactionStatus::Committed(2),
+        TransactionStatus::Mempool,
+    ]
+    .into_iter()
+    .map(|response| {
+        let fake_relay = ScriptedBitcoinRelay::default();
+        fake_relay.enqueue_requests(vec![FakeRelayRequest::TransactionStatus {
+            request: transaction.id(),
+            response,
+        }]);
+        Box::new(fake_relay) as DynBitcoinRelay
+    })
+    .chain(Some(Box::new(FailingBitcoinRelay) as DynBitcoinRelay));
+    let relay = BroadcastAllRelay::new(relays);
+    assert_eq!(
+        relay.transaction_status(transaction.id()).await.unwrap(),
+        TransactionStatus::Committed(2)
+    );
+
+    // All relays have failed.
+    let relay = BroadcastAllRelay::new(vec![FailingBitcoinRelay, FailingBitcoinRelay]);
+    match relay.send_transaction(&transaction).await.unwrap_err() {
+        BroadcastError::AllFailed(errors) => assert_eq!(errors.len(), 2),
+        e => panic!("Unexpected error: {}", e),
+    }
+    let relay = BroadcastAllRelay::<FailingBitcoinRelay>::new(vec![]);
+    assert!(matches!(
+        relay.transaction_proof(transaction.id()).await,
+        Err(BroadcastError::NoRelays)
+    ));
+}
+
+#[tokio::test]
+async fn fake_bitcoin_relay() {
+    let address = AnchoringTestKit::new(1, 5)
+        .actual_anchoring_config()
+        .anchoring_address();
+    let funding_tx = create_fake_funding_transaction(&address, 10_000);
+    let spend = |value| -> btc::Transaction {
+        bitcoin::Transaction {
+            version: 2,
+            lock_time: 0,
+            input: vec![bitcoin::TxIn {
+                previous_output: bitcoin::OutPoint {
+                    txid: funding_tx.id().into(),
+                    vout: 0,
+                },
+                ..bitcoin::TxIn::default()
+            }],
+            output: vec![bitcoin::TxOut {
+                value,
+                script_pubkey: address.0.script_pubkey(),
+            }],
+        }
+        .into()
+    };
+    let (tx, conflicting_tx) = (spend(9_000), spend(8_000));
+
+    let relay = FakeBitcoinRelay::new();
+    assert_eq!(
+        relay.is_output_spent(funding_tx.id(), 0).await.unwrap(),
+        None
+    );
+    assert_eq!(relay.confirm_transaction(funding_tx.clone()), 1);
+    assert_eq!(
+        relay.is_output_spent(funding_tx.id(), 0).await.unwrap(),
+        Some(false)
+    );
+    let proof = relay.transaction_proof(funding_tx.id()).await.unwrap();
+    assert_eq!(
+        proof.unwrap().matched_transactions(),
+        Some(vec![funding_tx.id()])
+    );
+
+    // Transaction spending the same output is rejected.
+    relay.send_transaction(&tx).await.unwrap();
+    relay.send_transaction(&conflicting_tx).await.unwrap_err();
+    assert_eq!(
+        relay.is_output_spent(funding_tx.id(), 0).await.unwrap(),
+        Some(true)
+    );
+
+    // After the eviction the conflicting transaction is accepted and mined.
+    assert!(relay.evict_transaction(tx.id()));
+    relay.send_transaction(&conflicting_tx).await.unwrap();
+    assert_eq!(relay.mine_blocks(3), 4);
+    assert_eq!(
+        relay.transaction_status(conflicting_tx.id()).await.unwrap(),
+        TransactionStatus::Committed(3)
+    );
+    assert_eq!(
+        relay.transaction_status(tx.id()).await.unwrap(),
+        TransactionStatus::Unknown
+    );
+    assert_eq!(relay.raw_transaction(tx.id()).await.unwrap(), None);
+
+    // Reorganized transactions return into the mempool.
+    relay.reorg(3);
+    assert_eq!(relay.height(), 1);
+    assert_eq!(relay.mempool(), vec![conflicting_tx.id()]);
+
+    relay.set_fee_rate(Some(5));
+    assert_eq!(relay.fee_rate(6).await.unwrap(), Some(5));
+}
+
+#[tokio::test]
+async fn relay_audit_log() {
+    let anchoring_testkit = AnchoringTestKit::default();
+    let address = anchoring_testkit
+        .actual_anchoring_config()
+        .anchoring_address();
+    let transaction = create_fake_funding_transaction(&address, 10_000);
+
+    let log_path =
+        std::env::temp_dir().join(format!("anchoring-relay-audit-{}", std::process::id()));
+    let log = RelayAuditLog::open(&log_path).unwrap();
+    assert_eq!(log.path(), Some(log_path.as_path()));
+
+    let fake_relay = ScriptedBitcoinRelay::default();
+    fake_relay.enqueue_requests(vec![
+        FakeRelayRequest::SendTransaction {
+            request: transaction.clone(),
+            response: transaction.id(),
+        },
+        FakeRelayRequest::TransactionStatus {
+            request: transaction.id(),
+            response: TransactionStatus::Mempool,
+        },
+    ]);
+    let relay = AuditRelay::new(fake_relay, log.clone());
+    relay.send_transaction(&transaction).await.unwrap();
+    relay.transaction_status(transaction.id()).await.unwrap();
+    drop(relay);
+
+    // Errors of the relay are recorded as well.
+    let relay = AuditRelay::new(FailingBitcoinRelay, log);
+    relay.fee_rate(6).await.unwrap_err();
+
+    let records = RelayAuditLog::read(&log_path).unwrap();
+    assert_eq!(records.len(), 3);
+    assert_eq!(records[0].method, "send_transaction");
+    assert_eq!(records[0].txid, Some(transaction.id()));
+    assert_eq!(records[0].response, Some(transaction.id().to_string()));
+    assert_eq!(records[1].method, "transaction_status");
+    assert_eq!(records[1].response.as_deref(), Some("Mempool"));
+    assert_ne!(records[0].payload_hash, records[1].payload_hash);
+    assert_eq!(records[2].method, "fee_rate");
+    assert_eq!(records[2].txid, None);
+    assert_eq!(records[2].response, None);
+    assert_eq!(records[2].error.as_deref(), Some("Relay is unreachable"));
+
+    // Log is appended to after reopening.
+    let relay = AuditRelay::new(FailingBitcoinRelay, RelayAuditLog::open(&log_path).unwrap());
+    relay
+        .is_output_spent(transaction.id(), 0)
+        .await
+        .unwrap_err();
+    assert_eq!(RelayAuditLog::read(&log_path).unwrap().len(), 4);
+
+    std::fs::remove_file(log_path).unwrap();
+}
+
+#[tokio::test]
+async fn bitcoind_relay_timeout() {
+    // Bitcoin node which accepts the connections, but never responds.
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let relay = BitcoindRelay::new(&BitcoindRelayConfig {
+        host: format!("http://{}", listener.local_addr().unwrap()),
+        user: Some("user".to_owned()),
+        password: Some("password".to_owned()),
+        request_timeout: Some(100),
+        ..BitcoindRelayConfig::default()
+    })
+    .unwrap();
+
+    let txid = btc::Sha256d::new([1; 32]);
+    match relay.transaction_status(txid).await.unwrap_err() {
+        BitcoindRelayError::Timeout(timeout) => assert_eq!(timeout, Duration::from_millis(100)),
+        e => panic!("Unexpected error: {}", e),
+    }
+    // Reset the pending connection, so the abandoned request is completed.
+    drop(listener);
+}
+
+/// State of the fake Electrum server, which knows the single transaction.
+#[cfg(feature = "electrum")]
+#[derive(Debug, Default)]
+struct FakeElectrumState {
+    transaction: Option<btc::Transaction>,
+    /// Header of the block with the coinbase and the known transaction.
+    block_header: Option<btc::BlockHeader>,
+    coinbase_txid: Option<btc::Sha256d>,
+    /// Height of the block with the transaction, zero if the transaction is in the mempool.
+    height: u64,
+    tip_height: u64,
+    /// Script hash subscribed by the client.
+    subscription: Option<Value>,
+    /// Whether to notify the client about the changed script hash status.
+    notify: bool,
+    /// Whether to drop the next connection on the first request after the handshake.
+    drop_connection: bool,
+    connections: usize,
+    history_requests: usize,
+}
+
+#[cfg(feature = "electrum")]
+impl FakeElectrumState {
+    fn response(&mut self, method: &str, params: &Value) -> Result<Value, Value> {
+        let transaction = self.transaction.clone().unwrap();
+        let unknown_transaction = json!({
+            "code": 2,
+            "message": "No such mempool or blockchain transaction",
+        });
+        let response = match method {
+            "server.version" => json!(["FakeElectrum", "1.4"]),
+            "server.ping" => Value::Null,
+            "blockchain.headers.subscribe" => json!({ "height": self.tip_height, "hex": "" }),
+            "blockchain.scripthash.subscribe" => {
+                self.subscription = Some(params[0].clone());
+                json!(format!("status-{}", self.height))
+            }
+            "blockchain.scripthash.get_history" => {
+                self.history_requests += 1;
+                json!([{ "tx_hash": transaction.id(), "height": self.height }])
+            }
+            "blockchain.transaction.get" if params[0] == json!(transaction.id()) => {
+                json!(transaction)
+            }
+            "blockchain.transaction.get" => return Err(unknown_transaction),
+            "blockchain.transaction.broadcast" => {
+                let transaction: btc::Transaction =
+                    serde_json::from_value(params[0].clone()).unwrap();
+                json!(transaction.id())
+            }
+            "blockchain.transaction.get_merkle" if params[1] == json!(self.height) => {
+                json!({ "block_height": self.height, "merkle": [self.coinbase_txid], "pos": 1 })
+            }
+            "blockchain.block.header" if params[0] == json!(self.height) => {
+                json!(self.block_header)
+            }
+            _ => panic!("Unexpected Electrum request {} {}", method, params),
+        };
+        Ok(response)
+    }
+}
+
+#[cfg(feature = "electrum")]
+async fn run_fake_electrum_server(
+    mut listener: AsyncTcpListener,
+    state: Arc<Mutex<FakeElectrumState>>,
+) {
+    loop {
+        let (socket, _) = listener.accept().await.unwrap();
+        let state = state.clone();
+        let drop_connection = {
+            let mut state = state.lock().unwrap();
+            state.connections += 1;
+            std::mem::replace(&mut state.drop_connection, false)
+        };
+        tokio::spawn(async move {
+            let (reader, mut writer) = tokio::io::split(socket);
+            let mut reader = BufReader::new(reader);
+            loop {
+                let mut line = String::new();
+                if reader.read_line(&mut line).await.unwrap() == 0 {
+                    return;
+                }
+                let request: Value = serde_json::from_str(&line).unwrap();
+                let method = request["method"].as_str().unwrap();
+                if drop_connection
+                    && !method.starts_with("server.")
+                    && method != "blockchain.headers.subscribe"
+                {
+                    return;
+                }
+
+                let mut messages = Vec::new();
+                let response = {
+                    let mut state = state.lock().unwrap();
+                    if std::mem::replace(&mut state.notify, false) {
+                        messages.push(json!({
+                            "jsonrpc": "2.0",
+                            "method": "blockchain.scripthash.subscribe",
+                            "params": [state.subscription, format!("status-{}", state.height)],
+                        }));
+                    }
+                    state.response(method, &request["params"])
+                };
+                messages.push(match response {
+                    Ok(result) => {
+                        json!({ "jsonrpc": "2.0", "id": request["id"], "result": result })
+                    }
+                    Err(error) => json!({ "jsonrpc": "2.0", "id": request["id"], "error": error }),
+                });
+                for message in messages {
+                    let message = format!("{}\n", message);
+                    writer.write_all(message.as_bytes()).await.unwrap();
+                }
+            }
+        });
+    }
+}
+
+#[cfg(feature = "electrum")]
+#[tokio::test]
+async fn electrum_relay() {
+    let address = AnchoringTestKit::default()
+        .actual_anchoring_config()
+        .anchoring_address();
+    let transaction = create_fake_funding_transaction(&address, 10_000);
+    let coinbase_txid = btc::Sha256d::new([2; 32]);
+    let merkle_root = bitcoin::TxMerkleNode::hash(
+        &[
+            coinbase_txid.to_internal_bytes(),
+            transaction.id().to_internal_bytes(),
+        ]
+        .concat(),
+    );
+    let block_header = btc::BlockHeader(bitcoin::BlockHeader {
+        version: 1,
+        prev_blockhash: Default::default(),
+        merkle_root,
+        time: 0,
+        bits: 0,
+        nonce: 0,
+    });
+    let state = Arc::new(Mutex::new(FakeElectrumState {
+        transaction: Some(transaction.clone()),
+        block_header: Some(block_header),
+        coinbase_txid: Some(coinbase_txid),
+        tip_height: 105,
+        drop_connection: true,
+        ..FakeElectrumState::default()
+    }));
+    let listener = AsyncTcpListener::bind("127.0.0.1:0").await.unwrap();
+    let relay = ElectrumRelay::new(ElectrumRelayConfig {
+        server: listener.local_addr().unwrap().to_string(),
+        request_timeout: Some(10_000),
+        ..ElectrumRelayConfig::default()
+    });
+    tokio::spawn(run_fake_electrum_server(listener, state.clone()));
+
+    // The broadcast request is repeated after the connection has been dropped.
+    assert_eq!(
+        relay.send_transaction(&transaction).await.unwrap(),
+        transaction.id()
+    );
+    assert_eq!(state.lock().unwrap().connections, 2);
+
+    // Histories of the subscribed addresses are cached until the status is changed.
+    relay.subscribe_address(&address).await.unwrap();
+    for _ in 0..2 {
+        assert_eq!(
+            relay.transaction_status(transaction.id()).await.unwrap(),
+            TransactionStatus::Mempool
+        );
+    }
+    assert_eq!(
+        relay.transaction_proof(transaction.id()).await.unwrap(),
+        None
+    );
+    assert_eq!(state.lock().unwrap().history_requests, 1);
+    {
+        let mut state = state.lock().unwrap();
+        state.height = 100;
+        state.notify = true;
+    }
+    assert_eq!(
+        relay.transaction_status(transaction.id()).await.unwrap(),
+        TransactionStatus::Committed(6)
+    );
+    assert_eq!(state.lock().unwrap().history_requests, 2);
+
+    let merkle_block = relay
+        .transaction_proof(transaction.id())
+        .await
+        .unwrap()
+        .unwrap();
+    assert_eq!(merkle_block.header(), block_header);
+    assert_eq!(
+        merkle_block.matched_transactions().unwrap(),
+        vec![transaction.id()]
+    );
+    assert_eq!(
+        relay.raw_transaction(transaction.id()).await.unwrap(),
+        Some(transaction)
+    );
+
+    // Unknown transaction.
+    let txid = btc::Sha256d::new([1; 32]);
+    assert_eq!(relay.raw_transaction(txid).await.unwrap(), None);
+    assert_eq!(
+        relay.transaction_status(txid).await.unwrap(),
+        TransactionStatus::Unknown
+    );
+    assert_eq!(state.lock().unwrap().connections, 2);
+}
+
+/// Publishes the message until the expected notification is received, since the publisher
+/// drops messages until the subscription is established.
+async fn publish_until(
+    publisher: &zmq::Socket,
+    notifications: &mut BitcoinNotifications,
+    message: &[&[u8]],
+    expected: BitcoinNotification,
+) {
+    for _ in 0..100 {
+        publisher.send_multipart(message, 0).unwrap();
+        let notification =
+            tokio::time::timeout(Duration::from_millis(50), notifications.next()).await;
+        if notification == Ok(expected) {
+            return;
+        }
+    }
+    panic!("Notification {:?} has not been received", expected);
+}
+
+#[tokio::test]
+async fn zmq_notifications() {
+    // Reserve the port for the publisher which is started later.
+    let port = TcpListener::bind("127.0.0.1:0")
+        .unwrap()
+        .local_addr()
+        .unwrap()
+        .port();
+    let endpoint = format!("tcp://127.0.0.1:{}", port);
+    let mut notifications = BitcoinNotifications::spawn(
+        &ZmqNotificationsConfig {
+            rawblock: Some(endpoint.clone()),
+            rawtx: Some(endpoint.clone()),
+        },
+        Duration::from_millis(100),
+    );
+    // Bitcoin node is unavailable.
+    assert!(
+        tokio::time::timeout(Duration::from_millis(300), notifications.next())
+            .await
+            .is_err()
+    );
+
+    let context = zmq::Context::new();
+    let publisher = context.socket(zmq::PUB).unwrap();
+    publisher.bind(&endpoint).unwrap();
+
+    let block = bitcoin::blockdata::constants::genesis_block(bitcoin::Network::Testnet);
+    let block_hash = btc::BlockHeader(block.header).block_hash();
+    let raw_block = bitcoin::consensus::serialize(&block);
+    publish_until(
+        &publisher,
+        &mut notifications,
+        &[b"rawblock", &raw_block, &0_u32.to_le_bytes()],
+        BitcoinNotification::Block(block_hash),
+    )
+    .await;
+
+    let transaction = btc::Transaction::from(block.txdata[0].clone());
+    let raw_transaction = bitcoin::consensus::serialize(&block.txdata[0]);
+    publish_until(
+        &publisher,
+        &mut notifications,
+        &[b"rawtx", &raw_transaction, &0_u32.to_le_bytes()],
+        BitcoinNotification::Transaction(transaction.id()),
+    )
+    .await;
+}
+
+#[derive(Debug)]
+struct FakeFilterPeerState {
+    blocks: Vec<bitcoin::Block>,
+    received: Vec<bitcoin::Transaction>,
+}
+
+impl FakeFilterPeerState {
+    fn block_height(&self, hash: &bitcoin::BlockHash) -> Option<usize> {
+        self.blocks
+            .iter()
+            .position(|block| block.header.bitcoin_hash() == *hash)
+    }
+
+    fn block_filter(&self, height: usize) -> Vec<u8> {
+        let script_for_coin = |outpoint: &bitcoin::OutPoint| {
+            let script = self
+                .blocks
+                .iter()
+                .flat_map(|block| &block.txdata)
+                .find(|transaction| transaction.txid() == outpoint.txid)
+                .map_or_else(bitcoin::Script::new, |transaction| {
+                    transaction.output[outpoint.vout as usize]
+                        .script_pubkey
+                        .clone()
+                });
+            Ok(script)
+        };
+        BlockFilter::new_script_filter(&self.blocks[height], script_for_coin)
+            .unwrap()
+            .content
+    }
+
+    fn mine_block(&mut self, prev_height: usize, transactions: Vec<bitcoin::Transaction>) {
+        let prev = self.blocks[prev_height].header;
+        // Coinbase transactions should differ in the different blocks.
+        let coinbase = bitcoin::Transaction {
+            version: 1,
+            lock_time: 0,
+            input: vec![bitcoin::TxIn {
+                previous_output: bitcoin::OutPoint::null(),
+                script_sig: bitcoin::Script::from(rand::random::<[u8; 8]>().to_vec()),
+                sequence: 0xFFFF_FFFF,
+                witness: vec![],
+            }],
+            output: vec![bitcoin::TxOut {
+                value: 50,
+                script_pubkey: bitcoin::Script::new(),
+            }],
+        };
+        let mut block = bitcoin::Block {
+            header: bitcoin::BlockHeader {
+                version: 1,
+                prev_blockhash: prev.bitcoin_hash(),
+                merkle_root: Default::default(),
+                time: prev.time + 600,
+                bits: prev.bits,
+                nonce: 0,
+            },
+            txdata: std::iter::once(coinbase).chain(transactions).collect(),
+        };
+        block.header.merkle_root = block.merkle_root();
+        while block.header.validate_pow(&block.header.target()).is_err() {
+            block.header.nonce += 1;
+        }
+        self.blocks.truncate(prev_height + 1);
+        self.blocks.push(block);
+    }
+
+    fn respond(&mut self, request: NetworkMessage) -> Vec<NetworkMessage> {
+        match request {
+            NetworkMessage::Version(_) => {
+                let address = PeerAddress::new(&"127.0.0.1:0".parse().unwrap(), ServiceFlags::NONE);
+                let services =
+                    ServiceFlags::NETWORK | ServiceFlags::WITNESS | ServiceFlags::COMPACT_FILTERS;
+                let version = VersionMessage::new(
+                    services,
+                    0,
+                    address.clone(),
+                    address,
+                    0,
+                    "/fake/".to_owned(),
+                    self.blocks.len() as i32 - 1,
+                );
+                // The relay should answer the ping during the handshake.
+                vec![
+                    NetworkMessage::Version(version),
+                    NetworkMessage::Ping(1),
+                    NetworkMessage::Verack,
+                ]
+            }
+            NetworkMessage::GetHeaders(request) => {
+                let start = request
+                    .locator_hashes
+                    .iter()
+                    .find_map(|hash| self.block_height(hash))
+                    .unwrap_or_default();
+                let headers = self.blocks[start + 1..]
+                    .iter()
+                    .map(|block| block.header)
+                    .collect();
+                vec![NetworkMessage::Headers(headers)]
+            }
+            NetworkMessage::GetCFilters(request) => {
+                let stop = self.block_height(&request.stop_hash).unwrap();
+                (request.start_height as usize..=stop)
+                    .map(|height| {
+                        NetworkMessage::CFilter(CFilter {
+                            filter_type: request.filter_type,
+                            block_hash: self.blocks[height].header.bitcoin_hash(),
+                            filter: self.block_filter(height),
+                        })
+                    })
+                    .collect()
+            }
+            NetworkMessage::GetData(inventory) => inventory
+                .into_iter()
+                .map(|item| match item {
+                    Inventory::WitnessBlock(hash) => {
+                        let height = self.block_height(&hash).unwrap();
+                        NetworkMessage::Block(self.blocks[height].clone())
+                    }
+                    item => NetworkMessage::NotFound(vec![item]),
+                })
+                .collect(),
+            NetworkMessage::Tx(transaction) => {
+                self.received.push(transaction);
+                vec![]
+            }
+            _ => vec![],
+        }
+    }
+}
+
+async fn run_fake_filter_peer(
+    mut listener: AsyncTcpListener,
+    state: Arc<Mutex<FakeFilterPeerState>>,
+) {
+    let magic = bitcoin::Network::Regtest.magic();
+    loop {
+        let (mut stream, _) = listener.accept().await.unwrap();
+        let state = state.clone();
+        tokio::spawn(async move {
+            loop {
+                let mut message = vec![0_u8; 24];
+                if stream.read_exact(&mut message).await.is_err() {
+                    return;
+                }
+                let payload_size = u32::from_le_bytes(message[16..20].try_into().unwrap());
+                message.resize(24 + payload_size as usize, 0);
+                stream.read_exact(&mut message[24..]).await.unwrap();
+                let request: RawNetworkMessage = deserialize(&message).unwrap();
+
+                let responses = state.lock().unwrap().respond(request.payload);
+                for payload in responses {
+                    let response = RawNetworkMessage { magic, payload };
+                    stream.write_all(&serialize(&response)).await.unwrap();
+                }
+            }
+        });
+    }
+}
+
+fn p2p_transaction(inputs: &[bitcoin::OutPoint], address: &btc::Address) -> bitcoin::Transaction {
+    bitcoin::Transaction {
+        version: 2,
+        lock_time: 0,
+        input: inputs
+            .iter()
+            .map(|outpoint| bitcoin::TxIn {
+                previous_output: *outpoint,
+                script_sig: bitcoin::Script::new(),
+                sequence: 0,
+                witness: vec![],
+            })
+            .collect(),
+        output: vec![bitcoin::TxOut {
+            value: 1000,
+            script_pubkey: address.0.script_pubkey(),
+        }],
+    }
+}
+
+#[tokio::test]
+async fn compact_filter_relay() {
+    let network = bitcoin::Network::Regtest;
+    let new_address = |_| {
+        let public_key = btc::gen_keypair(network).0;
+        btc::Address(bitcoin::Address::p2wpkh(&public_key.0, network))
+    };
+    let (address, other_address) = (new_address(0), new_address(1));
+    let random_outpoint = || bitcoin::OutPoint {
+        txid: btc::Sha256d::new(rand::random()).into(),
+        vout: 0,
+    };
+
+    let funding = p2p_transaction(&[random_outpoint()], &address);
+    let unrelated = p2p_transaction(&[random_outpoint()], &other_address);
+    // Spends the output of the watched address, but doesn't pay to it.
+    let spending = p2p_transaction(
+        &[bitcoin::OutPoint {
+            txid: funding.txid(),
+            vout: 0,
+        }],
+        &other_address,
+    );
+    let mut state = FakeFilterPeerState {
+        blocks: vec![genesis_block(network)],
+        received: Vec::new(),
+    };
+    state.mine_block(0, vec![funding.clone(), unrelated.clone()]);
+    state.mine_block(1, vec![]);
+    state.mine_block(2, vec![spending.clone()]);
+    let state = Arc::new(Mutex::new(state));
+
+    let listener = AsyncTcpListener::bind("127.0.0.1:0").await.unwrap();
+    let peer = listener.local_addr().unwrap().to_string();
+    tokio::spawn(run_fake_filter_peer(listener, state.clone()));
+
+    let relay = CompactFilterRelay::new(CompactFilterRelayConfig {
+        peer,
+        network,
+        checkpoint: None,
+        request_timeout: Some(5_000),
+        proxy: None,
+    });
+    relay.watch_address(&address).await;
+    let (funding, unrelated, spending) = (
+        btc::Transaction::from(funding),
+        btc::Transaction::from(unrelated),
+        btc::Transaction::from(spending),
+    );
+
+    assert_eq!(
+        relay.transaction_status(funding.id()).await.unwrap(),
+        TransactionStatus::Committed(3)
+    );
+    assert_eq!(
+        relay.transaction_status(spending.id()).await.unwrap(),
+        TransactionStatus::Committed(1)
+    );
+    assert_eq!(
+        relay.transaction_status(unrelated.id()).await.unwrap(),
+        TransactionStatus::Unknown
+    );
+    let proof = relay
+        .transaction_proof(funding.id())
+        .await
+        .unwrap()
+        .unwrap();
+    assert_eq!(
+        proof.header().block_hash(),
+        btc::BlockHeader(state.lock().unwrap().blocks[1].header).block_hash()
+    );
+    assert_eq!(proof.matched_transactions().unwrap(), vec![funding.id()]);
+    assert_eq!(
+        relay.raw_transaction(spending.id()).await.unwrap(),
+        Some(spending.clone())
+    );
+
+    // Transaction sent via the relay is in the mempool until it is mined.
+    let sent = btc::Transaction::from(p2p_transaction(
+        &[bitcoin::OutPoint {
+            txid: spending.0.txid(),
+            vout: 0,
+        }],
+        &new_address(2),
+    ));
+    assert_eq!(relay.send_transaction(&sent).await.unwrap(), sent.id());
+    assert_eq!(
+        relay.transaction_status(sent.id()).await.unwrap(),
+        TransactionStatus::Mempool
+    );
+    assert_eq!(state.lock().unwrap().received, vec![sent.0.clone()]);
+
+    state.lock().unwrap().mine_block(3, vec![sent.0.clone()]);
+    assert_eq!(
+        relay.transaction_status(sent.id()).await.unwrap(),
+        TransactionStatus::Committed(1)
+    );
+    assert_eq!(relay.sync().await.unwrap(), 4);
+
+    // The longer fork without the sent transaction replaces the chain.
+    {
+        let mut state = state.lock().unwrap();
+        state.mine_block(3, vec![]);
+        state.mine_block(4, vec![]);
+    }
+    assert_eq!(
+        relay.transaction_status(sent.id()).await.unwrap(),
+        TransactionStatus::Mempool
+    );
+    assert_eq!(
+        relay.transaction_status(funding.id()).await.unwrap(),
+        TransactionStatus::Committed(5)
+    );
+}
+
+/// Runs the SOCKS5 proxy which requires the `user:pass` credentials and records
+/// the requested targets.
+async fn run_fake_socks_proxy(mut listener: AsyncTcpListener, targets: Arc<Mutex<Vec<String>>>) {
+    loop {
+        let (mut stream, _) = listener.accept().await.unwrap();
+        let targets = targets.clone();
+        tokio::spawn(async move {
+            let mut greeting = [0_u8; 2];
+            stream.read_exact(&mut greeting).await.unwrap();
+            let mut methods = vec![0_u8; usize::from(greeting[1])];
+            stream.read_exact(&mut methods).await.unwrap();
+            assert!(methods.contains(&0x02));
+            stream.write_all(&[0x05, 0x02]).await.unwrap();
+
+            let mut credentials = Vec::new();
+            let _version = stream.read_u8().await.unwrap();
+            for _ in 0..2 {
+                let mut field = vec![0_u8; usize::from(stream.read_u8().await.unwrap())];
+                stream.read_exact(&mut field).await.unwrap();
+                credentials.push(String::from_utf8(field).unwrap());
+            }
+            assert_eq!(credentials, ["user", "pass"]);
+            stream.write_all(&[0x01, 0x00]).await.unwrap();
+
+            let mut request = [0_u8; 4];
+            stream.read_exact(&mut request).await.unwrap();
+            assert_eq!(request[..3], [0x05, 0x01, 0x00]);
+            let host = match request[3] {
+                0x01 => {
+                    let mut ip = [0_u8; 4];
+                    stream.read_exact(&mut ip).await.unwrap();
+                    std::net::Ipv4Addr::from(ip).to_string()
+                }
+                0x03 => {
+                    let mut host = vec![0_u8; usize::from(stream.read_u8().await.unwrap())];
+                    stream.read_exact(&mut host).await.unwrap();
+                    String::from_utf8(host).unwrap()
+                }
+                address_type => panic!("Unexpected address type {}", address_type),
+            };
+            let port = stream.read_u16().await.unwrap();
+            targets.lock().unwrap().push(format!("{}:{}", host, port));
+
+            let mut upstream = AsyncTcpStream::connect((host.as_str(), port))
+                .await
+                .unwrap();
+            stream
+                .write_all(&[0x05, 0x00, 0x00, 0x01, 0, 0, 0, 0, 0, 0])
+                .await
+                .unwrap();
+
+            let (mut client_reader, mut client_writer) = stream.split();
+            let (mut upstream_reader, mut upstream_writer) = upstream.split();
+            let to_upstream = async {
+                let _ = tokio::io::copy(&mut client_reader, &mut upstream_writer).await;
+                let _ = upstream_writer.shutdown().await;
+            };
+            let to_client = async {
+                let _ = tokio::io::copy(&mut upstream_reader, &mut client_writer).await;
+                let _ = client_writer.shutdown().await;
+            };
+            futures::join!(to_upstream, to_client);
+        });
+    }
+}
+
+/// Handles the Bitcoin RPC requests, only `sendrawtransaction` is supported.
+async fn handle_fake_bitcoind_request(
+    request: hyper::Request<hyper::Body>,
+) -> Result<hyper::Response<hyper::Body>, Infallible> {
+    assert_eq!(request.uri().path(), "/wallet/anchoring");
+    assert_eq!(
+        request.headers()["authorization"],
+        format!("Basic {}", base64::encode("rpc-user:rpc-password"))
+    );
+    let body = hyper::body::to_bytes(request.into_body()).await.unwrap();
+    let request: Value = serde_json::from_slice(&body).unwrap();
+
+    let (status, response) = if request["method"] == "sendrawtransaction" {
+        let transaction: btc::Transaction = request["params"][0].as_str().unwrap().parse().unwrap();
+        (
+            200,
+            json!({ "result": transaction.id(), "error": null, "id": request["id"] }),
+        )
+    } else {
+        let error = json!({ "code": -5, "message": "No such mempool or blockchain transaction" });
+        (
+            500,
+            json!({ "result": null, "error": error, "id": request["id"] }),
+        )
+    };
+    let mut response = hyper::Response::new(hyper::Body::from(response.to_string()));
+    *response.status_mut() = hyper::StatusCode::from_u16(status).unwrap();
+    Ok(response)
+}
+
+#[tokio::test]
+async fn relays_via_socks5_proxy() {
+    let targets = Arc::new(Mutex::new(Vec::new()));
+    let listener = AsyncTcpListener::bind("127.0.0.1:0").await.unwrap();
+    let proxy = ProxyConfig {
+        host: "127.0.0.1".to_owned(),
+        port: listener.local_addr().unwrap().port(),
+        username: Some("user".to_owned()),
+        password: Some("pass".to_owned()),
+    };
+    tokio::spawn(run_fake_socks_proxy(listener, targets.clone()));
+
+    // Bitcoin RPC.
+    let make_service = hyper::service::make_service_fn(|_| async {
+        Ok::<_, Infallible>(hyper::service::service_fn(handle_fake_bitcoind_request))
+    });
+    let server = hyper::Server::bind(&"127.0.0.1:0".parse().unwrap()).serve(make_service);
+    let bitcoind_port = server.local_addr().port();
+    tokio::spawn(server);
+    let relay = BitcoindRelay::new(&BitcoindRelayConfig {
+        host: format!("http://localhost:{}/wallet/anchoring", bitcoind_port),
+        user: Some("rpc-user".to_owned()),
+        password: Some("rpc-password".to_owned()),
+        proxy: Some(proxy.clone()),
+        ..BitcoindRelayConfig::default()
+    })
+    .unwrap();
+    let address = AnchoringTestKit::default()
+        .actual_anchoring_config()
+        .anchoring_address();
+    let transaction = create_fake_funding_transaction(&address, 10_000);
+    assert_eq!(
+        relay.send_transaction(&transaction).await.unwrap(),
+        transaction.id()
+    );
+    assert_eq!(relay.raw_transaction(transaction.id()).await.unwrap(), None);
+
+    // Bitcoin node with the compact block filters.
+    let network = bitcoin::Network::Regtest;
+    let state = FakeFilterPeerState {
+        blocks: vec![genesis_block(network)],
+        received: Vec::new(),
+    };
+    let listener = AsyncTcpListener::bind("127.0.0.1:0").await.unwrap();
+    let peer = listener.local_addr().unwrap().to_string();
+    tokio::spawn(run_fake_filter_peer(listener, Arc::new(Mutex::new(state))));
+    let relay = CompactFilterRelay::new(CompactFilterRelayConfig {
+        peer: peer.clone(),
+        network,
+        checkpoint: None,
+        request_timeout: Some(5_000),
+        proxy: Some(proxy),
+    });
+    assert_eq!(relay.sync().await.unwrap(), 0);
+
+    // Each Bitcoin RPC request uses a separate connection.
+    assert_eq!(
+        *targets.lock().unwrap(),
+        vec![
+            format!("localhost:{}", bitcoind_port),
+            format!("localhost:{}", bitcoind_port),
+            peer,
+        ]
+    );
+}
+
+#[cfg(feature = "electrum")]
+#[tokio::test]
+async fn electrum_relay_via_socks5_proxy() {
+    let targets = Arc::new(Mutex::new(Vec::new()));
+    let listener = AsyncTcpListener::bind("127.0.0.1:0").await.unwrap();
+    let proxy = ProxyConfig {
+        host: "127.0.0.1".to_owned(),
+        port: listener.local_addr().unwrap().port(),
+        username: Some("user".to_owned()),
+        password: Some("pass".to_owned()),
+    };
+    tokio::spawn(run_fake_socks_proxy(listener, targets.clone()));
+
+    let address = AnchoringTestKit::default()
+        .actual_anchoring_config()
+        .anchoring_address();
+    let transaction = create_fake_funding_transaction(&address, 10_000);
+    let listener = AsyncTcpListener::bind("127.0.0.1:0").await.unwrap();
+    let electrum_port = listener.local_addr().unwrap().port();
+    let state = FakeElectrumState {
+        transaction: Some(transaction.clone()),
+        ..FakeElectrumState::default()
+    };
+    tokio::spawn(run_fake_electrum_server(
+        listener,
+        Arc::new(Mutex::new(state)),
+    ));
+    let relay = ElectrumRelay::new(ElectrumRelayConfig {
+        server: format!("localhost:{}", electrum_port),
+        request_timeout: Some(10_000),
+        proxy: Some(proxy),
+        ..ElectrumRelayConfig::default()
+    });
+    assert_eq!(
+        relay.raw_transaction(transaction.id()).await.unwrap(),
+        Some(transaction)
+    );
+    assert_eq!(
+        *targets.lock().unwrap(),
+        vec![format!("localhost:{}", electrum_port)]
+    );
+}
+
+#[cfg(feature = "pkcs11")]
+#[test]
+fn pkcs11_signer_open_errors() {
+    let bitcoin_key = btc::gen_keypair(bitcoin::Network::Testnet).0;
+    let config = Pkcs11Config {
+        module_path: "/nonexistent/pkcs11.so".into(),
+        slot: 0,
+        pin_env: "EXONUM_TEST_PKCS11_PIN".to_owned(),
+        keys: vec![Pkcs11KeyConfig {
+            bitcoin_key,
+            key_id: "0064".to_owned(),
+        }],
+    };
+
+    let e = Pkcs11Signer::open(&config).unwrap_err();
+    assert!(e.to_string().contains("EXONUM_TEST_PKCS11_PIN"));
+
+    std::env::set_var("EXONUM_TEST_PKCS11_PIN", "0001password");
+    let e = Pkcs11Signer::open(&config).unwrap_err();
+    assert!(e.to_string().contains("Unable to load PKCS#11 module"));
 }
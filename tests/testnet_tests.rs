@@ -12,23 +12,41 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
+use bitcoin::blockdata::script::Builder;
 use exonum::helpers::Height;
 use exonum::{
+    merkledb::{
+        access::{AccessExt, Prefixed},
+        Database, ObjectHash, TemporaryDB,
+    },
     messages::{AnyTx, Verified},
-    runtime::{ErrorMatch, SnapshotExt},
+    runtime::{
+        migrations::{InitMigrationError, MigrateData},
+        versioning::Version,
+        ErrorMatch, SnapshotExt,
+    },
 };
 use exonum_btc_anchoring::{
-    blockchain::{errors::Error, BtcAnchoringInterface, SignInput},
+    blockchain::{
+        backup::AnchoringBackup,
+        data_layout::{AnchoringIntervalChange, TxInputId},
+        errors::Error,
+        verification::{verify_anchoring_chain, ChainVerificationError},
+        AccelerateAnchoring, BtcAnchoringInterface, RecoverAnchoring, ReportBitcoinHeaders,
+        ReportInclusionProof, Schema, SignInput, SignInputs, VoteFeeRate, FINALITY_DEPTH,
+    },
     btc::{self, BuilderError},
     config::Config,
     test_helpers::{
-        create_fake_funding_transaction, get_anchoring_schema, AnchoringTestKit,
-        ANCHORING_INSTANCE_ID,
+        create_fake_bitcoin_headers, create_fake_funding_transaction, create_fake_merkle_block,
+        get_anchoring_schema, AnchoringTestKit, ANCHORING_INSTANCE_ID, ANCHORING_INSTANCE_NAME,
     },
+    BtcAnchoringService,
 };
 use exonum_crypto::KeyPair;
 use exonum_explorer::CommittedTransaction;
 use exonum_supervisor::ConfigPropose;
+use exonum_testkit::migrations::MigrationTest;
 
 fn assert_tx_error(tx: &CommittedTransaction, e: ErrorMatch) {
     assert_eq!(
@@ -121,6 +139,11 @@ where
     let schema = get_anchoring_schema(&snapshot);
     assert!(schema.following_config().is_none());
     assert_eq!(schema.actual_config(), new_cfg);
+    // Transition transaction is signed by the keys of the previous configuration.
+    assert_eq!(
+        verify_anchoring_chain(snapshot.for_service(ANCHORING_INSTANCE_NAME).unwrap()),
+        Ok(2)
+    );
 
     assert_eq!(
         anchoring_tx_proposal.id(),
@@ -170,6 +193,11 @@ fn simple() {
     let tx0 = anchoring_testkit.last_anchoring_tx().unwrap();
     let tx0_meta = tx0.anchoring_metadata().unwrap();
     assert!(tx0_meta.1.block_height == Height(0));
+    // The first anchoring transaction commits the initial configuration.
+    assert_eq!(
+        tx0_meta.1.config_digest,
+        Some(anchoring_testkit.actual_anchoring_config().object_hash())
+    );
 
     anchoring_testkit
         .inner
@@ -204,6 +232,176 @@ fn simple() {
     // script_pubkey should be the same
     assert!(tx0_meta.0 == tx1_meta.0);
     assert!(tx1_meta.1.block_height == Height(anchoring_interval));
+    assert_eq!(tx1_meta.1.config_digest, None);
+}
+
+#[test]
+fn verify_chain() {
+    let mut anchoring_testkit = AnchoringTestKit::default();
+    let anchoring_interval = anchoring_testkit
+        .actual_anchoring_config()
+        .anchoring_interval;
+    for height in &[0, anchoring_interval] {
+        anchoring_testkit.inner.create_blocks_until(Height(*height));
+        anchoring_testkit.inner.create_block_with_transactions(
+            anchoring_testkit
+                .create_signature_txs()
+                .into_iter()
+                .flatten(),
+        );
+    }
+
+    let snapshot = anchoring_testkit.inner.snapshot();
+    assert_eq!(
+        verify_anchoring_chain(snapshot.for_service(ANCHORING_INSTANCE_NAME).unwrap()),
+        Ok(2)
+    );
+
+    // Import the anchoring state into the replica and corrupt it there.
+    let fork = TemporaryDB::new().fork();
+    let verify = |fork| verify_anchoring_chain(Prefixed::new(ANCHORING_INSTANCE_NAME, fork));
+    let schema = |fork| Schema::new(Prefixed::new(ANCHORING_INSTANCE_NAME, fork));
+    AnchoringBackup::export(&get_anchoring_schema(&snapshot))
+        .import(&mut schema(&fork))
+        .unwrap();
+    assert_eq!(verify(&fork), Ok(2));
+    // Spenders of the funding transactions are recovered from the imported chain.
+    let first_tx = schema(&fork).transactions_chain.get(0).unwrap();
+    let funding_txid = btc::Sha256d::from(first_tx.0.input[0].previous_output.txid);
+    assert_eq!(
+        schema(&fork).funding_transaction_spender(&funding_txid),
+        Some(0)
+    );
+
+    // Corrupt the signatures of the latest anchoring transaction.
+    let tx0 = schema(&fork).transactions_chain.get(0).unwrap();
+    let mut tx1 = schema(&fork).transactions_chain.get(1).unwrap();
+    tx1.0.input[0].witness.swap(1, 2);
+    schema(&fork).transactions_chain.set(1, tx1.clone());
+    assert_eq!(
+        verify(&fork),
+        Err(ChainVerificationError::InvalidSignatures { index: 1, input: 0 })
+    );
+
+    // Break the order of the anchoring transactions.
+    schema(&fork).transactions_chain.set(0, tx1);
+    schema(&fork).transactions_chain.set(1, tx0);
+    assert_eq!(
+        verify(&fork),
+        Err(ChainVerificationError::UnknownInput { index: 0, input: 0 })
+    );
+}
+
+#[test]
+fn data_migrations() {
+    let service = BtcAnchoringService::default();
+    // Data of the LECT-based anchoring service cannot be migrated.
+    let err = service
+        .migration_scripts(&Version::new(0, 13, 0))
+        .unwrap_err();
+    assert!(matches!(err, InitMigrationError::OldStartVersion { .. }));
+
+    let scripts = service.migration_scripts(&Version::new(1, 0, 0)).unwrap();
+    assert_eq!(scripts.len(), 1);
+}
+
+#[test]
+fn data_migration_from_1_0() {
+    let mut anchoring_testkit = AnchoringTestKit::default();
+    let anchoring_interval = anchoring_testkit
+        .actual_anchoring_config()
+        .anchoring_interval;
+    for height in &[0, anchoring_interval] {
+        anchoring_testkit.inner.create_blocks_until(Height(*height));
+        anchoring_testkit.inner.create_block_with_transactions(
+            anchoring_testkit
+                .create_signature_txs()
+                .into_iter()
+                .flatten(),
+        );
+    }
+    let snapshot = anchoring_testkit.inner.snapshot();
+    let schema = get_anchoring_schema(&snapshot);
+
+    // Only the indices of the `1.0` releases are filled before the migration.
+    let mut migration_test =
+        MigrationTest::new(BtcAnchoringService::default(), Version::new(1, 0, 0));
+    migration_test.setup(|access| {
+        let service_data = snapshot.for_service(ANCHORING_INSTANCE_NAME).unwrap();
+        access
+            .get_proof_list("transactions_chain")
+            .extend(schema.transactions_chain.iter());
+        let mut spent_funding_transactions = access.get_proof_map("spent_funding_transactions");
+        for (txid, tx) in service_data
+            .get_proof_map::<_, btc::Sha256d, btc::Transaction>("spent_funding_transactions")
+            .iter()
+        {
+            spent_funding_transactions.put(&txid, tx);
+        }
+        access
+            .get_entry("actual_config")
+            .set(schema.actual_config());
+    });
+    migration_test.migrate();
+
+    let migrated_schema = Schema::new(migration_test.end_snapshot());
+    assert_eq!(migrated_schema.transactions_chain.len(), 2);
+    for (index, tx) in schema.transactions_chain.iter().enumerate() {
+        assert_eq!(
+            migrated_schema.transaction_index(&tx.id()),
+            Some(index as u64)
+        );
+        assert_eq!(
+            migrated_schema.transaction_index_by_wtxid(&tx.wtxid()),
+            Some(index as u64)
+        );
+        let anchored_height = tx.anchoring_payload().unwrap().block_height;
+        assert_eq!(
+            migrated_schema.anchoring_transaction_index(anchored_height),
+            schema.anchoring_transaction_index(anchored_height)
+        );
+    }
+    let funding_txid = schema.transactions_chain.get(0).unwrap().0.input[0]
+        .previous_output
+        .txid
+        .into();
+    assert_eq!(
+        migrated_schema.funding_transaction_spender(&funding_txid),
+        Some(0)
+    );
+    assert_eq!(migrated_schema.anchoring_stats(), schema.anchoring_stats());
+    assert_eq!(
+        migrated_schema.anchoring_schedule(),
+        schema.anchoring_schedule()
+    );
+    assert_eq!(migrated_schema.config_history(), schema.config_history());
+}
+
+#[test]
+#[should_panic(expected = "Unable to find the inputs of the anchoring transaction")]
+fn data_migration_from_1_0_err_unknown_inputs() {
+    let mut anchoring_testkit = AnchoringTestKit::default();
+    anchoring_testkit.inner.create_block_with_transactions(
+        anchoring_testkit
+            .create_signature_txs()
+            .into_iter()
+            .flatten(),
+    );
+    let snapshot = anchoring_testkit.inner.snapshot();
+    let schema = get_anchoring_schema(&snapshot);
+
+    // The spent funding transaction is missing.
+    let mut migration_test =
+        MigrationTest::new(BtcAnchoringService::default(), Version::new(1, 0, 0));
+    migration_test.setup(|access| {
+        access
+            .get_proof_list("transactions_chain")
+            .extend(schema.transactions_chain.iter());
+        access
+            .get_entry("actual_config")
+            .set(schema.actual_config());
+    });
+    migration_test.migrate();
 }
 
 #[test]
@@ -333,7 +531,7 @@ fn insufficient_funds() {
             proposal,
             Err(BuilderError::InsufficientFunds {
                 balance: 20,
-                total_fee: 1530
+                total_fee: 1860
             })
         );
     }
@@ -424,6 +622,7 @@ fn unexpected_anchoring_proposal() {
 fn add_anchoring_node() {
     test_anchoring_config_change(|anchoring_testkit, cfg| {
         cfg.anchoring_keys.push(anchoring_testkit.add_node());
+        cfg.sort_anchoring_keys();
     });
 }
 
@@ -438,7 +637,45 @@ fn remove_anchoring_node() {
 fn change_anchoring_node_without_funds() {
     test_anchoring_config_change(|anchoring_testkit, cfg| {
         cfg.anchoring_keys[0].bitcoin_key = anchoring_testkit.gen_bitcoin_key();
+        cfg.sort_anchoring_keys();
+    });
+}
+
+#[test]
+fn switch_to_p2sh_wrapped_address() {
+    let mut anchoring_testkit = test_anchoring_config_change(|_, cfg| {
+        cfg.p2sh_wrapped = true;
     });
+    let config = anchoring_testkit.actual_anchoring_config();
+    assert!(config.anchoring_out_script().is_p2sh());
+
+    // The proposal spends the P2SH-wrapped output, thus its inputs contain the witness program.
+    let (proposal, inputs) = anchoring_testkit.anchoring_transaction_proposal().unwrap();
+    let witness_program = config.redeem_script().as_ref().to_v0_p2wsh();
+    assert_eq!(
+        proposal.0.input[0].script_sig,
+        Builder::new()
+            .push_slice(witness_program.as_bytes())
+            .into_script()
+    );
+    // PSBT refers to the same transaction.
+    let psbt = btc::Psbt::from_proposal(&proposal, &inputs, &config.redeem_script()).unwrap();
+    assert_eq!(psbt.txid(), proposal.id());
+
+    anchoring_testkit.inner.create_block_with_transactions(
+        anchoring_testkit
+            .create_signature_txs()
+            .into_iter()
+            .flatten(),
+    );
+    // Signing doesn't change the identifier of the anchoring transaction.
+    let last_tx = anchoring_testkit.last_anchoring_tx().unwrap();
+    assert_eq!(last_tx.id(), proposal.id());
+    assert_eq!(
+        last_tx.0.input[0].script_sig,
+        proposal.0.input[0].script_sig
+    );
+    assert!(!last_tx.0.input[0].witness.is_empty());
 }
 
 #[test]
@@ -470,6 +707,7 @@ fn add_anchoring_node_insufficient_funds() {
     // Add an anchoring node.
     let mut new_cfg = anchoring_testkit.actual_anchoring_config();
     new_cfg.anchoring_keys.push(anchoring_testkit.add_node());
+    new_cfg.sort_anchoring_keys();
 
     // Commit configuration with without last anchoring node.
     anchoring_testkit.inner.create_block_with_transaction(
@@ -491,7 +729,7 @@ fn add_anchoring_node_insufficient_funds() {
             proposal,
             Err(BuilderError::InsufficientFunds {
                 total_fee: 1530,
-                balance: 470
+                balance: 140
             })
         );
     }
@@ -510,116 +748,1446 @@ fn add_anchoring_node_insufficient_funds() {
 }
 
 #[test]
-fn funding_tx_err_unsuitable() {
+fn change_anchoring_interval() {
     let mut anchoring_testkit = AnchoringTestKit::default();
-
-    // Create weird funding transaction.
-    let mut config = anchoring_testkit.actual_anchoring_config();
-    config.anchoring_keys.swap(3, 1);
-    let funding_tx = create_fake_funding_transaction(&config.anchoring_address(), 10_000);
-
-    // Check that it did not pass.
-    let block = anchoring_testkit.inner.create_block_with_transactions(
-        anchoring_testkit.create_funding_confirmation_txs_with(funding_tx),
-    );
-    assert_tx_error(
-        &block[0],
-        ErrorMatch::from_fail(&Error::UnsuitableFundingTx),
+    let anchoring_interval = anchoring_testkit
+        .actual_anchoring_config()
+        .anchoring_interval;
+    anchoring_testkit.inner.create_block_with_transactions(
+        anchoring_testkit
+            .create_signature_txs()
+            .into_iter()
+            .flatten(),
     );
-}
 
-#[test]
-fn funding_tx_override() {
-    // Actually, we can override the funding transaction by another one.
-    let anchoring_interval = 5;
-    let mut anchoring_testkit = AnchoringTestKit::new(4, anchoring_interval);
-
-    // Add an initial funding transaction to enable anchoring.
-    let (txs, first_funding_transaction) = anchoring_testkit.create_funding_confirmation_txs(2000);
-    anchoring_testkit.inner.create_block_with_transactions(txs);
+    // Change the anchoring interval in the middle of the anchoring period.
+    let new_interval = 3;
+    let mut new_cfg = anchoring_testkit.actual_anchoring_config();
+    new_cfg.anchoring_interval = new_interval;
+    anchoring_testkit.inner.create_block_with_transaction(
+        anchoring_testkit.create_config_change_tx(
+            ConfigPropose::new(0, anchoring_testkit.inner.height().next())
+                .service_config(ANCHORING_INSTANCE_ID, new_cfg),
+        ),
+    );
+    anchoring_testkit.inner.create_block();
     assert_eq!(
-        unspent_funding_transaction(&anchoring_testkit).unwrap(),
-        first_funding_transaction
+        anchoring_testkit
+            .actual_anchoring_config()
+            .anchoring_interval,
+        new_interval
     );
 
-    // Override the funding transaction by the second one.
-    let (txs, second_funding_transaction) = anchoring_testkit.create_funding_confirmation_txs(2400);
-    anchoring_testkit.inner.create_block_with_transactions(txs);
+    // The new interval takes effect from the following anchoring height of the old schedule.
+    let schedule = get_anchoring_schema(&anchoring_testkit.inner.snapshot()).anchoring_schedule();
     assert_eq!(
-        unspent_funding_transaction(&anchoring_testkit).unwrap(),
-        second_funding_transaction
+        schedule.changes(),
+        &[
+            AnchoringIntervalChange {
+                effective_from: 0,
+                interval: anchoring_interval,
+            },
+            AnchoringIntervalChange {
+                effective_from: anchoring_interval,
+                interval: new_interval,
+            },
+        ]
     );
+
+    // Anchored heights at the switch point and after it follow the new schedule.
+    let expected_heights = [anchoring_interval, anchoring_interval + new_interval];
+    for &expected_height in &expected_heights {
+        anchoring_testkit
+            .inner
+            .create_blocks_until(Height(expected_height));
+        assert_eq!(
+            anchoring_testkit
+                .anchoring_transaction_proposal()
+                .unwrap()
+                .0
+                .anchoring_payload()
+                .unwrap()
+                .block_height,
+            Height(expected_height)
+        );
+        anchoring_testkit.inner.create_block_with_transactions(
+            anchoring_testkit
+                .create_signature_txs()
+                .into_iter()
+                .flatten(),
+        );
+    }
 }
 
 #[test]
-fn sign_input_err_unauthorized() {
-    let mut testkit = AnchoringTestKit::default();
-    // Create sign_input transaction from the anchoring node.
-    let tx = testkit
-        .create_signature_tx_for_node(&testkit.inner.us())
-        .unwrap()[0]
-        .clone();
-    // Re-sign this transaction by the other keypair.
-    let malformed_tx = change_tx_signature(tx, &KeyPair::random());
-    // Commit this transaction and check status.
-    let block = testkit.inner.create_block_with_transaction(malformed_tx);
-    assert_tx_error(
-        &block[0],
-        ErrorMatch::from_fail(&Error::UnauthorizedAnchoringKey),
+fn change_anchoring_interval_with_lag() {
+    let mut anchoring_testkit = AnchoringTestKit::default();
+    let anchoring_interval = anchoring_testkit
+        .actual_anchoring_config()
+        .anchoring_interval;
+    anchoring_testkit.inner.create_block_with_transactions(
+        anchoring_testkit
+            .create_signature_txs()
+            .into_iter()
+            .flatten(),
     );
-}
 
-#[test]
-fn add_funds_err_unauthorized() {
-    let mut testkit = AnchoringTestKit::default();
-    // Create add_funds transaction from the anchoring node.
-    let tx = testkit.create_funding_confirmation_txs(100).0[0].clone();
-    // Re-sign this transaction by the other keypair.
-    let malformed_tx = change_tx_signature(tx, &KeyPair::random());
-    // Commit this transaction and check status.
-    let block = testkit.inner.create_block_with_transaction(malformed_tx);
-    assert_tx_error(
-        &block[0],
-        ErrorMatch::from_fail(&Error::UnauthorizedAnchoringKey),
+    // Skip the anchoring height, so the anchoring falls behind the blockchain.
+    anchoring_testkit
+        .inner
+        .create_blocks_until(Height(anchoring_interval + 1));
+    let new_interval = 3;
+    let mut new_cfg = anchoring_testkit.actual_anchoring_config();
+    new_cfg.anchoring_interval = new_interval;
+    anchoring_testkit.inner.create_block_with_transaction(
+        anchoring_testkit.create_config_change_tx(
+            ConfigPropose::new(0, anchoring_testkit.inner.height().next())
+                .service_config(ANCHORING_INSTANCE_ID, new_cfg),
+        ),
     );
-}
+    anchoring_testkit.inner.create_block();
 
-#[test]
-fn sing_input_err_no_such_input() {
-    let mut testkit = AnchoringTestKit::default();
-    let us = testkit.inner.us();
-    // Create sign_input transaction for the anchoring node.
-    let tx = testkit.create_signature_tx_for_node(&us).unwrap()[0]
-        .payload()
-        .parse::<SignInput>()
-        .unwrap();
-    // Change input ID.
-    let malformed_tx = us
-        .service_keypair()
-        .sign_input(ANCHORING_INSTANCE_ID, SignInput { input: 10, ..tx });
-    // Commit this transaction and check status.
-    let block = testkit.inner.create_block_with_transaction(malformed_tx);
-    assert_tx_error(&block[0], ErrorMatch::from_fail(&Error::NoSuchInput));
+    // The skipped height is anchored according to the old schedule, and each of
+    // the following heights is anchored exactly once.
+    let expected_heights = [
+        anchoring_interval,
+        anchoring_interval * 2,
+        anchoring_interval * 2 + new_interval,
+    ];
+    for &expected_height in &expected_heights {
+        anchoring_testkit
+            .inner
+            .create_blocks_until(Height(expected_height));
+        assert_eq!(
+            get_anchoring_schema(&anchoring_testkit.inner.snapshot()).following_anchoring_height(),
+            Height(expected_height)
+        );
+        assert_eq!(
+            anchoring_testkit
+                .anchoring_transaction_proposal()
+                .unwrap()
+                .0
+                .anchoring_payload()
+                .unwrap()
+                .block_height,
+            Height(expected_height)
+        );
+        anchoring_testkit.inner.create_block_with_transactions(
+            anchoring_testkit
+                .create_signature_txs()
+                .into_iter()
+                .flatten(),
+        );
+    }
 }
 
 #[test]
-fn sign_input_err_input_verification_failed() {
-    let mut testkit = AnchoringTestKit::default();
-    let (first_node, second_node) = {
-        let validators = testkit.inner.network().validators();
-        (validators[0].clone(), validators[1].clone())
-    };
-    // Create sign_input transaction for the first anchoring node.
-    let tx = testkit.create_signature_tx_for_node(&first_node).unwrap()[0].clone();
-    // Re-sign this transaction by the second anchoring node.
-    let malformed_tx = change_tx_signature(tx, &second_node.service_keypair());
-    // Commit this transaction and check status.
-    let block = testkit.inner.create_block_with_transaction(malformed_tx);
-    assert_tx_error(
-        &block[0],
-        ErrorMatch::from_fail(&Error::InputVerificationFailed)
-            .with_description_containing("secp: signature failed verification"),
+fn emergency_freeze() {
+    let mut anchoring_testkit = AnchoringTestKit::default();
+    let anchoring_interval = anchoring_testkit
+        .actual_anchoring_config()
+        .anchoring_interval;
+    anchoring_testkit.inner.create_block_with_transactions(
+        anchoring_testkit
+            .create_signature_txs()
+            .into_iter()
+            .flatten(),
     );
-}
+    anchoring_testkit
+        .inner
+        .create_blocks_until(Height(anchoring_interval));
+    let signatures = anchoring_testkit.create_signature_txs().swap_remove(0);
+
+    // Freeze the anchoring.
+    let mut new_cfg = anchoring_testkit.actual_anchoring_config();
+    new_cfg.frozen = true;
+    anchoring_testkit.inner.create_block_with_transaction(
+        anchoring_testkit.create_config_change_tx(
+            ConfigPropose::new(0, anchoring_testkit.inner.height().next())
+                .service_config(ANCHORING_INSTANCE_ID, new_cfg.clone()),
+        ),
+    );
+    anchoring_testkit.inner.create_block();
+    assert!(get_anchoring_schema(&anchoring_testkit.inner.snapshot()).is_frozen());
+    assert_eq!(anchoring_testkit.anchoring_transaction_proposal(), None);
 
-// TODO Implement tests for anchoring recovery [ECR-3581]
+    // Signatures for the proposal created before freeze are rejected.
+    let block = anchoring_testkit
+        .inner
+        .create_block_with_transactions(signatures);
+    assert_tx_error(&block[0], ErrorMatch::from_fail(&Error::AnchoringFrozen));
+    assert_eq!(
+        anchoring_testkit
+            .last_anchoring_tx()
+            .unwrap()
+            .anchoring_payload()
+            .unwrap()
+            .block_height,
+        Height(0)
+    );
+
+    // Unfreeze the anchoring.
+    new_cfg.frozen = false;
+    anchoring_testkit.inner.create_block_with_transaction(
+        anchoring_testkit.create_config_change_tx(
+            ConfigPropose::new(1, anchoring_testkit.inner.height().next())
+                .service_config(ANCHORING_INSTANCE_ID, new_cfg),
+        ),
+    );
+    anchoring_testkit.inner.create_block();
+    // Signatures of the first node have been already committed.
+    anchoring_testkit.inner.create_block_with_transactions(
+        anchoring_testkit
+            .create_signature_txs()
+            .into_iter()
+            .skip(1)
+            .flatten(),
+    );
+    assert_eq!(
+        anchoring_testkit
+            .last_anchoring_tx()
+            .unwrap()
+            .anchoring_payload()
+            .unwrap()
+            .block_height,
+        Height(anchoring_interval)
+    );
+}
+
+#[test]
+fn compact_payload() {
+    let mut anchoring_testkit = AnchoringTestKit::default();
+    let anchoring_interval = anchoring_testkit
+        .actual_anchoring_config()
+        .anchoring_interval;
+    anchoring_testkit.inner.create_block_with_transactions(
+        anchoring_testkit
+            .create_signature_txs()
+            .into_iter()
+            .flatten(),
+    );
+    let regular_tx = anchoring_testkit.last_anchoring_tx().unwrap();
+
+    // Enable the compact payload encoding.
+    let mut new_cfg = anchoring_testkit.actual_anchoring_config();
+    new_cfg.compact_payload = true;
+    anchoring_testkit.inner.create_block_with_transaction(
+        anchoring_testkit.create_config_change_tx(
+            ConfigPropose::new(0, anchoring_testkit.inner.height().next())
+                .service_config(ANCHORING_INSTANCE_ID, new_cfg),
+        ),
+    );
+    anchoring_testkit
+        .inner
+        .create_blocks_until(Height(anchoring_interval));
+    anchoring_testkit.inner.create_block_with_transactions(
+        anchoring_testkit
+            .create_signature_txs()
+            .into_iter()
+            .flatten(),
+    );
+
+    // Compact payload is shorter, but contains the same data.
+    let compact_tx = anchoring_testkit.last_anchoring_tx().unwrap();
+    assert!(
+        compact_tx.0.output[1].script_pubkey.len() < regular_tx.0.output[1].script_pubkey.len()
+    );
+    let payload = compact_tx.anchoring_payload().unwrap();
+    assert_eq!(payload.block_height, Height(anchoring_interval));
+    assert_eq!(payload.prev_tx_chain, None);
+    assert_eq!(compact_tx.prev_tx_id(), regular_tx.id());
+}
+
+#[test]
+fn extensible_payload() {
+    let mut anchoring_testkit = AnchoringTestKit::default();
+    let anchoring_interval = anchoring_testkit
+        .actual_anchoring_config()
+        .anchoring_interval;
+    anchoring_testkit.inner.create_block_with_transactions(
+        anchoring_testkit
+            .create_signature_txs()
+            .into_iter()
+            .flatten(),
+    );
+    let regular_tx = anchoring_testkit.last_anchoring_tx().unwrap();
+
+    // Enable the extensible payload encoding.
+    let mut new_cfg = anchoring_testkit.actual_anchoring_config();
+    new_cfg.extensible_payload = true;
+    anchoring_testkit.inner.create_block_with_transaction(
+        anchoring_testkit.create_config_change_tx(
+            ConfigPropose::new(0, anchoring_testkit.inner.height().next())
+                .service_config(ANCHORING_INSTANCE_ID, new_cfg),
+        ),
+    );
+    anchoring_testkit
+        .inner
+        .create_blocks_until(Height(anchoring_interval));
+    anchoring_testkit.inner.create_block_with_transactions(
+        anchoring_testkit
+            .create_signature_txs()
+            .into_iter()
+            .flatten(),
+    );
+
+    let extensible_tx = anchoring_testkit.last_anchoring_tx().unwrap();
+    let payload_script = &extensible_tx.0.output[1].script_pubkey;
+    // Version byte follows the `OP_RETURN`, the push opcode and the prefix.
+    assert_eq!(payload_script.as_bytes()[8], 4);
+    let payload = extensible_tx
+        .anchoring_payload_with(&btc::StrictPayloadCodec)
+        .unwrap();
+    assert_eq!(payload.block_height, Height(anchoring_interval));
+    assert_eq!(payload.prev_tx_chain, None);
+    assert_eq!(extensible_tx.prev_tx_id(), regular_tx.id());
+}
+
+#[test]
+fn validators_only_anchoring_keys() {
+    let mut anchoring_testkit = AnchoringTestKit::default();
+
+    // All anchoring nodes are validators, so the config is accepted.
+    let mut new_cfg = anchoring_testkit.actual_anchoring_config();
+    new_cfg.validators_only = true;
+    let block = anchoring_testkit.inner.create_block_with_transaction(
+        anchoring_testkit.create_config_change_tx(
+            ConfigPropose::new(0, anchoring_testkit.inner.height().next())
+                .service_config(ANCHORING_INSTANCE_ID, new_cfg.clone()),
+        ),
+    );
+    block[0].status().unwrap();
+    anchoring_testkit.inner.create_block();
+    assert!(anchoring_testkit.actual_anchoring_config().validators_only);
+
+    // Anchoring key of the auditor node is rejected.
+    new_cfg.anchoring_keys.push(anchoring_testkit.add_node());
+    new_cfg.sort_anchoring_keys();
+    let block = anchoring_testkit.inner.create_block_with_transaction(
+        anchoring_testkit.create_config_change_tx(
+            ConfigPropose::new(1, anchoring_testkit.inner.height().next())
+                .service_config(ANCHORING_INSTANCE_ID, new_cfg.clone()),
+        ),
+    );
+    assert_tx_error(
+        &block[0],
+        ErrorMatch::from_fail(&Error::NonValidatorAnchoringKey).with_any_description(),
+    );
+
+    // The same config is accepted without the restriction.
+    new_cfg.validators_only = false;
+    let block = anchoring_testkit.inner.create_block_with_transaction(
+        anchoring_testkit.create_config_change_tx(
+            ConfigPropose::new(1, anchoring_testkit.inner.height().next())
+                .service_config(ANCHORING_INSTANCE_ID, new_cfg),
+        ),
+    );
+    block[0].status().unwrap();
+}
+
+#[test]
+fn unsorted_anchoring_keys() {
+    let mut anchoring_testkit = AnchoringTestKit::default();
+
+    // Reordering of the keys changes the anchoring address, so the keys must be sorted.
+    let mut new_cfg = anchoring_testkit.actual_anchoring_config();
+    new_cfg.anchoring_keys.reverse();
+    let block = anchoring_testkit.inner.create_block_with_transaction(
+        anchoring_testkit.create_config_change_tx(
+            ConfigPropose::new(0, anchoring_testkit.inner.height().next())
+                .service_config(ANCHORING_INSTANCE_ID, new_cfg.clone()),
+        ),
+    );
+    assert_tx_error(
+        &block[0],
+        ErrorMatch::from_fail(&Error::UnsortedAnchoringKeys).with_any_description(),
+    );
+
+    new_cfg.sort_anchoring_keys();
+    new_cfg.anchoring_keys.pop();
+    let block = anchoring_testkit.inner.create_block_with_transaction(
+        anchoring_testkit.create_config_change_tx(
+            ConfigPropose::new(0, anchoring_testkit.inner.height().next())
+                .service_config(ANCHORING_INSTANCE_ID, new_cfg),
+        ),
+    );
+    block[0].status().unwrap();
+}
+
+#[test]
+fn excluded_anchoring_key() {
+    let mut anchoring_testkit = AnchoringTestKit::default();
+    // Establish anchoring transactions chain.
+    anchoring_testkit.inner.create_block_with_transactions(
+        anchoring_testkit
+            .create_signature_txs()
+            .into_iter()
+            .flatten(),
+    );
+
+    let old_cfg = anchoring_testkit.actual_anchoring_config();
+    let compromised_keys = old_cfg.anchoring_keys[..2]
+        .iter()
+        .map(|keys| keys.bitcoin_key)
+        .collect::<Vec<_>>();
+    let mut new_cfg = old_cfg.clone();
+    for keys in &mut new_cfg.anchoring_keys[..2] {
+        keys.bitcoin_key = anchoring_testkit.gen_bitcoin_key();
+    }
+    new_cfg.sort_anchoring_keys();
+
+    // Two remaining nodes cannot sign the transition transaction.
+    let mut cfg = new_cfg.clone();
+    cfg.excluded_keys = compromised_keys.clone();
+    let block = anchoring_testkit.inner.create_block_with_transaction(
+        anchoring_testkit.create_config_change_tx(
+            ConfigPropose::new(0, anchoring_testkit.inner.height().next())
+                .service_config(ANCHORING_INSTANCE_ID, cfg),
+        ),
+    );
+    assert_tx_error(
+        &block[0],
+        ErrorMatch::from_fail(&Error::InsufficientAnchoringKeys).with_any_description(),
+    );
+
+    // Exclude the single compromised key.
+    new_cfg.excluded_keys = vec![compromised_keys[0]];
+    let block = anchoring_testkit.inner.create_block_with_transaction(
+        anchoring_testkit.create_config_change_tx(
+            ConfigPropose::new(0, anchoring_testkit.inner.height().next())
+                .service_config(ANCHORING_INSTANCE_ID, new_cfg.clone()),
+        ),
+    );
+    block[0].status().unwrap();
+    anchoring_testkit.inner.create_block();
+
+    // Signatures of the excluded key are rejected.
+    let compromised_node = anchoring_testkit
+        .find_anchoring_node(&compromised_keys[0])
+        .unwrap();
+    let signatures = anchoring_testkit
+        .create_signature_tx_for_node(compromised_node)
+        .unwrap();
+    let block = anchoring_testkit
+        .inner
+        .create_block_with_transactions(signatures);
+    assert_tx_error(
+        &block[0],
+        ErrorMatch::from_fail(&Error::ExcludedAnchoringKey),
+    );
+
+    // Other nodes finalize the transition transaction.
+    let signatures = old_cfg
+        .anchoring_keys
+        .iter()
+        .skip(1)
+        .flat_map(|keys| {
+            let node = anchoring_testkit
+                .find_anchoring_node(&keys.bitcoin_key)
+                .unwrap();
+            anchoring_testkit
+                .create_signature_tx_for_node(node)
+                .unwrap()
+        })
+        .collect::<Vec<_>>();
+    anchoring_testkit
+        .inner
+        .create_block_with_transactions(signatures);
+    assert_eq!(anchoring_testkit.actual_anchoring_config(), new_cfg);
+    assert_eq!(
+        anchoring_testkit
+            .last_anchoring_tx()
+            .unwrap()
+            .anchoring_metadata()
+            .unwrap()
+            .0,
+        &new_cfg.anchoring_out_script()
+    );
+}
+
+#[test]
+fn proposal_expiration() {
+    let mut anchoring_testkit = AnchoringTestKit::default();
+    let proposal_lifetime = 5;
+    let input_signatures_len = |anchoring_testkit: &AnchoringTestKit, txid| {
+        get_anchoring_schema(&anchoring_testkit.inner.snapshot())
+            .input_signatures(&TxInputId::new(txid, 0))
+            .0
+            .len()
+    };
+
+    // Limit the proposal lifetime.
+    let mut new_cfg = anchoring_testkit.actual_anchoring_config();
+    new_cfg.proposal_lifetime = proposal_lifetime;
+    anchoring_testkit.inner.create_block_with_transaction(
+        anchoring_testkit.create_config_change_tx(
+            ConfigPropose::new(0, anchoring_testkit.inner.height().next())
+                .service_config(ANCHORING_INSTANCE_ID, new_cfg),
+        ),
+    );
+    anchoring_testkit.inner.create_block();
+    let expires_at = get_anchoring_schema(&anchoring_testkit.inner.snapshot())
+        .proposal_expiration_height()
+        .unwrap();
+    anchoring_testkit.inner.create_blocks_until(expires_at);
+
+    // Proposal has been recreated after the expiration.
+    let proposal = get_anchoring_schema(&anchoring_testkit.inner.snapshot())
+        .actual_proposal()
+        .unwrap();
+    assert_eq!(proposal.created_at, expires_at.0);
+    assert_eq!(
+        proposal.txid,
+        anchoring_testkit
+            .anchoring_transaction_proposal()
+            .unwrap()
+            .0
+            .id()
+    );
+
+    // Signatures are discarded after the proposal expiration.
+    anchoring_testkit
+        .inner
+        .create_block_with_transactions(anchoring_testkit.create_signature_txs().swap_remove(0));
+    anchoring_testkit
+        .inner
+        .create_blocks_until(Height(proposal.created_at + proposal_lifetime - 1));
+    assert_eq!(input_signatures_len(&anchoring_testkit, proposal.txid), 1);
+    anchoring_testkit.inner.create_block();
+    assert_eq!(input_signatures_len(&anchoring_testkit, proposal.txid), 0);
+    let renewed_proposal = get_anchoring_schema(&anchoring_testkit.inner.snapshot())
+        .actual_proposal()
+        .unwrap();
+    assert_eq!(renewed_proposal.txid, proposal.txid);
+    assert_eq!(
+        renewed_proposal.created_at,
+        proposal.created_at + proposal_lifetime
+    );
+
+    // Signatures of the first node have been already committed.
+    anchoring_testkit.inner.create_block_with_transactions(
+        anchoring_testkit
+            .create_signature_txs()
+            .into_iter()
+            .skip(1)
+            .flatten(),
+    );
+    assert_eq!(
+        anchoring_testkit.last_anchoring_tx().unwrap().id(),
+        proposal.txid
+    );
+    // Signatures of the finalized proposal are kept.
+    assert_eq!(input_signatures_len(&anchoring_testkit, proposal.txid), 3);
+
+    // Proposal is recreated after the new funds arrival.
+    let proposal = get_anchoring_schema(&anchoring_testkit.inner.snapshot())
+        .actual_proposal()
+        .unwrap();
+    anchoring_testkit
+        .inner
+        .create_block_with_transactions(anchoring_testkit.create_signature_txs().swap_remove(0));
+    assert_eq!(input_signatures_len(&anchoring_testkit, proposal.txid), 1);
+    anchoring_testkit
+        .inner
+        .create_block_with_transactions(anchoring_testkit.create_funding_confirmation_txs(2000).0);
+    let new_proposal = get_anchoring_schema(&anchoring_testkit.inner.snapshot())
+        .actual_proposal()
+        .unwrap();
+    assert_ne!(new_proposal.txid, proposal.txid);
+    assert_eq!(new_proposal.inputs, 2);
+    assert_eq!(input_signatures_len(&anchoring_testkit, proposal.txid), 0);
+}
+
+#[test]
+fn funding_tx_err_unsuitable() {
+    let mut anchoring_testkit = AnchoringTestKit::default();
+
+    // Create weird funding transaction.
+    let mut config = anchoring_testkit.actual_anchoring_config();
+    config.anchoring_keys.swap(3, 1);
+    let funding_tx = create_fake_funding_transaction(&config.anchoring_address(), 10_000);
+
+    // Check that it did not pass.
+    let block = anchoring_testkit.inner.create_block_with_transactions(
+        anchoring_testkit.create_funding_confirmation_txs_with(funding_tx),
+    );
+    assert_tx_error(
+        &block[0],
+        ErrorMatch::from_fail(&Error::UnsuitableFundingTx),
+    );
+}
+
+#[test]
+fn funding_tx_override() {
+    // Actually, we can override the funding transaction by another one.
+    let anchoring_interval = 5;
+    let mut anchoring_testkit = AnchoringTestKit::new(4, anchoring_interval);
+
+    // Add an initial funding transaction to enable anchoring.
+    let (txs, first_funding_transaction) = anchoring_testkit.create_funding_confirmation_txs(2000);
+    anchoring_testkit.inner.create_block_with_transactions(txs);
+    assert_eq!(
+        unspent_funding_transaction(&anchoring_testkit).unwrap(),
+        first_funding_transaction
+    );
+
+    // Override the funding transaction by the second one.
+    let (txs, second_funding_transaction) = anchoring_testkit.create_funding_confirmation_txs(2400);
+    anchoring_testkit.inner.create_block_with_transactions(txs);
+    assert_eq!(
+        unspent_funding_transaction(&anchoring_testkit).unwrap(),
+        second_funding_transaction
+    );
+}
+
+#[test]
+fn sign_input_err_unauthorized() {
+    let mut testkit = AnchoringTestKit::default();
+    // Create sign_input transaction from the anchoring node.
+    let tx = testkit
+        .create_signature_tx_for_node(&testkit.inner.us())
+        .unwrap()[0]
+        .clone();
+    // Re-sign this transaction by the other keypair.
+    let malformed_tx = change_tx_signature(tx, &KeyPair::random());
+    // Commit this transaction and check status.
+    let block = testkit.inner.create_block_with_transaction(malformed_tx);
+    assert_tx_error(
+        &block[0],
+        ErrorMatch::from_fail(&Error::UnauthorizedAnchoringKey),
+    );
+}
+
+#[test]
+fn add_funds_err_unauthorized() {
+    let mut testkit = AnchoringTestKit::default();
+    // Create add_funds transaction from the anchoring node.
+    let tx = testkit.create_funding_confirmation_txs(100).0[0].clone();
+    // Re-sign this transaction by the other keypair.
+    let malformed_tx = change_tx_signature(tx, &KeyPair::random());
+    // Commit this transaction and check status.
+    let block = testkit.inner.create_block_with_transaction(malformed_tx);
+    assert_tx_error(
+        &block[0],
+        ErrorMatch::from_fail(&Error::UnauthorizedAnchoringKey),
+    );
+}
+
+#[test]
+fn sing_input_err_no_such_input() {
+    let mut testkit = AnchoringTestKit::default();
+    let us = testkit.inner.us();
+    // Create sign_input transaction for the anchoring node.
+    let tx = testkit.create_signature_tx_for_node(&us).unwrap()[0]
+        .payload()
+        .parse::<SignInput>()
+        .unwrap();
+    // Change input ID.
+    let malformed_tx = us
+        .service_keypair()
+        .sign_input(ANCHORING_INSTANCE_ID, SignInput { input: 10, ..tx });
+    // Commit this transaction and check status.
+    let block = testkit.inner.create_block_with_transaction(malformed_tx);
+    assert_tx_error(&block[0], ErrorMatch::from_fail(&Error::NoSuchInput));
+}
+
+#[test]
+fn sign_input_resubmitted() {
+    let mut testkit = AnchoringTestKit::default();
+    let anchoring_interval = testkit.actual_anchoring_config().anchoring_interval;
+    let us = testkit.inner.us();
+    // Make sure that the signature of our node is stored before the quorum is reached.
+    let our_signatures = testkit.create_signature_tx_for_node(&us).unwrap();
+    let our_hashes = our_signatures
+        .iter()
+        .map(ObjectHash::object_hash)
+        .collect::<Vec<_>>();
+    let other_signatures = testkit
+        .create_signature_txs()
+        .into_iter()
+        .flatten()
+        .filter(|tx| !our_hashes.contains(&tx.object_hash()))
+        .collect::<Vec<_>>();
+    let sign_inputs = our_signatures
+        .iter()
+        .map(|tx| tx.payload().parse::<SignInput>().unwrap())
+        .collect::<Vec<_>>();
+    testkit
+        .inner
+        .create_block_with_transactions(our_signatures.into_iter().chain(other_signatures));
+    let anchoring_tx = testkit.last_anchoring_tx().unwrap();
+    assert_eq!(anchoring_tx.id(), sign_inputs[0].txid);
+
+    // Already stored signatures are ignored even if there is a new proposal.
+    testkit
+        .inner
+        .create_blocks_until(Height(anchoring_interval));
+    assert!(testkit.anchoring_transaction_proposal().is_some());
+    let tx = us.service_keypair().sign_inputs(
+        ANCHORING_INSTANCE_ID,
+        SignInputs {
+            inputs: sign_inputs,
+        },
+    );
+    let block = testkit.inner.create_block_with_transaction(tx);
+    block[0].status().unwrap();
+}
+
+#[test]
+fn sign_input_err_input_verification_failed() {
+    let mut testkit = AnchoringTestKit::default();
+    let (first_node, second_node) = {
+        let validators = testkit.inner.network().validators();
+        (validators[0].clone(), validators[1].clone())
+    };
+    // Create sign_input transaction for the first anchoring node.
+    let tx = testkit.create_signature_tx_for_node(&first_node).unwrap()[0].clone();
+    // Re-sign this transaction by the second anchoring node.
+    let malformed_tx = change_tx_signature(tx, &second_node.service_keypair());
+    // Commit this transaction and check status.
+    let block = testkit.inner.create_block_with_transaction(malformed_tx);
+    assert_tx_error(
+        &block[0],
+        ErrorMatch::from_fail(&Error::InputVerificationFailed)
+            .with_description_containing("secp: signature failed verification"),
+    );
+}
+
+#[test]
+fn report_bitcoin_headers() {
+    let mut testkit = AnchoringTestKit::with_network(4, 5, bitcoin::Network::Regtest);
+    let headers = create_fake_bitcoin_headers(btc::Sha256d::new([0; 32]), 5);
+
+    // Each anchoring node reports the same headers.
+    let report = ReportBitcoinHeaders {
+        first_height: 100,
+        headers: headers[0..3].to_vec(),
+    };
+    let txs = testkit
+        .inner
+        .network()
+        .validators()
+        .iter()
+        .map(|node| {
+            node.service_keypair()
+                .report_bitcoin_headers(ANCHORING_INSTANCE_ID, report.clone())
+        })
+        .collect::<Vec<_>>();
+    testkit
+        .inner
+        .create_block_with_transactions(txs)
+        .transactions
+        .iter()
+        .try_for_each(|tx| tx.status())
+        .expect("Each transaction should be successful.");
+
+    // Report headers overlapping with the stored ones.
+    let report = ReportBitcoinHeaders {
+        first_height: 101,
+        headers: headers[1..].to_vec(),
+    };
+    let tx = testkit
+        .inner
+        .us()
+        .service_keypair()
+        .report_bitcoin_headers(ANCHORING_INSTANCE_ID, report);
+    testkit.inner.create_block_with_transaction(tx)[0]
+        .status()
+        .unwrap();
+
+    let snapshot = testkit.inner.snapshot();
+    let schema = get_anchoring_schema(&snapshot);
+    assert_eq!(schema.bitcoin_chain_tip(), Some((104, headers[4])));
+    assert_eq!(schema.bitcoin_header(100), Some(headers[0]));
+    assert_eq!(
+        schema.bitcoin_header_height(&headers[2].block_hash()),
+        Some(102)
+    );
+}
+
+#[test]
+fn report_bitcoin_headers_checkpoint() {
+    let mut testkit = AnchoringTestKit::with_network(4, 5, bitcoin::Network::Regtest);
+    let headers = create_fake_bitcoin_headers(btc::Sha256d::new([0; 32]), 3);
+    let other_headers = create_fake_bitcoin_headers(btc::Sha256d::new([1; 32]), 1);
+    let validators = testkit.inner.network().validators().to_vec();
+
+    // A single anchoring node cannot start the chain.
+    let txs = vec![
+        validators[0].service_keypair().report_bitcoin_headers(
+            ANCHORING_INSTANCE_ID,
+            ReportBitcoinHeaders {
+                first_height: 100,
+                headers: headers.clone(),
+            },
+        ),
+        validators[1].service_keypair().report_bitcoin_headers(
+            ANCHORING_INSTANCE_ID,
+            ReportBitcoinHeaders {
+                first_height: 100,
+                headers: other_headers.clone(),
+            },
+        ),
+        validators[2].service_keypair().report_bitcoin_headers(
+            ANCHORING_INSTANCE_ID,
+            ReportBitcoinHeaders {
+                first_height: 101,
+                headers: headers[0..1].to_vec(),
+            },
+        ),
+    ];
+    testkit
+        .inner
+        .create_block_with_transactions(txs)
+        .transactions
+        .iter()
+        .try_for_each(|tx| tx.status())
+        .expect("Each transaction should be successful.");
+    assert_eq!(
+        get_anchoring_schema(&testkit.inner.snapshot()).bitcoin_chain_tip(),
+        None
+    );
+
+    // The chain starts as soon as the byzantine majority agrees on the checkpoint.
+    let txs = validators[1..3]
+        .iter()
+        .map(|node| {
+            node.service_keypair().report_bitcoin_headers(
+                ANCHORING_INSTANCE_ID,
+                ReportBitcoinHeaders {
+                    first_height: 100,
+                    headers: headers[0..2].to_vec(),
+                },
+            )
+        })
+        .collect::<Vec<_>>();
+    testkit.inner.create_block_with_transactions(txs);
+    let snapshot = testkit.inner.snapshot();
+    let schema = get_anchoring_schema(&snapshot);
+    assert_eq!(schema.bitcoin_chain_tip(), Some((101, headers[1])));
+    assert_eq!(schema.bitcoin_header(100), Some(headers[0]));
+    // Votes for the checkpoint are discarded after the chain has been started.
+    assert_eq!(
+        schema.bitcoin_checkpoint(&testkit.actual_anchoring_config()),
+        None
+    );
+}
+
+#[test]
+fn report_bitcoin_headers_err_invalid() {
+    let mut testkit = AnchoringTestKit::with_network(4, 5, bitcoin::Network::Regtest);
+    let headers = create_fake_bitcoin_headers(btc::Sha256d::new([0; 32]), 3);
+    let keypair = testkit.inner.us().service_keypair();
+
+    let tx = keypair.report_bitcoin_headers(
+        ANCHORING_INSTANCE_ID,
+        ReportBitcoinHeaders {
+            first_height: 0,
+            headers: headers[0..1].to_vec(),
+        },
+    );
+    // Re-sign this transaction by the other keypair.
+    let malformed_tx = change_tx_signature(tx, &KeyPair::random());
+    let block = testkit.inner.create_block_with_transaction(malformed_tx);
+    assert_tx_error(
+        &block[0],
+        ErrorMatch::from_fail(&Error::UnauthorizedAnchoringKey),
+    );
+
+    testkit.inner.create_block_with_transactions(
+        testkit.create_report_bitcoin_headers_txs(0, headers[0..1].to_vec()),
+    );
+    // Skip the header with height 1.
+    let tx = keypair.report_bitcoin_headers(
+        ANCHORING_INSTANCE_ID,
+        ReportBitcoinHeaders {
+            first_height: 1,
+            headers: headers[2..].to_vec(),
+        },
+    );
+    let block = testkit.inner.create_block_with_transaction(tx);
+    assert_tx_error(
+        &block[0],
+        ErrorMatch::from_fail(&Error::InvalidBitcoinHeaders).with_any_description(),
+    );
+}
+
+#[test]
+fn report_bitcoin_headers_err_invalid_pow() {
+    let mut testkit = AnchoringTestKit::with_network(4, 5, bitcoin::Network::Regtest);
+    let mut headers = create_fake_bitcoin_headers(btc::Sha256d::new([0; 32]), 2);
+    let keypair = testkit.inner.us().service_keypair();
+
+    // Break the proof of work of the last header.
+    while headers[1].0.validate_pow(&headers[1].0.target()).is_ok() {
+        headers[1].0.nonce += 1;
+    }
+    let tx = keypair.report_bitcoin_headers(
+        ANCHORING_INSTANCE_ID,
+        ReportBitcoinHeaders {
+            first_height: 0,
+            headers,
+        },
+    );
+    let block = testkit.inner.create_block_with_transaction(tx);
+    assert_tx_error(
+        &block[0],
+        ErrorMatch::from_fail(&Error::InvalidBitcoinHeaders)
+            .with_description_containing("invalid proof of work"),
+    );
+    // The chain is left unchanged.
+    assert_eq!(
+        get_anchoring_schema(&testkit.inner.snapshot()).bitcoin_chain_tip(),
+        None
+    );
+
+    // Headers with the regtest difficulty are unsuitable for the testnet.
+    let mut testkit = AnchoringTestKit::default();
+    let headers = create_fake_bitcoin_headers(btc::Sha256d::new([0; 32]), 1);
+    let tx = testkit.inner.us().service_keypair().report_bitcoin_headers(
+        ANCHORING_INSTANCE_ID,
+        ReportBitcoinHeaders {
+            first_height: 0,
+            headers,
+        },
+    );
+    let block = testkit.inner.create_block_with_transaction(tx);
+    assert_tx_error(
+        &block[0],
+        ErrorMatch::from_fail(&Error::InvalidBitcoinHeaders)
+            .with_description_containing("unexpected target"),
+    );
+}
+
+#[test]
+fn report_inclusion_proof_reorg() {
+    let mut testkit = AnchoringTestKit::with_network(4, 5, bitcoin::Network::Regtest);
+    testkit
+        .inner
+        .create_block_with_transactions(testkit.create_funding_confirmation_txs(700_000).0);
+    testkit
+        .inner
+        .create_block_with_transactions(testkit.create_signature_txs().into_iter().flatten());
+    let tx = testkit.last_anchoring_tx().unwrap();
+    let keypairs = testkit
+        .inner
+        .network()
+        .validators()
+        .iter()
+        .map(|node| node.service_keypair())
+        .collect::<Vec<_>>();
+    let report_inclusion_txs = |keypairs: &[KeyPair], proof: &btc::MerkleBlock| {
+        keypairs
+            .iter()
+            .map(|keypair| {
+                keypair.report_inclusion_proof(
+                    ANCHORING_INSTANCE_ID,
+                    ReportInclusionProof {
+                        proof: proof.clone(),
+                    },
+                )
+            })
+            .collect::<Vec<_>>()
+    };
+
+    // The byzantine majority reports the inclusion in the block of the best chain.
+    let checkpoint = create_fake_bitcoin_headers(btc::Sha256d::new([0; 32]), 1)[0];
+    let merkle_block = create_fake_merkle_block(checkpoint.block_hash(), &[tx.id()]);
+    testkit.inner.create_block_with_transactions(
+        testkit.create_report_bitcoin_headers_txs(0, vec![checkpoint, merkle_block.header()]),
+    );
+    testkit
+        .inner
+        .create_block_with_transactions(report_inclusion_txs(&keypairs[0..3], &merkle_block));
+    assert_eq!(
+        get_anchoring_schema(&testkit.inner.snapshot()).inclusion_proof(&tx.id()),
+        Some(merkle_block)
+    );
+
+    // The competing branch with the other block including the transaction becomes
+    // the best chain.
+    let other_merkle_block = create_fake_merkle_block(checkpoint.block_hash(), &[tx.id()]);
+    let mut headers = vec![other_merkle_block.header()];
+    headers.extend(create_fake_bitcoin_headers(
+        other_merkle_block.header().block_hash(),
+        FINALITY_DEPTH as usize,
+    ));
+    let report = ReportBitcoinHeaders {
+        first_height: 1,
+        headers,
+    };
+    testkit.inner.create_block_with_transaction(
+        keypairs[0].report_bitcoin_headers(ANCHORING_INSTANCE_ID, report),
+    )[0]
+    .status()
+    .unwrap();
+    let snapshot = testkit.inner.snapshot();
+    let schema = get_anchoring_schema(&snapshot);
+    assert_eq!(schema.inclusion_proof(&tx.id()), None);
+    assert_eq!(schema.transaction_finality_height(&tx.id()), None);
+
+    // The inclusion in the block of the new best chain becomes final as soon as it is
+    // reported by the byzantine majority.
+    testkit
+        .inner
+        .create_block_with_transactions(report_inclusion_txs(&keypairs[0..2], &other_merkle_block));
+    let snapshot = testkit.inner.snapshot();
+    let schema = get_anchoring_schema(&snapshot);
+    assert_eq!(
+        schema.inclusion_proof(&tx.id()),
+        Some(other_merkle_block.clone())
+    );
+    assert_eq!(schema.transaction_finality_height(&tx.id()), None);
+
+    testkit
+        .inner
+        .create_block_with_transactions(report_inclusion_txs(&keypairs[2..3], &other_merkle_block));
+    let snapshot = testkit.inner.snapshot();
+    assert!(get_anchoring_schema(&snapshot)
+        .transaction_finality_height(&tx.id())
+        .is_some());
+}
+
+#[test]
+fn report_inclusion_proof_err_invalid() {
+    let mut testkit = AnchoringTestKit::with_network(4, 5, bitcoin::Network::Regtest);
+    testkit
+        .inner
+        .create_block_with_transactions(testkit.create_funding_confirmation_txs(700_000).0);
+    testkit
+        .inner
+        .create_block_with_transactions(testkit.create_signature_txs().into_iter().flatten());
+    let tx = testkit.last_anchoring_tx().unwrap();
+    let keypair = testkit.inner.us().service_keypair();
+
+    // The Bitcoin block is unknown.
+    let merkle_block = create_fake_merkle_block(btc::Sha256d::new([0; 32]), &[tx.id()]);
+    let report = ReportInclusionProof {
+        proof: merkle_block.clone(),
+    };
+    let block = testkit.inner.create_block_with_transaction(
+        keypair.report_inclusion_proof(ANCHORING_INSTANCE_ID, report),
+    );
+    assert_tx_error(
+        &block[0],
+        ErrorMatch::from_fail(&Error::InvalidInclusionProof).with_any_description(),
+    );
+
+    // The Bitcoin block doesn't contain anchoring or funding transactions.
+    let other_merkle_block = create_fake_merkle_block(
+        merkle_block.header().block_hash(),
+        &[btc::Sha256d::new([1; 32])],
+    );
+    testkit
+        .inner
+        .create_block_with_transactions(testkit.create_report_bitcoin_headers_txs(
+            0,
+            vec![merkle_block.header(), other_merkle_block.header()],
+        ));
+    let report = ReportInclusionProof {
+        proof: other_merkle_block.clone(),
+    };
+    let block = testkit.inner.create_block_with_transaction(
+        keypair.report_inclusion_proof(ANCHORING_INSTANCE_ID, report),
+    );
+    assert_tx_error(
+        &block[0],
+        ErrorMatch::from_fail(&Error::InvalidInclusionProof).with_any_description(),
+    );
+
+    // The transaction author is not an anchoring node.
+    let report = ReportInclusionProof {
+        proof: merkle_block,
+    };
+    let block = testkit.inner.create_block_with_transaction(
+        KeyPair::random().report_inclusion_proof(ANCHORING_INSTANCE_ID, report),
+    );
+    assert_tx_error(
+        &block[0],
+        ErrorMatch::from_fail(&Error::UnauthorizedAnchoringKey),
+    );
+}
+
+#[test]
+fn vote_fee_rate() {
+    let mut testkit = AnchoringTestKit::default();
+    let anchoring_interval = testkit.actual_anchoring_config().anchoring_interval;
+    testkit
+        .inner
+        .create_blocks_until(Height(anchoring_interval));
+    let config = testkit.actual_anchoring_config();
+    let keypairs = testkit
+        .anchoring_keypairs()
+        .into_iter()
+        .map(|(bitcoin_key, _)| {
+            testkit
+                .find_anchoring_node(&bitcoin_key)
+                .unwrap()
+                .service_keypair()
+        })
+        .collect::<Vec<_>>();
+
+    // Votes of the minority don't change the fee rate.
+    let block =
+        testkit
+            .inner
+            .create_block_with_transactions(keypairs[..2].iter().zip(&[20, 40]).map(
+                |(keypair, &fee_rate)| {
+                    keypair.vote_fee_rate(ANCHORING_INSTANCE_ID, VoteFeeRate { fee_rate })
+                },
+            ));
+    for tx in block.iter() {
+        tx.status().unwrap();
+    }
+    let snapshot = testkit.inner.snapshot();
+    assert_eq!(
+        get_anchoring_schema(&snapshot).fee_rate(&config),
+        config.transaction_fee
+    );
+
+    // The median of the votes is used as soon as the majority has voted.
+    testkit.inner.create_block_with_transaction(
+        keypairs[2].vote_fee_rate(ANCHORING_INSTANCE_ID, VoteFeeRate { fee_rate: 30 }),
+    )[0]
+    .status()
+    .unwrap();
+    let snapshot = testkit.inner.snapshot();
+    assert_eq!(get_anchoring_schema(&snapshot).fee_rate(&config), 30);
+
+    testkit
+        .inner
+        .create_block_with_transactions(testkit.create_signature_txs().into_iter().flatten());
+    let tx = testkit.last_anchoring_tx().unwrap();
+    let inputs = get_anchoring_schema(&testkit.inner.snapshot())
+        .spent_transactions(&tx)
+        .unwrap();
+    // The fee is calculated for the transaction without the witness data.
+    let mut unsigned_tx = tx.clone();
+    for input in &mut unsigned_tx.0.input {
+        input.witness.clear();
+    }
+    assert_eq!(tx.fee(&inputs).unwrap(), 30 * unsigned_tx.size());
+}
+
+#[test]
+fn vote_fee_rate_bounded() {
+    let mut testkit = AnchoringTestKit::default();
+    // Limit the fee rate of the anchoring transactions.
+    let mut config = testkit.actual_anchoring_config();
+    config.min_fee_rate = 8;
+    config.max_fee_rate = 25;
+    testkit.inner.create_block_with_transaction(
+        testkit.create_config_change_tx(
+            ConfigPropose::new(0, testkit.inner.height().next())
+                .service_config(ANCHORING_INSTANCE_ID, config.clone()),
+        ),
+    );
+    testkit.inner.create_block();
+    assert_eq!(testkit.actual_anchoring_config(), config);
+
+    let keypairs = testkit
+        .anchoring_keypairs()
+        .into_iter()
+        .map(|(bitcoin_key, _)| {
+            testkit
+                .find_anchoring_node(&bitcoin_key)
+                .unwrap()
+                .service_keypair()
+        })
+        .collect::<Vec<_>>();
+    let mut vote = |fee_rates: &[u64]| {
+        let block =
+            testkit
+                .inner
+                .create_block_with_transactions(keypairs.iter().zip(fee_rates).map(
+                    |(keypair, &fee_rate)| {
+                        keypair.vote_fee_rate(ANCHORING_INSTANCE_ID, VoteFeeRate { fee_rate })
+                    },
+                ));
+        for tx in block.iter() {
+            tx.status().unwrap();
+        }
+        get_anchoring_schema(&testkit.inner.snapshot()).fee_rate(&config)
+    };
+
+    // The median of the votes is bounded by the configuration.
+    assert_eq!(vote(&[20, 40, 30]), 25);
+    assert_eq!(vote(&[4, 5, 6]), 8);
+    assert_eq!(vote(&[10, 20, 15]), 15);
+}
+
+#[test]
+fn vote_fee_rate_err() {
+    let mut testkit = AnchoringTestKit::default();
+    let keypair = testkit.inner.us().service_keypair();
+
+    // The fee rate is too low.
+    let block = testkit.inner.create_block_with_transaction(
+        keypair.vote_fee_rate(ANCHORING_INSTANCE_ID, VoteFeeRate { fee_rate: 1 }),
+    );
+    assert_tx_error(
+        &block[0],
+        ErrorMatch::from_fail(&Error::UnsuitableFeeRate).with_any_description(),
+    );
+
+    // The transaction author is not an anchoring node.
+    let block = testkit.inner.create_block_with_transaction(
+        KeyPair::random().vote_fee_rate(ANCHORING_INSTANCE_ID, VoteFeeRate { fee_rate: 20 }),
+    );
+    assert_tx_error(
+        &block[0],
+        ErrorMatch::from_fail(&Error::UnauthorizedAnchoringKey),
+    );
+}
+
+#[test]
+fn accelerate_anchoring() {
+    let mut testkit = AnchoringTestKit::default();
+    let config = testkit.actual_anchoring_config();
+    // Establish anchoring transactions chain.
+    testkit
+        .inner
+        .create_block_with_transactions(testkit.create_signature_txs().into_iter().flatten());
+    let parent_tx = testkit.last_anchoring_tx().unwrap();
+    let parent_fee = {
+        let snapshot = testkit.inner.snapshot();
+        let inputs = get_anchoring_schema(&snapshot)
+            .spent_transactions(&parent_tx)
+            .unwrap();
+        parent_tx.fee(&inputs).unwrap()
+    };
+    // The following anchoring height is not reached yet.
+    assert!(testkit.anchoring_transaction_proposal().is_none());
+
+    let keypairs = testkit
+        .anchoring_keypairs()
+        .into_iter()
+        .map(|(bitcoin_key, _)| {
+            testkit
+                .find_anchoring_node(&bitcoin_key)
+                .unwrap()
+                .service_keypair()
+        })
+        .collect::<Vec<_>>();
+    let block =
+        testkit
+            .inner
+            .create_block_with_transactions(keypairs.iter().zip(&[20, 40, 30]).map(
+                |(keypair, &fee_rate)| {
+                    keypair.accelerate_anchoring(
+                        ANCHORING_INSTANCE_ID,
+                        AccelerateAnchoring {
+                            txid: parent_tx.id(),
+                            fee_rate,
+                        },
+                    )
+                },
+            ));
+    for tx in block.iter() {
+        tx.status().unwrap();
+    }
+    let snapshot = testkit.inner.snapshot();
+    assert_eq!(
+        get_anchoring_schema(&snapshot).acceleration_fee_rate(&config),
+        Some(30)
+    );
+
+    // The child transaction anchors the same height again.
+    let (proposal, inputs) = testkit.anchoring_transaction_proposal().unwrap();
+    assert_eq!(inputs[0], parent_tx);
+    assert_eq!(
+        proposal.anchoring_payload().unwrap().block_height,
+        Height(0)
+    );
+    // Both transactions pay the voted fee rate.
+    assert_eq!(
+        proposal.fee(&inputs).unwrap(),
+        30 * (proposal.size() + parent_tx.size()) - parent_fee
+    );
+
+    testkit
+        .inner
+        .create_block_with_transactions(testkit.create_signature_txs().into_iter().flatten());
+    assert_eq!(testkit.last_anchoring_tx().unwrap().id(), proposal.id());
+    // Acceleration votes are discarded along with the accelerated transaction.
+    let snapshot = testkit.inner.snapshot();
+    assert_eq!(
+        get_anchoring_schema(&snapshot).acceleration_fee_rate(&config),
+        None
+    );
+    assert!(testkit.anchoring_transaction_proposal().is_none());
+}
+
+#[test]
+fn accelerate_anchoring_err() {
+    let mut testkit = AnchoringTestKit::default();
+    let keypair = testkit.inner.us().service_keypair();
+
+    // There is no anchoring transaction to accelerate.
+    let config = testkit.actual_anchoring_config();
+    let txid = create_fake_funding_transaction(&config.anchoring_address(), 10_000).id();
+    let block = testkit
+        .inner
+        .create_block_with_transaction(keypair.accelerate_anchoring(
+            ANCHORING_INSTANCE_ID,
+            AccelerateAnchoring { txid, fee_rate: 20 },
+        ));
+    assert_tx_error(
+        &block[0],
+        ErrorMatch::from_fail(&Error::UnsuitableAcceleratedTx),
+    );
+
+    testkit
+        .inner
+        .create_block_with_transactions(testkit.create_signature_txs().into_iter().flatten());
+    let txid = testkit.last_anchoring_tx().unwrap().id();
+
+    // The fee rate is too low.
+    let block = testkit
+        .inner
+        .create_block_with_transaction(keypair.accelerate_anchoring(
+            ANCHORING_INSTANCE_ID,
+            AccelerateAnchoring { txid, fee_rate: 1 },
+        ));
+    assert_tx_error(
+        &block[0],
+        ErrorMatch::from_fail(&Error::UnsuitableFeeRate).with_any_description(),
+    );
+
+    // The transaction author is not an anchoring node.
+    let block =
+        testkit
+            .inner
+            .create_block_with_transaction(KeyPair::random().accelerate_anchoring(
+                ANCHORING_INSTANCE_ID,
+                AccelerateAnchoring { txid, fee_rate: 20 },
+            ));
+    assert_tx_error(
+        &block[0],
+        ErrorMatch::from_fail(&Error::UnauthorizedAnchoringKey),
+    );
+}
+
+#[test]
+fn recover_anchoring() {
+    let mut testkit = AnchoringTestKit::default();
+    let config = testkit.actual_anchoring_config();
+    // Establish anchoring transactions chain.
+    testkit
+        .inner
+        .create_block_with_transactions(testkit.create_signature_txs().into_iter().flatten());
+    let broken_tx = testkit.last_anchoring_tx().unwrap();
+    testkit
+        .inner
+        .create_blocks_until(Height(config.anchoring_interval));
+
+    let keypairs = testkit
+        .anchoring_keypairs()
+        .into_iter()
+        .map(|(bitcoin_key, _)| {
+            testkit
+                .find_anchoring_node(&bitcoin_key)
+                .unwrap()
+                .service_keypair()
+        })
+        .collect::<Vec<_>>();
+    let recover_anchoring = |keypair: &KeyPair| {
+        keypair.recover_anchoring(
+            ANCHORING_INSTANCE_ID,
+            RecoverAnchoring {
+                txid: broken_tx.id(),
+            },
+        )
+    };
+    // A single vote is not enough to abandon the latest anchoring transaction.
+    let block = testkit
+        .inner
+        .create_block_with_transaction(recover_anchoring(&keypairs[0]));
+    block[0].status().unwrap();
+    let snapshot = testkit.inner.snapshot();
+    assert_eq!(
+        get_anchoring_schema(&snapshot).recovered_transaction(&config),
+        None
+    );
+
+    let block = testkit
+        .inner
+        .create_block_with_transactions(keypairs[1..].iter().map(recover_anchoring));
+    for tx in block.iter() {
+        tx.status().unwrap();
+    }
+    let snapshot = testkit.inner.snapshot();
+    let schema = get_anchoring_schema(&snapshot);
+    assert_eq!(schema.recovered_transaction(&config), Some(broken_tx.id()));
+    // The new chain segment waits for the funding transaction.
+    assert_eq!(
+        schema
+            .actual_proposed_anchoring_transaction(snapshot.for_core())
+            .unwrap()
+            .unwrap_err(),
+        BuilderError::NoInputs
+    );
+
+    let (txs, funding_tx) = testkit.create_funding_confirmation_txs(150_000);
+    testkit.inner.create_block_with_transactions(txs);
+    // The recovery transaction spends only the funding transaction and refers
+    // to the broken chain in the payload.
+    let (proposal, inputs) = testkit.anchoring_transaction_proposal().unwrap();
+    assert_eq!(inputs, vec![funding_tx]);
+    let payload = proposal.anchoring_payload().unwrap();
+    assert_eq!(payload.prev_tx_chain, Some(broken_tx.id()));
+    assert_eq!(payload.block_height, Height(config.anchoring_interval));
+
+    testkit
+        .inner
+        .create_block_with_transactions(testkit.create_signature_txs().into_iter().flatten());
+    assert_eq!(testkit.last_anchoring_tx().unwrap().id(), proposal.id());
+    // Recovery votes are discarded along with the abandoned transaction, so the chain
+    // continues from the recovery transaction.
+    let snapshot = testkit.inner.snapshot();
+    assert_eq!(
+        get_anchoring_schema(&snapshot).recovered_transaction(&config),
+        None
+    );
+    assert_eq!(
+        verify_anchoring_chain(snapshot.for_service(ANCHORING_INSTANCE_NAME).unwrap()),
+        Ok(2)
+    );
+    testkit
+        .inner
+        .create_blocks_until(Height(config.anchoring_interval * 2));
+    let (_, inputs) = testkit.anchoring_transaction_proposal().unwrap();
+    assert_eq!(inputs.len(), 1);
+    assert_eq!(inputs[0].id(), proposal.id());
+}
+
+#[test]
+fn recover_anchoring_err() {
+    let mut testkit = AnchoringTestKit::default();
+    let keypair = testkit.inner.us().service_keypair();
+
+    // There is no anchoring transaction to abandon.
+    let config = testkit.actual_anchoring_config();
+    let txid = create_fake_funding_transaction(&config.anchoring_address(), 10_000).id();
+    let block = testkit.inner.create_block_with_transaction(
+        keypair.recover_anchoring(ANCHORING_INSTANCE_ID, RecoverAnchoring { txid }),
+    );
+    assert_tx_error(
+        &block[0],
+        ErrorMatch::from_fail(&Error::UnsuitableRecoveredTx),
+    );
+
+    testkit
+        .inner
+        .create_block_with_transactions(testkit.create_signature_txs().into_iter().flatten());
+    let txid = testkit.last_anchoring_tx().unwrap().id();
+
+    // The transaction author is not an anchoring node.
+    let block = testkit.inner.create_block_with_transaction(
+        KeyPair::random().recover_anchoring(ANCHORING_INSTANCE_ID, RecoverAnchoring { txid }),
+    );
+    assert_tx_error(
+        &block[0],
+        ErrorMatch::from_fail(&Error::UnauthorizedAnchoringKey),
+    );
+}